@@ -66,6 +66,149 @@ function mutateIdl() {
         },
       });
     }
+    if (
+      instruction.name === "ReduceMultipleOrders" ||
+      instruction.name === "ReduceMultipleOrdersWithFreeFunds"
+    ) {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "ReduceMultipleOrdersParams",
+        },
+      });
+    }
+    if (
+      instruction.name === "ReduceOrderByQuote" ||
+      instruction.name === "ReduceOrderByQuoteWithFreeFunds"
+    ) {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "ReduceOrderByQuoteParams",
+        },
+      });
+    }
+    if (instruction.name === "DepositAndPlace") {
+      instruction.args.push({
+        name: "depositFundsParams",
+        type: {
+          defined: "DepositParams",
+        },
+      });
+      instruction.args.push({
+        name: "orderPacket",
+        type: {
+          defined: "OrderPacket",
+        },
+      });
+    }
+    if (
+      instruction.name === "CancelByClientOrderId" ||
+      instruction.name === "CancelByClientOrderIdWithFreeFunds"
+    ) {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CancelByClientOrderIdParams",
+        },
+      });
+    }
+    if (
+      instruction.name === "CancelAllOrdersBounded" ||
+      instruction.name === "CancelAllOrdersBoundedWithFreeFunds"
+    ) {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CancelAllOrdersBoundedParams",
+        },
+      });
+    }
+    if (
+      instruction.name === "CancelInPriceRange" ||
+      instruction.name === "CancelInPriceRangeWithFreeFunds"
+    ) {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CancelInPriceRangeParams",
+        },
+      });
+    }
+    if (instruction.name === "PruneExpiredOrders") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "PruneExpiredOrdersParams",
+        },
+      });
+    }
+    if (instruction.name === "SetSelfTradeBehaviorOverride") {
+      instruction.args.push({
+        name: "selfTradeBehavior",
+        type: {
+          option: {
+            defined: "SelfTradeBehavior",
+          },
+        },
+      });
+    }
+    if (instruction.name === "PlaceByFundsFraction") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "PlaceByFundsFractionParams",
+        },
+      });
+    }
+    if (instruction.name === "SwapRoute") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "SwapRouteParams",
+        },
+      });
+    }
+    if (instruction.name === "InternalTransfer") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "InternalTransferParams",
+        },
+      });
+    }
+    if (instruction.name === "PlaceStopOrder") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "PlaceStopOrderParams",
+        },
+      });
+    }
+    if (instruction.name === "CancelStopOrder") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CancelStopOrderParams",
+        },
+      });
+    }
+    if (instruction.name === "CancelAndSwap") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CancelAndSwapParams",
+        },
+      });
+    }
+    if (instruction.name === "AmendOrder") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "AmendOrderParams",
+        },
+      });
+    }
     if (
       instruction.name === "CancelMulitpleOrdersById" ||
       instruction.name === "CancelMulitpleOrdersByIdWithFreeFunds"
@@ -81,7 +224,8 @@ function mutateIdl() {
       instruction.name === "PlaceLimitOrder" ||
       instruction.name === "PlaceLimitOrderWithFreeFunds" ||
       instruction.name === "Swap" ||
-      instruction.name === "SwapWithFreeFunds"
+      instruction.name === "SwapWithFreeFunds" ||
+      instruction.name === "SwapWithNativeSol"
     ) {
       instruction.args.push({
         name: "orderPacket",
@@ -92,7 +236,8 @@ function mutateIdl() {
     }
     if (
       instruction.name === "PlaceMultiplePostOnlyOrders" ||
-      instruction.name === "PlaceMultiplePostOnlyOrdersWithFreeFunds"
+      instruction.name === "PlaceMultiplePostOnlyOrdersWithFreeFunds" ||
+      instruction.name === "ReplaceQuotes"
     ) {
       instruction.args.push({
         name: "multipleOrderPacket",
@@ -104,7 +249,8 @@ function mutateIdl() {
     if (
       instruction.name === "CancelUpTo" ||
       instruction.name === "CancelUpToWithFreeFunds" ||
-      instruction.name === "ForceCancelOrders"
+      instruction.name === "ForceCancelOrders" ||
+      instruction.name === "ForceCancelOrdersWithFreeFunds"
     ) {
       instruction.args.push({
         name: "params",
@@ -124,7 +270,10 @@ function mutateIdl() {
         },
       });
     }
-    if (instruction.name === "DepositFunds") {
+    if (
+      instruction.name === "DepositFunds" ||
+      instruction.name === "DepositFundsWithNativeSol"
+    ) {
       instruction.args.push({
         name: "depositFundsParams",
         type: {
@@ -132,7 +281,10 @@ function mutateIdl() {
         },
       });
     }
-    if (instruction.name === "WithdrawFunds") {
+    if (
+      instruction.name === "WithdrawFunds" ||
+      instruction.name === "WithdrawFundsToNativeSol"
+    ) {
       instruction.args.push({
         name: "withdrawFundsParams",
         type: {
@@ -164,12 +316,215 @@ function mutateIdl() {
         },
       });
     }
+    if (instruction.name === "InitializeMarketPda") {
+      instruction.args.push({
+        name: "initializeMarketPdaParams",
+        type: {
+          defined: "InitializeMarketPdaParams",
+        },
+      });
+    }
     if (instruction.name === "NameSuccessor") {
       instruction.args.push({
         name: "successor",
         type: "publicKey",
       });
     }
+    if (instruction.name === "ChangeMakerRebate") {
+      instruction.args.push({
+        name: "makerRebateBps",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ChangeMinBaseLotsPerOrder") {
+      instruction.args.push({
+        name: "minBaseLotsPerOrder",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "SetTradingWindow") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "SetTradingWindowParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeTickSize") {
+      instruction.args.push({
+        name: "tickSizeInQuoteLotsPerBaseUnit",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ResizeMarket") {
+      instruction.args.push({
+        name: "marketSizeParams",
+        type: {
+          defined: "MarketSizeParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeFeeTiers") {
+      instruction.args.push({
+        name: "feeTiersBps",
+        type: {
+          array: ["u64", 4],
+        },
+      });
+    }
+    if (instruction.name === "SetTraderFeeTier") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "SetTraderFeeTierParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangePriceAccumulator") {
+      instruction.args.push({
+        name: "priceAccumulatorEnabled",
+        type: "bool",
+      });
+    }
+    if (instruction.name === "ChangeTraderVolumeTracking") {
+      instruction.args.push({
+        name: "traderVolumeTrackingEnabled",
+        type: "bool",
+      });
+    }
+    if (instruction.name === "ChangeMaxMatchLimit") {
+      instruction.args.push({
+        name: "maxMatchLimit",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ChangeDisabledEventTypes") {
+      instruction.args.push({
+        name: "disabledEventTypes",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ChangeTakingDisabled") {
+      instruction.args.push({
+        name: "takingDisabled",
+        type: "bool",
+      });
+    }
+    if (instruction.name === "RepriceOrder") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "RepriceOrderParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeReferralFee") {
+      instruction.args.push({
+        name: "referralFeeBps",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "AssertMarketPrice") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "AssertMarketPriceParams",
+        },
+      });
+    }
+    if (instruction.name === "DepositAtoms") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "DepositAtomsParams",
+        },
+      });
+    }
+    if (
+      instruction.name === "RegisterReferrer" ||
+      instruction.name === "DeregisterReferrer"
+    ) {
+      instruction.args.push({
+        name: "referrer",
+        type: "publicKey",
+      });
+    }
+    if (instruction.name === "ChangeMinTakerFill") {
+      instruction.args.push({
+        name: "minTakerFillBaseLots",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "SetVolatilityBand") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "SetVolatilityBandParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeMaxOrdersPerSidePerTrader") {
+      instruction.args.push({
+        name: "maxOrdersPerSidePerTrader",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ChangeMarketSymbols") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "ChangeMarketSymbolsParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeMaxOrderLifetimeSlots") {
+      instruction.args.push({
+        name: "maxOrderLifetimeSlots",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "ChangeSeatInactivityThreshold") {
+      instruction.args.push({
+        name: "seatInactivityThresholdSlots",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "RequestSeatBatch") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "RequestSeatBatchParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeFeeRoundingMode") {
+      instruction.args.push({
+        name: "feeRoundingMode",
+        type: {
+          defined: "FeeRoundingMode",
+        },
+      });
+    }
+    if (instruction.name === "ChangeMinFee") {
+      instruction.args.push({
+        name: "minFeeQuoteLots",
+        type: "u64",
+      });
+    }
+    if (instruction.name === "CollectFeesSplit") {
+      instruction.args.push({
+        name: "params",
+        type: {
+          defined: "CollectFeesSplitParams",
+        },
+      });
+    }
+    if (instruction.name === "ChangeBookCheckpoints") {
+      instruction.args.push({
+        name: "bookCheckpointsEnabled",
+        type: "bool",
+      });
+    }
   }
   fs.writeFileSync(generatedIdlPath, JSON.stringify(idl, null, 2));
 }
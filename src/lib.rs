@@ -28,8 +28,8 @@ use ellipsis_macros::declare_id;
 use solana_program::{program::set_return_data, pubkey::Pubkey};
 
 use program::{
-    assert_with_msg, event_recorder::EventRecorder, PhoenixInstruction, PhoenixLogContext,
-    PhoenixMarketContext,
+    assert_with_msg, event_recorder::EventRecorder, market_data_tracker::MarketDataTracker,
+    PhoenixInstruction, PhoenixLogContext, PhoenixMarketContext,
 };
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
@@ -124,44 +124,83 @@ pub fn process_instruction(
         return Ok(());
     }
 
+    // This instruction withdraws from multiple markets, so it does not load a single market context
+    if let PhoenixInstruction::WithdrawFromMarkets = instruction {
+        phoenix_log!("PhoenixInstruction::WithdrawFromMarkets");
+        return withdraw::process_withdraw_from_markets(program_id, accounts);
+    }
+
+    // This instruction trades on two markets, so it does not load a single market context
+    if let PhoenixInstruction::SwapRoute = instruction {
+        phoenix_log!("PhoenixInstruction::SwapRoute");
+        let swap_route_summary = new_order::process_swap_route(program_id, accounts, data)?;
+        set_return_data(swap_route_summary.try_to_vec()?.as_ref());
+        return Ok(());
+    }
+
+    // This instruction only reads the market, so it does not record events or load a market context
+    if let PhoenixInstruction::AssertMarketPrice = instruction {
+        phoenix_log!("PhoenixInstruction::AssertMarketPrice");
+        return assert_market_price::process_assert_market_price(program_id, accounts, data);
+    }
+
     let (program_accounts, accounts) = accounts.split_at(4);
     let accounts_iter = &mut program_accounts.iter();
     let phoenix_log_context = PhoenixLogContext::load(accounts_iter)?;
-    let market_context = if instruction == PhoenixInstruction::InitializeMarket {
-        PhoenixMarketContext::load_init(accounts_iter)?
-    } else {
-        PhoenixMarketContext::load(accounts_iter)?
+    let market_context = match instruction {
+        PhoenixInstruction::InitializeMarket => PhoenixMarketContext::load_init(accounts_iter)?,
+        PhoenixInstruction::InitializeMarketPda => {
+            // The market account is a PDA, so it must be created by the program before it can be
+            // loaded. Large markets are allocated over several instructions, and the market is
+            // only initialized once the account has its full size.
+            if !initialize::allocate_market_pda_account(program_accounts, accounts, data)? {
+                return Ok(());
+            }
+            PhoenixMarketContext::load_init(accounts_iter)?
+        }
+        _ => PhoenixMarketContext::load(accounts_iter)?,
     };
 
     let mut event_recorder = EventRecorder::new(phoenix_log_context, &market_context, instruction)?;
+    let mut market_data_tracker = MarketDataTracker::new(&market_context)?;
 
-    let mut record_event_fn = |e: MarketEvent<Pubkey>| event_recorder.add_event(e);
+    let mut record_event_fn = |e: MarketEvent<Pubkey>| {
+        market_data_tracker.track_event(&e);
+        event_recorder.add_event(e)
+    };
     let mut order_ids = Vec::new();
+    let mut has_remaining_orders = None;
+    let mut orders_found = None;
+    let mut swap_summary = None;
 
     match instruction {
         PhoenixInstruction::InitializeMarket => {
             phoenix_log!("PhoenixInstruction::Initialize");
             initialize::process_initialize_market(program_id, &market_context, accounts, data)?
         }
+        PhoenixInstruction::InitializeMarketPda => {
+            phoenix_log!("PhoenixInstruction::InitializeMarketPda");
+            initialize::process_initialize_market_pda(program_id, &market_context, accounts, data)?
+        }
         PhoenixInstruction::Swap => {
             phoenix_log!("PhoenixInstruction::Swap");
-            new_order::process_swap(
+            swap_summary = Some(new_order::process_swap(
                 program_id,
                 &market_context,
                 accounts,
                 data,
                 &mut record_event_fn,
-            )?;
+            )?);
         }
         PhoenixInstruction::SwapWithFreeFunds => {
             phoenix_log!("PhoenixInstruction::SwapWithFreeFunds");
-            new_order::process_swap_with_free_funds(
+            swap_summary = Some(new_order::process_swap_with_free_funds(
                 program_id,
                 &market_context,
                 accounts,
                 data,
                 &mut record_event_fn,
-            )?;
+            )?);
         }
         PhoenixInstruction::PlaceLimitOrder => {
             phoenix_log!("PhoenixInstruction::PlaceLimitOrder");
@@ -207,6 +246,68 @@ pub fn process_instruction(
                 &mut order_ids,
             )?;
         }
+        PhoenixInstruction::ReplaceQuotes => {
+            phoenix_log!("PhoenixInstruction::ReplaceQuotes");
+            new_order::process_replace_quotes(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+                &mut order_ids,
+            )?;
+        }
+        PhoenixInstruction::PlaceByFundsFraction => {
+            phoenix_log!("PhoenixInstruction::PlaceByFundsFraction");
+            new_order::process_place_by_funds_fraction(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+                &mut order_ids,
+            )?;
+        }
+        PhoenixInstruction::SetSelfTradeBehaviorOverride => {
+            phoenix_log!("PhoenixInstruction::SetSelfTradeBehaviorOverride");
+            manage_seat::process_set_self_trade_behavior_override(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+            )?
+        }
+        PhoenixInstruction::InternalTransfer => {
+            phoenix_log!("PhoenixInstruction::InternalTransfer");
+            internal_transfer::process_internal_transfer(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+            )?
+        }
+        PhoenixInstruction::AmendOrder => {
+            phoenix_log!("PhoenixInstruction::AmendOrder");
+            amend_order::process_amend_order(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+                &mut order_ids,
+            )?
+        }
+        PhoenixInstruction::DepositAndPlace => {
+            phoenix_log!("PhoenixInstruction::DepositAndPlace");
+            new_order::process_deposit_and_place(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+                &mut order_ids,
+            )?
+        }
         PhoenixInstruction::ReduceOrder => {
             phoenix_log!("PhoenixInstruction::ReduceOrder");
             reduce_order::process_reduce_order(
@@ -229,6 +330,50 @@ pub fn process_instruction(
                 &mut record_event_fn,
             )?
         }
+        PhoenixInstruction::ReduceOrderByQuote => {
+            phoenix_log!("PhoenixInstruction::ReduceOrderByQuote");
+            reduce_order::process_reduce_order_by_quote(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                true,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::ReduceOrderByQuoteWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::ReduceOrderByQuoteWithFreeFunds");
+            reduce_order::process_reduce_order_by_quote(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                false,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::ReduceMultipleOrders => {
+            phoenix_log!("PhoenixInstruction::ReduceMultipleOrders");
+            reduce_order::process_reduce_multiple_orders(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                true,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::ReduceMultipleOrdersWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::ReduceMultipleOrdersWithFreeFunds");
+            reduce_order::process_reduce_multiple_orders(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                false,
+                &mut record_event_fn,
+            )?
+        }
         PhoenixInstruction::CancelAllOrders => {
             phoenix_log!("PhoenixInstruction::CancelAllOrders");
             cancel_multiple_orders::process_cancel_all_orders(
@@ -275,7 +420,33 @@ pub fn process_instruction(
         }
         PhoenixInstruction::CancelMultipleOrdersById => {
             phoenix_log!("PhoenixInstruction::CancelMultipleOrdersById");
-            cancel_multiple_orders::process_cancel_multiple_orders_by_id(
+            orders_found = Some(
+                cancel_multiple_orders::process_cancel_multiple_orders_by_id(
+                    program_id,
+                    &market_context,
+                    accounts,
+                    data,
+                    true,
+                    &mut record_event_fn,
+                )?,
+            );
+        }
+        PhoenixInstruction::CancelMultipleOrdersByIdWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::CancelMultipleOrdersByIdWithFreeFunds");
+            orders_found = Some(
+                cancel_multiple_orders::process_cancel_multiple_orders_by_id(
+                    program_id,
+                    &market_context,
+                    accounts,
+                    data,
+                    false,
+                    &mut record_event_fn,
+                )?,
+            );
+        }
+        PhoenixInstruction::CancelByClientOrderId => {
+            phoenix_log!("PhoenixInstruction::CancelByClientOrderId");
+            cancel_multiple_orders::process_cancel_by_client_order_id(
                 program_id,
                 &market_context,
                 accounts,
@@ -284,9 +455,53 @@ pub fn process_instruction(
                 &mut record_event_fn,
             )?
         }
-        PhoenixInstruction::CancelMultipleOrdersByIdWithFreeFunds => {
-            phoenix_log!("PhoenixInstruction::CancelMultipleOrdersByIdWithFreeFunds");
-            cancel_multiple_orders::process_cancel_multiple_orders_by_id(
+        PhoenixInstruction::CancelByClientOrderIdWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::CancelByClientOrderIdWithFreeFunds");
+            cancel_multiple_orders::process_cancel_by_client_order_id(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                false,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::CancelAllOrdersBounded => {
+            phoenix_log!("PhoenixInstruction::CancelAllOrdersBounded");
+            has_remaining_orders = Some(cancel_multiple_orders::process_cancel_all_orders_bounded(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                true,
+                &mut record_event_fn,
+            )?);
+        }
+        PhoenixInstruction::CancelAllOrdersBoundedWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::CancelAllOrdersBoundedWithFreeFunds");
+            has_remaining_orders = Some(cancel_multiple_orders::process_cancel_all_orders_bounded(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                false,
+                &mut record_event_fn,
+            )?);
+        }
+        PhoenixInstruction::CancelInPriceRange => {
+            phoenix_log!("PhoenixInstruction::CancelInPriceRange");
+            cancel_multiple_orders::process_cancel_in_price_range(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                true,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::CancelInPriceRangeWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::CancelInPriceRangeWithFreeFunds");
+            cancel_multiple_orders::process_cancel_in_price_range(
                 program_id,
                 &market_context,
                 accounts,
@@ -303,6 +518,23 @@ pub fn process_instruction(
             phoenix_log!("PhoenixInstruction::DepositFunds");
             deposit::process_deposit_funds(program_id, &market_context, accounts, data)?
         }
+        PhoenixInstruction::DepositAtoms => {
+            phoenix_log!("PhoenixInstruction::DepositAtoms");
+            deposit::process_deposit_atoms(program_id, &market_context, accounts, data)?
+        }
+        PhoenixInstruction::DemoteInactiveSeat => {
+            phoenix_log!("PhoenixInstruction::DemoteInactiveSeat");
+            manage_seat::process_demote_inactive_seat(program_id, &market_context, accounts, data)?
+        }
+        PhoenixInstruction::PruneExpiredOrders => {
+            phoenix_log!("PhoenixInstruction::PruneExpiredOrders");
+            prune_expired_orders::process_prune_expired_orders(
+                program_id,
+                &market_context,
+                data,
+                &mut record_event_fn,
+            )?
+        }
         PhoenixInstruction::ForceCancelOrders => {
             phoenix_log!("PhoenixInstruction::ForceCancelOrders");
             governance::process_force_cancel_orders(
@@ -313,6 +545,16 @@ pub fn process_instruction(
                 &mut record_event_fn,
             )?
         }
+        PhoenixInstruction::ForceCancelOrdersWithFreeFunds => {
+            phoenix_log!("PhoenixInstruction::ForceCancelOrdersWithFreeFunds");
+            governance::process_force_cancel_orders_with_free_funds(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?
+        }
         PhoenixInstruction::EvictSeat => {
             phoenix_log!("PhoenixInstruction::EvictSeat");
             governance::process_evict_seat(program_id, &market_context, accounts, data)?
@@ -342,6 +584,10 @@ pub fn process_instruction(
             phoenix_log!("PhoenixInstruction::RequestSeat");
             manage_seat::process_request_seat(program_id, &market_context, accounts, data)?
         }
+        PhoenixInstruction::RequestSeatBatch => {
+            phoenix_log!("PhoenixInstruction::RequestSeatBatch");
+            manage_seat::process_request_seat_batch(program_id, &market_context, accounts, data)?
+        }
         PhoenixInstruction::ChangeSeatStatus => {
             phoenix_log!("PhoenixInstruction::ChangeSeatStatus");
             manage_seat::process_change_seat_status(program_id, &market_context, accounts, data)?;
@@ -356,17 +602,222 @@ pub fn process_instruction(
                 &mut record_event_fn,
             )?
         }
+        PhoenixInstruction::CollectFeesSplit => {
+            phoenix_log!("PhoenixInstruction::CollectFeesSplit");
+            fees::process_collect_fees_split(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?
+        }
         PhoenixInstruction::ChangeFeeRecipient => {
             phoenix_log!("PhoenixInstruction::ChangeFeeRecipient");
             fees::process_change_fee_recipient(program_id, &market_context, accounts, data)?
         }
+        PhoenixInstruction::ChangeMakerRebate => {
+            phoenix_log!("PhoenixInstruction::ChangeMakerRebate");
+            fees::process_change_maker_rebate(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeFeeRoundingMode => {
+            phoenix_log!("PhoenixInstruction::ChangeFeeRoundingMode");
+            fees::process_change_fee_rounding_mode(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeMinFee => {
+            phoenix_log!("PhoenixInstruction::ChangeMinFee");
+            fees::process_change_min_fee(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeFeeTiers => {
+            phoenix_log!("PhoenixInstruction::ChangeFeeTiers");
+            fees::process_change_fee_tiers(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::SetTraderFeeTier => {
+            phoenix_log!("PhoenixInstruction::SetTraderFeeTier");
+            fees::process_set_trader_fee_tier(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeBookCheckpoints => {
+            phoenix_log!("PhoenixInstruction::ChangeBookCheckpoints");
+            governance::process_change_book_checkpoints(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangePriceAccumulator => {
+            phoenix_log!("PhoenixInstruction::ChangePriceAccumulator");
+            governance::process_change_price_accumulator(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeTraderVolumeTracking => {
+            phoenix_log!("PhoenixInstruction::ChangeTraderVolumeTracking");
+            governance::process_change_trader_volume_tracking(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeMinBaseLotsPerOrder => {
+            phoenix_log!("PhoenixInstruction::ChangeMinBaseLotsPerOrder");
+            governance::process_change_min_base_lots_per_order(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeMaxOrderLifetimeSlots => {
+            phoenix_log!("PhoenixInstruction::ChangeMaxOrderLifetimeSlots");
+            governance::process_change_max_order_lifetime_slots(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::SweepDust => {
+            phoenix_log!("PhoenixInstruction::SweepDust");
+            fees::process_sweep_dust(program_id, &market_context, accounts, data)?
+        }
+        PhoenixInstruction::ChangeMaxMatchLimit => {
+            phoenix_log!("PhoenixInstruction::ChangeMaxMatchLimit");
+            governance::process_change_max_match_limit(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeMaxOrdersPerSidePerTrader => {
+            phoenix_log!("PhoenixInstruction::ChangeMaxOrdersPerSidePerTrader");
+            governance::process_change_max_orders_per_side_per_trader(
+                program_id,
+                &market_context,
+                data,
+            )?
+        }
+        PhoenixInstruction::PlaceStopOrder => {
+            phoenix_log!("PhoenixInstruction::PlaceStopOrder");
+            stop_orders::process_place_stop_order(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::CancelStopOrder => {
+            phoenix_log!("PhoenixInstruction::CancelStopOrder");
+            stop_orders::process_cancel_stop_order(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::CancelAndSwap => {
+            phoenix_log!("PhoenixInstruction::CancelAndSwap");
+            swap_summary = Some(new_order::process_cancel_and_swap(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?);
+        }
+        PhoenixInstruction::ChangeMarketSymbols => {
+            phoenix_log!("PhoenixInstruction::ChangeMarketSymbols");
+            governance::process_change_market_symbols(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::DepositFundsWithNativeSol => {
+            phoenix_log!("PhoenixInstruction::DepositFundsWithNativeSol");
+            deposit::process_deposit_funds_with_native_sol(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+            )?
+        }
+        PhoenixInstruction::WithdrawFundsToNativeSol => {
+            phoenix_log!("PhoenixInstruction::WithdrawFundsToNativeSol");
+            withdraw::process_withdraw_funds_to_native_sol(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+            )?
+        }
+        PhoenixInstruction::SwapWithNativeSol => {
+            phoenix_log!("PhoenixInstruction::SwapWithNativeSol");
+            swap_summary = Some(new_order::process_swap_with_native_sol(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?);
+        }
+        PhoenixInstruction::CreateSeat => {
+            phoenix_log!("PhoenixInstruction::CreateSeat");
+            manage_seat::process_create_seat(program_id, &market_context, accounts, data)?
+        }
+        PhoenixInstruction::SetVolatilityBand => {
+            phoenix_log!("PhoenixInstruction::SetVolatilityBand");
+            governance::process_set_volatility_band(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeTakingDisabled => {
+            phoenix_log!("PhoenixInstruction::ChangeTakingDisabled");
+            governance::process_change_taking_disabled(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeDisabledEventTypes => {
+            phoenix_log!("PhoenixInstruction::ChangeDisabledEventTypes");
+            governance::process_change_disabled_event_types(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::RepriceOrder => {
+            phoenix_log!("PhoenixInstruction::RepriceOrder");
+            amend_order::process_reprice_order(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+                &mut order_ids,
+            )?
+        }
+        PhoenixInstruction::ChangeReferralFee => {
+            phoenix_log!("PhoenixInstruction::ChangeReferralFee");
+            governance::process_change_referral_fee(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeMinTakerFill => {
+            phoenix_log!("PhoenixInstruction::ChangeMinTakerFill");
+            governance::process_change_min_taker_fill(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::RegisterReferrer => {
+            phoenix_log!("PhoenixInstruction::RegisterReferrer");
+            governance::process_register_referrer(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::DeregisterReferrer => {
+            phoenix_log!("PhoenixInstruction::DeregisterReferrer");
+            governance::process_deregister_referrer(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ClaimReferralFees => {
+            phoenix_log!("PhoenixInstruction::ClaimReferralFees");
+            fees::process_claim_referral_fees(program_id, &market_context, accounts, data)?
+        }
+        PhoenixInstruction::CancelAllAndCloseSeat => {
+            phoenix_log!("PhoenixInstruction::CancelAllAndCloseSeat");
+            withdraw::process_cancel_all_and_close_seat(
+                program_id,
+                &market_context,
+                accounts,
+                data,
+                &mut record_event_fn,
+            )?
+        }
+        PhoenixInstruction::ChangeSeatInactivityThreshold => {
+            phoenix_log!("PhoenixInstruction::ChangeSeatInactivityThreshold");
+            governance::process_change_seat_inactivity_threshold(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::SetTradingWindow => {
+            phoenix_log!("PhoenixInstruction::SetTradingWindow");
+            governance::process_set_trading_window(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ChangeTickSize => {
+            phoenix_log!("PhoenixInstruction::ChangeTickSize");
+            governance::process_change_tick_size(program_id, &market_context, data)?
+        }
+        PhoenixInstruction::ResizeMarket => {
+            phoenix_log!("PhoenixInstruction::ResizeMarket");
+            governance::process_resize_market(program_id, &market_context, accounts, data)?
+        }
         _ => unreachable!(),
     }
+    market_data_tracker.write(&market_context.market_info)?;
     event_recorder.increment_market_sequence_number_and_flush(market_context.market_info)?;
     // We set the order ids at the end of the instruction because the return data gets cleared after
     // every CPI call.
     if !order_ids.is_empty() {
         set_return_data(order_ids.try_to_vec()?.as_ref());
     }
+    if let Some(has_remaining_orders) = has_remaining_orders {
+        set_return_data(&[has_remaining_orders as u8]);
+    }
+    if let Some(orders_found) = orders_found {
+        set_return_data(&orders_found);
+    }
+    if let Some(swap_summary) = swap_summary {
+        set_return_data(swap_summary.try_to_vec()?.as_ref());
+    }
     Ok(())
 }
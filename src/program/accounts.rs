@@ -9,6 +9,9 @@ use crate::quantities::{
 
 use super::status::{MarketStatus, SeatApprovalStatus};
 
+/// The maximum length in bytes of the base and quote symbols stored in the `MarketHeader`
+pub const MARKET_SYMBOL_LEN: usize = 16;
+
 /// This function returns the canonical discriminant of the given type. It is the result
 /// of hashing together the program ID and the name of the type.
 ///
@@ -39,6 +42,37 @@ pub struct MarketSizeParams {
 }
 impl ZeroCopy for MarketSizeParams {}
 
+/// Determines how an incoming order is matched against the resting orders at a price level.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+#[repr(u64)]
+pub enum MarketType {
+    /// Resting orders at a price level are filled in time priority.
+    Fifo,
+    /// Resting orders at a price level are filled in proportion to their size.
+    ProRata,
+}
+
+impl Default for MarketType {
+    fn default() -> Self {
+        Self::Fifo
+    }
+}
+
+impl TryFrom<u64> for MarketType {
+    type Error = ProgramError;
+
+    fn try_from(market_type: u64) -> Result<Self, Self::Error> {
+        match market_type {
+            0 => Ok(Self::Fifo),
+            1 => Ok(Self::ProRata),
+            _ => {
+                phoenix_log!("Invalid market type {}", market_type);
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize, Zeroable, Pod)]
 #[repr(C)]
 pub struct TokenParams {
@@ -72,8 +106,52 @@ pub struct MarketHeader {
     pub market_sequence_number: u64,
     pub successor: Pubkey,
     pub raw_base_units_per_base_unit: u32,
-    _padding1: u32,
-    _padding2: [u64; 32],
+    /// If this is nonzero, the market account stores a `RecentFills` buffer after the referral
+    /// fee book (if it is enabled), which holds the most recent fills on the market
+    pub recent_fills_enabled: u32,
+    /// If this is nonzero, a `BookCheckpoint` event is emitted at the end of every instruction
+    pub book_checkpoints_enabled: u64,
+    /// If this is nonzero, the market account stores a `ClientOrderIdIndex` after the market
+    pub client_order_id_index_enabled: u64,
+    /// New orders are rejected while the current slot is in the range
+    /// [`trading_disabled_from_slot`, `trading_disabled_until_slot`). The window is unset if
+    /// `trading_disabled_until_slot` is zero.
+    pub trading_disabled_from_slot: u64,
+    pub trading_disabled_until_slot: u64,
+    /// The number of resting bids, resting asks, and registered traders on the market. These
+    /// are updated at the end of every instruction, so they can be read without loading the market.
+    pub num_bids: u64,
+    pub num_asks: u64,
+    pub num_traders: u64,
+    /// The matching algorithm of the market (see `MarketType`). This is set when the market is
+    /// initialized and cannot be changed
+    pub market_type: u64,
+    /// If this is nonzero, anyone can revoke the approval of a seat whose trader has not placed
+    /// an order in more than this many slots
+    pub seat_inactivity_threshold_slots: u64,
+    /// If this is nonzero, the market account stores a `StopOrderBook` after the market and the
+    /// client order id index
+    pub stop_orders_enabled: u64,
+    /// UTF-8 symbols of the base and quote tokens, padded with zeros. The symbols are all zeros
+    /// for markets that were initialized without them (see `get_base_symbol`)
+    pub base_symbol: [u8; MARKET_SYMBOL_LEN],
+    pub quote_symbol: [u8; MARKET_SYMBOL_LEN],
+    /// The number of decimals that clients should use to display base and quote amounts. These
+    /// are set when the market is initialized and cannot be changed
+    pub base_display_decimals: u32,
+    pub quote_display_decimals: u32,
+    /// Events whose flag (see `PhoenixMarketEvent::event_type_flag`) is set in this mask are not
+    /// recorded in the event log. This shrinks the log, but clients that rely on the suppressed
+    /// events lose them. For example, suppressing `Fill` events keeps the `FillSummary` of each
+    /// taker order but prevents reconstructing the trade history fill by fill
+    pub disabled_event_types: u64,
+    /// If this is nonzero, the market account stores a `ReferralFeeBook` after the stop order
+    /// book (if it is enabled), and immediate-or-cancel orders can name a referrer
+    pub referral_fees_enabled: u64,
+    /// If this is nonzero, the market account stores an `IcebergReserveBook` after the recent
+    /// fills (if they are enabled), and iceberg orders can be placed on the market
+    pub iceberg_orders_enabled: u64,
+    _padding2: [u64; 14],
 }
 impl ZeroCopy for MarketHeader {}
 
@@ -105,8 +183,25 @@ impl MarketHeader {
             market_sequence_number: 0,
             successor,
             raw_base_units_per_base_unit,
-            _padding1: 0,
-            _padding2: [0; 32],
+            recent_fills_enabled: 0,
+            book_checkpoints_enabled: 0,
+            client_order_id_index_enabled: 0,
+            trading_disabled_from_slot: 0,
+            trading_disabled_until_slot: 0,
+            num_bids: 0,
+            num_asks: 0,
+            num_traders: 0,
+            market_type: MarketType::Fifo as u64,
+            seat_inactivity_threshold_slots: 0,
+            stop_orders_enabled: 0,
+            base_symbol: [0; MARKET_SYMBOL_LEN],
+            quote_symbol: [0; MARKET_SYMBOL_LEN],
+            base_display_decimals: 0,
+            quote_display_decimals: 0,
+            disabled_event_types: 0,
+            referral_fees_enabled: 0,
+            iceberg_orders_enabled: 0,
+            _padding2: [0; 14],
         }
     }
 
@@ -127,11 +222,69 @@ impl MarketHeader {
         self.tick_size_in_quote_atoms_per_base_unit
     }
 
+    /// Returns the number of raw base units (10^base_mint_decimals atoms) in a base unit, which is
+    /// used to adjust the base unit of low-priced tokens. This is 1 for markets initialized without
+    /// the adjustment.
+    pub fn get_raw_base_units_per_base_unit(&self) -> u32 {
+        self.raw_base_units_per_base_unit.max(1)
+    }
+
+    pub(crate) fn set_tick_size_in_quote_atoms_per_base_unit(
+        &mut self,
+        tick_size_in_quote_atoms_per_base_unit: QuoteAtomsPerBaseUnitPerTick,
+    ) {
+        self.tick_size_in_quote_atoms_per_base_unit = tick_size_in_quote_atoms_per_base_unit;
+    }
+
+    /// Returns true if new orders cannot be placed in `slot`
+    pub fn is_trading_disabled(&self, slot: u64) -> bool {
+        self.trading_disabled_from_slot <= slot && slot < self.trading_disabled_until_slot
+    }
+
+    /// Returns the symbol of the base token, or None if the market does not have one
+    pub fn get_base_symbol(&self) -> Option<&str> {
+        decode_market_symbol(&self.base_symbol)
+    }
+
+    /// Returns the symbol of the quote token, or None if the market does not have one
+    pub fn get_quote_symbol(&self) -> Option<&str> {
+        decode_market_symbol(&self.quote_symbol)
+    }
+
     pub fn increment_sequence_number(&mut self) {
         self.market_sequence_number += 1;
     }
 }
 
+/// Pads `symbol` with zeros to the length stored in the `MarketHeader`. Returns None if the symbol
+/// is longer than `MARKET_SYMBOL_LEN` bytes or contains a zero byte.
+pub fn encode_market_symbol(symbol: &str) -> Option<[u8; MARKET_SYMBOL_LEN]> {
+    if symbol.len() > MARKET_SYMBOL_LEN || symbol.bytes().any(|b| b == 0) {
+        return None;
+    }
+    let mut encoded = [0; MARKET_SYMBOL_LEN];
+    encoded[..symbol.len()].copy_from_slice(symbol.as_bytes());
+    Some(encoded)
+}
+
+/// Returns the symbol without its zero padding. Returns None if the symbol is empty, is not valid
+/// UTF-8, or has nonzero bytes after the padding starts.
+pub fn decode_market_symbol(symbol: &[u8; MARKET_SYMBOL_LEN]) -> Option<&str> {
+    let len = symbol
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(MARKET_SYMBOL_LEN);
+    if len == 0 || symbol[len..].iter().any(|b| *b != 0) {
+        return None;
+    }
+    std::str::from_utf8(&symbol[..len]).ok()
+}
+
+/// Returns true if `symbol` is unset (all zeros) or can be decoded by `decode_market_symbol`
+pub fn is_valid_market_symbol(symbol: &[u8; MARKET_SYMBOL_LEN]) -> bool {
+    symbol.iter().all(|b| *b == 0) || decode_market_symbol(symbol).is_some()
+}
+
 /// This struct represents the state of a seat. Only traders with seats can
 /// place limit orders on the market. The seat is valid when the approval_status
 /// field is set to Approved. The initial state is NotApproved, and the seat will
@@ -178,3 +331,32 @@ fn test_valid_discriminants() {
     );
     assert_eq!(get_discriminant::<Seat>().unwrap(), 2002603505298356104);
 }
+
+#[test]
+fn test_market_symbols() {
+    let symbol = encode_market_symbol("SOL").unwrap();
+    assert_eq!(&symbol[..4], b"SOL\0");
+    assert_eq!(decode_market_symbol(&symbol), Some("SOL"));
+    let symbol = encode_market_symbol("0123456789ABCDEF").unwrap();
+    assert_eq!(decode_market_symbol(&symbol), Some("0123456789ABCDEF"));
+
+    assert!(encode_market_symbol("0123456789ABCDEFG").is_none());
+    assert!(encode_market_symbol("SO\0L").is_none());
+    assert_eq!(decode_market_symbol(&[0; MARKET_SYMBOL_LEN]), None);
+    assert!(is_valid_market_symbol(&[0; MARKET_SYMBOL_LEN]));
+    let mut symbol = [0; MARKET_SYMBOL_LEN];
+    symbol[..4].copy_from_slice(b"SO\0L");
+    assert_eq!(decode_market_symbol(&symbol), None);
+    assert!(!is_valid_market_symbol(&symbol));
+    symbol[..4].copy_from_slice(&[0xff, 0xfe, 0xfd, 0xfc]);
+    assert_eq!(decode_market_symbol(&symbol), None);
+}
+
+#[test]
+fn test_raw_base_units_per_base_unit() {
+    let mut header = MarketHeader::zeroed();
+    // Markets initialized without the adjustment report a factor of 1
+    assert_eq!(header.get_raw_base_units_per_base_unit(), 1);
+    header.raw_base_units_per_base_unit = 1_000;
+    assert_eq!(header.get_raw_base_units_per_base_unit(), 1_000);
+}
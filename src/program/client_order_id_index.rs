@@ -0,0 +1,167 @@
+use super::error::PhoenixError;
+use super::MarketSizeParams;
+use crate::quantities::Ticks;
+use bytemuck::{Pod, Zeroable};
+use sokoban::node_allocator::{OrderedNodeAllocatorMap, ZeroCopy};
+use sokoban::RedBlackTree;
+use solana_program::program_error::ProgramError;
+
+/// Markets that are initialized with the client order id index enabled store this struct in the
+/// market account directly after the market. It maps the sequence number of each resting order to
+/// the client order id that was supplied when the order was placed.
+///
+/// Entries are not removed when orders are filled or cancelled. Stale entries are pruned when the
+/// index is full, so lookups must always start from an order that is on the book.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable)]
+pub struct ClientOrderIdIndex<const NUM_ORDERS: usize> {
+    pub orders: RedBlackTree<u64, ClientOrderIdEntry, NUM_ORDERS>,
+}
+
+unsafe impl<const NUM_ORDERS: usize> Pod for ClientOrderIdIndex<NUM_ORDERS> {}
+
+impl<const NUM_ORDERS: usize> ZeroCopy for ClientOrderIdIndex<NUM_ORDERS> {}
+
+/// The client order id is split into two u64s to keep the entry 8-byte aligned
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Zeroable, Pod)]
+pub struct ClientOrderIdEntry {
+    client_order_id_low: u64,
+    client_order_id_high: u64,
+    /// The price of the order is stored so that the order can be located on the book
+    pub price_in_ticks: Ticks,
+}
+
+impl ClientOrderIdEntry {
+    pub fn new(client_order_id: u128, price_in_ticks: Ticks) -> Self {
+        ClientOrderIdEntry {
+            client_order_id_low: client_order_id as u64,
+            client_order_id_high: (client_order_id >> 64) as u64,
+            price_in_ticks,
+        }
+    }
+
+    pub fn client_order_id(&self) -> u128 {
+        ((self.client_order_id_high as u128) << 64) | self.client_order_id_low as u128
+    }
+}
+
+macro_rules! client_order_id_index_mut {
+    ($num_orders:literal, $bytes:expr) => {
+        &mut ClientOrderIdIndex::<$num_orders>::load_mut_bytes($bytes)
+            .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
+            .orders as &mut dyn OrderedNodeAllocatorMap<u64, ClientOrderIdEntry>
+    };
+}
+
+macro_rules! client_order_id_index {
+    ($num_orders:literal, $bytes:expr) => {
+        &ClientOrderIdIndex::<$num_orders>::load_bytes($bytes)
+            .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
+            .orders as &dyn OrderedNodeAllocatorMap<u64, ClientOrderIdEntry>
+    };
+}
+
+macro_rules! initialize_client_order_id_index {
+    ($num_orders:literal, $bytes:expr) => {
+        ClientOrderIdIndex::<$num_orders>::load_mut_bytes($bytes)
+            .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
+            .orders
+            .initialize()
+    };
+}
+
+/// The index can hold one entry for every order that fits on the book.
+pub fn get_client_order_id_index_size(
+    market_size_params: &MarketSizeParams,
+) -> Result<usize, ProgramError> {
+    let size = match market_size_params.bids_size + market_size_params.asks_size {
+        1024 => std::mem::size_of::<ClientOrderIdIndex<1024>>(),
+        2048 => std::mem::size_of::<ClientOrderIdIndex<2048>>(),
+        4096 => std::mem::size_of::<ClientOrderIdIndex<4096>>(),
+        8192 => std::mem::size_of::<ClientOrderIdIndex<8192>>(),
+        _ => {
+            phoenix_log!("Invalid parameters for client order id index");
+            return Err(PhoenixError::InvalidMarketParameters.into());
+        }
+    };
+    Ok(size)
+}
+
+pub(crate) fn initialize_client_order_id_index(
+    market_size_params: &MarketSizeParams,
+    bytes: &mut [u8],
+) -> Result<(), ProgramError> {
+    match market_size_params.bids_size + market_size_params.asks_size {
+        1024 => initialize_client_order_id_index!(1024, bytes),
+        2048 => initialize_client_order_id_index!(2048, bytes),
+        4096 => initialize_client_order_id_index!(4096, bytes),
+        8192 => initialize_client_order_id_index!(8192, bytes),
+        _ => {
+            phoenix_log!("Invalid parameters for client order id index");
+            return Err(PhoenixError::InvalidMarketParameters.into());
+        }
+    };
+    Ok(())
+}
+
+pub(crate) fn load_client_order_id_index_mut<'a>(
+    market_size_params: &MarketSizeParams,
+    bytes: &'a mut [u8],
+) -> Result<&'a mut dyn OrderedNodeAllocatorMap<u64, ClientOrderIdEntry>, ProgramError> {
+    let index = match market_size_params.bids_size + market_size_params.asks_size {
+        1024 => client_order_id_index_mut!(1024, bytes),
+        2048 => client_order_id_index_mut!(2048, bytes),
+        4096 => client_order_id_index_mut!(4096, bytes),
+        8192 => client_order_id_index_mut!(8192, bytes),
+        _ => {
+            phoenix_log!("Invalid parameters for client order id index");
+            return Err(PhoenixError::InvalidMarketParameters.into());
+        }
+    };
+    Ok(index)
+}
+
+pub fn load_client_order_id_index<'a>(
+    market_size_params: &MarketSizeParams,
+    bytes: &'a [u8],
+) -> Result<&'a dyn OrderedNodeAllocatorMap<u64, ClientOrderIdEntry>, ProgramError> {
+    let index = match market_size_params.bids_size + market_size_params.asks_size {
+        1024 => client_order_id_index!(1024, bytes),
+        2048 => client_order_id_index!(2048, bytes),
+        4096 => client_order_id_index!(4096, bytes),
+        8192 => client_order_id_index!(8192, bytes),
+        _ => {
+            phoenix_log!("Invalid parameters for client order id index");
+            return Err(PhoenixError::InvalidMarketParameters.into());
+        }
+    };
+    Ok(index)
+}
+
+#[test]
+fn test_client_order_id_index_size() {
+    use sokoban::node_allocator::NodeAllocatorMap;
+    for (bids_size, asks_size) in [(512, 512), (1024, 1024), (2048, 2048), (4096, 4096)] {
+        let market_size_params = MarketSizeParams {
+            bids_size,
+            asks_size,
+            num_seats: 128,
+        };
+        let size = get_client_order_id_index_size(&market_size_params).unwrap();
+        let mut bytes = vec![0_u8; size];
+        initialize_client_order_id_index(&market_size_params, &mut bytes).unwrap();
+        let index = load_client_order_id_index_mut(&market_size_params, &mut bytes).unwrap();
+        let entry = ClientOrderIdEntry::new(u128::MAX - 1, Ticks::new(100));
+        index.insert(1, entry).unwrap();
+        assert_eq!(index.get(&1).unwrap().client_order_id(), u128::MAX - 1);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.capacity(), (bids_size + asks_size) as usize);
+    }
+    assert!(get_client_order_id_index_size(&MarketSizeParams {
+        bids_size: 100,
+        asks_size: 100,
+        num_seats: 128,
+    })
+    .is_err());
+}
@@ -0,0 +1,430 @@
+//! Helpers for programs that invoke Phoenix through CPI.
+//!
+//! Each function assembles the account metas in the order expected by the corresponding
+//! instruction, serializes the instruction data, and calls `invoke_signed`. The `trader` account
+//! is marked as a signer, so `signer_seeds` must contain the seeds of the trader if it is a PDA
+//! owned by the calling program. Pass an empty slice if the trader signed the outer transaction.
+
+use crate::program::deposit::DepositParams;
+use crate::program::withdraw::WithdrawParams;
+use crate::program::PhoenixInstruction;
+use crate::state::OrderPacket;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+};
+
+/// Accounts that are required by every trader instruction.
+#[derive(Clone)]
+pub struct PhoenixAccounts<'info> {
+    pub phoenix_program: AccountInfo<'info>,
+    pub log_authority: AccountInfo<'info>,
+    pub market: AccountInfo<'info>,
+    pub trader: AccountInfo<'info>,
+}
+
+impl<'info> PhoenixAccounts<'info> {
+    fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*self.phoenix_program.key, false),
+            AccountMeta::new_readonly(*self.log_authority.key, false),
+            AccountMeta::new(*self.market.key, false),
+            AccountMeta::new_readonly(*self.trader.key, true),
+        ]
+    }
+
+    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+        vec![
+            self.phoenix_program.clone(),
+            self.log_authority.clone(),
+            self.market.clone(),
+            self.trader.clone(),
+        ]
+    }
+}
+
+/// Token accounts that are required by instructions that transfer funds between the trader and
+/// the market vaults. The base and quote mints must be set if the token program is Token-2022, and
+/// are ignored otherwise.
+#[derive(Clone)]
+pub struct TokenAccounts<'info> {
+    pub base_account: AccountInfo<'info>,
+    pub quote_account: AccountInfo<'info>,
+    pub base_vault: AccountInfo<'info>,
+    pub quote_vault: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub base_mint: Option<AccountInfo<'info>>,
+    pub quote_mint: Option<AccountInfo<'info>>,
+}
+
+impl<'info> TokenAccounts<'info> {
+    fn get_mints(&self) -> Vec<&AccountInfo<'info>> {
+        if self.token_program.key == &spl_token_2022::id() {
+            self.base_mint
+                .iter()
+                .chain(self.quote_mint.iter())
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn to_account_metas(&self) -> Vec<AccountMeta> {
+        let mut account_metas = vec![
+            AccountMeta::new(*self.base_account.key, false),
+            AccountMeta::new(*self.quote_account.key, false),
+            AccountMeta::new(*self.base_vault.key, false),
+            AccountMeta::new(*self.quote_vault.key, false),
+            AccountMeta::new_readonly(*self.token_program.key, false),
+        ];
+        account_metas.extend(
+            self.get_mints()
+                .into_iter()
+                .map(|mint| AccountMeta::new_readonly(*mint.key, false)),
+        );
+        account_metas
+    }
+
+    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+        let mut account_infos = vec![
+            self.base_account.clone(),
+            self.quote_account.clone(),
+            self.base_vault.clone(),
+            self.quote_vault.clone(),
+            self.token_program.clone(),
+        ];
+        account_infos.extend(self.get_mints().into_iter().cloned());
+        account_infos
+    }
+}
+
+fn invoke_phoenix<'info>(
+    instruction: PhoenixInstruction,
+    params: Vec<u8>,
+    mut account_metas: Vec<AccountMeta>,
+    mut account_infos: Vec<AccountInfo<'info>>,
+    seat: Option<&AccountInfo<'info>>,
+    token_accounts: Option<&TokenAccounts<'info>>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    if let Some(seat) = seat {
+        account_metas.push(AccountMeta::new_readonly(*seat.key, false));
+        account_infos.push(seat.clone());
+    }
+    if let Some(token_accounts) = token_accounts {
+        account_metas.extend(token_accounts.to_account_metas());
+        account_infos.extend(token_accounts.to_account_infos());
+    }
+    invoke_signed(
+        &Instruction {
+            program_id: crate::id(),
+            accounts: account_metas,
+            data: [instruction.to_vec(), params].concat(),
+        },
+        &account_infos,
+        signer_seeds,
+    )
+}
+
+/// Send a take-only order that settles directly with the trader's token accounts.
+pub fn swap<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    token_accounts: &TokenAccounts<'info>,
+    order_packet: &OrderPacket,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::Swap,
+        order_packet.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        None,
+        Some(token_accounts),
+        signer_seeds,
+    )
+}
+
+/// Send a take-only order using only the funds deposited in the trader's seat.
+pub fn swap_with_free_funds<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    seat: &AccountInfo<'info>,
+    order_packet: &OrderPacket,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::SwapWithFreeFunds,
+        order_packet.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        Some(seat),
+        None,
+        signer_seeds,
+    )
+}
+
+/// Place an order that can rest on the book. Any shortfall in deposited funds is transferred from
+/// the trader's token accounts.
+pub fn place_limit_order<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    seat: &AccountInfo<'info>,
+    token_accounts: &TokenAccounts<'info>,
+    order_packet: &OrderPacket,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::PlaceLimitOrder,
+        order_packet.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        Some(seat),
+        Some(token_accounts),
+        signer_seeds,
+    )
+}
+
+/// Place an order that can rest on the book using only deposited funds.
+pub fn place_limit_order_with_free_funds<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    seat: &AccountInfo<'info>,
+    order_packet: &OrderPacket,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::PlaceLimitOrderWithFreeFunds,
+        order_packet.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        Some(seat),
+        None,
+        signer_seeds,
+    )
+}
+
+/// Cancel all of the trader's orders and withdraw the released funds.
+pub fn cancel_all_orders<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    token_accounts: &TokenAccounts<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::CancelAllOrders,
+        vec![],
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        None,
+        Some(token_accounts),
+        signer_seeds,
+    )
+}
+
+/// Cancel all of the trader's orders and leave the released funds deposited.
+pub fn cancel_all_orders_with_free_funds<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::CancelAllOrdersWithFreeFunds,
+        vec![],
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        None,
+        None,
+        signer_seeds,
+    )
+}
+
+pub fn deposit_funds<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    seat: &AccountInfo<'info>,
+    token_accounts: &TokenAccounts<'info>,
+    params: &DepositParams,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::DepositFunds,
+        params.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        Some(seat),
+        Some(token_accounts),
+        signer_seeds,
+    )
+}
+
+/// Withdraw deposited funds. Closing the seat is not supported through this helper because it
+/// requires the seat account to be passed as a trailing writable account.
+pub fn withdraw_funds<'info>(
+    accounts: &PhoenixAccounts<'info>,
+    token_accounts: &TokenAccounts<'info>,
+    params: &WithdrawParams,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_phoenix(
+        PhoenixInstruction::WithdrawFunds,
+        params.try_to_vec()?,
+        accounts.to_account_metas(),
+        accounts.to_account_infos(),
+        None,
+        Some(token_accounts),
+        signer_seeds,
+    )
+}
+
+#[test]
+fn test_cpi_accounts_match_instruction_builders() {
+    use crate::phoenix_log_authority;
+    use crate::program::instruction_builders::*;
+    use crate::program::loaders::{get_seat_address, get_vault_address};
+    use crate::state::{SelfTradeBehavior, Side};
+    use solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+    use solana_program::pubkey::Pubkey;
+    use std::sync::{Arc, Mutex};
+
+    // Records the instructions passed to `invoke_signed` instead of executing them
+    struct RecordingStubs(Arc<Mutex<Vec<Instruction>>>);
+    impl SyscallStubs for RecordingStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            _account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            self.0.lock().unwrap().push(instruction.clone());
+            Ok(())
+        }
+    }
+    let invoked = Arc::new(Mutex::new(vec![]));
+    set_syscall_stubs(Box::new(RecordingStubs(invoked.clone())));
+
+    fn account_info(key: Pubkey) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            false,
+            Box::leak(Box::new(0)),
+            Box::leak(vec![].into_boxed_slice()),
+            Box::leak(Box::new(Pubkey::default())),
+            false,
+            0,
+        )
+    }
+
+    for token_program in [spl_token::id(), spl_token_2022::id()] {
+        let (market, trader, base, quote) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (base_account, quote_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (seat, _) = get_seat_address(&market, &trader);
+        let accounts = PhoenixAccounts {
+            phoenix_program: account_info(crate::id()),
+            log_authority: account_info(phoenix_log_authority::id()),
+            market: account_info(market),
+            trader: account_info(trader),
+        };
+        let token_accounts = TokenAccounts {
+            base_account: account_info(base_account),
+            quote_account: account_info(quote_account),
+            base_vault: account_info(get_vault_address(&market, &base).0),
+            quote_vault: account_info(get_vault_address(&market, &quote).0),
+            token_program: account_info(token_program),
+            base_mint: Some(account_info(base)),
+            quote_mint: Some(account_info(quote)),
+        };
+        let seat_info = account_info(seat);
+        let ioc_order = OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            100,
+            10,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        );
+        let limit_order = OrderPacket::new_limit_order_default(Side::Bid, 100, 10);
+        let deposit_params = DepositParams {
+            quote_lots_to_deposit: 1,
+            base_lots_to_deposit: 2,
+        };
+
+        swap(&accounts, &token_accounts, &ioc_order, &[]).unwrap();
+        swap_with_free_funds(&accounts, &seat_info, &ioc_order, &[]).unwrap();
+        place_limit_order(&accounts, &seat_info, &token_accounts, &limit_order, &[]).unwrap();
+        place_limit_order_with_free_funds(&accounts, &seat_info, &limit_order, &[]).unwrap();
+        cancel_all_orders(&accounts, &token_accounts, &[]).unwrap();
+        cancel_all_orders_with_free_funds(&accounts, &[]).unwrap();
+        deposit_funds(&accounts, &seat_info, &token_accounts, &deposit_params, &[]).unwrap();
+        withdraw_funds(
+            &accounts,
+            &token_accounts,
+            &WithdrawParams {
+                quote_lots_to_withdraw: None,
+                base_lots_to_withdraw: None,
+                close_seat: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+        let expected = vec![
+            create_new_order_instruction_with_custom_token_accounts(
+                &market,
+                &trader,
+                &base_account,
+                &quote_account,
+                &base,
+                &quote,
+                &token_program,
+                &ioc_order,
+            ),
+            create_new_order_with_free_funds_instruction(&market, &trader, &ioc_order),
+            create_new_order_instruction_with_custom_token_accounts(
+                &market,
+                &trader,
+                &base_account,
+                &quote_account,
+                &base,
+                &quote,
+                &token_program,
+                &limit_order,
+            ),
+            create_new_order_with_free_funds_instruction(&market, &trader, &limit_order),
+            create_cancel_all_orders_instruction_with_custom_token_accounts(
+                &market,
+                &trader,
+                &base_account,
+                &quote_account,
+                &base,
+                &quote,
+                &token_program,
+            ),
+            create_cancel_all_order_with_free_funds_instruction(&market, &trader),
+            create_deposit_funds_instruction_with_custom_token_accounts(
+                &market,
+                &trader,
+                &seat,
+                &base_account,
+                &quote_account,
+                &base,
+                &quote,
+                &token_program,
+                &deposit_params,
+            ),
+            create_withdraw_funds_instruction_with_custom_token_accounts(
+                &market,
+                &trader,
+                &base_account,
+                &quote_account,
+                &base,
+                &quote,
+                &token_program,
+            ),
+        ];
+        assert_eq!(std::mem::take(&mut *invoked.lock().unwrap()), expected);
+    }
+}
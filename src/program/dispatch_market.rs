@@ -1,5 +1,5 @@
 use super::error::{assert_with_msg, PhoenixError};
-use super::MarketSizeParams;
+use super::{MarketSizeParams, MarketType};
 use crate::state::markets::{
     FIFOMarket, FIFOOrderId, FIFORestingOrder, Market, MarketWrapper, MarketWrapperMut,
     WritableMarket,
@@ -8,34 +8,75 @@ use crate::state::OrderPacket;
 use sokoban::node_allocator::ZeroCopy;
 use solana_program::{program_error::ProgramError, pubkey::Pubkey};
 
+macro_rules! fifo_market_size {
+    ($num_bids:literal, $num_asks:literal, $num_seats:literal) => {
+        std::mem::size_of::<FIFOMarket<Pubkey, $num_bids, $num_asks, $num_seats>>()
+    };
+}
+
+// Markets with a client order id index store additional data after the market, so the
+// market bytes are truncated to the size of the market before loading
 macro_rules! fifo_market_mut {
-    ($num_bids:literal, $num_asks:literal, $num_seats:literal, $bytes:expr) => {
-        FIFOMarket::<Pubkey, $num_bids, $num_asks, $num_seats>::load_mut_bytes($bytes)
-            .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
+    ($num_bids:literal, $num_asks:literal, $num_seats:literal, $pro_rata:literal, $bytes:expr) => {
+        FIFOMarket::<Pubkey, $num_bids, $num_asks, $num_seats, $pro_rata>::load_mut_bytes(
+            $bytes
+                .get_mut(..fifo_market_size!($num_bids, $num_asks, $num_seats))
+                .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?,
+        )
+        .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
             as &mut dyn WritableMarket<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>
     };
 }
 
+// FIFO and pro-rata markets share the same layout, so the matching algorithm is selected with a
+// literal `$pro_rata` flag
+macro_rules! fifo_market_for_size_params_mut {
+    ($market_size_params:expr, $pro_rata:literal, $bytes:expr) => {{
+        let MarketSizeParams {
+            bids_size,
+            asks_size,
+            num_seats,
+        } = $market_size_params;
+        match (bids_size, asks_size, num_seats) {
+            (512, 512, 128) => fifo_market_mut!(512, 512, 128, $pro_rata, $bytes),
+            (512, 512, 1025) => fifo_market_mut!(512, 512, 1025, $pro_rata, $bytes),
+            (512, 512, 1153) => fifo_market_mut!(512, 512, 1153, $pro_rata, $bytes),
+            (1024, 1024, 128) => fifo_market_mut!(1024, 1024, 128, $pro_rata, $bytes),
+            (1024, 1024, 2049) => fifo_market_mut!(1024, 1024, 2049, $pro_rata, $bytes),
+            (1024, 1024, 2177) => fifo_market_mut!(1024, 1024, 2177, $pro_rata, $bytes),
+            (2048, 2048, 128) => fifo_market_mut!(2048, 2048, 128, $pro_rata, $bytes),
+            (2048, 2048, 4097) => fifo_market_mut!(2048, 2048, 4097, $pro_rata, $bytes),
+            (2048, 2048, 4225) => fifo_market_mut!(2048, 2048, 4225, $pro_rata, $bytes),
+            (4096, 4096, 128) => fifo_market_mut!(4096, 4096, 128, $pro_rata, $bytes),
+            (4096, 4096, 8193) => fifo_market_mut!(4096, 4096, 8193, $pro_rata, $bytes),
+            (4096, 4096, 8321) => fifo_market_mut!(4096, 4096, 8321, $pro_rata, $bytes),
+            _ => {
+                phoenix_log!("Invalid parameters for market");
+                return Err(PhoenixError::InvalidMarketParameters.into());
+            }
+        }
+    }};
+}
+
 macro_rules! fifo_market {
     ($num_bids:literal, $num_asks:literal, $num_seats:literal, $bytes:expr) => {
-        FIFOMarket::<Pubkey, $num_bids, $num_asks, $num_seats>::load_bytes($bytes)
-            .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
+        FIFOMarket::<Pubkey, $num_bids, $num_asks, $num_seats>::load_bytes(
+            $bytes
+                .get(..fifo_market_size!($num_bids, $num_asks, $num_seats))
+                .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?,
+        )
+        .ok_or(PhoenixError::FailedToLoadMarketFromAccount)?
             as &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>
     };
 }
 
-macro_rules! fifo_market_size {
-    ($num_bids:literal, $num_asks:literal, $num_seats:literal) => {
-        std::mem::size_of::<FIFOMarket<Pubkey, $num_bids, $num_asks, $num_seats>>()
-    };
-}
-
 pub(crate) fn load_with_dispatch_mut<'a>(
     market_size_params: &'a MarketSizeParams,
+    market_type: MarketType,
     bytes: &'a mut [u8],
 ) -> Result<MarketWrapperMut<'a, Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>, ProgramError>
 {
-    dispatch_market_mut(market_size_params, bytes, false)
+    dispatch_market_mut(market_size_params, market_type, bytes, false)
 }
 
 pub(crate) fn load_with_dispatch_init<'a>(
@@ -43,37 +84,21 @@ pub(crate) fn load_with_dispatch_init<'a>(
     bytes: &'a mut [u8],
 ) -> Result<MarketWrapperMut<'a, Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>, ProgramError>
 {
-    dispatch_market_mut(market_size_params, bytes, true)
+    // The matching algorithm does not affect the layout of the market, so a newly initialized
+    // market is always loaded as FIFO
+    dispatch_market_mut(market_size_params, MarketType::Fifo, bytes, true)
 }
 
 pub(crate) fn dispatch_market_mut<'a>(
     market_size_params: &'a MarketSizeParams,
+    market_type: MarketType,
     bytes: &'a mut [u8],
     is_initial: bool,
 ) -> Result<MarketWrapperMut<'a, Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>, ProgramError>
 {
-    let MarketSizeParams {
-        bids_size,
-        asks_size,
-        num_seats,
-    } = market_size_params;
-    let market = match (bids_size, asks_size, num_seats) {
-        (512, 512, 128) => fifo_market_mut!(512, 512, 128, bytes),
-        (512, 512, 1025) => fifo_market_mut!(512, 512, 1025, bytes),
-        (512, 512, 1153) => fifo_market_mut!(512, 512, 1153, bytes),
-        (1024, 1024, 128) => fifo_market_mut!(1024, 1024, 128, bytes),
-        (1024, 1024, 2049) => fifo_market_mut!(1024, 1024, 2049, bytes),
-        (1024, 1024, 2177) => fifo_market_mut!(1024, 1024, 2177, bytes),
-        (2048, 2048, 128) => fifo_market_mut!(2048, 2048, 128, bytes),
-        (2048, 2048, 4097) => fifo_market_mut!(2048, 2048, 4097, bytes),
-        (2048, 2048, 4225) => fifo_market_mut!(2048, 2048, 4225, bytes),
-        (4096, 4096, 128) => fifo_market_mut!(4096, 4096, 128, bytes),
-        (4096, 4096, 8193) => fifo_market_mut!(4096, 4096, 8193, bytes),
-        (4096, 4096, 8321) => fifo_market_mut!(4096, 4096, 8321, bytes),
-        _ => {
-            phoenix_log!("Invalid parameters for market");
-            return Err(PhoenixError::InvalidMarketParameters.into());
-        }
+    let market = match market_type {
+        MarketType::Fifo => fifo_market_for_size_params_mut!(market_size_params, false, bytes),
+        MarketType::ProRata => fifo_market_for_size_params_mut!(market_size_params, true, bytes),
     };
     if !is_initial {
         assert_with_msg(
@@ -91,6 +116,9 @@ pub(crate) fn dispatch_market_mut<'a>(
 }
 
 /// Loads a market from a given buffer and known market params.
+///
+/// The matching algorithm only affects how orders are placed, so markets of every `MarketType`
+/// can be read through this function.
 pub fn load_with_dispatch<'a>(
     market_size_params: &'a MarketSizeParams,
     bytes: &'a [u8],
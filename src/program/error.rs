@@ -1,8 +1,9 @@
-use num_enum::IntoPrimitive;
+use crate::state::PhoenixMatchError;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use solana_program::{entrypoint::ProgramResult, msg, program_error::ProgramError};
 use thiserror::Error;
 
-#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, IntoPrimitive)]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u32)]
 pub enum PhoenixError {
     #[error("Invalid market parameters error")]
@@ -57,6 +58,46 @@ pub enum PhoenixError {
     FailedToSerializeEvent = 24,
     #[error("Failed to flush buffer")]
     FailedToFlushBuffer = 25,
+    #[error("Amend order error")]
+    AmendOrderError = 26,
+    #[error("Client order id index error")]
+    ClientOrderIdIndexError = 27,
+    #[error("Insufficient deposit received error")]
+    InsufficientDepositReceived = 28,
+    #[error("Trading is disabled error")]
+    TradingDisabled = 29,
+    #[error("Internal transfer error")]
+    InternalTransferError = 30,
+    #[error("Swap route error")]
+    SwapRouteError = 31,
+    #[error("Sweep dust error")]
+    SweepDustError = 32,
+    #[error("Stop order error")]
+    StopOrderError = 33,
+    #[error("Reprice order error")]
+    RepriceOrderError = 34,
+    #[error("Referral fee error")]
+    ReferralFeeError = 35,
+    #[error("Market price out of bounds error")]
+    MarketPriceOutOfBounds = 36,
+    #[error("Unsupported mint extension error")]
+    UnsupportedMintExtension = 37,
+}
+
+impl PhoenixError {
+    /// Returns the error with the given custom program error code, if there is one
+    pub fn from_u32(code: u32) -> Option<Self> {
+        Self::try_from(code).ok()
+    }
+}
+
+/// Decodes the code of a `ProgramError::Custom` returned by the program into a readable message.
+/// Codes below 100 are program errors (`PhoenixError`) and codes from 100 are order rejections
+/// from the matching engine (`PhoenixMatchError`).
+pub fn decode_custom_error(code: u32) -> Option<String> {
+    PhoenixError::from_u32(code)
+        .map(|e| e.to_string())
+        .or_else(|| PhoenixMatchError::from_u32(code).map(|e| e.to_string()))
 }
 
 impl From<PhoenixError> for ProgramError {
@@ -65,6 +106,12 @@ impl From<PhoenixError> for ProgramError {
     }
 }
 
+impl From<PhoenixMatchError> for ProgramError {
+    fn from(e: PhoenixMatchError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
 #[track_caller]
 #[inline(always)]
 pub fn assert_with_msg(v: bool, err: impl Into<ProgramError>, msg: &str) -> ProgramResult {
@@ -76,3 +123,23 @@ pub fn assert_with_msg(v: bool, err: impl Into<ProgramError>, msg: &str) -> Prog
         Err(err.into())
     }
 }
+
+#[test]
+fn test_decode_custom_error() {
+    for code in 0..=37 {
+        let error = PhoenixError::from_u32(code).unwrap();
+        assert_eq!(u32::from(error), code);
+        assert_eq!(decode_custom_error(code), Some(error.to_string()));
+    }
+    for code in 100..=125 {
+        let error = PhoenixMatchError::from_u32(code).unwrap();
+        assert_eq!(error as u32, code);
+        assert_eq!(decode_custom_error(code), Some(error.to_string()));
+    }
+    assert_eq!(
+        decode_custom_error(PhoenixError::InvalidSeatStatus as u32).unwrap(),
+        "Invalid seat status"
+    );
+    assert_eq!(decode_custom_error(37), None);
+    assert_eq!(decode_custom_error(126), None);
+}
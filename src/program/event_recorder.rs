@@ -1,4 +1,8 @@
-use crate::{phoenix_log_authority, state::markets::MarketEvent};
+use crate::{
+    phoenix_log_authority,
+    quantities::{BaseLots, Ticks},
+    state::{markets::MarketEvent, Side},
+};
 use borsh::BorshSerialize;
 use solana_program::{
     account_info::AccountInfo,
@@ -10,10 +14,12 @@ use solana_program::{
     pubkey::Pubkey,
     sysvar::Sysvar,
 };
+use std::mem::size_of;
 
 use super::{
-    assert_with_msg, checkers::phoenix_checkers::MarketAccountInfo, AuditLogHeader, PhoenixError,
-    PhoenixInstruction, PhoenixLogContext, PhoenixMarketContext, PhoenixMarketEvent,
+    assert_with_msg, checkers::phoenix_checkers::MarketAccountInfo, load_with_dispatch,
+    AuditLogHeader, MarketHeader, PhoenixError, PhoenixInstruction, PhoenixLogContext,
+    PhoenixMarketContext, PhoenixMarketEvent, EVENT_LOG_SCHEMA_VERSION,
 };
 
 /// The maximum amount of data that can be sent through a CPI is 1280 bytes
@@ -29,6 +35,7 @@ const LOG_IX_ACCOUNT_META_SIZE: usize = 34;
 /// -----------------------------------------------------
 /// 1               log instruction enum         u8
 /// 1               market event enum            u8
+/// 1               event log schema version     u8
 /// 1               current instruction enum     u8
 /// 8               sequence number              u64
 /// 8               timestamp                    i64
@@ -36,21 +43,21 @@ const LOG_IX_ACCOUNT_META_SIZE: usize = 34;
 /// 32              market pubkey                Pubkey
 /// 32              signer pubkey                Pubkey
 /// 2               number of events in batch    u16
-const HEADER_LEN: usize = 93;
+const HEADER_LEN: usize = 94;
 
-/// The largest event is a fill event
+/// The largest event is a fill summary event
 /// It contains the following metadata:
 ///
 /// size (bytes)    description                  data type
 /// -----------------------------------------------------
 /// 1               market event enum            u8
 /// 2               index                        u16,
-/// 32              maker_id                     Pubkey,
-/// 8               order_sequence_number        u64,
-/// 8               price_in_ticks               u64,
-/// 8               base_lots_filled             u64,
-/// 8               base_lots_remaining          u64,
-const MAX_EVENT_SIZE: usize = 67;
+/// 32              taker_id                     Pubkey,
+/// 16              client_order_id              u128,
+/// 8               total_base_lots_filled       u64,
+/// 8               total_quote_lots_filled      u64,
+/// 8               total_fee_in_quote_lots      u64,
+const MAX_EVENT_SIZE: usize = 75;
 
 /// This struct manages in internal state of market events. It is used to
 /// track the current state of the event buffer and to serialize the
@@ -73,6 +80,8 @@ pub(crate) struct EventRecorder<'info> {
     /// (number of events, pending events, current batch index etc.)
     state_tracker: EventStateTracker,
     error_code: Option<PhoenixError>,
+    /// Events whose type flag is set in this mask are dropped instead of being recorded
+    disabled_event_types: u64,
 }
 
 impl<'info> EventRecorder<'info> {
@@ -96,6 +105,7 @@ impl<'info> EventRecorder<'info> {
         let mut data = Vec::with_capacity(MAX_INNER_INSTRUCTION_SIZE);
         data.push(PhoenixInstruction::Log as u8);
         PhoenixMarketEvent::Header(AuditLogHeader {
+            schema_version: EVENT_LOG_SCHEMA_VERSION,
             instruction: phoenix_instruction as u8,
             sequence_number: header.market_sequence_number,
             timestamp: clock.unix_timestamp,
@@ -119,6 +129,7 @@ impl<'info> EventRecorder<'info> {
             },
             state_tracker: EventStateTracker::default(),
             error_code: None,
+            disabled_event_types: header.disabled_event_types,
         })
     }
 
@@ -150,8 +161,13 @@ impl<'info> EventRecorder<'info> {
         if self.error_code.is_some() {
             return;
         }
-        // By serialzing into an existing buffer, we avoid allocating a new vector
         let mut event = PhoenixMarketEvent::from(event);
+        // Disabled events are dropped before they are assigned an index, so the indices of the
+        // recorded events stay contiguous
+        if event.event_type_flag() & self.disabled_event_types != 0 {
+            return;
+        }
+        // By serialzing into an existing buffer, we avoid allocating a new vector
         event.set_index(self.state_tracker.events_added);
 
         // This should always be false
@@ -183,11 +199,74 @@ impl<'info> EventRecorder<'info> {
         self.scratch_buffer.drain(..);
     }
 
+    /// Records the best bid and ask along with the aggregate size at each price. Expired orders
+    /// are excluded.
+    fn add_book_checkpoint(&mut self, market_info: &MarketAccountInfo<'_, 'info>) -> ProgramResult {
+        let clock = Clock::get()?;
+        // The size params are read from the header because the market may have been resized
+        // during the instruction
+        let market_size_params = market_info.get_header()?.market_size_params;
+        let ladder = {
+            let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+            load_with_dispatch(&market_size_params, market_bytes)?
+                .inner
+                .get_typed_ladder_with_expiration(
+                    1,
+                    Some(clock.slot),
+                    Some(clock.unix_timestamp as u64),
+                )
+        };
+        let (best_bid_in_ticks, best_bid_base_lots) = ladder
+            .bids
+            .first()
+            .map(|level| (level.price_in_ticks, level.size_in_base_lots))
+            .unwrap_or((Ticks::ZERO, BaseLots::ZERO));
+        let (best_ask_in_ticks, best_ask_base_lots) = ladder
+            .asks
+            .first()
+            .map(|level| (level.price_in_ticks, level.size_in_base_lots))
+            .unwrap_or((Ticks::ZERO, BaseLots::ZERO));
+        self.add_event(MarketEvent::BookCheckpoint {
+            best_bid_in_ticks,
+            best_bid_base_lots,
+            best_ask_in_ticks,
+            best_ask_base_lots,
+        });
+        Ok(())
+    }
+
+    /// Copies the number of resting orders and registered traders from the market into the header
+    fn update_header_counters(&self, market_info: &MarketAccountInfo<'_, 'info>) -> ProgramResult {
+        // The size params are read from the header because the market may have been resized
+        // during the instruction
+        let market_size_params = market_info.get_header()?.market_size_params;
+        let (num_bids, num_asks, num_traders) = {
+            let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+            let market = load_with_dispatch(&market_size_params, market_bytes)?.inner;
+            (
+                market.get_book(Side::Bid).len() as u64,
+                market.get_book(Side::Ask).len() as u64,
+                market.get_registered_traders().len() as u64,
+            )
+        };
+        let mut header = market_info.get_header_mut()?;
+        header.num_bids = num_bids;
+        header.num_asks = num_asks;
+        header.num_traders = num_traders;
+        Ok(())
+    }
+
     /// Increments the market sequence number and then emits the events
+    ///
+    /// If book checkpoints are enabled for the market, a `BookCheckpoint` event is
+    /// appended before the final flush.
     pub(crate) fn increment_market_sequence_number_and_flush(
         &mut self,
         market_info: MarketAccountInfo<'_, 'info>,
     ) -> ProgramResult {
+        if !market_info.data_is_empty() && market_info.get_header()?.book_checkpoints_enabled != 0 {
+            self.add_book_checkpoint(&market_info)?;
+        }
         if let Some(err) = self.error_code {
             // This should never happen because the program should have terminiated in `self.add_event`
             phoenix_log!("ERROR: Event recorder failed to record events: {}", err);
@@ -200,6 +279,7 @@ impl<'info> EventRecorder<'info> {
                 "The only instruction that can be used to delete a market is ChangeMarketStatus",
             )?;
         } else {
+            self.update_header_counters(&market_info)?;
             market_info.get_header_mut()?.increment_sequence_number();
         };
         if self.state_tracker.has_events_to_process() {
@@ -249,3 +329,67 @@ impl EventStateTracker {
         self.batch_index == 0 || self.events_emitted < self.events_added
     }
 }
+
+#[test]
+fn test_header_layout() {
+    use borsh::BorshDeserialize;
+
+    let mut data = vec![PhoenixInstruction::Log as u8];
+    PhoenixMarketEvent::Header(AuditLogHeader {
+        schema_version: EVENT_LOG_SCHEMA_VERSION,
+        instruction: PhoenixInstruction::PlaceLimitOrder as u8,
+        sequence_number: 1,
+        timestamp: 2,
+        slot: 3,
+        market: Pubkey::new_unique(),
+        signer: Pubkey::new_unique(),
+        total_events: 0,
+    })
+    .serialize(&mut data)
+    .unwrap();
+    assert_eq!(data.len(), HEADER_LEN);
+
+    // Clients read the schema version right after the event discriminant, before parsing the rest
+    // of the log
+    assert_eq!(data[2], EVENT_LOG_SCHEMA_VERSION);
+
+    // The number of events in the batch is overwritten in place before each log CPI
+    data[(HEADER_LEN - 2)..HEADER_LEN].copy_from_slice(&7_u16.to_le_bytes());
+    match PhoenixMarketEvent::try_from_slice(&data[1..]).unwrap() {
+        PhoenixMarketEvent::Header(header) => {
+            assert_eq!(header.schema_version, EVENT_LOG_SCHEMA_VERSION);
+            assert_eq!(
+                header.instruction,
+                PhoenixInstruction::PlaceLimitOrder as u8
+            );
+            assert_eq!(header.total_events, 7);
+        }
+        _ => panic!("Expected header event"),
+    }
+}
+
+#[test]
+fn test_evict_event_serialization() {
+    use crate::{quantities::WrapperU64, state::markets::FIFOOrderId};
+    use borsh::BorshDeserialize;
+
+    let evicting_order_id = FIFOOrderId::new_from_untyped(100, 42);
+    let event = PhoenixMarketEvent::from(MarketEvent::Evict {
+        maker_id: Pubkey::new_unique(),
+        order_sequence_number: 7,
+        price_in_ticks: Ticks::new(99),
+        base_lots_evicted: BaseLots::new(10),
+        evicting_order_id,
+    });
+    let data = event.try_to_vec().unwrap();
+    // The scratch buffer is sized for the largest event
+    assert!(data.len() <= MAX_EVENT_SIZE);
+    match PhoenixMarketEvent::try_from_slice(&data).unwrap() {
+        PhoenixMarketEvent::Evict(evict_event) => {
+            assert_eq!(evict_event.order_sequence_number, 7);
+            assert_eq!(evict_event.base_lots_evicted, 10);
+            assert_eq!(evict_event.evicting_order_id, evicting_order_id);
+        }
+        _ => panic!("Expected evict event"),
+    }
+}
@@ -1,9 +1,19 @@
-use crate::state::markets::MarketEvent;
+use crate::state::{
+    markets::{FIFOOrderId, MarketEvent},
+    Side,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
+/// The version of the event log layout written by the program. Clients should read the
+/// `schema_version` of each `AuditLogHeader` to select a parser.
+///
+/// This must be incremented whenever an event is added or the layout of an existing event changes.
+pub const EVENT_LOG_SCHEMA_VERSION: u8 = 2;
+
 #[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
 pub struct AuditLogHeader {
+    pub schema_version: u8,
     pub instruction: u8,
     pub sequence_number: u64,
     pub timestamp: i64,
@@ -48,11 +58,14 @@ pub struct EvictEvent {
     pub order_sequence_number: u64,
     pub price_in_ticks: u64,
     pub base_lots_evicted: u64,
+    /// Id of the incoming order that caused the eviction
+    pub evicting_order_id: FIFOOrderId,
 }
 
 #[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
 pub struct FillSummaryEvent {
     pub index: u16,
+    pub taker_id: Pubkey,
     pub client_order_id: u128,
     pub total_base_lots_filled: u64,
     pub total_quote_lots_filled: u64,
@@ -82,6 +95,88 @@ pub struct ExpiredOrderEvent {
     pub base_lots_removed: u64,
 }
 
+#[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
+pub struct RebateEvent {
+    pub index: u16,
+    pub maker_id: Pubkey,
+    pub rebate_in_quote_lots: u64,
+}
+
+/// The price and size fields of an empty side of the book are set to 0
+#[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
+pub struct BookCheckpointEvent {
+    pub index: u16,
+    pub best_bid_in_ticks: u64,
+    pub best_bid_base_lots: u64,
+    pub best_ask_in_ticks: u64,
+    pub best_ask_base_lots: u64,
+}
+
+#[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
+pub struct OrderCompleteEvent {
+    pub index: u16,
+    pub maker_id: Pubkey,
+    pub order_sequence_number: u64,
+}
+
+#[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
+pub struct StopTriggeredEvent {
+    pub index: u16,
+    pub trader_id: Pubkey,
+    pub stop_sequence_number: u64,
+    pub trigger_price_in_ticks: u64,
+}
+
+#[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
+pub struct StopPlacedEvent {
+    pub index: u16,
+    pub trader_id: Pubkey,
+    pub stop_sequence_number: u64,
+    pub side: Side,
+    pub trigger_price_in_ticks: u64,
+    pub limit_price_in_ticks: u64,
+    pub num_base_lots: u64,
+    pub client_order_id: u128,
+}
+
+/// Set as the return data of swap instructions, so that programs that invoke a swap through CPI
+/// can read its result with `get_return_data` instead of parsing the event log. The lots that the
+/// order posted to the book and the fills of any triggered stop orders are not included. For swaps
+/// that only use deposited funds, the lots out are credited to the trader's deposited funds.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct SwapSummary {
+    pub base_lots_in: u64,
+    pub base_lots_out: u64,
+    /// Includes the taker fee for buys
+    pub quote_lots_in: u64,
+    /// Excludes the taker fee for sells
+    pub quote_lots_out: u64,
+    pub fee_in_quote_lots: u64,
+}
+
+/// Set as the return data of `SwapRoute`. Each summary is denominated in the lots of its market.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct SwapRouteSummary {
+    pub first_swap: SwapSummary,
+    pub second_swap: SwapSummary,
+}
+
+/// Flags of the event types that can be disabled with `MarketHeader::disabled_event_types`. The
+/// flag of each event type is one shifted by the index of its variant in `PhoenixMarketEvent`.
+pub const FILL_EVENT_FLAG: u64 = 1 << 2;
+pub const PLACE_EVENT_FLAG: u64 = 1 << 3;
+pub const REDUCE_EVENT_FLAG: u64 = 1 << 4;
+pub const EVICT_EVENT_FLAG: u64 = 1 << 5;
+pub const FILL_SUMMARY_EVENT_FLAG: u64 = 1 << 6;
+pub const FEE_EVENT_FLAG: u64 = 1 << 7;
+pub const TIME_IN_FORCE_EVENT_FLAG: u64 = 1 << 8;
+pub const EXPIRED_ORDER_EVENT_FLAG: u64 = 1 << 9;
+pub const REBATE_EVENT_FLAG: u64 = 1 << 10;
+pub const BOOK_CHECKPOINT_EVENT_FLAG: u64 = 1 << 11;
+pub const ORDER_COMPLETE_EVENT_FLAG: u64 = 1 << 12;
+pub const STOP_TRIGGERED_EVENT_FLAG: u64 = 1 << 13;
+pub const STOP_PLACED_EVENT_FLAG: u64 = 1 << 14;
+
 #[derive(Debug, Copy, Clone, BorshDeserialize, BorshSerialize)]
 pub enum PhoenixMarketEvent {
     Uninitialized,
@@ -94,6 +189,11 @@ pub enum PhoenixMarketEvent {
     Fee(FeeEvent),
     TimeInForce(TimeInForceEvent),
     ExpiredOrder(ExpiredOrderEvent),
+    Rebate(RebateEvent),
+    BookCheckpoint(BookCheckpointEvent),
+    OrderComplete(OrderCompleteEvent),
+    StopTriggered(StopTriggeredEvent),
+    StopPlaced(StopPlacedEvent),
 }
 
 impl Default for PhoenixMarketEvent {
@@ -113,9 +213,35 @@ impl PhoenixMarketEvent {
             Self::Fee(FeeEvent { index, .. }) => *index = i,
             Self::TimeInForce(TimeInForceEvent { index, .. }) => *index = i,
             Self::ExpiredOrder(ExpiredOrderEvent { index, .. }) => *index = i,
+            Self::Rebate(RebateEvent { index, .. }) => *index = i,
+            Self::BookCheckpoint(BookCheckpointEvent { index, .. }) => *index = i,
+            Self::OrderComplete(OrderCompleteEvent { index, .. }) => *index = i,
+            Self::StopTriggered(StopTriggeredEvent { index, .. }) => *index = i,
+            Self::StopPlaced(StopPlacedEvent { index, .. }) => *index = i,
             _ => panic!("Cannot set index on uninitialized or header event"),
         }
     }
+
+    /// Returns the flag of the event type in `MarketHeader::disabled_event_types`. Uninitialized
+    /// and header events cannot be disabled, so their flag is zero.
+    pub fn event_type_flag(&self) -> u64 {
+        match self {
+            Self::Fill(_) => FILL_EVENT_FLAG,
+            Self::Place(_) => PLACE_EVENT_FLAG,
+            Self::Reduce(_) => REDUCE_EVENT_FLAG,
+            Self::Evict(_) => EVICT_EVENT_FLAG,
+            Self::FillSummary(_) => FILL_SUMMARY_EVENT_FLAG,
+            Self::Fee(_) => FEE_EVENT_FLAG,
+            Self::TimeInForce(_) => TIME_IN_FORCE_EVENT_FLAG,
+            Self::ExpiredOrder(_) => EXPIRED_ORDER_EVENT_FLAG,
+            Self::Rebate(_) => REBATE_EVENT_FLAG,
+            Self::BookCheckpoint(_) => BOOK_CHECKPOINT_EVENT_FLAG,
+            Self::OrderComplete(_) => ORDER_COMPLETE_EVENT_FLAG,
+            Self::StopTriggered(_) => STOP_TRIGGERED_EVENT_FLAG,
+            Self::StopPlaced(_) => STOP_PLACED_EVENT_FLAG,
+            Self::Uninitialized | Self::Header(_) => 0,
+        }
+    }
 }
 
 impl From<MarketEvent<Pubkey>> for PhoenixMarketEvent {
@@ -164,19 +290,23 @@ impl From<MarketEvent<Pubkey>> for PhoenixMarketEvent {
                 order_sequence_number,
                 price_in_ticks,
                 base_lots_evicted,
+                evicting_order_id,
             } => Self::Evict(EvictEvent {
                 maker_id,
                 order_sequence_number,
                 price_in_ticks: price_in_ticks.into(),
                 base_lots_evicted: base_lots_evicted.into(),
+                evicting_order_id,
                 index: 0,
             }),
             MarketEvent::<Pubkey>::FillSummary {
+                taker_id,
                 client_order_id,
                 total_base_lots_filled,
                 total_quote_lots_filled,
                 total_fee_in_quote_lots,
             } => Self::FillSummary(FillSummaryEvent {
+                taker_id,
                 client_order_id,
                 total_base_lots_filled: total_base_lots_filled.into(),
                 total_quote_lots_filled: total_quote_lots_filled.into(),
@@ -211,6 +341,109 @@ impl From<MarketEvent<Pubkey>> for PhoenixMarketEvent {
                 base_lots_removed: base_lots_removed.into(),
                 index: 0,
             }),
+            MarketEvent::<Pubkey>::Rebate {
+                maker_id,
+                rebate_in_quote_lots,
+            } => Self::Rebate(RebateEvent {
+                maker_id,
+                rebate_in_quote_lots: rebate_in_quote_lots.into(),
+                index: 0,
+            }),
+            MarketEvent::<Pubkey>::BookCheckpoint {
+                best_bid_in_ticks,
+                best_bid_base_lots,
+                best_ask_in_ticks,
+                best_ask_base_lots,
+            } => Self::BookCheckpoint(BookCheckpointEvent {
+                best_bid_in_ticks: best_bid_in_ticks.into(),
+                best_bid_base_lots: best_bid_base_lots.into(),
+                best_ask_in_ticks: best_ask_in_ticks.into(),
+                best_ask_base_lots: best_ask_base_lots.into(),
+                index: 0,
+            }),
+            MarketEvent::<Pubkey>::OrderComplete {
+                maker_id,
+                order_sequence_number,
+            } => Self::OrderComplete(OrderCompleteEvent {
+                maker_id,
+                order_sequence_number,
+                index: 0,
+            }),
+            MarketEvent::<Pubkey>::StopTriggered {
+                trader_id,
+                stop_sequence_number,
+                trigger_price_in_ticks,
+            } => Self::StopTriggered(StopTriggeredEvent {
+                trader_id,
+                stop_sequence_number,
+                trigger_price_in_ticks: trigger_price_in_ticks.into(),
+                index: 0,
+            }),
+            MarketEvent::<Pubkey>::StopPlaced {
+                trader_id,
+                stop_sequence_number,
+                side,
+                trigger_price_in_ticks,
+                limit_price_in_ticks,
+                num_base_lots,
+                client_order_id,
+            } => Self::StopPlaced(StopPlacedEvent {
+                trader_id,
+                stop_sequence_number,
+                side,
+                trigger_price_in_ticks: trigger_price_in_ticks.into(),
+                limit_price_in_ticks: limit_price_in_ticks.into(),
+                num_base_lots: num_base_lots.into(),
+                client_order_id,
+                index: 0,
+            }),
         }
     }
 }
+
+#[test]
+fn test_event_type_flags() {
+    let events = [
+        MarketEvent::Fill {
+            maker_id: Pubkey::default(),
+            order_sequence_number: 0,
+            price_in_ticks: Default::default(),
+            base_lots_filled: Default::default(),
+            base_lots_remaining: Default::default(),
+        },
+        MarketEvent::Place {
+            order_sequence_number: 0,
+            client_order_id: 0,
+            price_in_ticks: Default::default(),
+            base_lots_placed: Default::default(),
+        },
+        MarketEvent::FillSummary {
+            taker_id: Pubkey::default(),
+            client_order_id: 0,
+            total_base_lots_filled: Default::default(),
+            total_quote_lots_filled: Default::default(),
+            total_fee_in_quote_lots: Default::default(),
+        },
+        MarketEvent::StopTriggered {
+            trader_id: Pubkey::default(),
+            stop_sequence_number: 0,
+            trigger_price_in_ticks: Default::default(),
+        },
+        MarketEvent::StopPlaced {
+            trader_id: Pubkey::default(),
+            stop_sequence_number: 0,
+            side: Side::Bid,
+            trigger_price_in_ticks: Default::default(),
+            limit_price_in_ticks: Default::default(),
+            num_base_lots: Default::default(),
+            client_order_id: 0,
+        },
+    ];
+    for event in events {
+        // The flag of each event type matches its serialized discriminant
+        let event = PhoenixMarketEvent::from(event);
+        let discriminant = event.try_to_vec().unwrap()[0];
+        assert_eq!(event.event_type_flag(), 1 << discriminant);
+    }
+    assert_eq!(PhoenixMarketEvent::Uninitialized.event_type_flag(), 0);
+}
@@ -0,0 +1,124 @@
+use super::error::PhoenixError;
+use super::recent_fills::{get_recent_fills_offset, get_recent_fills_size};
+use super::{get_market_size, MarketHeader, MarketSizeParams};
+use crate::state::markets::{FIFOOrderId, IcebergReserve};
+use bytemuck::{Pod, Zeroable};
+use sokoban::node_allocator::{NodeAllocatorMap, OrderedNodeAllocatorMap, ZeroCopy};
+use sokoban::RedBlackTree;
+use solana_program::program_error::ProgramError;
+
+/// The maximum number of iceberg orders that can rest on a market at once
+pub const MAX_ICEBERG_ORDERS: usize = 128;
+
+/// Markets that are initialized with iceberg orders enabled store this struct in the market account
+/// after the market, the client order id index, the stop order book, the referral fee book and the
+/// recent fills (if they are enabled).
+///
+/// Only the visible size of an iceberg order rests on the book. The rest of the order is held in
+/// this struct, keyed by the order id of the visible order. When the visible order is filled, the
+/// matching engine replaces it with a new order from the reserve. When the visible order leaves
+/// the book in any other way, the market removes the reserve and returns its funds to the trader's
+/// free funds.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable)]
+pub struct IcebergReserveBook {
+    _padding: [u64; 8],
+    pub reserves: RedBlackTree<FIFOOrderId, IcebergReserve, MAX_ICEBERG_ORDERS>,
+}
+
+unsafe impl Pod for IcebergReserveBook {}
+
+impl ZeroCopy for IcebergReserveBook {}
+
+impl IcebergReserveBook {
+    pub fn len(&self) -> usize {
+        self.reserves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the reserves in the form that the market's methods take them
+    pub fn reserves_mut(
+        &mut self,
+    ) -> &mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve> {
+        &mut self.reserves
+    }
+}
+
+pub fn get_iceberg_reserve_book_size() -> usize {
+    std::mem::size_of::<IcebergReserveBook>()
+}
+
+/// Returns the offset of the iceberg reserve book from the end of the market header
+pub fn get_iceberg_reserve_book_offset(
+    market_size_params: &MarketSizeParams,
+    client_order_id_index_enabled: bool,
+    stop_orders_enabled: bool,
+    referral_fees_enabled: bool,
+    recent_fills_enabled: bool,
+) -> Result<usize, ProgramError> {
+    let mut offset = get_recent_fills_offset(
+        market_size_params,
+        client_order_id_index_enabled,
+        stop_orders_enabled,
+        referral_fees_enabled,
+    )?;
+    if recent_fills_enabled {
+        offset += get_recent_fills_size();
+    }
+    Ok(offset)
+}
+
+/// Returns the offset of the iceberg reserve book from the end of the market header, or None if
+/// iceberg orders are not enabled on the market
+pub fn get_iceberg_reserve_book_offset_from_header(
+    header: &MarketHeader,
+) -> Result<Option<usize>, ProgramError> {
+    if header.iceberg_orders_enabled == 0 {
+        return Ok(None);
+    }
+    get_iceberg_reserve_book_offset(
+        &header.market_size_params,
+        header.client_order_id_index_enabled != 0,
+        header.stop_orders_enabled != 0,
+        header.referral_fees_enabled != 0,
+        header.recent_fills_enabled != 0,
+    )
+    .map(Some)
+}
+
+pub(crate) fn initialize_iceberg_reserve_book(bytes: &mut [u8]) -> Result<(), ProgramError> {
+    load_iceberg_reserve_book_mut(bytes)?.reserves.initialize();
+    Ok(())
+}
+
+pub(crate) fn load_iceberg_reserve_book_mut(
+    bytes: &mut [u8],
+) -> Result<&mut IcebergReserveBook, ProgramError> {
+    IcebergReserveBook::load_mut_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+/// Splits the bytes after the market header into the market and the iceberg reserve book. The
+/// reserve book is None if iceberg orders are not enabled on the market.
+pub(crate) fn split_iceberg_reserve_book_mut<'a>(
+    header: &MarketHeader,
+    bytes: &'a mut [u8],
+) -> Result<(&'a mut [u8], Option<&'a mut IcebergReserveBook>), ProgramError> {
+    let market_size = get_market_size(&header.market_size_params)?;
+    let (market_bytes, trailing_bytes) = bytes.split_at_mut(market_size);
+    let iceberg_reserve_book = match get_iceberg_reserve_book_offset_from_header(header)? {
+        Some(offset) => Some(load_iceberg_reserve_book_mut(
+            &mut trailing_bytes[offset - market_size..],
+        )?),
+        None => None,
+    };
+    Ok((market_bytes, iceberg_reserve_book))
+}
+
+pub fn load_iceberg_reserve_book(bytes: &[u8]) -> Result<&IcebergReserveBook, ProgramError> {
+    IcebergReserveBook::load_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
@@ -129,11 +129,12 @@ pub enum PhoenixInstruction {
     #[account(1, name = "log_authority", desc = "Phoenix log authority")]
     #[account(2, writable, name = "market", desc = "This account holds the market state")]
     #[account(3, signer, name = "trader")]
-    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
-    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(4, writable, name = "base_account", desc = "Destination base token account, which may be owned by any account")]
+    #[account(5, writable, name = "quote_account", desc = "Destination quote token account, which may be owned by any account")]
     #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
     #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
     #[account(8, name = "token_program", desc = "Token program")]
+    #[account(9, optional, writable, name = "seat", desc = "Only required when closing the seat. The trader must also be writable")]
     WithdrawFunds = 12,
 
     #[account(0, name = "phoenix_program", desc = "Phoenix program")]
@@ -182,6 +183,336 @@ pub enum PhoenixInstruction {
     #[account(4, name = "seat")]
     PlaceMultiplePostOnlyOrdersWithFreeFunds = 17,
 
+    /// Amend the size and price of an existing order using only deposited funds.
+    /// The order keeps its queue priority if the price is unchanged and the size does not increase.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    AmendOrder = 18,
+
+    /// Deposit funds and then place a limit order on the book using only deposited funds.
+    /// Funds that are not used by the order remain deposited.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    #[account(5, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(6, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(7, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(8, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(9, name = "token_program", desc = "Token program")]
+    DepositAndPlace = 19,
+
+    /// Remove expired orders from one side of the book. This instruction is permissionless.
+    /// The makers' funds are unlocked and remain deposited in their seats.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "signer", desc = "Any signer can prune expired orders")]
+    PruneExpiredOrders = 20,
+
+    /// Reduce the size of an existing bid on the book by a quote lot amount
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    ReduceOrderByQuote = 21,
+
+    /// Reduce the size of an existing bid on the book by a quote lot amount
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader")]
+    ReduceOrderByQuoteWithFreeFunds = 22,
+
+    /// Cancel all orders with a given client order id. The market must have a client order id index.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    CancelByClientOrderId = 23,
+
+    /// Cancel all orders with a given client order id (no token transfers). The market must have a client order id index.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    CancelByClientOrderIdWithFreeFunds = 24,
+
+    /// Cancel at most `max_orders` of the trader's orders, most recently placed first. Sets the return data to 1 if orders remain, otherwise 0
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    CancelAllOrdersBounded = 25,
+
+    /// Cancel at most `max_orders` of the trader's orders, most recently placed first (no token transfers). Sets the return data to 1 if orders remain, otherwise 0
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    CancelAllOrdersBoundedWithFreeFunds = 26,
+
+    /// Withdraw all free funds from multiple markets. This instruction does not use the standard
+    /// account layout: the trader is followed by one group of accounts per market, consisting of
+    /// the market, base_account, quote_account, base_vault, quote_vault, and token_program
+    /// (followed by the base and quote mints for Token-2022 markets). Markets where the trader has
+    /// no free funds are skipped.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, signer, name = "trader")]
+    WithdrawFromMarkets = 27,
+
+    /// Cancel all of the trader's orders and place multiple post only orders in their place.
+    /// Funds released by the cancels remain in the market and are reused by the new orders, so
+    /// tokens are only deposited if the new orders require more funds than the trader has free.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    #[account(5, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(6, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(7, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(8, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(9, name = "token_program", desc = "Token program")]
+    ReplaceQuotes = 28,
+
+    /// Set a self trade behavior that supersedes the behavior of every order packet sent by the trader.
+    /// Setting it to None restores the behavior specified in each order packet.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    SetSelfTradeBehaviorOverride = 29,
+
+    /// Move free funds from the trader's seat to another trader's seat on the same market.
+    /// No tokens are transferred.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    #[account(5, name = "destination_seat", desc = "The seat of the trader receiving the funds")]
+    InternalTransfer = 30,
+
+    /// Place an order that uses a fraction of the trader's free funds. Bids are sized from free
+    /// quote lots and asks from free base lots when the instruction executes.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    PlaceByFundsFraction = 31,
+
+    /// Cancel all of the trader's orders on one side of the book priced within an inclusive tick range
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    CancelInPriceRange = 32,
+
+    /// Cancel all of the trader's orders on one side of the book priced within an inclusive tick range (no token transfers)
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    CancelInPriceRangeWithFreeFunds = 33,
+
+    /// Revoke the approval of a seat whose trader has not placed an order within the market's
+    /// seat inactivity threshold. The trader can request approval again.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "signer", desc = "Any signer can demote an inactive seat")]
+    #[account(4, writable, name = "seat")]
+    DemoteInactiveSeat = 34,
+
+    /// Swap on two markets atomically, spending the output of the first swap in the second swap.
+    /// This instruction does not use the standard account layout: the trader is followed by two
+    /// groups of accounts, one per market, each consisting of the market, base_account,
+    /// quote_account, base_vault, quote_vault, and token_program (followed by the base and quote
+    /// mints for Token-2022 markets).
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, signer, name = "trader")]
+    SwapRoute = 35,
+
+    /// Place a stop order that is sent as an immediate-or-cancel order when a fill crosses its trigger price.
+    /// The funds of the order are held from the trader's free funds until it is triggered or cancelled.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    PlaceStopOrder = 36,
+
+    /// Cancel a stop order that has not been triggered and return its funds to the trader's free funds
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    CancelStopOrder = 37,
+
+    /// Reduce a resting order and send the freed funds as an immediate-or-cancel order on the same side, using only deposited funds
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    CancelAndSwap = 38,
+
+    /// Deposit SOL into a market whose quote mint is a native mint. The trader's temporary wrapped SOL account is created, funded with the lamports to deposit, synced, deposited, and closed back to the trader
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    #[account(5, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(6, writable, name = "native_sol_account", desc = "Temporary wrapped SOL account PDA, seeds are [b'native_sol', market_address, trader_address]. It must not exist and is closed after the deposit")]
+    #[account(7, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(8, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(9, name = "token_program", desc = "Token program of the native mint")]
+    #[account(10, name = "system_program", desc = "System program")]
+    #[account(11, name = "native_mint", desc = "Native mint of the token program")]
+    DepositFundsWithNativeSol = 39,
+
+    /// Withdraw funds from a market whose quote mint is a native mint. The quote tokens are withdrawn to the trader's temporary wrapped SOL account, which is closed back to the trader as SOL. The system program and the native mint are always the last accounts
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Destination base token account, which may be owned by any account")]
+    #[account(5, writable, name = "native_sol_account", desc = "Temporary wrapped SOL account PDA, seeds are [b'native_sol', market_address, trader_address]. It must not exist and is closed after the withdrawal")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program of the native mint")]
+    #[account(9, optional, writable, name = "seat", desc = "Only required when closing the seat")]
+    #[account(10, name = "system_program", desc = "System program")]
+    #[account(11, name = "native_mint", desc = "Native mint of the token program")]
+    WithdrawFundsToNativeSol = 40,
+
+    /// Send a swap on a market whose quote mint is a native mint, paying or receiving SOL. The trader's temporary wrapped SOL account is created before the swap, buys wrap enough lamports to pay for the order, and the account is closed back to the trader after the swap
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "native_sol_account", desc = "Temporary wrapped SOL account PDA, seeds are [b'native_sol', market_address, trader_address]. It must not exist and is closed after the swap")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program of the native mint")]
+    #[account(9, name = "system_program", desc = "System program")]
+    #[account(10, name = "native_mint", desc = "Native mint of the token program")]
+    SwapWithNativeSol = 41,
+
+    /// Create the trader's seat and register the trader on the market without approving the seat. Succeeds without changes if the seat already exists
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader", desc = "The trader pays the rent of the seat")]
+    #[account(4, writable, name = "seat")]
+    #[account(5, name = "system_program", desc = "System program")]
+    CreateSeat = 42,
+
+    /// Move an existing order to a new price without changing its size, using only deposited funds.
+    /// The order is re-queued with a new sequence number
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    RepriceOrder = 43,
+
+    /// Withdraw the referral fees accrued to the signer
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "referrer", desc = "The referrer that claims their referral fees")]
+    #[account(4, writable, name = "referrer_quote_account", desc = "Referrer quote token account")]
+    #[account(5, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(6, name = "token_program", desc = "Token program")]
+    ClaimReferralFees = 44,
+
+    /// Cancel all orders, withdraw all funds and close the trader's seat. Fails if any funds are
+    /// still locked after the orders are cancelled
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, writable, signer, name = "trader", desc = "The trader receives the lamports of the closed seat")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    #[account(9, writable, name = "seat")]
+    CancelAllAndCloseSeat = 45,
+
+    /// Fail if the best price on one side of the book is outside of the given bounds. The market
+    /// is not modified
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "market", desc = "This account holds the market state")]
+    AssertMarketPrice = 46,
+
+    /// Deposit token atoms into a market. The amounts are rounded down to whole lots, and the
+    /// remainder stays in the trader's token accounts
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, name = "seat")]
+    #[account(5, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(6, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(7, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(8, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(9, name = "token_program", desc = "Token program")]
+    DepositAtoms = 47,
+
+    /// Reduce the size of multiple existing orders on the book. Orders that do not belong to the
+    /// trader are skipped
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    #[account(4, writable, name = "base_account", desc = "Trader base token account")]
+    #[account(5, writable, name = "quote_account", desc = "Trader quote token account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "token_program", desc = "Token program")]
+    ReduceMultipleOrders = 48,
+
+    /// Reduce the size of multiple existing orders on the book (no token transfers). Orders that
+    /// do not belong to the trader are skipped
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "trader")]
+    ReduceMultipleOrdersWithFreeFunds = 49,
+
 
     // Admin instructions
     /// Create a market 
@@ -273,6 +604,227 @@ pub enum PhoenixInstruction {
     #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the free recipient")]
     #[account(4, name = "new_fee_recipient", desc = "New fee recipient")]
     ChangeFeeRecipient = 109,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the maker rebate")]
+    ChangeMakerRebate = 110,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the book checkpoint setting")]
+    ChangeBookCheckpoints = 111,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the minimum order size")]
+    ChangeMinBaseLotsPerOrder = 112,
+
+    /// Create a market at the PDA with seeds [b'market', base_mint_address, quote_mint_address, nonce]
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "Market PDA, seeds are [b'market', base_mint_address, quote_mint_address, nonce]")]
+    #[account(3, writable, signer, name = "market_creator", desc = "The market_creator account must sign to pay for the creation of the market and vaults")]
+    #[account(4, name = "base_mint", desc = "Base mint account")]
+    #[account(5, name = "quote_mint", desc = "Quote mint account")]
+    #[account(6, writable, name = "base_vault", desc = "Base vault PDA, seeds are [b'vault', market_address, base_mint_address]")]
+    #[account(7, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(8, name = "system_program", desc = "System program")]
+    #[account(9, name = "token_program", desc = "Token program")]
+    InitializeMarketPda = 113,
+
+    /// Move a paused market into a larger memory layout. This instruction must be sent repeatedly
+    /// until the market account has grown enough to complete the resize.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to resize the market")]
+    #[account(4, writable, signer, name = "payer", desc = "Pays for the rent of the additional market space")]
+    #[account(5, name = "system_program", desc = "System program")]
+    ResizeMarket = 114,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the fee tiers")]
+    ChangeFeeTiers = 115,
+
+    /// Assign a fee tier to a trader that is registered on the market
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change a trader's fee tier")]
+    SetTraderFeeTier = 116,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the price accumulator setting")]
+    ChangePriceAccumulator = 117,
+
+    /// Cancel all orders of a trader without transferring tokens. The released funds are credited
+    /// to the trader's free lots, so this works even if a vault or token account is frozen.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to force cancel orders")]
+    #[account(4, name = "trader")]
+    #[account(5, name = "seat", desc = "The trader's PDA seat account, seeds are [b'seat', market_address, trader_address]")]
+    ForceCancelOrdersWithFreeFunds = 118,
+
+    /// Change the tick size of the market. Resting orders are moved to the nearest valid tick,
+    /// rounding bids down and asks up.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the tick size")]
+    ChangeTickSize = 119,
+
+    /// Disable new orders for a range of slots. Cancels and withdrawals are still allowed.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to set the trading window")]
+    SetTradingWindow = 120,
+
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the trader volume tracking setting")]
+    ChangeTraderVolumeTracking = 121,
+
+    /// Collect the unclaimed fees and divide them between multiple recipients by bps.
+    /// The recipients' quote token accounts are passed in the same order as the fee splits.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to split fees")]
+    #[account(4, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(5, name = "token_program", desc = "Token program")]
+    CollectFeesSplit = 122,
+
+    /// Set the maximum number of slots that a resting order may live for. Zero removes the limit.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the maximum order lifetime")]
+    ChangeMaxOrderLifetimeSlots = 123,
+
+    /// Create and approve seats for multiple traders. The seat accounts are passed in the same
+    /// order as the traders. Traders that already have an approved seat are skipped.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to request seats on behalf of traders")]
+    #[account(4, writable, signer, name = "payer")]
+    #[account(5, name = "system_program", desc = "System program")]
+    RequestSeatBatch = 124,
+
+    /// Change how taker fees are rounded to a whole number of quote lots.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the fee rounding mode")]
+    ChangeFeeRoundingMode = 125,
+
+    /// Set the minimum fee, in quote lots, charged on trades that match any base lots.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the minimum fee")]
+    ChangeMinFee = 126,
+
+    /// Set the number of slots a trader can go without placing an order before their seat can be demoted.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the seat inactivity threshold")]
+    ChangeSeatInactivityThreshold = 127,
+
+    /// Set the maximum number of resting orders that an order can match against. Zero removes the limit.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the maximum match limit")]
+    ChangeMaxMatchLimit = 128,
+
+    /// Transfer any quote tokens in the vault beyond the market's tracked obligations (trader funds
+    /// and unclaimed fees) to the fee recipient.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to sweep dust")]
+    #[account(4, writable, name = "fee_recipient", desc = "Fee recipient quote token account")]
+    #[account(5, writable, name = "quote_vault", desc = "Quote vault PDA, seeds are [b'vault', market_address, quote_mint_address]")]
+    #[account(6, name = "token_program", desc = "Token program")]
+    SweepDust = 129,
+
+    /// Set the maximum number of orders that a trader can have resting on each side of the book. Zero removes the limit.
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the maximum open orders per trader")]
+    ChangeMaxOrdersPerSidePerTrader = 130,
+
+    /// Change the base and quote symbols stored in the market header
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the market symbols")]
+    ChangeMarketSymbols = 131,
+
+    /// Configure the volatility band, which rejects aggressively priced orders for a number of slots after a large fill
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the volatility band")]
+    SetVolatilityBand = 132,
+
+    /// Allow or disallow orders that take liquidity
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change whether taking is disabled")]
+    ChangeTakingDisabled = 133,
+
+    /// Choose which event types are left out of the event log
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the disabled event types")]
+    ChangeDisabledEventTypes = 134,
+
+    /// Set the share of the taker fee that is accrued to the referrer named by an order
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the referral fee")]
+    ChangeReferralFee = 135,
+
+    /// Set the minimum number of base lots that an immediate-or-cancel or fill-or-kill order must
+    /// match. Orders that match less are voided
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to change the minimum taker fill")]
+    ChangeMinTakerFill = 136,
+
+    /// Register a referrer, which allows orders that name it to accrue a share of the taker fee
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to register a referrer")]
+    RegisterReferrer = 137,
+
+    /// Deregister a referrer. Its unclaimed referral fees are forfeited to the market
+    #[account(0, name = "phoenix_program", desc = "Phoenix program")]
+    #[account(1, name = "log_authority", desc = "Phoenix log authority")]
+    #[account(2, writable, name = "market", desc = "This account holds the market state")]
+    #[account(3, signer, name = "market_authority", desc = "The market_authority account must sign to deregister a referrer")]
+    DeregisterReferrer = 138,
 }
 
 impl PhoenixInstruction {
@@ -283,13 +835,13 @@ impl PhoenixInstruction {
 
 #[test]
 fn test_instruction_serialization() {
-    for i in 0..=108 {
+    for i in 0..=138 {
         let instruction = match PhoenixInstruction::try_from(i) {
             Ok(j) => j,
             Err(_) => {
                 assert!(i < 100);
                 // This needs to be changed if new instructions are added
-                assert!(i > 17);
+                assert!(i > 49);
                 continue;
             }
         };
@@ -1,9 +1,12 @@
 use crate::phoenix_log_authority;
-use crate::program::status::{MarketStatus, SeatApprovalStatus};
-use crate::program::{
-    get_market_size, processor::*, MarketHeader, MarketSizeParams, PhoenixInstruction,
+use crate::program::processor::fees::{CollectFeesSplitParams, FeeSplit, SetTraderFeeTierParams};
+use crate::program::processor::governance::{
+    ChangeMarketSymbolsParams, SetTradingWindowParams, SetVolatilityBandParams,
 };
-use crate::state::Side;
+use crate::program::processor::manage_seat::{RequestSeatBatchParams, MAX_TRADERS_PER_SEAT_BATCH};
+use crate::program::status::{MarketStatus, SeatApprovalStatus};
+use crate::program::{processor::*, MarketSizeParams, PhoenixInstruction, MARKET_SYMBOL_LEN};
+use crate::state::{markets::NUM_FEE_TIERS, FeeRoundingMode, Side};
 use borsh::BorshSerialize;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -12,26 +15,22 @@ use solana_program::{
     rent::Rent,
     system_instruction, system_program,
 };
-use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
 
-use crate::program::loaders::get_vault_address;
+use crate::program::loaders::{get_market_address, get_vault_address};
 use crate::program::validation::loaders::get_seat_address;
 
-#[allow(clippy::too_many_arguments)]
 pub fn create_initialize_market_instructions(
     market: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
     market_creator: &Pubkey,
-    header_params: MarketSizeParams,
-    num_quote_lots_per_quote_unit: u64,
-    num_base_lots_per_base_unit: u64,
-    tick_size_in_quote_lots_per_base_unit: u64,
-    taker_fee_bps: u16,
-    fee_collector: &Pubkey,
-    raw_base_units_per_base_unit: Option<u32>,
+    initialize_params: InitializeParams,
 ) -> Result<Vec<Instruction>, ProgramError> {
-    let space = std::mem::size_of::<MarketHeader>() + get_market_size(&header_params)?;
+    let space = initialize_params.get_market_account_size()?;
     Ok(vec![
         system_instruction::create_account(
             market_creator,
@@ -44,14 +43,9 @@ pub fn create_initialize_market_instructions(
             market,
             base,
             quote,
+            token_program,
             market_creator,
-            header_params,
-            num_quote_lots_per_quote_unit,
-            num_base_lots_per_base_unit,
-            tick_size_in_quote_lots_per_base_unit,
-            taker_fee_bps,
-            fee_collector,
-            raw_base_units_per_base_unit,
+            initialize_params,
         ),
     ])
 }
@@ -69,44 +63,32 @@ pub fn create_initialize_market_instructions_default(
     taker_fee_bps: u16,
     raw_base_units_per_base_unit: Option<u32>,
 ) -> Result<Vec<Instruction>, ProgramError> {
-    let space = std::mem::size_of::<MarketHeader>() + get_market_size(&header_params)?;
-    Ok(vec![
-        system_instruction::create_account(
-            market_creator,
-            market,
-            Rent::default().minimum_balance(space),
-            space as u64,
-            &crate::id(),
-        ),
-        create_initialize_market_instruction(
-            market,
-            base,
-            quote,
-            market_creator,
-            header_params,
+    create_initialize_market_instructions(
+        market,
+        base,
+        quote,
+        &spl_token::id(),
+        market_creator,
+        InitializeParams {
+            market_size_params: header_params,
             num_quote_lots_per_quote_unit,
             num_base_lots_per_base_unit,
             tick_size_in_quote_lots_per_base_unit,
             taker_fee_bps,
-            market_creator,
+            fee_collector: *market_creator,
             raw_base_units_per_base_unit,
-        ),
-    ])
+            ..Default::default()
+        },
+    )
 }
 
-#[allow(clippy::too_many_arguments)]
 pub fn create_initialize_market_instruction(
     market: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
     market_creator: &Pubkey,
-    header_params: MarketSizeParams,
-    num_quote_lots_per_quote_unit: u64,
-    num_base_lots_per_base_unit: u64,
-    tick_size_in_quote_lots_per_base_unit: u64,
-    taker_fee_bps: u16,
-    fee_collector: &Pubkey,
-    raw_base_units_per_base_unit: Option<u32>,
+    initialize_params: InitializeParams,
 ) -> Instruction {
     let (base_vault, _) = get_vault_address(market, base);
     let (quote_vault, _) = get_vault_address(market, quote);
@@ -122,18 +104,78 @@ pub fn create_initialize_market_instruction(
             AccountMeta::new(base_vault, false),
             AccountMeta::new(quote_vault, false),
             AccountMeta::new_readonly(system_program::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
         ],
         data: [
             PhoenixInstruction::InitializeMarket.to_vec(),
-            InitializeParams {
-                market_size_params: header_params,
-                num_quote_lots_per_quote_unit,
-                num_base_lots_per_base_unit,
-                tick_size_in_quote_lots_per_base_unit,
-                taker_fee_bps,
-                fee_collector: *fee_collector,
-                raw_base_units_per_base_unit,
+            initialize_params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Creates the instructions that allocate and initialize a market at the PDA returned by
+/// `get_market_address`. The market account is created by the program, so no separate
+/// `create_account` instruction is needed.
+///
+/// The runtime limits how much an account can grow in a single instruction, so the account is
+/// allocated over `get_num_initialize_market_pda_instructions` identical instructions, and the last
+/// one initializes the market. The instructions must be sent in order by the same market creator,
+/// and can be split across several transactions.
+pub fn create_initialize_market_pda_instructions(
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    market_creator: &Pubkey,
+    nonce: u64,
+    initialize_params: InitializeParams,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let num_instructions =
+        get_num_initialize_market_pda_instructions(initialize_params.get_market_account_size()?);
+    let ix = create_initialize_market_pda_instruction(
+        base,
+        quote,
+        token_program,
+        market_creator,
+        nonce,
+        initialize_params,
+    );
+    Ok(vec![ix; num_instructions])
+}
+
+/// Creates a single instruction that initializes a market at the PDA returned by
+/// `get_market_address`. This is only enough for markets whose account fits in a single
+/// allocation; use `create_initialize_market_pda_instructions` for larger markets.
+pub fn create_initialize_market_pda_instruction(
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    market_creator: &Pubkey,
+    nonce: u64,
+    initialize_params: InitializeParams,
+) -> Instruction {
+    let (market, _) = get_market_address(base, quote, nonce);
+    let (base_vault, _) = get_vault_address(&market, base);
+    let (quote_vault, _) = get_vault_address(&market, quote);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(market, false),
+            AccountMeta::new(*market_creator, true),
+            AccountMeta::new_readonly(*base, false),
+            AccountMeta::new_readonly(*quote, false),
+            AccountMeta::new(base_vault, false),
+            AccountMeta::new(quote_vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data: [
+            PhoenixInstruction::InitializeMarketPda.to_vec(),
+            InitializeMarketPdaParams {
+                nonce,
+                initialize_params,
             }
             .try_to_vec()
             .unwrap(),
@@ -148,27 +190,31 @@ pub fn create_evict_seat_instruction(
     trader: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
 ) -> Instruction {
-    let base_account = get_associated_token_address(trader, base);
-    let quote_account = get_associated_token_address(trader, quote);
+    let base_account = get_associated_token_address_with_program_id(trader, base, token_program);
+    let quote_account = get_associated_token_address_with_program_id(trader, quote, token_program);
     let (seat, _) = get_seat_address(market, trader);
     let (base_vault, _) = get_vault_address(market, base);
     let (quote_vault, _) = get_vault_address(market, quote);
     Instruction {
         program_id: crate::id(),
-        accounts: vec![
-            AccountMeta::new_readonly(crate::id(), false),
-            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
-            AccountMeta::new(*market, false),
-            AccountMeta::new_readonly(*authority, true),
-            AccountMeta::new_readonly(*trader, false),
-            AccountMeta::new_readonly(seat, false),
-            AccountMeta::new(base_account, false),
-            AccountMeta::new(quote_account, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new_readonly(*trader, false),
+                AccountMeta::new_readonly(seat, false),
+                AccountMeta::new(base_account, false),
+                AccountMeta::new(quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
         data: PhoenixInstruction::EvictSeat.to_vec(),
     }
 }
@@ -251,6 +297,47 @@ pub fn create_request_seat_authorized_instruction(
     }
 }
 
+/// Creates one `RequestSeatBatch` instruction for every `MAX_TRADERS_PER_SEAT_BATCH` traders. The
+/// instructions create and approve a seat for each trader.
+pub fn create_request_seat_batch_instructions(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    market: &Pubkey,
+    traders: &[Pubkey],
+) -> Vec<Instruction> {
+    traders
+        .chunks(MAX_TRADERS_PER_SEAT_BATCH)
+        .map(|traders| {
+            let mut accounts = vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ];
+            accounts.extend(
+                traders
+                    .iter()
+                    .map(|trader| AccountMeta::new(get_seat_address(market, trader).0, false)),
+            );
+            Instruction {
+                program_id: crate::id(),
+                accounts,
+                data: [
+                    PhoenixInstruction::RequestSeatBatch.to_vec(),
+                    RequestSeatBatchParams {
+                        traders: traders.to_vec(),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                ]
+                .concat(),
+            }
+        })
+        .collect()
+}
+
 pub fn create_change_seat_status_instruction(
     authority: &Pubkey,
     market: &Pubkey,
@@ -282,7 +369,13 @@ pub fn create_collect_fees_instruction_default(
     quote_mint: &Pubkey,
 ) -> Instruction {
     let quote_account = get_associated_token_address(fee_collector, quote_mint);
-    create_collect_fees_instruction(market, sweeper, &quote_account, quote_mint)
+    create_collect_fees_instruction(
+        market,
+        sweeper,
+        &quote_account,
+        quote_mint,
+        &spl_token::id(),
+    )
 }
 
 pub fn create_collect_fees_instruction(
@@ -290,23 +383,99 @@ pub fn create_collect_fees_instruction(
     sweeper: &Pubkey,
     quote_account: &Pubkey,
     quote_mint: &Pubkey,
+    token_program: &Pubkey,
 ) -> Instruction {
     let (quote_vault, _) = get_vault_address(market, quote_mint);
     Instruction {
         program_id: crate::id(),
-        accounts: vec![
-            AccountMeta::new_readonly(crate::id(), false),
-            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
-            AccountMeta::new(*market, false),
-            AccountMeta::new_readonly(*sweeper, true),
-            AccountMeta::new(*quote_account, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*sweeper, true),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[quote_mint]),
+        ]
+        .concat(),
         data: PhoenixInstruction::CollectFees.to_vec(),
     }
 }
 
+pub fn create_sweep_dust_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    fee_recipient: &Pubkey,
+    quote_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let quote_account =
+        get_associated_token_address_with_program_id(fee_recipient, quote_mint, token_program);
+    let (quote_vault, _) = get_vault_address(market, quote_mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(quote_account, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[quote_mint]),
+        ]
+        .concat(),
+        data: PhoenixInstruction::SweepDust.to_vec(),
+    }
+}
+
+pub fn create_collect_fees_split_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    quote_mint: &Pubkey,
+    token_program: &Pubkey,
+    splits: &[FeeSplit],
+) -> Instruction {
+    let (quote_vault, _) = get_vault_address(market, quote_mint);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(crate::id(), false),
+        AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(quote_vault, false),
+    ];
+    accounts.extend(get_token_program_account_metas(
+        token_program,
+        &[quote_mint],
+    ));
+    for split in splits.iter() {
+        accounts.push(AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                &split.recipient,
+                quote_mint,
+                token_program,
+            ),
+            false,
+        ));
+    }
+    Instruction {
+        program_id: crate::id(),
+        accounts,
+        data: [
+            PhoenixInstruction::CollectFeesSplit.to_vec(),
+            CollectFeesSplitParams {
+                splits: splits.to_vec(),
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
 pub fn create_change_fee_recipient_instruction(
     authority: &Pubkey,
     market: &Pubkey,
@@ -345,71 +514,666 @@ pub fn create_change_fee_recipient_with_unclaimed_fees_instruction(
     }
 }
 
-pub fn create_force_cancel_orders_instructions(
+pub fn create_change_maker_rebate_instruction(
+    authority: &Pubkey,
     market: &Pubkey,
-    trader: &Pubkey,
-    market_authority: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-) -> Vec<Instruction> {
-    vec![
-        create_force_cancel_orders_instruction(
-            market,
-            trader,
-            market_authority,
-            base,
-            quote,
-            Side::Bid,
-        ),
-        create_force_cancel_orders_instruction(
-            market,
-            trader,
-            market_authority,
-            base,
-            quote,
-            Side::Ask,
-        ),
-    ]
+    maker_rebate_bps: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMakerRebate.to_vec(),
+            maker_rebate_bps.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
 }
 
-fn create_force_cancel_orders_instruction(
+pub fn create_change_book_checkpoints_instruction(
+    authority: &Pubkey,
     market: &Pubkey,
-    trader: &Pubkey,
-    market_authority: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    side: Side,
+    book_checkpoints_enabled: bool,
 ) -> Instruction {
-    let (base_vault, _) = get_vault_address(market, base);
-    let (quote_vault, _) = get_vault_address(market, quote);
-    let base_account = get_associated_token_address(trader, base);
-    let quote_account = get_associated_token_address(trader, quote);
-    let (seat, _) = get_seat_address(market, trader);
     Instruction {
         program_id: crate::id(),
         accounts: vec![
             AccountMeta::new_readonly(crate::id(), false),
             AccountMeta::new_readonly(phoenix_log_authority::id(), false),
             AccountMeta::new(*market, false),
-            AccountMeta::new_readonly(*market_authority, true),
-            AccountMeta::new_readonly(*trader, false),
-            AccountMeta::new_readonly(seat, false),
-            AccountMeta::new(base_account, false),
-            AccountMeta::new(quote_account, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*authority, true),
         ],
         data: [
-            PhoenixInstruction::ForceCancelOrders.to_vec(),
-            CancelUpToParams {
-                side,
-                tick_limit: None,
-                num_orders_to_cancel: None,
-                num_orders_to_search: None,
-            }
-            .try_to_vec()
-            .unwrap(),
+            PhoenixInstruction::ChangeBookCheckpoints.to_vec(),
+            book_checkpoints_enabled.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_price_accumulator_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    price_accumulator_enabled: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangePriceAccumulator.to_vec(),
+            price_accumulator_enabled.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_trader_volume_tracking_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    trader_volume_tracking_enabled: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeTraderVolumeTracking.to_vec(),
+            trader_volume_tracking_enabled.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_max_order_lifetime_slots_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    max_order_lifetime_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMaxOrderLifetimeSlots.to_vec(),
+            max_order_lifetime_slots.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_seat_inactivity_threshold_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    seat_inactivity_threshold_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeSeatInactivityThreshold.to_vec(),
+            seat_inactivity_threshold_slots.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_max_match_limit_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    max_match_limit: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMaxMatchLimit.to_vec(),
+            max_match_limit.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_max_orders_per_side_per_trader_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    max_orders_per_side_per_trader: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMaxOrdersPerSidePerTrader.to_vec(),
+            max_orders_per_side_per_trader.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Symbols can be encoded with `encode_market_symbol`
+pub fn create_change_market_symbols_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    base_symbol: [u8; MARKET_SYMBOL_LEN],
+    quote_symbol: [u8; MARKET_SYMBOL_LEN],
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMarketSymbols.to_vec(),
+            ChangeMarketSymbolsParams {
+                base_symbol,
+                quote_symbol,
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_min_base_lots_per_order_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    min_base_lots_per_order: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMinBaseLotsPerOrder.to_vec(),
+            min_base_lots_per_order.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_tick_size_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    tick_size_in_quote_lots_per_base_unit: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeTickSize.to_vec(),
+            tick_size_in_quote_lots_per_base_unit.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_set_trading_window_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    trading_disabled_from_slot: u64,
+    trading_disabled_until_slot: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::SetTradingWindow.to_vec(),
+            SetTradingWindowParams {
+                trading_disabled_from_slot,
+                trading_disabled_until_slot,
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_fee_rounding_mode_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    fee_rounding_mode: FeeRoundingMode,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeFeeRoundingMode.to_vec(),
+            fee_rounding_mode.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_min_fee_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    min_fee_quote_lots: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMinFee.to_vec(),
+            min_fee_quote_lots.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_fee_tiers_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    fee_tiers_bps: [u64; NUM_FEE_TIERS],
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeFeeTiers.to_vec(),
+            fee_tiers_bps.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_set_trader_fee_tier_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    trader: &Pubkey,
+    fee_tier: u8,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::SetTraderFeeTier.to_vec(),
+            SetTraderFeeTierParams {
+                trader: *trader,
+                fee_tier,
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// The market must be paused. This instruction must be sent repeatedly until the market header
+/// reports the new size params.
+pub fn create_resize_market_instruction(
+    market: &Pubkey,
+    market_authority: &Pubkey,
+    payer: &Pubkey,
+    market_size_params: MarketSizeParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*market_authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: [
+            PhoenixInstruction::ResizeMarket.to_vec(),
+            market_size_params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_force_cancel_orders_instructions(
+    market: &Pubkey,
+    trader: &Pubkey,
+    market_authority: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+) -> Vec<Instruction> {
+    vec![
+        create_force_cancel_orders_instruction(
+            market,
+            trader,
+            market_authority,
+            base,
+            quote,
+            token_program,
+            Side::Bid,
+        ),
+        create_force_cancel_orders_instruction(
+            market,
+            trader,
+            market_authority,
+            base,
+            quote,
+            token_program,
+            Side::Ask,
+        ),
+    ]
+}
+
+fn create_force_cancel_orders_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    market_authority: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    side: Side,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    let base_account = get_associated_token_address_with_program_id(trader, base, token_program);
+    let quote_account = get_associated_token_address_with_program_id(trader, quote, token_program);
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*market_authority, true),
+                AccountMeta::new_readonly(*trader, false),
+                AccountMeta::new_readonly(seat, false),
+                AccountMeta::new(base_account, false),
+                AccountMeta::new(quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::ForceCancelOrders.to_vec(),
+            CancelUpToParams {
+                side,
+                tick_limit: None,
+                num_orders_to_cancel: None,
+                num_orders_to_search: None,
+                lots_to_free: None,
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_force_cancel_orders_with_free_funds_instructions(
+    market: &Pubkey,
+    trader: &Pubkey,
+    market_authority: &Pubkey,
+) -> Vec<Instruction> {
+    vec![
+        create_force_cancel_orders_with_free_funds_instruction(
+            market,
+            trader,
+            market_authority,
+            Side::Bid,
+        ),
+        create_force_cancel_orders_with_free_funds_instruction(
+            market,
+            trader,
+            market_authority,
+            Side::Ask,
+        ),
+    ]
+}
+
+fn create_force_cancel_orders_with_free_funds_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    market_authority: &Pubkey,
+    side: Side,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*market_authority, true),
+            AccountMeta::new_readonly(*trader, false),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::ForceCancelOrdersWithFreeFunds.to_vec(),
+            CancelUpToParams {
+                side,
+                tick_limit: None,
+                num_orders_to_cancel: None,
+                num_orders_to_search: None,
+                lots_to_free: None,
+            }
+            .try_to_vec()
+            .unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Setting `fill_threshold_base_lots` to zero disables the volatility band
+pub fn create_set_volatility_band_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    params: &SetVolatilityBandParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::SetVolatilityBand.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_taking_disabled_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    taking_disabled: bool,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeTakingDisabled.to_vec(),
+            taking_disabled.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// `disabled_event_types` is a combination of the event type flags, such as `FILL_EVENT_FLAG`
+pub fn create_change_disabled_event_types_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    disabled_event_types: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeDisabledEventTypes.to_vec(),
+            disabled_event_types.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// `referral_fee_bps` is the share of the taker fee accrued to referrers, in basis points of the fee
+pub fn create_change_referral_fee_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    referral_fee_bps: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeReferralFee.to_vec(),
+            referral_fee_bps.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Registers `referrer` so that orders naming it accrue a share of the taker fee
+pub fn create_register_referrer_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    referrer: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::RegisterReferrer.to_vec(),
+            referrer.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Deregisters `referrer`. Its unclaimed referral fees are forfeited to the market
+pub fn create_deregister_referrer_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    referrer: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::DeregisterReferrer.to_vec(),
+            referrer.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_change_min_taker_fill_instruction(
+    authority: &Pubkey,
+    market: &Pubkey,
+    min_taker_fill_base_lots: u64,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [
+            PhoenixInstruction::ChangeMinTakerFill.to_vec(),
+            min_taker_fill_base_lots.try_to_vec().unwrap(),
         ]
         .concat(),
     }
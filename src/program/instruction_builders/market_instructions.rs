@@ -1,20 +1,46 @@
 use crate::phoenix_log_authority;
-use crate::program::new_order::MultipleOrderPacket;
+use crate::program::new_order::{
+    CancelAndSwapParams, CondensedOrder, MultipleOrderPacket, PlaceByFundsFractionParams,
+    SwapRouteParams,
+};
 use crate::program::withdraw::WithdrawParams;
 use crate::program::{processor::*, PhoenixInstruction};
-use crate::state::{OrderPacket, OrderPacketMetadata};
+use crate::state::{OrderPacket, OrderPacketMetadata, SelfTradeBehavior, Side};
 use borsh::BorshSerialize;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program,
 };
-use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
 
-use crate::program::loaders::get_vault_address;
-use crate::program::processor::deposit::DepositParams;
+use crate::program::loaders::{get_native_sol_address, get_vault_address};
+use crate::program::processor::deposit::{DepositAtomsParams, DepositParams};
 use crate::program::validation::loaders::get_seat_address;
 
+/// Returns the token program account of an instruction, followed by the given mints if the token
+/// program is Token-2022. The program transfers Token-2022 tokens with `transfer_checked`, which
+/// needs the mint of each transferred token.
+///
+/// Builders that derive the trader's associated token accounts assume SPL Token mints. Markets with
+/// Token-2022 mints should use the builders that take the token accounts and the token program.
+pub fn get_token_program_account_metas(
+    token_program: &Pubkey,
+    mints: &[&Pubkey],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![AccountMeta::new_readonly(*token_program, false)];
+    if token_program == &spl_token_2022::id() {
+        accounts.extend(
+            mints
+                .iter()
+                .map(|mint| AccountMeta::new_readonly(**mint, false)),
+        );
+    }
+    accounts
+}
+
 pub fn create_new_order_instruction(
     market: &Pubkey,
     trader: &Pubkey,
@@ -31,6 +57,7 @@ pub fn create_new_order_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
         order_packet,
     )
 }
@@ -42,6 +69,7 @@ pub fn create_new_order_instruction_with_custom_token_accounts(
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
     order_packet: &OrderPacket,
 ) -> Instruction {
     let (base_vault, _) = get_vault_address(market, base);
@@ -49,17 +77,20 @@ pub fn create_new_order_instruction_with_custom_token_accounts(
     if order_packet.is_take_only() {
         Instruction {
             program_id: crate::id(),
-            accounts: vec![
-                AccountMeta::new_readonly(crate::id(), false),
-                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
-                AccountMeta::new(*market, false),
-                AccountMeta::new_readonly(*trader, true),
-                AccountMeta::new(*base_account, false),
-                AccountMeta::new(*quote_account, false),
-                AccountMeta::new(base_vault, false),
-                AccountMeta::new(quote_vault, false),
-                AccountMeta::new_readonly(spl_token::id(), false),
-            ],
+            accounts: [
+                vec![
+                    AccountMeta::new_readonly(crate::id(), false),
+                    AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                    AccountMeta::new(*market, false),
+                    AccountMeta::new_readonly(*trader, true),
+                    AccountMeta::new(*base_account, false),
+                    AccountMeta::new(*quote_account, false),
+                    AccountMeta::new(base_vault, false),
+                    AccountMeta::new(quote_vault, false),
+                ],
+                get_token_program_account_metas(token_program, &[base, quote]),
+            ]
+            .concat(),
             data: [
                 PhoenixInstruction::Swap.to_vec(),
                 order_packet.try_to_vec().unwrap(),
@@ -70,18 +101,21 @@ pub fn create_new_order_instruction_with_custom_token_accounts(
         let (seat, _) = get_seat_address(market, trader);
         Instruction {
             program_id: crate::id(),
-            accounts: vec![
-                AccountMeta::new_readonly(crate::id(), false),
-                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
-                AccountMeta::new(*market, false),
-                AccountMeta::new_readonly(*trader, true),
-                AccountMeta::new_readonly(seat, false),
-                AccountMeta::new(*base_account, false),
-                AccountMeta::new(*quote_account, false),
-                AccountMeta::new(base_vault, false),
-                AccountMeta::new(quote_vault, false),
-                AccountMeta::new_readonly(spl_token::id(), false),
-            ],
+            accounts: [
+                vec![
+                    AccountMeta::new_readonly(crate::id(), false),
+                    AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                    AccountMeta::new(*market, false),
+                    AccountMeta::new_readonly(*trader, true),
+                    AccountMeta::new_readonly(seat, false),
+                    AccountMeta::new(*base_account, false),
+                    AccountMeta::new(*quote_account, false),
+                    AccountMeta::new(base_vault, false),
+                    AccountMeta::new(quote_vault, false),
+                ],
+                get_token_program_account_metas(token_program, &[base, quote]),
+            ]
+            .concat(),
             data: [
                 PhoenixInstruction::PlaceLimitOrder.to_vec(),
                 order_packet.try_to_vec().unwrap(),
@@ -91,6 +125,40 @@ pub fn create_new_order_instruction_with_custom_token_accounts(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn create_new_fok_order_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    side: Side,
+    price_in_ticks: u64,
+    num_base_lots: u64,
+    self_trade_behavior: SelfTradeBehavior,
+    match_limit: Option<u64>,
+    client_order_id: u128,
+) -> Instruction {
+    let order_packet = match side {
+        Side::Bid => OrderPacket::new_fok_buy_with_limit_price(
+            price_in_ticks,
+            num_base_lots,
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            false,
+        ),
+        Side::Ask => OrderPacket::new_fok_sell_with_limit_price(
+            price_in_ticks,
+            num_base_lots,
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            false,
+        ),
+    };
+    create_new_order_instruction(market, trader, base, quote, &order_packet)
+}
+
 pub fn create_new_order_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
@@ -134,6 +202,7 @@ pub fn create_new_multiple_order_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
         multiple_order_packet,
     )
 }
@@ -145,6 +214,44 @@ pub fn create_new_multiple_order_instruction_with_custom_token_accounts(
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
+    multiple_order_packet: &MultipleOrderPacket,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*trader, true),
+                AccountMeta::new_readonly(seat, false),
+                AccountMeta::new(*base_account, false),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::PlaceMultiplePostOnlyOrders.to_vec(),
+            multiple_order_packet.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Cancels all of the trader's orders and places the orders in `multiple_order_packet`. The funds
+/// released by the cancels stay in the market and are reused by the new orders.
+pub fn create_replace_quotes_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
     multiple_order_packet: &MultipleOrderPacket,
 ) -> Instruction {
     let (base_vault, _) = get_vault_address(market, base);
@@ -158,20 +265,85 @@ pub fn create_new_multiple_order_instruction_with_custom_token_accounts(
             AccountMeta::new(*market, false),
             AccountMeta::new_readonly(*trader, true),
             AccountMeta::new_readonly(seat, false),
-            AccountMeta::new(*base_account, false),
-            AccountMeta::new(*quote_account, false),
+            AccountMeta::new(get_associated_token_address(trader, base), false),
+            AccountMeta::new(get_associated_token_address(trader, quote), false),
             AccountMeta::new(base_vault, false),
             AccountMeta::new(quote_vault, false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
         data: [
-            PhoenixInstruction::PlaceMultiplePostOnlyOrders.to_vec(),
+            PhoenixInstruction::ReplaceQuotes.to_vec(),
             multiple_order_packet.try_to_vec().unwrap(),
         ]
         .concat(),
     }
 }
 
+/// The maximum number of orders per side in a `MultipleOrderPacket`, which is bounded by the
+/// transaction size limit.
+pub const MAX_MULTIPLE_ORDERS_PER_SIDE: usize = 21;
+
+/// Places `num_orders` post-only orders on `side`, starting at `start_price_in_ticks` and moving
+/// `tick_step` ticks away from the spread with each order. The first order has a size of
+/// `base_size_in_base_lots`, and each subsequent order is `size_growth_factor` times larger than
+/// the previous one (rounded down).
+///
+/// Returns `None` if the ladder is empty or has more than `MAX_MULTIPLE_ORDERS_PER_SIDE` orders,
+/// if `tick_step` is zero, or if any order would have a zero price or size.
+#[allow(clippy::too_many_arguments)]
+pub fn create_geometric_ladder_orders(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    side: Side,
+    start_price_in_ticks: u64,
+    tick_step: u64,
+    num_orders: usize,
+    base_size_in_base_lots: u64,
+    size_growth_factor: f64,
+) -> Option<Instruction> {
+    if num_orders == 0
+        || num_orders > MAX_MULTIPLE_ORDERS_PER_SIDE
+        || tick_step == 0
+        || !size_growth_factor.is_finite()
+        || size_growth_factor <= 0.0
+    {
+        return None;
+    }
+    let mut orders = Vec::with_capacity(num_orders);
+    let mut price_in_ticks = start_price_in_ticks;
+    let mut size = base_size_in_base_lots as f64;
+    for i in 0..num_orders {
+        if i > 0 {
+            price_in_ticks = match side {
+                Side::Bid => price_in_ticks.checked_sub(tick_step)?,
+                Side::Ask => price_in_ticks.checked_add(tick_step)?,
+            };
+            size *= size_growth_factor;
+        }
+        let size_in_base_lots = size.floor();
+        if price_in_ticks == 0 || size_in_base_lots < 1.0 || size_in_base_lots >= u64::MAX as f64 {
+            return None;
+        }
+        orders.push(CondensedOrder::new_default(
+            price_in_ticks,
+            size_in_base_lots as u64,
+        ));
+    }
+    let multiple_order_packet = match side {
+        Side::Bid => MultipleOrderPacket::new_default(orders, vec![]),
+        Side::Ask => MultipleOrderPacket::new_default(vec![], orders),
+    };
+    Some(create_new_multiple_order_instruction(
+        market,
+        trader,
+        base,
+        quote,
+        &multiple_order_packet,
+    ))
+}
+
 pub fn create_new_multiple_order_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
@@ -253,10 +425,10 @@ pub fn create_cancel_multiple_orders_by_id_with_free_funds_instruction(
     }
 }
 
-pub fn create_reduce_order_with_free_funds_instruction(
+pub fn create_cancel_by_client_order_id_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    params: &ReduceOrderParams,
+    params: &CancelByClientOrderIdParams,
 ) -> Instruction {
     Instruction {
         program_id: crate::id(),
@@ -267,49 +439,39 @@ pub fn create_reduce_order_with_free_funds_instruction(
             AccountMeta::new_readonly(*trader, true),
         ],
         data: [
-            PhoenixInstruction::ReduceOrderWithFreeFunds.to_vec(),
+            PhoenixInstruction::CancelByClientOrderIdWithFreeFunds.to_vec(),
             params.try_to_vec().unwrap(),
         ]
         .concat(),
     }
 }
 
-pub fn create_deposit_funds_instruction(
+pub fn create_cancel_all_orders_bounded_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    params: &DepositParams,
+    params: &CancelAllOrdersBoundedParams,
 ) -> Instruction {
-    let base_account = get_associated_token_address(trader, base);
-    let quote_account = get_associated_token_address(trader, quote);
-    let (seat, _) = get_seat_address(market, trader);
-    create_deposit_funds_instruction_with_custom_token_accounts(
-        market,
-        trader,
-        &seat,
-        &base_account,
-        &quote_account,
-        base,
-        quote,
-        params,
-    )
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+        ],
+        data: [
+            PhoenixInstruction::CancelAllOrdersBoundedWithFreeFunds.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn create_deposit_funds_instruction_with_custom_token_accounts(
+pub fn create_cancel_in_price_range_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    seat: &Pubkey,
-    base_account: &Pubkey,
-    quote_account: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    params: &DepositParams,
+    params: &CancelInPriceRangeParams,
 ) -> Instruction {
-    let (base_vault, _) = get_vault_address(market, base);
-    let (quote_vault, _) = get_vault_address(market, quote);
-    let ix_data = params.try_to_vec().unwrap();
     Instruction {
         program_id: crate::id(),
         accounts: vec![
@@ -317,34 +479,20 @@ pub fn create_deposit_funds_instruction_with_custom_token_accounts(
             AccountMeta::new_readonly(phoenix_log_authority::id(), false),
             AccountMeta::new(*market, false),
             AccountMeta::new_readonly(*trader, true),
-            AccountMeta::new_readonly(*seat, false),
-            AccountMeta::new(*base_account, false),
-            AccountMeta::new(*quote_account, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: [PhoenixInstruction::DepositFunds.to_vec(), ix_data].concat(),
+        data: [
+            PhoenixInstruction::CancelInPriceRangeWithFreeFunds.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn _phoenix_instruction_template<T: BorshSerialize>(
+pub fn create_reduce_order_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base_account: &Pubkey,
-    quote_account: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    ix_id: PhoenixInstruction,
-    params: Option<&T>,
+    params: &ReduceOrderParams,
 ) -> Instruction {
-    let (base_vault, _) = get_vault_address(market, base);
-    let (quote_vault, _) = get_vault_address(market, quote);
-    let ix_data = match params {
-        Some(i) => i.try_to_vec().unwrap(),
-        None => vec![],
-    };
     Instruction {
         program_id: crate::id(),
         accounts: vec![
@@ -352,27 +500,20 @@ fn _phoenix_instruction_template<T: BorshSerialize>(
             AccountMeta::new_readonly(phoenix_log_authority::id(), false),
             AccountMeta::new(*market, false),
             AccountMeta::new_readonly(*trader, true),
-            AccountMeta::new(*base_account, false),
-            AccountMeta::new(*quote_account, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: [[ix_id as u8].to_vec(), ix_data].concat(),
+        data: [
+            PhoenixInstruction::ReduceOrderWithFreeFunds.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
     }
 }
 
-fn _phoenix_instruction_template_no_param(
+pub fn create_reduce_order_by_quote_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base_account: &Pubkey,
-    quote_account: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    ix_id: PhoenixInstruction,
+    params: &ReduceOrderByQuoteParams,
 ) -> Instruction {
-    let (base_vault, _) = get_vault_address(market, base);
-    let (quote_vault, _) = get_vault_address(market, quote);
     Instruction {
         program_id: crate::id(),
         accounts: vec![
@@ -380,82 +521,576 @@ fn _phoenix_instruction_template_no_param(
             AccountMeta::new_readonly(phoenix_log_authority::id(), false),
             AccountMeta::new(*market, false),
             AccountMeta::new_readonly(*trader, true),
-            AccountMeta::new(*base_account, false),
-            AccountMeta::new(*quote_account, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: [ix_id as u8].to_vec(),
+        data: [
+            PhoenixInstruction::ReduceOrderByQuoteWithFreeFunds.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
     }
 }
 
-pub fn reduce_order_instruction(
+pub fn create_reduce_multiple_orders_with_free_funds_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    params: &ReduceOrderParams,
+    params: &ReduceMultipleOrdersParams,
 ) -> Instruction {
-    let base_account = get_associated_token_address(trader, base);
-    let quote_account = get_associated_token_address(trader, quote);
-    create_reduce_order_instruction_with_custom_token_accounts(
-        market,
-        trader,
-        &base_account,
-        &quote_account,
-        base,
-        quote,
-        params,
-    )
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+        ],
+        data: [
+            PhoenixInstruction::ReduceMultipleOrdersWithFreeFunds.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
 }
 
-pub fn create_reduce_order_instruction_with_custom_token_accounts(
+pub fn create_amend_order_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base_account: &Pubkey,
-    quote_account: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
-    params: &ReduceOrderParams,
+    params: &AmendOrderParams,
 ) -> Instruction {
-    _phoenix_instruction_template::<ReduceOrderParams>(
-        market,
-        trader,
-        base_account,
-        quote_account,
-        base,
-        quote,
-        PhoenixInstruction::ReduceOrder,
-        Some(params),
-    )
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::AmendOrder.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
 }
 
-pub fn create_cancel_all_orders_instruction(
+pub fn create_reprice_order_instruction(
     market: &Pubkey,
     trader: &Pubkey,
-    base: &Pubkey,
-    quote: &Pubkey,
+    params: &RepriceOrderParams,
 ) -> Instruction {
-    let base_account = get_associated_token_address(trader, base);
-    let quote_account = get_associated_token_address(trader, quote);
-    create_cancel_all_orders_instruction_with_custom_token_accounts(
-        market,
-        trader,
-        &base_account,
-        &quote_account,
-        base,
-        quote,
-    )
-}
-
-pub fn create_cancel_all_orders_instruction_with_custom_token_accounts(
-    market: &Pubkey,
-    trader: &Pubkey,
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::RepriceOrder.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_set_self_trade_behavior_override_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    self_trade_behavior: Option<SelfTradeBehavior>,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::SetSelfTradeBehaviorOverride.to_vec(),
+            self_trade_behavior.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_internal_transfer_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    params: &InternalTransferParams,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    let (destination_seat, _) = get_seat_address(market, &params.destination);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+            AccountMeta::new_readonly(destination_seat, false),
+        ],
+        data: [
+            PhoenixInstruction::InternalTransfer.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_place_by_funds_fraction_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    params: &PlaceByFundsFractionParams,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::PlaceByFundsFraction.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_prune_expired_orders_instruction(
+    market: &Pubkey,
+    signer: &Pubkey,
+    params: &PruneExpiredOrdersParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*signer, true),
+        ],
+        data: [
+            PhoenixInstruction::PruneExpiredOrders.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Any signer can demote the seat of a trader that has been inactive for longer than the market's
+/// seat inactivity threshold.
+pub fn create_demote_inactive_seat_instruction(
+    market: &Pubkey,
+    signer: &Pubkey,
+    trader: &Pubkey,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(seat, false),
+        ],
+        data: PhoenixInstruction::DemoteInactiveSeat.to_vec(),
+    }
+}
+
+pub fn create_deposit_funds_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &DepositParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    let (seat, _) = get_seat_address(market, trader);
+    create_deposit_funds_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &seat,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_deposit_funds_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    seat: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &DepositParams,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    let ix_data = params.try_to_vec().unwrap();
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*trader, true),
+                AccountMeta::new_readonly(*seat, false),
+                AccountMeta::new(*base_account, false),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [PhoenixInstruction::DepositFunds.to_vec(), ix_data].concat(),
+    }
+}
+
+/// Deposits the whole lots contained in the given atom amounts. The atoms that do not make up a
+/// whole lot stay in the trader's associated token accounts.
+pub fn create_deposit_atoms_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &DepositAtomsParams,
+) -> Instruction {
+    let mut ix = create_deposit_funds_instruction(
+        market,
+        trader,
+        base,
+        quote,
+        &DepositParams {
+            quote_lots_to_deposit: 0,
+            base_lots_to_deposit: 0,
+        },
+    );
+    ix.data = [
+        PhoenixInstruction::DepositAtoms.to_vec(),
+        params.try_to_vec().unwrap(),
+    ]
+    .concat();
+    ix
+}
+
+pub fn create_deposit_and_place_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &DepositParams,
+    order_packet: &OrderPacket,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    let (seat, _) = get_seat_address(market, trader);
+    create_deposit_and_place_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &seat,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+        order_packet,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_deposit_and_place_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    seat: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &DepositParams,
+    order_packet: &OrderPacket,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*trader, true),
+                AccountMeta::new_readonly(*seat, false),
+                AccountMeta::new(*base_account, false),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::DepositAndPlace.to_vec(),
+            params.try_to_vec().unwrap(),
+            order_packet.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn _phoenix_instruction_template<T: BorshSerialize>(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    ix_id: PhoenixInstruction,
+    params: Option<&T>,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    let ix_data = match params {
+        Some(i) => i.try_to_vec().unwrap(),
+        None => vec![],
+    };
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*trader, true),
+                AccountMeta::new(*base_account, false),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [[ix_id as u8].to_vec(), ix_data].concat(),
+    }
+}
+
+fn _phoenix_instruction_template_no_param(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    ix_id: PhoenixInstruction,
+) -> Instruction {
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, quote);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*trader, true),
+                AccountMeta::new(*base_account, false),
+                AccountMeta::new(*quote_account, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, quote]),
+        ]
+        .concat(),
+        data: [ix_id as u8].to_vec(),
+    }
+}
+
+pub fn reduce_order_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &ReduceOrderParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_reduce_order_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_reduce_order_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &ReduceOrderParams,
+) -> Instruction {
+    _phoenix_instruction_template::<ReduceOrderParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::ReduceOrder,
+        Some(params),
+    )
+}
+
+pub fn create_reduce_order_by_quote_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &ReduceOrderByQuoteParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_reduce_order_by_quote_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_reduce_order_by_quote_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &ReduceOrderByQuoteParams,
+) -> Instruction {
+    _phoenix_instruction_template::<ReduceOrderByQuoteParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::ReduceOrderByQuote,
+        Some(params),
+    )
+}
+
+pub fn create_reduce_multiple_orders_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &ReduceMultipleOrdersParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_reduce_multiple_orders_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_reduce_multiple_orders_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &ReduceMultipleOrdersParams,
+) -> Instruction {
+    _phoenix_instruction_template::<ReduceMultipleOrdersParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::ReduceMultipleOrders,
+        Some(params),
+    )
+}
+
+pub fn create_cancel_all_orders_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_cancel_all_orders_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+    )
+}
+
+pub fn create_cancel_all_orders_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
     base_account: &Pubkey,
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
 ) -> Instruction {
     _phoenix_instruction_template_no_param(
         market,
@@ -464,6 +1099,7 @@ pub fn create_cancel_all_orders_instruction_with_custom_token_accounts(
         quote_account,
         base,
         quote,
+        token_program,
         PhoenixInstruction::CancelAllOrders,
     )
 }
@@ -484,6 +1120,7 @@ pub fn create_cancel_up_to_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
         params,
     )
 }
@@ -495,6 +1132,7 @@ pub fn create_cancel_up_to_instruction_with_custom_token_accounts(
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
     params: &CancelUpToParams,
 ) -> Instruction {
     _phoenix_instruction_template::<CancelUpToParams>(
@@ -504,6 +1142,7 @@ pub fn create_cancel_up_to_instruction_with_custom_token_accounts(
         quote_account,
         base,
         quote,
+        token_program,
         PhoenixInstruction::CancelUpTo,
         Some(params),
     )
@@ -525,6 +1164,7 @@ pub fn create_cancel_multiple_orders_by_id_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
         params,
     )
 }
@@ -536,16 +1176,150 @@ pub fn create_cancel_multiple_orders_by_id_instruction_with_custom_token_account
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
-    params: &CancelMultipleOrdersByIdParams,
+    token_program: &Pubkey,
+    params: &CancelMultipleOrdersByIdParams,
+) -> Instruction {
+    _phoenix_instruction_template::<CancelMultipleOrdersByIdParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::CancelMultipleOrdersById,
+        Some(params),
+    )
+}
+
+pub fn create_cancel_by_client_order_id_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &CancelByClientOrderIdParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_cancel_by_client_order_id_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_cancel_by_client_order_id_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &CancelByClientOrderIdParams,
+) -> Instruction {
+    _phoenix_instruction_template::<CancelByClientOrderIdParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::CancelByClientOrderId,
+        Some(params),
+    )
+}
+
+pub fn create_cancel_all_orders_bounded_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &CancelAllOrdersBoundedParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_cancel_all_orders_bounded_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_cancel_all_orders_bounded_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &CancelAllOrdersBoundedParams,
+) -> Instruction {
+    _phoenix_instruction_template::<CancelAllOrdersBoundedParams>(
+        market,
+        trader,
+        base_account,
+        quote_account,
+        base,
+        quote,
+        token_program,
+        PhoenixInstruction::CancelAllOrdersBounded,
+        Some(params),
+    )
+}
+
+pub fn create_cancel_in_price_range_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    params: &CancelInPriceRangeParams,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    create_cancel_in_price_range_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        params,
+    )
+}
+
+pub fn create_cancel_in_price_range_instruction_with_custom_token_accounts(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_account: &Pubkey,
+    quote_account: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+    params: &CancelInPriceRangeParams,
 ) -> Instruction {
-    _phoenix_instruction_template::<CancelMultipleOrdersByIdParams>(
+    _phoenix_instruction_template::<CancelInPriceRangeParams>(
         market,
         trader,
         base_account,
         quote_account,
         base,
         quote,
-        PhoenixInstruction::CancelMultipleOrdersById,
+        token_program,
+        PhoenixInstruction::CancelInPriceRange,
         Some(params),
     )
 }
@@ -565,6 +1339,7 @@ pub fn create_withdraw_funds_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
     )
 }
 
@@ -575,6 +1350,7 @@ pub fn create_withdraw_funds_instruction_with_custom_token_accounts(
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
 ) -> Instruction {
     _phoenix_instruction_template::<WithdrawParams>(
         market,
@@ -583,14 +1359,38 @@ pub fn create_withdraw_funds_instruction_with_custom_token_accounts(
         quote_account,
         base,
         quote,
+        token_program,
         PhoenixInstruction::WithdrawFunds,
         Some(&WithdrawParams {
             quote_lots_to_withdraw: None,
             base_lots_to_withdraw: None,
+            close_seat: None,
         }),
     )
 }
 
+/// Withdraws all free funds of the trader to the given destination token accounts, which do not
+/// need to be owned by the trader.
+pub fn create_withdraw_funds_to_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base_destination: &Pubkey,
+    quote_destination: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    create_withdraw_funds_instruction_with_custom_token_accounts(
+        market,
+        trader,
+        base_destination,
+        quote_destination,
+        base,
+        quote,
+        token_program,
+    )
+}
+
 pub fn create_withdraw_funds_with_custom_amounts_instruction(
     market: &Pubkey,
     trader: &Pubkey,
@@ -608,9 +1408,11 @@ pub fn create_withdraw_funds_with_custom_amounts_instruction(
         &quote_account,
         base,
         quote,
+        &spl_token::id(),
         &WithdrawParams {
             quote_lots_to_withdraw: Some(quote_lots),
             base_lots_to_withdraw: Some(base_lots),
+            close_seat: None,
         },
     )
 }
@@ -622,6 +1424,7 @@ pub fn create_withdraw_funds_with_custom_amounts_instruction_with_custom_token_a
     quote_account: &Pubkey,
     base: &Pubkey,
     quote: &Pubkey,
+    token_program: &Pubkey,
     params: &WithdrawParams,
 ) -> Instruction {
     _phoenix_instruction_template::<WithdrawParams>(
@@ -631,11 +1434,297 @@ pub fn create_withdraw_funds_with_custom_amounts_instruction_with_custom_token_a
         quote_account,
         base,
         quote,
+        token_program,
         PhoenixInstruction::WithdrawFunds,
         Some(params),
     )
 }
 
+/// The maximum number of markets in a single `WithdrawFromMarkets` instruction. This keeps the
+/// number of account keys low enough for the instruction to fit in a legacy transaction.
+pub const MAX_MARKETS_PER_WITHDRAW_INSTRUCTION: usize = 4;
+
+/// Withdraws all free funds from each market in `markets`, which contains the market address and
+/// the base and quote mints of each market. The markets are split into chunks of
+/// `MAX_MARKETS_PER_WITHDRAW_INSTRUCTION`, and each returned instruction should be sent in its
+/// own transaction.
+pub fn create_withdraw_from_markets_instructions(
+    trader: &Pubkey,
+    markets: &[(Pubkey, Pubkey, Pubkey)],
+) -> Vec<Instruction> {
+    markets
+        .chunks(MAX_MARKETS_PER_WITHDRAW_INSTRUCTION)
+        .map(|chunk| {
+            let mut accounts = vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new_readonly(*trader, true),
+            ];
+            for (market, base, quote) in chunk.iter() {
+                let (base_vault, _) = get_vault_address(market, base);
+                let (quote_vault, _) = get_vault_address(market, quote);
+                accounts.extend_from_slice(&[
+                    AccountMeta::new(*market, false),
+                    AccountMeta::new(get_associated_token_address(trader, base), false),
+                    AccountMeta::new(get_associated_token_address(trader, quote), false),
+                    AccountMeta::new(base_vault, false),
+                    AccountMeta::new(quote_vault, false),
+                    AccountMeta::new_readonly(spl_token::id(), false),
+                ]);
+            }
+            Instruction {
+                program_id: crate::id(),
+                accounts,
+                data: PhoenixInstruction::WithdrawFromMarkets.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Swaps on two markets in a single instruction. Each market is given as the market address and
+/// its base and quote mints, and the trader's associated token accounts are used for both swaps.
+pub fn create_swap_route_instruction(
+    trader: &Pubkey,
+    first_market: &(Pubkey, Pubkey, Pubkey),
+    second_market: &(Pubkey, Pubkey, Pubkey),
+    params: &SwapRouteParams,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(crate::id(), false),
+        AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+        AccountMeta::new_readonly(*trader, true),
+    ];
+    for (market, base, quote) in [first_market, second_market] {
+        let (base_vault, _) = get_vault_address(market, base);
+        let (quote_vault, _) = get_vault_address(market, quote);
+        accounts.extend_from_slice(&[
+            AccountMeta::new(*market, false),
+            AccountMeta::new(get_associated_token_address(trader, base), false),
+            AccountMeta::new(get_associated_token_address(trader, quote), false),
+            AccountMeta::new(base_vault, false),
+            AccountMeta::new(quote_vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+    }
+    Instruction {
+        program_id: crate::id(),
+        accounts,
+        data: [
+            PhoenixInstruction::SwapRoute.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Withdraws all free funds and closes the trader's seat. The lamports of the seat account are
+/// returned to the trader.
+pub fn create_withdraw_funds_and_close_seat_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    let mut ix = _phoenix_instruction_template::<WithdrawParams>(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        PhoenixInstruction::WithdrawFunds,
+        Some(&WithdrawParams {
+            quote_lots_to_withdraw: None,
+            base_lots_to_withdraw: None,
+            close_seat: Some(true),
+        }),
+    );
+    // The trader receives the lamports of the seat account
+    ix.accounts[3] = AccountMeta::new(*trader, true);
+    ix.accounts
+        .push(AccountMeta::new(get_seat_address(market, trader).0, false));
+    ix
+}
+
+/// Cancels all of the trader's orders, withdraws all funds to the trader's associated token
+/// accounts and closes the trader's seat. The lamports of the seat account are returned to the
+/// trader.
+pub fn create_cancel_all_and_close_seat_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    quote: &Pubkey,
+) -> Instruction {
+    let base_account = get_associated_token_address(trader, base);
+    let quote_account = get_associated_token_address(trader, quote);
+    let mut ix = _phoenix_instruction_template_no_param(
+        market,
+        trader,
+        &base_account,
+        &quote_account,
+        base,
+        quote,
+        &spl_token::id(),
+        PhoenixInstruction::CancelAllAndCloseSeat,
+    );
+    // The trader receives the lamports of the seat account
+    ix.accounts[3] = AccountMeta::new(*trader, true);
+    ix.accounts
+        .push(AccountMeta::new(get_seat_address(market, trader).0, false));
+    ix
+}
+
+/// Returns the native mint of the SPL Token program or of the Token-2022 program
+fn get_native_mint(token_program: &Pubkey) -> Pubkey {
+    if token_program == &spl_token_2022::id() {
+        spl_token_2022::native_mint::id()
+    } else {
+        spl_token::native_mint::id()
+    }
+}
+
+/// Deposits SOL into a market whose quote mint is the native mint of `token_program`. The SOL is
+/// wrapped in the trader's temporary wrapped SOL account, which is created and closed by the
+/// instruction.
+pub fn create_deposit_funds_with_native_sol_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    token_program: &Pubkey,
+    params: &DepositParams,
+) -> Instruction {
+    let quote = get_native_mint(token_program);
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, &quote);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new(*trader, true),
+                AccountMeta::new_readonly(get_seat_address(market, trader).0, false),
+                AccountMeta::new(
+                    get_associated_token_address_with_program_id(trader, base, token_program),
+                    false,
+                ),
+                AccountMeta::new(get_native_sol_address(market, trader).0, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, &quote]),
+            vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(quote, false),
+            ],
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::DepositFundsWithNativeSol.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Withdraws funds from a market whose quote mint is the native mint of `token_program`. The quote
+/// tokens are received as SOL through the trader's temporary wrapped SOL account, which is created
+/// and closed by the instruction.
+pub fn create_withdraw_funds_to_native_sol_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    token_program: &Pubkey,
+    params: &WithdrawParams,
+) -> Instruction {
+    let quote = get_native_mint(token_program);
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, &quote);
+    let seat_account = if params.close_seat == Some(true) {
+        vec![AccountMeta::new(get_seat_address(market, trader).0, false)]
+    } else {
+        vec![]
+    };
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new(*trader, true),
+                AccountMeta::new(
+                    get_associated_token_address_with_program_id(trader, base, token_program),
+                    false,
+                ),
+                AccountMeta::new(get_native_sol_address(market, trader).0, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, &quote]),
+            seat_account,
+            vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(quote, false),
+            ],
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::WithdrawFundsToNativeSol.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+/// Swaps on a market whose quote mint is the native mint of `token_program`. Buys are paid with
+/// SOL and sells are received as SOL through the trader's temporary wrapped SOL account, which is
+/// created and closed by the instruction.
+pub fn create_swap_with_native_sol_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    base: &Pubkey,
+    token_program: &Pubkey,
+    order_packet: &OrderPacket,
+) -> Instruction {
+    let quote = get_native_mint(token_program);
+    let (base_vault, _) = get_vault_address(market, base);
+    let (quote_vault, _) = get_vault_address(market, &quote);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new(*trader, true),
+                AccountMeta::new(
+                    get_associated_token_address_with_program_id(trader, base, token_program),
+                    false,
+                ),
+                AccountMeta::new(get_native_sol_address(market, trader).0, false),
+                AccountMeta::new(base_vault, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[base, &quote]),
+            vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(quote, false),
+            ],
+        ]
+        .concat(),
+        data: [
+            PhoenixInstruction::SwapWithNativeSol.to_vec(),
+            order_packet.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
 pub fn create_request_seat_instruction(payer: &Pubkey, market: &Pubkey) -> Instruction {
     let (seat, _) = get_seat_address(market, payer);
     Instruction {
@@ -651,3 +1740,175 @@ pub fn create_request_seat_instruction(payer: &Pubkey, market: &Pubkey) -> Instr
         data: PhoenixInstruction::RequestSeat.to_vec(),
     }
 }
+
+/// Creates the trader's seat and registers the trader on the market. The seat must still be
+/// approved by the market authority.
+pub fn create_create_seat_instruction(trader: &Pubkey, market: &Pubkey) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new(*trader, true),
+            AccountMeta::new(seat, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: PhoenixInstruction::CreateSeat.to_vec(),
+    }
+}
+
+pub fn create_place_stop_order_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    params: &PlaceStopOrderParams,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::PlaceStopOrder.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_cancel_stop_order_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    params: &CancelStopOrderParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+        ],
+        data: [
+            PhoenixInstruction::CancelStopOrder.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_cancel_and_swap_instruction(
+    market: &Pubkey,
+    trader: &Pubkey,
+    params: &CancelAndSwapParams,
+) -> Instruction {
+    let (seat, _) = get_seat_address(market, trader);
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+            AccountMeta::new(*market, false),
+            AccountMeta::new_readonly(*trader, true),
+            AccountMeta::new_readonly(seat, false),
+        ],
+        data: [
+            PhoenixInstruction::CancelAndSwap.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+pub fn create_claim_referral_fees_instruction(
+    market: &Pubkey,
+    referrer: &Pubkey,
+    quote_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let quote_account =
+        get_associated_token_address_with_program_id(referrer, quote_mint, token_program);
+    let (quote_vault, _) = get_vault_address(market, quote_mint);
+    Instruction {
+        program_id: crate::id(),
+        accounts: [
+            vec![
+                AccountMeta::new_readonly(crate::id(), false),
+                AccountMeta::new_readonly(phoenix_log_authority::id(), false),
+                AccountMeta::new(*market, false),
+                AccountMeta::new_readonly(*referrer, true),
+                AccountMeta::new(quote_account, false),
+                AccountMeta::new(quote_vault, false),
+            ],
+            get_token_program_account_metas(token_program, &[quote_mint]),
+        ]
+        .concat(),
+        data: PhoenixInstruction::ClaimReferralFees.to_vec(),
+    }
+}
+
+/// Fails the transaction if the best price on `params.side` is outside of the given bounds. The
+/// market account is only read, so it does not need to be writable.
+pub fn create_assert_market_price_instruction(
+    market: &Pubkey,
+    params: &AssertMarketPriceParams,
+) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: vec![
+            AccountMeta::new_readonly(crate::id(), false),
+            AccountMeta::new_readonly(*market, false),
+        ],
+        data: [
+            PhoenixInstruction::AssertMarketPrice.to_vec(),
+            params.try_to_vec().unwrap(),
+        ]
+        .concat(),
+    }
+}
+
+#[test]
+fn test_token_program_accounts() {
+    let market = Pubkey::new_unique();
+    let trader = Pubkey::new_unique();
+    let base = Pubkey::new_unique();
+    let quote = Pubkey::new_unique();
+    let base_account = Pubkey::new_unique();
+    let quote_account = Pubkey::new_unique();
+    let build = |token_program: &Pubkey| {
+        create_withdraw_funds_instruction_with_custom_token_accounts(
+            &market,
+            &trader,
+            &base_account,
+            &quote_account,
+            &base,
+            &quote,
+            token_program,
+        )
+        .accounts
+        .into_iter()
+        .map(|account| account.pubkey)
+        .collect::<Vec<_>>()
+    };
+
+    // SPL Token markets end with the token program
+    let spl_token_accounts = build(&spl_token::id());
+    assert_eq!(spl_token_accounts.last(), Some(&spl_token::id()));
+
+    // Token-2022 markets also pass the base and quote mints after the token program
+    let token_2022_accounts = build(&spl_token_2022::id());
+    assert_eq!(
+        token_2022_accounts[..spl_token_accounts.len() - 1],
+        spl_token_accounts[..spl_token_accounts.len() - 1]
+    );
+    assert_eq!(
+        token_2022_accounts[spl_token_accounts.len() - 1..],
+        [spl_token_2022::id(), base, quote]
+    );
+}
@@ -1,5 +1,9 @@
 pub mod market_authority_instructions;
 pub mod market_instructions;
+pub mod swap_builder;
 
 pub use market_authority_instructions::*;
 pub use market_instructions::*;
+pub use swap_builder::*;
+
+pub use crate::program::loaders::get_market_address;
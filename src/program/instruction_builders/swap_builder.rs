@@ -0,0 +1,251 @@
+use crate::state::{OrderPacket, SelfTradeBehavior, Side};
+
+/// Marker for a `SwapBuilder` that has not been given a budget yet. It can not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoBudget;
+
+/// Buy base lots by spending up to this many quote lots (fees included).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpendQuote(pub u64);
+
+/// Buy this many base lots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiveBase(pub u64);
+
+/// Sell this many base lots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SellBase(pub u64);
+
+/// Sell base lots until this many quote lots are received (fees excluded).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiveQuote(pub u64);
+
+/// The budget of a swap. Each budget sets exactly one of `num_base_lots` and `num_quote_lots`.
+pub trait SwapBudget {
+    fn side(&self) -> Side;
+
+    /// Returns `(num_base_lots, num_quote_lots)`
+    fn lots(&self) -> (u64, u64);
+}
+
+impl SwapBudget for SpendQuote {
+    fn side(&self) -> Side {
+        Side::Bid
+    }
+
+    fn lots(&self) -> (u64, u64) {
+        (0, self.0)
+    }
+}
+
+impl SwapBudget for ReceiveBase {
+    fn side(&self) -> Side {
+        Side::Bid
+    }
+
+    fn lots(&self) -> (u64, u64) {
+        (self.0, 0)
+    }
+}
+
+impl SwapBudget for SellBase {
+    fn side(&self) -> Side {
+        Side::Ask
+    }
+
+    fn lots(&self) -> (u64, u64) {
+        (self.0, 0)
+    }
+}
+
+impl SwapBudget for ReceiveQuote {
+    fn side(&self) -> Side {
+        Side::Ask
+    }
+
+    fn lots(&self) -> (u64, u64) {
+        (0, self.0)
+    }
+}
+
+/// Builds an `ImmediateOrCancel` order packet for a swap. The budget is chosen with one of
+/// `spend_quote`, `receive_base`, `sell_base` or `receive_quote`, and only a builder with a budget
+/// can be built, so the resulting packet always sets exactly one of `num_base_lots` and
+/// `num_quote_lots`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapBuilder<Budget> {
+    budget: Budget,
+    price_in_ticks: Option<u64>,
+    min_lots_out: u64,
+    self_trade_behavior: SelfTradeBehavior,
+    match_limit: Option<u64>,
+    client_order_id: u128,
+    use_only_deposited_funds: bool,
+}
+
+impl Default for SwapBuilder<NoBudget> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SwapBuilder<NoBudget> {
+    pub fn new() -> Self {
+        Self {
+            budget: NoBudget,
+            price_in_ticks: None,
+            min_lots_out: 0,
+            self_trade_behavior: SelfTradeBehavior::CancelProvide,
+            match_limit: None,
+            client_order_id: 0,
+            use_only_deposited_funds: false,
+        }
+    }
+
+    pub fn spend_quote(self, num_quote_lots: u64) -> SwapBuilder<SpendQuote> {
+        self.with_budget(SpendQuote(num_quote_lots))
+    }
+
+    pub fn receive_base(self, num_base_lots: u64) -> SwapBuilder<ReceiveBase> {
+        self.with_budget(ReceiveBase(num_base_lots))
+    }
+
+    pub fn sell_base(self, num_base_lots: u64) -> SwapBuilder<SellBase> {
+        self.with_budget(SellBase(num_base_lots))
+    }
+
+    pub fn receive_quote(self, num_quote_lots: u64) -> SwapBuilder<ReceiveQuote> {
+        self.with_budget(ReceiveQuote(num_quote_lots))
+    }
+
+    fn with_budget<Budget: SwapBudget>(self, budget: Budget) -> SwapBuilder<Budget> {
+        SwapBuilder {
+            budget,
+            price_in_ticks: self.price_in_ticks,
+            min_lots_out: self.min_lots_out,
+            self_trade_behavior: self.self_trade_behavior,
+            match_limit: self.match_limit,
+            client_order_id: self.client_order_id,
+            use_only_deposited_funds: self.use_only_deposited_funds,
+        }
+    }
+}
+
+impl<Budget> SwapBuilder<Budget> {
+    /// The swap does not match at prices worse than this. Without a limit price, the swap is
+    /// a market order.
+    pub fn with_limit_price(mut self, price_in_ticks: u64) -> Self {
+        self.price_in_ticks = Some(price_in_ticks);
+        self
+    }
+
+    /// The swap is voided unless it receives at least this many lots: base lots for buys and
+    /// quote lots for sells.
+    pub fn with_min_lots_out(mut self, min_lots_out: u64) -> Self {
+        self.min_lots_out = min_lots_out;
+        self
+    }
+
+    pub fn with_self_trade_behavior(mut self, self_trade_behavior: SelfTradeBehavior) -> Self {
+        self.self_trade_behavior = self_trade_behavior;
+        self
+    }
+
+    pub fn with_match_limit(mut self, match_limit: u64) -> Self {
+        self.match_limit = Some(match_limit);
+        self
+    }
+
+    pub fn with_client_order_id(mut self, client_order_id: u128) -> Self {
+        self.client_order_id = client_order_id;
+        self
+    }
+
+    pub fn use_only_deposited_funds(mut self) -> Self {
+        self.use_only_deposited_funds = true;
+        self
+    }
+}
+
+impl<Budget: SwapBudget> SwapBuilder<Budget> {
+    pub fn build(self) -> OrderPacket {
+        let side = self.budget.side();
+        let (num_base_lots, num_quote_lots) = self.budget.lots();
+        let (min_base_lots_to_fill, min_quote_lots_to_fill) = match side {
+            Side::Bid => (self.min_lots_out, 0),
+            Side::Ask => (0, self.min_lots_out),
+        };
+        OrderPacket::new_ioc(
+            side,
+            self.price_in_ticks,
+            num_base_lots,
+            num_quote_lots,
+            min_base_lots_to_fill,
+            min_quote_lots_to_fill,
+            self.self_trade_behavior,
+            self.match_limit,
+            self.client_order_id,
+            self.use_only_deposited_funds,
+            None,
+            None,
+        )
+    }
+}
+
+#[test]
+fn test_swap_builder() {
+    assert_eq!(
+        SwapBuilder::new()
+            .spend_quote(1000)
+            .with_min_lots_out(9)
+            .build(),
+        OrderPacket::new_ioc_buy_with_slippage(1000, 9)
+    );
+    assert_eq!(
+        SwapBuilder::new()
+            .sell_base(10)
+            .with_min_lots_out(900)
+            .build(),
+        OrderPacket::new_ioc_sell_with_slippage(10, 900)
+    );
+    assert_eq!(
+        SwapBuilder::new()
+            .with_limit_price(100)
+            .receive_base(10)
+            .with_self_trade_behavior(SelfTradeBehavior::Abort)
+            .with_match_limit(5)
+            .build(),
+        OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            100,
+            10,
+            SelfTradeBehavior::Abort,
+            Some(5),
+            0,
+            false
+        )
+    );
+    assert_eq!(
+        SwapBuilder::new()
+            .receive_quote(1000)
+            .with_limit_price(90)
+            .with_self_trade_behavior(SelfTradeBehavior::Abort)
+            .with_client_order_id(7)
+            .use_only_deposited_funds()
+            .build(),
+        OrderPacket::new_ioc(
+            Side::Ask,
+            Some(90),
+            0,
+            1000,
+            0,
+            0,
+            SelfTradeBehavior::Abort,
+            None,
+            7,
+            true,
+            None,
+            None,
+        )
+    );
+}
@@ -0,0 +1,165 @@
+use crate::{
+    quantities::{BaseLots, Ticks},
+    state::{
+        markets::{FIFOOrderId, MarketEvent},
+        Side,
+    },
+};
+use sokoban::node_allocator::NodeAllocatorMap;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use std::mem::size_of;
+
+use super::{
+    assert_with_msg,
+    checkers::phoenix_checkers::MarketAccountInfo,
+    client_order_id_index::load_client_order_id_index_mut,
+    get_market_size, load_with_dispatch,
+    recent_fills::{get_recent_fills_offset_from_header, load_recent_fills_mut, RecentFill},
+    ClientOrderIdEntry, MarketHeader, PhoenixError, PhoenixMarketContext,
+};
+
+/// The client order id index and the recent fills are stored in the market account after the
+/// market, so the market cannot update them while it is borrowed. This struct tracks the orders
+/// placed and the fills of the current instruction from the market's events, and writes them once
+/// the instruction has been processed.
+pub(crate) struct MarketDataTracker {
+    /// If the market has a client order id index, the orders placed in the current instruction
+    placed_orders: Option<Vec<(u64, ClientOrderIdEntry)>>,
+    /// If the market keeps its recent fills, the price and size of the fills in the current
+    /// instruction
+    fills: Option<Vec<(Ticks, BaseLots)>>,
+}
+
+impl MarketDataTracker {
+    pub(crate) fn new(
+        phoenix_market_context: &PhoenixMarketContext<'_, '_>,
+    ) -> Result<Self, ProgramError> {
+        let header = phoenix_market_context.market_info.get_header()?;
+        Ok(Self {
+            placed_orders: if header.client_order_id_index_enabled != 0 {
+                Some(vec![])
+            } else {
+                None
+            },
+            fills: if header.recent_fills_enabled != 0 {
+                Some(vec![])
+            } else {
+                None
+            },
+        })
+    }
+
+    pub(crate) fn track_event(&mut self, event: &MarketEvent<Pubkey>) {
+        match (event, self.placed_orders.as_mut(), self.fills.as_mut()) {
+            (
+                MarketEvent::Place {
+                    order_sequence_number,
+                    client_order_id,
+                    price_in_ticks,
+                    ..
+                },
+                Some(placed_orders),
+                _,
+            ) => {
+                placed_orders.push((
+                    *order_sequence_number,
+                    ClientOrderIdEntry::new(*client_order_id, *price_in_ticks),
+                ));
+            }
+            (
+                MarketEvent::Fill {
+                    price_in_ticks,
+                    base_lots_filled,
+                    ..
+                },
+                _,
+                Some(fills),
+            ) => {
+                fills.push((*price_in_ticks, *base_lots_filled));
+            }
+            _ => {}
+        }
+    }
+
+    /// Stores the tracked orders and fills. This must be called before the market sequence number
+    /// is incremented, since the recent fills are stamped with it.
+    pub(crate) fn write(self, market_info: &MarketAccountInfo<'_, '_>) -> ProgramResult {
+        if market_info.data_is_empty() {
+            return Ok(());
+        }
+        if let Some(placed_orders) = self.placed_orders {
+            if !placed_orders.is_empty() {
+                index_client_order_ids(market_info, &placed_orders)?;
+            }
+        }
+        if let Some(fills) = self.fills {
+            if !fills.is_empty() {
+                record_recent_fills(market_info, &fills)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stores the client order ids of the orders placed in the current instruction. If the index is
+/// full, entries for orders that are no longer on the book are pruned first.
+fn index_client_order_ids(
+    market_info: &MarketAccountInfo<'_, '_>,
+    placed_orders: &[(u64, ClientOrderIdEntry)],
+) -> ProgramResult {
+    let market_size = get_market_size(&market_info.size_params)?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, index_bytes) = data.split_at_mut(market_size);
+    let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+    let index = load_client_order_id_index_mut(&market_info.size_params, index_bytes)?;
+    for (order_sequence_number, entry) in placed_orders.iter() {
+        if index.len() == index.capacity() {
+            let stale_entries = index
+                .iter()
+                .filter(|(sequence_number, entry)| {
+                    market
+                        .get_book(Side::from_order_sequence_number(**sequence_number))
+                        .get(&FIFOOrderId::new(entry.price_in_ticks, **sequence_number))
+                        .is_none()
+                })
+                .map(|(sequence_number, _)| *sequence_number)
+                .collect::<Vec<_>>();
+            for sequence_number in stale_entries.iter() {
+                index.remove(sequence_number);
+            }
+        }
+        assert_with_msg(
+            index.insert(*order_sequence_number, *entry).is_some(),
+            PhoenixError::ClientOrderIdIndexError,
+            "Failed to insert order into the client order id index",
+        )?;
+    }
+    Ok(())
+}
+
+/// Appends the fills of the current instruction to the market's recent fills, stamped with the
+/// current market sequence number
+fn record_recent_fills(
+    market_info: &MarketAccountInfo<'_, '_>,
+    fills: &[(Ticks, BaseLots)],
+) -> ProgramResult {
+    let (recent_fills_offset, market_sequence_number) = {
+        let header = market_info.get_header()?;
+        match get_recent_fills_offset_from_header(&header)? {
+            Some(offset) => (offset, header.market_sequence_number),
+            None => return Ok(()),
+        }
+    };
+    let recent_fills = fills
+        .iter()
+        .map(|&(price_in_ticks, base_lots_filled)| RecentFill {
+            market_sequence_number,
+            price_in_ticks,
+            base_lots_filled,
+        })
+        .collect::<Vec<_>>();
+    let recent_fills_bytes =
+        &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + recent_fills_offset..];
+    load_recent_fills_mut(recent_fills_bytes)?.push_fills(&recent_fills);
+    Ok(())
+}
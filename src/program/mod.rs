@@ -1,23 +1,35 @@
 pub(crate) mod event_recorder;
+pub(crate) mod market_data_tracker;
 pub(crate) mod processor;
 pub(crate) mod token_utils;
 pub(crate) mod validation;
 
 pub mod accounts;
+pub mod client_order_id_index;
+pub mod cpi;
 pub mod dispatch_market;
 pub mod error;
 pub mod events;
+pub mod iceberg_reserve_book;
 pub mod instruction;
 pub mod instruction_builders;
+pub mod recent_fills;
+pub mod referral_fee_book;
 pub mod status;
+pub mod stop_order_book;
 pub mod system_utils;
 
 pub use accounts::*;
+pub use client_order_id_index::*;
 pub use dispatch_market::*;
 pub use error::*;
 pub use events::*;
+pub use iceberg_reserve_book::*;
 pub use instruction::*;
 pub use instruction_builders::*;
 pub use processor::*;
+pub use recent_fills::*;
+pub use referral_fee_book::*;
+pub use stop_order_book::*;
 pub use validation::loaders::*;
 pub use validation::*;
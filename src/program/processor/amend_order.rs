@@ -0,0 +1,168 @@
+use crate::{
+    program::{
+        dispatch_market::load_with_dispatch_mut,
+        error::PhoenixError,
+        iceberg_reserve_book::{split_iceberg_reserve_book_mut, IcebergReserveBook},
+        loaders::NewOrderContext,
+        new_order::assert_trading_enabled,
+        CancelOrderParams, MarketHeader, PhoenixMarketContext,
+    },
+    quantities::{BaseLots, Ticks, WrapperU64},
+    state::markets::{FIFOOrderId, MarketEvent},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::NodeAllocatorMap;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use std::mem::size_of;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct AmendOrderParams {
+    pub base_params: CancelOrderParams,
+    /// New size of the order in base lots
+    pub size: u64,
+    /// New price of the order in ticks. If this is None, the price is unchanged
+    pub price_in_ticks: Option<u64>,
+    /// Client order id attached to the order if it is re-queued
+    pub client_order_id: u128,
+}
+
+/// This function amends an existing order using only the funds already available to the trader.
+/// If the size decreases and the price is unchanged, the order keeps its queue priority.
+/// Otherwise, the order is cancelled and re-placed with a new sequence number.
+pub(crate) fn process_amend_order<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    order_ids: &mut Vec<FIFOOrderId>,
+) -> ProgramResult {
+    // Only the seat is loaded because amended orders never transfer tokens
+    NewOrderContext::load_post_allowed(market_context, accounts, true)?;
+    let AmendOrderParams {
+        base_params,
+        size,
+        price_in_ticks,
+        client_order_id,
+    } = AmendOrderParams::try_from_slice(data)?;
+    let CancelOrderParams {
+        side,
+        price_in_ticks: current_price_in_ticks,
+        order_sequence_number,
+    } = base_params;
+    let order_id = FIFOOrderId::new(Ticks::new(current_price_in_ticks), order_sequence_number);
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    let clock = Clock::get()?;
+    let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
+    let header = *market_info.get_header()?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+    // The reserve of an iceberg order is keyed by the order id of its visible order
+    if iceberg_reserve_book
+        .as_deref()
+        .map_or(false, |book| book.reserves.get(&order_id).is_some())
+    {
+        phoenix_log!("Iceberg orders cannot be amended");
+        return Err(PhoenixError::AmendOrderError.into());
+    }
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    let (new_order_id, _) = market
+        .amend_order(
+            trader.key,
+            &order_id,
+            side,
+            BaseLots::new(size),
+            price_in_ticks.map(Ticks::new),
+            client_order_id,
+            iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+            record_event_fn,
+            &mut get_clock_fn,
+        )
+        .ok_or(PhoenixError::AmendOrderError)?;
+    if let Some(new_order_id) = new_order_id {
+        order_ids.push(new_order_id);
+    }
+    Ok(())
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct RepriceOrderParams {
+    pub base_params: CancelOrderParams,
+    /// New price of the order in ticks
+    pub new_price_in_ticks: u64,
+    /// Client order id attached to the repriced order
+    pub client_order_id: u128,
+}
+
+/// This function moves an existing order to a new price without changing its size. The order is
+/// removed and re-inserted with a new sequence number. Bids use the trader's free funds to cover
+/// any increase in the quote lots that they lock.
+pub(crate) fn process_reprice_order<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    order_ids: &mut Vec<FIFOOrderId>,
+) -> ProgramResult {
+    // Only the seat is loaded because repriced orders never transfer tokens
+    NewOrderContext::load_post_allowed(market_context, accounts, true)?;
+    let RepriceOrderParams {
+        base_params,
+        new_price_in_ticks,
+        client_order_id,
+    } = RepriceOrderParams::try_from_slice(data)?;
+    let CancelOrderParams {
+        side,
+        price_in_ticks,
+        order_sequence_number,
+    } = base_params;
+    let order_id = FIFOOrderId::new(Ticks::new(price_in_ticks), order_sequence_number);
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    let clock = Clock::get()?;
+    // Repriced orders are new orders on the book, so they are subject to the trading window
+    assert_trading_enabled(market_info, clock.slot)?;
+    let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
+    let header = *market_info.get_header()?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    // Iceberg orders are rejected by the market, since their reserves are keyed by order id
+    let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    let new_order_id = market
+        .reprice_order(
+            trader.key,
+            &order_id,
+            side,
+            Ticks::new(new_price_in_ticks),
+            client_order_id,
+            iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+            record_event_fn,
+            &mut get_clock_fn,
+        )
+        .ok_or(PhoenixError::RepriceOrderError)?;
+    order_ids.push(new_order_id);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::{
+    program::{
+        dispatch_market::load_with_dispatch, error::assert_with_msg,
+        validation::checkers::phoenix_checkers::MarketAccountInfo, MarketHeader, PhoenixError,
+    },
+    quantities::WrapperU64,
+    state::Side,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::OrderedNodeAllocatorMap;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::mem::size_of;
+
+/// The best price on `side` must be between `min_price_in_ticks` and `max_price_in_ticks`,
+/// inclusive. The best price of `Side::Bid` is the highest bid and the best price of `Side::Ask`
+/// is the lowest ask.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssertMarketPriceParams {
+    pub side: Side,
+    pub min_price_in_ticks: u64,
+    pub max_price_in_ticks: u64,
+}
+
+/// Fails if the best price on one side of the book is outside of the given bounds, or if that side
+/// of the book is empty. The market is not modified. Placing this instruction before a swap in the
+/// same transaction aborts the transaction if the book has moved adversely.
+///
+/// This instruction does not use the standard account layout. The accounts are the Phoenix program
+/// and the market, which does not need to be writable.
+pub(crate) fn process_assert_market_price<'a, 'info>(
+    _program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let AssertMarketPriceParams {
+        side,
+        min_price_in_ticks,
+        max_price_in_ticks,
+    } = AssertMarketPriceParams::try_from_slice(data)?;
+    assert_with_msg(
+        accounts.len() >= 2,
+        ProgramError::NotEnoughAccountKeys,
+        "The Phoenix program and the market must be provided",
+    )?;
+    let market_info = MarketAccountInfo::new(&accounts[1])?;
+    let best_price_in_ticks = {
+        let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+        market
+            .get_book(side)
+            .get_min()
+            .map(|(order_id, _)| order_id.price_in_ticks.as_u64())
+    };
+    let best_price_in_ticks = match best_price_in_ticks {
+        Some(price_in_ticks) => price_in_ticks,
+        None => {
+            phoenix_log!("There are no {:?} orders on the book", side);
+            return Err(PhoenixError::MarketPriceOutOfBounds.into());
+        }
+    };
+    assert_with_msg(
+        (min_price_in_ticks..=max_price_in_ticks).contains(&best_price_in_ticks),
+        PhoenixError::MarketPriceOutOfBounds,
+        &format!(
+            "Best {:?} price {} is outside of [{}, {}]",
+            side, best_price_in_ticks, min_price_in_ticks, max_price_in_ticks
+        ),
+    )
+}
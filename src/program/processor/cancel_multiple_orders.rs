@@ -1,9 +1,13 @@
 use crate::{
     program::{
-        assert_with_msg, dispatch_market::load_with_dispatch_mut,
-        loaders::CancelOrWithdrawContext as Cancel, token_utils::try_withdraw,
-        validation::checkers::phoenix_checkers::MarketAccountInfo, MarketHeader, PhoenixError,
-        PhoenixMarketContext, PhoenixVaultContext,
+        assert_with_msg,
+        client_order_id_index::load_client_order_id_index_mut,
+        dispatch_market::{get_market_size, load_with_dispatch, load_with_dispatch_mut},
+        iceberg_reserve_book::{split_iceberg_reserve_book_mut, IcebergReserveBook},
+        loaders::CancelOrWithdrawContext as Cancel,
+        token_utils::try_withdraw,
+        validation::checkers::phoenix_checkers::MarketAccountInfo,
+        MarketHeader, PhoenixError, PhoenixMarketContext, PhoenixVaultContext,
     },
     quantities::{Ticks, WrapperU64},
     state::{
@@ -12,20 +16,36 @@ use crate::{
     },
 };
 use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::NodeAllocatorMap;
 use solana_program::{
     account_info::AccountInfo, entrypoint::ProgramResult, log::sol_log_compute_units,
-    pubkey::Pubkey,
+    program_error::ProgramError, pubkey::Pubkey,
 };
 use std::mem::size_of;
 
 use super::CancelOrderParams;
 
+/// Orders are considered from the most aggressive price outward. If multiple limits are set,
+/// cancellation stops as soon as any one of them is reached: `num_orders_to_search` bounds the
+/// number of orders inspected, `tick_limit` excludes orders less aggressive than the limit, and
+/// `num_orders_to_cancel` and `lots_to_free` bound the cancelled orders that remain.
 #[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
 pub struct CancelUpToParams {
     pub side: Side,
     pub tick_limit: Option<u64>,
     pub num_orders_to_search: Option<u32>,
     pub num_orders_to_cancel: Option<u32>,
+    /// Orders are cancelled until at least this many lots have been released: quote lots for
+    /// bids and base lots for asks. The last order cancelled is removed in full.
+    pub lots_to_free: Option<u64>,
+}
+
+impl CancelUpToParams {
+    /// Decodes the params, accepting instruction data from older clients that omit the trailing
+    /// `lots_to_free` parameter
+    pub(crate) fn decode(data: &[u8]) -> std::io::Result<Self> {
+        Self::try_from_slice(data).or_else(|_| Self::try_from_slice(&[data, &[0]].concat()))
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone)]
@@ -33,6 +53,38 @@ pub struct CancelMultipleOrdersByIdParams {
     pub orders: Vec<CancelOrderParams>,
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct CancelByClientOrderIdParams {
+    pub client_order_id: u128,
+}
+
+/// Orders are cancelled from the most recently placed (highest sequence number) to the oldest, so
+/// repeated calls make progress deterministically until none of the trader's orders remain.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct CancelAllOrdersBoundedParams {
+    pub max_orders: u32,
+    /// If set, at most this many orders are visited on each side of the book, starting from the
+    /// top. Every order visited counts, including expired orders and other traders' orders.
+    pub num_orders_to_search: Option<u32>,
+}
+
+impl CancelAllOrdersBoundedParams {
+    /// Instructions built before `num_orders_to_search` was added omit it, so it is decoded as
+    /// None when the data ends early.
+    pub(crate) fn decode(data: &[u8]) -> std::io::Result<Self> {
+        Self::try_from_slice(data).or_else(|_| Self::try_from_slice(&[data, &[0]].concat()))
+    }
+}
+
+/// Cancels all of the trader's orders on `side` priced within `[low_tick, high_tick]`, inclusive.
+/// Setting `low_tick` equal to `high_tick` cancels the orders at a single price level.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct CancelInPriceRangeParams {
+    pub side: Side,
+    pub low_tick: u64,
+    pub high_tick: u64,
+}
+
 pub(crate) fn process_cancel_all_orders<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
@@ -59,11 +111,232 @@ pub(crate) fn process_cancel_all_orders<'a, 'info>(
         num_quote_lots_out,
         ..
     } = {
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        sol_log_compute_units();
+        market
+            .cancel_all_orders(
+                trader.key,
+                claim_funds,
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
+            .unwrap_or_default()
+    };
+    sol_log_compute_units();
+
+    let header = market_info.get_header()?;
+
+    if let Some(PhoenixVaultContext {
+        base_account,
+        quote_account,
+        base_vault,
+        quote_vault,
+        token_program,
+        base_mint,
+        quote_mint,
+    }) = vault_context_option
+    {
+        try_withdraw(
+            market_info.key,
+            &header.base_params,
+            &header.quote_params,
+            &token_program,
+            quote_account.as_ref(),
+            quote_vault,
+            quote_mint.as_ref(),
+            base_account.as_ref(),
+            base_vault,
+            base_mint.as_ref(),
+            num_quote_lots_out * header.get_quote_lot_size(),
+            num_base_lots_out * header.get_base_lot_size(),
+        )?;
+    } else {
+        // This case is only reached if the user is cancelling orders with free funds
+        // In this case, there should be no funds to claim
+        assert_with_msg(
+            num_quote_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_quote_lots_out must be 0",
+        )?;
+        assert_with_msg(
+            num_base_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_base_lots_out must be 0",
+        )?;
+    }
+
+    drop(header);
+    Ok(())
+}
+
+/// Returns true if the trader still has orders on the book after this call.
+pub(crate) fn process_cancel_all_orders_bounded<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> Result<bool, ProgramError> {
+    let vault_context_option = if withdraw_funds {
+        let Cancel { vault_context } = Cancel::load(market_context, accounts)?;
+        Some(vault_context)
+    } else {
+        None
+    };
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    let CancelAllOrdersBoundedParams {
+        max_orders,
+        num_orders_to_search,
+    } = CancelAllOrdersBoundedParams::decode(data)?;
+
+    let claim_funds = vault_context_option.is_some();
+    let (
+        MatchingEngineResponse {
+            num_base_lots_out,
+            num_quote_lots_out,
+            ..
+        },
+        has_remaining_orders,
+    ) = {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        sol_log_compute_units();
+        market
+            .cancel_all_orders_bounded(
+                trader.key,
+                max_orders as usize,
+                num_orders_to_search.map(|n| n as usize),
+                claim_funds,
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
+            .unwrap_or_default()
+    };
+    sol_log_compute_units();
+
+    let header = market_info.get_header()?;
+
+    if let Some(PhoenixVaultContext {
+        base_account,
+        quote_account,
+        base_vault,
+        quote_vault,
+        token_program,
+        base_mint,
+        quote_mint,
+    }) = vault_context_option
+    {
+        try_withdraw(
+            market_info.key,
+            &header.base_params,
+            &header.quote_params,
+            &token_program,
+            quote_account.as_ref(),
+            quote_vault,
+            quote_mint.as_ref(),
+            base_account.as_ref(),
+            base_vault,
+            base_mint.as_ref(),
+            num_quote_lots_out * header.get_quote_lot_size(),
+            num_base_lots_out * header.get_base_lot_size(),
+        )?;
+    } else {
+        // This case is only reached if the user is cancelling orders with free funds
+        // In this case, there should be no funds to claim
+        assert_with_msg(
+            num_quote_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_quote_lots_out must be 0",
+        )?;
+        assert_with_msg(
+            num_base_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_base_lots_out must be 0",
+        )?;
+    }
+
+    drop(header);
+    Ok(has_remaining_orders)
+}
+
+pub(crate) fn process_cancel_in_price_range<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let vault_context_option = if withdraw_funds {
+        let Cancel { vault_context } = Cancel::load(market_context, accounts)?;
+        Some(vault_context)
+    } else {
+        None
+    };
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    let CancelInPriceRangeParams {
+        side,
+        low_tick,
+        high_tick,
+    } = CancelInPriceRangeParams::try_from_slice(data)?;
+    assert_with_msg(
+        low_tick <= high_tick,
+        PhoenixError::CancelMultipleOrdersError,
+        "Low tick must be less than or equal to high tick",
+    )?;
+
+    let claim_funds = vault_context_option.is_some();
+    let MatchingEngineResponse {
+        num_base_lots_out,
+        num_quote_lots_out,
+        ..
+    } = {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         sol_log_compute_units();
         market
-            .cancel_all_orders(trader.key, claim_funds, record_event_fn)
+            .cancel_in_price_range(
+                trader.key,
+                side,
+                Ticks::new(low_tick),
+                Ticks::new(high_tick),
+                claim_funds,
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
             .unwrap_or_default()
     };
     sol_log_compute_units();
@@ -76,6 +349,8 @@ pub(crate) fn process_cancel_all_orders<'a, 'info>(
         base_vault,
         quote_vault,
         token_program,
+        base_mint,
+        quote_mint,
     }) = vault_context_option
     {
         try_withdraw(
@@ -85,8 +360,10 @@ pub(crate) fn process_cancel_all_orders<'a, 'info>(
             &token_program,
             quote_account.as_ref(),
             quote_vault,
+            quote_mint.as_ref(),
             base_account.as_ref(),
             base_vault,
+            base_mint.as_ref(),
             num_quote_lots_out * header.get_quote_lot_size(),
             num_base_lots_out * header.get_base_lot_size(),
         )?;
@@ -129,7 +406,7 @@ pub(crate) fn process_cancel_up_to<'a, 'info>(
         signer: trader,
     } = market_context;
 
-    let params = CancelUpToParams::try_from_slice(data)?;
+    let params = CancelUpToParams::decode(data)?;
     process_cancel_orders(
         market_info,
         trader.key,
@@ -139,6 +416,8 @@ pub(crate) fn process_cancel_up_to<'a, 'info>(
     )
 }
 
+/// Returns a bitmap with one bit per requested order, in request order. A bit is set if the order
+/// was resting on the book and owned by the trader, so that it was actually cancelled.
 pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
@@ -146,7 +425,7 @@ pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
     data: &[u8],
     withdraw_funds: bool,
     record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
-) -> ProgramResult {
+) -> Result<Vec<u8>, ProgramError> {
     let vault_context_option = if withdraw_funds {
         let Cancel { vault_context } = Cancel::load(market_context, accounts)?;
         Some(vault_context)
@@ -162,21 +441,32 @@ pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
     let cancel_params = CancelMultipleOrdersByIdParams::try_from_slice(data)?;
     if cancel_params.orders.is_empty() {
         phoenix_log!("No orders to cancel");
-        return Ok(());
+        return Ok(vec![]);
     }
 
-    let MatchingEngineResponse {
-        num_quote_lots_out,
-        num_base_lots_out,
-        ..
-    } = {
+    let (
+        MatchingEngineResponse {
+            num_quote_lots_out,
+            num_base_lots_out,
+            ..
+        },
+        orders_found,
+    ) = {
         sol_log_compute_units();
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
-        let orders_to_cancel = cancel_params
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let trader_index = market.get_trader_index(trader.key);
+        let order_ids = cancel_params
             .orders
             .iter()
-            .filter_map(
+            .map(
                 |CancelOrderParams {
                      side,
                      price_in_ticks,
@@ -194,11 +484,155 @@ pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
             )
             .collect::<Vec<_>>();
 
+        // Bit i is set if the i-th requested order was resting on the book and owned by the trader
+        let mut orders_found = vec![0_u8; (order_ids.len() + 7) / 8];
+        for (i, order_id) in order_ids.iter().enumerate() {
+            let found = order_id
+                .and_then(|order_id| market.get_order(&order_id))
+                .zip(trader_index)
+                .map_or(false, |(order, trader_index)| {
+                    order.trader_index == trader_index as u64
+                });
+            if found {
+                orders_found[i / 8] |= 1 << (i % 8);
+            }
+        }
+        let orders_to_cancel = order_ids.into_iter().flatten().collect::<Vec<_>>();
+
+        let response = market
+            .cancel_multiple_orders_by_id(
+                trader.key,
+                &orders_to_cancel,
+                vault_context_option.is_some(),
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
+            .unwrap_or_default();
+        (response, orders_found)
+    };
+    sol_log_compute_units();
+
+    let header = market_info.get_header()?;
+
+    if let Some(PhoenixVaultContext {
+        base_account,
+        quote_account,
+        base_vault,
+        quote_vault,
+        token_program,
+        base_mint,
+        quote_mint,
+    }) = vault_context_option
+    {
+        try_withdraw(
+            market_info.key,
+            &header.base_params,
+            &header.quote_params,
+            &token_program,
+            quote_account.as_ref(),
+            quote_vault,
+            quote_mint.as_ref(),
+            base_account.as_ref(),
+            base_vault,
+            base_mint.as_ref(),
+            num_quote_lots_out * header.get_quote_lot_size(),
+            num_base_lots_out * header.get_base_lot_size(),
+        )?;
+    } else {
+        // This case is only reached if the user is cancelling orders with free funds
+        // In this case, there should be no funds to claim
+        assert_with_msg(
+            num_quote_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_quote_lots_out must be 0",
+        )?;
+        assert_with_msg(
+            num_base_lots_out == 0,
+            PhoenixError::CancelMultipleOrdersError,
+            "WARNING: num_base_lots_out must be 0",
+        )?;
+    }
+
+    Ok(orders_found)
+}
+
+/// This function cancels all of the trader's resting orders that were placed with the given client
+/// order id. It can only be used on markets that were initialized with the client order id index.
+pub(crate) fn process_cancel_by_client_order_id<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let vault_context_option = if withdraw_funds {
+        let Cancel { vault_context } = Cancel::load(market_context, accounts)?;
+        Some(vault_context)
+    } else {
+        None
+    };
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    assert_with_msg(
+        market_info.get_header()?.client_order_id_index_enabled != 0,
+        PhoenixError::ClientOrderIdIndexError,
+        "Market does not have a client order id index",
+    )?;
+    let CancelByClientOrderIdParams { client_order_id } =
+        CancelByClientOrderIdParams::try_from_slice(data)?;
+
+    sol_log_compute_units();
+    let orders_to_cancel = {
+        let market_size = get_market_size(&market_info.size_params)?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, index_bytes) = data.split_at_mut(market_size);
+        let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+        let index = load_client_order_id_index_mut(&market_info.size_params, index_bytes)?;
+        let orders_to_cancel = market
+            .get_orders_for_trader(trader.key)
+            .into_iter()
+            .filter(|(order_id, _)| {
+                index
+                    .get(&order_id.order_sequence_number)
+                    .map(|entry| entry.client_order_id() == client_order_id)
+                    .unwrap_or(false)
+            })
+            .map(|(order_id, _)| order_id)
+            .collect::<Vec<_>>();
+        if orders_to_cancel.is_empty() {
+            phoenix_log!("No orders found with client order id {}", client_order_id);
+            return Ok(());
+        }
+        for order_id in orders_to_cancel.iter() {
+            index.remove(&order_id.order_sequence_number);
+        }
+        orders_to_cancel
+    };
+    let MatchingEngineResponse {
+        num_quote_lots_out,
+        num_base_lots_out,
+        ..
+    } = {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         market
             .cancel_multiple_orders_by_id(
                 trader.key,
                 &orders_to_cancel,
                 vault_context_option.is_some(),
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
                 record_event_fn,
             )
             .unwrap_or_default()
@@ -213,6 +647,8 @@ pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
         base_vault,
         quote_vault,
         token_program,
+        base_mint,
+        quote_mint,
     }) = vault_context_option
     {
         try_withdraw(
@@ -222,8 +658,10 @@ pub(crate) fn process_cancel_multiple_orders_by_id<'a, 'info>(
             &token_program,
             quote_account.as_ref(),
             quote_vault,
+            quote_mint.as_ref(),
             base_account.as_ref(),
             base_vault,
+            base_mint.as_ref(),
             num_quote_lots_out * header.get_quote_lot_size(),
             num_base_lots_out * header.get_base_lot_size(),
         )?;
@@ -258,24 +696,38 @@ pub(crate) fn process_cancel_orders<'a, 'info>(
         tick_limit,
         num_orders_to_search,
         num_orders_to_cancel,
+        lots_to_free,
     } = cancel_params;
 
     let claim_funds = vault_context_option.is_some();
     let released = {
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         sol_log_compute_units();
-        market
-            .cancel_up_to(
-                trader_key,
-                side,
-                num_orders_to_search.map(|x| x as usize),
-                num_orders_to_cancel.map(|x| x as usize),
-                tick_limit.map(Ticks::new),
-                claim_funds,
-                record_event_fn,
-            )
-            .unwrap_or_default()
+        let trader_registered = market.get_trader_index(trader_key).is_some();
+        match market.cancel_up_to(
+            trader_key,
+            side,
+            num_orders_to_search.map(|x| x as usize),
+            num_orders_to_cancel.map(|x| x as usize),
+            tick_limit.map(Ticks::new),
+            lots_to_free,
+            claim_funds,
+            iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+            record_event_fn,
+        ) {
+            Some(released) => released,
+            // Trader has no seat on the market, so there is nothing to cancel
+            None if !trader_registered => MatchingEngineResponse::default(),
+            None => return Err(PhoenixError::CancelMultipleOrdersError.into()),
+        }
     };
     sol_log_compute_units();
 
@@ -292,6 +744,8 @@ pub(crate) fn process_cancel_orders<'a, 'info>(
         base_vault,
         quote_vault,
         token_program,
+        base_mint,
+        quote_mint,
     }) = vault_context_option
     {
         try_withdraw(
@@ -301,8 +755,10 @@ pub(crate) fn process_cancel_orders<'a, 'info>(
             &token_program,
             quote_account.as_ref(),
             quote_vault,
+            quote_mint.as_ref(),
             base_account.as_ref(),
             base_vault,
+            base_mint.as_ref(),
             num_quote_lots_out * header.get_quote_lot_size(),
             num_base_lots_out * header.get_base_lot_size(),
         )?;
@@ -323,3 +779,57 @@ pub(crate) fn process_cancel_orders<'a, 'info>(
 
     Ok(())
 }
+
+#[test]
+fn test_decode_cancel_up_to_params_without_lots_to_free() {
+    // Layout of CancelUpToParams before `lots_to_free` was added
+    #[derive(BorshSerialize)]
+    struct LegacyCancelUpToParams {
+        side: Side,
+        tick_limit: Option<u64>,
+        num_orders_to_search: Option<u32>,
+        num_orders_to_cancel: Option<u32>,
+    }
+    let data = LegacyCancelUpToParams {
+        side: Side::Ask,
+        tick_limit: Some(100),
+        num_orders_to_search: None,
+        num_orders_to_cancel: Some(3),
+    }
+    .try_to_vec()
+    .unwrap();
+    let params = CancelUpToParams::decode(&data).unwrap();
+    assert_eq!(params.side, Side::Ask);
+    assert_eq!(params.tick_limit, Some(100));
+    assert_eq!(params.num_orders_to_search, None);
+    assert_eq!(params.num_orders_to_cancel, Some(3));
+    assert_eq!(params.lots_to_free, None);
+
+    let data = CancelUpToParams {
+        lots_to_free: Some(1_000),
+        ..params
+    }
+    .try_to_vec()
+    .unwrap();
+    let params = CancelUpToParams::decode(&data).unwrap();
+    assert_eq!(params.lots_to_free, Some(1_000));
+    assert_eq!(params.num_orders_to_cancel, Some(3));
+}
+
+#[test]
+fn test_decode_cancel_all_orders_bounded_params_without_num_orders_to_search() {
+    // Layout of CancelAllOrdersBoundedParams before `num_orders_to_search` was added
+    let data = 5_u32.try_to_vec().unwrap();
+    let params = CancelAllOrdersBoundedParams::decode(&data).unwrap();
+    assert_eq!(params.max_orders, 5);
+    assert_eq!(params.num_orders_to_search, None);
+
+    let data = CancelAllOrdersBoundedParams {
+        max_orders: 5,
+        num_orders_to_search: Some(100),
+    }
+    .try_to_vec()
+    .unwrap();
+    let params = CancelAllOrdersBoundedParams::decode(&data).unwrap();
+    assert_eq!(params.num_orders_to_search, Some(100));
+}
@@ -1,9 +1,16 @@
 use crate::{
     program::{
-        dispatch_market::load_with_dispatch_mut, loaders::DepositContext, token_utils::try_deposit,
+        dispatch_market::load_with_dispatch_mut,
+        loaders::{DepositContext, NativeSolContext},
+        token_utils::{
+            close_native_sol_account, create_native_sol_account, try_deposit, wrap_native_sol,
+        },
         MarketHeader, PhoenixError, PhoenixMarketContext, PhoenixVaultContext,
     },
-    quantities::{BaseLots, QuoteLots, WrapperU64},
+    quantities::{
+        BaseAtoms, BaseAtomsPerBaseLot, BaseLots, QuoteAtoms, QuoteAtomsPerQuoteLot, QuoteLots,
+        WrapperU64,
+    },
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
@@ -15,11 +22,111 @@ pub struct DepositParams {
     pub base_lots_to_deposit: u64,
 }
 
+/// Deposit amounts in token atoms. The amounts are rounded down to whole lots, and the atoms that
+/// do not make up a whole lot are not transferred.
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct DepositAtomsParams {
+    pub quote_atoms_to_deposit: u64,
+    pub base_atoms_to_deposit: u64,
+}
+
 pub(crate) fn process_deposit_funds<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
     accounts: &'a [AccountInfo<'info>],
     data: &[u8],
+) -> ProgramResult {
+    let deposit_params = DepositParams::try_from_slice(data)?;
+    process_deposit(market_context, accounts, deposit_params)
+}
+
+/// Deposits the whole lots contained in the atom amounts of `DepositAtomsParams`. The remainder of
+/// each amount stays in the trader's token account. The accounts are the same as the accounts of
+/// `DepositFunds`.
+pub(crate) fn process_deposit_atoms<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let DepositAtomsParams {
+        quote_atoms_to_deposit,
+        base_atoms_to_deposit,
+    } = DepositAtomsParams::try_from_slice(data)?;
+    let (quote_lot_size, base_lot_size) = {
+        let header = market_context.market_info.get_header()?;
+        (header.get_quote_lot_size(), header.get_base_lot_size())
+    };
+    let quote_atoms = QuoteAtoms::new(quote_atoms_to_deposit);
+    let base_atoms = BaseAtoms::new(base_atoms_to_deposit);
+    let quote_lots = quote_atoms.unchecked_div::<QuoteAtomsPerQuoteLot, QuoteLots>(quote_lot_size);
+    let base_lots = base_atoms.unchecked_div::<BaseAtomsPerBaseLot, BaseLots>(base_lot_size);
+    let quote_atoms_remainder = quote_atoms - quote_lots * quote_lot_size;
+    let base_atoms_remainder = base_atoms - base_lots * base_lot_size;
+    if quote_atoms_remainder > QuoteAtoms::ZERO || base_atoms_remainder > BaseAtoms::ZERO {
+        phoenix_log!(
+            "{} quote atoms and {} base atoms are less than a lot and are not deposited",
+            quote_atoms_remainder,
+            base_atoms_remainder
+        );
+    }
+    process_deposit(
+        market_context,
+        accounts,
+        DepositParams {
+            quote_lots_to_deposit: quote_lots.as_u64(),
+            base_lots_to_deposit: base_lots.as_u64(),
+        },
+    )
+}
+
+/// Creates the trader's temporary wrapped SOL account, wraps the SOL needed for the quote deposit
+/// into it, deposits the funds, and closes the account back to the trader. The system program and
+/// the native mint are passed after the accounts of `DepositFunds`.
+pub(crate) fn process_deposit_funds_with_native_sol<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let deposit_params = DepositParams::try_from_slice(data)?;
+    let NativeSolContext {
+        accounts,
+        native_sol_account,
+        native_sol_account_bump,
+        token_program,
+        system_program,
+        native_mint,
+    } = NativeSolContext::load(market_context, accounts, true)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    create_native_sol_account(
+        market_info.key,
+        trader.as_ref(),
+        native_sol_account,
+        native_sol_account_bump,
+        &native_mint,
+        token_program.as_ref(),
+        system_program.as_ref(),
+    )?;
+    let quote_lot_size = market_info.get_header()?.get_quote_lot_size();
+    wrap_native_sol(
+        (QuoteLots::new(deposit_params.quote_lots_to_deposit) * quote_lot_size).as_u64(),
+        trader.as_ref(),
+        native_sol_account,
+        token_program.as_ref(),
+        system_program.as_ref(),
+    )?;
+    process_deposit(market_context, accounts, deposit_params)?;
+    close_native_sol_account(trader.as_ref(), native_sol_account, token_program.as_ref())
+}
+
+pub(crate) fn process_deposit<'a, 'info>(
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    deposit_params: DepositParams,
 ) -> ProgramResult {
     let DepositContext {
         vault_context:
@@ -29,13 +136,15 @@ pub(crate) fn process_deposit_funds<'a, 'info>(
                 base_vault,
                 quote_vault,
                 token_program,
+                base_mint,
+                quote_mint,
             },
         ..
     } = DepositContext::load(market_context, accounts)?;
     let DepositParams {
         quote_lots_to_deposit,
         base_lots_to_deposit,
-    } = DepositParams::try_from_slice(data)?;
+    } = deposit_params;
 
     let quote_lots = QuoteLots::new(quote_lots_to_deposit);
     let base_lots = BaseLots::new(base_lots_to_deposit);
@@ -45,31 +154,55 @@ pub(crate) fn process_deposit_funds<'a, 'info>(
         signer: trader,
     } = market_context;
 
+    let (quote_lot_size, base_lot_size) = {
+        let header = market_info.get_header()?;
+        (header.get_quote_lot_size(), header.get_base_lot_size())
+    };
+
+    let (quote_atoms_received, base_atoms_received) = try_deposit(
+        token_program.as_ref(),
+        quote_account,
+        quote_vault,
+        quote_mint.as_ref(),
+        base_account,
+        base_vault,
+        base_mint.as_ref(),
+        quote_lots * quote_lot_size,
+        base_lots * base_lot_size,
+        trader,
+    )?;
+
+    // The trader is only credited with the lots that arrived in the vault, so the vault always
+    // covers the balances of the market. Any remaining dust stays in the vault.
+    let quote_lots_received =
+        quote_atoms_received.unchecked_div::<QuoteAtomsPerQuoteLot, QuoteLots>(quote_lot_size);
+    let base_lots_received =
+        base_atoms_received.unchecked_div::<BaseAtomsPerBaseLot, BaseLots>(base_lot_size);
+    if quote_lots_received < quote_lots || base_lots_received < base_lots {
+        phoenix_log!(
+            "Vault only received {} quote lots and {} base lots",
+            quote_lots_received,
+            base_lots_received
+        );
+    }
+
     {
         let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         market
             .get_or_register_trader(trader.key)
             .ok_or(PhoenixError::TraderNotFound)?;
         let trader_state = market
             .get_trader_state_mut(trader.key)
             .ok_or(PhoenixError::TraderNotFound)?;
-        trader_state.deposit_free_base_lots(base_lots);
-        trader_state.deposit_free_quote_lots(quote_lots);
+        trader_state.deposit_free_base_lots(base_lots_received);
+        trader_state.deposit_free_quote_lots(quote_lots_received);
     }
 
-    let header = market_info.get_header()?;
-
-    try_deposit(
-        token_program.as_ref(),
-        quote_account,
-        quote_vault,
-        base_account,
-        base_vault,
-        quote_lots * header.get_quote_lot_size(),
-        base_lots * header.get_base_lot_size(),
-        trader,
-    )?;
-
     Ok(())
 }
@@ -2,13 +2,19 @@ use std::mem::size_of;
 
 use crate::{
     program::{
-        assert_with_msg, load_with_dispatch_mut,
+        assert_with_msg, load_with_dispatch, load_with_dispatch_mut,
+        referral_fee_book::{get_referral_fee_book_offset_from_header, load_referral_fee_book_mut},
         token_utils::{get_decimal_string, maybe_invoke_withdraw},
-        ChangeFeeRecipientContext, CollectFeesContext, MarketHeader, PhoenixMarketContext,
+        ChangeFeeRecipientContext, ClaimReferralFeesContext, CollectFeesContext,
+        CollectFeesSplitContext, MarketHeader, PhoenixError, PhoenixMarketContext,
     },
     quantities::{QuoteLots, WrapperU64},
-    state::markets::MarketEvent,
+    state::{
+        markets::{MarketEvent, NUM_FEE_TIERS},
+        FeeRoundingMode,
+    },
 };
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
@@ -25,6 +31,7 @@ pub(crate) fn process_collect_fees<'a, 'info>(
         fee_recipient_token_account,
         quote_vault,
         token_program,
+        quote_mint,
     } = CollectFeesContext::load(market_context, accounts)?;
 
     let PhoenixMarketContext {
@@ -34,7 +41,12 @@ pub(crate) fn process_collect_fees<'a, 'info>(
 
     let num_quote_lots_out = {
         let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         market.collect_fees(record_event_fn)
     };
 
@@ -53,10 +65,246 @@ pub(crate) fn process_collect_fees<'a, 'info>(
         token_program.as_ref(),
         fee_recipient_token_account.as_ref(),
         &quote_vault,
+        quote_mint.as_ref(),
     )?;
     Ok(())
 }
 
+/// Withdraws the referral fees accrued to the signer to their quote token account. The referrer
+/// stays registered in the `ReferralFeeBook`.
+pub(crate) fn process_claim_referral_fees<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    _data: &[u8],
+) -> ProgramResult {
+    let ClaimReferralFeesContext {
+        referrer_token_account,
+        quote_vault,
+        token_program,
+        quote_mint,
+    } = ClaimReferralFeesContext::load(market_context, accounts)?;
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: referrer,
+    } = market_context;
+
+    let referral_fee_book_offset =
+        get_referral_fee_book_offset_from_header(&market_info.get_header()?)?.ok_or_else(|| {
+            phoenix_log!("Referral fees are not enabled on this market");
+            PhoenixError::ReferralFeeError
+        })?;
+    let num_quote_lots_out = {
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, referral_fee_book_bytes) = data.split_at_mut(referral_fee_book_offset);
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let num_quote_lots_out =
+            load_referral_fee_book_mut(referral_fee_book_bytes)?.claim(referrer.key);
+        assert_with_msg(
+            market.claim_referral_fees(num_quote_lots_out).is_some(),
+            PhoenixError::ReferralFeeError,
+            "The market holds fewer referral fees than the referrer is owed",
+        )?;
+        num_quote_lots_out
+    };
+
+    let header = market_info.get_header()?;
+    let quote_atoms_claimed = num_quote_lots_out * header.get_quote_lot_size();
+    phoenix_log!(
+        "Claimed {} in referral fees",
+        get_decimal_string(quote_atoms_claimed.as_u64(), header.quote_params.decimals)
+    );
+
+    maybe_invoke_withdraw(
+        market_info.key,
+        &header.quote_params.mint_key,
+        header.quote_params.vault_bump as u8,
+        quote_atoms_claimed.as_u64(),
+        token_program.as_ref(),
+        referrer_token_account.as_ref(),
+        &quote_vault,
+        quote_mint.as_ref(),
+    )?;
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to transfer the quote
+/// tokens in the vault that are not owed to anyone to the fee recipient.
+///
+/// The quote vault must always hold at least the quote lots tracked by the market (the free and
+/// locked quote lots of every trader plus the unclaimed fees) times the quote lot size. Rounding
+/// and direct transfers into the vault can leave it holding more than that. After this
+/// instruction, the vault balance is exactly equal to the tracked amount.
+pub(crate) fn process_sweep_dust<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    _data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+
+    let CollectFeesContext {
+        fee_recipient_token_account,
+        quote_vault,
+        token_program,
+        quote_mint,
+    } = CollectFeesContext::load(market_context, accounts)?;
+
+    let tracked_quote_lots = {
+        let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+        market.get_tracked_quote_lots()
+    };
+
+    let header = market_info.get_header()?;
+    let tracked_quote_atoms = tracked_quote_lots * header.get_quote_lot_size();
+    let vault_quote_atoms = quote_vault.amount()?;
+    assert_with_msg(
+        vault_quote_atoms >= tracked_quote_atoms.as_u64(),
+        PhoenixError::SweepDustError,
+        "Quote vault holds less than the market's tracked quote lots",
+    )?;
+    let dust = vault_quote_atoms - tracked_quote_atoms.as_u64();
+    phoenix_log!(
+        "Swept {} of dust from the quote vault",
+        get_decimal_string(dust, header.quote_params.decimals)
+    );
+
+    maybe_invoke_withdraw(
+        market_info.key,
+        &header.quote_params.mint_key,
+        header.quote_params.vault_bump as u8,
+        dust,
+        token_program.as_ref(),
+        fee_recipient_token_account.as_ref(),
+        &quote_vault,
+        quote_mint.as_ref(),
+    )?;
+    Ok(())
+}
+
+/// The maximum number of recipients that fees can be split between in a single instruction
+pub const MAX_FEE_SPLIT_RECIPIENTS: usize = 8;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSplit {
+    /// Owner of the quote token account that receives this share of the fees
+    pub recipient: Pubkey,
+    pub bps: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollectFeesSplitParams {
+    /// The bps of all splits must sum to 10000
+    pub splits: Vec<FeeSplit>,
+}
+
+/// This function can only be called by the current market authority to
+/// collect the unclaimed fees and divide them between multiple recipients.
+/// Any rounding remainder is sent to the first recipient.
+pub(crate) fn process_collect_fees_split<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let CollectFeesSplitParams { splits } = CollectFeesSplitParams::try_from_slice(data)?;
+    assert_with_msg(
+        !splits.is_empty() && splits.len() <= MAX_FEE_SPLIT_RECIPIENTS,
+        ProgramError::InvalidInstructionData,
+        &format!(
+            "Fees must be split between 1 and {} recipients, got {}",
+            MAX_FEE_SPLIT_RECIPIENTS,
+            splits.len()
+        ),
+    )?;
+    let total_bps = splits
+        .iter()
+        .try_fold(0_u64, |acc, split| acc.checked_add(split.bps));
+    assert_with_msg(
+        total_bps == Some(10_000),
+        ProgramError::InvalidInstructionData,
+        "Fee split bps must sum to 10000",
+    )?;
+
+    let recipients = splits
+        .iter()
+        .map(|split| split.recipient)
+        .collect::<Vec<_>>();
+    let CollectFeesSplitContext {
+        quote_vault,
+        token_program,
+        quote_mint,
+        recipient_token_accounts,
+    } = CollectFeesSplitContext::load(market_context, accounts, &recipients)?;
+
+    let PhoenixMarketContext { market_info, .. } = market_context;
+
+    let num_quote_lots_out = {
+        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        market.collect_fees(record_event_fn)
+    };
+
+    let header = market_info.get_header()?;
+    phoenix_log!(
+        "Collected {} in fees",
+        get_decimal_string(
+            (num_quote_lots_out * header.get_quote_lot_size()).as_u64(),
+            header.quote_params.decimals
+        )
+    );
+
+    let shares = split_quote_lots(num_quote_lots_out, &splits);
+    for (num_quote_lots, recipient_token_account) in
+        shares.into_iter().zip(recipient_token_accounts.iter())
+    {
+        maybe_invoke_withdraw(
+            market_info.key,
+            &header.quote_params.mint_key,
+            header.quote_params.vault_bump as u8,
+            (num_quote_lots * header.get_quote_lot_size()).as_u64(),
+            token_program.as_ref(),
+            recipient_token_account.as_ref(),
+            &quote_vault,
+            quote_mint.as_ref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Divides `num_quote_lots` according to the bps of each split. The splits are assumed to sum to
+/// 10000 bps, and the rounding remainder is assigned to the first split.
+pub(crate) fn split_quote_lots(num_quote_lots: QuoteLots, splits: &[FeeSplit]) -> Vec<QuoteLots> {
+    let mut shares = splits
+        .iter()
+        .map(|split| QuoteLots::new((num_quote_lots.as_u128() * split.bps as u128 / 10_000) as u64))
+        .collect::<Vec<_>>();
+    let distributed = shares
+        .iter()
+        .fold(QuoteLots::ZERO, |acc, share| acc + *share);
+    if let Some(first) = shares.first_mut() {
+        *first += num_quote_lots - distributed;
+    }
+    shares
+}
+
 pub(crate) fn process_change_fee_recipient<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
@@ -71,9 +319,13 @@ pub(crate) fn process_change_fee_recipient<'a, 'info>(
 
     let uncollected_fees = {
         let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        load_with_dispatch_mut(&market_info.size_params, market_bytes)?
-            .inner
-            .get_uncollected_fee_amount()
+        load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner
+        .get_uncollected_fee_amount()
     };
 
     let mut header = market_info.get_header_mut()?;
@@ -87,3 +339,159 @@ pub(crate) fn process_change_fee_recipient<'a, 'info>(
     header.fee_recipient = *new_fee_recipient.key;
     Ok(())
 }
+
+/// This function can only be called by the current market authority to
+/// modify the maker rebate. The rebate can never exceed the taker fee.
+pub(crate) fn process_change_maker_rebate<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let maker_rebate_bps = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    assert_with_msg(
+        maker_rebate_bps <= market.get_taker_fee_bps(),
+        ProgramError::InvalidArgument,
+        &format!(
+            "Maker rebate ({} bps) cannot exceed the taker fee ({} bps)",
+            maker_rebate_bps,
+            market.get_taker_fee_bps()
+        ),
+    )?;
+    market.set_maker_rebate(maker_rebate_bps);
+    phoenix_log!("Maker rebate changed to {} bps", maker_rebate_bps);
+    Ok(())
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct SetTraderFeeTierParams {
+    pub trader: Pubkey,
+    /// 0 is the default tier, tiers 1 through `NUM_FEE_TIERS` use the market's fee tier table
+    pub fee_tier: u8,
+}
+
+/// This function can only be called by the current market authority to change how
+/// taker fees are rounded to a whole number of quote lots.
+pub(crate) fn process_change_fee_rounding_mode<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let fee_rounding_mode = FeeRoundingMode::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_fee_rounding_mode(fee_rounding_mode);
+    phoenix_log!("Fee rounding mode changed to {:?}", fee_rounding_mode);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to set the minimum fee
+/// charged on trades that match any base lots. Setting it to zero removes the minimum.
+pub(crate) fn process_change_min_fee<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let min_fee_quote_lots = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_min_fee_quote_lots(QuoteLots::new(min_fee_quote_lots));
+    phoenix_log!("Minimum fee changed to {} quote lots", min_fee_quote_lots);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to
+/// modify the taker fees of fee tiers 1 through `NUM_FEE_TIERS`.
+pub(crate) fn process_change_fee_tiers<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let fee_tiers_bps = <[u64; NUM_FEE_TIERS]>::try_from_slice(data)?;
+    for fee_tier_bps in fee_tiers_bps.iter() {
+        assert_with_msg(
+            *fee_tier_bps <= 10_000,
+            ProgramError::InvalidArgument,
+            &format!("Fee tier ({} bps) cannot exceed 10000 bps", fee_tier_bps),
+        )?;
+    }
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_fee_tiers(fee_tiers_bps);
+    phoenix_log!("Fee tiers changed to {:?} bps", fee_tiers_bps);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to
+/// assign a fee tier to a trader with a seat on the market.
+pub(crate) fn process_set_trader_fee_tier<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let SetTraderFeeTierParams { trader, fee_tier } = SetTraderFeeTierParams::try_from_slice(data)?;
+    assert_with_msg(
+        fee_tier as usize <= NUM_FEE_TIERS,
+        ProgramError::InvalidArgument,
+        &format!("Fee tier {} does not exist", fee_tier),
+    )?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market
+        .get_trader_state_mut(&trader)
+        .ok_or(PhoenixError::TraderNotFound)?
+        .fee_tier = fee_tier;
+    phoenix_log!("Trader {} assigned to fee tier {}", trader, fee_tier);
+    Ok(())
+}
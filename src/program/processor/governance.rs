@@ -6,16 +6,45 @@ use super::{
 };
 use crate::{
     program::{
-        error::assert_with_msg, load_with_dispatch_mut, status::MarketStatus,
-        AuthorizedActionContext, ChangeMarketStatusContext, MarketHeader, PhoenixMarketContext,
+        client_order_id_index::{
+            get_client_order_id_index_size, initialize_client_order_id_index,
+            load_client_order_id_index, load_client_order_id_index_mut,
+        },
+        dispatch_market::load_with_dispatch_init,
+        error::{assert_with_msg, PhoenixError},
+        get_market_size,
+        iceberg_reserve_book::{
+            get_iceberg_reserve_book_offset_from_header, get_iceberg_reserve_book_size,
+            load_iceberg_reserve_book, load_iceberg_reserve_book_mut,
+        },
+        is_valid_market_symbol, load_with_dispatch, load_with_dispatch_mut,
+        recent_fills::get_recent_fills_size,
+        referral_fee_book::{
+            get_referral_fee_book_offset_from_header, get_referral_fee_book_size,
+            load_referral_fee_book_mut,
+        },
+        status::MarketStatus,
+        stop_order_book::{
+            get_stop_order_book_offset, get_stop_order_book_size, load_stop_order_book,
+        },
+        AuthorizedActionContext, AuthorizedActionWithFreeFundsContext, ChangeMarketStatusContext,
+        MarketHeader, MarketSizeParams, PhoenixMarketContext, ResizeMarketContext,
+        MARKET_SYMBOL_LEN,
     },
-    quantities::QuoteLots,
+    quantities::{BaseLots, QuoteLots, QuoteLotsPerBaseUnitPerTick, WrapperU64},
     state::{markets::MarketEvent, Side},
 };
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::NodeAllocatorMap;
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    pubkey::Pubkey, system_program,
+    account_info::AccountInfo,
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
 };
 
 /// This action can be taken by the market authority to remove the seat (on the Market account) of a
@@ -63,7 +92,28 @@ pub(crate) fn process_force_cancel_orders<'a, 'info>(
         &market_context.market_info,
         trader.key,
         Some(vault_context),
-        CancelUpToParams::try_from_slice(data)?,
+        CancelUpToParams::decode(data)?,
+        record_event_fn,
+    )
+}
+
+/// This action can be taken by the market authority to cancel the orders of a trader without
+/// transferring any tokens. The released funds are credited to the trader's free lots and can be
+/// withdrawn later. This path works even if a vault or the trader's token accounts are frozen.
+pub(crate) fn process_force_cancel_orders_with_free_funds<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let AuthorizedActionWithFreeFundsContext { trader, .. } =
+        AuthorizedActionWithFreeFundsContext::load(market_context, accounts)?;
+    process_cancel_orders(
+        &market_context.market_info,
+        trader.key,
+        None,
+        CancelUpToParams::decode(data)?,
         record_event_fn,
     )
 }
@@ -83,7 +133,7 @@ pub(crate) fn process_claim_authority<'a, 'info>(
     Ok(())
 }
 
-/// The authority can be changed to a successor, but the successor must explicitly claim the 
+/// The authority can be changed to a successor, but the successor must explicitly claim the
 /// authority from the previous market authority
 pub(crate) fn process_name_successor<'a, 'info>(
     _program_id: &Pubkey,
@@ -100,6 +150,571 @@ pub(crate) fn process_name_successor<'a, 'info>(
     Ok(())
 }
 
+/// This function can only be called by the current market authority to enable
+/// or disable the top of book checkpoint event emitted after each instruction
+pub(crate) fn process_change_book_checkpoints<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let book_checkpoints_enabled = bool::try_from_slice(data)?;
+    market_info.get_header_mut()?.book_checkpoints_enabled = book_checkpoints_enabled as u64;
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to enable
+/// or disable the fill price accumulator. Enabling the accumulator resets its totals.
+pub(crate) fn process_change_price_accumulator<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let price_accumulator_enabled = bool::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_price_accumulator_enabled(price_accumulator_enabled);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to enable or disable
+/// tracking of each trader's cumulative volume and fill count. Existing totals are kept.
+pub(crate) fn process_change_trader_volume_tracking<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let trader_volume_tracking_enabled = bool::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_trader_volume_tracking_enabled(trader_volume_tracking_enabled);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to allow or disallow orders
+/// that take liquidity. While taking is disabled, only orders that rest on the book without
+/// crossing can be placed. Cancels and withdrawals are unaffected.
+pub(crate) fn process_change_taking_disabled<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let taking_disabled = bool::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_taking_disabled(taking_disabled);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to choose which event types
+/// are left out of the event log (see `MarketHeader::disabled_event_types`). Disabling events
+/// reduces the size and compute cost of the log, at the expense of the clients that read them.
+pub(crate) fn process_change_disabled_event_types<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let disabled_event_types = u64::try_from_slice(data)?;
+    market_info.get_header_mut()?.disabled_event_types = disabled_event_types;
+    phoenix_log!("Disabled event types set to {:#b}", disabled_event_types);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to set the share of each
+/// taker fee, in basis points of the fee, that is accrued to the referrer named by the order.
+/// The market must have been initialized with referral fees enabled.
+pub(crate) fn process_change_referral_fee<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let referral_fee_bps = u64::try_from_slice(data)?;
+    assert_with_msg(
+        market_info.get_header()?.referral_fees_enabled != 0,
+        PhoenixError::ReferralFeeError,
+        "Referral fees are not enabled on this market",
+    )?;
+    assert_with_msg(
+        referral_fee_bps <= 10_000,
+        PhoenixError::ReferralFeeError,
+        "The referral fee cannot be more than the entire taker fee",
+    )?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_referral_fee_bps(referral_fee_bps);
+    phoenix_log!(
+        "Referral fee set to {} bps of the taker fee",
+        referral_fee_bps
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to register a referrer.
+/// Only registered referrers accrue a share of the taker fee of the orders that name them, which
+/// keeps the `ReferralFeeBook` from being filled with arbitrary keys.
+pub(crate) fn process_register_referrer<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let referrer = Pubkey::try_from_slice(data)?;
+    let referral_fee_book_offset =
+        get_referral_fee_book_offset_from_header(&market_info.get_header()?)?.ok_or_else(|| {
+            phoenix_log!("Referral fees are not enabled on this market");
+            PhoenixError::ReferralFeeError
+        })?;
+    let referral_fee_book_bytes = &mut market_info.try_borrow_mut_data()?
+        [size_of::<MarketHeader>() + referral_fee_book_offset..];
+    assert_with_msg(
+        load_referral_fee_book_mut(referral_fee_book_bytes)?
+            .register(&referrer)
+            .is_some(),
+        PhoenixError::ReferralFeeError,
+        "The referral fee book is full",
+    )?;
+    phoenix_log!("Registered referrer {}", referrer);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to deregister a referrer and
+/// free its entry in the `ReferralFeeBook`. Any referral fees that the referrer has not claimed are
+/// forfeited to the market's uncollected fees.
+pub(crate) fn process_deregister_referrer<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let referrer = Pubkey::try_from_slice(data)?;
+    let referral_fee_book_offset =
+        get_referral_fee_book_offset_from_header(&market_info.get_header()?)?.ok_or_else(|| {
+            phoenix_log!("Referral fees are not enabled on this market");
+            PhoenixError::ReferralFeeError
+        })?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, referral_fee_book_bytes) = data.split_at_mut(referral_fee_book_offset);
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    let forfeited_quote_lots = load_referral_fee_book_mut(referral_fee_book_bytes)?
+        .deregister(&referrer)
+        .ok_or_else(|| {
+            phoenix_log!("{} is not a registered referrer", referrer);
+            PhoenixError::ReferralFeeError
+        })?;
+    assert_with_msg(
+        market.forfeit_referral_fees(forfeited_quote_lots).is_some(),
+        PhoenixError::ReferralFeeError,
+        "The market holds fewer referral fees than the referrer is owed",
+    )?;
+    phoenix_log!(
+        "Deregistered referrer {}, {} quote lots of unclaimed referral fees were forfeited",
+        referrer,
+        forfeited_quote_lots.as_u64()
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to set the minimum number of
+/// base lots that an immediate-or-cancel or fill-or-kill order must match. Orders that do not
+/// match at all are unaffected. Setting it to zero removes the minimum.
+pub(crate) fn process_change_min_taker_fill<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let min_taker_fill_base_lots = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_min_taker_fill_base_lots(BaseLots::new(min_taker_fill_base_lots));
+    Ok(())
+}
+
+/// Trading is disabled for slots in [`trading_disabled_from_slot`, `trading_disabled_until_slot`).
+/// Setting `trading_disabled_until_slot` to zero removes the window.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct SetTradingWindowParams {
+    pub trading_disabled_from_slot: u64,
+    pub trading_disabled_until_slot: u64,
+}
+
+/// This function can only be called by the current market authority to schedule a window of slots
+/// in which new orders are rejected. Cancels and withdrawals remain available during the window.
+pub(crate) fn process_set_trading_window<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let SetTradingWindowParams {
+        trading_disabled_from_slot,
+        trading_disabled_until_slot,
+    } = SetTradingWindowParams::try_from_slice(data)?;
+    assert_with_msg(
+        trading_disabled_from_slot <= trading_disabled_until_slot,
+        ProgramError::InvalidArgument,
+        "Trading window must end after it starts",
+    )?;
+    let mut header = market_info.get_header_mut()?;
+    header.trading_disabled_from_slot = trading_disabled_from_slot;
+    header.trading_disabled_until_slot = trading_disabled_until_slot;
+    phoenix_log!(
+        "Trading disabled from slot {} until slot {}",
+        trading_disabled_from_slot,
+        trading_disabled_until_slot
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to change the tick size of
+/// the market. Resting orders are migrated to the nearest valid tick on the new grid, with bids
+/// rounded down and asks rounded up (see `FIFOMarket::change_tick_size_inner`). The new tick size
+/// must be a multiple of the number of base lots per base unit.
+pub(crate) fn process_change_tick_size<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let tick_size_in_quote_lots_per_base_unit = u64::try_from_slice(data)?;
+    let (
+        client_order_id_index_enabled,
+        stop_orders_enabled,
+        iceberg_reserve_book_offset,
+        quote_lot_size,
+    ) = {
+        let header = market_info.get_header()?;
+        (
+            header.client_order_id_index_enabled != 0,
+            header.stop_orders_enabled != 0,
+            get_iceberg_reserve_book_offset_from_header(&header)?,
+            header.get_quote_lot_size(),
+        )
+    };
+    {
+        let market_size = get_market_size(&market_info.size_params)?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, index_bytes) = data.split_at_mut(market_size);
+        // Stop orders store their trigger and limit prices in ticks, so they are not migrated
+        if stop_orders_enabled {
+            let stop_order_book_offset = get_stop_order_book_offset(
+                &market_info.size_params,
+                client_order_id_index_enabled,
+            )?;
+            assert_with_msg(
+                load_stop_order_book(&index_bytes[stop_order_book_offset - market_size..])?
+                    .is_empty(),
+                PhoenixError::StopOrderError,
+                "The tick size cannot be changed while there are stop orders",
+            )?;
+        }
+        // Iceberg reserves are keyed by the order ids of their visible orders, which change when
+        // the orders are migrated
+        if let Some(iceberg_reserve_book_offset) = iceberg_reserve_book_offset {
+            assert_with_msg(
+                load_iceberg_reserve_book(
+                    &index_bytes[iceberg_reserve_book_offset - market_size..],
+                )?
+                .is_empty(),
+                PhoenixError::InvalidMarketParameters,
+                "The tick size cannot be changed while there are iceberg orders",
+            )?;
+        }
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let order_ids = market
+            .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(
+                tick_size_in_quote_lots_per_base_unit,
+            ))
+            .ok_or_else(|| {
+                phoenix_log!(
+                    "Failed to change tick size to {} quote lots per base unit",
+                    tick_size_in_quote_lots_per_base_unit
+                );
+                PhoenixError::InvalidMarketParameters
+            })?;
+        // The client order id index stores the price of each order, so it must track the migration
+        if client_order_id_index_enabled {
+            let index = load_client_order_id_index_mut(&market_info.size_params, index_bytes)?;
+            for order_id in order_ids.iter() {
+                if let Some(entry) = index.get_mut(&order_id.order_sequence_number) {
+                    entry.price_in_ticks = order_id.price_in_ticks;
+                }
+            }
+        }
+    }
+    market_info
+        .get_header_mut()?
+        .set_tick_size_in_quote_atoms_per_base_unit(
+            quote_lot_size
+                * QuoteLotsPerBaseUnitPerTick::new(tick_size_in_quote_lots_per_base_unit),
+        );
+    phoenix_log!(
+        "Tick size changed to {} quote lots per base unit",
+        tick_size_in_quote_lots_per_base_unit
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to
+/// modify the minimum size of orders that rest on the book. Existing orders are unaffected.
+pub(crate) fn process_change_min_base_lots_per_order<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let min_base_lots_per_order = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_min_base_lots_per_order(BaseLots::new(min_base_lots_per_order));
+    phoenix_log!(
+        "Minimum order size changed to {} base lots",
+        min_base_lots_per_order
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to modify the
+/// maximum lifetime of resting orders. Existing orders are unaffected.
+pub(crate) fn process_change_max_order_lifetime_slots<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let max_order_lifetime_slots = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_max_order_lifetime_slots(max_order_lifetime_slots);
+    phoenix_log!(
+        "Maximum order lifetime changed to {} slots",
+        max_order_lifetime_slots
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to modify the
+/// maximum number of resting orders that an order can match against.
+pub(crate) fn process_change_max_match_limit<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let max_match_limit = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_max_match_limit(max_match_limit);
+    phoenix_log!("Maximum match limit changed to {}", max_match_limit);
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to modify the
+/// maximum number of orders that a trader can have resting on each side of the book.
+/// Traders above a new limit keep their orders but cannot place more until they are below it.
+pub(crate) fn process_change_max_orders_per_side_per_trader<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let max_orders_per_side_per_trader = u64::try_from_slice(data)?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_max_orders_per_side_per_trader(max_orders_per_side_per_trader);
+    phoenix_log!(
+        "Maximum open orders per side per trader changed to {}",
+        max_orders_per_side_per_trader
+    );
+    Ok(())
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct ChangeMarketSymbolsParams {
+    /// UTF-8 symbol of the base token, padded with zeros (see `encode_market_symbol`)
+    pub base_symbol: [u8; MARKET_SYMBOL_LEN],
+    /// UTF-8 symbol of the quote token, padded with zeros (see `encode_market_symbol`)
+    pub quote_symbol: [u8; MARKET_SYMBOL_LEN],
+}
+
+/// This function can only be called by the current market authority to modify the base and
+/// quote symbols in the market header. The display decimals cannot be changed.
+pub(crate) fn process_change_market_symbols<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let ChangeMarketSymbolsParams {
+        base_symbol,
+        quote_symbol,
+    } = ChangeMarketSymbolsParams::try_from_slice(data)?;
+    for symbol in [&base_symbol, &quote_symbol] {
+        assert_with_msg(
+            is_valid_market_symbol(symbol),
+            ProgramError::InvalidInstructionData,
+            "Market symbols must be UTF-8 and padded with zeros",
+        )?;
+    }
+    let mut header = market_info.get_header_mut()?;
+    header.base_symbol = base_symbol;
+    header.quote_symbol = quote_symbol;
+    phoenix_log!(
+        "Market symbols changed to {}/{}",
+        header.get_base_symbol().unwrap_or_default(),
+        header.get_quote_symbol().unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// This function can only be called by the current market authority to modify the number
+/// of slots a trader can go without placing an order before their seat can be demoted.
+/// Setting it to zero disables demotion.
+pub(crate) fn process_change_seat_inactivity_threshold<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let seat_inactivity_threshold_slots = u64::try_from_slice(data)?;
+    market_info
+        .get_header_mut()?
+        .seat_inactivity_threshold_slots = seat_inactivity_threshold_slots;
+    phoenix_log!(
+        "Seat inactivity threshold changed to {} slots",
+        seat_inactivity_threshold_slots
+    );
+    Ok(())
+}
+
 /// This function can only be called by the current market authority to
 /// modify the current market status (based on valid transitions)
 pub(crate) fn process_change_market_status<'a, 'info>(
@@ -128,7 +743,12 @@ pub(crate) fn process_change_market_status<'a, 'info>(
             {
                 let market_bytes =
                     &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-                let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+                let market = load_with_dispatch_mut(
+                    &market_info.size_params,
+                    market_info.market_type,
+                    market_bytes,
+                )?
+                .inner;
                 assert_with_msg(
                     market.get_book(Side::Bid).is_empty() && market.get_book(Side::Ask).is_empty(),
                     ProgramError::InvalidAccountData,
@@ -174,3 +794,250 @@ pub(crate) fn process_change_market_status<'a, 'info>(
     }
     Ok(())
 }
+
+/// This function can only be called by the current market authority to move a paused market into
+/// a larger memory layout. All resting orders, trader seats, and sequence numbers are preserved.
+///
+/// An account can only grow by `MAX_PERMITTED_DATA_INCREASE` bytes per instruction, so this
+/// instruction must be sent repeatedly. Each call grows the market account until the new market
+/// can be staged directly after the current market. The call that completes the allocation copies
+/// the market into the new layout, moves it to the front of the account, and shrinks the account
+/// to its final size. Lamports that are no longer needed for rent are returned to the payer.
+pub(crate) fn process_resize_market<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let ResizeMarketContext {
+        payer,
+        system_program,
+    } = ResizeMarketContext::load(market_context, accounts)?;
+    let PhoenixMarketContext { market_info, .. } = market_context;
+    let new_size_params = MarketSizeParams::try_from_slice(data)?;
+    let (
+        current_size_params,
+        client_order_id_index_enabled,
+        stop_orders_enabled,
+        referral_fees_enabled,
+        recent_fills_enabled,
+        iceberg_orders_enabled,
+    ) = {
+        let header = market_info.get_header()?;
+        let status = MarketStatus::from(header.status);
+        assert_with_msg(
+            matches!(status, MarketStatus::Paused),
+            ProgramError::InvalidAccountData,
+            &format!(
+                "Market must be paused to be resized, market status is {}",
+                status
+            ),
+        )?;
+        (
+            header.market_size_params,
+            header.client_order_id_index_enabled != 0,
+            header.stop_orders_enabled != 0,
+            header.referral_fees_enabled != 0,
+            header.recent_fills_enabled != 0,
+            header.iceberg_orders_enabled != 0,
+        )
+    };
+
+    let current_market_size = get_market_size(&current_size_params)?;
+    let new_market_size = get_market_size(&new_size_params)?;
+    assert_with_msg(
+        new_size_params.bids_size >= current_size_params.bids_size
+            && new_size_params.asks_size >= current_size_params.asks_size
+            && new_size_params.num_seats >= current_size_params.num_seats
+            && new_market_size > current_market_size,
+        PhoenixError::InvalidMarketParameters,
+        "New market size must be strictly larger than the current market size",
+    )?;
+
+    let (current_index_size, new_index_size) = if client_order_id_index_enabled {
+        (
+            get_client_order_id_index_size(&current_size_params)?,
+            get_client_order_id_index_size(&new_size_params)?,
+        )
+    } else {
+        (0, 0)
+    };
+    // The stop order book, the referral fee book, the recent fills and the iceberg reserve book
+    // have fixed sizes, so they are copied as is
+    let mut fixed_regions_size = 0;
+    if stop_orders_enabled {
+        fixed_regions_size += get_stop_order_book_size();
+    }
+    if referral_fees_enabled {
+        fixed_regions_size += get_referral_fee_book_size();
+    }
+    if recent_fills_enabled {
+        fixed_regions_size += get_recent_fills_size();
+    }
+    if iceberg_orders_enabled {
+        fixed_regions_size += get_iceberg_reserve_book_size();
+    }
+    let header_size = size_of::<MarketHeader>();
+    let staging_offset =
+        header_size + current_market_size + current_index_size + fixed_regions_size;
+    let staging_len = staging_offset + new_market_size + new_index_size + fixed_regions_size;
+
+    let rent = Rent::get()?;
+    if market_info.data_len() < staging_len {
+        let next_len = staging_len.min(market_info.data_len() + MAX_PERMITTED_DATA_INCREASE);
+        market_info.realloc(next_len, true)?;
+        let required_lamports = rent
+            .minimum_balance(next_len)
+            .saturating_sub(market_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, market_info.key, required_lamports),
+                &[
+                    payer.as_ref().clone(),
+                    market_info.as_ref().clone(),
+                    system_program.as_ref().clone(),
+                ],
+            )?;
+        }
+        if next_len < staging_len {
+            phoenix_log!(
+                "Market account grown to {} of {} bytes, send the instruction again to continue",
+                next_len,
+                staging_len
+            );
+            return Ok(());
+        }
+    }
+
+    // Stage the resized market (and client order id index, stop order book, referral fee book,
+    // recent fills and iceberg reserve book) after the current market
+    {
+        let data = &mut market_info.try_borrow_mut_data()?[header_size..staging_len];
+        let (current_bytes, new_bytes) = data.split_at_mut(staging_offset - header_size);
+        new_bytes.fill(0);
+        let (current_market_bytes, current_index_bytes) =
+            current_bytes.split_at(current_market_size);
+        let (current_index_bytes, current_fixed_regions_bytes) =
+            current_index_bytes.split_at(current_index_size);
+        let (new_market_bytes, new_index_bytes) = new_bytes.split_at_mut(new_market_size);
+        let (new_index_bytes, new_fixed_regions_bytes) =
+            new_index_bytes.split_at_mut(new_index_size);
+        new_fixed_regions_bytes.copy_from_slice(current_fixed_regions_bytes);
+        let current_market = load_with_dispatch(&current_size_params, current_market_bytes)?.inner;
+        let new_market = load_with_dispatch_init(&new_size_params, new_market_bytes)?.inner;
+        assert_with_msg(
+            new_market.copy_from(current_market).is_some(),
+            PhoenixError::InvalidMarketParameters,
+            "Failed to copy the market into the resized market",
+        )?;
+        if client_order_id_index_enabled {
+            initialize_client_order_id_index(&new_size_params, new_index_bytes)?;
+            let current_index =
+                load_client_order_id_index(&current_size_params, current_index_bytes)?;
+            let new_index = load_client_order_id_index_mut(&new_size_params, new_index_bytes)?;
+            for (order_sequence_number, entry) in current_index.iter() {
+                assert_with_msg(
+                    new_index.insert(*order_sequence_number, *entry).is_some(),
+                    PhoenixError::ClientOrderIdIndexError,
+                    "Failed to copy the client order id index into the resized market",
+                )?;
+            }
+        }
+        // The iceberg reserve book is the last fixed region. Its reserves store trader indices,
+        // which are not preserved when the traders are copied into the resized market.
+        if iceberg_orders_enabled {
+            let iceberg_reserve_book = load_iceberg_reserve_book_mut(
+                &mut new_fixed_regions_bytes
+                    [fixed_regions_size - get_iceberg_reserve_book_size()..],
+            )?;
+            for (_, iceberg_reserve) in iceberg_reserve_book.reserves.iter_mut() {
+                let trader_id =
+                    current_market.get_trader_id_from_index(iceberg_reserve.trader_index as u32);
+                iceberg_reserve.trader_index =
+                    new_market.get_trader_index(&trader_id).ok_or_else(|| {
+                        phoenix_log!("Failed to copy the iceberg reserves into the resized market");
+                        PhoenixError::InvalidMarketParameters
+                    })? as u64;
+            }
+        }
+    }
+
+    // Move the resized market to the front of the account and release the staging space
+    market_info
+        .try_borrow_mut_data()?
+        .copy_within(staging_offset..staging_len, header_size);
+    market_info.realloc(
+        header_size + new_market_size + new_index_size + fixed_regions_size,
+        false,
+    )?;
+    market_info.get_header_mut()?.market_size_params = new_size_params;
+
+    let excess_lamports = market_info
+        .lamports()
+        .saturating_sub(rent.minimum_balance(market_info.data_len()));
+    **market_info.lamports.borrow_mut() -= excess_lamports;
+    **payer.lamports.borrow_mut() += excess_lamports;
+
+    phoenix_log!(
+        "Market resized to {} bids, {} asks, and {} seats",
+        new_size_params.bids_size,
+        new_size_params.asks_size,
+        new_size_params.num_seats
+    );
+    Ok(())
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetVolatilityBandParams {
+    /// An order that matches more than this many base lots starts a cooldown. Zero disables the
+    /// volatility band
+    pub fill_threshold_base_lots: u64,
+    /// The number of slots after a large fill during which orders are checked against the band
+    pub cooldown_slots: u64,
+    /// During the cooldown, bids priced more than this many basis points above the last trade
+    /// price and asks priced more than this many basis points below it are rejected
+    pub band_bps: u64,
+}
+
+/// This function can only be called by the current market authority to configure the volatility
+/// band, which rejects aggressively priced orders for a number of slots after a large fill.
+pub(crate) fn process_set_volatility_band<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let PhoenixMarketContext {
+        market_info,
+        signer: authority,
+    } = market_context;
+    market_info.assert_valid_authority(authority.key)?;
+    let SetVolatilityBandParams {
+        fill_threshold_base_lots,
+        cooldown_slots,
+        band_bps,
+    } = SetVolatilityBandParams::try_from_slice(data)?;
+    assert_with_msg(
+        fill_threshold_base_lots == 0 || cooldown_slots > 0,
+        PhoenixError::InvalidMarketParameters,
+        "The cooldown must be nonzero when the volatility band is enabled",
+    )?;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market.set_volatility_band(
+        BaseLots::new(fill_threshold_base_lots),
+        cooldown_slots,
+        band_bps,
+    );
+    phoenix_log!(
+        "Volatility band set to {} bps for {} slots after fills of more than {} base lots",
+        band_bps,
+        cooldown_slots,
+        fill_threshold_base_lots
+    );
+    Ok(())
+}
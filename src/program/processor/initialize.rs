@@ -1,24 +1,47 @@
 use crate::{
     program::{
-        dispatch_market::load_with_dispatch_init,
+        client_order_id_index::{get_client_order_id_index_size, initialize_client_order_id_index},
+        dispatch_market::{get_market_size, load_with_dispatch_init},
+        encode_market_symbol,
         error::{assert_with_msg, PhoenixError},
-        loaders::{get_vault_address, InitializeMarketContext},
+        iceberg_reserve_book::{
+            get_iceberg_reserve_book_offset, get_iceberg_reserve_book_size,
+            initialize_iceberg_reserve_book,
+        },
+        is_valid_market_symbol,
+        loaders::{get_market_address, get_vault_address, InitializeMarketContext},
+        recent_fills::{get_recent_fills_offset, get_recent_fills_size, initialize_recent_fills},
+        referral_fee_book::{
+            get_referral_fee_book_offset, get_referral_fee_book_size, initialize_referral_fee_book,
+        },
+        status::MarketStatus,
+        stop_order_book::{
+            get_stop_order_book_offset, get_stop_order_book_size, initialize_stop_order_book,
+        },
         system_utils::create_account,
-        MarketHeader, MarketSizeParams, PhoenixMarketContext, TokenParams,
+        validation::checkers::{EmptyAccount, Program, Signer},
+        MarketHeader, MarketSizeParams, MarketType, PhoenixMarketContext, TokenParams,
+        MARKET_SYMBOL_LEN,
     },
     quantities::{
-        BaseAtomsPerBaseUnit, BaseLotsPerBaseUnit, QuoteAtomsPerQuoteUnit,
+        BaseAtomsPerBaseUnit, BaseLots, BaseLotsPerBaseUnit, QuoteAtomsPerQuoteUnit,
         QuoteLotsPerBaseUnitPerTick, QuoteLotsPerQuoteUnit, WrapperU64,
     },
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program::invoke,
-    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, rent::Rent, sysvar::Sysvar,
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
 };
 use std::{mem::size_of, ops::DerefMut};
 
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, Default)]
 pub struct InitializeParams {
     /// These parameters define the number of orders on each side of the market as well as the maximum
     /// number of supported traders. They are used to deserialize the market state (see `dispatch_market.rs`).
@@ -63,6 +86,121 @@ pub struct InitializeParams {
     /// If this parameter is supplied, the market will treat the number of base atoms in a base unit as
     /// `(10^base_mint_decimals) * raw_base_units_per_base_unit`.
     pub raw_base_units_per_base_unit: Option<u32>,
+
+    /// Orders that would rest on the book with fewer base lots than this minimum are rejected.
+    /// Immediate-or-cancel orders are exempt. There is no minimum if the Option is passed in as `None`.
+    pub min_base_lots_per_order: Option<u64>,
+
+    /// If this is set to true, the client order id of every order placed on the market is stored in a
+    /// `ClientOrderIdIndex` so that orders can be cancelled by client order id. The market account must
+    /// be allocated with enough space for the index (see `get_client_order_id_index_size`).
+    pub enable_client_order_id_index: Option<bool>,
+
+    /// The matching algorithm of the market. Markets are FIFO if the Option is passed in as `None`.
+    pub market_type: Option<MarketType>,
+
+    /// If this is set to true, traders can place stop orders that are stored in a `StopOrderBook`
+    /// until a fill triggers them. The market account must be allocated with enough space for the
+    /// stop order book (see `get_stop_order_book_size`).
+    pub enable_stop_orders: Option<bool>,
+
+    /// Symbols and display decimals of the base and quote tokens that are stored in the `MarketHeader`
+    /// so that clients can render the market without an external token registry. The symbols can be
+    /// changed by the market authority, but the display decimals cannot. The metadata is zeroed if
+    /// the Option is passed in as `None`.
+    pub market_metadata: Option<MarketMetadataParams>,
+
+    /// If this is set to true, immediate-or-cancel orders can name a referrer that accrues a share
+    /// of the taker fee in a `ReferralFeeBook`. The market account must be allocated with enough
+    /// space for the referral fee book (see `get_referral_fee_book_size`).
+    pub enable_referral_fees: Option<bool>,
+
+    /// If this is set to true, the market keeps its most recent fills in a `RecentFills` buffer so
+    /// that clients can read a bounded trade history from the market account. The market account
+    /// must be allocated with enough space for the buffer (see `get_recent_fills_size`).
+    pub enable_recent_fills: Option<bool>,
+
+    /// If this is set to true, traders can place iceberg orders, whose hidden reserves are stored in
+    /// an `IcebergReserveBook`. The market account must be allocated with enough space for the
+    /// reserve book (see `get_iceberg_reserve_book_size`).
+    pub enable_iceberg_orders: Option<bool>,
+}
+
+impl InitializeParams {
+    /// The number of `Option` parameters at the end of `InitializeParams`
+    const NUM_TRAILING_OPTIONAL_PARAMS: usize = 9;
+
+    /// Returns the size of the market account, including the header and every optional region that
+    /// the params enable
+    pub fn get_market_account_size(&self) -> Result<usize, ProgramError> {
+        let mut space = size_of::<MarketHeader>() + get_market_size(&self.market_size_params)?;
+        if self.enable_client_order_id_index.unwrap_or(false) {
+            space += get_client_order_id_index_size(&self.market_size_params)?;
+        }
+        if self.enable_stop_orders.unwrap_or(false) {
+            space += get_stop_order_book_size();
+        }
+        if self.enable_referral_fees.unwrap_or(false) {
+            space += get_referral_fee_book_size();
+        }
+        if self.enable_recent_fills.unwrap_or(false) {
+            space += get_recent_fills_size();
+        }
+        if self.enable_iceberg_orders.unwrap_or(false) {
+            space += get_iceberg_reserve_book_size();
+        }
+        Ok(space)
+    }
+
+    /// Decodes the params, accepting instruction data from older clients that omit any number of
+    /// the trailing optional parameters. Each omitted parameter is decoded as `None`.
+    pub(crate) fn decode(data: &[u8]) -> std::io::Result<Self> {
+        let mut result = Self::try_from_slice(data);
+        let mut padded_data = data.to_vec();
+        for _ in 0..Self::NUM_TRAILING_OPTIONAL_PARAMS {
+            if result.is_ok() {
+                break;
+            }
+            padded_data.push(0);
+            result = Self::try_from_slice(&padded_data);
+        }
+        result
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, Default)]
+pub struct MarketMetadataParams {
+    /// UTF-8 symbol of the base token, padded with zeros (see `encode_market_symbol`)
+    pub base_symbol: [u8; MARKET_SYMBOL_LEN],
+    /// UTF-8 symbol of the quote token, padded with zeros (see `encode_market_symbol`)
+    pub quote_symbol: [u8; MARKET_SYMBOL_LEN],
+    pub base_display_decimals: u32,
+    pub quote_display_decimals: u32,
+}
+
+impl MarketMetadataParams {
+    /// Returns None if either symbol cannot be stored in the `MarketHeader`
+    pub fn new(
+        base_symbol: &str,
+        quote_symbol: &str,
+        base_display_decimals: u32,
+        quote_display_decimals: u32,
+    ) -> Option<Self> {
+        Some(Self {
+            base_symbol: encode_market_symbol(base_symbol)?,
+            quote_symbol: encode_market_symbol(quote_symbol)?,
+            base_display_decimals,
+            quote_display_decimals,
+        })
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct InitializeMarketPdaParams {
+    /// The nonce distinguishes markets that are created for the same base and quote mints.
+    /// The market address is a PDA with seeds [b"market", base_mint, quote_mint, nonce.to_le_bytes()].
+    pub nonce: u64,
+    pub initialize_params: InitializeParams,
 }
 
 pub(crate) fn process_initialize_market<'a, 'info>(
@@ -70,6 +208,164 @@ pub(crate) fn process_initialize_market<'a, 'info>(
     market_context: &PhoenixMarketContext<'a, 'info>,
     accounts: &'a [AccountInfo<'info>],
     data: &[u8],
+) -> ProgramResult {
+    let params = InitializeParams::decode(data)?;
+    initialize_market(market_context, accounts, params)
+}
+
+/// Creates the market account at the PDA derived from the base mint, quote mint, and nonce, or grows
+/// it if it has already been created. Returns true once the account has the full size of the market.
+///
+/// This must run before the market context is loaded because the market account does not exist yet.
+/// The runtime limits the data that can be allocated through CPI, and the data that an account can
+/// grow by in one instruction, to `MAX_PERMITTED_DATA_INCREASE` bytes. Larger markets are allocated
+/// over several `InitializeMarketPda` instructions with the same parameters (see
+/// `get_num_initialize_market_pda_instructions`). Each instruction grows the account, and the one
+/// that completes the allocation initializes the market. Until then, only the market creator that
+/// created the account can grow it.
+pub(crate) fn allocate_market_pda_account<'a, 'info>(
+    program_accounts: &'a [AccountInfo<'info>],
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> Result<bool, ProgramError> {
+    let InitializeMarketPdaParams {
+        nonce,
+        initialize_params,
+    } = InitializeMarketPdaParams::try_from_slice(data)?;
+    // The Phoenix program and log authority are validated by the caller
+    let market_info = program_accounts
+        .get(2)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let market_creator = Signer::new_payer(
+        program_accounts
+            .get(3)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?,
+    )?;
+    let accounts_iter = &mut accounts.iter();
+    let base_mint = next_account_info(accounts_iter)?;
+    let quote_mint = next_account_info(accounts_iter)?;
+    // The vaults are validated and created when the market is initialized
+    let _base_vault = next_account_info(accounts_iter)?;
+    let _quote_vault = next_account_info(accounts_iter)?;
+    let system_program = Program::new(next_account_info(accounts_iter)?, &system_program::id())?;
+
+    let (market_key, bump) = get_market_address(base_mint.key, quote_mint.key, nonce);
+    assert_with_msg(
+        market_key == *market_info.key,
+        ProgramError::InvalidSeeds,
+        &format!(
+            "Supplied market ({}) does not match computed key ({})",
+            market_info.key, market_key
+        ),
+    )?;
+
+    let space = initialize_params.get_market_account_size()?;
+    let rent = Rent::get()?;
+    if market_info.owner == &system_program::id() {
+        let market_info = EmptyAccount::new(market_info)?;
+        let seeds = vec![
+            b"market".to_vec(),
+            base_mint.key.as_ref().to_vec(),
+            quote_mint.key.as_ref().to_vec(),
+            nonce.to_le_bytes().to_vec(),
+            vec![bump],
+        ];
+        create_account(
+            market_creator.as_ref(),
+            market_info.as_ref(),
+            system_program.as_ref(),
+            &crate::id(),
+            &rent,
+            space.min(MAX_PERMITTED_DATA_INCREASE) as u64,
+            seeds,
+        )?;
+        // The creator is stored as the authority of the uninitialized market, so that nobody else
+        // can finish allocating the account with different parameters
+        let mut market_data = market_info.try_borrow_mut_data()?;
+        MarketHeader::load_mut_bytes(&mut market_data[..size_of::<MarketHeader>()])
+            .ok_or(ProgramError::InvalidAccountData)?
+            .authority = *market_creator.key;
+    } else {
+        {
+            let market_data = market_info.try_borrow_data()?;
+            let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()])
+                .ok_or(ProgramError::InvalidAccountData)?;
+            assert_with_msg(
+                market_info.owner == &crate::id()
+                    && header.discriminant == 0
+                    && header.status == MarketStatus::Uninitialized as u64,
+                PhoenixError::MarketAlreadyInitialized,
+                "Market is already initialized",
+            )?;
+            assert_with_msg(
+                header.authority == *market_creator.key,
+                PhoenixError::InvalidMarketAuthority,
+                "Only the creator of the market account can continue allocating it",
+            )?;
+            assert_with_msg(
+                market_info.data_len() < space,
+                ProgramError::InvalidAccountData,
+                &format!(
+                    "Market account has {} bytes, but the market only needs {} bytes",
+                    market_info.data_len(),
+                    space
+                ),
+            )?;
+        }
+        let next_len = space.min(market_info.data_len() + MAX_PERMITTED_DATA_INCREASE);
+        market_info.realloc(next_len, true)?;
+        let required_lamports = rent
+            .minimum_balance(next_len)
+            .saturating_sub(market_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    market_creator.key,
+                    market_info.key,
+                    required_lamports,
+                ),
+                &[
+                    market_creator.as_ref().clone(),
+                    market_info.clone(),
+                    system_program.as_ref().clone(),
+                ],
+            )?;
+        }
+    }
+
+    if market_info.data_len() < space {
+        phoenix_log!(
+            "Market account allocated {} of {} bytes, send the instruction again to continue",
+            market_info.data_len(),
+            space
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Returns the number of `InitializeMarketPda` instructions that are needed to allocate and
+/// initialize a market account of `market_account_size` bytes
+pub fn get_num_initialize_market_pda_instructions(market_account_size: usize) -> usize {
+    ((market_account_size + MAX_PERMITTED_DATA_INCREASE - 1) / MAX_PERMITTED_DATA_INCREASE).max(1)
+}
+
+pub(crate) fn process_initialize_market_pda<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let InitializeMarketPdaParams {
+        initialize_params, ..
+    } = InitializeMarketPdaParams::try_from_slice(data)?;
+    initialize_market(market_context, accounts, initialize_params)
+}
+
+fn initialize_market<'a, 'info>(
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    params: InitializeParams,
 ) -> ProgramResult {
     let PhoenixMarketContext {
         market_info,
@@ -93,7 +389,29 @@ pub(crate) fn process_initialize_market<'a, 'info>(
         taker_fee_bps,
         fee_collector,
         raw_base_units_per_base_unit,
-    } = InitializeParams::try_from_slice(data)?;
+        min_base_lots_per_order,
+        enable_client_order_id_index,
+        market_type,
+        enable_stop_orders,
+        market_metadata,
+        enable_referral_fees,
+        enable_recent_fills,
+        enable_iceberg_orders,
+    } = params;
+    let enable_client_order_id_index = enable_client_order_id_index.unwrap_or(false);
+    let enable_stop_orders = enable_stop_orders.unwrap_or(false);
+    let enable_referral_fees = enable_referral_fees.unwrap_or(false);
+    let enable_recent_fills = enable_recent_fills.unwrap_or(false);
+    let enable_iceberg_orders = enable_iceberg_orders.unwrap_or(false);
+    let min_base_lots_per_order = min_base_lots_per_order.unwrap_or(0);
+    let market_metadata = market_metadata.unwrap_or_default();
+    for symbol in [&market_metadata.base_symbol, &market_metadata.quote_symbol] {
+        assert_with_msg(
+            is_valid_market_symbol(symbol),
+            ProgramError::InvalidInstructionData,
+            "Market symbols must be UTF-8 and padded with zeros",
+        )?;
+    }
 
     let tick_size_in_quote_lots_per_base_unit =
         QuoteLotsPerBaseUnitPerTick::new(tick_size_in_quote_lots_per_base_unit);
@@ -157,10 +475,11 @@ pub(crate) fn process_initialize_market<'a, 'info>(
     // Create the base and quote vaults of this market
     let rent = Rent::get()?;
     let mut bumps = vec![];
-    for (token_account, mint) in [
-        (base_vault.as_ref(), base_mint.as_ref()),
-        (quote_vault.as_ref(), quote_mint.as_ref()),
+    for (token_account, mint_info) in [
+        (base_vault.as_ref(), &base_mint),
+        (quote_vault.as_ref(), &quote_mint),
     ] {
+        let mint = mint_info.as_ref();
         let (vault_key, bump) = get_vault_address(market_info.key, mint.key);
         assert_with_msg(
             vault_key == *token_account.key,
@@ -170,7 +489,8 @@ pub(crate) fn process_initialize_market<'a, 'info>(
                 token_account.key, vault_key
             ),
         )?;
-        let space = spl_token::state::Account::LEN;
+        // Token-2022 vaults must reserve space for the extensions required by the mint
+        let space = mint_info.get_token_account_len()?;
         let seeds = vec![
             b"vault".to_vec(),
             market_info.key.as_ref().to_vec(),
@@ -181,14 +501,14 @@ pub(crate) fn process_initialize_market<'a, 'info>(
             market_creator.as_ref(),
             token_account,
             system_program.as_ref(),
-            &spl_token::id(),
+            token_program.key,
             &rent,
             space as u64,
             seeds,
         )?;
         invoke(
-            &spl_token::instruction::initialize_account3(
-                &spl_token::id(),
+            &spl_token_2022::instruction::initialize_account3(
+                token_program.key,
                 token_account.key,
                 mint.key,
                 token_account.key,
@@ -218,6 +538,82 @@ pub(crate) fn process_initialize_market<'a, 'info>(
             num_base_lots_per_base_unit,
         );
         market.set_fee(taker_fee_bps as u64);
+        market.set_min_base_lots_per_order(BaseLots::new(min_base_lots_per_order));
+    }
+
+    if enable_client_order_id_index {
+        let market_size = get_market_size(&market_size_params)?;
+        let index_bytes =
+            &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + market_size..];
+        assert_with_msg(
+            index_bytes.len() >= get_client_order_id_index_size(&market_size_params)?,
+            PhoenixError::ClientOrderIdIndexError,
+            "Market account is too small to store the client order id index",
+        )?;
+        initialize_client_order_id_index(&market_size_params, index_bytes)?;
+    }
+
+    if enable_stop_orders {
+        let offset = get_stop_order_book_offset(&market_size_params, enable_client_order_id_index)?;
+        let stop_order_book_bytes =
+            &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + offset..];
+        assert_with_msg(
+            stop_order_book_bytes.len() >= get_stop_order_book_size(),
+            PhoenixError::StopOrderError,
+            "Market account is too small to store the stop order book",
+        )?;
+        initialize_stop_order_book(stop_order_book_bytes)?;
+    }
+
+    if enable_referral_fees {
+        let offset = get_referral_fee_book_offset(
+            &market_size_params,
+            enable_client_order_id_index,
+            enable_stop_orders,
+        )?;
+        let referral_fee_book_bytes =
+            &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + offset..];
+        assert_with_msg(
+            referral_fee_book_bytes.len() >= get_referral_fee_book_size(),
+            PhoenixError::ReferralFeeError,
+            "Market account is too small to store the referral fee book",
+        )?;
+        initialize_referral_fee_book(referral_fee_book_bytes)?;
+    }
+
+    if enable_recent_fills {
+        let offset = get_recent_fills_offset(
+            &market_size_params,
+            enable_client_order_id_index,
+            enable_stop_orders,
+            enable_referral_fees,
+        )?;
+        let recent_fills_bytes =
+            &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + offset..];
+        assert_with_msg(
+            recent_fills_bytes.len() >= get_recent_fills_size(),
+            ProgramError::AccountDataTooSmall,
+            "Market account is too small to store the recent fills",
+        )?;
+        initialize_recent_fills(recent_fills_bytes)?;
+    }
+
+    if enable_iceberg_orders {
+        let offset = get_iceberg_reserve_book_offset(
+            &market_size_params,
+            enable_client_order_id_index,
+            enable_stop_orders,
+            enable_referral_fees,
+            enable_recent_fills,
+        )?;
+        let iceberg_reserve_book_bytes =
+            &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>() + offset..];
+        assert_with_msg(
+            iceberg_reserve_book_bytes.len() >= get_iceberg_reserve_book_size(),
+            ProgramError::AccountDataTooSmall,
+            "Market account is too small to store the iceberg reserve book",
+        )?;
+        initialize_iceberg_reserve_book(iceberg_reserve_book_bytes)?;
     }
 
     // Populate the header data
@@ -245,7 +641,77 @@ pub(crate) fn process_initialize_market<'a, 'info>(
         fee_collector,
         raw_base_units_per_base_unit.unwrap_or(1),
     );
+    header.client_order_id_index_enabled = enable_client_order_id_index as u64;
+    header.market_type = market_type.unwrap_or_default() as u64;
+    header.stop_orders_enabled = enable_stop_orders as u64;
+    header.referral_fees_enabled = enable_referral_fees as u64;
+    header.recent_fills_enabled = enable_recent_fills as u32;
+    header.iceberg_orders_enabled = enable_iceberg_orders as u64;
+    header.base_symbol = market_metadata.base_symbol;
+    header.quote_symbol = market_metadata.quote_symbol;
+    header.base_display_decimals = market_metadata.base_display_decimals;
+    header.quote_display_decimals = market_metadata.quote_display_decimals;
 
     drop(header);
     Ok(())
 }
+
+#[test]
+fn test_decode_initialize_params_from_older_clients() {
+    // Layout of InitializeParams before any of the trailing optional parameters were added
+    #[derive(BorshSerialize)]
+    struct LegacyInitializeParams {
+        market_size_params: MarketSizeParams,
+        num_quote_lots_per_quote_unit: u64,
+        tick_size_in_quote_lots_per_base_unit: u64,
+        num_base_lots_per_base_unit: u64,
+        taker_fee_bps: u16,
+        fee_collector: Pubkey,
+    }
+    let legacy_params = LegacyInitializeParams {
+        market_size_params: MarketSizeParams {
+            bids_size: 512,
+            asks_size: 512,
+            num_seats: 128,
+        },
+        num_quote_lots_per_quote_unit: 100_000,
+        tick_size_in_quote_lots_per_base_unit: 1_000,
+        num_base_lots_per_base_unit: 1_000,
+        taker_fee_bps: 5,
+        fee_collector: Pubkey::new_unique(),
+    };
+    let legacy_data = legacy_params.try_to_vec().unwrap();
+
+    // Every trailing optional parameter can be omitted
+    for num_options in 0..=InitializeParams::NUM_TRAILING_OPTIONAL_PARAMS {
+        let data = [legacy_data.as_slice(), &vec![0; num_options]].concat();
+        let params = InitializeParams::decode(&data).unwrap();
+        assert_eq!(params.market_size_params.bids_size, 512);
+        assert_eq!(params.num_base_lots_per_base_unit, 1_000);
+        assert_eq!(params.taker_fee_bps, 5);
+        assert_eq!(params.fee_collector, legacy_params.fee_collector);
+        assert!(params.raw_base_units_per_base_unit.is_none());
+        assert!(params.enable_recent_fills.is_none());
+        assert!(params.enable_iceberg_orders.is_none());
+    }
+
+    // Parameters that are supplied are decoded as is
+    let data = [
+        legacy_data.as_slice(),
+        &Some(10_u32).try_to_vec().unwrap(),
+        &Some(7_u64).try_to_vec().unwrap(),
+    ]
+    .concat();
+    let params = InitializeParams::decode(&data).unwrap();
+    assert_eq!(params.raw_base_units_per_base_unit, Some(10));
+    assert_eq!(params.min_base_lots_per_order, Some(7));
+    assert!(params.enable_client_order_id_index.is_none());
+
+    // Trailing bytes beyond the known parameters are still rejected
+    let data = [
+        legacy_data.as_slice(),
+        &vec![0; InitializeParams::NUM_TRAILING_OPTIONAL_PARAMS + 1],
+    ]
+    .concat();
+    assert!(InitializeParams::decode(&data).is_err());
+}
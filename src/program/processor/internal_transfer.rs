@@ -0,0 +1,78 @@
+use crate::{
+    program::{
+        dispatch_market::load_with_dispatch_mut, error::assert_with_msg, InternalTransferContext,
+        MarketHeader, PhoenixError, PhoenixMarketContext,
+    },
+    quantities::{BaseLots, QuoteLots, WrapperU64},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+use std::mem::size_of;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InternalTransferParams {
+    /// The trader that receives the funds. This trader must have an approved seat
+    pub destination: Pubkey,
+    pub quote_lots_to_transfer: u64,
+    pub base_lots_to_transfer: u64,
+}
+
+/// Moves free funds from the signer's seat to another seat on the same market. No tokens are
+/// transferred, so the vault balances are unchanged.
+pub(crate) fn process_internal_transfer<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let InternalTransferParams {
+        destination,
+        quote_lots_to_transfer,
+        base_lots_to_transfer,
+    } = InternalTransferParams::try_from_slice(data)?;
+    InternalTransferContext::load(market_context, accounts, &destination)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let quote_lots = QuoteLots::new(quote_lots_to_transfer);
+    let base_lots = BaseLots::new(base_lots_to_transfer);
+
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    let source_state = market
+        .get_trader_state_mut(trader.key)
+        .ok_or(PhoenixError::TraderNotFound)?;
+    assert_with_msg(
+        source_state.quote_lots_free >= quote_lots && source_state.base_lots_free >= base_lots,
+        PhoenixError::InternalTransferError,
+        &format!(
+            "Insufficient free funds to transfer {} quote lots and {} base lots. Free quote lots: {}, free base lots: {}",
+            quote_lots,
+            base_lots,
+            source_state.quote_lots_free,
+            source_state.base_lots_free
+        ),
+    )?;
+    source_state.use_free_quote_lots(quote_lots);
+    source_state.use_free_base_lots(base_lots);
+
+    let destination_state = market
+        .get_trader_state_mut(&destination)
+        .ok_or(PhoenixError::TraderNotFound)?;
+    destination_state.deposit_free_quote_lots(quote_lots);
+    destination_state.deposit_free_base_lots(base_lots);
+    phoenix_log!(
+        "Transferred {} quote lots and {} base lots from {} to {}",
+        quote_lots,
+        base_lots,
+        trader.key,
+        destination
+    );
+    Ok(())
+}
@@ -1,13 +1,19 @@
 use crate::program::{
-    dispatch_market::load_with_dispatch_mut, error::assert_with_msg, loaders::get_seat_address,
-    status::SeatApprovalStatus, system_utils::create_account, AuthorizedSeatRequestContext,
-    MarketHeader, ModifySeatContext, PhoenixMarketContext, RequestSeatContext, Seat,
+    dispatch_market::load_with_dispatch_mut,
+    error::assert_with_msg,
+    loaders::{get_seat_address, DemoteInactiveSeatContext},
+    status::SeatApprovalStatus,
+    system_utils::create_account,
+    AuthorizedSeatRequestContext, CreateSeatContext, MarketHeader, ModifySeatContext, PhoenixError,
+    PhoenixMarketContext, RequestSeatBatchContext, RequestSeatContext, Seat, SeatAccountInfo,
+    TraderSeatContext,
 };
-use borsh::BorshDeserialize;
+use crate::state::SelfTradeBehavior;
+use borsh::{BorshDeserialize, BorshSerialize};
 use sokoban::node_allocator::ZeroCopy;
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    pubkey::Pubkey, rent::Rent, sysvar::Sysvar,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
+    program_error::ProgramError, pubkey::Pubkey, rent::Rent, sysvar::Sysvar,
 };
 use std::mem::size_of;
 
@@ -33,6 +39,81 @@ pub(crate) fn process_request_seat_authorized<'a, 'info>(
     )
 }
 
+/// The maximum number of traders in a single `RequestSeatBatch` instruction. Each trader adds a
+/// seat account and a pubkey to the transaction, so larger batches do not fit in a transaction.
+pub const MAX_TRADERS_PER_SEAT_BATCH: usize = 12;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequestSeatBatchParams {
+    pub traders: Vec<Pubkey>,
+}
+
+/// Creates and approves seats for many traders at once (by the market authority). Traders that
+/// already have an approved seat are skipped, as are traders whose seat is retired.
+pub(crate) fn process_request_seat_batch<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let RequestSeatBatchParams { traders } = RequestSeatBatchParams::try_from_slice(data)?;
+    assert_with_msg(
+        traders.len() <= MAX_TRADERS_PER_SEAT_BATCH,
+        ProgramError::InvalidInstructionData,
+        &format!(
+            "At most {} seats can be requested in a batch, got {}",
+            MAX_TRADERS_PER_SEAT_BATCH,
+            traders.len()
+        ),
+    )?;
+    let RequestSeatBatchContext {
+        payer,
+        system_program,
+        seats,
+    } = RequestSeatBatchContext::load(market_context, accounts, &traders)?;
+    let market_info = &market_context.market_info;
+    let current_slot = Clock::get()?.slot;
+
+    for (trader, seat_info) in traders.iter().zip(seats.into_iter()) {
+        if seat_info.data_is_empty() {
+            _create_seat(
+                payer.as_ref(),
+                trader,
+                seat_info,
+                market_info.key,
+                system_program.as_ref(),
+            )?;
+        }
+        let seat_account = SeatAccountInfo::new(seat_info, market_info.key)?;
+        let mut seat = seat_account.load_mut()?;
+        match SeatApprovalStatus::from(seat.approval_status) {
+            SeatApprovalStatus::NotApproved => {}
+            status => {
+                phoenix_log!("Skipping trader {}, seat is {}", trader, status);
+                continue;
+            }
+        }
+        seat.approval_status = SeatApprovalStatus::Approved as u64;
+        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        assert_with_msg(
+            market.get_or_register_trader(trader).is_some(),
+            ProgramError::InvalidArgument,
+            "Failed to register trader",
+        )?;
+        market
+            .get_trader_state_mut(trader)
+            .ok_or(PhoenixError::TraderNotFound)?
+            .last_active_slot = current_slot;
+    }
+    Ok(())
+}
+
 /// This instruction is used to request a seat on the market for a trader (by the trader)
 pub(crate) fn process_request_seat<'a, 'info>(
     _program_id: &Pubkey,
@@ -58,6 +139,55 @@ pub(crate) fn process_request_seat<'a, 'info>(
     )
 }
 
+/// Creates the trader's seat, paid for by the trader, and registers the trader on the market. The
+/// seat is not approved, so the market authority must still approve it before the trader can place
+/// orders. If the seat already exists, it is left unchanged and only the registration is performed.
+pub(crate) fn process_create_seat<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    _data: &[u8],
+) -> ProgramResult {
+    let CreateSeatContext {
+        seat: seat_info,
+        system_program,
+    } = CreateSeatContext::load(market_context, accounts)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    if seat_info.data_is_empty() {
+        _create_seat(
+            trader.as_ref(),
+            trader.key,
+            seat_info,
+            market_info.key,
+            system_program.as_ref(),
+        )?;
+    } else {
+        phoenix_log!("Seat already exists");
+    }
+    let seat_account = SeatAccountInfo::new(seat_info, market_info.key)?;
+    let approval_status = SeatApprovalStatus::from(seat_account.load_mut()?.approval_status);
+    if approval_status == SeatApprovalStatus::Retired {
+        phoenix_log!("Seat is retired, the trader is not registered");
+        return Ok(());
+    }
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    assert_with_msg(
+        market.get_or_register_trader(trader.key).is_some(),
+        ProgramError::InvalidArgument,
+        "Failed to register trader",
+    )?;
+    Ok(())
+}
+
 fn _create_seat<'a, 'info>(
     payer: &'a AccountInfo<'info>,
     trader: &'a Pubkey,
@@ -118,12 +248,22 @@ pub(crate) fn process_change_seat_status<'a, 'info>(
             seat.approval_status = SeatApprovalStatus::Approved as u64;
             // Initialize a seat for the approved trader
             let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-            let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+            let market = load_with_dispatch_mut(
+                &market_info.size_params,
+                market_info.market_type,
+                market_bytes,
+            )?
+            .inner;
             assert_with_msg(
                 market.get_or_register_trader(&seat.trader).is_some(),
                 ProgramError::InvalidArgument,
                 "Failed to register trader",
             )?;
+            // Approval counts as activity, so the seat cannot be demoted right away
+            market
+                .get_trader_state_mut(&seat.trader)
+                .ok_or(PhoenixError::TraderNotFound)?
+                .last_active_slot = Clock::get()?.slot;
         }
         (SeatApprovalStatus::Approved, SeatApprovalStatus::NotApproved) => {
             seat.approval_status = SeatApprovalStatus::NotApproved as u64;
@@ -145,3 +285,108 @@ pub(crate) fn process_change_seat_status<'a, 'info>(
     }
     Ok(())
 }
+
+/// Sets the self trade behavior that the market applies to every order the trader sends,
+/// regardless of the behavior in the order packet. Passing None restores the packet's behavior.
+pub(crate) fn process_set_self_trade_behavior_override<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    TraderSeatContext::load(market_context, accounts)?;
+    let self_trade_behavior = Option::<SelfTradeBehavior>::try_from_slice(data)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        market_bytes,
+    )?
+    .inner;
+    market
+        .get_trader_state_mut(trader.key)
+        .ok_or(PhoenixError::TraderNotFound)?
+        .set_self_trade_behavior_override(self_trade_behavior);
+    phoenix_log!(
+        "Self trade behavior override for {} set to {:?}",
+        trader.key,
+        self_trade_behavior
+    );
+    Ok(())
+}
+
+/// This function is permissionless. It revokes the approval of a seat whose trader has not placed
+/// an order for more than the market's `seat_inactivity_threshold_slots`. The trader's funds and
+/// orders are untouched, and the trader can request approval again. If the trader's last active
+/// slot was never recorded, it is set to the current slot and the seat is not demoted.
+pub(crate) fn process_demote_inactive_seat<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    _data: &[u8],
+) -> ProgramResult {
+    let DemoteInactiveSeatContext { seat: seat_info } =
+        DemoteInactiveSeatContext::load(market_context, accounts)?;
+    let PhoenixMarketContext { market_info, .. } = market_context;
+    let seat_inactivity_threshold_slots = market_info.get_header()?.seat_inactivity_threshold_slots;
+    assert_with_msg(
+        seat_inactivity_threshold_slots != 0,
+        PhoenixError::InvalidSeatStatus,
+        "Seat inactivity demotion is disabled on this market",
+    )?;
+
+    let mut seat = seat_info.load_mut()?;
+    assert_with_msg(
+        SeatApprovalStatus::from(seat.approval_status) == SeatApprovalStatus::Approved,
+        PhoenixError::InvalidSeatStatus,
+        "Seat must be approved",
+    )?;
+    let current_slot = Clock::get()?.slot;
+    let last_active_slot = {
+        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let trader_state = market.get_trader_state_mut(&seat.trader).ok_or_else(|| {
+            phoenix_log!(
+                "Trader {} has no recorded activity on this market",
+                seat.trader
+            );
+            PhoenixError::TraderNotFound
+        })?;
+        // Seats that were approved before activity was tracked have no last active slot. Their
+        // inactivity is measured from the first demotion attempt instead of from slot 0.
+        if trader_state.last_active_slot == 0 {
+            trader_state.last_active_slot = current_slot;
+            phoenix_log!(
+                "Trader {} has no recorded activity, inactivity is measured from slot {}",
+                seat.trader,
+                current_slot
+            );
+            return Ok(());
+        }
+        trader_state.last_active_slot
+    };
+    assert_with_msg(
+        current_slot.saturating_sub(last_active_slot) > seat_inactivity_threshold_slots,
+        PhoenixError::InvalidSeatStatus,
+        &format!(
+            "Trader {} was last active in slot {}, which is within {} slots of {}",
+            seat.trader, last_active_slot, seat_inactivity_threshold_slots, current_slot
+        ),
+    )?;
+    seat.approval_status = SeatApprovalStatus::NotApproved as u64;
+    phoenix_log!(
+        "Seat for trader {} demoted after {} inactive slots",
+        seat.trader,
+        current_slot - last_active_slot
+    );
+    Ok(())
+}
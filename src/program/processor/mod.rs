@@ -1,13 +1,23 @@
+pub mod amend_order;
+pub mod assert_market_price;
 pub mod cancel_multiple_orders;
 pub mod deposit;
 pub mod fees;
 pub mod governance;
 pub mod initialize;
+pub mod internal_transfer;
 pub mod manage_seat;
 pub mod new_order;
+pub mod prune_expired_orders;
 pub mod reduce_order;
+pub mod stop_orders;
 pub mod withdraw;
 
+pub use amend_order::*;
+pub use assert_market_price::*;
 pub use cancel_multiple_orders::*;
 pub use initialize::*;
+pub use internal_transfer::*;
+pub use prune_expired_orders::*;
 pub use reduce_order::*;
+pub use stop_orders::*;
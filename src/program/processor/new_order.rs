@@ -1,20 +1,42 @@
 use crate::{
     program::{
-        dispatch_market::load_with_dispatch_mut,
+        deposit::{process_deposit, DepositParams},
+        dispatch_market::{get_market_size, load_with_dispatch, load_with_dispatch_mut},
         error::{assert_with_msg, PhoenixError},
-        loaders::NewOrderContext,
+        event_recorder::EventRecorder,
+        events::{SwapRouteSummary, SwapSummary},
+        iceberg_reserve_book::{
+            get_iceberg_reserve_book_offset_from_header, load_iceberg_reserve_book_mut,
+            split_iceberg_reserve_book_mut, IcebergReserveBook,
+        },
+        loaders::{NativeSolContext, NewOrderContext, SwapRouteHopContext},
+        market_data_tracker::MarketDataTracker,
+        referral_fee_book::{
+            get_referral_fee_book_offset_from_header, load_referral_fee_book,
+            load_referral_fee_book_mut,
+        },
         status::MarketStatus,
-        token_utils::{maybe_invoke_deposit, maybe_invoke_withdraw},
-        MarketHeader, PhoenixMarketContext, PhoenixVaultContext,
+        stop_order_book::{
+            get_quote_lots_to_lock_for_stop_bid, get_stop_order_book_offset,
+            get_stop_order_book_size, load_stop_order_book_mut,
+            MAX_STOP_ORDERS_TRIGGERED_PER_INSTRUCTION,
+        },
+        token_utils::{
+            close_native_sol_account, create_native_sol_account, invoke_exact_deposit,
+            maybe_invoke_withdraw, wrap_native_sol,
+        },
+        validation::checkers::{phoenix_checkers::MarketAccountInfo, Signer},
+        CancelOrderParams, MarketHeader, PhoenixInstruction, PhoenixLogContext,
+        PhoenixMarketContext, PhoenixVaultContext,
     },
     quantities::{
-        BaseAtoms, BaseAtomsPerBaseLot, BaseLots, QuoteAtoms, QuoteAtomsPerQuoteLot, QuoteLots,
-        Ticks, WrapperU64,
+        BaseAtoms, BaseAtomsPerBaseLot, BaseLots, BaseLotsPerBaseUnit, QuoteAtoms,
+        QuoteAtomsPerQuoteLot, QuoteLots, QuoteLotsPerBaseUnitPerTick, Ticks, WrapperU64,
     },
     state::{
         decode_order_packet,
-        markets::{FIFOOrderId, FIFORestingOrder, MarketEvent, MarketWrapperMut},
-        OrderPacket, OrderPacketMetadata, Side,
+        markets::{FIFOOrderId, FIFORestingOrder, Market, MarketEvent, MarketWrapperMut},
+        OrderPacket, OrderPacketMetadata, PhoenixMatchError, SelfTradeBehavior, Side,
     },
 };
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -23,7 +45,7 @@ use solana_program::{
     account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, log::sol_log_compute_units,
     program_error::ProgramError, pubkey::Pubkey, sysvar::Sysvar,
 };
-use std::mem::size_of;
+use std::{cell::Cell, mem::size_of};
 
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
 pub enum FailedMultipleLimitOrderBehavior {
@@ -136,14 +158,56 @@ impl MultipleOrderPacket {
     }
 }
 
+/// Parameters for an order that is sized as a fraction of the trader's free funds at the time
+/// the order is placed.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy)]
+pub struct PlaceByFundsFractionParams {
+    /// The fraction of the trader's free funds to use, in basis points. Bids use free quote lots
+    /// and asks use free base lots. Must be in (0, 10000].
+    pub fraction_in_bps: u64,
+    /// A PostOnly, Limit or ImmediateOrCancel order that only uses deposited funds. The size of
+    /// the packet is ignored and replaced by the size computed from the trader's free funds.
+    pub order_packet: OrderPacket,
+}
+
+impl PlaceByFundsFractionParams {
+    pub fn new(fraction_in_bps: u64, order_packet: OrderPacket) -> Self {
+        PlaceByFundsFractionParams {
+            fraction_in_bps,
+            order_packet,
+        }
+    }
+}
+
+/// Parameters to reduce a resting order and send the freed funds as an immediate-or-cancel
+/// order on the same side. Reducing an ask frees base lots that are sold, and reducing a bid frees
+/// quote lots that are used to buy.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy)]
+pub struct CancelAndSwapParams {
+    pub base_params: CancelOrderParams,
+    /// Size of the order to reduce in base lots. If this is larger than the order, the order is
+    /// cancelled in full
+    pub size: u64,
+    /// The most aggressive price the immediate-or-cancel order can be matched at. If this is None,
+    /// the order is treated as a market order
+    pub price_in_ticks: Option<u64>,
+    /// The instruction fails if the immediate-or-cancel order does not fill this many base lots
+    pub min_base_lots_to_fill: u64,
+    /// The instruction fails if the immediate-or-cancel order does not fill this many quote lots
+    pub min_quote_lots_to_fill: u64,
+    pub self_trade_behavior: SelfTradeBehavior,
+    pub client_order_id: u128,
+}
+
 /// This function performs an IOC or FOK order against the specified market.
+/// Returns a summary of the swap, which is set as the return data of the instruction.
 pub(crate) fn process_swap<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
     accounts: &'a [AccountInfo<'info>],
     data: &[u8],
     record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
-) -> ProgramResult {
+) -> Result<SwapSummary, ProgramError> {
     sol_log_compute_units();
     let new_order_context = NewOrderContext::load_cross_only(market_context, accounts, false)?;
     let mut order_packet = decode_order_packet(data).ok_or_else(|| {
@@ -175,17 +239,306 @@ pub(crate) fn process_swap<'a, 'info>(
     )
 }
 
+/// This function performs an IOC or FOK order against a market whose quote mint is the native mint.
+/// The trader's temporary wrapped SOL account is created before the swap and closed after it, which
+/// returns the unspent or received SOL to the trader. Buys wrap the SOL returned by
+/// `get_quote_lots_to_wrap_for_swap`. The system program and the native mint are passed after the
+/// accounts of `Swap`. Returns a summary of the swap, which is set as the return data of the
+/// instruction.
+pub(crate) fn process_swap_with_native_sol<'a, 'info>(
+    program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> Result<SwapSummary, ProgramError> {
+    let NativeSolContext {
+        accounts,
+        native_sol_account,
+        native_sol_account_bump,
+        token_program,
+        system_program,
+        native_mint,
+    } = NativeSolContext::load(market_context, accounts, false)?;
+    let order_packet = decode_order_packet(data).ok_or_else(|| {
+        phoenix_log!("Failed to decode order packet");
+        ProgramError::InvalidInstructionData
+    })?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    create_native_sol_account(
+        market_info.key,
+        trader.as_ref(),
+        native_sol_account,
+        native_sol_account_bump,
+        &native_mint,
+        token_program.as_ref(),
+        system_program.as_ref(),
+    )?;
+    if order_packet.side() == Side::Bid {
+        let quote_lots_to_wrap = {
+            let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+            let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+            get_quote_lots_to_wrap_for_swap(&order_packet, market).ok_or_else(|| {
+                phoenix_log!("Buys with SOL must specify a quote lot budget or a limit price");
+                ProgramError::InvalidInstructionData
+            })?
+        };
+        let quote_lot_size = market_info.get_header()?.get_quote_lot_size();
+        wrap_native_sol(
+            (quote_lots_to_wrap * quote_lot_size).as_u64(),
+            trader.as_ref(),
+            native_sol_account,
+            token_program.as_ref(),
+            system_program.as_ref(),
+        )?;
+    }
+    let swap_summary = process_swap(program_id, market_context, accounts, data, record_event_fn)?;
+    close_native_sol_account(trader.as_ref(), native_sol_account, token_program.as_ref())?;
+    Ok(swap_summary)
+}
+
+/// Returns the number of quote lots that a buy on a native SOL market must wrap to pay for the
+/// order. Buys with a quote lot budget wrap the budget. Otherwise, the order can spend at most its
+/// size at its limit price, plus the highest taker fee of the market, because the fee tier of the
+/// trader is only known once the order is matched. Returns None if the order sets neither a budget
+/// nor a limit price, or if the amount overflows.
+fn get_quote_lots_to_wrap_for_swap(
+    order_packet: &OrderPacket,
+    market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+) -> Option<QuoteLots> {
+    if let Some(quote_lot_budget) = order_packet.quote_lot_budget() {
+        return Some(quote_lot_budget);
+    }
+    let price_in_ticks = order_packet.get_price_in_ticks();
+    if price_in_ticks == Ticks::MAX {
+        return None;
+    }
+    let base_lots_per_base_unit = market.get_base_lots_per_base_unit().as_u128();
+    let quote_lots = u64::try_from(
+        price_in_ticks
+            .as_u128()
+            .checked_mul(market.get_tick_size().as_u128())?
+            .checked_mul(order_packet.num_base_lots().as_u128())?
+            .checked_add(base_lots_per_base_unit - 1)?
+            / base_lots_per_base_unit,
+    )
+    .ok()?;
+    let max_taker_fee_bps = market
+        .get_fee_tiers_bps()
+        .iter()
+        .fold(market.get_taker_fee_bps(), |max_fee_bps, fee_bps| {
+            max_fee_bps.max(*fee_bps)
+        });
+    let fee = u64::try_from((quote_lots as u128 * max_taker_fee_bps as u128 + 10000 - 1) / 10000)
+        .ok()?
+        .max(market.get_min_fee_quote_lots().as_u64());
+    quote_lots.checked_add(fee).map(QuoteLots::new)
+}
+
+/// Parameters for two swaps on different markets that are executed in a single instruction. The
+/// token received from the first swap must be the token spent by the second swap.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy)]
+pub struct SwapRouteParams {
+    pub first_order_packet: OrderPacket,
+    /// The input size of this order is replaced by the amount received from the first swap,
+    /// rounded down to whole lots of the second market.
+    pub second_order_packet: OrderPacket,
+    /// The instruction fails if the second swap returns fewer atoms than this.
+    pub min_output_atoms: u64,
+}
+
+/// This function performs two IOC or FOK orders on different markets, where the tokens received
+/// from the first order are spent by the second. Both swaps settle through the trader's token
+/// accounts, and the instruction fails if the second swap returns less than `min_output_atoms`.
+/// Returns a summary of both swaps, which is set as the return data of the instruction.
+pub(crate) fn process_swap_route<'a, 'info>(
+    _program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> Result<SwapRouteSummary, ProgramError> {
+    assert_with_msg(
+        accounts.len() > 3,
+        ProgramError::NotEnoughAccountKeys,
+        "Both markets must be provided",
+    )?;
+    let (program_accounts, market_accounts) = accounts.split_at(3);
+    let trader = Signer::new(&program_accounts[2])?;
+    let SwapRouteParams {
+        first_order_packet,
+        mut second_order_packet,
+        min_output_atoms,
+    } = SwapRouteParams::try_from_slice(data)?;
+    for order_packet in [&first_order_packet, &second_order_packet] {
+        assert_with_msg(
+            order_packet.is_take_only(),
+            ProgramError::InvalidInstructionData,
+            "Order type must be IOC or FOK",
+        )?;
+        assert_with_msg(
+            !order_packet.no_deposit_or_withdrawal(),
+            ProgramError::InvalidInstructionData,
+            "Instruction does not allow using deposited funds",
+        )?;
+    }
+
+    let account_iter = &mut market_accounts.iter();
+    let first_hop = SwapRouteHopContext::load_from_iter(account_iter, &trader)?;
+    let second_hop = SwapRouteHopContext::load_from_iter(account_iter, &trader)?;
+    assert_with_msg(
+        first_hop.market_context.market_info.key != second_hop.market_context.market_info.key,
+        ProgramError::InvalidArgument,
+        "The swaps must be on different markets",
+    )?;
+
+    let intermediate_account = match first_order_packet.side() {
+        Side::Bid => first_hop.vault_context.base_account.clone(),
+        Side::Ask => first_hop.vault_context.quote_account.clone(),
+    };
+    let (second_input_account, output_account) = match second_order_packet.side() {
+        Side::Bid => (
+            second_hop.vault_context.quote_account.clone(),
+            second_hop.vault_context.base_account.clone(),
+        ),
+        Side::Ask => (
+            second_hop.vault_context.base_account.clone(),
+            second_hop.vault_context.quote_account.clone(),
+        ),
+    };
+    assert_with_msg(
+        intermediate_account.key == second_input_account.key,
+        PhoenixError::SwapRouteError,
+        "The second swap must spend from the token account that receives the first swap's output",
+    )?;
+    let (base_lot_size, quote_lot_size) = {
+        let header = second_hop.market_context.market_info.get_header()?;
+        (header.get_base_lot_size(), header.get_quote_lot_size())
+    };
+
+    // The intermediate amount is measured from the token account rather than assumed from the swap
+    let intermediate_atoms_before = intermediate_account.amount()?;
+    let first_swap = process_swap_route_hop(program_accounts, first_hop, first_order_packet)?;
+    let intermediate_atoms = intermediate_account
+        .amount()?
+        .saturating_sub(intermediate_atoms_before);
+    assert_with_msg(
+        set_order_packet_input_size(
+            &mut second_order_packet,
+            intermediate_atoms,
+            base_lot_size,
+            quote_lot_size,
+        ),
+        PhoenixError::SwapRouteError,
+        &format!(
+            "The first swap returned {} atoms, which is less than one lot of the second market",
+            intermediate_atoms
+        ),
+    )?;
+
+    let output_atoms_before = output_account.amount()?;
+    let second_swap = process_swap_route_hop(program_accounts, second_hop, second_order_packet)?;
+    let output_atoms = output_account.amount()?.saturating_sub(output_atoms_before);
+    assert_with_msg(
+        output_atoms >= min_output_atoms,
+        PhoenixError::SwapRouteError,
+        &format!(
+            "Swap route returned {} atoms, expected at least {}",
+            output_atoms, min_output_atoms
+        ),
+    )?;
+    Ok(SwapRouteSummary {
+        first_swap,
+        second_swap,
+    })
+}
+
+fn process_swap_route_hop<'a, 'info>(
+    program_accounts: &'a [AccountInfo<'info>],
+    hop: SwapRouteHopContext<'a, 'info>,
+    mut order_packet: OrderPacket,
+) -> Result<SwapSummary, ProgramError> {
+    let SwapRouteHopContext {
+        market_context,
+        vault_context,
+    } = hop;
+    // Each market has its own sequence number, so events are recorded separately for each market
+    let mut event_recorder = EventRecorder::new(
+        PhoenixLogContext::load(&mut program_accounts.iter())?,
+        &market_context,
+        PhoenixInstruction::SwapRoute,
+    )?;
+    let mut market_data_tracker = MarketDataTracker::new(&market_context)?;
+    let swap_summary = {
+        let mut record_event_fn = |e: MarketEvent<Pubkey>| {
+            market_data_tracker.track_event(&e);
+            event_recorder.add_event(e)
+        };
+        process_new_order(
+            NewOrderContext {
+                seat_option: None,
+                vault_context: Some(vault_context),
+            },
+            &market_context,
+            &mut order_packet,
+            &mut record_event_fn,
+            &mut vec![],
+        )?
+    };
+    market_data_tracker.write(&market_context.market_info)?;
+    event_recorder.increment_market_sequence_number_and_flush(market_context.market_info)?;
+    Ok(swap_summary)
+}
+
+/// Sets the input size of an IOC or FOK order to `input_atoms`, rounded down to whole lots.
+/// Bids spend quote lots and asks spend base lots. Returns false if the input is less than a lot.
+fn set_order_packet_input_size(
+    order_packet: &mut OrderPacket,
+    input_atoms: u64,
+    base_lot_size: BaseAtomsPerBaseLot,
+    quote_lot_size: QuoteAtomsPerQuoteLot,
+) -> bool {
+    let side = order_packet.side();
+    match order_packet {
+        OrderPacket::ImmediateOrCancel {
+            num_base_lots,
+            num_quote_lots,
+            ..
+        }
+        | OrderPacket::FillOrKill {
+            num_base_lots,
+            num_quote_lots,
+            ..
+        } => {
+            match side {
+                Side::Bid => {
+                    *num_base_lots = BaseLots::ZERO;
+                    *num_quote_lots = QuoteAtoms::new(input_atoms).unchecked_div(quote_lot_size);
+                }
+                Side::Ask => {
+                    *num_base_lots = BaseAtoms::new(input_atoms).unchecked_div(base_lot_size);
+                    *num_quote_lots = QuoteLots::ZERO;
+                }
+            }
+            *num_base_lots > BaseLots::ZERO || *num_quote_lots > QuoteLots::ZERO
+        }
+        _ => false,
+    }
+}
+
 /// This function performs an IOC or FOK order against the specified market
 /// using only the funds already available to the trader.
 /// Only users with sufficient funds and a "seat" on the market are authorized
 /// to perform this action.
+/// Returns a summary of the swap, which is set as the return data of the instruction.
 pub(crate) fn process_swap_with_free_funds<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
     accounts: &'a [AccountInfo<'info>],
     data: &[u8],
     record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
-) -> ProgramResult {
+) -> Result<SwapSummary, ProgramError> {
     let new_order_context = NewOrderContext::load_cross_only(market_context, accounts, true)?;
     let mut order_packet = decode_order_packet(data).ok_or_else(|| {
         phoenix_log!("Failed to decode order packet");
@@ -216,6 +569,132 @@ pub(crate) fn process_swap_with_free_funds<'a, 'info>(
     )
 }
 
+/// This function reduces a resting order and sends the funds it frees as an IOC order on the
+/// same side, using only the funds already available to the trader. If the IOC order fails,
+/// including when it does not meet its minimum fill, the order is not reduced.
+/// Only users with a "seat" on the market are authorized to perform this action.
+/// Returns a summary of the swap, which is set as the return data of the instruction.
+pub(crate) fn process_cancel_and_swap<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> Result<SwapSummary, ProgramError> {
+    let new_order_context = NewOrderContext::load_cross_only(market_context, accounts, true)?;
+    assert_with_msg(
+        new_order_context.seat_option.is_some(),
+        ProgramError::InvalidInstructionData,
+        "Missing seat for market maker",
+    )?;
+    let CancelAndSwapParams {
+        base_params,
+        size,
+        price_in_ticks,
+        min_base_lots_to_fill,
+        min_quote_lots_to_fill,
+        self_trade_behavior,
+        client_order_id,
+    } = CancelAndSwapParams::try_from_slice(data)?;
+    let CancelOrderParams {
+        side,
+        price_in_ticks: order_price_in_ticks,
+        order_sequence_number,
+    } = base_params;
+    let order_id = FIFOOrderId::new(Ticks::new(order_price_in_ticks), order_sequence_number);
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let (base_lots_freed, quote_lots_freed) = {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let trader_state = market
+            .get_trader_state(trader.key)
+            .ok_or(PhoenixError::TraderNotFound)?;
+        let (base_lots_free_before, quote_lots_free_before) =
+            (trader_state.base_lots_free, trader_state.quote_lots_free);
+        market
+            .reduce_order(
+                trader.key,
+                &order_id,
+                side,
+                Some(BaseLots::new(size)),
+                false,
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
+            .ok_or(PhoenixError::ReduceOrderError)?;
+        let trader_state = market
+            .get_trader_state(trader.key)
+            .ok_or(PhoenixError::TraderNotFound)?;
+        (
+            trader_state.base_lots_free - base_lots_free_before,
+            trader_state.quote_lots_free - quote_lots_free_before,
+        )
+    };
+    let mut order_packet = match side {
+        Side::Bid => {
+            assert_with_msg(
+                quote_lots_freed > QuoteLots::ZERO,
+                PhoenixError::ReduceOrderError,
+                "Reducing the bid did not free any quote lots",
+            )?;
+            OrderPacket::new_ioc(
+                Side::Bid,
+                price_in_ticks,
+                0,
+                quote_lots_freed.as_u64(),
+                min_base_lots_to_fill,
+                min_quote_lots_to_fill,
+                self_trade_behavior,
+                None,
+                client_order_id,
+                true,
+                None,
+                None,
+            )
+        }
+        Side::Ask => {
+            assert_with_msg(
+                base_lots_freed > BaseLots::ZERO,
+                PhoenixError::ReduceOrderError,
+                "Reducing the ask did not free any base lots",
+            )?;
+            OrderPacket::new_ioc(
+                Side::Ask,
+                price_in_ticks,
+                base_lots_freed.as_u64(),
+                0,
+                min_base_lots_to_fill,
+                min_quote_lots_to_fill,
+                self_trade_behavior,
+                None,
+                client_order_id,
+                true,
+                None,
+                None,
+            )
+        }
+    };
+    let mut order_ids = vec![];
+    process_new_order(
+        new_order_context,
+        market_context,
+        &mut order_packet,
+        record_event_fn,
+        &mut order_ids,
+    )
+}
+
 /// This function performs a Post-Only or Limit order against the specified market.
 /// Only users with a "seat" on the market are authorized to perform this action.
 pub(crate) fn process_place_limit_order<'a, 'info>(
@@ -252,7 +731,8 @@ pub(crate) fn process_place_limit_order<'a, 'info>(
         &mut order_packet,
         record_event_fn,
         order_ids,
-    )
+    )?;
+    Ok(())
 }
 
 /// This function performs a Post-Only or Limit order against the specified market
@@ -293,7 +773,50 @@ pub(crate) fn process_place_limit_order_with_free_funds<'a, 'info>(
         &mut order_packet,
         record_event_fn,
         order_ids,
-    )
+    )?;
+    Ok(())
+}
+
+/// This function deposits funds to the trader's seat and then places a Post-Only or Limit order
+/// using only the funds available to the trader. The instruction data is a `DepositParams`
+/// followed by an `OrderPacket`. If either step fails, the entire instruction fails.
+/// Only users with a "seat" on the market are authorized to perform this action.
+pub(crate) fn process_deposit_and_place<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    order_ids: &mut Vec<FIFOOrderId>,
+) -> ProgramResult {
+    let mut order_packet_bytes = data;
+    let deposit_params = DepositParams::deserialize(&mut order_packet_bytes)?;
+    let mut order_packet = decode_order_packet(order_packet_bytes).ok_or_else(|| {
+        phoenix_log!("Failed to decode order packet");
+        ProgramError::InvalidInstructionData
+    })?;
+    assert_with_msg(
+        !order_packet.is_take_only(),
+        ProgramError::InvalidInstructionData,
+        "Order type must be Limit or PostOnly",
+    )?;
+    assert_with_msg(
+        order_packet.no_deposit_or_withdrawal(),
+        ProgramError::InvalidInstructionData,
+        "Order must be set to use only deposited funds",
+    )?;
+    process_deposit(market_context, accounts, deposit_params)?;
+
+    // The seat is the first account after the market context, so it is shared with the deposit
+    let new_order_context = NewOrderContext::load_post_allowed(market_context, accounts, true)?;
+    process_new_order(
+        new_order_context,
+        market_context,
+        &mut order_packet,
+        record_event_fn,
+        order_ids,
+    )?;
+    Ok(())
 }
 
 /// This function places multiple Post-Only orders against the specified market.
@@ -326,6 +849,59 @@ pub(crate) fn process_place_multiple_post_only_orders<'a, 'info>(
     )
 }
 
+/// This function cancels all of the trader's resting orders and then places multiple
+/// Post-Only orders against the specified market. The funds released by the cancels are
+/// kept in the market as free funds, so the new orders only deposit the shortfall.
+/// Only users with a "seat" on the market are authorized to perform this action.
+pub(crate) fn process_replace_quotes<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    order_ids: &mut Vec<FIFOOrderId>,
+) -> ProgramResult {
+    let new_order_context = NewOrderContext::load_post_allowed(market_context, accounts, false)?;
+    let multiple_order_packet = MultipleOrderPacket::try_from_slice(data)?;
+    assert_with_msg(
+        new_order_context.seat_option.is_some(),
+        ProgramError::InvalidInstructionData,
+        "Missing seat for market maker",
+    )?;
+
+    {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        // Funds are not claimed, so they are available to the orders placed below
+        market.cancel_all_orders(
+            trader.key,
+            false,
+            iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+            record_event_fn,
+        );
+    }
+
+    process_multiple_new_orders(
+        new_order_context,
+        market_context,
+        multiple_order_packet,
+        record_event_fn,
+        order_ids,
+        false,
+    )
+}
+
 /// This function plcaces multiple Post-Only orders against the specified market
 /// using only the funds already available to the trader.
 /// Only users with sufficient funds and a "seat" on the market are authorized
@@ -357,13 +933,178 @@ pub(crate) fn process_place_multiple_post_only_orders_with_free_funds<'a, 'info>
     )
 }
 
+/// This function places an order that uses a fraction of the trader's free funds. The size of
+/// the order is computed from the trader's state when the instruction executes, so it cannot be
+/// invalidated by balance changes between the client reading its funds and submitting the order.
+/// If the fraction of free funds is too small to buy or sell a single lot, no order is placed.
+pub(crate) fn process_place_by_funds_fraction<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    order_ids: &mut Vec<FIFOOrderId>,
+) -> ProgramResult {
+    let PlaceByFundsFractionParams {
+        fraction_in_bps,
+        mut order_packet,
+    } = PlaceByFundsFractionParams::try_from_slice(data)?;
+    assert_with_msg(
+        fraction_in_bps > 0 && fraction_in_bps <= 10000,
+        ProgramError::InvalidInstructionData,
+        "Fraction of funds must be between 1 and 10000 bps",
+    )?;
+    assert_with_msg(
+        matches!(
+            order_packet,
+            OrderPacket::PostOnly { .. }
+                | OrderPacket::Limit { .. }
+                | OrderPacket::ImmediateOrCancel { .. }
+        ),
+        ProgramError::InvalidInstructionData,
+        "Order type must be PostOnly, Limit or ImmediateOrCancel",
+    )?;
+    assert_with_msg(
+        order_packet.no_deposit_or_withdrawal(),
+        ProgramError::InvalidInstructionData,
+        "Order must be set to use only deposited funds",
+    )?;
+    let new_order_context = if order_packet.is_take_only() {
+        NewOrderContext::load_cross_only(market_context, accounts, true)?
+    } else {
+        NewOrderContext::load_post_allowed(market_context, accounts, true)?
+    };
+    assert_with_msg(
+        new_order_context.seat_option.is_some(),
+        ProgramError::InvalidInstructionData,
+        "Missing seat for market maker",
+    )?;
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let has_size = {
+        let market_bytes = &market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+        let market = load_with_dispatch(&market_info.size_params, market_bytes)?.inner;
+        let trader_index = market
+            .get_trader_index(trader.key)
+            .ok_or(PhoenixError::TraderNotFound)?;
+        let trader_state = market.get_trader_state_from_index(trader_index);
+        set_order_packet_size_from_funds_fraction(
+            &mut order_packet,
+            fraction_in_bps,
+            trader_state.base_lots_free,
+            trader_state.quote_lots_free,
+            market.get_tick_size(),
+            market.get_base_lots_per_base_unit(),
+            market.get_taker_fee_bps(),
+        )
+    };
+    if !has_size {
+        phoenix_log!(
+            "Trader has insufficient free funds to place an order with {} bps of their funds",
+            fraction_in_bps
+        );
+        return Ok(());
+    }
+    process_new_order(
+        new_order_context,
+        market_context,
+        &mut order_packet,
+        record_event_fn,
+        order_ids,
+    )?;
+    Ok(())
+}
+
+/// Replaces the size of `order_packet` with the size that uses `fraction_in_bps` of the trader's
+/// free funds. Bids that can cross the book reserve enough quote lots to pay the taker fee.
+/// Returns false if the resulting order would be empty.
+#[allow(clippy::too_many_arguments)]
+fn set_order_packet_size_from_funds_fraction(
+    order_packet: &mut OrderPacket,
+    fraction_in_bps: u64,
+    base_lots_free: BaseLots,
+    quote_lots_free: QuoteLots,
+    tick_size: QuoteLotsPerBaseUnitPerTick,
+    base_lots_per_base_unit: BaseLotsPerBaseUnit,
+    taker_fee_bps: u64,
+) -> bool {
+    let base_lots_to_use = (base_lots_free.as_u128() * fraction_in_bps as u128 / 10000) as u64;
+    let quote_lots_to_use = (quote_lots_free.as_u128() * fraction_in_bps as u128 / 10000) as u64;
+    let quote_lots_per_base_lot_numerator =
+        order_packet.get_price_in_ticks().as_u128() * tick_size.as_u128();
+    // Converts a quote lot budget to the number of base lots it buys at the order's price
+    let base_lots_for_quote_lots = |quote_lots: u128| -> u64 {
+        if quote_lots_per_base_lot_numerator == 0 {
+            return 0;
+        }
+        (quote_lots * base_lots_per_base_unit.as_u128() / quote_lots_per_base_lot_numerator)
+            .min(u64::MAX as u128) as u64
+    };
+    let side = order_packet.side();
+    match order_packet {
+        OrderPacket::ImmediateOrCancel {
+            num_base_lots,
+            num_quote_lots,
+            ..
+        } => {
+            // IOC bids are sized in quote lots, and the quote lot budget includes fees
+            match side {
+                Side::Bid => {
+                    *num_base_lots = BaseLots::ZERO;
+                    *num_quote_lots = QuoteLots::new(quote_lots_to_use);
+                }
+                Side::Ask => {
+                    *num_base_lots = BaseLots::new(base_lots_to_use);
+                    *num_quote_lots = QuoteLots::ZERO;
+                }
+            }
+            *num_base_lots > BaseLots::ZERO || *num_quote_lots > QuoteLots::ZERO
+        }
+        OrderPacket::PostOnly { num_base_lots, .. } => {
+            *num_base_lots = BaseLots::new(match side {
+                Side::Bid => base_lots_for_quote_lots(quote_lots_to_use as u128),
+                Side::Ask => base_lots_to_use,
+            });
+            *num_base_lots > BaseLots::ZERO
+        }
+        OrderPacket::Limit { num_base_lots, .. } => {
+            *num_base_lots = BaseLots::new(match side {
+                Side::Bid => base_lots_for_quote_lots(
+                    quote_lots_to_use as u128 * 10000 / (10000 + taker_fee_bps as u128),
+                ),
+                Side::Ask => base_lots_to_use,
+            });
+            *num_base_lots > BaseLots::ZERO
+        }
+        _ => false,
+    }
+}
+
+/// Orders cannot be placed while the current slot is in the market's trading window. Cancels and
+/// withdrawals are unaffected.
+pub(crate) fn assert_trading_enabled<'a, 'info>(
+    market_info: &MarketAccountInfo<'a, 'info>,
+    slot: u64,
+) -> ProgramResult {
+    assert_with_msg(
+        !market_info.get_header()?.is_trading_disabled(slot),
+        PhoenixError::TradingDisabled,
+        "Trading is disabled for the current slot",
+    )
+}
+
+/// Returns a summary of the lots that the order exchanged with the book. Triggered stop orders are
+/// not included.
 fn process_new_order<'a, 'info>(
     new_order_context: NewOrderContext<'a, 'info>,
     market_context: &PhoenixMarketContext<'a, 'info>,
     order_packet: &mut OrderPacket,
     record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
     order_ids: &mut Vec<FIFOOrderId>,
-) -> ProgramResult {
+) -> Result<SwapSummary, ProgramError> {
     let PhoenixMarketContext {
         market_info,
         signer: trader,
@@ -375,16 +1116,60 @@ fn process_new_order<'a, 'info>(
     };
 
     let side = order_packet.side();
+    let clock = Clock::get()?;
+    let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
+    // The referrer's share of the fee is only split off if the referrer is registered
+    if let Some(referrer) = order_packet.get_referrer() {
+        if !can_credit_referrer(market_info, &referrer)? {
+            phoenix_log!(
+                "{} is not a registered referrer, the market keeps the entire fee",
+                referrer
+            );
+            order_packet.set_referrer(None);
+        }
+    }
+    // Stop orders are triggered by the prices of the fills in this instruction
+    let fill_price_range = Cell::new(None);
+    let taker_fee_in_quote_lots = Cell::new(QuoteLots::ZERO);
+    let taker_lots_filled = Cell::new((BaseLots::ZERO, QuoteLots::ZERO));
+    let mut referral_fees = QuoteLots::ZERO;
     let (
         quote_atoms_to_withdraw,
         quote_atoms_to_deposit,
         base_atoms_to_withdraw,
         base_atoms_to_deposit,
+        order_crossed,
+        swap_summary,
     ) = {
-        let clock = Clock::get()?;
-        let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market_wrapper = load_with_dispatch_mut(&market_info.size_params, market_bytes)?;
+        assert_trading_enabled(market_info, clock.slot)?;
+        let mut record_order_event_fn = |e: MarketEvent<Pubkey>| {
+            record_fill_price(&fill_price_range, &e);
+            if let MarketEvent::FillSummary {
+                total_base_lots_filled,
+                total_quote_lots_filled,
+                total_fee_in_quote_lots,
+                ..
+            } = e
+            {
+                taker_fee_in_quote_lots
+                    .set(taker_fee_in_quote_lots.get() + total_fee_in_quote_lots);
+                let (base_lots_filled, quote_lots_filled) = taker_lots_filled.get();
+                taker_lots_filled.set((
+                    base_lots_filled + total_base_lots_filled,
+                    quote_lots_filled + total_quote_lots_filled,
+                ));
+            }
+            record_event_fn(e);
+        };
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, mut iceberg_reserve_book) =
+            split_iceberg_reserve_book_mut(&header, data)?;
+        let market_wrapper = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?;
 
         // If the order should fail silently on insufficient funds, and the trader does not have
         // sufficient funds for the order, return silently without modifying the book.
@@ -402,36 +1187,104 @@ fn process_new_order<'a, 'info>(
                 base_lots_available,
                 quote_lots_available,
             ) {
-                return Ok(());
+                return Ok(SwapSummary::default());
             }
         }
 
-        let (order_id, matching_engine_response) = market_wrapper
-            .inner
-            .place_order(
-                trader.key,
-                *order_packet,
-                record_event_fn,
-                &mut get_clock_fn,
-            )
-            .ok_or(PhoenixError::NewOrderError)?;
+        let unclaimed_referral_fees_before =
+            market_wrapper.inner.get_unclaimed_referral_fee_amount();
+        let token_budget = if order_packet.is_place_partial() {
+            Some(get_token_budget_for_trader(
+                vault_context.as_ref(),
+                base_lot_size,
+                quote_lot_size,
+            )?)
+        } else {
+            None
+        };
+        let (order_id, matching_engine_response) =
+            match (iceberg_reserve_book.as_deref_mut(), token_budget) {
+                // Orders on markets with iceberg orders enabled are placed with the reserve book, so
+                // iceberg orders that are filled by this order are replenished
+                (Some(iceberg_reserve_book), token_budget) => {
+                    market_wrapper.inner.place_order_with_iceberg_reserves(
+                        trader.key,
+                        *order_packet,
+                        token_budget,
+                        &mut iceberg_reserve_book.reserves,
+                        &mut record_order_event_fn,
+                        &mut get_clock_fn,
+                    )?
+                }
+                (None, Some(token_budget)) => market_wrapper.inner.place_order_with_token_budget(
+                    trader.key,
+                    *order_packet,
+                    token_budget,
+                    &mut record_order_event_fn,
+                    &mut get_clock_fn,
+                )?,
+                (None, None) => market_wrapper.inner.place_order(
+                    trader.key,
+                    *order_packet,
+                    &mut record_order_event_fn,
+                    &mut get_clock_fn,
+                )?,
+            };
 
         if let Some(order_id) = order_id {
             order_ids.push(order_id);
         }
+        referral_fees = market_wrapper.inner.get_unclaimed_referral_fee_amount()
+            - unclaimed_referral_fees_before;
+
+        let mut swap_summary = SwapSummary {
+            base_lots_in: matching_engine_response.num_base_lots_in.as_u64(),
+            base_lots_out: matching_engine_response.num_base_lots_out.as_u64(),
+            quote_lots_in: matching_engine_response.num_quote_lots_in.as_u64(),
+            quote_lots_out: matching_engine_response.num_quote_lots_out.as_u64(),
+            fee_in_quote_lots: taker_fee_in_quote_lots.get().as_u64(),
+        };
+        // Orders that only use deposited funds credit their fills to the trader's deposited funds
+        // instead of withdrawing them, so the lots out are taken from the fill summary
+        if order_packet.no_deposit_or_withdrawal() {
+            let (base_lots_filled, quote_lots_filled) = taker_lots_filled.get();
+            match side {
+                Side::Bid => swap_summary.base_lots_out = base_lots_filled.as_u64(),
+                Side::Ask => {
+                    swap_summary.quote_lots_out =
+                        (quote_lots_filled - taker_fee_in_quote_lots.get()).as_u64()
+                }
+            }
+        }
 
         (
             matching_engine_response.num_quote_lots_out * quote_lot_size,
             matching_engine_response.get_deposit_amount_bid_in_quote_lots() * quote_lot_size,
             matching_engine_response.num_base_lots_out * base_lot_size,
             matching_engine_response.get_deposit_amount_ask_in_base_lots() * base_lot_size,
+            // Orders that only use deposited funds do not withdraw their fills, so the matched
+            // amounts are checked instead of the withdrawals
+            matching_engine_response.num_base_lots() > BaseLots::ZERO
+                || matching_engine_response.num_quote_lots() > QuoteLots::ZERO,
+            swap_summary,
         )
     };
+    if let Some(referrer) = order_packet.get_referrer() {
+        credit_referral_fees(market_info, &referrer, referral_fees)?;
+    }
+    if fill_price_range.get().is_some() {
+        process_triggered_stop_orders(
+            market_info,
+            &fill_price_range,
+            record_event_fn,
+            &mut get_clock_fn,
+        )?;
+    }
     let header = market_info.get_header()?;
     let quote_params = &header.quote_params;
     let base_params = &header.base_params;
 
-    if quote_atoms_to_withdraw > QuoteAtoms::ZERO || base_atoms_to_withdraw > BaseAtoms::ZERO {
+    if order_crossed {
         let status = MarketStatus::from(header.status);
         assert_with_msg(
             status.cross_allowed(),
@@ -446,6 +1299,8 @@ fn process_new_order<'a, 'info>(
             base_vault,
             quote_vault,
             token_program,
+            base_mint,
+            quote_mint,
         }) = vault_context
         {
             match side {
@@ -458,12 +1313,14 @@ fn process_new_order<'a, 'info>(
                         &token_program,
                         &base_account,
                         &base_vault,
+                        base_mint.as_ref(),
                     )?;
-                    maybe_invoke_deposit(
+                    invoke_exact_deposit(
                         quote_atoms_to_deposit.as_u64(),
                         &token_program,
                         &quote_account,
                         &quote_vault,
+                        quote_mint.as_ref(),
                         trader.as_ref(),
                     )?;
                 }
@@ -476,12 +1333,14 @@ fn process_new_order<'a, 'info>(
                         &token_program,
                         &quote_account,
                         &quote_vault,
+                        quote_mint.as_ref(),
                     )?;
-                    maybe_invoke_deposit(
+                    invoke_exact_deposit(
                         base_atoms_to_deposit.as_u64(),
                         &token_program,
                         &base_account,
                         &base_vault,
+                        base_mint.as_ref(),
                         trader.as_ref(),
                     )?;
                 }
@@ -497,6 +1356,206 @@ fn process_new_order<'a, 'info>(
         return Err(ProgramError::InsufficientFunds);
     }
 
+    Ok(swap_summary)
+}
+
+/// Returns true if referral fees are enabled on the market and `referrer` is registered in the
+/// `ReferralFeeBook`
+fn can_credit_referrer<'a, 'info>(
+    market_info: &MarketAccountInfo<'a, 'info>,
+    referrer: &Pubkey,
+) -> Result<bool, ProgramError> {
+    let referral_fee_book_offset =
+        match get_referral_fee_book_offset_from_header(&market_info.get_header()?)? {
+            Some(offset) => offset,
+            None => return Ok(false),
+        };
+    let referral_fee_book_bytes =
+        &market_info.try_borrow_data()?[size_of::<MarketHeader>() + referral_fee_book_offset..];
+    Ok(load_referral_fee_book(referral_fee_book_bytes)?.can_credit(referrer))
+}
+
+/// Adds the referral fees split off from an order to the referrer's unclaimed referral fees
+fn credit_referral_fees<'a, 'info>(
+    market_info: &MarketAccountInfo<'a, 'info>,
+    referrer: &Pubkey,
+    referral_fees: QuoteLots,
+) -> ProgramResult {
+    if referral_fees == QuoteLots::ZERO {
+        return Ok(());
+    }
+    let referral_fee_book_offset =
+        get_referral_fee_book_offset_from_header(&market_info.get_header()?)?
+            .ok_or(PhoenixError::ReferralFeeError)?;
+    let referral_fee_book_bytes = &mut market_info.try_borrow_mut_data()?
+        [size_of::<MarketHeader>() + referral_fee_book_offset..];
+    load_referral_fee_book_mut(referral_fee_book_bytes)?
+        .credit(referrer, referral_fees)
+        .ok_or_else(|| {
+            phoenix_log!("Failed to credit referral fees to {}", referrer);
+            PhoenixError::ReferralFeeError.into()
+        })
+}
+
+/// Widens the range of fill prices to include `event` if it is a fill
+fn record_fill_price(fill_price_range: &Cell<Option<(Ticks, Ticks)>>, event: &MarketEvent<Pubkey>) {
+    if let MarketEvent::Fill { price_in_ticks, .. } = event {
+        fill_price_range.set(Some(match fill_price_range.get() {
+            Some((min_price, max_price)) => (
+                min_price.min(*price_in_ticks),
+                max_price.max(*price_in_ticks),
+            ),
+            None => (*price_in_ticks, *price_in_ticks),
+        }));
+    }
+}
+
+/// Triggers the stop orders that are crossed by the fills in `fill_price_range`, up to
+/// `MAX_STOP_ORDERS_TRIGGERED_PER_INSTRUCTION`. The funds of each triggered stop order are returned
+/// to the trader's free funds, and the stop order is sent as an immediate-or-cancel order at its
+/// limit price that only uses the trader's free funds. The fills of triggered stop orders can
+/// trigger more stop orders.
+///
+/// A stop order that is rejected before it is matched does not fail the instruction. It is logged
+/// and dropped, and its funds remain free. Buy stops are dropped if the trader can no longer afford
+/// them, which can happen if the taker fee was raised after the stop order was placed. Any error
+/// raised once the stop order has matched the book fails the instruction. Triggered stop orders
+/// are not subject to the market's minimum taker fill.
+fn process_triggered_stop_orders<'a, 'info>(
+    market_info: &MarketAccountInfo<'a, 'info>,
+    fill_price_range: &Cell<Option<(Ticks, Ticks)>>,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+    get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+) -> ProgramResult {
+    let header = *market_info.get_header()?;
+    if header.stop_orders_enabled == 0 {
+        return Ok(());
+    }
+    let stop_order_book_offset = get_stop_order_book_offset(
+        &market_info.size_params,
+        header.client_order_id_index_enabled != 0,
+    )?;
+    let iceberg_reserve_book_offset = get_iceberg_reserve_book_offset_from_header(&header)?;
+    let market_size = get_market_size(&market_info.size_params)?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, stop_order_book_bytes) = data.split_at_mut(stop_order_book_offset);
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        &mut market_bytes[..market_size],
+    )?
+    .inner;
+    // The iceberg reserve book is stored after the stop order book
+    let (stop_order_book_bytes, trailing_bytes) =
+        stop_order_book_bytes.split_at_mut(get_stop_order_book_size());
+    let stop_order_book = load_stop_order_book_mut(stop_order_book_bytes)?;
+    let mut iceberg_reserve_book = match iceberg_reserve_book_offset {
+        Some(offset) => Some(load_iceberg_reserve_book_mut(
+            &mut trailing_bytes[offset - stop_order_book_offset - get_stop_order_book_size()..],
+        )?),
+        None => None,
+    };
+    let mut record_stop_event_fn = |e: MarketEvent<Pubkey>| {
+        record_fill_price(fill_price_range, &e);
+        record_event_fn(e);
+    };
+
+    let mut num_stops_remaining = MAX_STOP_ORDERS_TRIGGERED_PER_INSTRUCTION;
+    while let Some((min_fill_price, max_fill_price)) = fill_price_range.get() {
+        let triggered_stop_orders =
+            stop_order_book.remove_triggered(min_fill_price, max_fill_price, num_stops_remaining);
+        if triggered_stop_orders.is_empty() {
+            break;
+        }
+        num_stops_remaining -= triggered_stop_orders.len();
+        for (side, stop_order_id, stop_order) in triggered_stop_orders {
+            record_stop_event_fn(MarketEvent::StopTriggered {
+                trader_id: stop_order.trader,
+                stop_sequence_number: stop_order_id.stop_sequence_number,
+                trigger_price_in_ticks: stop_order_id.trigger_price_in_ticks,
+            });
+            let (tick_size, base_lots_per_base_unit, min_fee_quote_lots, max_taker_fee_bps) = (
+                market.get_tick_size(),
+                market.get_base_lots_per_base_unit(),
+                market.get_min_fee_quote_lots(),
+                market
+                    .get_fee_tiers_bps()
+                    .iter()
+                    .fold(market.get_taker_fee_bps(), |max_fee_bps, fee_bps| {
+                        max_fee_bps.max(*fee_bps)
+                    }),
+            );
+            let trader_state = match market.get_trader_state_mut(&stop_order.trader) {
+                Some(trader_state) => trader_state,
+                None => {
+                    phoenix_log!(
+                        "Dropping stop order {}: trader not found",
+                        stop_order_id.stop_sequence_number
+                    );
+                    continue;
+                }
+            };
+            trader_state
+                .unlock_funds_for_stop(stop_order.quote_lots_locked, stop_order.base_lots_locked);
+            if side == Side::Bid {
+                let quote_lots_required = get_quote_lots_to_lock_for_stop_bid(
+                    stop_order.limit_price_in_ticks,
+                    stop_order.num_base_lots,
+                    tick_size,
+                    base_lots_per_base_unit,
+                    max_taker_fee_bps,
+                    min_fee_quote_lots,
+                )
+                .unwrap_or(QuoteLots::MAX);
+                if trader_state.quote_lots_free < quote_lots_required {
+                    phoenix_log!(
+                        "Dropping stop order {}: {} quote lots required, {} quote lots free",
+                        stop_order_id.stop_sequence_number,
+                        quote_lots_required,
+                        trader_state.quote_lots_free
+                    );
+                    continue;
+                }
+            }
+            let order_packet = OrderPacket::new_ioc_by_lots(
+                side,
+                stop_order.limit_price_in_ticks.as_u64(),
+                stop_order.num_base_lots.as_u64(),
+                SelfTradeBehavior::CancelProvide,
+                None,
+                stop_order.client_order_id(),
+                true,
+            );
+            let result = market.place_order_with_min_taker_fill(
+                &stop_order.trader,
+                order_packet,
+                BaseLots::ZERO,
+                iceberg_reserve_book
+                    .as_deref_mut()
+                    .map(IcebergReserveBook::reserves_mut),
+                &mut record_stop_event_fn,
+                get_clock_fn,
+            );
+            match result {
+                Ok(_) => {}
+                // These errors are returned before the order is matched, so the book is unchanged
+                // and the stop order can be dropped
+                Err(
+                    e @ (PhoenixMatchError::OutsideVolatilityBand
+                    | PhoenixMatchError::TakingDisabled),
+                ) => {
+                    phoenix_log!(
+                        "Dropping stop order {}: {}",
+                        stop_order_id.stop_sequence_number,
+                        e
+                    );
+                }
+                // Any other error can be raised after the book was matched, so the instruction
+                // must fail
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
     Ok(())
 }
 
@@ -548,9 +1607,17 @@ fn process_multiple_new_orders<'a, 'info>(
 
     {
         let clock = Clock::get()?;
+        assert_trading_enabled(market_info, clock.slot)?;
         let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market_wrapper = load_with_dispatch_mut(&market_info.size_params, market_bytes)?;
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, mut iceberg_reserve_book) =
+            split_iceberg_reserve_book_mut(&header, data)?;
+        let market_wrapper = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?;
 
         let (mut base_lots_available, mut quote_lots_available) =
             get_available_balances_for_trader(
@@ -604,6 +1671,9 @@ fn process_multiple_new_orders<'a, 'info>(
                     last_valid_unix_timestamp_in_seconds,
                     fail_silently_on_insufficient_funds: failed_multiple_limit_order_behavior
                         .should_skip_orders_with_insufficient_funds(),
+                    max_tick_adjustment: None,
+                    require_top_of_book: false,
+                    valid_for_slots: None,
                 };
 
                 let matching_engine_response = {
@@ -619,10 +1689,27 @@ fn process_multiple_new_orders<'a, 'info>(
                         // Skip this order if the trader does not have sufficient funds
                         continue;
                     }
-                    let (order_id, matching_engine_response) = market_wrapper
-                        .inner
-                        .place_order(trader.key, order_packet, record_event_fn, &mut get_clock_fn)
-                        .ok_or(PhoenixError::NewOrderError)?;
+                    // Post-only orders can evict iceberg orders or remove expired ones, so the
+                    // reserve book is passed to release their reserves
+                    let (order_id, matching_engine_response) =
+                        match iceberg_reserve_book.as_deref_mut() {
+                            Some(iceberg_reserve_book) => {
+                                market_wrapper.inner.place_order_with_iceberg_reserves(
+                                    trader.key,
+                                    order_packet,
+                                    None,
+                                    iceberg_reserve_book.reserves_mut(),
+                                    record_event_fn,
+                                    &mut get_clock_fn,
+                                )?
+                            }
+                            None => market_wrapper.inner.place_order(
+                                trader.key,
+                                order_packet,
+                                record_event_fn,
+                                &mut get_clock_fn,
+                            )?,
+                        };
                     if let Some(order_id) = order_id {
                         order_ids.push(order_id);
                     }
@@ -657,14 +1744,17 @@ fn process_multiple_new_orders<'a, 'info>(
             base_vault,
             quote_vault,
             token_program,
+            base_mint,
+            quote_mint,
         }) = vault_context
         {
             if !bids.is_empty() {
-                maybe_invoke_deposit(
+                invoke_exact_deposit(
                     (quote_lots_to_deposit * quote_lot_size).as_u64(),
                     &token_program,
                     &quote_account,
                     &quote_vault,
+                    quote_mint.as_ref(),
                     trader.as_ref(),
                 )?;
             } else {
@@ -675,11 +1765,12 @@ fn process_multiple_new_orders<'a, 'info>(
                 )?;
             }
             if !asks.is_empty() {
-                maybe_invoke_deposit(
+                invoke_exact_deposit(
                     (base_lots_to_deposit * base_lot_size).as_u64(),
                     &token_program,
                     &base_account,
                     &base_vault,
+                    base_mint.as_ref(),
                     trader.as_ref(),
                 )?;
             } else {
@@ -719,18 +1810,32 @@ fn get_available_balances_for_trader<'a>(
             .get_trader_state_from_index(trader_index);
         (trader_state.base_lots_free, trader_state.quote_lots_free)
     };
-    let (base_lots_available, quote_lots_available) = match vault_context.as_ref() {
-        None => (base_lots_free, quote_lots_free),
+    let (base_lots_to_deposit, quote_lots_to_deposit) =
+        get_token_budget_for_trader(vault_context, base_lot_size, quote_lot_size)?;
+    Ok((
+        base_lots_free + base_lots_to_deposit,
+        quote_lots_free + quote_lots_to_deposit,
+    ))
+}
+
+/// Returns the number of base and quote lots that the trader can deposit from their token
+/// accounts. This is zero if the order does not transfer tokens.
+fn get_token_budget_for_trader(
+    vault_context: Option<&PhoenixVaultContext>,
+    base_lot_size: BaseAtomsPerBaseLot,
+    quote_lot_size: QuoteAtomsPerQuoteLot,
+) -> Result<(BaseLots, QuoteLots), ProgramError> {
+    match vault_context {
+        None => Ok((BaseLots::ZERO, QuoteLots::ZERO)),
         Some(ctx) => {
             let quote_account_atoms = ctx.quote_account.amount().map(QuoteAtoms::new)?;
             let base_account_atoms = ctx.base_account.amount().map(BaseAtoms::new)?;
-            (
-                base_lots_free + base_account_atoms.unchecked_div(base_lot_size),
-                quote_lots_free + quote_account_atoms.unchecked_div(quote_lot_size),
-            )
+            Ok((
+                base_account_atoms.unchecked_div(base_lot_size),
+                quote_account_atoms.unchecked_div(quote_lot_size),
+            ))
         }
-    };
-    Ok((base_lots_available, quote_lots_available))
+    }
 }
 
 fn order_packet_has_sufficient_funds<'a>(
@@ -0,0 +1,67 @@
+use crate::{
+    program::{
+        dispatch_market::load_with_dispatch_mut,
+        iceberg_reserve_book::{split_iceberg_reserve_book_mut, IcebergReserveBook},
+        MarketHeader, PhoenixMarketContext,
+    },
+    state::{markets::MarketEvent, Side},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    clock::Clock, entrypoint::ProgramResult, log::sol_log_compute_units, pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use std::mem::size_of;
+
+/// The maximum number of orders that can be scanned in a single instruction
+pub const MAX_ORDERS_TO_SCAN_FOR_EXPIRY: u32 = 128;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct PruneExpiredOrdersParams {
+    pub side: Side,
+    /// Number of orders to scan from the top of the book. This is capped at
+    /// `MAX_ORDERS_TO_SCAN_FOR_EXPIRY`.
+    pub num_orders_to_scan: u32,
+}
+
+/// This function is permissionless. It removes expired orders from one side of the book and
+/// unlocks the makers' funds, which remain deposited in their seats.
+pub(crate) fn process_prune_expired_orders<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let PruneExpiredOrdersParams {
+        side,
+        num_orders_to_scan,
+    } = PruneExpiredOrdersParams::try_from_slice(data)?;
+    let PhoenixMarketContext { market_info, .. } = market_context;
+
+    let clock = Clock::get()?;
+    let mut get_clock_fn = || (clock.slot, clock.unix_timestamp as u64);
+    sol_log_compute_units();
+    let num_orders_pruned = {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        market
+            .prune_expired_orders(
+                side,
+                num_orders_to_scan.min(MAX_ORDERS_TO_SCAN_FOR_EXPIRY) as usize,
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap_or_default()
+    };
+    sol_log_compute_units();
+    phoenix_log!("Pruned {} expired orders", num_orders_pruned);
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use crate::{
     program::{
-        assert_with_msg, dispatch_market::load_with_dispatch_mut, error::PhoenixError,
-        loaders::CancelOrWithdrawContext as Cancel, token_utils::try_withdraw, MarketHeader,
-        PhoenixMarketContext, PhoenixVaultContext,
+        assert_with_msg,
+        dispatch_market::load_with_dispatch_mut,
+        error::PhoenixError,
+        iceberg_reserve_book::{split_iceberg_reserve_book_mut, IcebergReserveBook},
+        loaders::CancelOrWithdrawContext as Cancel,
+        token_utils::try_withdraw,
+        MarketHeader, PhoenixMarketContext, PhoenixVaultContext,
     },
-    quantities::{BaseLots, Ticks, WrapperU64},
+    quantities::{BaseLots, QuoteLots, Ticks, WrapperU64},
     state::{
         markets::{FIFOOrderId, MarketEvent},
         MatchingEngineResponse, Side,
@@ -31,6 +35,18 @@ pub struct ReduceOrderParams {
     pub size: u64,
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct ReduceMultipleOrdersParams {
+    pub orders: Vec<ReduceOrderParams>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct ReduceOrderByQuoteParams {
+    pub base_params: CancelOrderParams,
+    /// Notional amount to remove from the bid in quote lots
+    pub quote_lots: u64,
+}
+
 pub(crate) fn process_reduce_order<'a, 'info>(
     _program_id: &Pubkey,
     market_context: &PhoenixMarketContext<'a, 'info>,
@@ -41,6 +57,184 @@ pub(crate) fn process_reduce_order<'a, 'info>(
 ) -> ProgramResult {
     sol_log_compute_units();
     let ReduceOrderParams { base_params, size } = ReduceOrderParams::try_from_slice(data)?;
+    reduce_order(
+        market_context,
+        accounts,
+        base_params,
+        ReduceOrderSize::BaseLots(BaseLots::new(size)),
+        withdraw_funds,
+        record_event_fn,
+    )
+}
+
+/// This function reduces a bid by a notional amount. The quote lots are converted to base lots at
+/// the order's resting price and rounded down. If the quote lots exceed the value of the order,
+/// the order is removed in full.
+pub(crate) fn process_reduce_order_by_quote<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    sol_log_compute_units();
+    let ReduceOrderByQuoteParams {
+        base_params,
+        quote_lots,
+    } = ReduceOrderByQuoteParams::try_from_slice(data)?;
+    assert_with_msg(
+        base_params.side == Side::Bid,
+        PhoenixError::ReduceOrderError,
+        "Only bids can be reduced by quote lots",
+    )?;
+    reduce_order(
+        market_context,
+        accounts,
+        base_params,
+        ReduceOrderSize::QuoteLots(QuoteLots::new(quote_lots)),
+        withdraw_funds,
+        record_event_fn,
+    )
+}
+
+/// This function reduces each of the given orders by its size. Orders that are not on the book or
+/// do not belong to the trader are skipped instead of failing the instruction. The released funds
+/// are withdrawn in a single transfer per token.
+pub(crate) fn process_reduce_multiple_orders<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let vault_context_option = if withdraw_funds {
+        let Cancel { vault_context } = Cancel::load(market_context, accounts)?;
+        Some(vault_context)
+    } else {
+        None
+    };
+
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+
+    let ReduceMultipleOrdersParams { orders } = ReduceMultipleOrdersParams::try_from_slice(data)?;
+    if orders.is_empty() {
+        phoenix_log!("No orders to reduce");
+        return Ok(());
+    }
+    // Orders whose side does not match their sequence number cannot be on the book
+    let orders_to_reduce = orders
+        .iter()
+        .filter(|ReduceOrderParams { base_params, .. }| {
+            let side_matches = base_params.side
+                == Side::from_order_sequence_number(base_params.order_sequence_number);
+            if !side_matches {
+                phoenix_log!(
+                    "Skipping order {}, which is not on the {:?} side",
+                    base_params.order_sequence_number,
+                    base_params.side
+                );
+            }
+            side_matches
+        })
+        .map(|ReduceOrderParams { base_params, size }| {
+            (
+                FIFOOrderId::new(
+                    Ticks::new(base_params.price_in_ticks),
+                    base_params.order_sequence_number,
+                ),
+                BaseLots::new(*size),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let MatchingEngineResponse {
+        num_quote_lots_out,
+        num_base_lots_out,
+        ..
+    } = {
+        sol_log_compute_units();
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        market
+            .reduce_multiple_orders(
+                trader.key,
+                &orders_to_reduce,
+                vault_context_option.is_some(),
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+                record_event_fn,
+            )
+            .ok_or(PhoenixError::ReduceOrderError)?
+    };
+    sol_log_compute_units();
+
+    let header = market_info.get_header()?;
+
+    if let Some(PhoenixVaultContext {
+        base_account,
+        quote_account,
+        base_vault,
+        quote_vault,
+        token_program,
+        base_mint,
+        quote_mint,
+    }) = vault_context_option
+    {
+        try_withdraw(
+            market_info.key,
+            &header.base_params,
+            &header.quote_params,
+            token_program.as_ref(),
+            quote_account.as_ref(),
+            quote_vault,
+            quote_mint.as_ref(),
+            base_account.as_ref(),
+            base_vault,
+            base_mint.as_ref(),
+            num_quote_lots_out * header.get_quote_lot_size(),
+            num_base_lots_out * header.get_base_lot_size(),
+        )?;
+    } else {
+        // This case is only reached if the user is reducing orders with free funds
+        // In this case, there should be no funds to claim
+        assert_with_msg(
+            num_quote_lots_out == 0,
+            PhoenixError::ReduceOrderError,
+            "WARNING: num_quote_lots_out must be 0",
+        )?;
+        assert_with_msg(
+            num_base_lots_out == 0,
+            PhoenixError::ReduceOrderError,
+            "WARNING: num_base_lots_out must be 0",
+        )?;
+    }
+    Ok(())
+}
+
+enum ReduceOrderSize {
+    BaseLots(BaseLots),
+    QuoteLots(QuoteLots),
+}
+
+fn reduce_order<'a, 'info>(
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    base_params: CancelOrderParams,
+    size: ReduceOrderSize,
+    withdraw_funds: bool,
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
     let CancelOrderParams {
         side,
         price_in_ticks,
@@ -65,16 +259,39 @@ pub(crate) fn process_reduce_order<'a, 'info>(
         num_base_lots_out,
         ..
     } = {
-        let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        let size = match size {
+            ReduceOrderSize::BaseLots(base_lots) => base_lots,
+            ReduceOrderSize::QuoteLots(quote_lots) => {
+                // Round down so that no more than the requested quote lots are unlocked.
+                // The engine caps the size at the size of the order.
+                let quote_lots_per_base_unit =
+                    order_id.price_in_ticks.as_u128() * market.get_tick_size().as_u128();
+                BaseLots::new(
+                    (quote_lots.as_u128() * market.get_base_lots_per_base_unit().as_u128())
+                        .checked_div(quote_lots_per_base_unit)
+                        .unwrap_or(0)
+                        .min(u64::MAX as u128) as u64,
+                )
+            }
+        };
         sol_log_compute_units();
         market
             .reduce_order(
                 trader.key,
                 &order_id,
                 side,
-                Some(BaseLots::new(size)),
+                Some(size),
                 vault_context_option.is_some(),
+                iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
                 record_event_fn,
             )
             .ok_or(PhoenixError::ReduceOrderError)?
@@ -89,6 +306,8 @@ pub(crate) fn process_reduce_order<'a, 'info>(
         base_vault,
         quote_vault,
         token_program,
+        base_mint,
+        quote_mint,
     }) = vault_context_option
     {
         try_withdraw(
@@ -98,8 +317,10 @@ pub(crate) fn process_reduce_order<'a, 'info>(
             token_program.as_ref(),
             quote_account.as_ref(),
             quote_vault,
+            quote_mint.as_ref(),
             base_account.as_ref(),
             base_vault,
+            base_mint.as_ref(),
             num_quote_lots_out * header.get_quote_lot_size(),
             num_base_lots_out * header.get_base_lot_size(),
         )?;
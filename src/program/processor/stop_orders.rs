@@ -0,0 +1,235 @@
+use crate::{
+    program::{
+        dispatch_market::{get_market_size, load_with_dispatch_mut},
+        error::{assert_with_msg, PhoenixError},
+        loaders::NewOrderContext,
+        stop_order_book::{
+            get_quote_lots_to_lock_for_stop_bid, get_stop_order_book_offset,
+            load_stop_order_book_mut, StopOrder, StopOrderId, MAX_STOP_ORDERS_PER_TRADER,
+        },
+        MarketHeader, PhoenixMarketContext,
+    },
+    quantities::{BaseLots, QuoteLots, Ticks, WrapperU64},
+    state::{markets::MarketEvent, Side},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::NodeAllocatorMap;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::mem::size_of;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaceStopOrderParams {
+    pub side: Side,
+    /// Buy stops are triggered by a fill at or above this price, and sell stops are triggered by
+    /// a fill at or below this price
+    pub trigger_price_in_ticks: u64,
+    /// When the stop order is triggered, it is sent as an immediate-or-cancel order that does not
+    /// match at prices worse than this
+    pub limit_price_in_ticks: u64,
+    pub num_base_lots: u64,
+    pub client_order_id: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CancelStopOrderParams {
+    pub side: Side,
+    pub trigger_price_in_ticks: u64,
+    pub stop_sequence_number: u64,
+}
+
+/// Places a stop order that waits outside of the book until a fill triggers it. The funds needed
+/// by the triggered order are moved out of the trader's free funds until the stop order is
+/// triggered or cancelled. Buy stops hold enough quote lots to buy the full size at the limit
+/// price and pay the highest taker fee of the market. Each trader can have at most
+/// `MAX_STOP_ORDERS_PER_TRADER` stop orders waiting to trigger.
+/// Only users with sufficient funds and a "seat" on the market are authorized
+/// to perform this action.
+pub(crate) fn process_place_stop_order<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let PlaceStopOrderParams {
+        side,
+        trigger_price_in_ticks,
+        limit_price_in_ticks,
+        num_base_lots,
+        client_order_id,
+    } = PlaceStopOrderParams::try_from_slice(data)?;
+    let new_order_context = NewOrderContext::load_post_allowed(market_context, accounts, true)?;
+    assert_with_msg(
+        new_order_context.seat_option.is_some(),
+        ProgramError::InvalidInstructionData,
+        "Missing seat for market maker",
+    )?;
+    assert_with_msg(
+        trigger_price_in_ticks > 0 && limit_price_in_ticks > 0 && num_base_lots > 0,
+        ProgramError::InvalidInstructionData,
+        "Stop orders must have a nonzero trigger price, limit price, and size",
+    )?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let stop_order_book_offset = {
+        let header = market_info.get_header()?;
+        assert_with_msg(
+            header.stop_orders_enabled != 0,
+            PhoenixError::StopOrderError,
+            "Stop orders are not enabled on this market",
+        )?;
+        get_stop_order_book_offset(
+            &market_info.size_params,
+            header.client_order_id_index_enabled != 0,
+        )?
+    };
+    let market_size = get_market_size(&market_info.size_params)?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, stop_order_book_bytes) = data.split_at_mut(stop_order_book_offset);
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        &mut market_bytes[..market_size],
+    )?
+    .inner;
+    let stop_order_book = load_stop_order_book_mut(stop_order_book_bytes)?;
+    assert_with_msg(
+        stop_order_book.get_num_stops_for_trader(trader.key) < MAX_STOP_ORDERS_PER_TRADER,
+        PhoenixError::StopOrderError,
+        &format!(
+            "Traders can have at most {} stop orders",
+            MAX_STOP_ORDERS_PER_TRADER
+        ),
+    )?;
+
+    let limit_price_in_ticks = Ticks::new(limit_price_in_ticks);
+    let num_base_lots = BaseLots::new(num_base_lots);
+    let (quote_lots_to_lock, base_lots_to_lock) = match side {
+        Side::Bid => {
+            let max_taker_fee_bps = market
+                .get_fee_tiers_bps()
+                .iter()
+                .fold(market.get_taker_fee_bps(), |max_fee_bps, fee_bps| {
+                    max_fee_bps.max(*fee_bps)
+                });
+            let quote_lots = get_quote_lots_to_lock_for_stop_bid(
+                limit_price_in_ticks,
+                num_base_lots,
+                market.get_tick_size(),
+                market.get_base_lots_per_base_unit(),
+                max_taker_fee_bps,
+                market.get_min_fee_quote_lots(),
+            )
+            .ok_or(ProgramError::InvalidInstructionData)?;
+            (quote_lots, BaseLots::ZERO)
+        }
+        Side::Ask => (QuoteLots::ZERO, num_base_lots),
+    };
+    let trader_state = market
+        .get_trader_state_mut(trader.key)
+        .ok_or(PhoenixError::TraderNotFound)?;
+    assert_with_msg(
+        trader_state.quote_lots_free >= quote_lots_to_lock
+            && trader_state.base_lots_free >= base_lots_to_lock,
+        PhoenixError::StopOrderError,
+        &format!(
+            "Insufficient free funds for the stop order, {} quote lots and {} base lots are required. Free quote lots: {}, free base lots: {}",
+            quote_lots_to_lock,
+            base_lots_to_lock,
+            trader_state.quote_lots_free,
+            trader_state.base_lots_free
+        ),
+    )?;
+    trader_state.lock_funds_for_stop(quote_lots_to_lock, base_lots_to_lock);
+
+    let stop_order_id = stop_order_book
+        .insert(
+            side,
+            Ticks::new(trigger_price_in_ticks),
+            StopOrder::new(
+                *trader.key,
+                limit_price_in_ticks,
+                num_base_lots,
+                quote_lots_to_lock,
+                base_lots_to_lock,
+                client_order_id,
+            ),
+        )
+        .ok_or_else(|| {
+            phoenix_log!("There is no space left for {:?} stop orders", side);
+            PhoenixError::StopOrderError
+        })?;
+    record_event_fn(MarketEvent::StopPlaced {
+        trader_id: *trader.key,
+        stop_sequence_number: stop_order_id.stop_sequence_number,
+        side,
+        trigger_price_in_ticks: stop_order_id.trigger_price_in_ticks,
+        limit_price_in_ticks,
+        num_base_lots,
+        client_order_id,
+    });
+    Ok(())
+}
+
+/// Cancels a stop order that has not been triggered and returns its funds to the trader's free
+/// funds.
+pub(crate) fn process_cancel_stop_order<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    data: &[u8],
+) -> ProgramResult {
+    let CancelStopOrderParams {
+        side,
+        trigger_price_in_ticks,
+        stop_sequence_number,
+    } = CancelStopOrderParams::try_from_slice(data)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    let stop_order_book_offset = {
+        let header = market_info.get_header()?;
+        assert_with_msg(
+            header.stop_orders_enabled != 0,
+            PhoenixError::StopOrderError,
+            "Stop orders are not enabled on this market",
+        )?;
+        get_stop_order_book_offset(
+            &market_info.size_params,
+            header.client_order_id_index_enabled != 0,
+        )?
+    };
+    let market_size = get_market_size(&market_info.size_params)?;
+    let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+    let (market_bytes, stop_order_book_bytes) = data.split_at_mut(stop_order_book_offset);
+    let market = load_with_dispatch_mut(
+        &market_info.size_params,
+        market_info.market_type,
+        &mut market_bytes[..market_size],
+    )?
+    .inner;
+    let stops = load_stop_order_book_mut(stop_order_book_bytes)?.get_stops_mut(side);
+
+    let stop_order_id = StopOrderId::new(Ticks::new(trigger_price_in_ticks), stop_sequence_number);
+    let stop_order = stops.get(&stop_order_id).copied().ok_or_else(|| {
+        phoenix_log!("Stop order {} not found", stop_sequence_number);
+        PhoenixError::StopOrderError
+    })?;
+    assert_with_msg(
+        stop_order.trader == *trader.key,
+        PhoenixError::StopOrderError,
+        "Only the trader that placed a stop order can cancel it",
+    )?;
+    stops.remove(&stop_order_id);
+    market
+        .get_trader_state_mut(trader.key)
+        .ok_or(PhoenixError::TraderNotFound)?
+        .unlock_funds_for_stop(stop_order.quote_lots_locked, stop_order.base_lots_locked);
+    phoenix_log!("Cancelled stop order {}", stop_sequence_number);
+    Ok(())
+}
@@ -1,19 +1,25 @@
 use crate::{
     program::{
-        dispatch_market::load_with_dispatch_mut,
+        dispatch_market::{load_with_dispatch, load_with_dispatch_mut},
         error::{assert_with_msg, PhoenixError},
-        loaders::CancelOrWithdrawContext as Withdraw,
-        token_utils::try_withdraw,
-        validation::checkers::phoenix_checkers::MarketAccountInfo,
-        MarketHeader, PhoenixMarketContext, PhoenixVaultContext,
+        event_recorder::EventRecorder,
+        iceberg_reserve_book::{split_iceberg_reserve_book_mut, IcebergReserveBook},
+        loaders::{
+            CancelAllAndCloseSeatContext, MarketWithdrawalContext, NativeSolContext,
+            WithdrawContext,
+        },
+        token_utils::{close_native_sol_account, create_native_sol_account, try_withdraw},
+        validation::checkers::{phoenix_checkers::MarketAccountInfo, Signer},
+        MarketHeader, PhoenixInstruction, PhoenixLogContext, PhoenixMarketContext,
+        PhoenixVaultContext,
     },
     quantities::{BaseLots, QuoteLots, WrapperU64},
-    state::MatchingEngineResponse,
+    state::{markets::MarketEvent, MatchingEngineResponse},
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::AccountInfo, entrypoint::ProgramResult, log::sol_log_compute_units,
-    pubkey::Pubkey,
+    program_error::ProgramError, pubkey::Pubkey, system_program,
 };
 use std::mem::size_of;
 
@@ -21,6 +27,10 @@ use std::mem::size_of;
 pub struct WithdrawParams {
     pub quote_lots_to_withdraw: Option<u64>,
     pub base_lots_to_withdraw: Option<u64>,
+    /// If true, all free funds are withdrawn, the trader is removed from the market, and the
+    /// lamports of the seat account are returned to the trader. The trader must not have any
+    /// locked funds or open orders. A new seat must be requested and approved to trade again.
+    pub close_seat: Option<bool>,
 }
 
 pub(crate) fn process_withdraw_funds<'a, 'info>(
@@ -29,11 +39,23 @@ pub(crate) fn process_withdraw_funds<'a, 'info>(
     accounts: &'a [AccountInfo<'info>],
     data: &[u8],
 ) -> ProgramResult {
-    let Withdraw { vault_context } = Withdraw::load(market_context, accounts)?;
     let WithdrawParams {
         quote_lots_to_withdraw,
         base_lots_to_withdraw,
-    } = WithdrawParams::try_from_slice(data)?;
+        close_seat,
+    } = WithdrawParams::try_from_slice(data)
+        // The trailing close_seat parameter may be omitted from the instruction data by older clients
+        .or_else(|_| WithdrawParams::try_from_slice(&[data, &[0]].concat()))?;
+    let close_seat = close_seat.unwrap_or(false);
+    assert_with_msg(
+        !close_seat || (quote_lots_to_withdraw.is_none() && base_lots_to_withdraw.is_none()),
+        ProgramError::InvalidInstructionData,
+        "All funds must be withdrawn to close the seat",
+    )?;
+    let WithdrawContext {
+        vault_context,
+        seat_option,
+    } = WithdrawContext::load(market_context, accounts, close_seat)?;
     let PhoenixMarketContext {
         market_info,
         signer: trader,
@@ -44,8 +66,184 @@ pub(crate) fn process_withdraw_funds<'a, 'info>(
         vault_context,
         quote_lots_to_withdraw,
         base_lots_to_withdraw,
-        false,
-    )
+        close_seat,
+    )?;
+
+    // The trader has been removed from the market, so the seat account can be closed
+    if let Some(seat) = seat_option {
+        close_seat_account(trader, &seat)?;
+    }
+    Ok(())
+}
+
+/// Cancels all of the trader's orders, withdraws all of their funds to the trader's token accounts,
+/// removes the trader from the market and closes the seat, returning its lamports to the trader.
+/// Fails if any of the trader's funds are still locked after the orders are cancelled, for example
+/// by a stop order that has not been triggered.
+pub(crate) fn process_cancel_all_and_close_seat<'a, 'info>(
+    _program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    _data: &[u8],
+    record_event_fn: &mut dyn FnMut(MarketEvent<Pubkey>),
+) -> ProgramResult {
+    let CancelAllAndCloseSeatContext {
+        vault_context,
+        seat,
+    } = CancelAllAndCloseSeatContext::load(market_context, accounts)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    {
+        let header = *market_info.get_header()?;
+        let data = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
+        let (market_bytes, iceberg_reserve_book) = split_iceberg_reserve_book_mut(&header, data)?;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
+        // The released funds are credited to the trader's free funds and withdrawn below
+        market.cancel_all_orders(
+            trader.key,
+            false,
+            iceberg_reserve_book.map(IcebergReserveBook::reserves_mut),
+            record_event_fn,
+        );
+        let trader_state = market
+            .get_trader_state(trader.key)
+            .ok_or(PhoenixError::TraderNotFound)?;
+        assert_with_msg(
+            trader_state.quote_lots_locked == QuoteLots::ZERO
+                && trader_state.base_lots_locked == BaseLots::ZERO
+                && trader_state.quote_lots_locked_for_stops == QuoteLots::ZERO
+                && trader_state.base_lots_locked_for_stops == BaseLots::ZERO,
+            PhoenixError::EvictionError,
+            &format!(
+                "Trader {} still has locked funds, cancel all stop orders before closing the seat",
+                trader.key
+            ),
+        )?;
+    }
+    process_withdraw(
+        market_info,
+        trader.as_ref().clone(),
+        vault_context,
+        None,
+        None,
+        true,
+    )?;
+    close_seat_account(trader, &seat)
+}
+
+/// Creates the trader's temporary wrapped SOL account, withdraws funds to it, and closes the
+/// account, so that the quote tokens are received as SOL. The system program and the native mint
+/// are passed after the accounts of `WithdrawFunds`.
+pub(crate) fn process_withdraw_funds_to_native_sol<'a, 'info>(
+    program_id: &Pubkey,
+    market_context: &PhoenixMarketContext<'a, 'info>,
+    accounts: &'a [AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let NativeSolContext {
+        accounts,
+        native_sol_account,
+        native_sol_account_bump,
+        token_program,
+        system_program,
+        native_mint,
+    } = NativeSolContext::load(market_context, accounts, false)?;
+    let PhoenixMarketContext {
+        market_info,
+        signer: trader,
+    } = market_context;
+    create_native_sol_account(
+        market_info.key,
+        trader.as_ref(),
+        native_sol_account,
+        native_sol_account_bump,
+        &native_mint,
+        token_program.as_ref(),
+        system_program.as_ref(),
+    )?;
+    process_withdraw_funds(program_id, market_context, accounts, data)?;
+    close_native_sol_account(trader.as_ref(), native_sol_account, token_program.as_ref())
+}
+
+/// Withdraws all free funds of the trader from every market passed to the instruction. Markets
+/// where the trader has no free funds are skipped.
+///
+/// This instruction does not use the standard account layout. The first three accounts are the
+/// Phoenix program, the log authority, and the trader. They are followed by one group of accounts
+/// per market, see `MarketWithdrawalContext`.
+pub(crate) fn process_withdraw_from_markets<'a, 'info>(
+    _program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+) -> ProgramResult {
+    assert_with_msg(
+        accounts.len() > 3,
+        ProgramError::NotEnoughAccountKeys,
+        "At least one market must be provided",
+    )?;
+    let (program_accounts, market_accounts) = accounts.split_at(3);
+    let trader = Signer::new(&program_accounts[2])?;
+    let account_iter = &mut market_accounts.iter();
+    while account_iter.len() > 0 {
+        let MarketWithdrawalContext {
+            market_context,
+            vault_context,
+        } = MarketWithdrawalContext::load_from_iter(account_iter, &trader)?;
+        let has_free_funds = {
+            let market_bytes =
+                &market_context.market_info.try_borrow_data()?[size_of::<MarketHeader>()..];
+            let market =
+                load_with_dispatch(&market_context.market_info.size_params, market_bytes)?.inner;
+            market
+                .get_trader_state(trader.key)
+                .map(|trader_state| {
+                    trader_state.base_lots_free > BaseLots::ZERO
+                        || trader_state.quote_lots_free > QuoteLots::ZERO
+                })
+                .unwrap_or(false)
+        };
+        if !has_free_funds {
+            phoenix_log!(
+                "No free funds to withdraw from market {}",
+                market_context.market_info.key
+            );
+            continue;
+        }
+        // Each market has its own sequence number, so events are recorded separately for each market
+        let mut event_recorder = EventRecorder::new(
+            PhoenixLogContext::load(&mut program_accounts.iter())?,
+            &market_context,
+            PhoenixInstruction::WithdrawFromMarkets,
+        )?;
+        process_withdraw(
+            &market_context.market_info,
+            trader.as_ref().clone(),
+            vault_context,
+            None,
+            None,
+            false,
+        )?;
+        event_recorder.increment_market_sequence_number_and_flush(market_context.market_info)?;
+    }
+    Ok(())
+}
+
+/// Closes the seat of a trader that has been removed from the market, returning its lamports to
+/// the trader
+fn close_seat_account(trader: &AccountInfo, seat: &AccountInfo) -> ProgramResult {
+    let trader_starting_lamports = trader.lamports();
+    **trader.lamports.borrow_mut() = trader_starting_lamports + seat.lamports();
+    **seat.lamports.borrow_mut() = 0;
+    seat.assign(&system_program::id());
+    seat.realloc(0, false)?;
+    phoenix_log!("Seat has been closed");
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -65,6 +263,8 @@ pub(crate) fn process_withdraw<'a, 'info>(
         base_vault,
         quote_vault,
         token_program,
+        base_mint,
+        quote_mint,
     } = vault_context;
     let MatchingEngineResponse {
         num_quote_lots_out,
@@ -73,7 +273,12 @@ pub(crate) fn process_withdraw<'a, 'info>(
     } = {
         sol_log_compute_units();
         let market_bytes = &mut market_info.try_borrow_mut_data()?[size_of::<MarketHeader>()..];
-        let market = load_with_dispatch_mut(&market_info.size_params, market_bytes)?.inner;
+        let market = load_with_dispatch_mut(
+            &market_info.size_params,
+            market_info.market_type,
+            market_bytes,
+        )?
+        .inner;
         let response = market
             .claim_funds(
                 trader.key,
@@ -102,8 +307,10 @@ pub(crate) fn process_withdraw<'a, 'info>(
         token_program.as_ref(),
         quote_account.as_ref(),
         quote_vault,
+        quote_mint.as_ref(),
         base_account.as_ref(),
         base_vault,
+        base_mint.as_ref(),
         num_quote_lots_out * header.get_quote_lot_size(),
         num_base_lots_out * header.get_base_lot_size(),
     )?;
@@ -0,0 +1,148 @@
+use super::error::PhoenixError;
+use super::referral_fee_book::{get_referral_fee_book_offset, get_referral_fee_book_size};
+use super::{MarketHeader, MarketSizeParams};
+use crate::quantities::{BaseLots, Ticks};
+use bytemuck::{Pod, Zeroable};
+use sokoban::node_allocator::ZeroCopy;
+use solana_program::program_error::ProgramError;
+
+/// The number of fills kept by the recent fills buffer of a market
+pub const MAX_RECENT_FILLS: usize = 64;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Zeroable, Pod)]
+pub struct RecentFill {
+    /// The market sequence number of the instruction in which the fill occurred. Fills from the
+    /// same instruction share a sequence number and are stored in the order they occurred.
+    pub market_sequence_number: u64,
+    pub price_in_ticks: Ticks,
+    pub base_lots_filled: BaseLots,
+}
+
+/// Markets that are initialized with recent fills enabled store this struct in the market account
+/// after the market, the client order id index, the stop order book and the referral fee book (if
+/// they are enabled).
+///
+/// The buffer holds the last `MAX_RECENT_FILLS` fills on the market, oldest first, so light clients
+/// can read a bounded trade history from the market account without the event log. Once the buffer
+/// is full, each new fill evicts the oldest one.
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod)]
+pub struct RecentFills {
+    num_fills: u64,
+    _padding: [u64; 7],
+    fills: [RecentFill; MAX_RECENT_FILLS],
+}
+
+impl ZeroCopy for RecentFills {}
+
+impl RecentFills {
+    /// Returns the stored fills, oldest first
+    pub fn get_recent_fills(&self) -> &[RecentFill] {
+        &self.fills[..self.num_fills as usize]
+    }
+
+    /// Appends `new_fills` to the buffer, evicting the oldest fills if there is not enough space
+    pub fn push_fills(&mut self, new_fills: &[RecentFill]) {
+        let new_fills = &new_fills[new_fills.len().saturating_sub(MAX_RECENT_FILLS)..];
+        let num_fills = self.num_fills as usize;
+        let num_evicted = (num_fills + new_fills.len()).saturating_sub(MAX_RECENT_FILLS);
+        self.fills.copy_within(num_evicted..num_fills, 0);
+        let start = num_fills - num_evicted;
+        self.fills[start..start + new_fills.len()].copy_from_slice(new_fills);
+        self.num_fills = (start + new_fills.len()) as u64;
+    }
+}
+
+pub fn get_recent_fills_size() -> usize {
+    std::mem::size_of::<RecentFills>()
+}
+
+/// Returns the offset of the recent fills buffer from the end of the market header
+pub fn get_recent_fills_offset(
+    market_size_params: &MarketSizeParams,
+    client_order_id_index_enabled: bool,
+    stop_orders_enabled: bool,
+    referral_fees_enabled: bool,
+) -> Result<usize, ProgramError> {
+    let mut offset = get_referral_fee_book_offset(
+        market_size_params,
+        client_order_id_index_enabled,
+        stop_orders_enabled,
+    )?;
+    if referral_fees_enabled {
+        offset += get_referral_fee_book_size();
+    }
+    Ok(offset)
+}
+
+/// Returns the offset of the recent fills buffer from the end of the market header, or None if
+/// recent fills are not enabled on the market
+pub fn get_recent_fills_offset_from_header(
+    header: &MarketHeader,
+) -> Result<Option<usize>, ProgramError> {
+    if header.recent_fills_enabled == 0 {
+        return Ok(None);
+    }
+    get_recent_fills_offset(
+        &header.market_size_params,
+        header.client_order_id_index_enabled != 0,
+        header.stop_orders_enabled != 0,
+        header.referral_fees_enabled != 0,
+    )
+    .map(Some)
+}
+
+pub(crate) fn initialize_recent_fills(bytes: &mut [u8]) -> Result<(), ProgramError> {
+    *load_recent_fills_mut(bytes)? = RecentFills::zeroed();
+    Ok(())
+}
+
+pub(crate) fn load_recent_fills_mut(bytes: &mut [u8]) -> Result<&mut RecentFills, ProgramError> {
+    RecentFills::load_mut_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+pub fn load_recent_fills(bytes: &[u8]) -> Result<&RecentFills, ProgramError> {
+    RecentFills::load_bytes(bytes).ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+#[test]
+fn test_recent_fills_evicts_oldest() {
+    use crate::quantities::WrapperU64;
+    let mut bytes = vec![0_u8; get_recent_fills_size()];
+    initialize_recent_fills(&mut bytes).unwrap();
+    let recent_fills = load_recent_fills_mut(&mut bytes).unwrap();
+    let fill = |i: u64| RecentFill {
+        market_sequence_number: i,
+        price_in_ticks: Ticks::new(100 + i),
+        base_lots_filled: BaseLots::new(1),
+    };
+
+    assert!(recent_fills.get_recent_fills().is_empty());
+    recent_fills.push_fills(&[fill(0), fill(1)]);
+    assert_eq!(recent_fills.get_recent_fills(), &[fill(0), fill(1)]);
+
+    // Once the buffer is full, the oldest fills are evicted
+    let new_fills = (2..MAX_RECENT_FILLS as u64 + 5)
+        .map(fill)
+        .collect::<Vec<_>>();
+    recent_fills.push_fills(&new_fills);
+    assert_eq!(
+        recent_fills.get_recent_fills(),
+        (5..MAX_RECENT_FILLS as u64 + 5)
+            .map(fill)
+            .collect::<Vec<_>>()
+            .as_slice()
+    );
+
+    // Only the last `MAX_RECENT_FILLS` of a large batch are kept
+    let new_fills = (0..2 * MAX_RECENT_FILLS as u64)
+        .map(fill)
+        .collect::<Vec<_>>();
+    recent_fills.push_fills(&new_fills);
+    assert_eq!(
+        recent_fills.get_recent_fills(),
+        &new_fills[MAX_RECENT_FILLS..]
+    );
+}
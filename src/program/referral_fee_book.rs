@@ -0,0 +1,192 @@
+use super::error::PhoenixError;
+use super::stop_order_book::{get_stop_order_book_offset, get_stop_order_book_size};
+use super::{MarketHeader, MarketSizeParams};
+use crate::quantities::QuoteLots;
+use bytemuck::{Pod, Zeroable};
+use sokoban::node_allocator::{NodeAllocatorMap, ZeroCopy};
+use sokoban::RedBlackTree;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+/// The maximum number of referrers that can be registered on a market
+pub const MAX_REFERRERS: usize = 128;
+
+/// Markets that are initialized with referral fees enabled store this struct in the market account
+/// after the market, the client order id index and the stop order book (if they are enabled).
+///
+/// Referrers must be registered by the market authority. Immediate-or-cancel orders that name a
+/// registered referrer accrue a share of their taker fee to the referrer, and orders that name any
+/// other key pay the entire fee to the market. The accrued fees are held by the market until the
+/// referrer claims them. A referrer keeps their entry until the authority deregisters them.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable)]
+pub struct ReferralFeeBook {
+    _padding: [u64; 8],
+    pub referrers: RedBlackTree<Pubkey, QuoteLots, MAX_REFERRERS>,
+}
+
+unsafe impl Pod for ReferralFeeBook {}
+
+impl ZeroCopy for ReferralFeeBook {}
+
+impl ReferralFeeBook {
+    pub fn len(&self) -> usize {
+        self.referrers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the unclaimed referral fees of `referrer`, in quote lots
+    pub fn get_unclaimed(&self, referrer: &Pubkey) -> QuoteLots {
+        self.referrers
+            .get(referrer)
+            .copied()
+            .unwrap_or(QuoteLots::ZERO)
+    }
+
+    /// Returns true if `referrer` is registered and can be credited
+    pub fn can_credit(&self, referrer: &Pubkey) -> bool {
+        self.referrers.contains(referrer)
+    }
+
+    /// Adds an entry for `referrer` with no unclaimed referral fees. Registering a referrer twice
+    /// has no effect. Returns None if the book is full.
+    pub fn register(&mut self, referrer: &Pubkey) -> Option<()> {
+        if !self.referrers.contains(referrer) {
+            self.referrers.insert(*referrer, QuoteLots::ZERO)?;
+        }
+        Some(())
+    }
+
+    /// Removes the entry of `referrer` and returns its unclaimed referral fees. Returns None if
+    /// `referrer` is not registered.
+    pub fn deregister(&mut self, referrer: &Pubkey) -> Option<QuoteLots> {
+        self.referrers.remove(referrer)
+    }
+
+    /// Adds `quote_lots` to the unclaimed referral fees of `referrer`. Returns None if `referrer`
+    /// is not registered.
+    pub fn credit(&mut self, referrer: &Pubkey, quote_lots: QuoteLots) -> Option<()> {
+        *self.referrers.get_mut(referrer)? += quote_lots;
+        Some(())
+    }
+
+    /// Returns the unclaimed referral fees of `referrer` and resets them to zero. The referrer
+    /// stays registered.
+    pub fn claim(&mut self, referrer: &Pubkey) -> QuoteLots {
+        match self.referrers.get_mut(referrer) {
+            Some(unclaimed) => std::mem::replace(unclaimed, QuoteLots::ZERO),
+            None => QuoteLots::ZERO,
+        }
+    }
+}
+
+pub fn get_referral_fee_book_size() -> usize {
+    std::mem::size_of::<ReferralFeeBook>()
+}
+
+/// Returns the offset of the referral fee book from the end of the market header
+pub fn get_referral_fee_book_offset(
+    market_size_params: &MarketSizeParams,
+    client_order_id_index_enabled: bool,
+    stop_orders_enabled: bool,
+) -> Result<usize, ProgramError> {
+    let mut offset = get_stop_order_book_offset(market_size_params, client_order_id_index_enabled)?;
+    if stop_orders_enabled {
+        offset += get_stop_order_book_size();
+    }
+    Ok(offset)
+}
+
+/// Returns the offset of the referral fee book from the end of the market header, or None if
+/// referral fees are not enabled on the market
+pub fn get_referral_fee_book_offset_from_header(
+    header: &MarketHeader,
+) -> Result<Option<usize>, ProgramError> {
+    if header.referral_fees_enabled == 0 {
+        return Ok(None);
+    }
+    get_referral_fee_book_offset(
+        &header.market_size_params,
+        header.client_order_id_index_enabled != 0,
+        header.stop_orders_enabled != 0,
+    )
+    .map(Some)
+}
+
+pub(crate) fn initialize_referral_fee_book(bytes: &mut [u8]) -> Result<(), ProgramError> {
+    load_referral_fee_book_mut(bytes)?.referrers.initialize();
+    Ok(())
+}
+
+pub(crate) fn load_referral_fee_book_mut(
+    bytes: &mut [u8],
+) -> Result<&mut ReferralFeeBook, ProgramError> {
+    ReferralFeeBook::load_mut_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+pub fn load_referral_fee_book(bytes: &[u8]) -> Result<&ReferralFeeBook, ProgramError> {
+    ReferralFeeBook::load_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+#[test]
+fn test_referral_fee_book_credit_and_claim() {
+    use crate::quantities::WrapperU64;
+    let mut bytes = vec![0_u8; get_referral_fee_book_size()];
+    initialize_referral_fee_book(&mut bytes).unwrap();
+    let referral_fee_book = load_referral_fee_book_mut(&mut bytes).unwrap();
+    let referrer = Pubkey::new_unique();
+
+    // Referrers that are not registered cannot be credited
+    assert!(!referral_fee_book.can_credit(&referrer));
+    assert!(referral_fee_book
+        .credit(&referrer, QuoteLots::new(10))
+        .is_none());
+    assert!(referral_fee_book.is_empty());
+
+    referral_fee_book.register(&referrer).unwrap();
+    referral_fee_book.register(&referrer).unwrap();
+    assert_eq!(referral_fee_book.len(), 1);
+    assert_eq!(referral_fee_book.get_unclaimed(&referrer), QuoteLots::ZERO);
+    referral_fee_book
+        .credit(&referrer, QuoteLots::new(10))
+        .unwrap();
+    referral_fee_book
+        .credit(&referrer, QuoteLots::new(5))
+        .unwrap();
+    assert_eq!(
+        referral_fee_book.get_unclaimed(&referrer),
+        QuoteLots::new(15)
+    );
+
+    // Claiming resets the unclaimed fees but keeps the referrer registered
+    assert_eq!(referral_fee_book.claim(&referrer), QuoteLots::new(15));
+    assert_eq!(referral_fee_book.claim(&referrer), QuoteLots::ZERO);
+    assert!(referral_fee_book.can_credit(&referrer));
+    referral_fee_book
+        .credit(&referrer, QuoteLots::new(3))
+        .unwrap();
+
+    // Once the book is full, no new referrers can be registered
+    for _ in 1..MAX_REFERRERS {
+        referral_fee_book.register(&Pubkey::new_unique()).unwrap();
+    }
+    let new_referrer = Pubkey::new_unique();
+    assert!(referral_fee_book.register(&new_referrer).is_none());
+    assert!(referral_fee_book.register(&referrer).is_some());
+
+    // Deregistering returns the unclaimed fees and frees the entry
+    assert_eq!(
+        referral_fee_book.deregister(&referrer),
+        Some(QuoteLots::new(3))
+    );
+    assert_eq!(referral_fee_book.deregister(&referrer), None);
+    assert!(!referral_fee_book.can_credit(&referrer));
+    assert_eq!(referral_fee_book.len(), MAX_REFERRERS - 1);
+    referral_fee_book.register(&new_referrer).unwrap();
+    assert!(referral_fee_book.can_credit(&new_referrer));
+}
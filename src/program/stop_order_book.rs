@@ -0,0 +1,311 @@
+use super::client_order_id_index::get_client_order_id_index_size;
+use super::error::PhoenixError;
+use super::{get_market_size, MarketSizeParams};
+use crate::quantities::{
+    BaseLots, BaseLotsPerBaseUnit, QuoteLots, QuoteLotsPerBaseUnitPerTick, Ticks, WrapperU64,
+};
+use crate::state::Side;
+use bytemuck::{Pod, Zeroable};
+use sokoban::node_allocator::{NodeAllocatorMap, OrderedNodeAllocatorMap, ZeroCopy};
+use sokoban::RedBlackTree;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+/// The maximum number of stop orders that can be waiting to trigger on each side of a market
+pub const MAX_STOP_ORDERS_PER_SIDE: usize = 128;
+
+/// The maximum number of stop orders that a single trader can have waiting to trigger on a market
+pub const MAX_STOP_ORDERS_PER_TRADER: usize = 8;
+
+/// The maximum number of stop orders that can be triggered by the fills of a single instruction.
+/// Stop orders past this limit stay in the stop order book and are triggered by the next fill in
+/// their range.
+pub const MAX_STOP_ORDERS_TRIGGERED_PER_INSTRUCTION: usize = 8;
+
+/// Markets that are initialized with stop orders enabled store this struct in the market account
+/// after the market and the client order id index (if it is enabled).
+///
+/// Stop orders are not on the book. Buy stops are triggered by a fill at or above their trigger
+/// price, and sell stops are triggered by a fill at or below their trigger price. A triggered stop
+/// is removed from this struct and sent as an immediate-or-cancel order at its limit price.
+#[repr(C)]
+#[derive(Default, Copy, Clone, Zeroable)]
+pub struct StopOrderBook {
+    /// Incremented every time a stop order is placed. Stop orders trigger in the order they were placed
+    pub stop_sequence_number: u64,
+    _padding: [u64; 7],
+    pub buy_stops: RedBlackTree<StopOrderId, StopOrder, MAX_STOP_ORDERS_PER_SIDE>,
+    pub sell_stops: RedBlackTree<StopOrderId, StopOrder, MAX_STOP_ORDERS_PER_SIDE>,
+}
+
+unsafe impl Pod for StopOrderBook {}
+
+impl ZeroCopy for StopOrderBook {}
+
+/// Stop orders are sorted by trigger price, and then by the order in which they were placed
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Zeroable, Pod)]
+pub struct StopOrderId {
+    pub trigger_price_in_ticks: Ticks,
+    pub stop_sequence_number: u64,
+}
+
+impl StopOrderId {
+    pub fn new(trigger_price_in_ticks: Ticks, stop_sequence_number: u64) -> Self {
+        StopOrderId {
+            trigger_price_in_ticks,
+            stop_sequence_number,
+        }
+    }
+}
+
+/// The funds of a stop order are moved out of the trader's free funds while the stop waits to
+/// trigger, and they are returned to the trader's free funds when it triggers or is cancelled.
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Zeroable, Pod)]
+pub struct StopOrder {
+    pub trader: Pubkey,
+    /// The triggered order does not match at prices worse than this
+    pub limit_price_in_ticks: Ticks,
+    pub num_base_lots: BaseLots,
+    pub quote_lots_locked: QuoteLots,
+    pub base_lots_locked: BaseLots,
+    /// The client order id is split into two u64s to keep the stop order 8-byte aligned
+    client_order_id_low: u64,
+    client_order_id_high: u64,
+}
+
+impl StopOrder {
+    pub fn new(
+        trader: Pubkey,
+        limit_price_in_ticks: Ticks,
+        num_base_lots: BaseLots,
+        quote_lots_locked: QuoteLots,
+        base_lots_locked: BaseLots,
+        client_order_id: u128,
+    ) -> Self {
+        StopOrder {
+            trader,
+            limit_price_in_ticks,
+            num_base_lots,
+            quote_lots_locked,
+            base_lots_locked,
+            client_order_id_low: client_order_id as u64,
+            client_order_id_high: (client_order_id >> 64) as u64,
+        }
+    }
+
+    pub fn client_order_id(&self) -> u128 {
+        ((self.client_order_id_high as u128) << 64) | self.client_order_id_low as u128
+    }
+}
+
+impl StopOrderBook {
+    pub fn get_stops(&self, side: Side) -> &dyn OrderedNodeAllocatorMap<StopOrderId, StopOrder> {
+        match side {
+            Side::Bid => &self.buy_stops,
+            Side::Ask => &self.sell_stops,
+        }
+    }
+
+    pub fn get_stops_mut(
+        &mut self,
+        side: Side,
+    ) -> &mut dyn OrderedNodeAllocatorMap<StopOrderId, StopOrder> {
+        match side {
+            Side::Bid => &mut self.buy_stops,
+            Side::Ask => &mut self.sell_stops,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buy_stops.len() + self.sell_stops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of stop orders that `trader` has waiting to trigger on both sides
+    pub fn get_num_stops_for_trader(&self, trader: &Pubkey) -> usize {
+        self.buy_stops
+            .iter()
+            .chain(self.sell_stops.iter())
+            .filter(|(_, stop_order)| stop_order.trader == *trader)
+            .count()
+    }
+
+    /// Returns None if there is no space for the stop order on its side
+    pub fn insert(
+        &mut self,
+        side: Side,
+        trigger_price_in_ticks: Ticks,
+        stop_order: StopOrder,
+    ) -> Option<StopOrderId> {
+        let stop_order_id = StopOrderId::new(trigger_price_in_ticks, self.stop_sequence_number);
+        self.get_stops_mut(side).insert(stop_order_id, stop_order)?;
+        self.stop_sequence_number += 1;
+        Some(stop_order_id)
+    }
+
+    /// Removes up to `max_triggered` of the stop orders that are triggered by fills with prices
+    /// between `min_fill_price` and `max_fill_price`. The earliest placed stop orders are removed
+    /// first, and they are returned in the order they were placed.
+    pub fn remove_triggered(
+        &mut self,
+        min_fill_price: Ticks,
+        max_fill_price: Ticks,
+        max_triggered: usize,
+    ) -> Vec<(Side, StopOrderId, StopOrder)> {
+        let mut triggered = self
+            .buy_stops
+            .iter()
+            .take_while(|(stop_order_id, _)| stop_order_id.trigger_price_in_ticks <= max_fill_price)
+            .map(|(stop_order_id, stop_order)| (Side::Bid, *stop_order_id, *stop_order))
+            .chain(
+                self.sell_stops
+                    .iter()
+                    .filter(|(stop_order_id, _)| {
+                        stop_order_id.trigger_price_in_ticks >= min_fill_price
+                    })
+                    .map(|(stop_order_id, stop_order)| (Side::Ask, *stop_order_id, *stop_order)),
+            )
+            .collect::<Vec<_>>();
+        triggered.sort_by_key(|(_, stop_order_id, _)| stop_order_id.stop_sequence_number);
+        triggered.truncate(max_triggered);
+        for (side, stop_order_id, _) in triggered.iter() {
+            self.get_stops_mut(*side).remove(stop_order_id);
+        }
+        triggered
+    }
+}
+
+/// Returns the quote lots that a buy stop order must hold to buy `num_base_lots` at
+/// `limit_price_in_ticks`, including the taker fee at `taker_fee_bps` and the market's minimum fee.
+/// Both the cost and the fee are rounded up. Returns None if the amount does not fit in a u64.
+pub fn get_quote_lots_to_lock_for_stop_bid(
+    limit_price_in_ticks: Ticks,
+    num_base_lots: BaseLots,
+    tick_size: QuoteLotsPerBaseUnitPerTick,
+    base_lots_per_base_unit: BaseLotsPerBaseUnit,
+    taker_fee_bps: u64,
+    min_fee_quote_lots: QuoteLots,
+) -> Option<QuoteLots> {
+    let adjusted_quote_lots = limit_price_in_ticks
+        .as_u128()
+        .checked_mul(tick_size.as_u128())?
+        .checked_mul(num_base_lots.as_u128())?;
+    let base_lots_per_base_unit = base_lots_per_base_unit.as_u128();
+    let quote_lots = u64::try_from(
+        (adjusted_quote_lots + base_lots_per_base_unit - 1) / base_lots_per_base_unit,
+    )
+    .ok()? as u128;
+    let fee = ((quote_lots * taker_fee_bps as u128 + 10000 - 1) / 10000)
+        .max(min_fee_quote_lots.as_u128());
+    u64::try_from(quote_lots + fee).ok().map(QuoteLots::new)
+}
+
+pub fn get_stop_order_book_size() -> usize {
+    std::mem::size_of::<StopOrderBook>()
+}
+
+/// Returns the offset of the stop order book from the end of the market header
+pub fn get_stop_order_book_offset(
+    market_size_params: &MarketSizeParams,
+    client_order_id_index_enabled: bool,
+) -> Result<usize, ProgramError> {
+    let mut offset = get_market_size(market_size_params)?;
+    if client_order_id_index_enabled {
+        offset += get_client_order_id_index_size(market_size_params)?;
+    }
+    Ok(offset)
+}
+
+pub(crate) fn initialize_stop_order_book(bytes: &mut [u8]) -> Result<(), ProgramError> {
+    let stop_order_book = load_stop_order_book_mut(bytes)?;
+    stop_order_book.buy_stops.initialize();
+    stop_order_book.sell_stops.initialize();
+    Ok(())
+}
+
+pub(crate) fn load_stop_order_book_mut(
+    bytes: &mut [u8],
+) -> Result<&mut StopOrderBook, ProgramError> {
+    StopOrderBook::load_mut_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+pub fn load_stop_order_book(bytes: &[u8]) -> Result<&StopOrderBook, ProgramError> {
+    StopOrderBook::load_bytes(bytes)
+        .ok_or_else(|| PhoenixError::FailedToLoadMarketFromAccount.into())
+}
+
+#[test]
+fn test_stop_order_book_triggers() {
+    let mut bytes = vec![0_u8; get_stop_order_book_size()];
+    initialize_stop_order_book(&mut bytes).unwrap();
+    let stop_order_book = load_stop_order_book_mut(&mut bytes).unwrap();
+    let trader = Pubkey::new_unique();
+    let stop = |client_order_id| {
+        StopOrder::new(
+            trader,
+            Ticks::new(100),
+            BaseLots::new(10),
+            QuoteLots::ZERO,
+            BaseLots::new(10),
+            client_order_id,
+        )
+    };
+    for (side, trigger_price) in [
+        (Side::Bid, 110),
+        (Side::Ask, 90),
+        (Side::Bid, 105),
+        (Side::Ask, 95),
+        (Side::Bid, 120),
+    ] {
+        let client_order_id = stop_order_book.stop_sequence_number as u128;
+        stop_order_book
+            .insert(side, Ticks::new(trigger_price), stop(client_order_id))
+            .unwrap();
+    }
+    assert_eq!(stop_order_book.len(), 5);
+
+    // Fills between 100 and 110 trigger the buy stops at 105 and 110
+    let triggered = stop_order_book.remove_triggered(Ticks::new(100), Ticks::new(110), 8);
+    assert_eq!(
+        triggered
+            .iter()
+            .map(|(side, id, s)| (
+                *side,
+                id.trigger_price_in_ticks.as_u64(),
+                s.client_order_id()
+            ))
+            .collect::<Vec<_>>(),
+        vec![(Side::Bid, 110, 0), (Side::Bid, 105, 2)]
+    );
+
+    // A fill at 90 triggers both sell stops in the order they were placed, but only as many as
+    // the limit allows
+    assert_eq!(stop_order_book.get_num_stops_for_trader(&trader), 3);
+    let triggered = stop_order_book.remove_triggered(Ticks::new(90), Ticks::new(90), 1);
+    assert_eq!(
+        triggered
+            .iter()
+            .map(|(side, id, _)| (*side, id.stop_sequence_number))
+            .collect::<Vec<_>>(),
+        vec![(Side::Ask, 1)]
+    );
+    let triggered = stop_order_book.remove_triggered(Ticks::new(90), Ticks::new(90), 8);
+    assert_eq!(
+        triggered
+            .iter()
+            .map(|(side, id, _)| (*side, id.stop_sequence_number))
+            .collect::<Vec<_>>(),
+        vec![(Side::Ask, 3)]
+    );
+    assert_eq!(stop_order_book.len(), 1);
+    assert!(stop_order_book
+        .get_stops(Side::Bid)
+        .get(&StopOrderId::new(Ticks::new(120), 4))
+        .is_some());
+}
@@ -6,13 +6,23 @@ use std::{
 use solana_program::{
     account_info::AccountInfo,
     entrypoint::ProgramResult,
+    instruction::Instruction,
     program::{invoke, invoke_signed},
+    program_error::ProgramError,
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
 };
 
 use crate::quantities::{BaseAtoms, QuoteAtoms, WrapperU64};
 
-use super::{checkers::TokenAccountInfo, TokenParams};
+use super::{
+    assert_with_msg,
+    checkers::{MintAccountInfo, TokenAccountInfo},
+    system_utils::create_account,
+    PhoenixError, TokenParams,
+};
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn try_withdraw<'a, 'info>(
@@ -22,21 +32,25 @@ pub(crate) fn try_withdraw<'a, 'info>(
     token_program: &AccountInfo<'info>,
     quote_account: &AccountInfo<'info>,
     quote_vault: TokenAccountInfo<'a, 'info>,
+    quote_mint: Option<&MintAccountInfo<'a, 'info>>,
     base_account: &AccountInfo<'info>,
     base_vault: TokenAccountInfo<'a, 'info>,
+    base_mint: Option<&MintAccountInfo<'a, 'info>>,
     quote_atoms_to_withdraw: QuoteAtoms,
     base_atoms_to_withdraw: BaseAtoms,
 ) -> ProgramResult {
-    for (withdraw_vault, withdraw_account, withdraw_amount, params) in [
+    for (withdraw_vault, withdraw_account, withdraw_mint, withdraw_amount, params) in [
         (
             quote_vault,
             quote_account,
+            quote_mint,
             quote_atoms_to_withdraw.as_u64(),
             quote_params,
         ),
         (
             base_vault,
             base_account,
+            base_mint,
             base_atoms_to_withdraw.as_u64(),
             base_params,
         ),
@@ -49,11 +63,45 @@ pub(crate) fn try_withdraw<'a, 'info>(
             token_program,
             withdraw_account,
             &withdraw_vault,
+            withdraw_mint,
         )?;
     }
     Ok(())
 }
 
+/// Token-2022 transfers use `transfer_checked`, which checks the mint and its decimals. SPL Token
+/// transfers are unchanged.
+fn create_transfer_instruction(
+    token_program: &AccountInfo,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    mint: Option<&MintAccountInfo>,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    match mint {
+        Some(mint) => spl_token_2022::instruction::transfer_checked(
+            token_program.key,
+            source,
+            mint.info.key,
+            destination,
+            authority,
+            &[],
+            amount,
+            mint.decimals,
+        ),
+        None => spl_token::instruction::transfer(
+            token_program.key,
+            source,
+            destination,
+            authority,
+            &[],
+            amount,
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn maybe_invoke_withdraw<'a, 'info>(
     market_key: &Pubkey,
     mint_key: &Pubkey,
@@ -62,80 +110,222 @@ pub(crate) fn maybe_invoke_withdraw<'a, 'info>(
     token_program: &AccountInfo<'info>,
     withdraw_account: &AccountInfo<'info>,
     withdraw_vault: &'a TokenAccountInfo<'a, 'info>,
+    mint: Option<&MintAccountInfo<'a, 'info>>,
 ) -> ProgramResult {
     if withdraw_amount != 0 {
+        let mut account_infos = vec![
+            token_program.clone(),
+            withdraw_vault.as_ref().clone(),
+            withdraw_account.clone(),
+        ];
+        if let Some(mint) = mint {
+            account_infos.push(mint.as_ref().clone());
+        }
         invoke_signed(
-            &spl_token::instruction::transfer(
-                token_program.key,
+            &create_transfer_instruction(
+                token_program,
                 withdraw_vault.key,
                 withdraw_account.key,
                 withdraw_vault.key,
-                &[],
+                mint,
                 withdraw_amount,
             )?,
-            &[
-                token_program.clone(),
-                withdraw_vault.as_ref().clone(),
-                withdraw_account.clone(),
-            ],
+            &account_infos,
             &[&[b"vault", market_key.as_ref(), mint_key.as_ref(), &[bump]]],
         )?;
     }
     Ok(())
 }
 
+/// Returns the number of atoms that were received by the vault, measured from the vault balance
+/// rather than assumed from the deposit amount.
 pub(crate) fn maybe_invoke_deposit<'a, 'info>(
     deposit_amount: u64,
     token_program: &AccountInfo<'info>,
     deposit_account: &'a TokenAccountInfo<'a, 'info>,
     deposit_vault: &'a TokenAccountInfo<'a, 'info>,
+    mint: Option<&MintAccountInfo<'a, 'info>>,
     trader: &AccountInfo<'info>,
-) -> ProgramResult {
-    if deposit_amount > 0 {
-        invoke(
-            &spl_token::instruction::transfer(
-                token_program.key,
-                deposit_account.key,
-                deposit_vault.key,
-                trader.key,
-                &[],
-                deposit_amount,
-            )?,
-            &[
-                token_program.as_ref().clone(),
-                deposit_account.as_ref().clone(),
-                deposit_vault.as_ref().clone(),
-                trader.as_ref().clone(),
-            ],
-        )?;
+) -> Result<u64, ProgramError> {
+    if deposit_amount == 0 {
+        return Ok(0);
     }
-    Ok(())
+    let mut account_infos = vec![
+        token_program.as_ref().clone(),
+        deposit_account.as_ref().clone(),
+        deposit_vault.as_ref().clone(),
+        trader.as_ref().clone(),
+    ];
+    if let Some(mint) = mint {
+        account_infos.push(mint.as_ref().clone());
+    }
+    let vault_amount_before = deposit_vault.amount()?;
+    invoke(
+        &create_transfer_instruction(
+            token_program,
+            deposit_account.key,
+            deposit_vault.key,
+            trader.key,
+            mint,
+            deposit_amount,
+        )?,
+        &account_infos,
+    )?;
+    Ok(deposit_vault.amount()? - vault_amount_before)
 }
 
+/// Deposits that settle an order must arrive in full, otherwise the vault would hold fewer
+/// atoms than the market has credited.
+pub(crate) fn invoke_exact_deposit<'a, 'info>(
+    deposit_amount: u64,
+    token_program: &AccountInfo<'info>,
+    deposit_account: &'a TokenAccountInfo<'a, 'info>,
+    deposit_vault: &'a TokenAccountInfo<'a, 'info>,
+    mint: Option<&MintAccountInfo<'a, 'info>>,
+    trader: &AccountInfo<'info>,
+) -> ProgramResult {
+    let amount_received = maybe_invoke_deposit(
+        deposit_amount,
+        token_program,
+        deposit_account,
+        deposit_vault,
+        mint,
+        trader,
+    )?;
+    assert_with_msg(
+        amount_received == deposit_amount,
+        PhoenixError::InsufficientDepositReceived,
+        &format!(
+            "Vault received {} atoms but expected {}",
+            amount_received, deposit_amount
+        ),
+    )
+}
+
+/// Returns the number of quote and base atoms that were received by the vaults
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn try_deposit<'a, 'info>(
     token_program: &AccountInfo<'info>,
     quote_account: TokenAccountInfo<'a, 'info>,
     quote_vault: TokenAccountInfo<'a, 'info>,
+    quote_mint: Option<&MintAccountInfo<'a, 'info>>,
     base_account: TokenAccountInfo<'a, 'info>,
     base_vault: TokenAccountInfo<'a, 'info>,
+    base_mint: Option<&MintAccountInfo<'a, 'info>>,
     quote_amount: QuoteAtoms,
     base_amount: BaseAtoms,
     trader: &AccountInfo<'info>,
+) -> Result<(QuoteAtoms, BaseAtoms), ProgramError> {
+    let quote_atoms_received = maybe_invoke_deposit(
+        quote_amount.as_u64(),
+        token_program,
+        &quote_account,
+        &quote_vault,
+        quote_mint,
+        trader,
+    )?;
+    let base_atoms_received = maybe_invoke_deposit(
+        base_amount.as_u64(),
+        token_program,
+        &base_account,
+        &base_vault,
+        base_mint,
+        trader,
+    )?;
+    Ok((
+        QuoteAtoms::new(quote_atoms_received),
+        BaseAtoms::new(base_atoms_received),
+    ))
+}
+
+/// Creates the trader's temporary wrapped SOL account for a market. The trader pays the rent of
+/// the account, which is returned when the account is closed by `close_native_sol_account`.
+pub(crate) fn create_native_sol_account<'a, 'info>(
+    market_key: &Pubkey,
+    trader: &'a AccountInfo<'info>,
+    native_sol_account: &'a AccountInfo<'info>,
+    native_sol_account_bump: u8,
+    native_mint: &MintAccountInfo<'a, 'info>,
+    token_program: &'a AccountInfo<'info>,
+    system_program: &'a AccountInfo<'info>,
 ) -> ProgramResult {
-    for (deposit_vault, deposit_account, deposit_amount) in [
-        (quote_vault, quote_account, quote_amount.as_u64()),
-        (base_vault, base_account, base_amount.as_u64()),
-    ] {
-        maybe_invoke_deposit(
-            deposit_amount,
-            token_program,
-            &deposit_account,
-            &deposit_vault,
-            trader,
-        )?;
+    let space = native_mint.get_token_account_len()?;
+    create_account(
+        trader,
+        native_sol_account,
+        system_program,
+        token_program.key,
+        &Rent::get()?,
+        space as u64,
+        vec![
+            b"native_sol".to_vec(),
+            market_key.as_ref().to_vec(),
+            trader.key.as_ref().to_vec(),
+            vec![native_sol_account_bump],
+        ],
+    )?;
+    invoke(
+        &spl_token_2022::instruction::initialize_account3(
+            token_program.key,
+            native_sol_account.key,
+            native_mint.info.key,
+            trader.key,
+        )?,
+        &[
+            native_sol_account.clone(),
+            native_mint.info.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+/// Transfers `lamports` from the trader to their temporary wrapped SOL account and syncs the token
+/// balance of the account with its lamports.
+pub(crate) fn wrap_native_sol<'info>(
+    lamports: u64,
+    trader: &AccountInfo<'info>,
+    native_sol_account: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if lamports == 0 {
+        return Ok(());
     }
-    Ok(())
+    invoke(
+        &system_instruction::transfer(trader.key, native_sol_account.key, lamports),
+        &[
+            system_program.clone(),
+            trader.clone(),
+            native_sol_account.clone(),
+        ],
+    )?;
+    invoke(
+        &spl_token_2022::instruction::sync_native(token_program.key, native_sol_account.key)?,
+        &[token_program.clone(), native_sol_account.clone()],
+    )
+}
+
+/// Closes the trader's temporary wrapped SOL account. All of its lamports, including the rent and
+/// any wrapped SOL left in the account, are returned to the trader.
+pub(crate) fn close_native_sol_account<'info>(
+    trader: &AccountInfo<'info>,
+    native_sol_account: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    invoke(
+        &spl_token_2022::instruction::close_account(
+            token_program.key,
+            native_sol_account.key,
+            trader.key,
+            trader.key,
+            &[],
+        )?,
+        &[
+            token_program.clone(),
+            native_sol_account.clone(),
+            trader.clone(),
+        ],
+    )
 }
 
 pub fn get_decimal_string<N: Display + Div + Rem + Copy + TryFrom<u64>>(
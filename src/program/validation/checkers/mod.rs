@@ -59,6 +59,18 @@ impl<'a, 'info> Program<'a, 'info> {
         )?;
         Ok(Self { info })
     }
+
+    /// Accepts either the SPL Token program or the Token-2022 program
+    pub fn new_token_program(
+        info: &'a AccountInfo<'info>,
+    ) -> Result<Program<'a, 'info>, ProgramError> {
+        assert_with_msg(
+            is_token_program(info.key),
+            ProgramError::IncorrectProgramId,
+            "Incorrect token program id",
+        )?;
+        Ok(Self { info })
+    }
 }
 
 impl<'a, 'info> AsRef<AccountInfo<'info>> for Program<'a, 'info> {
@@ -2,7 +2,7 @@ use crate::program::{
     error::assert_with_msg,
     get_discriminant, get_seat_address,
     status::{MarketStatus, SeatApprovalStatus},
-    MarketHeader, MarketSizeParams, PhoenixError, Seat,
+    MarketHeader, MarketSizeParams, MarketType, PhoenixError, Seat,
 };
 use sokoban::node_allocator::ZeroCopy;
 use solana_program::{
@@ -19,6 +19,7 @@ use std::{
 pub(crate) struct MarketAccountInfo<'a, 'info> {
     pub(crate) info: &'a AccountInfo<'info>,
     pub(crate) size_params: MarketSizeParams,
+    pub(crate) market_type: MarketType,
 }
 
 impl<'a, 'info> MarketAccountInfo<'a, 'info> {
@@ -34,6 +35,7 @@ impl<'a, 'info> MarketAccountInfo<'a, 'info> {
         Ok(Self {
             info,
             size_params: MarketSizeParams::default(),
+            market_type: MarketType::default(),
         })
     }
 
@@ -48,8 +50,10 @@ impl<'a, 'info> MarketAccountInfo<'a, 'info> {
             "Invalid market discriminant",
         )?;
         let params = header.market_size_params;
+        let market_type = MarketType::try_from(header.market_type)?;
         drop(header);
         market_info.size_params = params;
+        market_info.market_type = market_type;
         Ok(market_info)
     }
 
@@ -133,6 +137,7 @@ impl<'a, 'info> MarketAccountInfo<'a, 'info> {
         Ok(Self {
             info,
             size_params: MarketSizeParams::default(),
+            market_type: MarketType::default(),
         })
     }
 
@@ -1,10 +1,30 @@
-use crate::program::error::assert_with_msg;
+use crate::program::error::{assert_with_msg, PhoenixError};
 use solana_program::{
     account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
 };
-use spl_token::state::{Account, Mint};
+use spl_token_2022::{
+    extension::{default_account_state::DefaultAccountState, ExtensionType, StateWithExtensions},
+    state::{Account, AccountState, Mint},
+};
 use std::ops::Deref;
 
+/// The Token-2022 mint extensions that a market accepts. All other extensions are rejected because
+/// they would break the accounting of the vaults: a permanent delegate can move tokens out of the
+/// vaults, transfer fees change the amount that a vault receives, transfer hooks can block or
+/// alter transfers, and non-transferable or confidential tokens can not be moved by the program.
+/// `DefaultAccountState` is only accepted if new token accounts start out initialized, because a
+/// frozen vault can not be used.
+const ALLOWED_MINT_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::DefaultAccountState,
+];
+
+/// Returns true if the key belongs to either the SPL Token program or the Token-2022 program
+pub fn is_token_program(key: &Pubkey) -> bool {
+    key == &spl_token::id() || key == &spl_token_2022::id()
+}
+
 #[derive(Clone)]
 pub struct MintAccountInfo<'a, 'info> {
     pub mint: Mint,
@@ -14,14 +34,77 @@ pub struct MintAccountInfo<'a, 'info> {
 impl<'a, 'info> MintAccountInfo<'a, 'info> {
     pub fn new(info: &'a AccountInfo<'info>) -> Result<MintAccountInfo<'a, 'info>, ProgramError> {
         assert_with_msg(
-            info.owner == &spl_token::id(),
+            is_token_program(info.owner),
             ProgramError::IllegalOwner,
             "Mint account must be owned by the Token Program",
         )?;
-        let mint = Mint::unpack(&info.try_borrow_data()?)?;
+        let mint = if info.owner == &spl_token::id() {
+            Mint::unpack(&info.try_borrow_data()?)?
+        } else {
+            StateWithExtensions::<Mint>::unpack(&info.try_borrow_data()?)?.base
+        };
 
         Ok(Self { mint, info })
     }
+
+    pub fn new_with_key(
+        info: &'a AccountInfo<'info>,
+        key: &Pubkey,
+    ) -> Result<MintAccountInfo<'a, 'info>, ProgramError> {
+        assert_with_msg(
+            info.key == key,
+            ProgramError::InvalidInstructionData,
+            "Invalid pubkey for Mint",
+        )?;
+        Self::new(info)
+    }
+
+    /// Fails if the mint has a Token-2022 extension that markets do not support. The extensions of
+    /// a mint are fixed when it is created, so this only needs to be checked when a market is
+    /// initialized.
+    pub fn assert_supported_extensions(&self) -> Result<(), ProgramError> {
+        if self.info.owner == &spl_token::id() {
+            return Ok(());
+        }
+        let mint_data = self.info.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        for extension_type in mint.get_extension_types()? {
+            assert_with_msg(
+                ALLOWED_MINT_EXTENSIONS.contains(&extension_type),
+                PhoenixError::UnsupportedMintExtension,
+                &format!(
+                    "Mint {} has an unsupported extension: {:?}",
+                    self.info.key, extension_type
+                ),
+            )?;
+        }
+        if let Ok(default_account_state) = mint.get_extension::<DefaultAccountState>() {
+            assert_with_msg(
+                default_account_state.state == AccountState::Initialized as u8,
+                PhoenixError::UnsupportedMintExtension,
+                &format!(
+                    "Token accounts of mint {} are frozen by default",
+                    self.info.key
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the size of a token account for this mint. Token-2022 mints may require token
+    /// accounts to carry extensions.
+    pub fn get_token_account_len(&self) -> Result<usize, ProgramError> {
+        if self.info.owner == &spl_token::id() {
+            return Ok(Account::LEN);
+        }
+        let mint_data = self.info.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        let account_extensions =
+            ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+        Ok(ExtensionType::get_account_len::<Account>(
+            &account_extensions,
+        ))
+    }
 }
 
 impl<'a, 'info> AsRef<AccountInfo<'info>> for MintAccountInfo<'a, 'info> {
@@ -49,15 +132,20 @@ impl<'a, 'info> TokenAccountInfo<'a, 'info> {
         mint: &Pubkey,
     ) -> Result<TokenAccountInfo<'a, 'info>, ProgramError> {
         assert_with_msg(
-            info.owner == &spl_token::id(),
+            is_token_program(info.owner),
             ProgramError::IllegalOwner,
             "Token account must be owned by the Token Program",
         )?;
-        assert_with_msg(
-            info.data_len() == Account::LEN,
-            ProgramError::InvalidAccountData,
-            "Token account data length must be 165 bytes",
-        )?;
+        if info.owner == &spl_token::id() {
+            assert_with_msg(
+                info.data_len() == Account::LEN,
+                ProgramError::InvalidAccountData,
+                "Token account data length must be 165 bytes",
+            )?;
+        } else {
+            // Token-2022 accounts may have extensions appended after the base account
+            StateWithExtensions::<Account>::unpack(&info.try_borrow_data()?)?;
+        }
         // The mint key is found at offset 0 of the token account
         assert_with_msg(
             &info.try_borrow_data()?[0..32] == mint.as_ref(),
@@ -120,3 +208,69 @@ impl<'a, 'info> Deref for TokenAccountInfo<'a, 'info> {
         self.info
     }
 }
+
+#[test]
+fn test_mint_extension_allowlist() {
+    use solana_program::program_option::COption;
+
+    // Packs a Token-2022 mint followed by the given (extension type, value length, value) entries
+    fn mint_with_extensions(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0_u8; Account::LEN];
+        Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data[..Mint::LEN]);
+        // The account type follows the padding of a mint to the size of a token account
+        data.push(1);
+        for (extension_type, value) in extensions {
+            data.extend_from_slice(&extension_type.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            data.extend_from_slice(value);
+        }
+        data
+    }
+
+    let check = |data: &mut Vec<u8>| {
+        let key = Pubkey::new_unique();
+        let owner = spl_token_2022::id();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, false, &mut lamports, data, &owner, false, 0);
+        MintAccountInfo::new(&info).and_then(|mint| mint.assert_supported_extensions())
+    };
+
+    let mint_close_authority = (ExtensionType::MintCloseAuthority as u16, vec![1; 32]);
+    let interest_bearing_config = (ExtensionType::InterestBearingConfig as u16, vec![0; 52]);
+    let default_account_state =
+        |state: AccountState| (ExtensionType::DefaultAccountState as u16, vec![state as u8]);
+    assert!(check(&mut mint_with_extensions(&[])).is_ok());
+    assert!(check(&mut mint_with_extensions(&[
+        mint_close_authority.clone(),
+        interest_bearing_config,
+        default_account_state(AccountState::Initialized),
+    ]))
+    .is_ok());
+
+    // Mints whose token accounts are frozen by default are rejected
+    assert_eq!(
+        check(&mut mint_with_extensions(&[default_account_state(
+            AccountState::Frozen
+        )])),
+        Err(PhoenixError::UnsupportedMintExtension.into())
+    );
+    // Transfer fees are rejected, even alongside allowed extensions
+    assert_eq!(
+        check(&mut mint_with_extensions(&[
+            mint_close_authority,
+            (ExtensionType::TransferFeeConfig as u16, vec![0; 108]),
+        ])),
+        Err(PhoenixError::UnsupportedMintExtension.into())
+    );
+    // Non-transferable mints (9), permanent delegates (12) and transfer hooks (14) are rejected
+    for extension in [(9, vec![]), (12, vec![1; 32]), (14, vec![1; 64])] {
+        assert!(check(&mut mint_with_extensions(&[extension])).is_err());
+    }
+}
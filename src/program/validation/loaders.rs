@@ -15,6 +15,7 @@ use super::checkers::{
 use crate::{
     phoenix_log_authority,
     program::{
+        error::assert_with_msg,
         validation::checkers::{EmptyAccount, Program, Signer},
         MarketHeader, TokenParams,
     },
@@ -32,10 +33,31 @@ pub fn get_vault_address(market: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[b"vault", market.as_ref(), mint.as_ref()], &crate::ID)
 }
 
+pub fn get_market_address(base_mint: &Pubkey, quote_mint: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"market",
+            base_mint.as_ref(),
+            quote_mint.as_ref(),
+            &nonce.to_le_bytes(),
+        ],
+        &crate::ID,
+    )
+}
+
 pub fn get_seat_address(market: &Pubkey, trader: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[b"seat", market.as_ref(), trader.as_ref()], &crate::ID)
 }
 
+/// Returns the address of the temporary wrapped SOL account that is used by the native SOL
+/// instructions of a market
+pub fn get_native_sol_address(market: &Pubkey, trader: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"native_sol", market.as_ref(), trader.as_ref()],
+        &crate::ID,
+    )
+}
+
 pub(crate) struct PhoenixLogContext<'a, 'info> {
     pub(crate) phoenix_program: Program<'a, 'info>,
     pub(crate) log_authority: PDA<'a, 'info>,
@@ -83,12 +105,17 @@ impl<'a, 'info> PhoenixMarketContext<'a, 'info> {
 }
 
 /// These accounts that are required for all market actions that interact with a token vault
+///
+/// Markets with Token-2022 mints must also pass the base and quote mints after the token program.
+/// The program transfers Token-2022 tokens with `transfer_checked`, which requires the mint.
 pub(crate) struct PhoenixVaultContext<'a, 'info> {
     pub(crate) base_account: TokenAccountInfo<'a, 'info>,
     pub(crate) quote_account: TokenAccountInfo<'a, 'info>,
     pub(crate) base_vault: TokenAccountInfo<'a, 'info>,
     pub(crate) quote_vault: TokenAccountInfo<'a, 'info>,
     pub(crate) token_program: Program<'a, 'info>,
+    pub(crate) base_mint: Option<MintAccountInfo<'a, 'info>>,
+    pub(crate) quote_mint: Option<MintAccountInfo<'a, 'info>>,
 }
 
 impl<'a, 'info> PhoenixVaultContext<'a, 'info> {
@@ -98,30 +125,156 @@ impl<'a, 'info> PhoenixVaultContext<'a, 'info> {
         quote_params: &TokenParams,
         trader_key: &Pubkey,
     ) -> Result<Self, ProgramError> {
+        let base_account = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &base_params.mint_key,
+            trader_key,
+        )?;
+        let quote_account = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            trader_key,
+        )?;
+        Self::load_vaults_from_iter(
+            account_iter,
+            base_account,
+            quote_account,
+            base_params,
+            quote_params,
+        )
+    }
+
+    /// Loads the vault context for a withdrawal. Funds only ever leave the trader's own balance,
+    /// so the destination token accounts may be owned by anyone, but they must match the market's
+    /// mints.
+    pub(crate) fn load_withdrawal_from_iter(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        base_params: &TokenParams,
+        quote_params: &TokenParams,
+    ) -> Result<Self, ProgramError> {
+        let base_account =
+            TokenAccountInfo::new(next_account_info(account_iter)?, &base_params.mint_key)?;
+        let quote_account =
+            TokenAccountInfo::new(next_account_info(account_iter)?, &quote_params.mint_key)?;
+        Self::load_vaults_from_iter(
+            account_iter,
+            base_account,
+            quote_account,
+            base_params,
+            quote_params,
+        )
+    }
+
+    fn load_vaults_from_iter(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        base_account: TokenAccountInfo<'a, 'info>,
+        quote_account: TokenAccountInfo<'a, 'info>,
+        base_params: &TokenParams,
+        quote_params: &TokenParams,
+    ) -> Result<Self, ProgramError> {
+        let base_vault = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &base_params.mint_key,
+            &base_params.vault_key,
+            &base_params.vault_key,
+        )?;
+        let quote_vault = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            &quote_params.vault_key,
+            &quote_params.vault_key,
+        )?;
+        let token_program = Program::new_token_program(next_account_info(account_iter)?)?;
+        let (base_mint, quote_mint) = if token_program.key == &spl_token_2022::id() {
+            (
+                Some(MintAccountInfo::new_with_key(
+                    next_account_info(account_iter)?,
+                    &base_params.mint_key,
+                )?),
+                Some(MintAccountInfo::new_with_key(
+                    next_account_info(account_iter)?,
+                    &quote_params.mint_key,
+                )?),
+            )
+        } else {
+            (None, None)
+        };
         Ok(Self {
-            base_account: TokenAccountInfo::new_with_owner(
-                next_account_info(account_iter)?,
-                &base_params.mint_key,
-                trader_key,
-            )?,
-            quote_account: TokenAccountInfo::new_with_owner(
-                next_account_info(account_iter)?,
-                &quote_params.mint_key,
-                trader_key,
-            )?,
-            base_vault: TokenAccountInfo::new_with_owner_and_key(
-                next_account_info(account_iter)?,
-                &base_params.mint_key,
-                &base_params.vault_key,
-                &base_params.vault_key,
-            )?,
-            quote_vault: TokenAccountInfo::new_with_owner_and_key(
-                next_account_info(account_iter)?,
-                &quote_params.mint_key,
-                &quote_params.vault_key,
-                &quote_params.vault_key,
-            )?,
-            token_program: Program::new(next_account_info(account_iter)?, &spl_token::id())?,
+            base_account,
+            quote_account,
+            base_vault,
+            quote_vault,
+            token_program,
+            base_mint,
+            quote_mint,
+        })
+    }
+}
+
+/// The accounts of a single market in a `WithdrawFromMarkets` instruction. Each group consists of
+/// the market account followed by the accounts of a `PhoenixVaultContext`.
+pub(crate) struct MarketWithdrawalContext<'a, 'info> {
+    pub(crate) market_context: PhoenixMarketContext<'a, 'info>,
+    pub(crate) vault_context: PhoenixVaultContext<'a, 'info>,
+}
+
+impl<'a, 'info> MarketWithdrawalContext<'a, 'info> {
+    pub(crate) fn load_from_iter(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        trader: &Signer<'a, 'info>,
+    ) -> Result<Self, ProgramError> {
+        let market_info = MarketAccountInfo::new(next_account_info(account_iter)?)?;
+        market_info.assert_reduce_allowed()?;
+        let (base_params, quote_params) = {
+            let header = market_info.get_header()?;
+            (header.base_params, header.quote_params)
+        };
+        let vault_context = PhoenixVaultContext::load_from_iter(
+            account_iter,
+            &base_params,
+            &quote_params,
+            trader.key,
+        )?;
+        Ok(Self {
+            market_context: PhoenixMarketContext {
+                market_info,
+                signer: trader.clone(),
+            },
+            vault_context,
+        })
+    }
+}
+
+/// The accounts of a single market in a `SwapRoute` instruction. Each group consists of the market
+/// account followed by the accounts of a `PhoenixVaultContext`.
+pub(crate) struct SwapRouteHopContext<'a, 'info> {
+    pub(crate) market_context: PhoenixMarketContext<'a, 'info>,
+    pub(crate) vault_context: PhoenixVaultContext<'a, 'info>,
+}
+
+impl<'a, 'info> SwapRouteHopContext<'a, 'info> {
+    pub(crate) fn load_from_iter(
+        account_iter: &mut Iter<'a, AccountInfo<'info>>,
+        trader: &Signer<'a, 'info>,
+    ) -> Result<Self, ProgramError> {
+        let market_info = MarketAccountInfo::new(next_account_info(account_iter)?)?;
+        market_info.assert_cross_allowed()?;
+        let (base_params, quote_params) = {
+            let header = market_info.get_header()?;
+            (header.base_params, header.quote_params)
+        };
+        let vault_context = PhoenixVaultContext::load_from_iter(
+            account_iter,
+            &base_params,
+            &quote_params,
+            trader.key,
+        )?;
+        Ok(Self {
+            market_context: PhoenixMarketContext {
+                market_info,
+                signer: trader.clone(),
+            },
+            vault_context,
         })
     }
 }
@@ -144,8 +297,16 @@ impl<'a, 'info> InitializeMarketContext<'a, 'info> {
             base_vault: EmptyAccount::new(next_account_info(account_iter)?)?,
             quote_vault: EmptyAccount::new(next_account_info(account_iter)?)?,
             system_program: Program::new(next_account_info(account_iter)?, &system_program::id())?,
-            token_program: Program::new(next_account_info(account_iter)?, &spl_token::id())?,
+            token_program: Program::new_token_program(next_account_info(account_iter)?)?,
         };
+        assert_with_msg(
+            ctx.base_mint.info.owner == ctx.token_program.key
+                && ctx.quote_mint.info.owner == ctx.token_program.key,
+            ProgramError::IncorrectProgramId,
+            "Base and quote mints must be owned by the supplied token program",
+        )?;
+        ctx.base_mint.assert_supported_extensions()?;
+        ctx.quote_mint.assert_supported_extensions()?;
         Ok(ctx)
     }
 }
@@ -268,6 +429,100 @@ impl<'a, 'info> CancelOrWithdrawContext<'a, 'info> {
     }
 }
 
+pub(crate) struct WithdrawContext<'a, 'info> {
+    pub(crate) vault_context: PhoenixVaultContext<'a, 'info>,
+    /// This is only loaded when the trader closes their seat
+    pub(crate) seat_option: Option<SeatAccountInfo<'a, 'info>>,
+}
+
+impl<'a, 'info> WithdrawContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+        close_seat: bool,
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        market_info.assert_reduce_allowed()?;
+        let account_iter = &mut accounts.iter();
+        let (base_params, quote_params) = {
+            let header = market_info.get_header()?;
+            (header.base_params, header.quote_params)
+        };
+        let vault_context = PhoenixVaultContext::load_withdrawal_from_iter(
+            account_iter,
+            &base_params,
+            &quote_params,
+        )?;
+        let seat_option = if close_seat {
+            assert_with_msg(
+                trader.is_writable,
+                ProgramError::InvalidArgument,
+                "Trader must be writable to receive the lamports of the closed seat",
+            )?;
+            let seat = SeatAccountInfo::new(next_account_info(account_iter)?, market_info.key)?;
+            assert_with_msg(
+                seat.key == &get_seat_address(market_info.key, trader.key).0,
+                ProgramError::InvalidInstructionData,
+                "Invalid address for seat",
+            )?;
+            Some(seat)
+        } else {
+            None
+        };
+        Ok(Self {
+            vault_context,
+            seat_option,
+        })
+    }
+}
+
+pub(crate) struct CancelAllAndCloseSeatContext<'a, 'info> {
+    pub(crate) vault_context: PhoenixVaultContext<'a, 'info>,
+    pub(crate) seat: SeatAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> CancelAllAndCloseSeatContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        market_info.assert_reduce_allowed()?;
+        assert_with_msg(
+            trader.is_writable,
+            ProgramError::InvalidArgument,
+            "Trader must be writable to receive the lamports of the closed seat",
+        )?;
+        let account_iter = &mut accounts.iter();
+        let (base_params, quote_params) = {
+            let header = market_info.get_header()?;
+            (header.base_params, header.quote_params)
+        };
+        let vault_context = PhoenixVaultContext::load_from_iter(
+            account_iter,
+            &base_params,
+            &quote_params,
+            trader.key,
+        )?;
+        let seat = SeatAccountInfo::new(next_account_info(account_iter)?, market_info.key)?;
+        assert_with_msg(
+            seat.key == &get_seat_address(market_info.key, trader.key).0,
+            ProgramError::InvalidInstructionData,
+            "Invalid address for seat",
+        )?;
+        Ok(Self {
+            vault_context,
+            seat,
+        })
+    }
+}
+
 pub(crate) struct DepositContext<'a, 'info> {
     _seat: SeatAccountInfo<'a, 'info>,
     pub(crate) vault_context: PhoenixVaultContext<'a, 'info>,
@@ -308,6 +563,89 @@ impl<'a, 'info> DepositContext<'a, 'info> {
     }
 }
 
+/// The accounts used to wrap and unwrap SOL on markets whose quote mint is the native mint of the
+/// SPL Token program or of the Token-2022 program.
+///
+/// The quote token account of the wrapped instruction must be the trader's temporary wrapped SOL
+/// account, see `get_native_sol_address`. The account must not exist when the instruction starts.
+/// It is created and closed within the instruction, so the trader's own wrapped SOL accounts are
+/// never touched. The system program and the native mint are passed after the accounts of the
+/// wrapped instruction.
+pub(crate) struct NativeSolContext<'a, 'info> {
+    /// The accounts of the wrapped instruction
+    pub(crate) accounts: &'a [AccountInfo<'info>],
+    pub(crate) native_sol_account: &'a AccountInfo<'info>,
+    pub(crate) native_sol_account_bump: u8,
+    pub(crate) token_program: Program<'a, 'info>,
+    pub(crate) system_program: Program<'a, 'info>,
+    pub(crate) native_mint: MintAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> NativeSolContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+        has_seat: bool,
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        let quote_mint = market_info.get_header()?.quote_params.mint_key;
+        let is_spl_token_native_mint = quote_mint == spl_token::native_mint::id();
+        assert_with_msg(
+            is_spl_token_native_mint || quote_mint == spl_token_2022::native_mint::id(),
+            ProgramError::InvalidAccountData,
+            "The quote mint of the market must be a native mint",
+        )?;
+        let token_program_id = if is_spl_token_native_mint {
+            spl_token::id()
+        } else {
+            spl_token_2022::id()
+        };
+        assert_with_msg(
+            trader.is_writable,
+            ProgramError::InvalidArgument,
+            "Trader must be writable to wrap and unwrap SOL",
+        )?;
+        assert_with_msg(
+            accounts.len() >= 2,
+            ProgramError::NotEnoughAccountKeys,
+            "Missing the system program and the native mint",
+        )?;
+        let (accounts, native_sol_accounts) = accounts.split_at(accounts.len() - 2);
+        let account_iter = &mut accounts.iter();
+        if has_seat {
+            let _seat = next_account_info(account_iter)?;
+        }
+        let _base_account = next_account_info(account_iter)?;
+        let native_sol_account = next_account_info(account_iter)?;
+        let (native_sol_key, native_sol_account_bump) =
+            get_native_sol_address(market_info.key, trader.key);
+        assert_with_msg(
+            native_sol_account.key == &native_sol_key,
+            ProgramError::InvalidSeeds,
+            &format!(
+                "Supplied wrapped SOL account ({}) does not match computed key ({})",
+                native_sol_account.key, native_sol_key
+            ),
+        )?;
+        let _base_vault = next_account_info(account_iter)?;
+        let _quote_vault = next_account_info(account_iter)?;
+        let token_program = Program::new(next_account_info(account_iter)?, &token_program_id)?;
+        let system_program = Program::new(&native_sol_accounts[0], &system_program::id())?;
+        let native_mint = MintAccountInfo::new_with_key(&native_sol_accounts[1], &quote_mint)?;
+        Ok(Self {
+            accounts,
+            native_sol_account,
+            native_sol_account_bump,
+            token_program,
+            system_program,
+            native_mint,
+        })
+    }
+}
+
 pub(crate) struct AuthorizedActionContext<'a, 'info> {
     pub(crate) trader: &'a AccountInfo<'info>,
     _seat: SeatAccountInfo<'a, 'info>,
@@ -353,6 +691,42 @@ impl<'a, 'info> AuthorizedActionContext<'a, 'info> {
     }
 }
 
+/// Same as `AuthorizedActionContext`, but without the token accounts and vaults. Used for
+/// actions that only modify the trader's state on the market.
+pub(crate) struct AuthorizedActionWithFreeFundsContext<'a, 'info> {
+    pub(crate) trader: &'a AccountInfo<'info>,
+    _seat: SeatAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> AuthorizedActionWithFreeFundsContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: authority,
+        } = market_context;
+        market_info.assert_valid_authority(authority.key)?;
+        let market_key = *market_info.key;
+
+        let account_iter = &mut accounts.iter();
+        let trader_info = next_account_info(account_iter)?;
+
+        let ctx = Self {
+            trader: trader_info,
+            _seat: SeatAccountInfo::new_with_context(
+                next_account_info(account_iter)?,
+                &market_key,
+                trader_info.key,
+                false,
+            )?,
+        };
+
+        Ok(ctx)
+    }
+}
+
 pub(crate) struct ChangeMarketStatusContext<'a, 'info> {
     pub(crate) receiver: Option<&'a AccountInfo<'info>>,
 }
@@ -396,6 +770,49 @@ impl<'a, 'info> AuthorizedSeatRequestContext<'a, 'info> {
     }
 }
 
+pub(crate) struct RequestSeatBatchContext<'a, 'info> {
+    pub(crate) payer: Signer<'a, 'info>,
+    pub(crate) system_program: Program<'a, 'info>,
+    /// Seat accounts of the traders, in the same order as the traders. These may or may not exist.
+    pub(crate) seats: Vec<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> RequestSeatBatchContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+        traders: &[Pubkey],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: authority,
+        } = market_context;
+        market_info.assert_valid_authority(authority.key)?;
+
+        let account_iter = &mut accounts.iter();
+        let payer = Signer::new_payer(next_account_info(account_iter)?)?;
+        let system_program = Program::new(next_account_info(account_iter)?, &system_program::id())?;
+        let seats = traders
+            .iter()
+            .map(|trader| {
+                let seat = next_account_info(account_iter)?;
+                let (seat_address, _) = get_seat_address(market_info.key, trader);
+                assert_with_msg(
+                    &seat_address == seat.key,
+                    ProgramError::InvalidAccountData,
+                    &format!("Invalid seat address for trader {}", trader),
+                )?;
+                Ok(seat)
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        Ok(Self {
+            payer,
+            system_program,
+            seats,
+        })
+    }
+}
+
 pub(crate) struct RequestSeatContext<'a, 'info> {
     pub(crate) seat: EmptyAccount<'a, 'info>,
     pub(crate) system_program: Program<'a, 'info>,
@@ -418,6 +835,38 @@ impl<'a, 'info> RequestSeatContext<'a, 'info> {
     }
 }
 
+/// The seat of the trader may or may not exist.
+pub(crate) struct CreateSeatContext<'a, 'info> {
+    pub(crate) seat: &'a AccountInfo<'info>,
+    pub(crate) system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> CreateSeatContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        market_info.assert_post_allowed()?;
+
+        let account_iter = &mut accounts.iter();
+        let seat = next_account_info(account_iter)?;
+        assert_with_msg(
+            seat.key == &get_seat_address(market_info.key, trader.key).0,
+            ProgramError::InvalidAccountData,
+            "Invalid seat address",
+        )?;
+        let system_program = Program::new(next_account_info(account_iter)?, &system_program::id())?;
+        Ok(Self {
+            seat,
+            system_program,
+        })
+    }
+}
+
 pub(crate) struct ModifySeatContext<'a, 'info> {
     pub(crate) seat: SeatAccountInfo<'a, 'info>,
 }
@@ -441,10 +890,117 @@ impl<'a, 'info> ModifySeatContext<'a, 'info> {
     }
 }
 
+pub(crate) struct DemoteInactiveSeatContext<'a, 'info> {
+    pub(crate) seat: SeatAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> DemoteInactiveSeatContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let account_iter = &mut accounts.iter();
+        let ctx = Self {
+            seat: SeatAccountInfo::new(
+                next_account_info(account_iter)?,
+                market_context.market_info.key,
+            )?,
+        };
+        Ok(ctx)
+    }
+}
+
+pub(crate) struct TraderSeatContext<'a, 'info> {
+    pub(crate) seat: SeatAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> TraderSeatContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        let account_iter = &mut accounts.iter();
+        let ctx = Self {
+            seat: SeatAccountInfo::new_with_context(
+                next_account_info(account_iter)?,
+                market_info.key,
+                trader.key,
+                true,
+            )?,
+        };
+        Ok(ctx)
+    }
+}
+
+pub(crate) struct InternalTransferContext<'a, 'info> {
+    pub(crate) seat: SeatAccountInfo<'a, 'info>,
+    pub(crate) destination_seat: SeatAccountInfo<'a, 'info>,
+}
+
+impl<'a, 'info> InternalTransferContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+        destination: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: trader,
+        } = market_context;
+        let account_iter = &mut accounts.iter();
+        let ctx = Self {
+            seat: SeatAccountInfo::new_with_context(
+                next_account_info(account_iter)?,
+                market_info.key,
+                trader.key,
+                true,
+            )?,
+            destination_seat: SeatAccountInfo::new_with_context(
+                next_account_info(account_iter)?,
+                market_info.key,
+                destination,
+                true,
+            )?,
+        };
+        Ok(ctx)
+    }
+}
+
+pub(crate) struct ResizeMarketContext<'a, 'info> {
+    pub(crate) payer: Signer<'a, 'info>,
+    pub(crate) system_program: Program<'a, 'info>,
+}
+
+impl<'a, 'info> ResizeMarketContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: authority,
+        } = market_context;
+        market_info.assert_valid_authority(authority.key)?;
+
+        let account_iter = &mut accounts.iter();
+        let ctx = Self {
+            payer: Signer::new_payer(next_account_info(account_iter)?)?,
+            system_program: Program::new(next_account_info(account_iter)?, &system_program::id())?,
+        };
+        Ok(ctx)
+    }
+}
+
 pub(crate) struct CollectFeesContext<'a, 'info> {
     pub(crate) fee_recipient_token_account: TokenAccountInfo<'a, 'info>,
     pub(crate) quote_vault: TokenAccountInfo<'a, 'info>,
     pub(crate) token_program: Program<'a, 'info>,
+    /// Only required for Token-2022 markets
+    pub(crate) quote_mint: Option<MintAccountInfo<'a, 'info>>,
 }
 
 impl<'a, 'info> CollectFeesContext<'a, 'info> {
@@ -457,21 +1013,137 @@ impl<'a, 'info> CollectFeesContext<'a, 'info> {
             (header.quote_params, header.fee_recipient)
         };
         let account_iter = &mut accounts.iter();
+        let fee_recipient_token_account = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            &fee_recipient,
+        )?;
+        let quote_vault = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            &quote_params.vault_key,
+            &quote_params.vault_key,
+        )?;
+        let token_program = Program::new_token_program(next_account_info(account_iter)?)?;
+        let quote_mint = if token_program.key == &spl_token_2022::id() {
+            Some(MintAccountInfo::new_with_key(
+                next_account_info(account_iter)?,
+                &quote_params.mint_key,
+            )?)
+        } else {
+            None
+        };
         let ctx = Self {
-            fee_recipient_token_account: TokenAccountInfo::new_with_owner(
+            fee_recipient_token_account,
+            quote_vault,
+            token_program,
+            quote_mint,
+        };
+        Ok(ctx)
+    }
+}
+
+pub(crate) struct ClaimReferralFeesContext<'a, 'info> {
+    pub(crate) referrer_token_account: TokenAccountInfo<'a, 'info>,
+    pub(crate) quote_vault: TokenAccountInfo<'a, 'info>,
+    pub(crate) token_program: Program<'a, 'info>,
+    /// Only required for Token-2022 markets
+    pub(crate) quote_mint: Option<MintAccountInfo<'a, 'info>>,
+}
+
+impl<'a, 'info> ClaimReferralFeesContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: referrer,
+        } = market_context;
+        let quote_params = market_info.get_header()?.quote_params;
+        let account_iter = &mut accounts.iter();
+        let referrer_token_account = TokenAccountInfo::new_with_owner(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            referrer.key,
+        )?;
+        let quote_vault = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            &quote_params.vault_key,
+            &quote_params.vault_key,
+        )?;
+        let token_program = Program::new_token_program(next_account_info(account_iter)?)?;
+        let quote_mint = if token_program.key == &spl_token_2022::id() {
+            Some(MintAccountInfo::new_with_key(
                 next_account_info(account_iter)?,
                 &quote_params.mint_key,
-                &fee_recipient,
-            )?,
-            quote_vault: TokenAccountInfo::new_with_owner_and_key(
+            )?)
+        } else {
+            None
+        };
+        Ok(Self {
+            referrer_token_account,
+            quote_vault,
+            token_program,
+            quote_mint,
+        })
+    }
+}
+
+pub(crate) struct CollectFeesSplitContext<'a, 'info> {
+    pub(crate) quote_vault: TokenAccountInfo<'a, 'info>,
+    pub(crate) token_program: Program<'a, 'info>,
+    /// Only required for Token-2022 markets
+    pub(crate) quote_mint: Option<MintAccountInfo<'a, 'info>>,
+    /// Quote token accounts of the fee recipients, in the same order as the fee splits
+    pub(crate) recipient_token_accounts: Vec<TokenAccountInfo<'a, 'info>>,
+}
+
+impl<'a, 'info> CollectFeesSplitContext<'a, 'info> {
+    pub(crate) fn load(
+        market_context: &PhoenixMarketContext<'a, 'info>,
+        accounts: &'a [AccountInfo<'info>],
+        recipients: &[Pubkey],
+    ) -> Result<Self, ProgramError> {
+        let PhoenixMarketContext {
+            market_info,
+            signer: authority,
+        } = market_context;
+        market_info.assert_valid_authority(authority.key)?;
+        let quote_params = market_info.get_header()?.quote_params;
+        let account_iter = &mut accounts.iter();
+        let quote_vault = TokenAccountInfo::new_with_owner_and_key(
+            next_account_info(account_iter)?,
+            &quote_params.mint_key,
+            &quote_params.vault_key,
+            &quote_params.vault_key,
+        )?;
+        let token_program = Program::new_token_program(next_account_info(account_iter)?)?;
+        let quote_mint = if token_program.key == &spl_token_2022::id() {
+            Some(MintAccountInfo::new_with_key(
                 next_account_info(account_iter)?,
                 &quote_params.mint_key,
-                &quote_params.vault_key,
-                &quote_params.vault_key,
-            )?,
-            token_program: Program::new(next_account_info(account_iter)?, &spl_token::id())?,
+            )?)
+        } else {
+            None
         };
-        Ok(ctx)
+        let recipient_token_accounts = recipients
+            .iter()
+            .map(|recipient| {
+                TokenAccountInfo::new_with_owner(
+                    next_account_info(account_iter)?,
+                    &quote_params.mint_key,
+                    recipient,
+                )
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        Ok(Self {
+            quote_vault,
+            token_program,
+            quote_mint,
+            recipient_token_accounts,
+        })
     }
 }
 
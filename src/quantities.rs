@@ -14,6 +14,13 @@ pub trait WrapperU64 {
     fn as_u64(&self) -> u64;
 }
 
+/// Multiplication that returns `None` instead of overflowing. This is implemented for every pair
+/// of quantities that can be multiplied with `*`.
+pub trait CheckedMul<Rhs = Self> {
+    type Output;
+    fn checked_mul(self, other: Rhs) -> Option<Self::Output>;
+}
+
 macro_rules! basic_u64_struct {
     ($type_name:ident) => {
         #[derive(Debug, Clone, Copy, PartialOrd, Ord, Zeroable, Pod)]
@@ -51,6 +58,29 @@ macro_rules! basic_u64 {
                 $type_name::new(self.inner.saturating_sub(other.inner))
             }
 
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                self.inner.checked_add(other.inner).map($type_name::new)
+            }
+
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                self.inner.checked_sub(other.inner).map($type_name::new)
+            }
+
+            /// Downcasts an intermediate u128 result. Debug builds assert that the value fits in
+            /// a u64, while release builds truncate without the check to keep hot paths cheap.
+            #[inline(always)]
+            #[track_caller]
+            pub fn from_u128(value: u128) -> Self {
+                #[cfg(debug_assertions)]
+                assert!(
+                    value <= u64::MAX as u128,
+                    "{} overflowed u64: {}",
+                    stringify!($type_name),
+                    value
+                );
+                $type_name::new(value as u64)
+            }
+
             pub fn unchecked_div<Divisor: WrapperU64, Quotient: WrapperU64>(
                 self,
                 other: Divisor,
@@ -72,6 +102,13 @@ macro_rules! basic_u64 {
             }
         }
 
+        impl CheckedMul for $type_name {
+            type Output = Self;
+            fn checked_mul(self, other: Self) -> Option<Self> {
+                self.inner.checked_mul(other.inner).map($type_name::new)
+            }
+        }
+
         impl Sum<$type_name> for $type_name {
             fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
                 iter.fold($type_name::ZERO, |acc, x| acc + x)
@@ -162,6 +199,20 @@ macro_rules! allow_multiply {
             }
         }
 
+        impl CheckedMul<$type_2> for $type_1 {
+            type Output = $type_result;
+            fn checked_mul(self, other: $type_2) -> Option<$type_result> {
+                self.inner.checked_mul(other.inner).map($type_result::new)
+            }
+        }
+
+        impl CheckedMul<$type_1> for $type_2 {
+            type Output = $type_result;
+            fn checked_mul(self, other: $type_1) -> Option<$type_result> {
+                self.inner.checked_mul(other.inner).map($type_result::new)
+            }
+        }
+
         impl Div<$type_1> for $type_result {
             type Output = $type_2;
             #[track_caller]
@@ -319,3 +370,49 @@ fn test_multiply_macro() {
     // let quote_units = QuoteUnits::new(5);
     // let result = quote_units * base_lots_per_base_unit;
 }
+
+#[test]
+fn test_checked_arithmetic() {
+    assert_eq!(
+        BaseLots::new(5).checked_add(BaseLots::new(10)),
+        Some(BaseLots::new(15))
+    );
+    assert_eq!(BaseLots::MAX.checked_add(BaseLots::ONE), None);
+    assert_eq!(
+        BaseLots::new(5).checked_sub(BaseLots::new(5)),
+        Some(BaseLots::ZERO)
+    );
+    assert_eq!(BaseLots::new(5).checked_sub(BaseLots::new(6)), None);
+    assert_eq!(
+        Ticks::new(5).checked_mul(Ticks::new(10)),
+        Some(Ticks::new(50))
+    );
+    assert_eq!(Ticks::MAX.checked_mul(Ticks::new(2)), None);
+
+    let quote_lots = QuoteLots::new(u64::MAX / 100);
+    let base_lots_per_base_unit = BaseLotsPerBaseUnit::new(100);
+    assert_eq!(
+        quote_lots.checked_mul(base_lots_per_base_unit),
+        Some(quote_lots * base_lots_per_base_unit)
+    );
+    assert_eq!(
+        base_lots_per_base_unit.checked_mul(quote_lots),
+        Some(quote_lots * base_lots_per_base_unit)
+    );
+    assert_eq!(
+        (quote_lots + QuoteLots::ONE).checked_mul(base_lots_per_base_unit),
+        None
+    );
+
+    assert_eq!(
+        AdjustedQuoteLots::from_u128(u64::MAX as u128),
+        AdjustedQuoteLots::MAX
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn test_from_u128_overflow() {
+    AdjustedQuoteLots::from_u128(u64::MAX as u128 + 1);
+}
@@ -27,8 +27,25 @@ struct MarketHeader {
     market_sequence_number: u64,
     successor: Pubkey,
     raw_base_units_per_base_unit: u32,
-    _padding1: u32,
-    _padding2: [u64; 32],
+    recent_fills_enabled: u32,
+    book_checkpoints_enabled: u64,
+    client_order_id_index_enabled: u64,
+    trading_disabled_from_slot: u64,
+    trading_disabled_until_slot: u64,
+    num_bids: u64,
+    num_asks: u64,
+    num_traders: u64,
+    market_type: u64,
+    seat_inactivity_threshold_slots: u64,
+    stop_orders_enabled: u64,
+    base_symbol: [u8; 16],
+    quote_symbol: [u8; 16],
+    base_display_decimals: u32,
+    quote_display_decimals: u32,
+    disabled_event_types: u64,
+    referral_fees_enabled: u64,
+    iceberg_orders_enabled: u64,
+    _padding2: [u64; 14],
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Copy, Clone, PartialEq, Eq, Debug)]
@@ -43,6 +60,9 @@ enum OrderPacket {
         last_valid_slot: Option<u64>,
         last_valid_unix_timestamp_in_seconds: Option<u64>,
         fail_silently_on_insufficient_funds: bool,
+        max_tick_adjustment: Option<u64>,
+        require_top_of_book: bool,
+        valid_for_slots: Option<u32>,
     },
     Limit {
         side: Side,
@@ -55,6 +75,9 @@ enum OrderPacket {
         last_valid_slot: Option<u64>,
         last_valid_unix_timestamp_in_seconds: Option<u64>,
         fail_silently_on_insufficient_funds: bool,
+        reduce_only: bool,
+        place_partial: bool,
+        valid_for_slots: Option<u32>,
     },
     ImmediateOrCancel {
         side: Side,
@@ -69,5 +92,43 @@ enum OrderPacket {
         use_only_deposited_funds: bool,
         last_valid_slot: Option<u64>,
         last_valid_unix_timestamp_in_seconds: Option<u64>,
+        max_avg_price_in_ticks: Option<u64>,
+        referrer: Option<Pubkey>,
+    },
+    FillOrKill {
+        side: Side,
+        price_in_ticks: Option<u64>,
+        num_base_lots: u64,
+        num_quote_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+        last_valid_slot: Option<u64>,
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+    },
+    Iceberg {
+        side: Side,
+        price_in_ticks: u64,
+        num_base_lots: u64,
+        display_base_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+        last_valid_slot: Option<u64>,
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+    },
+    LimitByQuote {
+        side: Side,
+        price_in_ticks: u64,
+        num_quote_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+        last_valid_slot: Option<u64>,
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+        valid_for_slots: Option<u32>,
     },
 }
@@ -27,4 +27,35 @@ pub enum SelfTradeBehavior {
     Abort,
     CancelProvide,
     DecrementTake,
+    CancelTake,
+}
+
+/// Determines how taker fees are rounded to a whole number of quote lots. Markets round fees up
+/// unless this is changed by the market authority.
+#[derive(BorshDeserialize, BorshSerialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u64)]
+pub enum FeeRoundingMode {
+    /// Fees are rounded up, in favor of the market
+    Up,
+    /// Fees are rounded down, in favor of the trader
+    Down,
+    /// Fees are rounded to the nearest quote lot, with ties rounded up
+    Nearest,
+}
+
+impl Default for FeeRoundingMode {
+    fn default() -> Self {
+        Self::Up
+    }
+}
+
+impl From<u64> for FeeRoundingMode {
+    fn from(fee_rounding_mode: u64) -> Self {
+        match fee_rounding_mode {
+            0 => Self::Up,
+            1 => Self::Down,
+            2 => Self::Nearest,
+            _ => panic!("Invalid fee rounding mode"),
+        }
+    }
 }
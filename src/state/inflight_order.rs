@@ -1,9 +1,11 @@
-use crate::quantities::{AdjustedQuoteLots, BaseLots, QuoteLots, Ticks};
+use crate::quantities::{
+    AdjustedQuoteLots, BaseLots, QuoteLots, QuoteLotsPerBaseUnitPerTick, Ticks,
+};
 
 use super::{SelfTradeBehavior, Side};
 
 #[derive(Copy, Clone, Debug)]
-pub(crate) struct InflightOrder {
+pub struct InflightOrder {
     pub side: Side,
     pub self_trade_behavior: SelfTradeBehavior,
 
@@ -33,6 +35,9 @@ pub(crate) struct InflightOrder {
     /// Number of quote lots paid in fees
     pub quote_lot_fees: QuoteLots,
 
+    /// Price of the last resting order that the order was matched against
+    pub last_matched_price_in_ticks: Ticks,
+
     pub last_valid_slot: Option<u64>,
 
     pub last_valid_unix_timestamp_in_seconds: Option<u64>,
@@ -40,7 +45,7 @@ pub(crate) struct InflightOrder {
 
 impl InflightOrder {
     #[allow(clippy::too_many_arguments)]
-    pub(crate) fn new(
+    pub fn new(
         side: Side,
         self_trade_behavior: SelfTradeBehavior,
         limit_price_in_ticks: Ticks,
@@ -61,6 +66,7 @@ impl InflightOrder {
             matched_adjusted_quote_lots: AdjustedQuoteLots::ZERO,
             matched_base_lots: BaseLots::ZERO,
             quote_lot_fees: QuoteLots::ZERO,
+            last_matched_price_in_ticks: Ticks::ZERO,
             last_valid_slot,
             last_valid_unix_timestamp_in_seconds,
         }
@@ -74,6 +80,29 @@ impl InflightOrder {
             && !self.should_terminate
     }
 
+    /// Returns true if a resting order at `price_in_ticks` can be matched against this order
+    #[inline(always)]
+    pub(crate) fn crosses(&self, price_in_ticks: Ticks) -> bool {
+        match self.side {
+            Side::Bid => price_in_ticks <= self.limit_price_in_ticks,
+            Side::Ask => price_in_ticks >= self.limit_price_in_ticks,
+        }
+    }
+
+    /// Returns true if the order matched and its remaining quote lot budget cannot pay for another
+    /// base lot at the price of its last match. Orders are matched in whole base lots, so this is
+    /// when a quote lot budget is filled.
+    pub(crate) fn quote_lot_budget_filled(
+        &self,
+        tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    ) -> bool {
+        self.matched_base_lots > BaseLots::ZERO
+            && self.adjusted_quote_lot_budget
+                < tick_size_in_quote_lots_per_base_unit
+                    * self.last_matched_price_in_ticks
+                    * BaseLots::ONE
+    }
+
     pub(crate) fn process_match(
         &mut self,
         matched_adjusted_quote_lots: AdjustedQuoteLots,
@@ -1,11 +1,14 @@
 use super::Market;
 use super::MarketEvent;
 use super::OrderId;
+use super::PriceAccumulator;
 use super::RestingOrder;
+use super::TypedLadder;
 use super::WritableMarket;
 use crate::quantities::AdjustedQuoteLots;
 use crate::quantities::BaseLots;
 use crate::quantities::BaseLotsPerBaseUnit;
+use crate::quantities::CheckedMul;
 use crate::quantities::QuoteLots;
 use crate::quantities::QuoteLotsPerBaseUnit;
 use crate::quantities::QuoteLotsPerBaseUnitPerTick;
@@ -16,10 +19,13 @@ use crate::state::matching_engine_response::MatchingEngineResponse;
 use crate::state::*;
 use borsh::{BorshDeserialize, BorshSerialize};
 use bytemuck::{Pod, Zeroable};
+use itertools::Itertools;
 use phoenix_log;
 use sokoban::node_allocator::{NodeAllocatorMap, OrderedNodeAllocatorMap, ZeroCopy, SENTINEL};
 use sokoban::{FromSlice, RedBlackTree};
+use static_assertions::const_assert_eq;
 use std::fmt::Debug;
+use std::iter::Peekable;
 
 #[repr(C)]
 #[derive(
@@ -64,6 +70,15 @@ impl FIFOOrderId {
             order_sequence_number,
         }
     }
+
+    /// Returns the id of an order on `side` with the given market sequence number
+    pub fn new_for_side(side: Side, price_in_ticks: Ticks, order_sequence_number: u64) -> Self {
+        // Bids store the inverted sequence number so that older orders sort first on both sides
+        match side {
+            Side::Bid => FIFOOrderId::new(price_in_ticks, !order_sequence_number),
+            Side::Ask => FIFOOrderId::new(price_in_ticks, order_sequence_number),
+        }
+    }
 }
 
 impl PartialOrd for FIFOOrderId {
@@ -109,6 +124,8 @@ pub struct FIFORestingOrder {
     pub last_valid_unix_timestamp_in_seconds: u64,
 }
 
+const_assert_eq!(std::mem::size_of::<FIFORestingOrder>(), 32);
+
 impl FIFORestingOrder {
     pub fn new_default(trader_index: u64, num_base_lots: BaseLots) -> Self {
         FIFORestingOrder {
@@ -160,6 +177,49 @@ impl FIFORestingOrder {
     }
 }
 
+/// The hidden reserve of an iceberg order. Reserves are stored outside of the market, keyed by the
+/// order id of the visible part of the order on the book.
+///
+/// The funds for the reserve are locked along with the visible size. For bids, `quote_lots_locked`
+/// tracks the quote lots locked for the reserve, so that rounding never releases more than was
+/// locked at placement.
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Zeroable, Pod)]
+pub struct IcebergReserve {
+    pub trader_index: u64,
+    /// Number of base lots that are hidden from the book
+    pub reserve_base_lots: BaseLots,
+    /// Visible size that the order is replenished to
+    pub display_base_lots: BaseLots,
+    pub quote_lots_locked: QuoteLots,
+    /// The client order id is split into two u64s to keep the reserve 8-byte aligned
+    client_order_id_low: u64,
+    client_order_id_high: u64,
+}
+
+impl IcebergReserve {
+    pub fn new(
+        trader_index: u64,
+        reserve_base_lots: BaseLots,
+        display_base_lots: BaseLots,
+        quote_lots_locked: QuoteLots,
+        client_order_id: u128,
+    ) -> Self {
+        IcebergReserve {
+            trader_index,
+            reserve_base_lots,
+            display_base_lots,
+            quote_lots_locked,
+            client_order_id_low: client_order_id as u64,
+            client_order_id_high: (client_order_id >> 64) as u64,
+        }
+    }
+
+    pub fn client_order_id(&self) -> u128 {
+        ((self.client_order_id_high as u128) << 64) | self.client_order_id_low as u128
+    }
+}
+
 impl RestingOrder for FIFORestingOrder {
     fn size(&self) -> u64 {
         self.num_base_lots.as_u64()
@@ -188,6 +248,204 @@ impl RestingOrder for FIFORestingOrder {
     }
 }
 
+/// Sums the size of the resting orders at `price_in_ticks` by descending the tree. Only subtrees
+/// that can contain the target price are visited, so the search stops at the nodes bordering the
+/// price level instead of walking the book from the top.
+fn get_base_lots_at_tick_in_book<const SIZE: usize>(
+    book: &RedBlackTree<FIFOOrderId, FIFORestingOrder, SIZE>,
+    side: Side,
+    price_in_ticks: Ticks,
+) -> BaseLots {
+    let mut base_lots = BaseLots::ZERO;
+    let mut stack = vec![book.root as u32];
+    while let Some(addr) = stack.pop() {
+        if addr == SENTINEL {
+            continue;
+        }
+        let node = book.get_node(addr);
+        let node_price_in_ticks = node.key.price_in_ticks;
+        // Bids are sorted in descending order and asks in ascending order
+        let precedes_target = match side {
+            Side::Bid => node_price_in_ticks > price_in_ticks,
+            Side::Ask => node_price_in_ticks < price_in_ticks,
+        };
+        if node_price_in_ticks == price_in_ticks {
+            base_lots += node.value.num_base_lots;
+            stack.push(book.get_left(addr));
+            stack.push(book.get_right(addr));
+        } else if precedes_target {
+            stack.push(book.get_right(addr));
+        } else {
+            stack.push(book.get_left(addr));
+        }
+    }
+    base_lots
+}
+
+/// Lazily aggregates the resting orders on one side of the book into price levels. Consecutive
+/// orders at the same tick are coalesced into a single `(price_in_ticks, base_lots)` level, so
+/// callers can stop after the levels they need without materializing a ladder.
+pub struct LevelIterator<'a, I: Iterator<Item = (&'a FIFOOrderId, &'a FIFORestingOrder)>> {
+    orders: Peekable<I>,
+}
+
+impl<'a, I: Iterator<Item = (&'a FIFOOrderId, &'a FIFORestingOrder)>> LevelIterator<'a, I> {
+    /// `orders` must yield the orders of a single side in book order
+    pub fn new(orders: I) -> Self {
+        Self {
+            orders: orders.peekable(),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = (&'a FIFOOrderId, &'a FIFORestingOrder)>> Iterator
+    for LevelIterator<'a, I>
+{
+    type Item = (Ticks, BaseLots);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (order_id, resting_order) = self.orders.next()?;
+        let price_in_ticks = order_id.price_in_ticks;
+        let mut base_lots = resting_order.num_base_lots;
+        while let Some((_, resting_order)) = self
+            .orders
+            .next_if(|(order_id, _)| order_id.price_in_ticks == price_in_ticks)
+        {
+            base_lots += resting_order.num_base_lots;
+        }
+        Some((price_in_ticks, base_lots))
+    }
+}
+
+/// A read-only view of one side of the book with the changes of a match applied on top of it.
+/// Only the orders that the match touches are tracked, so the book itself is never copied.
+/// `match_order` applies each step of a match to the book and reads it through an unchanged view,
+/// while `peek_match` applies the steps to the view.
+struct BookView<'a> {
+    book: &'a dyn OrderedNodeAllocatorMap<FIFOOrderId, FIFORestingOrder>,
+    /// Remaining size of the resting orders that have been reduced. Removed orders have no size left.
+    reduced_orders: Vec<(FIFOOrderId, BaseLots)>,
+    /// Replenished iceberg orders, in book order
+    inserted_orders: Vec<(FIFOOrderId, FIFORestingOrder)>,
+}
+
+impl<'a> BookView<'a> {
+    fn new(book: &'a dyn OrderedNodeAllocatorMap<FIFOOrderId, FIFORestingOrder>) -> Self {
+        Self {
+            book,
+            reduced_orders: vec![],
+            inserted_orders: vec![],
+        }
+    }
+
+    fn apply_reductions(
+        &self,
+        order_id: FIFOOrderId,
+        resting_order: FIFORestingOrder,
+    ) -> Option<(FIFOOrderId, FIFORestingOrder)> {
+        match self
+            .reduced_orders
+            .iter()
+            .find(|(reduced_order_id, _)| *reduced_order_id == order_id)
+        {
+            Some((_, num_base_lots)) if *num_base_lots == BaseLots::ZERO => None,
+            Some((_, num_base_lots)) => Some((
+                order_id,
+                FIFORestingOrder {
+                    num_base_lots: *num_base_lots,
+                    ..resting_order
+                },
+            )),
+            None => Some((order_id, resting_order)),
+        }
+    }
+
+    /// Returns the resting orders in book order
+    fn orders(&self) -> impl Iterator<Item = (FIFOOrderId, FIFORestingOrder)> + '_ {
+        self.book
+            .iter()
+            .map(|(order_id, resting_order)| (*order_id, *resting_order))
+            .merge_by(self.inserted_orders.iter().copied(), |(a, _), (b, _)| a < b)
+            .filter_map(move |(order_id, resting_order)| {
+                self.apply_reductions(order_id, resting_order)
+            })
+    }
+
+    fn get(&self, order_id: &FIFOOrderId) -> Option<(FIFOOrderId, FIFORestingOrder)> {
+        let resting_order = match self.book.get(order_id) {
+            Some(resting_order) => *resting_order,
+            None => self
+                .inserted_orders
+                .iter()
+                .find(|(inserted_order_id, _)| inserted_order_id == order_id)
+                .map(|(_, resting_order)| *resting_order)?,
+        };
+        self.apply_reductions(*order_id, resting_order)
+    }
+
+    fn reduce(&mut self, order_id: FIFOOrderId, num_base_lots_remaining: BaseLots) {
+        match self
+            .reduced_orders
+            .iter_mut()
+            .find(|(reduced_order_id, _)| *reduced_order_id == order_id)
+        {
+            Some((_, num_base_lots)) => *num_base_lots = num_base_lots_remaining,
+            None => self
+                .reduced_orders
+                .push((order_id, num_base_lots_remaining)),
+        }
+    }
+
+    fn insert(&mut self, order_id: FIFOOrderId, resting_order: FIFORestingOrder) {
+        let index = self
+            .inserted_orders
+            .partition_point(|(inserted_order_id, _)| *inserted_order_id < order_id);
+        self.inserted_orders
+            .insert(index, (order_id, resting_order));
+    }
+}
+
+/// What happens to the next crossed resting order when an inflight order is matched. The inflight
+/// order's budget is already updated for the step, and the step is applied to the book by the
+/// caller.
+#[derive(Debug, Clone, Copy)]
+enum MatchStep {
+    /// The resting order is a tombstone or has expired, and is removed from the book
+    Remove {
+        order_id: FIFOOrderId,
+        resting_order: FIFORestingOrder,
+    },
+    /// The resting order belongs to the taker, whose self trade behavior aborts the order
+    Abort,
+    /// The resting order belongs to the taker. `base_lots_removed` of it are cancelled by the
+    /// self trade behavior, or none if the resting order is left on the book.
+    SelfTrade {
+        order_id: FIFOOrderId,
+        resting_order: FIFORestingOrder,
+        base_lots_removed: Option<BaseLots>,
+    },
+    /// The inflight order matches `matched_base_lots` of the resting order
+    Fill {
+        order_id: FIFOOrderId,
+        resting_order: FIFORestingOrder,
+        matched_base_lots: BaseLots,
+        matched_adjusted_quote_lots: AdjustedQuoteLots,
+        order_completed: bool,
+    },
+}
+
+/// Number of fee tiers that can be assigned to traders in addition to the default tier
+pub const NUM_FEE_TIERS: usize = 4;
+
+/// Orders cannot be placed once the market's order sequence number reaches this value. The top bit
+/// is reserved because bids store the bitwise inverse of the sequence number in their order ids.
+pub const MAX_ORDER_SEQUENCE_NUMBER: u64 = u64::MAX >> 1;
+
+/// The order book and trader state of a market.
+///
+/// If `PRO_RATA` is false, resting orders at a price level are filled in time priority. If it is true,
+/// an incoming order that cannot fill the entire level is split across the resting orders at that
+/// level in proportion to their size (see `ProRataMarket`). Both configurations have the same layout.
 #[repr(C)]
 #[derive(Default, Copy, Clone, Zeroable)]
 pub struct FIFOMarket<
@@ -204,9 +462,79 @@ pub struct FIFOMarket<
     const BIDS_SIZE: usize,
     const ASKS_SIZE: usize,
     const NUM_SEATS: usize,
+    const PRO_RATA: bool = false,
 > {
     /// Padding
-    pub _padding: [u64; 32],
+    pub _padding: [u64; 2],
+
+    /// If this is nonzero, immediate-or-cancel and fill-or-kill orders that match fewer than this
+    /// many base lots are voided. Orders that do not match at all are unaffected.
+    pub min_taker_fill_base_lots: BaseLots,
+
+    /// The share of the taker fee of an order that names a referrer, in basis points of the fee,
+    /// that is accrued to the referrer instead of the market.
+    pub referral_fee_bps: u64,
+
+    /// Amount of referral fees accrued to referrers that have not been claimed, in quote lots.
+    unclaimed_referral_quote_lot_fees: QuoteLots,
+
+    /// If this is nonzero, orders that would take liquidity are rejected. Immediate-or-cancel
+    /// and fill-or-kill orders are always rejected, and other orders are rejected if they cross
+    /// the book. Post-only orders are unaffected.
+    pub taking_disabled: u64,
+
+    /// If this is nonzero, an order that matches more than this many base lots starts a volatility
+    /// cooldown of `volatility_cooldown_slots` slots. During the cooldown, orders that can take
+    /// liquidity are rejected if their limit price is more than `volatility_band_bps` away from
+    /// the last trade price in the direction of the order.
+    pub volatility_fill_threshold_base_lots: BaseLots,
+    pub volatility_cooldown_slots: u64,
+    pub volatility_band_bps: u64,
+
+    /// The slot of the last order that matched more than `volatility_fill_threshold_base_lots`.
+    pub last_large_fill_slot: u64,
+
+    /// The price of the last fill on the market.
+    pub last_trade_price_in_ticks: Ticks,
+
+    /// If this is nonzero, a trader can have at most this many orders resting on each side of
+    /// the book. Orders that would exceed the limit are rejected.
+    pub max_orders_per_side_per_trader: u64,
+
+    /// If this is nonzero, orders never match against more than this many resting orders,
+    /// regardless of the `match_limit` of the order. Orders without a `match_limit` use this limit.
+    pub max_match_limit: u64,
+
+    /// Trades that match any base lots pay at least this fee, capped at the quote value of the
+    /// trade. Zero disables the minimum.
+    pub min_fee_quote_lots: QuoteLots,
+
+    /// How taker fees are rounded to a whole number of quote lots (see `FeeRoundingMode`).
+    pub fee_rounding_mode: u64,
+
+    /// If this is nonzero, orders that rest on the book must expire within this many slots of
+    /// being placed. Orders without a `last_valid_slot` are given the latest allowed slot.
+    pub max_order_lifetime_slots: u64,
+
+    /// If this is nonzero, the cumulative volume and fill count of each trader is updated on every fill.
+    pub trader_volume_tracking_enabled: u64,
+
+    /// If this is nonzero, `price_accumulator` is updated on every fill.
+    pub price_accumulator_enabled: u64,
+
+    /// Running totals of the fills on the market, used to compute volume and time weighted average prices.
+    pub price_accumulator: PriceAccumulator,
+
+    /// Taker fees for fee tiers 1 through `NUM_FEE_TIERS`, in basis points. Traders in the
+    /// default tier (0) and unregistered takers pay `taker_fee_bps`.
+    pub fee_tiers_bps: [u64; NUM_FEE_TIERS],
+
+    /// Orders that would rest on the book with fewer base lots than this are rejected.
+    pub min_base_lots_per_order: BaseLots,
+
+    /// Maker rebates are credited to the resting order's trader on the quote lots transacted in the trade, in basis points.
+    /// Rebates are funded from (and bounded by) the taker fees collected on the same match.
+    pub maker_rebate_bps: u64,
 
     /// Number of base lots in a base unit. For example, if the lot size is 0.001 SOL, then base_lots_per_base_unit is 1000.
     pub base_lots_per_base_unit: BaseLotsPerBaseUnit,
@@ -217,7 +545,7 @@ pub struct FIFOMarket<
     /// The sequence number of the next event.
     order_sequence_number: u64,
 
-    /// There are no maker fees. Taker fees are charged on the quote lots transacted in the trade, in basis points.
+    /// Taker fees are charged on the quote lots transacted in the trade, in basis points.
     pub taker_fee_bps: u64,
 
     /// Amount of fees collected from the market in its lifetime, in quote lots.
@@ -236,6 +564,16 @@ pub struct FIFOMarket<
     pub traders: RedBlackTree<MarketTraderId, TraderState, NUM_SEATS>,
 }
 
+/// A market that fills the resting orders at each crossed price level pro-rata by size. Orders are
+/// still matched in price priority, and any base lots that cannot be divided evenly are assigned to
+/// the oldest orders first.
+pub type ProRataMarket<
+    MarketTraderId,
+    const BIDS_SIZE: usize,
+    const ASKS_SIZE: usize,
+    const NUM_SEATS: usize,
+> = FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, true>;
+
 unsafe impl<
         MarketTraderId: Debug
             + PartialOrd
@@ -250,7 +588,8 @@ unsafe impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
-    > Pod for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+        const PRO_RATA: bool,
+    > Pod for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
 }
 
@@ -268,7 +607,8 @@ impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
-    > FromSlice for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+        const PRO_RATA: bool,
+    > FromSlice for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
     fn new_from_slice(data: &mut [u8]) -> &mut Self {
         let market = Self::load_mut_bytes(data).unwrap();
@@ -293,7 +633,8 @@ impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
-    > ZeroCopy for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+        const PRO_RATA: bool,
+    > ZeroCopy for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
 }
 
@@ -311,8 +652,9 @@ impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
+        const PRO_RATA: bool,
     > Market<MarketTraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>
-    for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+    for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
     fn get_data_size(&self) -> usize {
         std::mem::size_of::<Self>()
@@ -322,6 +664,128 @@ impl<
         self.taker_fee_bps
     }
 
+    fn get_maker_rebate_bps(&self) -> u64 {
+        self.maker_rebate_bps
+    }
+
+    fn get_min_base_lots_per_order(&self) -> BaseLots {
+        self.min_base_lots_per_order
+    }
+
+    fn get_max_order_lifetime_slots(&self) -> u64 {
+        self.max_order_lifetime_slots
+    }
+
+    fn get_max_match_limit(&self) -> u64 {
+        self.max_match_limit
+    }
+
+    fn get_volatility_band(&self) -> (BaseLots, u64, u64) {
+        (
+            self.volatility_fill_threshold_base_lots,
+            self.volatility_cooldown_slots,
+            self.volatility_band_bps,
+        )
+    }
+
+    fn get_last_trade_price(&self) -> Ticks {
+        self.last_trade_price_in_ticks
+    }
+
+    fn get_last_large_fill_slot(&self) -> u64 {
+        self.last_large_fill_slot
+    }
+
+    fn get_max_orders_per_side_per_trader(&self) -> u64 {
+        self.max_orders_per_side_per_trader
+    }
+
+    fn get_fee_rounding_mode(&self) -> FeeRoundingMode {
+        FeeRoundingMode::from(self.fee_rounding_mode)
+    }
+
+    fn get_min_fee_quote_lots(&self) -> QuoteLots {
+        self.min_fee_quote_lots
+    }
+
+    fn get_fee_tiers_bps(&self) -> [u64; NUM_FEE_TIERS] {
+        self.fee_tiers_bps
+    }
+
+    fn get_twap(&self) -> Option<PriceAccumulator> {
+        if self.price_accumulator_enabled == 0 {
+            return None;
+        }
+        Some(self.price_accumulator)
+    }
+
+    fn is_trader_volume_tracking_enabled(&self) -> bool {
+        self.trader_volume_tracking_enabled != 0
+    }
+
+    fn is_taking_disabled(&self) -> bool {
+        self.taking_disabled != 0
+    }
+
+    fn get_referral_fee_bps(&self) -> u64 {
+        self.referral_fee_bps
+    }
+
+    fn get_unclaimed_referral_fee_amount(&self) -> QuoteLots {
+        self.unclaimed_referral_quote_lot_fees
+    }
+
+    fn get_min_taker_fill_base_lots(&self) -> BaseLots {
+        self.min_taker_fill_base_lots
+    }
+
+    fn get_impact_price_with_expiration(
+        &self,
+        side: Side,
+        size: BaseLots,
+        current_slot: Option<u64>,
+        current_unix_timestamp_in_seconds: Option<u64>,
+    ) -> Option<(Ticks, BaseLots)> {
+        let current_slot = current_slot.unwrap_or(0);
+        let current_unix_timestamp = current_unix_timestamp_in_seconds.unwrap_or(0);
+        let mut filled_base_lots = BaseLots::ZERO;
+        let mut filled_adjusted_quote_lots = AdjustedQuoteLots::ZERO;
+        for (order_id, resting_order) in self.get_book(side.opposite()).iter() {
+            if filled_base_lots >= size {
+                break;
+            }
+            // Expired orders would be removed during matching, so they do not contribute to the fill
+            if resting_order.is_expired(current_slot, current_unix_timestamp)
+                || resting_order.num_base_lots == BaseLots::ZERO
+            {
+                continue;
+            }
+            let base_lots = resting_order.num_base_lots.min(size - filled_base_lots);
+            filled_base_lots += base_lots;
+            filled_adjusted_quote_lots +=
+                order_id.price_in_ticks * self.tick_size_in_quote_lots_per_base_unit * base_lots;
+        }
+        if filled_base_lots == BaseLots::ZERO {
+            return None;
+        }
+        let numerator = filled_adjusted_quote_lots.as_u128();
+        let denominator =
+            self.tick_size_in_quote_lots_per_base_unit.as_u128() * filled_base_lots.as_u128();
+        // The average price is rounded against the taker
+        let average_price_in_ticks = match side {
+            Side::Bid => (numerator + denominator - 1) / denominator,
+            Side::Ask => numerator / denominator,
+        };
+        Some((Ticks::from_u128(average_price_in_ticks), filled_base_lots))
+    }
+
+    fn get_base_lots_at_tick(&self, side: Side, price_in_ticks: Ticks) -> BaseLots {
+        match side {
+            Side::Bid => get_base_lots_at_tick_in_book(&self.bids, side, price_in_ticks),
+            Side::Ask => get_base_lots_at_tick_in_book(&self.asks, side, price_in_ticks),
+        }
+    }
+
     fn get_tick_size(&self) -> QuoteLotsPerBaseUnitPerTick {
         self.tick_size_in_quote_lots_per_base_unit
     }
@@ -334,6 +798,10 @@ impl<
         self.order_sequence_number
     }
 
+    fn get_next_order_id(&self, side: Side, price_in_ticks: Ticks) -> FIFOOrderId {
+        FIFOOrderId::new_for_side(side, price_in_ticks, self.order_sequence_number)
+    }
+
     fn get_collected_fee_amount(&self) -> QuoteLots {
         self.collected_quote_lot_fees
     }
@@ -375,6 +843,88 @@ impl<
             Side::Ask => &self.asks,
         }
     }
+
+    fn get_order(&self, order_id: &FIFOOrderId) -> Option<FIFORestingOrder> {
+        self.get_book(Side::from_order_sequence_number(
+            order_id.order_sequence_number,
+        ))
+        .get(order_id)
+        .copied()
+    }
+
+    fn get_orders_for_trader(
+        &self,
+        trader_id: &MarketTraderId,
+    ) -> Vec<(FIFOOrderId, FIFORestingOrder)> {
+        let trader_index = match self.get_trader_index(trader_id) {
+            Some(index) => index as u64,
+            None => return vec![],
+        };
+        let mut orders = self
+            .get_book(Side::Bid)
+            .iter()
+            .chain(self.get_book(Side::Ask).iter())
+            .filter(|(_, order)| order.trader_index == trader_index)
+            .map(|(order_id, order)| (*order_id, *order))
+            .collect::<Vec<_>>();
+        // The sort is stable, so orders at the same price level remain in time priority
+        orders.sort_by_key(|(order_id, _)| order_id.price_in_ticks);
+        orders
+    }
+
+    fn get_typed_ladder_excluding_trader(
+        &self,
+        levels: u64,
+        trader_id: &MarketTraderId,
+    ) -> TypedLadder {
+        let trader_index = self.get_trader_index(trader_id).map(|index| index as u64);
+        self.get_typed_ladder_with_filter(levels, &|_, resting_order| {
+            Some(resting_order.trader_index) != trader_index
+        })
+    }
+
+    fn simulate_order(
+        &self,
+        order_packet: &OrderPacket,
+        clock: (u64, u64),
+    ) -> Option<MatchingEngineResponse> {
+        // Orders that can rest on the book are not simulated, and an unregistered taker has no
+        // deposited funds to trade with
+        if !order_packet.is_take_only() || order_packet.no_deposit_or_withdrawal() {
+            return None;
+        }
+        let (current_slot, current_unix_timestamp) = clock;
+        if order_packet.side() == Side::Bid && order_packet.get_price_in_ticks() == Ticks::ZERO {
+            return None;
+        }
+        Self::check_immediate_order_params(order_packet).ok()?;
+        if order_packet.is_expired(current_slot, current_unix_timestamp) {
+            return Some(MatchingEngineResponse::default());
+        }
+        self.check_taking_allowed(order_packet, current_slot).ok()?;
+
+        // The order is matched with the same steps as `place_order`, but against a read-only view
+        // of the book
+        let (mut inflight_order, taker_fee_bps) =
+            self.get_inflight_order(order_packet, u32::MAX, None);
+        let (matched_base_lots, matched_adjusted_quote_lots) =
+            self.peek_match(&mut inflight_order, u32::MAX, None, clock);
+        inflight_order.matched_base_lots = matched_base_lots;
+        inflight_order.matched_adjusted_quote_lots = matched_adjusted_quote_lots;
+        inflight_order.quote_lot_fees = self
+            .round_fee_to_quote_lots(self.compute_fee(matched_adjusted_quote_lots, taker_fee_bps))
+            .max(self.get_min_fee_quote_lots(&inflight_order));
+        let matching_engine_response =
+            self.get_matching_engine_response(order_packet.side(), &inflight_order);
+        self.check_immediate_order_fills(
+            order_packet,
+            inflight_order.quote_lot_budget_filled(self.tick_size_in_quote_lots_per_base_unit),
+            &matching_engine_response,
+            self.min_taker_fill_base_lots,
+        )
+        .ok()?;
+        Some(matching_engine_response)
+    }
 }
 
 impl<
@@ -391,8 +941,9 @@ impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
+        const PRO_RATA: bool,
     > WritableMarket<MarketTraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>
-    for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+    for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
     fn initialize_with_params(
         &mut self,
@@ -409,6 +960,80 @@ impl<
         self.taker_fee_bps = taker_fee_bps;
     }
 
+    fn set_maker_rebate(&mut self, maker_rebate_bps: u64) {
+        self.maker_rebate_bps = maker_rebate_bps;
+    }
+
+    fn set_fee_tiers(&mut self, fee_tiers_bps: [u64; NUM_FEE_TIERS]) {
+        self.fee_tiers_bps = fee_tiers_bps;
+    }
+
+    fn set_price_accumulator_enabled(&mut self, price_accumulator_enabled: bool) {
+        // Totals from a previous period of activity would skew averages that span the gap
+        if price_accumulator_enabled && self.price_accumulator_enabled == 0 {
+            self.price_accumulator = PriceAccumulator::default();
+        }
+        self.price_accumulator_enabled = price_accumulator_enabled as u64;
+    }
+
+    fn set_trader_volume_tracking_enabled(&mut self, trader_volume_tracking_enabled: bool) {
+        self.trader_volume_tracking_enabled = trader_volume_tracking_enabled as u64;
+    }
+
+    fn set_taking_disabled(&mut self, taking_disabled: bool) {
+        self.taking_disabled = taking_disabled as u64;
+    }
+
+    fn set_referral_fee_bps(&mut self, referral_fee_bps: u64) {
+        self.referral_fee_bps = referral_fee_bps;
+    }
+
+    fn set_min_taker_fill_base_lots(&mut self, min_taker_fill_base_lots: BaseLots) {
+        self.min_taker_fill_base_lots = min_taker_fill_base_lots;
+    }
+
+    fn set_min_base_lots_per_order(&mut self, min_base_lots_per_order: BaseLots) {
+        self.min_base_lots_per_order = min_base_lots_per_order;
+    }
+
+    fn set_max_order_lifetime_slots(&mut self, max_order_lifetime_slots: u64) {
+        self.max_order_lifetime_slots = max_order_lifetime_slots;
+    }
+
+    fn set_max_match_limit(&mut self, max_match_limit: u64) {
+        self.max_match_limit = max_match_limit;
+    }
+
+    fn set_volatility_band(
+        &mut self,
+        fill_threshold_base_lots: BaseLots,
+        cooldown_slots: u64,
+        band_bps: u64,
+    ) {
+        self.volatility_fill_threshold_base_lots = fill_threshold_base_lots;
+        self.volatility_cooldown_slots = cooldown_slots;
+        self.volatility_band_bps = band_bps;
+    }
+
+    fn set_max_orders_per_side_per_trader(&mut self, max_orders_per_side_per_trader: u64) {
+        self.max_orders_per_side_per_trader = max_orders_per_side_per_trader;
+    }
+
+    fn set_fee_rounding_mode(&mut self, fee_rounding_mode: FeeRoundingMode) {
+        self.fee_rounding_mode = fee_rounding_mode as u64;
+    }
+
+    fn set_min_fee_quote_lots(&mut self, min_fee_quote_lots: QuoteLots) {
+        self.min_fee_quote_lots = min_fee_quote_lots;
+    }
+
+    fn change_tick_size(
+        &mut self,
+        tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    ) -> Option<Vec<FIFOOrderId>> {
+        self.change_tick_size_inner(tick_size_in_quote_lots_per_base_unit)
+    }
+
     fn get_registered_traders_mut(
         &mut self,
     ) -> &mut dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState> {
@@ -440,10 +1065,78 @@ impl<
         order_packet: OrderPacket,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
         get_clock_fn: &mut dyn FnMut() -> (u64, u64),
-    ) -> Option<(Option<FIFOOrderId>, MatchingEngineResponse)> {
-        self.place_order_inner(trader_id, order_packet, record_event_fn, get_clock_fn)
+    ) -> Result<(Option<FIFOOrderId>, MatchingEngineResponse), PhoenixMatchError> {
+        self.place_order_inner(
+            trader_id,
+            order_packet,
+            None,
+            None,
+            self.min_taker_fill_base_lots,
+            record_event_fn,
+            get_clock_fn,
+        )
+    }
+
+    fn place_order_with_token_budget(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_packet: OrderPacket,
+        token_budget: (BaseLots, QuoteLots),
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<FIFOOrderId>, MatchingEngineResponse), PhoenixMatchError> {
+        self.place_order_inner(
+            trader_id,
+            order_packet,
+            Some(token_budget),
+            None,
+            self.min_taker_fill_base_lots,
+            record_event_fn,
+            get_clock_fn,
+        )
+    }
+
+    fn place_order_with_iceberg_reserves(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_packet: OrderPacket,
+        token_budget: Option<(BaseLots, QuoteLots)>,
+        iceberg_reserves: &mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<FIFOOrderId>, MatchingEngineResponse), PhoenixMatchError> {
+        self.place_order_inner(
+            trader_id,
+            order_packet,
+            token_budget,
+            Some(iceberg_reserves),
+            self.min_taker_fill_base_lots,
+            record_event_fn,
+            get_clock_fn,
+        )
+    }
+
+    fn place_order_with_min_taker_fill(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_packet: OrderPacket,
+        min_taker_fill_base_lots: BaseLots,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<FIFOOrderId>, MatchingEngineResponse), PhoenixMatchError> {
+        self.place_order_inner(
+            trader_id,
+            order_packet,
+            None,
+            iceberg_reserves,
+            min_taker_fill_base_lots,
+            record_event_fn,
+            get_clock_fn,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn reduce_order(
         &mut self,
         trader_id: &MarketTraderId,
@@ -451,6 +1144,7 @@ impl<
         side: Side,
         size: Option<BaseLots>,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         self.reduce_order_inner(
@@ -460,28 +1154,101 @@ impl<
             size,
             false,
             claim_funds,
+            iceberg_reserves,
             record_event_fn,
         )
     }
 
-    fn cancel_all_orders(
-        &mut self,
-        trader_id: &MarketTraderId,
-        claim_funds: bool,
-        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
-    ) -> Option<MatchingEngineResponse> {
-        self.cancel_all_orders_inner(trader_id, claim_funds, record_event_fn)
-    }
-
     #[allow(clippy::too_many_arguments)]
-    fn cancel_up_to(
+    fn amend_order(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &FIFOOrderId,
+        side: Side,
+        num_base_lots: BaseLots,
+        price_in_ticks: Option<Ticks>,
+        client_order_id: u128,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<(Option<FIFOOrderId>, MatchingEngineResponse)> {
+        self.amend_order_inner(
+            trader_id,
+            order_id,
+            side,
+            num_base_lots,
+            price_in_ticks,
+            client_order_id,
+            iceberg_reserves,
+            record_event_fn,
+            get_clock_fn,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn reprice_order(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &FIFOOrderId,
+        side: Side,
+        new_price_in_ticks: Ticks,
+        client_order_id: u128,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<FIFOOrderId> {
+        self.reprice_order_inner(
+            trader_id,
+            order_id,
+            side,
+            new_price_in_ticks,
+            client_order_id,
+            iceberg_reserves,
+            record_event_fn,
+            get_clock_fn,
+        )
+    }
+
+    fn cancel_all_orders(
+        &mut self,
+        trader_id: &MarketTraderId,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse> {
+        self.cancel_all_orders_inner(trader_id, claim_funds, iceberg_reserves, record_event_fn)
+    }
+
+    fn cancel_all_orders_bounded(
+        &mut self,
+        trader_id: &MarketTraderId,
+        max_orders: usize,
+        num_orders_to_search: Option<usize>,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<(MatchingEngineResponse, bool)> {
+        self.cancel_all_orders_bounded_inner(
+            trader_id,
+            max_orders,
+            num_orders_to_search,
+            claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn cancel_up_to(
         &mut self,
         trader_id: &MarketTraderId,
         side: Side,
         num_orders_to_search: Option<usize>,
         num_orders_to_cancel: Option<usize>,
         tick_limit: Option<Ticks>,
+        lots_to_free: Option<u64>,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         self.cancel_up_to_inner(
@@ -490,7 +1257,31 @@ impl<
             num_orders_to_search,
             num_orders_to_cancel,
             tick_limit,
+            lots_to_free,
+            claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn cancel_in_price_range(
+        &mut self,
+        trader_id: &MarketTraderId,
+        side: Side,
+        low_tick: Ticks,
+        high_tick: Ticks,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse> {
+        self.cancel_in_price_range_inner(
+            trader_id,
+            side,
+            low_tick,
+            high_tick,
             claim_funds,
+            iceberg_reserves,
             record_event_fn,
         )
     }
@@ -500,13 +1291,49 @@ impl<
         trader_id: &MarketTraderId,
         orders_to_cancel: &[FIFOOrderId],
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         self.cancel_multiple_orders_by_id_inner(
             self.get_trader_index(trader_id)?,
             orders_to_cancel,
             claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )
+    }
+
+    fn reduce_multiple_orders(
+        &mut self,
+        trader_id: &MarketTraderId,
+        orders_to_reduce: &[(FIFOOrderId, BaseLots)],
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse> {
+        self.reduce_multiple_orders_inner(
+            self.get_trader_index(trader_id)?,
+            orders_to_reduce,
+            claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )
+    }
+
+    fn prune_expired_orders(
+        &mut self,
+        side: Side,
+        num_orders_to_scan: usize,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<usize> {
+        self.prune_expired_orders_inner(
+            side,
+            num_orders_to_scan,
+            iceberg_reserves,
             record_event_fn,
+            get_clock_fn,
         )
     }
 
@@ -538,6 +1365,77 @@ impl<
         });
         fees_collected_in_quote_lots
     }
+
+    fn claim_referral_fees(&mut self, quote_lots: QuoteLots) -> Option<()> {
+        if quote_lots > self.unclaimed_referral_quote_lot_fees {
+            return None;
+        }
+        self.unclaimed_referral_quote_lot_fees -= quote_lots;
+        Some(())
+    }
+
+    fn forfeit_referral_fees(&mut self, quote_lots: QuoteLots) -> Option<()> {
+        self.claim_referral_fees(quote_lots)?;
+        self.unclaimed_quote_lot_fees += quote_lots;
+        Some(())
+    }
+
+    fn copy_from(
+        &mut self,
+        source: &dyn Market<MarketTraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    ) -> Option<()> {
+        self.initialize();
+        self.tick_size_in_quote_lots_per_base_unit = source.get_tick_size();
+        self.base_lots_per_base_unit = source.get_base_lots_per_base_unit();
+        self.min_base_lots_per_order = source.get_min_base_lots_per_order();
+        self.max_order_lifetime_slots = source.get_max_order_lifetime_slots();
+        self.max_match_limit = source.get_max_match_limit();
+        (
+            self.volatility_fill_threshold_base_lots,
+            self.volatility_cooldown_slots,
+            self.volatility_band_bps,
+        ) = source.get_volatility_band();
+        self.last_trade_price_in_ticks = source.get_last_trade_price();
+        self.last_large_fill_slot = source.get_last_large_fill_slot();
+        self.max_orders_per_side_per_trader = source.get_max_orders_per_side_per_trader();
+        self.fee_rounding_mode = source.get_fee_rounding_mode() as u64;
+        self.min_fee_quote_lots = source.get_min_fee_quote_lots();
+        self.taker_fee_bps = source.get_taker_fee_bps();
+        self.maker_rebate_bps = source.get_maker_rebate_bps();
+        self.fee_tiers_bps = source.get_fee_tiers_bps();
+        if let Some(price_accumulator) = source.get_twap() {
+            self.price_accumulator_enabled = 1;
+            self.price_accumulator = price_accumulator;
+        }
+        self.trader_volume_tracking_enabled = source.is_trader_volume_tracking_enabled() as u64;
+        self.taking_disabled = source.is_taking_disabled() as u64;
+        self.referral_fee_bps = source.get_referral_fee_bps();
+        self.min_taker_fill_base_lots = source.get_min_taker_fill_base_lots();
+        self.unclaimed_referral_quote_lot_fees = source.get_unclaimed_referral_fee_amount();
+        self.order_sequence_number = source.get_sequence_number();
+        self.collected_quote_lot_fees = source.get_collected_fee_amount();
+        self.unclaimed_quote_lot_fees = source.get_uncollected_fee_amount();
+
+        for (trader_id, trader_state) in source.get_registered_traders().iter() {
+            self.traders.insert(*trader_id, *trader_state)?;
+        }
+        for side in [Side::Bid, Side::Ask] {
+            for (order_id, resting_order) in source.get_book(side).iter() {
+                // Trader indices are node addresses in the trader tree, which are not preserved
+                // when the traders are inserted into the new tree
+                let trader_id = source.get_trader_id_from_index(resting_order.trader_index as u32);
+                let trader_index = self.get_trader_index(&trader_id)? as u64;
+                self.get_book_mut(side).insert(
+                    *order_id,
+                    FIFORestingOrder {
+                        trader_index,
+                        ..*resting_order
+                    },
+                )?;
+            }
+        }
+        Some(())
+    }
 }
 
 impl<
@@ -554,7 +1452,8 @@ impl<
         const BIDS_SIZE: usize,
         const ASKS_SIZE: usize,
         const NUM_SEATS: usize,
-    > FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
+        const PRO_RATA: bool,
+    > FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS, PRO_RATA>
 {
     pub fn new(
         tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
@@ -568,6 +1467,14 @@ impl<
         market
     }
 
+    /// Iterates over the price levels on `side`, starting from the top of the book
+    pub fn get_levels(
+        &self,
+        side: Side,
+    ) -> LevelIterator<'_, impl Iterator<Item = (&FIFOOrderId, &FIFORestingOrder)> + '_> {
+        LevelIterator::new(self.get_book(side).iter())
+    }
+
     fn initialize(&mut self) {
         self.bids.initialize();
         self.asks.initialize();
@@ -602,12 +1509,64 @@ impl<
     }
 
     #[inline]
-    /// Round up the fee to the nearest adjusted quote lot
-    fn compute_fee(&self, size_in_adjusted_quote_lots: AdjustedQuoteLots) -> AdjustedQuoteLots {
-        AdjustedQuoteLots::new(
-            ((size_in_adjusted_quote_lots.as_u128() * self.taker_fee_bps as u128 + 10000 - 1)
-                / 10000) as u64,
+    /// Taker fee paid by the trader at `trader_index`. Unregistered takers (`u32::MAX`) pay the
+    /// default taker fee.
+    fn get_taker_fee_bps_for_trader_index(&self, trader_index: u32) -> u64 {
+        if trader_index == u32::MAX {
+            return self.taker_fee_bps;
+        }
+        match self.get_trader_state_from_index(trader_index).fee_tier as usize {
+            0 => self.taker_fee_bps,
+            fee_tier => self
+                .fee_tiers_bps
+                .get(fee_tier - 1)
+                .copied()
+                .unwrap_or(self.taker_fee_bps),
+        }
+    }
+
+    #[inline]
+    /// Round the fee to an adjusted quote lot in the direction of the market's fee rounding mode
+    fn compute_fee(
+        &self,
+        size_in_adjusted_quote_lots: AdjustedQuoteLots,
+        taker_fee_bps: u64,
+    ) -> AdjustedQuoteLots {
+        let fee_numerator = size_in_adjusted_quote_lots.as_u128() * taker_fee_bps as u128;
+        AdjustedQuoteLots::from_u128(match self.get_fee_rounding_mode() {
+            FeeRoundingMode::Up => (fee_numerator + 10000 - 1) / 10000,
+            FeeRoundingMode::Down => fee_numerator / 10000,
+            FeeRoundingMode::Nearest => (fee_numerator + 10000 / 2) / 10000,
+        })
+    }
+
+    #[inline]
+    /// Converts a fee to quote lots, rounding in the direction of the market's fee rounding mode
+    fn round_fee_to_quote_lots(&self, fee_in_adjusted_quote_lots: AdjustedQuoteLots) -> QuoteLots {
+        let rounded_fee = match self.get_fee_rounding_mode() {
+            FeeRoundingMode::Up => self.round_adjusted_quote_lots_up(fee_in_adjusted_quote_lots),
+            FeeRoundingMode::Down => {
+                self.round_adjusted_quote_lots_down(fee_in_adjusted_quote_lots)
+            }
+            FeeRoundingMode::Nearest => self.round_adjusted_quote_lots_down(
+                fee_in_adjusted_quote_lots
+                    + AdjustedQuoteLots::new(self.base_lots_per_base_unit.as_u64() / 2),
+            ),
+        };
+        rounded_fee / self.base_lots_per_base_unit
+    }
+
+    #[inline]
+    /// Maker rebate on the given size at `rebate_bps`, rounded down to the nearest quote lot
+    fn compute_maker_rebate(
+        &self,
+        size_in_adjusted_quote_lots: AdjustedQuoteLots,
+        rebate_bps: u64,
+    ) -> QuoteLots {
+        AdjustedQuoteLots::from_u128(
+            size_in_adjusted_quote_lots.as_u128() * rebate_bps as u128 / 10000,
         )
+        .unchecked_div::<BaseLotsPerBaseUnit, QuoteLots>(self.base_lots_per_base_unit)
     }
 
     #[inline]
@@ -621,8 +1580,12 @@ impl<
     fn adjusted_quote_lot_budget_post_fee_adjustment_for_buys(
         &self,
         size_in_adjusted_quote_lots: AdjustedQuoteLots,
+        taker_fee_bps: u64,
     ) -> Option<AdjustedQuoteLots> {
-        let fee_adjustment = self.compute_fee(AdjustedQuoteLots::MAX).as_u128() + u64::MAX as u128;
+        let fee_adjustment = self
+            .compute_fee(AdjustedQuoteLots::MAX, taker_fee_bps)
+            .as_u128()
+            + u64::MAX as u128;
         // Return an option to catch truncation from downcasting to u64
         u64::try_from(size_in_adjusted_quote_lots.as_u128() * u64::MAX as u128 / fee_adjustment)
             .ok()
@@ -640,8 +1603,12 @@ impl<
     fn adjusted_quote_lot_budget_post_fee_adjustment_for_sells(
         &self,
         size_in_adjusted_quote_lots: AdjustedQuoteLots,
+        taker_fee_bps: u64,
     ) -> Option<AdjustedQuoteLots> {
-        let fee_adjustment = u64::MAX as u128 - self.compute_fee(AdjustedQuoteLots::MAX).as_u128();
+        let fee_adjustment = u64::MAX as u128
+            - self
+                .compute_fee(AdjustedQuoteLots::MAX, taker_fee_bps)
+                .as_u128();
         // Return an option to catch truncation from downcasting to u64
         u64::try_from(size_in_adjusted_quote_lots.as_u128() * u64::MAX as u128 / fee_adjustment)
             .ok()
@@ -671,6 +1638,27 @@ impl<
             * self.base_lots_per_base_unit
     }
 
+    /// Quote lots locked by a resting bid of `base_lots` at `price_in_ticks`, rounded down.
+    ///
+    /// Every site that locks or unlocks quote lots for a resting bid uses this function. When only
+    /// part of a bid is removed, the amount unlocked is the difference between the amounts locked
+    /// before and after the removal, so the amounts unlocked over the lifetime of an order always
+    /// add up to exactly the amount that was locked when it was placed. The product is computed
+    /// in u128 so that it cannot overflow.
+    #[inline(always)]
+    fn get_quote_lots_locked_for_bid(
+        tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+        base_lots_per_base_unit: BaseLotsPerBaseUnit,
+        price_in_ticks: Ticks,
+        base_lots: BaseLots,
+    ) -> QuoteLots {
+        let quote_lots = price_in_ticks.as_u128()
+            * tick_size_in_quote_lots_per_base_unit.as_u128()
+            * base_lots.as_u128()
+            / base_lots_per_base_unit.as_u128();
+        QuoteLots::new(u64::try_from(quote_lots).unwrap_or(u64::MAX))
+    }
+
     /// This function determines whether a PostOnly order crosses the book.
     /// If the order crosses the book, the function returns the price of the best unexpired order
     /// on the opposite side of the book in Ticks. Otherwise, it returns None.
@@ -680,6 +1668,7 @@ impl<
         num_ticks: Ticks,
         current_slot: u64,
         current_unix_timestamp_in_seconds: u64,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<Ticks> {
         loop {
@@ -700,6 +1689,7 @@ impl<
                             None,
                             true,
                             false,
+                            iceberg_reserves.as_deref_mut(),
                             record_event_fn,
                         )?;
                     } else {
@@ -710,6 +1700,8 @@ impl<
                     // This case should never occur in v1
                     phoenix_log!("WARNING: Empty order found in check_for_cross");
                     self.get_book_mut(side.opposite()).remove(&o_id);
+                    self.get_trader_state_from_index_mut(order.trader_index as u32)
+                        .decrement_open_orders(side.opposite());
                 }
             } else {
                 // Book is empty
@@ -740,11 +1732,7 @@ impl<
                 .min(trader_state.base_lots_free);
             trader_state.quote_lots_free -= quote_lots_free;
             trader_state.base_lots_free -= base_lots_free;
-            (
-                *trader_state == TraderState::default(),
-                quote_lots_free,
-                base_lots_free,
-            )
+            (trader_state.is_empty(), quote_lots_free, base_lots_free)
         };
         if is_empty && allow_seat_eviction {
             let trader_id = self.get_trader_id_from_index(trader_index);
@@ -756,54 +1744,18 @@ impl<
         ))
     }
 
-    fn place_order_inner(
-        &mut self,
-        trader_id: &MarketTraderId,
-        mut order_packet: OrderPacket,
-        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
-        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
-    ) -> Option<(Option<FIFOOrderId>, MatchingEngineResponse)> {
-        if self.order_sequence_number == 0 {
-            phoenix_log!("Market is uninitialized");
-            return None;
-        }
-        if self.order_sequence_number == u64::MAX >> 1 {
-            phoenix_log!("Sequence number exceeded maximum");
-            return None;
-        }
-
-        let side = order_packet.side();
-        match side {
-            Side::Bid => {
-                if order_packet.get_price_in_ticks() == Ticks::ZERO {
-                    phoenix_log!("Bid price is too low");
-                    return None;
-                }
-            }
-            Side::Ask => {
-                if !order_packet.is_take_only() {
-                    let tick_price = order_packet.get_price_in_ticks();
-                    order_packet.set_price_in_ticks(tick_price.max(Ticks::ONE));
-                }
-            }
-        }
-        let trader_index = if order_packet.is_take_only() {
-            self.get_trader_index(trader_id).unwrap_or(u32::MAX)
-        } else {
-            self.get_or_register_trader(trader_id)?
-        };
-
-        if order_packet.num_base_lots() == 0 && order_packet.num_quote_lots() == 0 {
-            phoenix_log!("Either num_base_lots or num_quote_lots must be nonzero");
-            return None;
-        }
-
-        // For IOC order types exactly one of num_quote_lots or num_base_lots needs to be specified.
+    /// Checks that exactly one of num_base_lots or num_quote_lots is set for IOC and FOK orders
+    fn check_immediate_order_params(order_packet: &OrderPacket) -> Result<(), PhoenixMatchError> {
         if let OrderPacket::ImmediateOrCancel {
             num_base_lots,
             num_quote_lots,
             ..
-        } = order_packet
+        }
+        | OrderPacket::FillOrKill {
+            num_base_lots,
+            num_quote_lots,
+            ..
+        } = *order_packet
         {
             if num_base_lots > BaseLots::ZERO && num_quote_lots > QuoteLots::ZERO
                 || num_base_lots == BaseLots::ZERO && num_quote_lots == QuoteLots::ZERO
@@ -816,154 +1768,233 @@ impl<
                     num_quote_lots,
                     num_base_lots
                 );
-                return None;
+                return Err(PhoenixMatchError::InvalidImmediateOrderParams);
             }
         }
+        Ok(())
+    }
 
-        let (current_slot, current_unix_timestamp) = get_clock_fn();
+    /// Checks that the market accepts orders that can take liquidity at the order's price
+    fn check_taking_allowed(
+        &self,
+        order_packet: &OrderPacket,
+        current_slot: u64,
+    ) -> Result<(), PhoenixMatchError> {
+        if !matches!(order_packet, OrderPacket::PostOnly { .. })
+            && self.is_in_volatility_cooldown(current_slot)
+            && !self
+                .is_within_volatility_band(order_packet.side(), order_packet.get_price_in_ticks())
+        {
+            phoenix_log!(
+                "Order price of {} ticks is outside of the volatility band around the last trade price of {} ticks",
+                order_packet.get_price_in_ticks(),
+                self.last_trade_price_in_ticks
+            );
+            return Err(PhoenixMatchError::OutsideVolatilityBand);
+        }
 
-        if order_packet.is_expired(current_slot, current_unix_timestamp) {
-            phoenix_log!("Order parameters include a last_valid_slot or last_valid_unix_timestamp_in_seconds in the past, skipping matching and posting");
-            // Do not fail the transaction if the order is expired, but do not place or match the order
-            return Some((None, MatchingEngineResponse::default()));
+        // Other orders that cross the book are rejected by `match_order`
+        if self.taking_disabled != 0 && order_packet.is_take_only() {
+            phoenix_log!("Taking liquidity is disabled on this market");
+            return Err(PhoenixMatchError::TakingDisabled);
         }
+        Ok(())
+    }
 
-        let (resting_order, mut matching_engine_response) = if let OrderPacket::PostOnly {
-            price_in_ticks,
-            reject_post_only,
-            ..
-        } = &mut order_packet
-        {
-            // Handle cases where PostOnly order would cross the book
-            if let Some(ticks) = self.check_for_cross(
-                side,
-                *price_in_ticks,
-                current_slot,
-                current_unix_timestamp,
-                record_event_fn,
-            ) {
-                if *reject_post_only {
-                    phoenix_log!("PostOnly order crosses the book - order rejected");
-                    return None;
-                } else {
-                    match side {
-                        Side::Bid => {
-                            if ticks <= Ticks::ONE {
-                                phoenix_log!("PostOnly order crosses the book and can not be amended to a valid price - order rejected");
-                                return None;
-                            }
-                            *price_in_ticks = ticks - Ticks::ONE;
-                        }
-                        Side::Ask => {
-                            *price_in_ticks = ticks + Ticks::ONE;
-                        }
-                    }
-                    phoenix_log!("PostOnly order crosses the book - order amended");
+    /// Returns the inflight order that matches `order_packet` for the trader at `trader_index`,
+    /// with its quote lot budget adjusted for the taker fee, and the taker fee in basis points
+    fn get_inflight_order(
+        &self,
+        order_packet: &OrderPacket,
+        trader_index: u32,
+        available_funds: Option<(BaseLots, QuoteLots)>,
+    ) -> (InflightOrder, u64) {
+        let side = order_packet.side();
+        let taker_fee_bps = self.get_taker_fee_bps_for_trader_index(trader_index);
+        let mut base_lot_budget = order_packet.base_lot_budget();
+        // Multiply the quote lot budget by the number of base lots per unit to get the number of
+        // adjusted quote lots (quote_lots * base_lots_per_base_unit). A budget that overflows
+        // the adjusted quote lots is treated as unbounded.
+        let mut quote_lot_budget = order_packet.quote_lot_budget();
+        // Place-partial orders never match more than the trader can pay for
+        if let Some((base_lots_available, quote_lots_available)) = available_funds {
+            match side {
+                Side::Bid => {
+                    quote_lot_budget = Some(
+                        quote_lot_budget
+                            .map_or(quote_lots_available, |q| q.min(quote_lots_available)),
+                    )
                 }
+                Side::Ask => base_lot_budget = base_lot_budget.min(base_lots_available),
             }
-
-            (
-                FIFORestingOrder::new(
-                    trader_index as u64,
-                    order_packet.num_base_lots(),
-                    order_packet.get_last_valid_slot(),
-                    order_packet.get_last_valid_unix_timestamp_in_seconds(),
-                ),
-                MatchingEngineResponse::default(),
-            )
-        } else {
-            let base_lot_budget = order_packet.base_lot_budget();
-            // Multiply the quote lot budget by the number of base lots per unit to get the number of
-            // adjusted quote lots (quote_lots * base_lots_per_base_unit)
-            let quote_lot_budget = order_packet.quote_lot_budget();
-            let adjusted_quote_lot_budget = match side {
-                // For buys, the adjusted quote lot budget is decreased by the max fee.
-                // This is because the fee is added to the quote lots spent after the matching is complete.
-                Side::Bid => quote_lot_budget.and_then(|quote_lot_budget| {
-                    self.adjusted_quote_lot_budget_post_fee_adjustment_for_buys(
-                        quote_lot_budget * self.base_lots_per_base_unit,
-                    )
-                }),
-                // For sells, the adjusted quote lot budget is increased by the max fee.
-                // This is because the fee is subtracted from the quote lot received after the matching is complete.
-                Side::Ask => quote_lot_budget.and_then(|quote_lot_budget| {
+        }
+        let adjusted_quote_lot_budget = match side {
+            // For buys, the adjusted quote lot budget is decreased by the max fee.
+            // This is because the fee is added to the quote lots spent after the matching is complete.
+            Side::Bid => quote_lot_budget.and_then(|quote_lot_budget| {
+                let fee_adjusted_budget = quote_lot_budget
+                    .checked_mul(self.base_lots_per_base_unit)
+                    .and_then(|adjusted_quote_lot_budget| {
+                        self.adjusted_quote_lot_budget_post_fee_adjustment_for_buys(
+                            adjusted_quote_lot_budget,
+                            taker_fee_bps,
+                        )
+                    })?;
+                // The fee is never less than the minimum fee, which is capped at the quote lots
+                // matched, so the budget must also leave room for the minimum fee
+                let min_fee_adjusted_budget = quote_lot_budget
+                    .saturating_sub(self.min_fee_quote_lots)
+                    .max(QuoteLots::new(quote_lot_budget.as_u64() / 2))
+                    * self.base_lots_per_base_unit;
+                Some(fee_adjusted_budget.min(min_fee_adjusted_budget))
+            }),
+            // For sells, the adjusted quote lot budget is increased by the max fee.
+            // This is because the fee is subtracted from the quote lot received after the matching is complete.
+            Side::Ask => quote_lot_budget
+                .and_then(|quote_lot_budget| {
+                    quote_lot_budget.checked_mul(self.base_lots_per_base_unit)
+                })
+                .and_then(|adjusted_quote_lot_budget| {
                     self.adjusted_quote_lot_budget_post_fee_adjustment_for_sells(
-                        quote_lot_budget * self.base_lots_per_base_unit,
+                        adjusted_quote_lot_budget,
+                        taker_fee_bps,
                     )
                 }),
-            }
-            .unwrap_or_else(|| AdjustedQuoteLots::new(u64::MAX));
-
-            let mut inflight_order = InflightOrder::new(
-                side,
-                order_packet.self_trade_behavior(),
-                order_packet.get_price_in_ticks(),
-                order_packet.match_limit(),
-                base_lot_budget,
-                adjusted_quote_lot_budget,
-                order_packet.get_last_valid_slot(),
-                order_packet.get_last_valid_unix_timestamp_in_seconds(),
-            );
-            let resting_order = self
-                .match_order(
-                    &mut inflight_order,
-                    trader_index,
-                    record_event_fn,
-                    current_slot,
-                    current_unix_timestamp,
-                )
-                .map_or_else(
-                    || {
-                        phoenix_log!("Encountered error matching order");
-                        None
-                    },
-                    Some,
-                )?;
-            // matched_adjusted_quote_lots is rounded down to the nearest tick for buys and up for
-            // sells to yield a whole number of matched_quote_lots.
-            let matched_quote_lots = match side {
-                // We add the quote_lot_fees to account for the fee being paid on a buy order
-                Side::Bid => {
-                    (self.round_adjusted_quote_lots_up(inflight_order.matched_adjusted_quote_lots)
-                        / self.base_lots_per_base_unit)
-                        + inflight_order.quote_lot_fees
-                }
-                // We subtract the quote_lot_fees to account for the fee being paid on a sell order
-                Side::Ask => {
-                    (self
-                        .round_adjusted_quote_lots_down(inflight_order.matched_adjusted_quote_lots)
-                        / self.base_lots_per_base_unit)
-                        - inflight_order.quote_lot_fees
-                }
-            };
-            let matching_engine_response = match side {
-                Side::Bid => MatchingEngineResponse::new_from_buy(
-                    matched_quote_lots,
-                    inflight_order.matched_base_lots,
-                ),
-                Side::Ask => MatchingEngineResponse::new_from_sell(
-                    inflight_order.matched_base_lots,
-                    matched_quote_lots,
-                ),
-            };
+        }
+        .unwrap_or_else(|| AdjustedQuoteLots::new(u64::MAX));
 
-            record_event_fn(MarketEvent::FillSummary {
-                client_order_id: order_packet.client_order_id(),
-                total_base_lots_filled: inflight_order.matched_base_lots,
-                total_quote_lots_filled: matched_quote_lots,
-                total_fee_in_quote_lots: inflight_order.quote_lot_fees,
-            });
+        // A trader's self trade behavior override takes precedence over the order packet
+        let self_trade_behavior = if trader_index != u32::MAX {
+            self.get_trader_state_from_index(trader_index)
+                .self_trade_behavior_override()
+        } else {
+            None
+        }
+        .unwrap_or_else(|| order_packet.self_trade_behavior());
 
-            (resting_order, matching_engine_response)
+        // The market's match limit caps the order's match limit, and applies to orders that
+        // do not set one
+        let match_limit = if self.max_match_limit != 0 {
+            order_packet.match_limit().min(self.max_match_limit)
+        } else {
+            order_packet.match_limit()
         };
 
-        let mut placed_order_id = None;
-
-        if let OrderPacket::ImmediateOrCancel {
+        let inflight_order = InflightOrder::new(
+            side,
+            self_trade_behavior,
+            order_packet.get_price_in_ticks(),
+            match_limit,
+            base_lot_budget,
+            adjusted_quote_lot_budget,
+            order_packet.get_last_valid_slot(),
+            order_packet.get_last_valid_unix_timestamp_in_seconds(),
+        );
+        (inflight_order, taker_fee_bps)
+    }
+
+    /// Minimum fee paid by a matched inflight order, which can never exceed the quote value of the
+    /// match. Orders that match nothing pay no fee.
+    fn get_min_fee_quote_lots(&self, inflight_order: &InflightOrder) -> QuoteLots {
+        if inflight_order.matched_base_lots == BaseLots::ZERO {
+            return QuoteLots::ZERO;
+        }
+        self.min_fee_quote_lots.min(
+            self.round_adjusted_quote_lots_down(inflight_order.matched_adjusted_quote_lots)
+                / self.base_lots_per_base_unit,
+        )
+    }
+
+    /// Returns the quote and base lots exchanged by a matched inflight order, including its fees
+    fn get_matching_engine_response(
+        &self,
+        side: Side,
+        inflight_order: &InflightOrder,
+    ) -> MatchingEngineResponse {
+        // matched_adjusted_quote_lots is rounded down to the nearest tick for buys and up for
+        // sells to yield a whole number of matched_quote_lots.
+        let matched_quote_lots = match side {
+            // We add the quote_lot_fees to account for the fee being paid on a buy order
+            Side::Bid => {
+                (self.round_adjusted_quote_lots_up(inflight_order.matched_adjusted_quote_lots)
+                    / self.base_lots_per_base_unit)
+                    + inflight_order.quote_lot_fees
+            }
+            // We subtract the quote_lot_fees to account for the fee being paid on a sell order
+            Side::Ask => {
+                (self.round_adjusted_quote_lots_down(inflight_order.matched_adjusted_quote_lots)
+                    / self.base_lots_per_base_unit)
+                    - inflight_order.quote_lot_fees
+            }
+        };
+        match side {
+            Side::Bid => MatchingEngineResponse::new_from_buy(
+                matched_quote_lots,
+                inflight_order.matched_base_lots,
+            ),
+            Side::Ask => MatchingEngineResponse::new_from_sell(
+                inflight_order.matched_base_lots,
+                matched_quote_lots,
+            ),
+        }
+    }
+
+    /// Checks the fills of an IOC or FOK order against the order's fill requirements and the
+    /// minimum taker fill. Orders that fail these checks are voided. `quote_lot_budget_filled` is
+    /// whether the matched order could not pay for another base lot (see
+    /// `InflightOrder::quote_lot_budget_filled`).
+    fn check_immediate_order_fills(
+        &self,
+        order_packet: &OrderPacket,
+        quote_lot_budget_filled: bool,
+        matching_engine_response: &MatchingEngineResponse,
+        min_taker_fill_base_lots: BaseLots,
+    ) -> Result<(), PhoenixMatchError> {
+        // Orders that only take liquidity are voided if they match a positive amount below the
+        // market's minimum, so tiny fills cannot be used to consume the queue position of makers
+        if order_packet.is_take_only() {
+            let matched_base_lots = matching_engine_response.num_base_lots();
+            if matched_base_lots > BaseLots::ZERO && matched_base_lots < min_taker_fill_base_lots {
+                phoenix_log!(
+                    "Taker order matched {} base lots, less than the minimum of {}",
+                    matched_base_lots,
+                    min_taker_fill_base_lots
+                );
+                return Err(PhoenixMatchError::TakerFillBelowMinimum);
+            }
+        }
+
+        if let OrderPacket::FillOrKill {
+            num_base_lots,
+            num_quote_lots,
+            ..
+        } = *order_packet
+        {
+            // For FOK orders, the order is voided unless the entire base or quote budget is filled.
+            // The quote lot budget is adjusted for fees and matched in whole base lots, so it is
+            // filled once the rest of the budget cannot pay for another base lot.
+            if matching_engine_response.num_base_lots() < num_base_lots
+                || num_quote_lots > QuoteLots::ZERO && !quote_lot_budget_filled
+            {
+                phoenix_log!(
+                    "FOK order failed to fill completely.
+                        num_base_lots: {},
+                        num_quote_lots: {},
+                        matched_base_lots: {},
+                        matched_quote_lots: {}",
+                    num_base_lots,
+                    num_quote_lots,
+                    matching_engine_response.num_base_lots(),
+                    matching_engine_response.num_quote_lots(),
+                );
+                return Err(PhoenixMatchError::FillOrKillNotFilled);
+            }
+        } else if let OrderPacket::ImmediateOrCancel {
             min_base_lots_to_fill,
             min_quote_lots_to_fill,
+            max_avg_price_in_ticks,
             ..
-        } = order_packet
+        } = *order_packet
         {
             // For IOC orders, if the order's minimum fill requirements are not met, then
             // the order is voided
@@ -981,19 +2012,401 @@ impl<
                     matching_engine_response.num_base_lots(),
                     matching_engine_response.num_quote_lots(),
                 );
-                return None;
+                return Err(PhoenixMatchError::ImmediateOrCancelMinimumNotMet);
+            }
+            // If a worst average price is set, the average fill price (including fees) must be
+            // no worse than it, otherwise the order is voided
+            if let Some(max_avg_price_in_ticks) = max_avg_price_in_ticks {
+                let matched_base_lots = matching_engine_response.num_base_lots();
+                if matched_base_lots > BaseLots::ZERO {
+                    let matched_adjusted_quote_lots =
+                        matching_engine_response.num_quote_lots().as_u128()
+                            * self.base_lots_per_base_unit.as_u128();
+                    let limit_adjusted_quote_lots = max_avg_price_in_ticks.as_u128()
+                        * self.tick_size_in_quote_lots_per_base_unit.as_u128()
+                        * matched_base_lots.as_u128();
+                    let limit_exceeded = match order_packet.side() {
+                        Side::Bid => matched_adjusted_quote_lots > limit_adjusted_quote_lots,
+                        Side::Ask => matched_adjusted_quote_lots < limit_adjusted_quote_lots,
+                    };
+                    if limit_exceeded {
+                        phoenix_log!(
+                            "IOC order failed to meet its worst average price.
+                                max_avg_price_in_ticks: {},
+                                matched_base_lots: {},
+                                matched_quote_lots: {}",
+                            max_avg_price_in_ticks,
+                            matched_base_lots,
+                            matching_engine_response.num_quote_lots(),
+                        );
+                        return Err(PhoenixMatchError::ImmediateOrCancelAveragePriceNotMet);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `token_budget` is the number of base and quote lots that the trader can deposit in addition
+    /// to their free funds. It is only used to size orders with `place_partial` set, and is treated
+    /// as unbounded if it is not provided. Take-only orders that match fewer than
+    /// `min_taker_fill_base_lots` base lots are voided.
+    #[allow(clippy::too_many_arguments)]
+    fn place_order_inner(
+        &mut self,
+        trader_id: &MarketTraderId,
+        mut order_packet: OrderPacket,
+        token_budget: Option<(BaseLots, QuoteLots)>,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        min_taker_fill_base_lots: BaseLots,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<FIFOOrderId>, MatchingEngineResponse), PhoenixMatchError> {
+        if self.order_sequence_number == 0 {
+            phoenix_log!("Market is uninitialized");
+            return Err(PhoenixMatchError::MarketUninitialized);
+        }
+        if self.order_sequence_number == MAX_ORDER_SEQUENCE_NUMBER {
+            phoenix_log!("Sequence number exceeded maximum");
+            return Err(PhoenixMatchError::SequenceNumberExhausted);
+        }
+
+        let side = order_packet.side();
+        match side {
+            Side::Bid => {
+                if order_packet.get_price_in_ticks() == Ticks::ZERO {
+                    phoenix_log!("Bid price is too low");
+                    return Err(PhoenixMatchError::InvalidBidPrice);
+                }
+            }
+            Side::Ask => {
+                if !order_packet.is_take_only() {
+                    let tick_price = order_packet.get_price_in_ticks();
+                    order_packet.set_price_in_ticks(tick_price.max(Ticks::ONE));
+                }
+            }
+        }
+        // Quote-denominated limit orders are converted into limit orders for the number of base
+        // lots that the quote lots are worth at the limit price, rounded down
+        if let OrderPacket::LimitByQuote {
+            side,
+            price_in_ticks,
+            num_quote_lots,
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot,
+            last_valid_unix_timestamp_in_seconds,
+            valid_for_slots,
+        } = order_packet
+        {
+            let quote_lots_per_base_unit = self
+                .tick_size_in_quote_lots_per_base_unit
+                .checked_mul(price_in_ticks)
+                .ok_or_else(|| {
+                    phoenix_log!("Order price overflows the quote lots per base unit");
+                    PhoenixMatchError::InvalidOrderPrice
+                })?;
+            let num_base_lots = num_quote_lots.as_u128() * self.base_lots_per_base_unit.as_u128()
+                / quote_lots_per_base_unit.as_u128();
+            order_packet = OrderPacket::Limit {
+                side,
+                price_in_ticks,
+                num_base_lots: BaseLots::new(num_base_lots.min(u64::MAX as u128) as u64),
+                self_trade_behavior,
+                match_limit,
+                client_order_id,
+                use_only_deposited_funds,
+                last_valid_slot,
+                last_valid_unix_timestamp_in_seconds,
+                fail_silently_on_insufficient_funds: false,
+                reduce_only: false,
+                place_partial: false,
+                valid_for_slots,
+            };
+        }
+
+        let trader_index = if order_packet.is_take_only() {
+            self.get_trader_index(trader_id).unwrap_or(u32::MAX)
+        } else {
+            self.get_or_register_trader(trader_id).ok_or_else(|| {
+                phoenix_log!("Failed to register trader");
+                PhoenixMatchError::TraderRegistrationFailed
+            })?
+        };
+
+        if order_packet.num_base_lots() == 0 && order_packet.num_quote_lots() == 0 {
+            phoenix_log!("Either num_base_lots or num_quote_lots must be nonzero");
+            return Err(PhoenixMatchError::EmptyOrder);
+        }
+
+        Self::check_immediate_order_params(&order_packet)?;
+
+        if let Some(display_base_lots) = order_packet.display_base_lots() {
+            if display_base_lots == BaseLots::ZERO
+                || display_base_lots > order_packet.num_base_lots()
+            {
+                phoenix_log!(
+                    "Invalid iceberg params. display_base_lots: {}, num_base_lots: {}",
+                    display_base_lots,
+                    order_packet.num_base_lots()
+                );
+                return Err(PhoenixMatchError::InvalidIcebergParams);
+            }
+            if iceberg_reserves.is_none() {
+                phoenix_log!("Iceberg orders are not enabled on this market");
+                return Err(PhoenixMatchError::IcebergOrdersDisabled);
+            }
+        }
+
+        let (current_slot, current_unix_timestamp) = get_clock_fn();
+        if trader_index != u32::MAX {
+            self.get_trader_state_from_index_mut(trader_index)
+                .last_active_slot = current_slot;
+        }
+
+        if let Some(valid_for_slots) = order_packet.get_valid_for_slots() {
+            if order_packet.get_last_valid_slot().is_some() {
+                phoenix_log!("Order cannot set both last_valid_slot and valid_for_slots");
+                return Err(PhoenixMatchError::InvalidOrderExpiration);
+            }
+            // The relative expiration is converted to an absolute slot before any other checks
+            order_packet
+                .set_last_valid_slot(Some(current_slot.saturating_add(valid_for_slots as u64)));
+            order_packet.set_valid_for_slots(None);
+        }
+
+        if order_packet.is_expired(current_slot, current_unix_timestamp) {
+            phoenix_log!("Order parameters include a last_valid_slot or last_valid_unix_timestamp_in_seconds in the past, skipping matching and posting");
+            // Do not fail the transaction if the order is expired, but do not place or match the order
+            return Ok((None, MatchingEngineResponse::default()));
+        }
+
+        if self.max_order_lifetime_slots != 0 && !order_packet.is_take_only() {
+            let max_last_valid_slot = current_slot.saturating_add(self.max_order_lifetime_slots);
+            match order_packet.get_last_valid_slot() {
+                Some(last_valid_slot) if last_valid_slot > max_last_valid_slot => {
+                    phoenix_log!(
+                        "Order last_valid_slot {} exceeds the maximum of {}",
+                        last_valid_slot,
+                        max_last_valid_slot
+                    );
+                    return Err(PhoenixMatchError::OrderLifetimeTooLong);
+                }
+                Some(_) => {}
+                None => {
+                    phoenix_log!(
+                        "Order has no last_valid_slot, clamping to the maximum of {}",
+                        max_last_valid_slot
+                    );
+                    order_packet.set_last_valid_slot(Some(max_last_valid_slot));
+                }
+            }
+        }
+
+        self.check_taking_allowed(&order_packet, current_slot)?;
+
+        // Reduce-only orders are only capped here. The resting portion locks the inventory it was
+        // capped to, so it stays within the trader's inventory without being trimmed on later touches.
+        if order_packet.is_reduce_only() {
+            let max_base_lots = self.get_reduce_only_base_lot_limit(
+                trader_index,
+                side,
+                order_packet.get_price_in_ticks(),
+            );
+            if max_base_lots == BaseLots::ZERO {
+                phoenix_log!(
+                    "Reduce-only order has no inventory to trade, skipping matching and posting"
+                );
+                return Ok((None, MatchingEngineResponse::default()));
+            }
+            if let OrderPacket::Limit { num_base_lots, .. } = &mut order_packet {
+                *num_base_lots = (*num_base_lots).min(max_base_lots);
             }
+        }
+
+        // The base and quote lots that a place-partial order can spend, including its fills
+        let available_funds = if order_packet.is_place_partial() {
+            let (base_lots_to_deposit, quote_lots_to_deposit) =
+                if order_packet.no_deposit_or_withdrawal() {
+                    (BaseLots::ZERO, QuoteLots::ZERO)
+                } else {
+                    token_budget.unwrap_or((BaseLots::MAX, QuoteLots::MAX))
+                };
+            let trader_state = self.get_trader_state_from_index(trader_index);
+            Some((
+                trader_state
+                    .base_lots_free
+                    .checked_add(base_lots_to_deposit)
+                    .unwrap_or(BaseLots::MAX),
+                trader_state
+                    .quote_lots_free
+                    .checked_add(quote_lots_to_deposit)
+                    .unwrap_or(QuoteLots::MAX),
+            ))
         } else {
+            None
+        };
+
+        let mut deferred_fill_summary = None;
+        let (mut resting_order, mut matching_engine_response, quote_lot_budget_filled) =
+            if let OrderPacket::PostOnly {
+                price_in_ticks,
+                reject_post_only,
+                max_tick_adjustment,
+                require_top_of_book,
+                ..
+            } = &mut order_packet
+            {
+                // Handle cases where PostOnly order would cross the book
+                if let Some(ticks) = self.check_for_cross(
+                    side,
+                    *price_in_ticks,
+                    current_slot,
+                    current_unix_timestamp,
+                    iceberg_reserves.as_deref_mut(),
+                    record_event_fn,
+                ) {
+                    if *reject_post_only {
+                        phoenix_log!("PostOnly order crosses the book - order rejected");
+                        return Err(PhoenixMatchError::PostOnlyCrossesBook);
+                    } else {
+                        let amended_price_in_ticks = match side {
+                            Side::Bid => {
+                                if ticks <= Ticks::ONE {
+                                    phoenix_log!("PostOnly order crosses the book and can not be amended to a valid price - order rejected");
+                                    return Err(PhoenixMatchError::PostOnlyInvalidPrice);
+                                }
+                                ticks - Ticks::ONE
+                            }
+                            Side::Ask => ticks + Ticks::ONE,
+                        };
+                        if let Some(max_tick_adjustment) = max_tick_adjustment {
+                            let tick_adjustment = match side {
+                                Side::Bid => *price_in_ticks - amended_price_in_ticks,
+                                Side::Ask => amended_price_in_ticks - *price_in_ticks,
+                            };
+                            if tick_adjustment > Ticks::new(*max_tick_adjustment) {
+                                phoenix_log!(
+                                "PostOnly order would be amended by {} ticks, exceeding the maximum of {} - order rejected",
+                                tick_adjustment,
+                                max_tick_adjustment
+                            );
+                                return Err(PhoenixMatchError::PostOnlyInvalidPrice);
+                            }
+                        }
+                        *price_in_ticks = amended_price_in_ticks;
+                        phoenix_log!("PostOnly order crosses the book - order amended");
+                    }
+                }
+
+                if *require_top_of_book {
+                    if let Some((best_order_id, _)) = self.get_book(side).get_min() {
+                        let is_top_of_book = match side {
+                            Side::Bid => *price_in_ticks >= best_order_id.price_in_ticks,
+                            Side::Ask => *price_in_ticks <= best_order_id.price_in_ticks,
+                        };
+                        if !is_top_of_book {
+                            phoenix_log!(
+                            "PostOnly order at {} ticks is behind the best price of {} ticks - order rejected",
+                            price_in_ticks,
+                            best_order_id.price_in_ticks
+                        );
+                            return Err(PhoenixMatchError::PostOnlyNotTopOfBook);
+                        }
+                    }
+                }
+
+                (
+                    FIFORestingOrder::new(
+                        trader_index as u64,
+                        order_packet.num_base_lots(),
+                        order_packet.get_last_valid_slot(),
+                        order_packet.get_last_valid_unix_timestamp_in_seconds(),
+                    ),
+                    MatchingEngineResponse::default(),
+                    false,
+                )
+            } else {
+                let (mut inflight_order, taker_fee_bps) =
+                    self.get_inflight_order(&order_packet, trader_index, available_funds);
+                let unclaimed_fees_before_match = self.unclaimed_quote_lot_fees;
+                let resting_order = self.match_order(
+                    &mut inflight_order,
+                    trader_index,
+                    taker_fee_bps,
+                    iceberg_reserves.as_deref_mut(),
+                    record_event_fn,
+                    current_slot,
+                    current_unix_timestamp,
+                )?;
+                // Trades that match any base lots pay at least the minimum fee
+                let min_fee_quote_lots = self.get_min_fee_quote_lots(&inflight_order);
+                if min_fee_quote_lots > inflight_order.quote_lot_fees {
+                    self.unclaimed_quote_lot_fees +=
+                        min_fee_quote_lots - inflight_order.quote_lot_fees;
+                    inflight_order.quote_lot_fees = min_fee_quote_lots;
+                }
+                // The referrer's share is taken from what the market keeps after maker rebates, so the
+                // market, the makers and the referrer never receive more than the fee paid by the taker
+                if order_packet.get_referrer().is_some() && self.referral_fee_bps > 0 {
+                    let market_fees = self.unclaimed_quote_lot_fees - unclaimed_fees_before_match;
+                    let referral_fees = QuoteLots::new(
+                        (inflight_order.quote_lot_fees.as_u128() * self.referral_fee_bps as u128
+                            / 10_000) as u64,
+                    )
+                    .min(market_fees);
+                    self.unclaimed_quote_lot_fees -= referral_fees;
+                    self.unclaimed_referral_quote_lot_fees += referral_fees;
+                }
+                let matching_engine_response =
+                    self.get_matching_engine_response(side, &inflight_order);
+                let matched_quote_lots = matching_engine_response.num_quote_lots();
+
+                let fill_summary = MarketEvent::FillSummary {
+                    taker_id: *trader_id,
+                    client_order_id: order_packet.client_order_id(),
+                    total_base_lots_filled: inflight_order.matched_base_lots,
+                    total_quote_lots_filled: matched_quote_lots,
+                    total_fee_in_quote_lots: inflight_order.quote_lot_fees,
+                };
+
+                // FOK orders only record the fill summary once the order is known to be fully filled
+                if let OrderPacket::FillOrKill { .. } = order_packet {
+                    deferred_fill_summary = Some(fill_summary);
+                } else {
+                    record_event_fn(fill_summary);
+                }
+
+                (
+                    resting_order,
+                    matching_engine_response,
+                    inflight_order
+                        .quote_lot_budget_filled(self.tick_size_in_quote_lots_per_base_unit),
+                )
+            };
+
+        let mut placed_order_id = None;
+
+        self.check_immediate_order_fills(
+            &order_packet,
+            quote_lot_budget_filled,
+            &matching_engine_response,
+            min_taker_fill_base_lots,
+        )?;
+
+        if let OrderPacket::FillOrKill { .. } = order_packet {
+            // The fill summary is only recorded once the order is known to be fully filled
+            if let Some(fill_summary) = deferred_fill_summary {
+                record_event_fn(fill_summary);
+            }
+        } else if !order_packet.is_take_only() {
             let price_in_ticks = order_packet.get_price_in_ticks();
-            let (order_id, book_full) = match side {
-                Side::Bid => (
-                    FIFOOrderId::new(price_in_ticks, !self.order_sequence_number),
-                    self.bids.len() == self.bids.capacity(),
-                ),
-                Side::Ask => (
-                    FIFOOrderId::new(price_in_ticks, self.order_sequence_number),
-                    self.asks.len() == self.asks.capacity(),
-                ),
+            let order_id = self.get_next_order_id(side, price_in_ticks);
+            let book_full = match side {
+                Side::Bid => self.bids.len() == self.bids.capacity(),
+                Side::Ask => self.asks.len() == self.asks.capacity(),
             };
 
             let limit_order_crosses = if matches!(order_packet, OrderPacket::PostOnly { .. }) {
@@ -1019,36 +2432,126 @@ impl<
                 }
             };
 
+            if let Some((base_lots_available, quote_lots_available)) = available_funds {
+                let max_base_lots = self.get_place_partial_base_lot_limit(
+                    side,
+                    price_in_ticks,
+                    base_lots_available.saturating_sub(matching_engine_response.num_base_lots()),
+                    quote_lots_available.saturating_sub(matching_engine_response.num_quote_lots()),
+                );
+                if resting_order.num_base_lots > max_base_lots {
+                    phoenix_log!(
+                        "Order size of {} base lots is reduced to the affordable size of {} base lots",
+                        resting_order.num_base_lots,
+                        max_base_lots
+                    );
+                    resting_order.num_base_lots = if max_base_lots < self.min_base_lots_per_order {
+                        BaseLots::ZERO
+                    } else {
+                        max_base_lots
+                    };
+                }
+            }
+
+            // Only the display size of an iceberg order is shown on the book, the rest of the order
+            // is held in its reserve
+            let display_base_lots = order_packet.display_base_lots().unwrap_or_default();
+            let mut reserve_base_lots = BaseLots::ZERO;
+            if display_base_lots > BaseLots::ZERO {
+                let visible_base_lots = resting_order.num_base_lots.min(display_base_lots);
+                reserve_base_lots = resting_order.num_base_lots - visible_base_lots;
+                resting_order.num_base_lots = visible_base_lots;
+            }
+
             // Only place an order if there is more size to place and the limit order doesn't cross the book
             if resting_order.num_base_lots > BaseLots::ZERO && !limit_order_crosses {
+                // Dust orders are rejected before they are inserted into the book
+                if resting_order.num_base_lots < self.min_base_lots_per_order {
+                    phoenix_log!(
+                        "Order size of {} base lots is below the market minimum of {} base lots",
+                        resting_order.num_base_lots,
+                        self.min_base_lots_per_order
+                    );
+                    return Err(PhoenixMatchError::OrderBelowMinimumSize);
+                }
+                if self.max_orders_per_side_per_trader != 0 {
+                    let num_open_orders = self
+                        .get_trader_state_from_index(trader_index)
+                        .num_open_orders(side) as u64;
+                    if num_open_orders >= self.max_orders_per_side_per_trader {
+                        phoenix_log!(
+                            "Trader already has {} open orders on this side, the market maximum is {}",
+                            num_open_orders,
+                            self.max_orders_per_side_per_trader
+                        );
+                        return Err(PhoenixMatchError::TooManyOpenOrders);
+                    }
+                }
+                // These constants need to be copied because we mutably borrow below
+                let tick_size_in_quote_lots_per_base_unit =
+                    self.tick_size_in_quote_lots_per_base_unit;
+                let base_lots_per_base_unit = self.base_lots_per_base_unit;
+                let quote_lots_locked_for_size = |base_lots| {
+                    Self::get_quote_lots_locked_for_bid(
+                        tick_size_in_quote_lots_per_base_unit,
+                        base_lots_per_base_unit,
+                        order_id.price_in_ticks,
+                        base_lots,
+                    )
+                };
+                let base_lots_to_lock = resting_order.num_base_lots + reserve_base_lots;
+                if reserve_base_lots > BaseLots::ZERO {
+                    let quote_lots_locked_for_reserve = match side {
+                        Side::Bid => {
+                            quote_lots_locked_for_size(base_lots_to_lock)
+                                - quote_lots_locked_for_size(resting_order.num_base_lots)
+                        }
+                        Side::Ask => QuoteLots::ZERO,
+                    };
+                    // This is always Some because iceberg orders are rejected without reserves
+                    if let Some(iceberg_reserves) = iceberg_reserves.as_deref_mut() {
+                        iceberg_reserves
+                            .insert(
+                                order_id,
+                                IcebergReserve::new(
+                                    trader_index as u64,
+                                    reserve_base_lots,
+                                    display_base_lots,
+                                    quote_lots_locked_for_reserve,
+                                    order_packet.client_order_id(),
+                                ),
+                            )
+                            .ok_or_else(|| {
+                                phoenix_log!("Failed to insert the iceberg reserve");
+                                PhoenixMatchError::BookInsertionFailed
+                            })?;
+                    }
+                }
                 // Evict order from the book if it is at capacity
                 placed_order_id = Some(order_id);
                 if book_full {
                     phoenix_log!("Book is full. Evicting order");
-                    self.evict_least_aggressive_order(side, record_event_fn, &order_id);
+                    self.evict_least_aggressive_order(
+                        side,
+                        iceberg_reserves.as_deref_mut(),
+                        record_event_fn,
+                        &order_id,
+                    );
                 }
                 // Add new order to the book
                 self.get_book_mut(side)
                     .insert(order_id, resting_order)
-                    .map_or_else(
-                        || {
-                            phoenix_log!("Failed to insert order into book");
-                            None
-                        },
-                        Some,
-                    )?;
-                // These constants need to be copied because we mutably borrow below
-                let tick_size_in_quote_lots_per_base_unit =
-                    self.tick_size_in_quote_lots_per_base_unit;
-                let base_lots_per_base_unit = self.base_lots_per_base_unit;
+                    .ok_or_else(|| {
+                        phoenix_log!("Failed to insert order into book");
+                        PhoenixMatchError::BookInsertionFailed
+                    })?;
                 let trader_state = self.get_trader_state_from_index_mut(trader_index);
-                // Update trader state and matching engine response accordingly
+                trader_state.increment_open_orders(side);
+                // Update trader state and matching engine response accordingly. The reserve of an
+                // iceberg order is locked along with its visible size.
                 match side {
                     Side::Bid => {
-                        let quote_lots_to_lock = (tick_size_in_quote_lots_per_base_unit
-                            * order_id.price_in_ticks
-                            * resting_order.num_base_lots)
-                            / base_lots_per_base_unit;
+                        let quote_lots_to_lock = quote_lots_locked_for_size(base_lots_to_lock);
                         let quote_lots_free_to_use =
                             quote_lots_to_lock.min(trader_state.quote_lots_free);
                         trader_state.use_free_quote_lots(quote_lots_free_to_use);
@@ -1058,10 +2561,10 @@ impl<
                     }
                     Side::Ask => {
                         let base_lots_free_to_use =
-                            resting_order.num_base_lots.min(trader_state.base_lots_free);
+                            base_lots_to_lock.min(trader_state.base_lots_free);
                         trader_state.use_free_base_lots(base_lots_free_to_use);
-                        trader_state.lock_base_lots(resting_order.num_base_lots);
-                        matching_engine_response.post_base_lots(resting_order.num_base_lots);
+                        trader_state.lock_base_lots(base_lots_to_lock);
+                        matching_engine_response.post_base_lots(base_lots_to_lock);
                         matching_engine_response.use_free_base_lots(base_lots_free_to_use);
                     }
                 }
@@ -1130,23 +2633,109 @@ impl<
                 // Check if trader has enough deposited funds to process the order
                 if !matching_engine_response.verify_no_deposit() {
                     phoenix_log!("Trader does not have enough deposited funds to process order");
-                    return None;
+                    return Err(PhoenixMatchError::InsufficientDepositedFunds);
                 }
 
                 // Check that the matching engine response does not withdraw any base or quote lots
                 if !matching_engine_response.verify_no_withdrawal() {
                     phoenix_log!("Matching engine response withdraws base or quote lots");
-                    return None;
+                    return Err(PhoenixMatchError::UnexpectedWithdrawal);
                 }
             }
         }
 
-        Some((placed_order_id, matching_engine_response))
+        Ok((placed_order_id, matching_engine_response))
+    }
+
+    /// Returns the maximum number of base lots that a place-partial order can rest on the book at
+    /// `price_in_ticks` with the funds that remain after matching. Asks are limited by the base
+    /// lots remaining, and bids are limited by the quote lots needed to lock the order.
+    fn get_place_partial_base_lot_limit(
+        &self,
+        side: Side,
+        price_in_ticks: Ticks,
+        base_lots_remaining: BaseLots,
+        quote_lots_remaining: QuoteLots,
+    ) -> BaseLots {
+        match side {
+            Side::Ask => base_lots_remaining,
+            Side::Bid => match quote_lots_remaining.checked_mul(self.base_lots_per_base_unit) {
+                Some(adjusted_quote_lots_remaining) => adjusted_quote_lots_remaining
+                    .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(
+                    price_in_ticks * self.tick_size_in_quote_lots_per_base_unit,
+                ),
+                // The remaining quote lots can lock any order that fits in the book
+                None => BaseLots::MAX,
+            },
+        }
+    }
+
+    /// Returns true if an order matched more than `volatility_fill_threshold_base_lots` less than
+    /// `volatility_cooldown_slots` slots before `current_slot`. A `last_large_fill_slot` of zero
+    /// means that no large fill has been recorded.
+    fn is_in_volatility_cooldown(&self, current_slot: u64) -> bool {
+        self.volatility_fill_threshold_base_lots > BaseLots::ZERO
+            && self.last_large_fill_slot != 0
+            && current_slot
+                < self
+                    .last_large_fill_slot
+                    .saturating_add(self.volatility_cooldown_slots)
+    }
+
+    /// Returns true if an order on `side` at `price_in_ticks` does not trade more than
+    /// `volatility_band_bps` away from the last trade price. Bids are bounded above and asks are
+    /// bounded below.
+    fn is_within_volatility_band(&self, side: Side, price_in_ticks: Ticks) -> bool {
+        let last_trade_price = self.last_trade_price_in_ticks.as_u128();
+        let band = last_trade_price * self.volatility_band_bps as u128 / 10000;
+        match side {
+            Side::Bid => price_in_ticks.as_u128() <= last_trade_price + band,
+            Side::Ask => price_in_ticks.as_u128() >= last_trade_price.saturating_sub(band),
+        }
+    }
+
+    /// Returns the maximum number of base lots that a reduce-only order can trade without requiring
+    /// new funds. Asks are limited by the trader's free base lots, and bids are limited by the
+    /// number of base lots that the trader's free quote lots can buy at `price_in_ticks` after the
+    /// maximum taker fee.
+    fn get_reduce_only_base_lot_limit(
+        &self,
+        trader_index: u32,
+        side: Side,
+        price_in_ticks: Ticks,
+    ) -> BaseLots {
+        let trader_state = self.get_trader_state_from_index(trader_index);
+        match side {
+            Side::Ask => trader_state.base_lots_free,
+            Side::Bid => {
+                let taker_fee_bps = self.get_taker_fee_bps_for_trader_index(trader_index);
+                let adjusted_quote_lots_free = trader_state
+                    .quote_lots_free
+                    .checked_mul(self.base_lots_per_base_unit)
+                    .unwrap_or(AdjustedQuoteLots::MAX);
+                let adjusted_quote_lots_free = self
+                    .adjusted_quote_lot_budget_post_fee_adjustment_for_buys(
+                        adjusted_quote_lots_free,
+                        taker_fee_bps,
+                    )
+                    .unwrap_or_default();
+                let quote_lots_per_base_unit =
+                    self.tick_size_in_quote_lots_per_base_unit * price_in_ticks;
+                if quote_lots_per_base_unit == QuoteLotsPerBaseUnit::ZERO {
+                    return BaseLots::ZERO;
+                }
+                BaseLots::new(adjusted_quote_lots_free.as_u64() / quote_lots_per_base_unit.as_u64())
+            }
+        }
     }
 
+    /// Removes the least aggressive order on `side` of the book to make room for the order with id
+    /// `placed_order_id`. The evicted maker's funds are unlocked, including the reserve of an
+    /// iceberg order.
     fn evict_least_aggressive_order(
         &mut self,
         side: Side,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
         placed_order_id: &FIFOOrderId,
     ) -> Option<FIFORestingOrder> {
@@ -1167,6 +2756,7 @@ impl<
                 order_sequence_number: fifo_order_id.order_sequence_number,
                 price_in_ticks: fifo_order_id.price_in_ticks,
                 base_lots_evicted: resting_order.num_base_lots,
+                evicting_order_id: *placed_order_id,
             });
             (fifo_order_id, resting_order)
         };
@@ -1174,218 +2764,344 @@ impl<
         let tick_size_in_quote_lots_per_base_unit = self.tick_size_in_quote_lots_per_base_unit;
         let base_lots_per_base_unit = self.base_lots_per_base_unit;
         let trader_state = self.get_trader_state_from_index_mut(resting_order.trader_index as u32);
+        trader_state.decrement_open_orders(side);
         match side {
             Side::Bid => {
-                let quote_lots_to_unlock = (order_id.price_in_ticks
-                    * tick_size_in_quote_lots_per_base_unit
-                    * resting_order.num_base_lots)
-                    / base_lots_per_base_unit;
+                let quote_lots_to_unlock = Self::get_quote_lots_locked_for_bid(
+                    tick_size_in_quote_lots_per_base_unit,
+                    base_lots_per_base_unit,
+                    order_id.price_in_ticks,
+                    resting_order.num_base_lots,
+                );
                 trader_state.unlock_quote_lots(quote_lots_to_unlock);
             }
             Side::Ask => trader_state.unlock_base_lots(resting_order.num_base_lots),
         }
+        self.release_iceberg_reserve(&order_id, side, iceberg_reserves);
         Some(resting_order)
     }
 
-    fn match_order(
-        &mut self,
+    /// Computes the fills of an inflight order against the book without modifying the market. Each
+    /// step of the match is resolved by `next_match_step`, like in `match_order`, and applied to a
+    /// read-only view of the opposite side of the book, so no events are recorded and no trader
+    /// state is updated. Expired orders and self trades are resolved in the same way they would be
+    /// on the book.
+    ///
+    /// Iceberg orders are only replenished in the simulation if `iceberg_reserves` holds the
+    /// market's iceberg reserves.
+    ///
+    /// Returns the base lots and adjusted quote lots that would be matched, before fees. Nothing is
+    /// matched if the order would be aborted by a self trade. The budget of `inflight_order` is
+    /// depleted by the matches, as in `match_order`.
+    pub fn peek_match(
+        &self,
         inflight_order: &mut InflightOrder,
         current_trader_index: u32,
-        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
-        current_slot: u64,
-        current_unix_timestamp: u64,
-    ) -> Option<FIFORestingOrder> {
+        iceberg_reserves: Option<&dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        clock: (u64, u64),
+    ) -> (BaseLots, AdjustedQuoteLots) {
+        let (current_slot, current_unix_timestamp) = clock;
+        let side = inflight_order.side.opposite();
+        let mut book = BookView::new(self.get_book(side));
+        let mut total_matched_base_lots = BaseLots::ZERO;
         let mut total_matched_adjusted_quote_lots = AdjustedQuoteLots::ZERO;
+        // Replenished iceberg orders are given sequence numbers as if they were placed on the book
+        let mut order_sequence_number = self.order_sequence_number;
+        // The reserves are not modified, so the reserves of replenished orders are tracked here
+        let mut replenished_reserves: Vec<(FIFOOrderId, IcebergReserve)> = vec![];
+        let mut pro_rata_allocations: Vec<(FIFOOrderId, BaseLots)> = vec![];
         while inflight_order.in_progress() {
-            // Find the first order on the opposite side of the book that matches the inflight order.
-            let (
-                trader_index,
-                order_id,
-                num_base_lots_quoted,
-                last_valid_slot,
-                last_valid_unix_timestamp_in_seconds,
-            ) = {
-                let book = self.get_book_mut(inflight_order.side.opposite());
-                // Look at the top of the book to compare the book's price to the order's price
-                let (
-                    crossed,
+            let match_step = self.next_match_step(
+                &book,
+                inflight_order,
+                &mut pro_rata_allocations,
+                current_trader_index,
+                current_slot,
+                current_unix_timestamp,
+            );
+            match match_step {
+                None => break,
+                Some(MatchStep::Remove { order_id, .. }) => book.reduce(order_id, BaseLots::ZERO),
+                Some(MatchStep::Abort) => return (BaseLots::ZERO, AdjustedQuoteLots::ZERO),
+                Some(MatchStep::SelfTrade {
                     order_id,
-                    FIFORestingOrder {
-                        trader_index,
-                        num_base_lots: num_base_lots_quoted,
-                        last_valid_slot,
-                        last_valid_unix_timestamp_in_seconds,
-                    },
-                ) = if let Some((o_id, quote)) = book.get_min() {
-                    (
-                        match inflight_order.side {
-                            Side::Bid => o_id.price_in_ticks <= inflight_order.limit_price_in_ticks,
-                            Side::Ask => o_id.price_in_ticks >= inflight_order.limit_price_in_ticks,
-                        },
-                        o_id,
-                        quote,
-                    )
-                } else {
-                    phoenix_log!("Book is empty");
-                    break;
-                };
-                // If the order no longer crosses the limit price (based on limit_price_in_ticks), stop matching
-                if !crossed {
-                    break;
+                    resting_order,
+                    base_lots_removed,
+                }) => {
+                    if let Some(base_lots_removed) = base_lots_removed {
+                        book.reduce(order_id, resting_order.num_base_lots - base_lots_removed);
+                    }
                 }
-                if num_base_lots_quoted == BaseLots::ZERO {
-                    // This block is entered if we encounter tombstoned orders during the matching process
-                    // (Should never trigger in v1)
-                    book.remove(&order_id)?;
-                    // The tombstone should count as part of the match limit
+                Some(MatchStep::Fill {
+                    order_id,
+                    resting_order,
+                    matched_base_lots,
+                    matched_adjusted_quote_lots,
+                    order_completed,
+                }) => {
+                    book.reduce(order_id, resting_order.num_base_lots - matched_base_lots);
+                    total_matched_base_lots += matched_base_lots;
+                    total_matched_adjusted_quote_lots += matched_adjusted_quote_lots;
+                    if !order_completed {
+                        continue;
+                    }
+                    let iceberg_reserve = match replenished_reserves
+                        .iter()
+                        .position(|(reserve_order_id, _)| *reserve_order_id == order_id)
+                    {
+                        Some(i) => Some(replenished_reserves.swap_remove(i).1),
+                        None => iceberg_reserves
+                            .and_then(|iceberg_reserves| iceberg_reserves.get(&order_id).copied()),
+                    };
+                    if let Some(iceberg_reserve) = iceberg_reserve {
+                        let replenished_order_id = FIFOOrderId::new_for_side(
+                            side,
+                            order_id.price_in_ticks,
+                            order_sequence_number,
+                        );
+                        let num_base_lots = iceberg_reserve
+                            .reserve_base_lots
+                            .min(iceberg_reserve.display_base_lots);
+                        book.insert(
+                            replenished_order_id,
+                            FIFORestingOrder {
+                                num_base_lots,
+                                ..resting_order
+                            },
+                        );
+                        order_sequence_number += 1;
+                        if num_base_lots < iceberg_reserve.reserve_base_lots {
+                            replenished_reserves.push((
+                                replenished_order_id,
+                                IcebergReserve {
+                                    reserve_base_lots: iceberg_reserve.reserve_base_lots
+                                        - num_base_lots,
+                                    ..iceberg_reserve
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        (total_matched_base_lots, total_matched_adjusted_quote_lots)
+    }
+
+    /// Resolves the next step of matching the inflight order against the opposite side of the
+    /// book, as seen through `book`. The inflight order's budget and match limit are updated for
+    /// the step, but the book is not modified, so the caller applies the step. This is the matching
+    /// logic shared by `match_order` and `peek_match`.
+    ///
+    /// Returns None once no resting order crosses the inflight order.
+    fn next_match_step(
+        &self,
+        book: &BookView,
+        inflight_order: &mut InflightOrder,
+        pro_rata_allocations: &mut Vec<(FIFOOrderId, BaseLots)>,
+        current_trader_index: u32,
+        current_slot: u64,
+        current_unix_timestamp: u64,
+    ) -> Option<MatchStep> {
+        if PRO_RATA && pro_rata_allocations.is_empty() {
+            *pro_rata_allocations = self.get_pro_rata_allocations(book, inflight_order);
+        }
+        let pro_rata_allocation = pro_rata_allocations.pop();
+        // Find the first order on the opposite side of the book that matches the inflight order.
+        let (order_id, resting_order) =
+            Self::get_next_crossed_order(book, inflight_order, pro_rata_allocation)?;
+        let num_base_lots_quoted = resting_order.num_base_lots;
+
+        // Tombstoned and expired orders are removed from the book and count towards the match
+        // limit. The remaining budget may change, so the level is reallocated on the next step.
+        if num_base_lots_quoted == BaseLots::ZERO
+            || resting_order.is_expired(current_slot, current_unix_timestamp)
+        {
+            inflight_order.match_limit -= 1;
+            pro_rata_allocations.clear();
+            return Some(MatchStep::Remove {
+                order_id,
+                resting_order,
+            });
+        }
+
+        // Handle self trade
+        if resting_order.trader_index == current_trader_index as u64 {
+            pro_rata_allocations.clear();
+            let base_lots_removed = match inflight_order.self_trade_behavior {
+                SelfTradeBehavior::Abort => return Some(MatchStep::Abort),
+                // The resting order is cancelled
+                SelfTradeBehavior::CancelProvide => {
                     inflight_order.match_limit -= 1;
-                    continue;
+                    Some(num_base_lots_quoted)
                 }
-                (
-                    trader_index,
-                    order_id,
+                SelfTradeBehavior::DecrementTake => Some(self.process_decrement_take(
+                    inflight_order,
+                    &order_id,
                     num_base_lots_quoted,
-                    last_valid_slot,
-                    last_valid_unix_timestamp_in_seconds,
-                )
+                )),
+                // The resting order is left on the book and the remainder of the crossing order
+                // is cancelled
+                SelfTradeBehavior::CancelTake => {
+                    inflight_order.should_terminate = true;
+                    None
+                }
             };
+            return Some(MatchStep::SelfTrade {
+                order_id,
+                resting_order,
+                base_lots_removed,
+            });
+        }
 
-            // This block is entered if the order has expired. The order is removed from the book and
-            // the match limit is decremented.
-            if (last_valid_slot != 0 && last_valid_slot < current_slot)
-                || (last_valid_unix_timestamp_in_seconds != 0
-                    && last_valid_unix_timestamp_in_seconds < current_unix_timestamp)
-            {
-                self.reduce_order_inner(
-                    trader_index as u32,
-                    &order_id,
-                    inflight_order.side.opposite(),
-                    None,
-                    true,
-                    false,
-                    record_event_fn,
-                )?;
-                inflight_order.match_limit -= 1;
-                continue;
-            }
+        let (matched_base_lots, matched_adjusted_quote_lots, order_completed) = self.compute_match(
+            inflight_order,
+            &order_id,
+            num_base_lots_quoted,
+            pro_rata_allocation,
+        );
+        // Partial fills outside of a pro-rata allocation exhaust the order's budget, so we
+        // make sure that the loop terminates
+        if !order_completed && pro_rata_allocation.is_none() {
+            inflight_order.should_terminate = true;
+        }
+        // Deplete the inflight order's budget by the amount matched
+        inflight_order.process_match(matched_adjusted_quote_lots, matched_base_lots);
+        inflight_order.last_matched_price_in_ticks = order_id.price_in_ticks;
+        Some(MatchStep::Fill {
+            order_id,
+            resting_order,
+            matched_base_lots,
+            matched_adjusted_quote_lots,
+            order_completed,
+        })
+    }
 
-            // Handle self trade
-            if trader_index == current_trader_index as u64 {
-                match inflight_order.self_trade_behavior {
-                    SelfTradeBehavior::Abort => return None,
-                    SelfTradeBehavior::CancelProvide => {
-                        // This block is entered if the self trade behavior for the crossing order is
-                        // CancelProvide
-                        //
-                        // We cancel the order from the book and free up the locked quote_lots or base_lots, but
-                        // we do not claim them as part of the match
+    fn match_order(
+        &mut self,
+        inflight_order: &mut InflightOrder,
+        current_trader_index: u32,
+        taker_fee_bps: u64,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        current_slot: u64,
+        current_unix_timestamp: u64,
+    ) -> Result<FIFORestingOrder, PhoenixMatchError> {
+        let mut total_matched_adjusted_quote_lots = AdjustedQuoteLots::ZERO;
+        let mut total_rebate_in_quote_lots = QuoteLots::ZERO;
+        let mut num_fills = 0;
+        // Pending pro-rata fills at the current price level, stored in reverse time priority
+        let mut pro_rata_allocations: Vec<(FIFOOrderId, BaseLots)> = vec![];
+        let side = inflight_order.side.opposite();
+        while inflight_order.in_progress() {
+            let match_step = self.next_match_step(
+                &BookView::new(self.get_book(side)),
+                inflight_order,
+                &mut pro_rata_allocations,
+                current_trader_index,
+                current_slot,
+                current_unix_timestamp,
+            );
+            let (
+                order_id,
+                resting_order,
+                matched_base_lots,
+                matched_adjusted_quote_lots,
+                order_completed,
+            ) = match match_step {
+                None => break,
+                Some(MatchStep::Remove {
+                    order_id,
+                    resting_order,
+                }) => {
+                    if resting_order.num_base_lots == BaseLots::ZERO {
+                        // This block is entered if we encounter tombstoned orders during the
+                        // matching process (Should never trigger in v1)
+                        self.get_book_mut(side)
+                            .remove(&order_id)
+                            .ok_or(PhoenixMatchError::MatchOrderFailed)?;
+                        self.get_trader_state_from_index_mut(resting_order.trader_index as u32)
+                            .decrement_open_orders(side);
+                    } else {
+                        // Expired orders are removed from the book and their funds are unlocked
                         self.reduce_order_inner(
-                            current_trader_index,
+                            resting_order.trader_index as u32,
                             &order_id,
-                            inflight_order.side.opposite(),
+                            side,
                             None,
+                            true,
                             false,
-                            false,
+                            iceberg_reserves.as_deref_mut(),
                             record_event_fn,
-                        )?;
-                        inflight_order.match_limit -= 1;
+                        )
+                        .ok_or(PhoenixMatchError::MatchOrderFailed)?;
                     }
-                    SelfTradeBehavior::DecrementTake => {
-                        let base_lots_removed = inflight_order
-                            .base_lot_budget
-                            .min(
-                                inflight_order
-                                    .adjusted_quote_lot_budget
-                                    .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(
-                                        order_id.price_in_ticks
-                                            * self.tick_size_in_quote_lots_per_base_unit,
-                                    ),
-                            )
-                            .min(num_base_lots_quoted);
-
+                    continue;
+                }
+                Some(MatchStep::Abort) => {
+                    phoenix_log!("Encountered error matching order");
+                    return Err(PhoenixMatchError::MatchOrderFailed);
+                }
+                Some(MatchStep::SelfTrade {
+                    order_id,
+                    base_lots_removed,
+                    ..
+                }) => {
+                    // We cancel the removed part of the trader's resting order and free up its
+                    // locked quote_lots or base_lots, but we do not claim them as part of the match
+                    if let Some(base_lots_removed) = base_lots_removed {
                         self.reduce_order_inner(
                             current_trader_index,
                             &order_id,
-                            inflight_order.side.opposite(),
+                            side,
                             Some(base_lots_removed),
                             false,
                             false,
+                            iceberg_reserves.as_deref_mut(),
                             record_event_fn,
-                        )?;
-                        // In the case that the self trade behavior is DecrementTake, we decrement the
-                        // the base lot and adjusted quote lot budgets accordingly
-                        inflight_order.base_lot_budget = inflight_order
-                            .base_lot_budget
-                            .saturating_sub(base_lots_removed);
-                        inflight_order.adjusted_quote_lot_budget =
-                            inflight_order.adjusted_quote_lot_budget.saturating_sub(
-                                self.tick_size_in_quote_lots_per_base_unit
-                                    * order_id.price_in_ticks
-                                    * base_lots_removed,
-                            );
-                        // Self trades will count towards the match limit
-                        inflight_order.match_limit -= 1;
-                        // If base_lots_removed < num_base_lots_quoted, then the order budget must be fully
-                        // exhausted
-                        inflight_order.should_terminate = base_lots_removed < num_base_lots_quoted;
+                        )
+                        .ok_or(PhoenixMatchError::MatchOrderFailed)?;
                     }
+                    continue;
                 }
-                continue;
-            }
-
-            let num_adjusted_quote_lots_quoted = order_id.price_in_ticks
-                * self.tick_size_in_quote_lots_per_base_unit
-                * num_base_lots_quoted;
-
-            let (matched_base_lots, matched_adjusted_quote_lots, order_remaining_base_lots) = {
-                // This constant needs to be copied because we mutably borrow below
-                let tick_size_in_quote_lots_per_base_unit =
-                    self.tick_size_in_quote_lots_per_base_unit;
-
-                let book = self.get_book_mut(inflight_order.side.opposite());
+                Some(MatchStep::Fill {
+                    order_id,
+                    resting_order,
+                    matched_base_lots,
+                    matched_adjusted_quote_lots,
+                    order_completed,
+                }) => (
+                    order_id,
+                    resting_order,
+                    matched_base_lots,
+                    matched_adjusted_quote_lots,
+                    order_completed,
+                ),
+            };
+            let trader_index = resting_order.trader_index;
+            let num_base_lots_quoted = resting_order.num_base_lots;
 
-                // Check if the inflight order's budget is exhausted
-                let has_remaining_adjusted_quote_lots =
-                    num_adjusted_quote_lots_quoted <= inflight_order.adjusted_quote_lot_budget;
-                let has_remaining_base_lots =
-                    num_base_lots_quoted <= inflight_order.base_lot_budget;
+            // Taking is checked against the market's flag rather than its status, so a status
+            // change never re-enables it
+            if self.taking_disabled != 0 {
+                phoenix_log!("Taking liquidity is disabled on this market");
+                return Err(PhoenixMatchError::TakingDisabled);
+            }
 
-                if has_remaining_base_lots && has_remaining_adjusted_quote_lots {
-                    // If there is remaining budget, we match the entire book order
-                    book.remove(&order_id)?;
-                    (
-                        num_base_lots_quoted,
-                        num_adjusted_quote_lots_quoted,
-                        BaseLots::ZERO,
-                    )
+            let order_remaining_base_lots = {
+                let book = self.get_book_mut(side);
+                if order_completed {
+                    book.remove(&order_id)
+                        .ok_or(PhoenixMatchError::MatchOrderFailed)?;
+                    BaseLots::ZERO
                 } else {
-                    // If the order's budget is exhausted, we match as much as we can
-                    let base_lots_to_remove = inflight_order.base_lot_budget.min(
-                        inflight_order
-                            .adjusted_quote_lot_budget
-                            .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(
-                                order_id.price_in_ticks * tick_size_in_quote_lots_per_base_unit,
-                            ),
-                    );
-                    let adjusted_quote_lots_to_remove = order_id.price_in_ticks
-                        * tick_size_in_quote_lots_per_base_unit
-                        * base_lots_to_remove;
-                    let matched_order = book.get_mut(&order_id)?;
-                    matched_order.num_base_lots -= base_lots_to_remove;
-                    // If this clause is reached, we make ensure that the loop terminates
-                    // as the order has been fully filled
-                    inflight_order.should_terminate = true;
-                    (
-                        base_lots_to_remove,
-                        adjusted_quote_lots_to_remove,
-                        matched_order.num_base_lots,
-                    )
+                    let matched_order = book
+                        .get_mut(&order_id)
+                        .ok_or(PhoenixMatchError::MatchOrderFailed)?;
+                    matched_order.num_base_lots -= matched_base_lots;
+                    matched_order.num_base_lots
                 }
             };
 
-            // Deplete the inflight order's budget by the amount matched
-            inflight_order.process_match(matched_adjusted_quote_lots, matched_base_lots);
-
             // Increment the matched adjusted quote lots for fee calculation
             total_matched_adjusted_quote_lots += matched_adjusted_quote_lots;
 
@@ -1399,12 +3115,61 @@ impl<
                     base_lots_filled: matched_base_lots,
                     base_lots_remaining: order_remaining_base_lots,
                 });
+                self.last_trade_price_in_ticks = order_id.price_in_ticks;
+                if self.price_accumulator_enabled != 0 {
+                    self.price_accumulator.record_fill(
+                        order_id.price_in_ticks,
+                        matched_base_lots,
+                        current_slot,
+                    );
+                }
             } else if !inflight_order.should_terminate {
                 phoenix_log!(
                     "WARNING: should_terminate should always be true if matched_base_lots is zero"
                 );
             }
 
+            if order_completed {
+                record_event_fn(MarketEvent::<MarketTraderId>::OrderComplete {
+                    maker_id: self.get_trader_id_from_index(trader_index as u32),
+                    order_sequence_number: order_id.order_sequence_number,
+                });
+                match iceberg_reserves.as_deref_mut() {
+                    Some(iceberg_reserves) if iceberg_reserves.get(&order_id).is_some() => {
+                        self.replenish_iceberg_order(
+                            side,
+                            &order_id,
+                            &resting_order,
+                            iceberg_reserves,
+                            record_event_fn,
+                        )
+                        .ok_or(PhoenixMatchError::MatchOrderFailed)?;
+                    }
+                    _ => self
+                        .get_trader_state_from_index_mut(trader_index as u32)
+                        .decrement_open_orders(side),
+                }
+            }
+
+            if self.maker_rebate_bps > 0 && matched_base_lots > BaseLots::ZERO {
+                // The rebate on each fill is capped at the taker's fee rate and rounded down, so
+                // the rebates of a match never add up to more than the taker fee it collects
+                let rebate_in_quote_lots = self.compute_maker_rebate(
+                    matched_adjusted_quote_lots,
+                    self.maker_rebate_bps.min(taker_fee_bps),
+                );
+                if rebate_in_quote_lots > QuoteLots::ZERO {
+                    total_rebate_in_quote_lots += rebate_in_quote_lots;
+                    self.get_trader_state_from_index_mut(trader_index as u32)
+                        .deposit_free_quote_lots(rebate_in_quote_lots);
+                    record_event_fn(MarketEvent::<MarketTraderId>::Rebate {
+                        maker_id: self.get_trader_id_from_index(trader_index as u32),
+                        rebate_in_quote_lots,
+                    });
+                }
+            }
+
+            let tick_size_in_quote_lots_per_base_unit = self.tick_size_in_quote_lots_per_base_unit;
             let base_lots_per_base_unit = self.base_lots_per_base_unit;
             // Update the maker's state to reflect the match
             let trader_state = self.get_trader_state_from_index_mut(trader_index as u32);
@@ -1413,19 +3178,51 @@ impl<
                     matched_base_lots,
                     matched_adjusted_quote_lots / base_lots_per_base_unit,
                 ),
-                Side::Ask => trader_state.process_limit_buy(
-                    matched_adjusted_quote_lots / base_lots_per_base_unit,
-                    matched_base_lots,
-                ),
+                Side::Ask => {
+                    let quote_lots_paid = matched_adjusted_quote_lots / base_lots_per_base_unit;
+                    trader_state.process_limit_buy(quote_lots_paid, matched_base_lots);
+                    // Any rounding difference between the quote lots released by the fill and the
+                    // quote lots paid for it is returned to the maker
+                    let quote_lots_locked_for_size = |base_lots| {
+                        Self::get_quote_lots_locked_for_bid(
+                            tick_size_in_quote_lots_per_base_unit,
+                            base_lots_per_base_unit,
+                            order_id.price_in_ticks,
+                            base_lots,
+                        )
+                    };
+                    let quote_lots_released = quote_lots_locked_for_size(num_base_lots_quoted)
+                        - quote_lots_locked_for_size(num_base_lots_quoted - matched_base_lots);
+                    trader_state.unlock_quote_lots(quote_lots_released - quote_lots_paid);
+                }
+            }
+            if self.trader_volume_tracking_enabled != 0 && matched_base_lots != BaseLots::ZERO {
+                num_fills += 1;
+                self.get_trader_state_from_index_mut(trader_index as u32)
+                    .record_trade(matched_adjusted_quote_lots / base_lots_per_base_unit, 1);
             }
         }
+        if self.volatility_fill_threshold_base_lots > BaseLots::ZERO
+            && inflight_order.matched_base_lots > self.volatility_fill_threshold_base_lots
+        {
+            self.last_large_fill_slot = current_slot;
+        }
+        // Unregistered takers have no trader state to record volume on
+        if num_fills > 0 && current_trader_index != u32::MAX {
+            let quote_lots_traded =
+                total_matched_adjusted_quote_lots / self.base_lots_per_base_unit;
+            self.get_trader_state_from_index_mut(current_trader_index)
+                .record_trade(quote_lots_traded, num_fills);
+        }
         // Fees are updated based on the total amount matched
-        inflight_order.quote_lot_fees = self
-            .round_adjusted_quote_lots_up(self.compute_fee(total_matched_adjusted_quote_lots))
-            / self.base_lots_per_base_unit;
+        inflight_order.quote_lot_fees = self.round_fee_to_quote_lots(
+            self.compute_fee(total_matched_adjusted_quote_lots, taker_fee_bps),
+        );
+        // Maker rebates are paid out of the taker fees of this match
         self.unclaimed_quote_lot_fees += inflight_order.quote_lot_fees;
+        self.unclaimed_quote_lot_fees -= total_rebate_in_quote_lots;
 
-        Some(FIFORestingOrder::new(
+        Ok(FIFORestingOrder::new(
             current_trader_index as u64,
             inflight_order.base_lot_budget,
             inflight_order.last_valid_slot,
@@ -1433,10 +3230,259 @@ impl<
         ))
     }
 
+    /// Replaces a filled iceberg order with an order for the next part of its reserve. The new
+    /// order has a new sequence number, so it joins the back of the queue. Its funds are already
+    /// locked, so they are moved from the reserve to the new order.
+    fn replenish_iceberg_order(
+        &mut self,
+        side: Side,
+        order_id: &FIFOOrderId,
+        filled_order: &FIFORestingOrder,
+        iceberg_reserves: &mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<()> {
+        let iceberg_reserve = iceberg_reserves.remove(order_id)?;
+        let replenished_order = FIFORestingOrder {
+            num_base_lots: iceberg_reserve
+                .reserve_base_lots
+                .min(iceberg_reserve.display_base_lots),
+            ..*filled_order
+        };
+        let replenished_order_id = self.get_next_order_id(side, order_id.price_in_ticks);
+        self.get_book_mut(side)
+            .insert(replenished_order_id, replenished_order)?;
+        self.order_sequence_number += 1;
+
+        let quote_lots_locked = match side {
+            Side::Bid => {
+                iceberg_reserve.quote_lots_locked
+                    - Self::get_quote_lots_locked_for_bid(
+                        self.tick_size_in_quote_lots_per_base_unit,
+                        self.base_lots_per_base_unit,
+                        order_id.price_in_ticks,
+                        replenished_order.num_base_lots,
+                    )
+            }
+            Side::Ask => QuoteLots::ZERO,
+        };
+        let reserve_base_lots = iceberg_reserve.reserve_base_lots - replenished_order.num_base_lots;
+        if reserve_base_lots > BaseLots::ZERO {
+            iceberg_reserves.insert(
+                replenished_order_id,
+                IcebergReserve {
+                    reserve_base_lots,
+                    quote_lots_locked,
+                    ..iceberg_reserve
+                },
+            )?;
+        } else {
+            // Any quote lots left over from rounding are released with the last of the reserve
+            self.get_trader_state_from_index_mut(filled_order.trader_index as u32)
+                .unlock_quote_lots(quote_lots_locked);
+        }
+
+        record_event_fn(MarketEvent::<MarketTraderId>::Place {
+            order_sequence_number: replenished_order_id.order_sequence_number,
+            client_order_id: iceberg_reserve.client_order_id(),
+            price_in_ticks: replenished_order_id.price_in_ticks,
+            base_lots_placed: replenished_order.num_base_lots,
+        });
+        if replenished_order.last_valid_slot != 0
+            || replenished_order.last_valid_unix_timestamp_in_seconds != 0
+        {
+            record_event_fn(MarketEvent::<MarketTraderId>::TimeInForce {
+                order_sequence_number: replenished_order_id.order_sequence_number,
+                last_valid_slot: replenished_order.last_valid_slot,
+                last_valid_unix_timestamp_in_seconds: replenished_order
+                    .last_valid_unix_timestamp_in_seconds,
+            });
+        }
+        Some(())
+    }
+
+    /// Returns the next resting order to match against the inflight order, or None if the book is
+    /// empty or the best order does not cross the order's limit price. Pro-rata matching selects
+    /// the order of the current allocation instead of the top of the book.
+    fn get_next_crossed_order(
+        book: &BookView,
+        inflight_order: &InflightOrder,
+        pro_rata_allocation: Option<(FIFOOrderId, BaseLots)>,
+    ) -> Option<(FIFOOrderId, FIFORestingOrder)> {
+        let next_order = match pro_rata_allocation {
+            Some((o_id, _)) => book.get(&o_id),
+            None => book.orders().next(),
+        };
+        match next_order {
+            // If the order no longer crosses the limit price (based on limit_price_in_ticks), stop matching
+            Some((o_id, resting_order)) if inflight_order.crosses(o_id.price_in_ticks) => {
+                Some((o_id, resting_order))
+            }
+            Some(_) => None,
+            None => {
+                phoenix_log!("Book is empty");
+                None
+            }
+        }
+    }
+
+    /// Returns the base lots and adjusted quote lots matched when the inflight order crosses a
+    /// resting order of `num_base_lots_quoted`, and whether the resting order is completely filled.
+    /// A pro-rata match is bounded by the resting order's allocation.
+    fn compute_match(
+        &self,
+        inflight_order: &InflightOrder,
+        order_id: &FIFOOrderId,
+        num_base_lots_quoted: BaseLots,
+        pro_rata_allocation: Option<(FIFOOrderId, BaseLots)>,
+    ) -> (BaseLots, AdjustedQuoteLots, bool) {
+        let quote_lots_per_base_unit =
+            order_id.price_in_ticks * self.tick_size_in_quote_lots_per_base_unit;
+        let num_adjusted_quote_lots_quoted = quote_lots_per_base_unit * num_base_lots_quoted;
+
+        // Check if the inflight order's budget is exhausted
+        let has_remaining_adjusted_quote_lots =
+            num_adjusted_quote_lots_quoted <= inflight_order.adjusted_quote_lot_budget;
+        let has_remaining_base_lots = num_base_lots_quoted <= inflight_order.base_lot_budget;
+        let allocation_fills_order = match pro_rata_allocation {
+            Some((_, allocation)) => allocation >= num_base_lots_quoted,
+            None => true,
+        };
+        if has_remaining_base_lots && has_remaining_adjusted_quote_lots && allocation_fills_order {
+            // If there is remaining budget, we match the entire book order
+            return (num_base_lots_quoted, num_adjusted_quote_lots_quoted, true);
+        }
+
+        let matched_base_lots = match pro_rata_allocation {
+            // The allocation is bounded by the order's budget, so matching continues with the next
+            // order at this level
+            Some((_, allocation)) => allocation,
+            // If the order's budget is exhausted, we match as much as we can
+            None => inflight_order.base_lot_budget.min(
+                inflight_order
+                    .adjusted_quote_lot_budget
+                    .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(quote_lots_per_base_unit),
+            ),
+        };
+        (
+            matched_base_lots,
+            quote_lots_per_base_unit * matched_base_lots,
+            false,
+        )
+    }
+
+    /// Handles a self trade with the DecrementTake behavior. The budget of the inflight order is
+    /// decremented by the size it would have matched against the trader's own resting order, and the
+    /// number of base lots to remove from the resting order is returned.
+    fn process_decrement_take(
+        &self,
+        inflight_order: &mut InflightOrder,
+        order_id: &FIFOOrderId,
+        num_base_lots_quoted: BaseLots,
+    ) -> BaseLots {
+        let base_lots_removed = inflight_order
+            .base_lot_budget
+            .min(
+                inflight_order
+                    .adjusted_quote_lot_budget
+                    .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(
+                        order_id.price_in_ticks * self.tick_size_in_quote_lots_per_base_unit,
+                    ),
+            )
+            .min(num_base_lots_quoted);
+
+        // In the case that the self trade behavior is DecrementTake, we decrement the
+        // the base lot and adjusted quote lot budgets accordingly
+        inflight_order.base_lot_budget = inflight_order
+            .base_lot_budget
+            .saturating_sub(base_lots_removed);
+        inflight_order.adjusted_quote_lot_budget =
+            inflight_order.adjusted_quote_lot_budget.saturating_sub(
+                self.tick_size_in_quote_lots_per_base_unit
+                    * order_id.price_in_ticks
+                    * base_lots_removed,
+            );
+        // Self trades will count towards the match limit
+        inflight_order.match_limit -= 1;
+        // If base_lots_removed < num_base_lots_quoted, then the order budget must be fully
+        // exhausted
+        inflight_order.should_terminate = base_lots_removed < num_base_lots_quoted;
+        base_lots_removed
+    }
+
+    /// Splits the inflight order's budget across the orders at the best crossed price level, in
+    /// proportion to their size. Returns an empty vector if the level can be filled entirely or the
+    /// budget cannot fill a single base lot, in which case the orders are matched in time priority.
+    ///
+    /// The level is read in a single pass over the book that visits at most `match_limit` orders,
+    /// since the inflight order cannot match more orders than that.
+    fn get_pro_rata_allocations(
+        &self,
+        book: &BookView,
+        inflight_order: &InflightOrder,
+    ) -> Vec<(FIFOOrderId, BaseLots)> {
+        let price_in_ticks = match book.orders().next() {
+            Some((o_id, _)) => o_id.price_in_ticks,
+            None => return vec![],
+        };
+        if !inflight_order.crosses(price_in_ticks) {
+            return vec![];
+        }
+        let fillable_base_lots = inflight_order.base_lot_budget.min(
+            inflight_order
+                .adjusted_quote_lot_budget
+                .unchecked_div::<QuoteLotsPerBaseUnit, BaseLots>(
+                    price_in_ticks * self.tick_size_in_quote_lots_per_base_unit,
+                ),
+        );
+        if fillable_base_lots == BaseLots::ZERO {
+            return vec![];
+        }
+
+        // Each entry holds the size of the order until it is replaced by the order's allocation
+        let mut level_base_lots = BaseLots::ZERO;
+        let mut allocations = vec![];
+        for (o_id, order) in book.orders().take(inflight_order.match_limit as usize) {
+            if o_id.price_in_ticks != price_in_ticks {
+                break;
+            }
+            if order.num_base_lots > BaseLots::ZERO {
+                level_base_lots += order.num_base_lots;
+                allocations.push((o_id, order.num_base_lots));
+            }
+        }
+        if fillable_base_lots >= level_base_lots {
+            return vec![];
+        }
+
+        let pro_rata_share = |base_lots: BaseLots| {
+            BaseLots::new(
+                ((base_lots.as_u64() as u128 * fillable_base_lots.as_u64() as u128)
+                    / level_base_lots.as_u64() as u128) as u64,
+            )
+        };
+        let mut remainder = allocations
+            .iter()
+            .fold(fillable_base_lots, |remainder, (_, base_lots)| {
+                remainder - pro_rata_share(*base_lots)
+            });
+        // Base lots that cannot be split evenly are assigned in time priority
+        for (_, allocation) in allocations.iter_mut() {
+            let base_lots = *allocation;
+            *allocation = pro_rata_share(base_lots);
+            let additional_base_lots = remainder.min(base_lots - *allocation);
+            *allocation += additional_base_lots;
+            remainder -= additional_base_lots;
+        }
+        allocations.retain(|(_, allocation)| *allocation > BaseLots::ZERO);
+        allocations.reverse();
+        allocations
+    }
+
     fn cancel_all_orders_inner(
         &mut self,
         trader_id: &MarketTraderId,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         let trader_index = self.get_trader_index(trader_id)?;
@@ -1455,10 +3501,152 @@ impl<
             trader_index,
             &orders_to_cancel,
             claim_funds,
+            iceberg_reserves,
             record_event_fn,
         )
     }
 
+    /// Prices that do not fall on the new tick grid are rounded away from the spread: bids are
+    /// rounded down and asks are rounded up. No order becomes more aggressive, so the book cannot
+    /// cross. A bid that is rounded down needs fewer quote lots, and the difference is unlocked to
+    /// the maker's free funds. Orders that land on the same tick keep their relative time priority,
+    /// since it is determined by the order sequence number.
+    ///
+    /// The market is left unchanged if the tick size is not a multiple of `base_lots_per_base_unit`
+    /// or if a bid would be rounded down to a price of zero. The price accumulator is reset because
+    /// its totals are denominated in ticks.
+    fn change_tick_size_inner(
+        &mut self,
+        tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    ) -> Option<Vec<FIFOOrderId>> {
+        if tick_size_in_quote_lots_per_base_unit == QuoteLotsPerBaseUnitPerTick::ZERO
+            || tick_size_in_quote_lots_per_base_unit % self.base_lots_per_base_unit != 0
+        {
+            return None;
+        }
+        let old_tick_size = self.tick_size_in_quote_lots_per_base_unit.as_u128();
+        let new_tick_size = tick_size_in_quote_lots_per_base_unit.as_u128();
+
+        let mut migrated_orders = vec![];
+        for side in [Side::Bid, Side::Ask] {
+            for (order_id, resting_order) in self.get_book(side).iter() {
+                let price = order_id.price_in_ticks.as_u128() * old_tick_size;
+                let new_price_in_ticks = match side {
+                    Side::Bid => price / new_tick_size,
+                    Side::Ask => (price + new_tick_size - 1) / new_tick_size,
+                };
+                if new_price_in_ticks == 0 {
+                    phoenix_log!(
+                        "Bid at {} ticks would be rounded down to a price of zero",
+                        order_id.price_in_ticks
+                    );
+                    return None;
+                }
+                let new_order_id = FIFOOrderId::new_from_untyped(
+                    u64::try_from(new_price_in_ticks).ok()?,
+                    order_id.order_sequence_number,
+                );
+                migrated_orders.push((side, *order_id, new_order_id, *resting_order));
+            }
+        }
+
+        // All orders are removed before any are reinserted, so the book never exceeds its capacity
+        for (side, order_id, _, _) in migrated_orders.iter() {
+            self.get_book_mut(*side).remove(order_id)?;
+        }
+        let old_tick_size = self.tick_size_in_quote_lots_per_base_unit;
+        let base_lots_per_base_unit = self.base_lots_per_base_unit;
+        for (side, order_id, new_order_id, resting_order) in migrated_orders.iter() {
+            self.get_book_mut(*side)
+                .insert(*new_order_id, *resting_order)?;
+            if *side == Side::Bid {
+                let locked_quote_lots = Self::get_quote_lots_locked_for_bid(
+                    old_tick_size,
+                    base_lots_per_base_unit,
+                    order_id.price_in_ticks,
+                    resting_order.num_base_lots,
+                );
+                let required_quote_lots = Self::get_quote_lots_locked_for_bid(
+                    tick_size_in_quote_lots_per_base_unit,
+                    base_lots_per_base_unit,
+                    new_order_id.price_in_ticks,
+                    resting_order.num_base_lots,
+                );
+                self.get_trader_state_from_index_mut(resting_order.trader_index as u32)
+                    .unlock_quote_lots(locked_quote_lots - required_quote_lots);
+            }
+        }
+        // The last trade price anchors the volatility band, so it is moved to the nearest tick on
+        // the new grid. A nonzero price is never rounded down to zero.
+        if self.last_trade_price_in_ticks != Ticks::ZERO {
+            let price = self.last_trade_price_in_ticks.as_u128() * old_tick_size.as_u128();
+            let new_price_in_ticks = ((price + new_tick_size / 2) / new_tick_size).max(1);
+            self.last_trade_price_in_ticks = Ticks::new(u64::try_from(new_price_in_ticks).ok()?);
+        }
+        self.tick_size_in_quote_lots_per_base_unit = tick_size_in_quote_lots_per_base_unit;
+        self.price_accumulator = PriceAccumulator::default();
+        Some(
+            migrated_orders
+                .into_iter()
+                .map(|(_, _, new_order_id, _)| new_order_id)
+                .collect(),
+        )
+    }
+
+    fn cancel_all_orders_bounded_inner(
+        &mut self,
+        trader_id: &MarketTraderId,
+        max_orders: usize,
+        num_orders_to_search: Option<usize>,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<(MatchingEngineResponse, bool)> {
+        let trader_index = self.get_trader_index(trader_id)?;
+        // Bid order ids store the bitwise negation of the sequence number, so it is undone here to
+        // cancel in placement order regardless of side. The search bound is applied before the
+        // trader's orders are filtered, so every order visited counts towards it.
+        let mut orders = [Side::Bid, Side::Ask]
+            .iter()
+            .flat_map(|side| {
+                self.get_book(*side)
+                    .iter()
+                    .take(num_orders_to_search.unwrap_or(usize::MAX))
+                    .filter(|(_o_id, o)| {
+                        o.trader_index == trader_index as u64 && o.num_base_lots > BaseLots::ZERO
+                    })
+                    .map(|(o_id, _)| {
+                        let sequence_number =
+                            match Side::from_order_sequence_number(o_id.order_sequence_number) {
+                                Side::Bid => !o_id.order_sequence_number,
+                                Side::Ask => o_id.order_sequence_number,
+                            };
+                        (sequence_number, *o_id)
+                    })
+            })
+            .collect::<Vec<_>>();
+        // Orders beyond the search bound are not visited, so the trader may still have orders
+        // there if the search stopped before the end of either side of the book
+        let search_stopped_early = num_orders_to_search.map_or(false, |num_orders_to_search| {
+            self.bids.len() > num_orders_to_search || self.asks.len() > num_orders_to_search
+        });
+        let has_remaining_orders = orders.len() > max_orders || search_stopped_early;
+        orders.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+        let orders_to_cancel = orders
+            .into_iter()
+            .take(max_orders)
+            .map(|(_, o_id)| o_id)
+            .collect::<Vec<_>>();
+        let response = self.cancel_multiple_orders_by_id_inner(
+            trader_index,
+            &orders_to_cancel,
+            claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )?;
+        Some((response, has_remaining_orders))
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn cancel_up_to_inner(
         &mut self,
@@ -1467,10 +3655,14 @@ impl<
         num_orders_to_search: Option<usize>,
         num_orders_to_cancel: Option<usize>,
         tick_limit: Option<Ticks>,
+        lots_to_free: Option<u64>,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         let trader_index = self.get_trader_index(trader_id)?;
+        let tick_size_in_quote_lots_per_base_unit = self.tick_size_in_quote_lots_per_base_unit;
+        let base_lots_per_base_unit = self.base_lots_per_base_unit;
 
         let last_tick = tick_limit.unwrap_or(match side {
             Side::Ask => Ticks::MAX,
@@ -1479,7 +3671,9 @@ impl<
         let book = self.get_book(side);
         let num_orders = book.len();
 
-        let orders_to_cancel = book
+        let mut orders_to_cancel = vec![];
+        let mut lots_freed = 0_u128;
+        for (o_id, o) in book
             .iter()
             .take(num_orders_to_search.unwrap_or(num_orders))
             .filter(|(_o_id, o)| o.trader_index == trader_index as u64)
@@ -1488,6 +3682,61 @@ impl<
                 Side::Ask => o_id.price_in_ticks <= last_tick,
             })
             .take(num_orders_to_cancel.unwrap_or(num_orders))
+        {
+            if let Some(lots_to_free) = lots_to_free {
+                if lots_freed >= lots_to_free as u128 {
+                    break;
+                }
+                // Cancelling a bid frees quote lots and cancelling an ask frees base lots
+                let lots_released = match side {
+                    Side::Bid => o_id
+                        .price_in_ticks
+                        .as_u128()
+                        .checked_mul(tick_size_in_quote_lots_per_base_unit.as_u128())
+                        .and_then(|x| x.checked_mul(o.num_base_lots.as_u128()))
+                        .map(|x| x / base_lots_per_base_unit.as_u128()),
+                    Side::Ask => Some(o.num_base_lots.as_u128()),
+                };
+                match lots_released.and_then(|x| lots_freed.checked_add(x)) {
+                    Some(x) => lots_freed = x,
+                    None => {
+                        phoenix_log!("Overflow while computing the lots freed by cancelling");
+                        return None;
+                    }
+                }
+            }
+            orders_to_cancel.push(*o_id);
+        }
+
+        self.cancel_multiple_orders_by_id_inner(
+            trader_index,
+            &orders_to_cancel,
+            claim_funds,
+            iceberg_reserves,
+            record_event_fn,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn cancel_in_price_range_inner(
+        &mut self,
+        trader_id: &MarketTraderId,
+        side: Side,
+        low_tick: Ticks,
+        high_tick: Ticks,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse> {
+        let trader_index = self.get_trader_index(trader_id)?;
+        let orders_to_cancel = self
+            .get_book(side)
+            .iter()
+            .filter(|(o_id, o)| {
+                o.trader_index == trader_index as u64
+                    && o_id.price_in_ticks >= low_tick
+                    && o_id.price_in_ticks <= high_tick
+            })
             .map(|(o_id, _)| *o_id)
             .collect::<Vec<_>>();
 
@@ -1495,6 +3744,7 @@ impl<
             trader_index,
             &orders_to_cancel,
             claim_funds,
+            iceberg_reserves,
             record_event_fn,
         )
     }
@@ -1504,6 +3754,7 @@ impl<
         trader_index: u32,
         orders_to_cancel: &[FIFOOrderId],
         claim_funds: bool,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         let (quote_lots_released, base_lots_released) = orders_to_cancel
@@ -1516,6 +3767,7 @@ impl<
                     None,
                     false,
                     claim_funds,
+                    iceberg_reserves.as_deref_mut(),
                     record_event_fn,
                 )
                 .map(
@@ -1542,6 +3794,59 @@ impl<
         ))
     }
 
+    fn reduce_multiple_orders_inner(
+        &mut self,
+        trader_index: u32,
+        orders_to_reduce: &[(FIFOOrderId, BaseLots)],
+        claim_funds: bool,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse> {
+        // Orders that do not belong to the trader are skipped
+        let (quote_lots_released, base_lots_released) = orders_to_reduce
+            .iter()
+            .filter_map(|&(order_id, size)| {
+                let released = self.unlock_reduced_order_inner(
+                    trader_index,
+                    &order_id,
+                    Side::from_order_sequence_number(order_id.order_sequence_number),
+                    Some(size),
+                    false,
+                    iceberg_reserves.as_deref_mut(),
+                    record_event_fn,
+                );
+                if released.is_none() {
+                    phoenix_log!(
+                        "Skipping order {} at {} ticks, which does not belong to the trader",
+                        order_id.order_sequence_number,
+                        order_id.price_in_ticks.as_u64()
+                    );
+                }
+                released
+            })
+            .fold(
+                (QuoteLots::ZERO, BaseLots::ZERO),
+                |(quote_lots_released, base_lots_released), (quote_lots_out, base_lots_out)| {
+                    (
+                        quote_lots_released + quote_lots_out,
+                        base_lots_released + base_lots_out,
+                    )
+                },
+            );
+
+        // The released funds are claimed once for the whole batch
+        if claim_funds {
+            self.claim_funds_inner(
+                trader_index,
+                Some(quote_lots_released),
+                Some(base_lots_released),
+                false,
+            )
+        } else {
+            Some(MatchingEngineResponse::default())
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[inline(always)]
     fn reduce_order_inner(
@@ -1552,30 +3857,71 @@ impl<
         size: Option<BaseLots>,
         order_is_expired: bool,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
+        let (num_quote_lots, num_base_lots) = self.unlock_reduced_order_inner(
+            trader_index,
+            order_id,
+            side,
+            size,
+            order_is_expired,
+            iceberg_reserves,
+            record_event_fn,
+        )?;
+        // We don't want to claim funds if an order is removed from the book during a self trade
+        // or if the user specifically indicates that they don't want to claim funds.
+        if claim_funds {
+            self.claim_funds_inner(
+                trader_index,
+                Some(num_quote_lots),
+                Some(num_base_lots),
+                false,
+            )
+        } else {
+            Some(MatchingEngineResponse::default())
+        }
+    }
+
+    /// Reduces the order and unlocks the released funds without claiming them. Returns the
+    /// unlocked quote lots and base lots, or None if the order does not belong to the trader.
+    /// If the order is removed from the book, the reserve of an iceberg order is released as well.
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    fn unlock_reduced_order_inner(
+        &mut self,
+        trader_index: u32,
+        order_id: &FIFOOrderId,
+        side: Side,
+        size: Option<BaseLots>,
+        order_is_expired: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<(QuoteLots, BaseLots)> {
         let maker_id = self.get_trader_id_from_index(trader_index);
-        let removed_base_lots = {
+        let (removed_base_lots, order_base_lots, order_removed) = {
             let book = self.get_book_mut(side);
-            let (should_remove_order_from_book, base_lots_to_remove) = {
+            let (should_remove_order_from_book, base_lots_to_remove, order_base_lots) = {
                 if let Some(order) = book.get(order_id) {
+                    let order_base_lots = order.num_base_lots;
                     let base_lots_to_remove = size
-                        .map(|s| s.min(order.num_base_lots))
-                        .unwrap_or(order.num_base_lots);
+                        .map(|s| s.min(order_base_lots))
+                        .unwrap_or(order_base_lots);
                     if order.trader_index != trader_index as u64 {
                         return None;
                     }
                     // If the order is tagged as expired, we remove it from the book regardless of the size.
                     if order_is_expired {
-                        (true, order.num_base_lots)
+                        (true, order_base_lots, order_base_lots)
                     } else {
                         (
-                            base_lots_to_remove == order.num_base_lots,
+                            base_lots_to_remove == order_base_lots,
                             base_lots_to_remove,
+                            order_base_lots,
                         )
                     }
                 } else {
-                    return Some(MatchingEngineResponse::default());
+                    return Some((QuoteLots::ZERO, BaseLots::ZERO));
                 }
             };
             let base_lots_remaining = if should_remove_order_from_book {
@@ -1604,19 +3950,34 @@ impl<
                     base_lots_remaining,
                 });
             }
-            base_lots_to_remove
+            (
+                base_lots_to_remove,
+                order_base_lots,
+                should_remove_order_from_book,
+            )
         };
-        let (num_quote_lots, num_base_lots) = {
+        let (unlocked_quote_lots, unlocked_base_lots) = {
             // These constants need to be copied because we mutably borrow below
             let tick_size_in_quote_lots_per_base_unit = self.tick_size_in_quote_lots_per_base_unit;
             let base_lots_per_base_unit = self.base_lots_per_base_unit;
             let trader_state = self.get_trader_state_from_index_mut(trader_index);
+            if order_removed {
+                trader_state.decrement_open_orders(side);
+            }
             match side {
                 Side::Bid => {
-                    let quote_lots = (order_id.price_in_ticks
-                        * tick_size_in_quote_lots_per_base_unit
-                        * removed_base_lots)
-                        / base_lots_per_base_unit;
+                    let quote_lots_locked_for_size = |base_lots| {
+                        Self::get_quote_lots_locked_for_bid(
+                            tick_size_in_quote_lots_per_base_unit,
+                            base_lots_per_base_unit,
+                            order_id.price_in_ticks,
+                            base_lots,
+                        )
+                    };
+                    // Unlocking the difference keeps the unlocked amounts consistent with the
+                    // amount locked at placement, regardless of rounding
+                    let quote_lots = quote_lots_locked_for_size(order_base_lots)
+                        - quote_lots_locked_for_size(order_base_lots - removed_base_lots);
                     trader_state.unlock_quote_lots(quote_lots);
                     (quote_lots, BaseLots::ZERO)
                 }
@@ -1626,17 +3987,291 @@ impl<
                 }
             }
         };
-        // We don't want to claim funds if an order is removed from the book during a self trade
-        // or if the user specifically indicates that they don't want to claim funds.
-        if claim_funds {
-            self.claim_funds_inner(
-                trader_index,
-                Some(num_quote_lots),
-                Some(num_base_lots),
-                false,
-            )
+        // The reserve of an iceberg order is released once its visible order leaves the book
+        let (reserve_quote_lots, reserve_base_lots) = if order_removed {
+            self.release_iceberg_reserve(order_id, side, iceberg_reserves)
         } else {
-            Some(MatchingEngineResponse::default())
+            (QuoteLots::ZERO, BaseLots::ZERO)
+        };
+        Some((
+            unlocked_quote_lots + reserve_quote_lots,
+            unlocked_base_lots + reserve_base_lots,
+        ))
+    }
+
+    /// Removes the reserve of an iceberg order that left the book and unlocks the funds that were
+    /// locked for it. Returns the unlocked quote lots and base lots, which are zero if the order
+    /// has no reserve.
+    fn release_iceberg_reserve(
+        &mut self,
+        order_id: &FIFOOrderId,
+        side: Side,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+    ) -> (QuoteLots, BaseLots) {
+        let iceberg_reserve =
+            match iceberg_reserves.and_then(|iceberg_reserves| iceberg_reserves.remove(order_id)) {
+                Some(iceberg_reserve) => iceberg_reserve,
+                None => return (QuoteLots::ZERO, BaseLots::ZERO),
+            };
+        let trader_state =
+            self.get_trader_state_from_index_mut(iceberg_reserve.trader_index as u32);
+        match side {
+            Side::Bid => {
+                trader_state.unlock_quote_lots(iceberg_reserve.quote_lots_locked);
+                (iceberg_reserve.quote_lots_locked, BaseLots::ZERO)
+            }
+            Side::Ask => {
+                trader_state.unlock_base_lots(iceberg_reserve.reserve_base_lots);
+                (QuoteLots::ZERO, iceberg_reserve.reserve_base_lots)
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn amend_order_inner(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &FIFOOrderId,
+        side: Side,
+        num_base_lots: BaseLots,
+        price_in_ticks: Option<Ticks>,
+        client_order_id: u128,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<(Option<FIFOOrderId>, MatchingEngineResponse)> {
+        let trader_index = self.get_trader_index(trader_id)?;
+        let resting_order = match self.get_book(side).get(order_id) {
+            Some(order) if order.trader_index == trader_index as u64 => *order,
+            Some(_) => {
+                phoenix_log!("Order does not belong to trader");
+                return None;
+            }
+            None => {
+                phoenix_log!("Order not found");
+                return None;
+            }
+        };
+        let new_price_in_ticks = price_in_ticks.unwrap_or(order_id.price_in_ticks);
+        if new_price_in_ticks == order_id.price_in_ticks
+            && num_base_lots <= resting_order.num_base_lots
+        {
+            // Shrinking the order in place preserves its priority in the queue
+            if num_base_lots < resting_order.num_base_lots {
+                self.reduce_order_inner(
+                    trader_index,
+                    order_id,
+                    side,
+                    Some(resting_order.num_base_lots - num_base_lots),
+                    false,
+                    false,
+                    iceberg_reserves,
+                    record_event_fn,
+                )?;
+            }
+            let order_id = if num_base_lots > BaseLots::ZERO {
+                Some(*order_id)
+            } else {
+                None
+            };
+            return Some((order_id, MatchingEngineResponse::default()));
+        }
+
+        // Any price change or size increase requires the order to be re-queued. The cancelled
+        // order's funds are unlocked to the trader's free balance and used for the new order.
+        self.reduce_order_inner(
+            trader_index,
+            order_id,
+            side,
+            None,
+            false,
+            false,
+            iceberg_reserves.as_deref_mut(),
+            record_event_fn,
+        )?;
+        if num_base_lots == BaseLots::ZERO {
+            return Some((None, MatchingEngineResponse::default()));
+        }
+        self.place_order_inner(
+            trader_id,
+            OrderPacket::PostOnly {
+                side,
+                price_in_ticks: new_price_in_ticks,
+                num_base_lots,
+                client_order_id,
+                reject_post_only: true,
+                use_only_deposited_funds: true,
+                last_valid_slot: resting_order.last_valid_slot(),
+                last_valid_unix_timestamp_in_seconds: resting_order
+                    .last_valid_unix_timestamp_in_seconds(),
+                fail_silently_on_insufficient_funds: false,
+                max_tick_adjustment: None,
+                require_top_of_book: false,
+                valid_for_slots: None,
+            },
+            None,
+            iceberg_reserves,
+            self.min_taker_fill_base_lots,
+            record_event_fn,
+            get_clock_fn,
+        )
+        .ok()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn reprice_order_inner(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &FIFOOrderId,
+        side: Side,
+        new_price_in_ticks: Ticks,
+        client_order_id: u128,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<FIFOOrderId> {
+        // Repriced orders are re-inserted with a new sequence number
+        if self.order_sequence_number == MAX_ORDER_SEQUENCE_NUMBER {
+            phoenix_log!("Sequence number exceeded maximum");
+            return None;
+        }
+        let trader_index = self.get_trader_index(trader_id)?;
+        let resting_order = match self.get_book(side).get(order_id) {
+            Some(order) if order.trader_index == trader_index as u64 => *order,
+            Some(_) => {
+                phoenix_log!("Order does not belong to trader");
+                return None;
+            }
+            None => {
+                phoenix_log!("Order not found");
+                return None;
+            }
+        };
+        if new_price_in_ticks == Ticks::ZERO {
+            phoenix_log!("Orders cannot be repriced to a price of 0");
+            return None;
+        }
+        // The reserve of an iceberg order is keyed by the order id of its visible order
+        if iceberg_reserves
+            .as_deref()
+            .map_or(false, |iceberg_reserves| {
+                iceberg_reserves.get(order_id).is_some()
+            })
+        {
+            phoenix_log!("Iceberg orders cannot be repriced");
+            return None;
+        }
+        let (current_slot, current_unix_timestamp) = get_clock_fn();
+        if resting_order.is_expired(current_slot, current_unix_timestamp) {
+            phoenix_log!("Expired orders cannot be repriced");
+            return None;
+        }
+        if self
+            .check_for_cross(
+                side,
+                new_price_in_ticks,
+                current_slot,
+                current_unix_timestamp,
+                iceberg_reserves.as_deref_mut(),
+                record_event_fn,
+            )
+            .is_some()
+        {
+            phoenix_log!("Repriced order would cross the book");
+            return None;
+        }
+
+        // The size of a bid is fixed, but the quote lots it locks depend on its price
+        let (quote_lots_to_unlock, quote_lots_to_lock) = match side {
+            Side::Bid => {
+                let quote_lots_for_price = |price_in_ticks| {
+                    Self::get_quote_lots_locked_for_bid(
+                        self.tick_size_in_quote_lots_per_base_unit,
+                        self.base_lots_per_base_unit,
+                        price_in_ticks,
+                        resting_order.num_base_lots,
+                    )
+                };
+                (
+                    quote_lots_for_price(order_id.price_in_ticks),
+                    quote_lots_for_price(new_price_in_ticks),
+                )
+            }
+            Side::Ask => (QuoteLots::ZERO, QuoteLots::ZERO),
+        };
+        let quote_lots_free = self
+            .get_trader_state_from_index(trader_index)
+            .quote_lots_free;
+        if quote_lots_free + quote_lots_to_unlock < quote_lots_to_lock {
+            phoenix_log!("Insufficient free quote lots to reprice the bid");
+            return None;
+        }
+
+        let new_order_id = self.get_next_order_id(side, new_price_in_ticks);
+        let book = self.get_book_mut(side);
+        book.remove(order_id)?;
+        book.insert(new_order_id, resting_order)?;
+        self.order_sequence_number += 1;
+        if side == Side::Bid {
+            let trader_state = self.get_trader_state_from_index_mut(trader_index);
+            trader_state.unlock_quote_lots(quote_lots_to_unlock);
+            trader_state.use_free_quote_lots(quote_lots_to_lock);
+            trader_state.lock_quote_lots(quote_lots_to_lock);
+        }
+
+        record_event_fn(MarketEvent::Reduce {
+            order_sequence_number: order_id.order_sequence_number,
+            price_in_ticks: order_id.price_in_ticks,
+            base_lots_removed: resting_order.num_base_lots,
+            base_lots_remaining: BaseLots::ZERO,
+        });
+        record_event_fn(MarketEvent::<MarketTraderId>::Place {
+            order_sequence_number: new_order_id.order_sequence_number,
+            price_in_ticks: new_order_id.price_in_ticks,
+            base_lots_placed: resting_order.num_base_lots,
+            client_order_id,
+        });
+        if resting_order.last_valid_slot != 0
+            || resting_order.last_valid_unix_timestamp_in_seconds != 0
+        {
+            record_event_fn(MarketEvent::<MarketTraderId>::TimeInForce {
+                order_sequence_number: new_order_id.order_sequence_number,
+                last_valid_slot: resting_order.last_valid_slot,
+                last_valid_unix_timestamp_in_seconds: resting_order
+                    .last_valid_unix_timestamp_in_seconds,
+            });
+        }
+        Some(new_order_id)
+    }
+
+    fn prune_expired_orders_inner(
+        &mut self,
+        side: Side,
+        num_orders_to_scan: usize,
+        mut iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<FIFOOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<usize> {
+        let (current_slot, current_unix_timestamp) = get_clock_fn();
+        let expired_orders = self
+            .get_book(side)
+            .iter()
+            .take(num_orders_to_scan)
+            .filter(|(_, order)| order.is_expired(current_slot, current_unix_timestamp))
+            .map(|(o_id, order)| (*o_id, order.trader_index as u32))
+            .collect::<Vec<_>>();
+        for (order_id, trader_index) in expired_orders.iter() {
+            self.reduce_order_inner(
+                *trader_index,
+                order_id,
+                side,
+                None,
+                true,
+                false,
+                iceberg_reserves.as_deref_mut(),
+                record_event_fn,
+            )?;
         }
+        Some(expired_orders.len())
     }
 }
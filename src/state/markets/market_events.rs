@@ -1,6 +1,8 @@
 use borsh::BorshDeserialize;
 
 use crate::quantities::{BaseLots, QuoteLots, Ticks};
+use crate::state::markets::FIFOOrderId;
+use crate::state::Side;
 
 #[derive(Debug, Copy, Clone)]
 pub enum MarketEvent<MarketTraderId: BorshDeserialize + BorshDeserialize> {
@@ -28,8 +30,11 @@ pub enum MarketEvent<MarketTraderId: BorshDeserialize + BorshDeserialize> {
         order_sequence_number: u64,
         price_in_ticks: Ticks,
         base_lots_evicted: BaseLots,
+        /// Id of the incoming order that caused the eviction
+        evicting_order_id: FIFOOrderId,
     },
     FillSummary {
+        taker_id: MarketTraderId,
         client_order_id: u128,
         total_base_lots_filled: BaseLots,
         total_quote_lots_filled: QuoteLots,
@@ -49,4 +54,37 @@ pub enum MarketEvent<MarketTraderId: BorshDeserialize + BorshDeserialize> {
         price_in_ticks: Ticks,
         base_lots_removed: BaseLots,
     },
+    Rebate {
+        maker_id: MarketTraderId,
+        rebate_in_quote_lots: QuoteLots,
+    },
+    BookCheckpoint {
+        best_bid_in_ticks: Ticks,
+        best_bid_base_lots: BaseLots,
+        best_ask_in_ticks: Ticks,
+        best_ask_base_lots: BaseLots,
+    },
+    /// Emitted after a `Fill` event when the resting order was fully matched and removed from
+    /// the book
+    OrderComplete {
+        maker_id: MarketTraderId,
+        order_sequence_number: u64,
+    },
+    /// Emitted when a fill triggers a stop order. The events that follow, up to the next
+    /// `FillSummary`, belong to the immediate-or-cancel order that the stop order was sent as.
+    StopTriggered {
+        trader_id: MarketTraderId,
+        stop_sequence_number: u64,
+        trigger_price_in_ticks: Ticks,
+    },
+    /// Emitted when a stop order is placed. The stop order is not on the book until it triggers.
+    StopPlaced {
+        trader_id: MarketTraderId,
+        stop_sequence_number: u64,
+        side: Side,
+        trigger_price_in_ticks: Ticks,
+        limit_price_in_ticks: Ticks,
+        num_base_lots: BaseLots,
+        client_order_id: u128,
+    },
 }
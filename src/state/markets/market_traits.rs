@@ -9,7 +9,9 @@ use crate::{
 use borsh::{BorshDeserialize, BorshSerialize};
 use sokoban::node_allocator::OrderedNodeAllocatorMap;
 
-use super::MarketEvent;
+use super::{
+    IcebergReserve, MarketEvent, PriceAccumulator, MAX_ORDER_SEQUENCE_NUMBER, NUM_FEE_TIERS,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LadderOrder {
@@ -37,6 +39,21 @@ pub struct TypedLadder {
     pub asks: Vec<TypedLadderOrder>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedLadderOrder {
+    pub price_in_ticks: Ticks,
+    pub size_in_base_lots: BaseLots,
+    /// The number of resting orders at this price level
+    pub num_orders: u64,
+}
+
+/// A ladder that also counts the resting orders that make up each price level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetailedLadder {
+    pub bids: Vec<DetailedLadderOrder>,
+    pub asks: Vec<DetailedLadderOrder>,
+}
+
 pub trait OrderId {
     fn price_in_ticks(&self) -> u64;
 }
@@ -114,6 +131,17 @@ pub trait Market<
     ) -> TypedLadder {
         let slot_expiration = last_valid_slot.unwrap_or(0);
         let unix_timestamp_expiration = last_valid_unix_timestamp_in_seconds.unwrap_or(0);
+        self.get_typed_ladder_with_filter(levels, &|_, resting_order| {
+            !resting_order.is_expired(slot_expiration, unix_timestamp_expiration)
+        })
+    }
+
+    /// Aggregates the resting orders for which `include` returns true into price levels.
+    fn get_typed_ladder_with_filter(
+        &self,
+        levels: u64,
+        include: &dyn Fn(&MarketOrderId, &MarketRestingOrder) -> bool,
+    ) -> TypedLadder {
         let mut bids = vec![];
         let mut asks = vec![];
         for (side, book) in [(Side::Bid, &mut bids), (Side::Ask, &mut asks)].iter_mut() {
@@ -122,10 +150,10 @@ pub trait Market<
                     .get_book(*side)
                     .iter()
                     .filter_map(|(order_id, resting_order)| {
-                        if resting_order.is_expired(slot_expiration, unix_timestamp_expiration) {
-                            None
-                        } else {
+                        if include(order_id, resting_order) {
                             Some((order_id.price_in_ticks(), resting_order.size()))
+                        } else {
+                            None
                         }
                     })
                     .group_by(|(price_in_ticks, _)| *price_in_ticks)
@@ -141,12 +169,276 @@ pub trait Market<
         TypedLadder { bids, asks }
     }
 
+    /// Returns the top `levels` price levels of each side of the book with the total size and the
+    /// number of resting orders at each level. Use `get_typed_ladder` if the order counts are not
+    /// needed.
+    fn get_detailed_ladder(&self, levels: usize) -> DetailedLadder {
+        let mut bids = vec![];
+        let mut asks = vec![];
+        for (side, book) in [(Side::Bid, &mut bids), (Side::Ask, &mut asks)].iter_mut() {
+            for (order_id, resting_order) in self.get_book(*side).iter() {
+                let price_in_ticks = Ticks::new(order_id.price_in_ticks());
+                let size_in_base_lots = BaseLots::new(resting_order.size());
+                // Orders are iterated in price priority, so a level's orders are consecutive
+                match book.last_mut() {
+                    Some(level) if level.price_in_ticks == price_in_ticks => {
+                        level.size_in_base_lots += size_in_base_lots;
+                        level.num_orders += 1;
+                    }
+                    _ => {
+                        if book.len() == levels {
+                            break;
+                        }
+                        book.push(DetailedLadderOrder {
+                            price_in_ticks,
+                            size_in_base_lots,
+                            num_orders: 1,
+                        });
+                    }
+                }
+            }
+        }
+        DetailedLadder { bids, asks }
+    }
+
+    /// Returns the imbalance between the base lots resting in the top `depth_levels` price levels
+    /// of each side, in basis points: `(bid_size - ask_size) * 10000 / (bid_size + ask_size)`.
+    /// The value is positive when there is more size on the bid side. Returns None if both sides
+    /// are empty.
+    fn get_book_imbalance(&self, depth_levels: usize) -> Option<i64> {
+        let ladder = self.get_typed_ladder(depth_levels as u64);
+        let bid_size = ladder
+            .bids
+            .iter()
+            .map(|level| level.size_in_base_lots.as_u64() as i128)
+            .sum::<i128>();
+        let ask_size = ladder
+            .asks
+            .iter()
+            .map(|level| level.size_in_base_lots.as_u64() as i128)
+            .sum::<i128>();
+        if bid_size + ask_size == 0 {
+            return None;
+        }
+        Some(((bid_size - ask_size) * 10000 / (bid_size + ask_size)) as i64)
+    }
+
+    /// Returns the volume-weighted average price (in ticks) and the number of base lots filled
+    /// for a hypothetical order of `size` base lots on `side`. Does not modify the book.
+    fn get_impact_price(&self, side: Side, size: BaseLots) -> Option<(Ticks, BaseLots)> {
+        self.get_impact_price_with_expiration(side, size, None, None)
+    }
+
+    fn get_impact_price_with_expiration(
+        &self,
+        side: Side,
+        size: BaseLots,
+        current_slot: Option<u64>,
+        current_unix_timestamp_in_seconds: Option<u64>,
+    ) -> Option<(Ticks, BaseLots)>;
+
+    /// Returns the number of base lots resting on the opposite side of the book that an order on
+    /// `side` could match without trading at a price worse than `limit_price`. Does not modify
+    /// the book.
+    fn get_fillable_base_lots(&self, side: Side, limit_price: Ticks) -> BaseLots {
+        self.get_fillable_base_lots_with_expiration(side, limit_price, None, None)
+    }
+
+    /// Same as `get_fillable_base_lots`, but orders that have expired at the given slot and unix
+    /// timestamp are skipped.
+    fn get_fillable_base_lots_with_expiration(
+        &self,
+        side: Side,
+        limit_price: Ticks,
+        current_slot: Option<u64>,
+        current_unix_timestamp_in_seconds: Option<u64>,
+    ) -> BaseLots {
+        let current_slot = current_slot.unwrap_or(0);
+        let current_unix_timestamp_in_seconds = current_unix_timestamp_in_seconds.unwrap_or(0);
+        BaseLots::new(
+            self.get_book(side.opposite())
+                .iter()
+                .take_while(|(order_id, _)| match side {
+                    Side::Bid => order_id.price_in_ticks() <= limit_price.as_u64(),
+                    Side::Ask => order_id.price_in_ticks() >= limit_price.as_u64(),
+                })
+                .filter(|(_, resting_order)| {
+                    !resting_order.is_expired(current_slot, current_unix_timestamp_in_seconds)
+                })
+                .map(|(_, resting_order)| resting_order.size())
+                .sum(),
+        )
+    }
+
+    /// Returns the midpoint between the best bid and the best ask, rounded down to a whole tick,
+    /// or None if either side of the book is empty.
+    fn get_mid_price(&self) -> Option<Ticks> {
+        let best_bid = self.get_book(Side::Bid).get_min()?.0.price_in_ticks();
+        let best_ask = self.get_book(Side::Ask).get_min()?.0.price_in_ticks();
+        Some(Ticks::new(
+            ((best_bid as u128 + best_ask as u128) / 2) as u64,
+        ))
+    }
+
+    /// Returns the difference between the best ask and the best bid, or None if either side of
+    /// the book is empty.
+    fn get_spread(&self) -> Option<Ticks> {
+        let best_bid = self.get_book(Side::Bid).get_min()?.0.price_in_ticks();
+        let best_ask = self.get_book(Side::Ask).get_min()?.0.price_in_ticks();
+        Some(Ticks::new(best_ask.saturating_sub(best_bid)))
+    }
+
+    /// Returns a hash of the order sequence number and the price and size of the best level on
+    /// each side of the book. Every placement and every change to the top of the book changes the
+    /// hash, so clients can poll it to detect a stale view of the book without diffing ladders.
+    ///
+    /// This is a fast change detector, not a cryptographic hash.
+    fn get_order_book_hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        let mut words = vec![self.get_sequence_number()];
+        for side in [Side::Bid, Side::Ask] {
+            match self.get_book(side).get_min() {
+                Some((order_id, _)) => {
+                    let price_in_ticks = Ticks::new(order_id.price_in_ticks());
+                    words.push(price_in_ticks.as_u64());
+                    words.push(self.get_base_lots_at_tick(side, price_in_ticks).as_u64());
+                }
+                None => words.extend([u64::MAX, 0]),
+            }
+        }
+        words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    /// Returns the total size of all resting orders at `price_in_ticks` on the given side of the
+    /// book, or `BaseLots::ZERO` if there are none.
+    fn get_base_lots_at_tick(&self, side: Side, price_in_ticks: Ticks) -> BaseLots;
+
     fn get_taker_fee_bps(&self) -> u64;
+    fn get_maker_rebate_bps(&self) -> u64;
+    fn get_min_base_lots_per_order(&self) -> BaseLots;
+    fn get_max_order_lifetime_slots(&self) -> u64;
+    fn get_max_match_limit(&self) -> u64;
+
+    /// Returns the number of matched base lots that starts a volatility cooldown, the length of
+    /// the cooldown in slots, and the width of the price band in basis points. The band is
+    /// disabled if the number of base lots is zero.
+    fn get_volatility_band(&self) -> (BaseLots, u64, u64);
+    fn get_last_trade_price(&self) -> Ticks;
+    fn get_last_large_fill_slot(&self) -> u64;
+    fn get_max_orders_per_side_per_trader(&self) -> u64;
+    fn get_fee_rounding_mode(&self) -> FeeRoundingMode;
+    fn get_min_fee_quote_lots(&self) -> QuoteLots;
+    fn get_fee_tiers_bps(&self) -> [u64; NUM_FEE_TIERS];
+
+    /// Returns the fill accumulator if it is enabled. Callers sample it twice and divide the
+    /// differences to compute a volume or time weighted average price over the interval.
+    fn get_twap(&self) -> Option<PriceAccumulator>;
+
+    /// Returns true if per-trader volume and fill counts are updated on every match.
+    fn is_trader_volume_tracking_enabled(&self) -> bool;
+
+    /// Returns true if orders that would take liquidity are rejected.
+    fn is_taking_disabled(&self) -> bool;
+
+    /// Returns the share of the taker fee, in basis points of the fee, that is accrued to the
+    /// referrer of an order that names one.
+    fn get_referral_fee_bps(&self) -> u64;
+
+    /// Returns the total referral fees that have been accrued to referrers and not claimed.
+    fn get_unclaimed_referral_fee_amount(&self) -> QuoteLots;
+
+    /// Returns the minimum number of base lots that an immediate-or-cancel or fill-or-kill order
+    /// must match, unless it does not match at all. Zero means there is no minimum.
+    fn get_min_taker_fill_base_lots(&self) -> BaseLots;
     fn get_tick_size(&self) -> QuoteLotsPerBaseUnitPerTick;
     fn get_base_lots_per_base_unit(&self) -> BaseLotsPerBaseUnit;
     fn get_sequence_number(&self) -> u64;
+
+    /// Returns the number of orders that can still be placed before the sequence number reaches
+    /// `MAX_ORDER_SEQUENCE_NUMBER`, after which the market rejects all new orders.
+    fn get_remaining_sequence_numbers(&self) -> u64 {
+        MAX_ORDER_SEQUENCE_NUMBER.saturating_sub(self.get_sequence_number())
+    }
+
+    /// Returns the id that the next order placed on `side` at `price_in_ticks` will be assigned.
+    /// The preview is only valid until another order is placed on the market, since every placed
+    /// order increments the sequence number.
+    fn get_next_order_id(&self, side: Side, price_in_ticks: Ticks) -> MarketOrderId;
     fn get_registered_traders(&self) -> &dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState>;
     fn get_trader_state(&self, key: &MarketTraderId) -> Option<&TraderState>;
+
+    /// Returns the base and quote lots that a registered trader can withdraw, which are the
+    /// trader's free lots. Funds locked in resting orders or stop orders are not included.
+    /// Returns None if the trader is not registered, to distinguish a trader without a seat from
+    /// a trader with no free funds.
+    fn get_withdrawable(&self, trader_id: &MarketTraderId) -> Option<(BaseLots, QuoteLots)> {
+        self.get_trader_state(trader_id)
+            .map(|trader_state| (trader_state.base_lots_free, trader_state.quote_lots_free))
+    }
+
+    /// Returns the cumulative quote lots traded and the number of fills for a registered trader.
+    /// Both values are zero for traders that have not traded while volume tracking was enabled.
+    fn get_trader_volume(&self, key: &MarketTraderId) -> Option<(u128, u64)> {
+        self.get_trader_state(key).map(|trader_state| {
+            (
+                trader_state.cumulative_quote_lots_traded(),
+                trader_state.cumulative_fills,
+            )
+        })
+    }
+
+    /// Returns the value of a registered trader's free and locked funds in quote lots, with base
+    /// lots valued at `valuation_price`, rounded down. Returns None if the trader is not registered
+    /// or if the value does not fit in a u64.
+    fn get_trader_equity_in_quote_lots(
+        &self,
+        key: &MarketTraderId,
+        valuation_price: Ticks,
+    ) -> Option<QuoteLots> {
+        let trader_state = self.get_trader_state(key)?;
+        let base_lots = trader_state.base_lots_free.as_u128()
+            + trader_state.base_lots_locked.as_u128()
+            + trader_state.base_lots_locked_for_stops.as_u128();
+        let base_value_in_quote_lots = base_lots
+            .checked_mul(self.get_tick_size().as_u128() * valuation_price.as_u128())?
+            / self.get_base_lots_per_base_unit().as_u128();
+        let quote_lots = trader_state.quote_lots_free.as_u128()
+            + trader_state.quote_lots_locked.as_u128()
+            + trader_state.quote_lots_locked_for_stops.as_u128()
+            + base_value_in_quote_lots;
+        u64::try_from(quote_lots).ok().map(QuoteLots::new)
+    }
+
+    /// Returns the quote lots that the quote vault must hold to cover the market's obligations:
+    /// the free and locked quote lots of every registered trader (including quote lots held by
+    /// stop orders) plus the unclaimed fees and referral fees.
+    fn get_tracked_quote_lots(&self) -> QuoteLots {
+        self.get_registered_traders().iter().fold(
+            self.get_uncollected_fee_amount() + self.get_unclaimed_referral_fee_amount(),
+            |total, (_, trader_state)| {
+                total
+                    + trader_state.quote_lots_free
+                    + trader_state.quote_lots_locked
+                    + trader_state.quote_lots_locked_for_stops
+            },
+        )
+    }
+
+    /// Returns every registered trader that holds free or locked funds on the market, in the
+    /// order of the traders tree. Registered traders with no funds are skipped.
+    fn get_active_traders(&self) -> Vec<(MarketTraderId, TraderState)> {
+        self.get_registered_traders()
+            .iter()
+            .filter(|(_, trader_state)| !trader_state.is_empty())
+            .map(|(trader_id, trader_state)| (*trader_id, *trader_state))
+            .collect()
+    }
     fn get_trader_state_from_index(&self, index: u32) -> &TraderState;
     fn get_trader_index(&self, trader: &MarketTraderId) -> Option<u32>;
     fn get_trader_id_from_index(&self, trader_index: u32) -> MarketTraderId;
@@ -154,6 +446,50 @@ pub trait Market<
         &self,
         side: Side,
     ) -> &dyn OrderedNodeAllocatorMap<MarketOrderId, MarketRestingOrder>;
+
+    /// Looks up a resting order by id. The side of the book is derived from the order id, so only
+    /// one tree is searched.
+    fn get_order(&self, order_id: &MarketOrderId) -> Option<MarketRestingOrder>;
+
+    /// Returns the least aggressive resting order on one side of the book. This is the order that
+    /// is evicted when a more aggressive order is placed on a full book.
+    fn get_worst_order(&self, side: Side) -> Option<(MarketOrderId, MarketRestingOrder)> {
+        self.get_book(side).get_max()
+    }
+
+    /// Returns true if one side of the book is at capacity. A new order on a full side must be
+    /// more aggressive than the order returned by `get_worst_order` to be placed.
+    fn is_book_full(&self, side: Side) -> bool {
+        let book = self.get_book(side);
+        book.len() == book.capacity()
+    }
+
+    /// Returns all of the trader's resting orders on both sides of the book, sorted by price in
+    /// ascending order. Orders at the same price level are returned in time priority.
+    fn get_orders_for_trader(
+        &self,
+        trader_id: &MarketTraderId,
+    ) -> Vec<(MarketOrderId, MarketRestingOrder)>;
+
+    /// Returns the ladder as it is seen by every other trader, i.e. without the resting orders
+    /// of `trader_id`. This is useful for makers that want to compute an external mid price.
+    fn get_typed_ladder_excluding_trader(
+        &self,
+        levels: u64,
+        trader_id: &MarketTraderId,
+    ) -> TypedLadder;
+
+    /// Matches an IOC or FOK `order_packet` against the book as an unregistered taker that pays
+    /// for the order from its token accounts. The book is read through a read-only view, so it is
+    /// never copied or modified, no trader state is updated and no events are recorded.
+    ///
+    /// Returns None if the order can rest on the book, only uses deposited funds, or would be
+    /// rejected by `place_order`.
+    fn simulate_order(
+        &self,
+        order_packet: &MarketOrderPacket,
+        clock: (u64, u64),
+    ) -> Option<MatchingEngineResponse>;
 }
 
 pub(crate) trait WritableMarket<
@@ -171,6 +507,46 @@ pub(crate) trait WritableMarket<
 
     fn set_fee(&mut self, taker_fee_bps: u64);
 
+    fn set_maker_rebate(&mut self, maker_rebate_bps: u64);
+
+    fn set_fee_tiers(&mut self, fee_tiers_bps: [u64; NUM_FEE_TIERS]);
+
+    fn set_price_accumulator_enabled(&mut self, price_accumulator_enabled: bool);
+
+    fn set_trader_volume_tracking_enabled(&mut self, trader_volume_tracking_enabled: bool);
+
+    fn set_taking_disabled(&mut self, taking_disabled: bool);
+
+    fn set_referral_fee_bps(&mut self, referral_fee_bps: u64);
+
+    fn set_min_taker_fill_base_lots(&mut self, min_taker_fill_base_lots: BaseLots);
+
+    fn set_min_base_lots_per_order(&mut self, min_base_lots_per_order: BaseLots);
+
+    fn set_max_order_lifetime_slots(&mut self, max_order_lifetime_slots: u64);
+
+    fn set_max_match_limit(&mut self, max_match_limit: u64);
+
+    fn set_volatility_band(
+        &mut self,
+        fill_threshold_base_lots: BaseLots,
+        cooldown_slots: u64,
+        band_bps: u64,
+    );
+
+    fn set_max_orders_per_side_per_trader(&mut self, max_orders_per_side_per_trader: u64);
+
+    fn set_fee_rounding_mode(&mut self, fee_rounding_mode: FeeRoundingMode);
+
+    fn set_min_fee_quote_lots(&mut self, min_fee_quote_lots: QuoteLots);
+
+    /// Moves the market to a new tick size and migrates the resting orders onto the new ticks.
+    /// Returns the new ids of the resting orders, or None if the market was left unchanged.
+    fn change_tick_size(
+        &mut self,
+        tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    ) -> Option<Vec<MarketOrderId>>;
+
     fn get_trader_state_mut(&mut self, key: &MarketTraderId) -> Option<&mut TraderState>;
 
     fn get_registered_traders_mut(
@@ -190,7 +566,7 @@ pub(crate) trait WritableMarket<
     fn try_remove_trader_state(&mut self, trader: &MarketTraderId) -> Option<()> {
         let registered_traders = self.get_registered_traders_mut();
         let trader_state = registered_traders.get(trader)?;
-        if *trader_state == TraderState::default() {
+        if trader_state.is_empty() {
             registered_traders.remove(trader)?;
         }
         Some(())
@@ -207,7 +583,46 @@ pub(crate) trait WritableMarket<
         order_packet: MarketOrderPacket,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
         get_clock_fn: &mut dyn FnMut() -> (u64, u64),
-    ) -> Option<(Option<MarketOrderId>, MatchingEngineResponse)>;
+    ) -> Result<(Option<MarketOrderId>, MatchingEngineResponse), PhoenixMatchError>;
+
+    /// Places an order like `place_order`, where `token_budget` is the number of base and quote
+    /// lots the trader can deposit from their token accounts. Orders with `place_partial` set are
+    /// sized so that the trader's free funds and this budget cover them.
+    fn place_order_with_token_budget(
+        &mut self,
+        trader: &MarketTraderId,
+        order_packet: MarketOrderPacket,
+        token_budget: (BaseLots, QuoteLots),
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<MarketOrderId>, MatchingEngineResponse), PhoenixMatchError>;
+
+    /// Places an order like `place_order_with_token_budget`, where `iceberg_reserves` holds the
+    /// hidden reserves of the iceberg orders on the market. Iceberg orders can only be placed with
+    /// this method. When the visible size of an iceberg order is filled, it is replenished from
+    /// its reserve at the back of the queue.
+    fn place_order_with_iceberg_reserves(
+        &mut self,
+        trader: &MarketTraderId,
+        order_packet: MarketOrderPacket,
+        token_budget: Option<(BaseLots, QuoteLots)>,
+        iceberg_reserves: &mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<MarketOrderId>, MatchingEngineResponse), PhoenixMatchError>;
+
+    /// Places an order like `place_order`, where `min_taker_fill_base_lots` replaces the market's
+    /// minimum taker fill for this order. `iceberg_reserves` must be provided if the market has
+    /// iceberg orders, so that matched iceberg orders are replenished.
+    fn place_order_with_min_taker_fill(
+        &mut self,
+        trader: &MarketTraderId,
+        order_packet: MarketOrderPacket,
+        min_taker_fill_base_lots: BaseLots,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Result<(Option<MarketOrderId>, MatchingEngineResponse), PhoenixMatchError>;
 
     fn cancel_order(
         &mut self,
@@ -215,6 +630,7 @@ pub(crate) trait WritableMarket<
         order_id: &MarketOrderId,
         side: Side,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse> {
         self.reduce_order(
@@ -223,10 +639,16 @@ pub(crate) trait WritableMarket<
             side,
             None,
             claim_funds,
+            iceberg_reserves,
             record_event_fn,
         )
     }
 
+    /// Reduces an order by `size` base lots, or removes it from the book if `size` is None.
+    /// `iceberg_reserves` must be provided if the market has iceberg orders, so that the reserve
+    /// of an iceberg order is released when its visible order leaves the book. The same applies to
+    /// the other methods that remove orders from the book.
+    #[allow(clippy::too_many_arguments)]
     fn reduce_order(
         &mut self,
         trader_id: &MarketTraderId,
@@ -234,16 +656,70 @@ pub(crate) trait WritableMarket<
         side: Side,
         size: Option<BaseLots>,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse>;
 
+    /// Amends the size and optionally the price of an existing order. If the price is unchanged
+    /// and the size does not increase, the order is reduced in place and keeps its queue priority.
+    /// Otherwise, the order is cancelled and re-placed with a new sequence number, using only
+    /// the trader's deposited funds.
+    #[allow(clippy::too_many_arguments)]
+    fn amend_order(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &MarketOrderId,
+        side: Side,
+        num_base_lots: BaseLots,
+        price_in_ticks: Option<Ticks>,
+        client_order_id: u128,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<(Option<MarketOrderId>, MatchingEngineResponse)>;
+
+    /// Moves a resting order to a new price without changing its size. The order is re-queued
+    /// with a new sequence number, and a bid locks the quote lots needed at its new price using
+    /// the trader's free funds. Returns None if the order cannot be repriced or would cross.
+    #[allow(clippy::too_many_arguments)]
+    fn reprice_order(
+        &mut self,
+        trader_id: &MarketTraderId,
+        order_id: &MarketOrderId,
+        side: Side,
+        new_price_in_ticks: Ticks,
+        client_order_id: u128,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<MarketOrderId>;
+
     fn cancel_all_orders(
         &mut self,
         trader_id: &MarketTraderId,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse>;
 
+    /// Cancels at most `max_orders` of the trader's orders, most recently placed first (highest
+    /// sequence number). If `num_orders_to_search` is set, only that many orders are visited on
+    /// each side of the book. Also returns whether the trader may have orders left on the book,
+    /// which is always the case if the search stopped before the end of either side.
+    fn cancel_all_orders_bounded(
+        &mut self,
+        trader_id: &MarketTraderId,
+        max_orders: usize,
+        num_orders_to_search: Option<usize>,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<(MatchingEngineResponse, bool)>;
+
+    /// Cancels the trader's orders on one side of the book, starting from the most aggressive.
+    /// If `lots_to_free` is set, cancellation stops once the cancelled orders release at least this
+    /// many lots: quote lots for bids and base lots for asks. Returns `None` if the amount released
+    /// overflows.
     #[allow(clippy::too_many_arguments)]
     fn cancel_up_to(
         &mut self,
@@ -252,7 +728,23 @@ pub(crate) trait WritableMarket<
         num_orders_to_search: Option<usize>,
         num_orders_to_cancel: Option<usize>,
         tick_limit: Option<Ticks>,
+        lots_to_free: Option<u64>,
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse>;
+
+    /// Cancels all of the trader's orders on one side of the book whose price lies in the
+    /// inclusive range `[low_tick, high_tick]`.
+    #[allow(clippy::too_many_arguments)]
+    fn cancel_in_price_range(
+        &mut self,
+        trader_id: &MarketTraderId,
+        side: Side,
+        low_tick: Ticks,
+        high_tick: Ticks,
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse>;
 
@@ -261,9 +753,35 @@ pub(crate) trait WritableMarket<
         trader_id: &MarketTraderId,
         orders_to_cancel: &[MarketOrderId],
         claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> Option<MatchingEngineResponse>;
 
+    /// Reduces each of the given orders by the paired number of base lots, removing an order in
+    /// full if the size exceeds it. Orders that are not on the book or do not belong to the trader
+    /// are skipped. If `claim_funds` is true, the funds released by all of the orders are claimed
+    /// at once.
+    fn reduce_multiple_orders(
+        &mut self,
+        trader_id: &MarketTraderId,
+        orders_to_reduce: &[(MarketOrderId, BaseLots)],
+        claim_funds: bool,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+    ) -> Option<MatchingEngineResponse>;
+
+    /// Scans up to `num_orders_to_scan` orders on one side of the book, starting from the top of
+    /// book, and removes any that have expired. The makers' funds are unlocked but not claimed.
+    /// Returns the number of orders removed.
+    fn prune_expired_orders(
+        &mut self,
+        side: Side,
+        num_orders_to_scan: usize,
+        iceberg_reserves: Option<&mut dyn OrderedNodeAllocatorMap<MarketOrderId, IcebergReserve>>,
+        record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
+        get_clock_fn: &mut dyn FnMut() -> (u64, u64),
+    ) -> Option<usize>;
+
     fn claim_all_funds(
         &mut self,
         trader: &MarketTraderId,
@@ -284,4 +802,23 @@ pub(crate) trait WritableMarket<
         &mut self,
         record_event_fn: &mut dyn FnMut(MarketEvent<MarketTraderId>),
     ) -> QuoteLots;
+
+    /// Removes `quote_lots` from the unclaimed referral fees when a referrer claims their share.
+    /// Returns None if the market holds fewer unclaimed referral fees than `quote_lots`.
+    fn claim_referral_fees(&mut self, quote_lots: QuoteLots) -> Option<()>;
+
+    /// Moves `quote_lots` from the unclaimed referral fees to the market's uncollected fees when a
+    /// referrer is deregistered. Returns None if the market holds fewer unclaimed referral fees
+    /// than `quote_lots`.
+    fn forfeit_referral_fees(&mut self, quote_lots: QuoteLots) -> Option<()>;
+
+    /// Initializes this market with the full state of `source`, which is used to move a market
+    /// into a larger memory layout. Order ids (and therefore sequence numbers) are preserved, but
+    /// the resting orders are updated to point to the new trader indices.
+    ///
+    /// Returns None if the state of `source` does not fit in this market.
+    fn copy_from(
+        &mut self,
+        source: &dyn Market<MarketTraderId, MarketOrderId, MarketRestingOrder, MarketOrderPacket>,
+    ) -> Option<()>;
 }
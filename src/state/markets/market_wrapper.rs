@@ -1,4 +1,8 @@
-use super::{Market, WritableMarket};
+use super::{FIFOOrderId, FIFORestingOrder, Market, OrderId, RestingOrder, WritableMarket};
+use crate::quantities::WrapperU64;
+use crate::state::{MatchingEngineResponse, OrderPacket, OrderPacketMetadata, Side};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sokoban::node_allocator::OrderedNodeAllocatorMap;
 
 /// Struct that holds an object implementing the WritableMarket trait.
 pub(crate) struct MarketWrapperMut<
@@ -50,3 +54,85 @@ impl<'a, MarketTraderId, MarketOrderId, MarketRestingOrder, MarketOrderPacket>
         Self { inner: market }
     }
 }
+
+/// A resting order in a `BookSnapshot`. An expiry of zero means the order does not expire.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookSnapshotOrder<MarketTraderId> {
+    pub price_in_ticks: u64,
+    pub order_sequence_number: u64,
+    pub num_base_lots: u64,
+    pub maker_id: MarketTraderId,
+    pub last_valid_slot: u64,
+    pub last_valid_unix_timestamp_in_seconds: u64,
+}
+
+/// All of the resting orders of a market, ordered from the top of the book on each side.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq, Eq)]
+pub struct BookSnapshot<MarketTraderId> {
+    /// The market's order sequence number when the snapshot was taken
+    pub sequence_number: u64,
+    pub bids: Vec<BookSnapshotOrder<MarketTraderId>>,
+    pub asks: Vec<BookSnapshotOrder<MarketTraderId>>,
+}
+
+impl<'a, MarketTraderId: BorshDeserialize + BorshSerialize + Copy>
+    MarketWrapper<'a, MarketTraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>
+{
+    /// Serializes every resting order on the book so that clients can bootstrap their view of the
+    /// market from a single account fetch. Use `deserialize_book_snapshot` to read the result.
+    pub fn serialize_book_snapshot(&self) -> Vec<u8> {
+        let get_orders = |side: Side| {
+            self.inner
+                .get_book(side)
+                .iter()
+                .filter(|(_, order)| order.num_base_lots.as_u64() > 0)
+                .map(|(order_id, order)| BookSnapshotOrder {
+                    price_in_ticks: order_id.price_in_ticks.as_u64(),
+                    order_sequence_number: order_id.order_sequence_number,
+                    num_base_lots: order.num_base_lots.as_u64(),
+                    maker_id: self
+                        .inner
+                        .get_trader_id_from_index(order.trader_index as u32),
+                    last_valid_slot: order.last_valid_slot,
+                    last_valid_unix_timestamp_in_seconds: order
+                        .last_valid_unix_timestamp_in_seconds,
+                })
+                .collect::<Vec<_>>()
+        };
+        BookSnapshot {
+            sequence_number: self.inner.get_sequence_number(),
+            bids: get_orders(Side::Bid),
+            asks: get_orders(Side::Ask),
+        }
+        .try_to_vec()
+        .unwrap()
+    }
+}
+
+/// Reads a snapshot produced by `MarketWrapper::serialize_book_snapshot`.
+pub fn deserialize_book_snapshot<MarketTraderId: BorshDeserialize>(
+    data: &[u8],
+) -> std::io::Result<BookSnapshot<MarketTraderId>> {
+    BookSnapshot::try_from_slice(data)
+}
+
+/// Quotes a swap against the current state of `market` without sending a transaction. The order
+/// goes through the same matching logic as `Swap`, but runs on a read-only view of the book, so
+/// no state is persisted and no events are recorded. Orders that could rest on the book or that only use
+/// deposited funds are not swaps, and an empty response is returned for them.
+pub fn simulate_swap<
+    MarketTraderId: BorshDeserialize + BorshSerialize + Copy,
+    MarketOrderId: OrderId,
+    MarketRestingOrder: RestingOrder,
+>(
+    market: &dyn Market<MarketTraderId, MarketOrderId, MarketRestingOrder, OrderPacket>,
+    order_packet: &OrderPacket,
+    clock: (u64, u64),
+) -> MatchingEngineResponse {
+    if !order_packet.is_take_only() || order_packet.no_deposit_or_withdrawal() {
+        return MatchingEngineResponse::default();
+    }
+    market
+        .simulate_order(order_packet, clock)
+        .unwrap_or_default()
+}
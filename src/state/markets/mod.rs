@@ -2,11 +2,13 @@ pub mod fifo;
 pub mod market_events;
 pub mod market_traits;
 pub mod market_wrapper;
+pub mod price_accumulator;
 
 pub use fifo::*;
 pub use market_events::*;
 pub use market_traits::*;
 pub use market_wrapper::*;
+pub use price_accumulator::*;
 
 #[cfg(test)]
 pub mod test_market;
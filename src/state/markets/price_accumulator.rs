@@ -0,0 +1,68 @@
+use crate::quantities::{BaseLots, Ticks};
+use bytemuck::{Pod, Zeroable};
+
+/// Running totals of the fills on a market. Averages are computed by reading the accumulator at
+/// two points in time and dividing the differences:
+///
+/// - VWAP = Δcumulative_price_volume / Δtotal_base_lots_filled
+/// - TWAP = Δcumulative_price_slots / Δlast_update_slot
+///
+/// The cumulative price-slot sum only advances when a fill occurs, so a TWAP sampled over a window
+/// must account for the slots elapsed since `last_update_slot` at the last price.
+///
+/// The u128 totals are split into two u64s to keep the struct 8-byte aligned.
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Zeroable, Pod)]
+pub struct PriceAccumulator {
+    cumulative_price_volume: [u64; 2],
+    total_base_lots_filled: [u64; 2],
+    cumulative_price_slots: [u64; 2],
+    /// The price of the most recent fill
+    pub last_price_in_ticks: Ticks,
+    /// The slot of the most recent fill
+    pub last_update_slot: u64,
+}
+
+fn to_u128(value: [u64; 2]) -> u128 {
+    (value[1] as u128) << 64 | value[0] as u128
+}
+
+fn from_u128(value: u128) -> [u64; 2] {
+    [value as u64, (value >> 64) as u64]
+}
+
+impl PriceAccumulator {
+    /// Sum of `price_in_ticks * base_lots_filled` over all fills
+    pub fn cumulative_price_volume(&self) -> u128 {
+        to_u128(self.cumulative_price_volume)
+    }
+
+    /// Sum of `base_lots_filled` over all fills
+    pub fn total_base_lots_filled(&self) -> u128 {
+        to_u128(self.total_base_lots_filled)
+    }
+
+    /// Sum of the last fill price multiplied by the number of slots it remained the last fill price
+    pub fn cumulative_price_slots(&self) -> u128 {
+        to_u128(self.cumulative_price_slots)
+    }
+
+    /// The totals wrap on overflow, so differences between two samples remain correct
+    pub(crate) fn record_fill(&mut self, price_in_ticks: Ticks, base_lots: BaseLots, slot: u64) {
+        let elapsed_slots = slot.saturating_sub(self.last_update_slot);
+        self.cumulative_price_slots = from_u128(
+            self.cumulative_price_slots()
+                .wrapping_add(self.last_price_in_ticks.as_u128() * elapsed_slots as u128),
+        );
+        self.cumulative_price_volume = from_u128(
+            self.cumulative_price_volume()
+                .wrapping_add(price_in_ticks.as_u128() * base_lots.as_u128()),
+        );
+        self.total_base_lots_filled = from_u128(
+            self.total_base_lots_filled()
+                .wrapping_add(base_lots.as_u128()),
+        );
+        self.last_price_in_ticks = price_in_ticks;
+        self.last_update_slot = slot.max(self.last_update_slot);
+    }
+}
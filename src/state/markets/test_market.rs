@@ -2,11 +2,12 @@ use std::collections::VecDeque;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::quantities::*;
+use crate::state::inflight_order::InflightOrder;
 use crate::state::markets::*;
 use crate::state::*;
 use rand::prelude::*;
 use sokoban::node_allocator::NodeAllocatorMap;
-use sokoban::ZeroCopy;
+use sokoban::{RedBlackTree, ZeroCopy};
 
 use crate::state::markets::MarketEvent;
 
@@ -90,6 +91,32 @@ fn layer_orders(
     }
 }
 
+/// Returns a market with 200 post-only orders of random sizes from a few traders, with bids
+/// between 80 and 99 ticks and asks between 101 and 120 ticks
+fn setup_market_with_random_book(rng: &mut StdRng) -> Dex {
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let traders = (0..4).map(|_| rng.gen::<u128>()).collect::<Vec<_>>();
+    for _ in 0..200 {
+        let trader = traders[rng.gen_range(0, traders.len())];
+        let (side, price) = if rng.gen::<bool>() {
+            (Side::Bid, rng.gen_range(80, 100))
+        } else {
+            (Side::Ask, rng.gen_range(101, 121))
+        };
+        market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(side, price, rng.gen_range(1, 20)),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    market
+}
+
 #[test]
 fn test_market_simple() {
     use std::collections::HashSet;
@@ -336,7 +363,17 @@ fn test_market_simple() {
     for m in makers.iter() {
         assert!(registed_makers.contains(m));
         if rng.gen::<f64>() < 0.5 {
-            market.cancel_up_to(m, Side::Bid, None, None, None, true, &mut record_event_fn);
+            market.cancel_up_to(
+                m,
+                Side::Bid,
+                None,
+                None,
+                None,
+                None,
+                true,
+                None,
+                &mut record_event_fn,
+            );
         } else {
             let orders = market
                 .bids
@@ -344,13 +381,23 @@ fn test_market_simple() {
                 .filter(|(_k, v)| v.trader_index == market.traders.get_addr(m) as u64)
                 .map(|(k, _v)| *k)
                 .collect::<Vec<_>>();
-            market.cancel_multiple_orders_by_id(m, &orders, true, &mut record_event_fn);
+            market.cancel_multiple_orders_by_id(m, &orders, true, None, &mut record_event_fn);
         }
     }
 
     for m in makers.iter() {
         let ts1 = *market.traders.get(m).unwrap();
-        market.cancel_up_to(m, Side::Ask, None, None, None, true, &mut record_event_fn);
+        market.cancel_up_to(
+            m,
+            Side::Ask,
+            None,
+            None,
+            None,
+            None,
+            true,
+            None,
+            &mut record_event_fn,
+        );
         let ts2 = *market.traders.get(m).unwrap();
         market.claim_all_funds(m, true);
         assert!(
@@ -392,7 +439,7 @@ fn test_post_only_default() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
     // Cannot place post only order that would match
     assert!(market
         .place_order(
@@ -401,7 +448,7 @@ fn test_post_only_default() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     assert!(market
         .place_order(
@@ -410,7 +457,7 @@ fn test_post_only_default() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
     assert!(market
         .place_order(
             &trader,
@@ -418,7 +465,7 @@ fn test_post_only_default() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
 
     // Cannot place post only order that would match
     assert!(market
@@ -428,7 +475,7 @@ fn test_post_only_default() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     let ladder = market.get_typed_ladder(5);
     assert!(ladder.bids[0].size_in_base_lots == BaseLots::ONE);
@@ -454,7 +501,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
     // Cannot place post only order that would match if reject flag is true
     assert!(market
         .place_order(
@@ -463,7 +510,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     assert!(market
         .place_order(
@@ -472,7 +519,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
     assert!(market
         .place_order(
             &trader,
@@ -480,7 +527,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
 
     // Cannot place post only order that would match if reject flag is true
     assert!(market
@@ -490,7 +537,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     // Post only is amended if reject flag is false
     assert!(market
@@ -500,7 +547,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
 
     // Post only is amended if reject flag is false
     assert!(market
@@ -510,7 +557,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
 
     let ladder = market.get_typed_ladder(5);
     assert!(ladder.bids[0].size_in_base_lots == BaseLots::new(2));
@@ -520,7 +567,7 @@ fn test_post_only_rejection() {
     assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(2));
     assert!(ladder.asks[0].price_in_ticks == Ticks::new(102));
 
-    market.cancel_all_orders(&trader, true, &mut record_event_fn);
+    market.cancel_all_orders(&trader, true, None, &mut record_event_fn);
 
     // Price of the ask is set to the minimum price (1 tick) if the book is empty
     assert!(market
@@ -530,7 +577,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
 
     // Test rejection of post_only if amended price is below min market price
     assert!(market
@@ -540,7 +587,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     // Test rejection of post_only if original price is below min market price
     assert!(market
@@ -550,7 +597,7 @@ fn test_post_only_rejection() {
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
     let ladder = market.get_typed_ladder(5);
     assert!(ladder.asks[0].price_in_ticks == Ticks::ONE);
@@ -574,7 +621,7 @@ fn test_cancel_all() {
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
+            .is_ok());
     }
 
     // Place 10 asks starting from 102.
@@ -586,2005 +633,7164 @@ fn test_cancel_all() {
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
+            .is_ok());
     }
-    market.cancel_all_orders(&trader, true, &mut record_event_fn);
+    market.cancel_all_orders(&trader, true, None, &mut record_event_fn);
 
     assert!(market.asks.is_empty());
     assert!(market.bids.is_empty());
 }
 
 #[test]
-fn test_limit_orders_with_self_trade() {
+fn test_cancel_all_bounded() {
     let mut rng = StdRng::seed_from_u64(2);
     let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
-    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 5),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-    let ladder = market.get_typed_ladder(1);
-    // Assert that cancel provide yields the correct order on the book
-    assert!(ladder.bids[0].size_in_base_lots == BaseLots::new(5));
-    // Can place limit order that would self trade
-    let (_order, matching_engine_response) = market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Ask, 100, 10),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
+
+    // Alternate bids and asks so that cancellation order does not follow the book order.
+    let mut order_ids = vec![];
+    for i in 0..6 {
+        let order_packet = if i % 2 == 0 {
+            OrderPacket::new_post_only_default(Side::Bid, 100 - i, 1)
+        } else {
+            OrderPacket::new_post_only_default(Side::Ask, 102 + i, 1)
+        };
+        let (order_id, _) = market
+            .place_order(
+                &trader,
+                order_packet,
+                &mut |e| event_recorder.push_back(e),
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        order_ids.push(order_id.unwrap());
+    }
+    event_recorder.clear();
+
+    let (response, has_remaining_orders) = market
+        .cancel_all_orders_bounded(&trader, 4, None, true, None, &mut |e| {
+            event_recorder.push_back(e)
+        })
         .unwrap();
-    let mut res = MatchingEngineResponse::default();
-    res.post_base_lots(BaseLots::new(10));
-    assert!(matching_engine_response == res);
-    let ladder = market.get_typed_ladder(1);
-    // Assert that cancel provide yields the correct order on the book
-    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(10));
+    assert!(has_remaining_orders);
+    assert_eq!(response.num_base_lots_out, BaseLots::new(2));
+    let cancelled = event_recorder
+        .drain(..)
+        .filter_map(|e| match e {
+            MarketEvent::Reduce {
+                order_sequence_number,
+                ..
+            } => Some(order_sequence_number),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // The most recently placed orders are cancelled first
+    assert_eq!(
+        cancelled,
+        order_ids
+            .iter()
+            .rev()
+            .take(4)
+            .map(|o| o.order_sequence_number)
+            .collect::<Vec<_>>()
+    );
 
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order(
-                Side::Bid,
-                100,
-                15,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_none());
-    let (order, matching_engine_response) = market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order(
-                Side::Bid,
-                100,
-                15,
-                SelfTradeBehavior::DecrementTake,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
+    let (_, has_remaining_orders) = market
+        .cancel_all_orders_bounded(&trader, 4, None, true, None, &mut |e| {
+            event_recorder.push_back(e)
+        })
         .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::default();
+    assert!(!has_remaining_orders);
+    assert!(market.asks.is_empty());
+    assert!(market.bids.is_empty());
 
-    res.post_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
-            / market.base_lots_per_base_unit,
-    );
-    res.use_free_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
-            / market.base_lots_per_base_unit,
+    // Other traders' orders count towards the search bound
+    let other_trader = rng.gen::<u128>();
+    for (owner, price) in [(other_trader, 101), (other_trader, 102), (trader, 103)] {
+        market
+            .place_order(
+                &owner,
+                OrderPacket::new_post_only_default(Side::Ask, price, 1),
+                &mut |e| event_recorder.push_back(e),
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    let (_, has_remaining_orders) = market
+        .cancel_all_orders_bounded(&trader, 4, Some(2), true, None, &mut |e| {
+            event_recorder.push_back(e)
+        })
+        .unwrap();
+    assert!(has_remaining_orders);
+    assert_eq!(market.asks.len(), 3);
+    let (_, has_remaining_orders) = market
+        .cancel_all_orders_bounded(&trader, 4, Some(3), true, None, &mut |e| {
+            event_recorder.push_back(e)
+        })
+        .unwrap();
+    assert!(!has_remaining_orders);
+    assert_eq!(market.asks.len(), 2);
+
+    // A search that stops before the end of the book may have missed some of the trader's orders
+    let (_, has_remaining_orders) = market
+        .cancel_all_orders_bounded(&trader, 4, Some(1), true, None, &mut |e| {
+            event_recorder.push_back(e)
+        })
+        .unwrap();
+    assert!(has_remaining_orders);
+}
+
+#[test]
+fn test_simulate_swap() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market_with_params(10000, 100, 5);
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+    for i in 0..5 {
+        assert!(market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+
+    let order_packet = OrderPacket::new_ioc_by_lots(
+        Side::Bid,
+        103,
+        25,
+        SelfTradeBehavior::CancelProvide,
+        None,
+        rng.gen::<u128>(),
+        false,
     );
-    assert!(matching_engine_response == res);
-    let ladder = market.get_typed_ladder(1);
-    assert!(ladder.bids[0].size_in_base_lots == BaseLots::new(5));
+    let ladder = market.get_typed_ladder(5);
+    let simulated = simulate_swap(&market, &order_packet, get_clock_fn());
+    assert_eq!(simulated.num_base_lots_out, BaseLots::new(25));
+    // The simulation does not modify the book
+    assert!(market.get_typed_ladder(5) == ladder);
 
-    // Try to trade with DecrementTake for more than the order size
-    let (order, matching_engine_response) = market
+    let (_, matching_engine_response) = market
         .place_order(
             &taker,
-            OrderPacket::new_limit_order(
-                Side::Ask,
-                100,
-                10,
-                SelfTradeBehavior::DecrementTake,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            order_packet,
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::new_from_sell(
-        BaseLots::new(5),
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
-            / market.base_lots_per_base_unit,
+    assert_eq!(simulated, matching_engine_response);
+
+    // Orders that can rest on the book are not swaps
+    assert_eq!(
+        simulate_swap(
+            &market,
+            &OrderPacket::new_post_only_default(Side::Bid, 90, 10),
+            get_clock_fn()
+        ),
+        MatchingEngineResponse::default()
     );
-    res.post_base_lots(BaseLots::new(5));
-    assert!(matching_engine_response == res);
-    let ladder = market.get_typed_ladder(1);
-    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(5));
 
-    // Try to trade with DecrementTake for less than the order size
-    let (order, matching_engine_response) = market
-        .place_order(
+    // A FOK order that cannot be filled completely is voided, so nothing would be swapped
+    let fok_order_packet = OrderPacket::new_fok_buy_with_limit_price(
+        104,
+        1000,
+        SelfTradeBehavior::CancelProvide,
+        None,
+        rng.gen::<u128>(),
+        false,
+    );
+    assert_eq!(
+        simulate_swap(&market, &fok_order_packet, get_clock_fn()),
+        MatchingEngineResponse::default()
+    );
+    assert_eq!(
+        market.place_order(
             &taker,
-            OrderPacket::new_limit_order(
-                Side::Bid,
-                100,
-                1,
-                SelfTradeBehavior::DecrementTake,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            fok_order_packet,
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .unwrap();
-
-    let res = MatchingEngineResponse::default();
-    assert!(order.is_none());
-    assert!(matching_engine_response == res);
-    let ladder = market.get_typed_ladder(1);
-    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(4));
+        ),
+        Err(PhoenixMatchError::FillOrKillNotFilled)
+    );
 }
 
 #[test]
-fn test_limit_orders_with_free_lots() {
+fn test_fee_tiers() {
     let mut rng = StdRng::seed_from_u64(2);
-    let mut market = setup_market();
+    let mut market = setup_market_with_params(10000, 100, 100);
+    market.set_fee_tiers([0, 50, 0, 0]);
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    let default_taker = rng.gen::<u128>();
+    let discounted_taker = rng.gen::<u128>();
+    let vip_taker = rng.gen::<u128>();
+    market.get_or_register_trader(&discounted_taker).unwrap();
+    market
+        .get_trader_state_mut(&discounted_taker)
+        .unwrap()
+        .fee_tier = 2;
+    market.get_or_register_trader(&vip_taker).unwrap();
+    market.get_trader_state_mut(&vip_taker).unwrap().fee_tier = 1;
 
-    // Place 2 bids for 100 and 95, then fill them both
     assert!(market
         .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 5),
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 30),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
+
+    // 10 base lots at 100 ticks cost 100000 quote lots before fees
+    for (taker, expected_quote_lots_in) in [
+        (default_taker, 101000),
+        (discounted_taker, 100500),
+        (vip_taker, 100000),
+    ] {
+        let (_, matching_engine_response) = market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    100,
+                    10,
+                    SelfTradeBehavior::CancelProvide,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert_eq!(
+            matching_engine_response.num_base_lots_out,
+            BaseLots::new(10)
+        );
+        assert_eq!(
+            matching_engine_response.num_quote_lots_in,
+            QuoteLots::new(expected_quote_lots_in)
+        );
+    }
 
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 95, 15),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+    // Traders in a non-default fee tier keep their tier when they withdraw all of their funds
+    market.claim_all_funds(&vip_taker, true).unwrap();
+    assert_eq!(market.get_trader_state(&vip_taker).unwrap().fee_tier, 1);
+    market.claim_all_funds(&maker, true).unwrap();
+    assert!(market.get_trader_state(&maker).is_none());
+}
 
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_limit_order_default(Side::Ask, 95, 15,),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+#[test]
+fn test_get_base_lots_at_tick() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let market = setup_market_with_random_book(&mut rng);
 
-    // Place an offer that utilizes only free lots
-    let (order, matching_engine_response) = market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Ask, 100, 5),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::default();
-    res.post_base_lots(BaseLots::new(5));
-    res.use_free_base_lots(BaseLots::new(5));
-    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(u64::MAX);
+    for (side, levels) in [(Side::Bid, &ladder.bids), (Side::Ask, &ladder.asks)] {
+        for level in levels.iter() {
+            assert_eq!(
+                market.get_base_lots_at_tick(side, level.price_in_ticks),
+                level.size_in_base_lots
+            );
+        }
+    }
+    assert_eq!(
+        market.get_base_lots_at_tick(Side::Bid, Ticks::new(100)),
+        BaseLots::ZERO
+    );
+    assert_eq!(
+        market.get_base_lots_at_tick(Side::Ask, Ticks::new(90)),
+        BaseLots::ZERO
+    );
+}
 
-    // Place an offer that utilizes both free and new lots
-    let (order, matching_engine_response) = market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Ask, 100, 20),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::default();
-    res.post_base_lots(BaseLots::new(20));
-    res.use_free_base_lots(BaseLots::new(10));
+#[test]
+fn test_level_iterator() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let market = setup_market_with_random_book(&mut rng);
+
+    let ladder = market.get_typed_ladder(u64::MAX);
+    for (side, levels) in [(Side::Bid, &ladder.bids), (Side::Ask, &ladder.asks)] {
+        let expected = levels
+            .iter()
+            .map(|level| (level.price_in_ticks, level.size_in_base_lots))
+            .collect::<Vec<_>>();
+        assert_eq!(market.get_levels(side).collect::<Vec<_>>(), expected);
+        assert_eq!(
+            market.get_levels(side).take(3).collect::<Vec<_>>(),
+            expected[..3]
+        );
+    }
 
-    assert!(matching_engine_response == res);
+    // Adapters compose with the iterator, e.g. the size within 5 ticks of the best bid
+    let best_bid = market.get_levels(Side::Bid).next().unwrap().0;
+    let size_near_top = market
+        .get_levels(Side::Bid)
+        .take_while(|(price_in_ticks, _)| *price_in_ticks + Ticks::new(5) > best_bid)
+        .fold(BaseLots::ZERO, |acc, (_, base_lots)| acc + base_lots);
+    assert_eq!(
+        size_near_top,
+        ladder
+            .bids
+            .iter()
+            .filter(|level| level.price_in_ticks + Ticks::new(5) > best_bid)
+            .fold(BaseLots::ZERO, |acc, level| acc + level.size_in_base_lots)
+    );
 
-    // Place a self trade to free up lots
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+    let empty_market = setup_market();
+    assert!(empty_market.get_levels(Side::Ask).next().is_none());
+}
 
-    // Place an offer that matches with the book and posts using free lots
+#[test]
+fn test_post_only_max_tick_adjustment() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let maker = rng.gen::<u128>();
+    let trader = rng.gen::<u128>();
     assert!(market
         .place_order(
-            &taker,
-            OrderPacket::new_limit_order_default(Side::Bid, 101, 10),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-    let (order, matching_engine_response) = market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Ask, 100, 50),
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::new_from_sell(
-        BaseLots::new(10),
-        Ticks::new(101) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
-            / market.base_lots_per_base_unit,
-    );
-    res.post_base_lots(BaseLots::new(40));
-    res.use_free_base_lots(BaseLots::new(25));
-    assert!(matching_engine_response == res);
-
+        .is_ok());
     assert!(market
         .place_order(
-            &taker,
-            OrderPacket::new_limit_order_default(Side::Bid, 105, 55,),
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 90, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .is_ok());
+
+    let post_only =
+        |side: Side, price_in_ticks: u64, max_tick_adjustment: Option<u64>| OrderPacket::PostOnly {
+            side,
+            price_in_ticks: Ticks::new(price_in_ticks),
+            num_base_lots: BaseLots::new(1),
+            client_order_id: 0,
+            reject_post_only: false,
+            use_only_deposited_funds: false,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+            fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment,
+            require_top_of_book: false,
+            valid_for_slots: None,
+        };
 
-    // Place a bid using some of the freed lots
-    let (order, matching_engine_response) = market
-        .place_order(
+    // A bid at 110 would be amended to 99, which is 11 ticks away
+    assert_eq!(
+        market.place_order(
             &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 20),
+            post_only(Side::Bid, 110, Some(10)),
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::default();
-
-    res.post_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(20)
-            / market.base_lots_per_base_unit,
-    );
-    res.use_free_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(20)
-            / market.base_lots_per_base_unit,
+        ),
+        Err(PhoenixMatchError::PostOnlyInvalidPrice)
     );
-    assert!(matching_engine_response == res);
-
-    // Place a bid using the rest of the freed lots
-    let (order, matching_engine_response) = market
+    let (order_id, _) = market
         .place_order(
             &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 50),
+            post_only(Side::Bid, 110, Some(11)),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::default();
-    res.post_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
-            / market.base_lots_per_base_unit,
-    );
-    res.use_free_quote_lots(
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
-            / market.base_lots_per_base_unit,
-    );
-    assert!(matching_engine_response == res);
+    assert_eq!(order_id.unwrap().price_in_ticks, Ticks::new(99));
 
-    // Place a bid and self trade against it to free up lots
-    println!("ladder: {:?}", market.get_typed_ladder(3));
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 120, 50),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-    assert!(market
-        .place_order(
+    // The best bid is now 99, so an ask at 85 would be amended to 100, which is 15 ticks away
+    assert_eq!(
+        market.place_order(
             &trader,
-            OrderPacket::new_limit_order_default(Side::Ask, 120, 25),
+            post_only(Side::Ask, 85, Some(14)),
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .is_some());
-
-    // Place a bid that matches with the book and posts using the newly freed lots
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_limit_order_default(Side::Ask, 110, 25),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-    let (order, matching_engine_response) = market
+        ),
+        Err(PhoenixMatchError::PostOnlyInvalidPrice)
+    );
+    let (order_id, _) = market
         .place_order(
             &trader,
-            OrderPacket::new_limit_order_default(Side::Bid, 111, 75),
+            post_only(Side::Ask, 85, None),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(order.is_some());
-    let mut res = MatchingEngineResponse::new_from_buy(
-        Ticks::new(110) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(25)
-            / market.base_lots_per_base_unit,
-        BaseLots::new(25),
-    );
-    res.post_quote_lots(
-        Ticks::new(111) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
-            / market.base_lots_per_base_unit,
-    );
-    res.use_free_quote_lots(
-        Ticks::new(120) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
-            / market.base_lots_per_base_unit,
-    );
+    assert_eq!(order_id.unwrap().price_in_ticks, Ticks::new(100));
+}
 
-    assert!(matching_engine_response == res);
+#[test]
+fn test_post_only_require_top_of_book() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let trader = rng.gen::<u128>();
+
+    let post_only = |side: Side, price_in_ticks: u64| OrderPacket::PostOnly {
+        side,
+        price_in_ticks: Ticks::new(price_in_ticks),
+        num_base_lots: BaseLots::new(1),
+        client_order_id: 0,
+        reject_post_only: true,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: true,
+        valid_for_slots: None,
+    };
+
+    // On an empty book, any price is the top of the book
+    for (side, price_in_ticks) in [(Side::Bid, 90), (Side::Ask, 110)] {
+        assert!(market
+            .place_order(
+                &trader,
+                post_only(side, price_in_ticks),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+
+    // Orders behind the best price are rejected
+    for (side, price_in_ticks) in [(Side::Bid, 89), (Side::Ask, 111)] {
+        assert_eq!(
+            market.place_order(
+                &trader,
+                post_only(side, price_in_ticks),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            ),
+            Err(PhoenixMatchError::PostOnlyNotTopOfBook)
+        );
+    }
+
+    // Orders tied with or improving on the best price are placed
+    for (side, price_in_ticks) in [
+        (Side::Bid, 90),
+        (Side::Bid, 91),
+        (Side::Ask, 110),
+        (Side::Ask, 109),
+    ] {
+        assert!(market
+            .place_order(
+                &trader,
+                post_only(side, price_in_ticks),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+    assert_eq!(market.get_book(Side::Bid).len(), 3);
+    assert_eq!(market.get_book(Side::Ask).len(), 3);
 }
 
 #[test]
-fn test_orders_with_only_free_funds() {
+fn test_price_accumulator() {
     let mut rng = StdRng::seed_from_u64(2);
     let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
+    assert!(market.get_twap().is_none());
+    market.set_price_accumulator_enabled(true);
+    assert_eq!(market.get_twap(), Some(PriceAccumulator::default()));
+
+    let maker = rng.gen::<u128>();
     let taker = rng.gen::<u128>();
+    for price in [100, 102] {
+        assert!(market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
 
-    // Note that both the taker and trader will be registered after attempting to place their first limit order
-    // Limit order fails as the taker has no free funds
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_post_only(Side::Bid, 100, 5, 0, false, true,),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_none());
+    for (slot, num_base_lots) in [(10, 5), (20, 10)] {
+        assert!(market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    102,
+                    num_base_lots,
+                    SelfTradeBehavior::CancelProvide,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut || (slot, 0),
+            )
+            .is_ok());
+    }
 
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_post_only(Side::Bid, 100, 5, 0, false, false,),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+    let price_accumulator = market.get_twap().unwrap();
+    // Fills of 5 @ 100, 5 @ 100 and 5 @ 102
+    assert_eq!(price_accumulator.cumulative_price_volume(), 1510);
+    assert_eq!(price_accumulator.total_base_lots_filled(), 15);
+    // The price of 100 was the last fill price from slot 10 until slot 20
+    assert_eq!(price_accumulator.cumulative_price_slots(), 1000);
+    assert_eq!(price_accumulator.last_price_in_ticks, Ticks::new(102));
+    assert_eq!(price_accumulator.last_update_slot, 20);
+
+    market.set_price_accumulator_enabled(false);
+    assert!(market.get_twap().is_none());
+}
 
-    // IOC order fails as the taker has no free funds
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_ioc_by_lots(
-                Side::Ask,
-                100,
-                5,
-                SelfTradeBehavior::CancelProvide,
+#[test]
+fn test_iceberg_orders() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut reserves = Box::new(RedBlackTree::<FIFOOrderId, IcebergReserve, 128>::default());
+    reserves.initialize();
+    let mut event_recorder = VecDeque::new();
+
+    let iceberg_maker = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+    let client_order_id = rng.gen::<u128>();
+
+    for display_base_lots in [0, 26] {
+        assert_eq!(
+            market.place_order_with_iceberg_reserves(
+                &iceberg_maker,
+                OrderPacket::new_iceberg_order(
+                    Side::Ask,
+                    100,
+                    25,
+                    display_base_lots,
+                    SelfTradeBehavior::Abort,
+                    client_order_id,
+                    false,
+                ),
                 None,
-                0,
-                true,
+                &mut *reserves,
+                &mut |e| event_recorder.push_back(e),
+                &mut get_clock_fn,
             ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_none());
+            Err(PhoenixMatchError::InvalidIcebergParams)
+        );
+    }
 
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_ioc_by_lots(
+    // Iceberg orders need somewhere to store their reserve
+    assert_eq!(
+        market.place_order(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
                 Side::Ask,
                 100,
-                5,
-                SelfTradeBehavior::CancelProvide,
-                None,
-                0,
+                25,
+                10,
+                SelfTradeBehavior::Abort,
+                client_order_id,
                 false,
             ),
-            &mut record_event_fn,
+            &mut |e| event_recorder.push_back(e),
             &mut get_clock_fn,
-        )
-        .is_some());
+        ),
+        Err(PhoenixMatchError::IcebergOrdersDisabled)
+    );
 
-    // Order succeeds with sufficient deposited funds
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_limit_order(
+    // Only the display size is shown on the book, but the full size is locked
+    let (order_id, matching_engine_response) = market
+        .place_order_with_iceberg_reserves(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
                 Side::Ask,
                 100,
-                5,
+                25,
+                10,
                 SelfTradeBehavior::Abort,
-                None,
-                0,
-                true,
+                client_order_id,
+                false,
             ),
-            &mut record_event_fn,
+            None,
+            &mut *reserves,
+            &mut |e| event_recorder.push_back(e),
             &mut get_clock_fn,
         )
-        .is_some());
+        .unwrap();
+    let order_id = order_id.unwrap();
+    let resting_order = *market.get_book(Side::Ask).get(&order_id).unwrap();
+    assert_eq!(resting_order.num_base_lots, BaseLots::new(10));
+    let iceberg_reserve = *reserves.get(&order_id).unwrap();
+    assert_eq!(iceberg_reserve.reserve_base_lots, BaseLots::new(15));
+    assert_eq!(iceberg_reserve.display_base_lots, BaseLots::new(10));
+    assert_eq!(iceberg_reserve.client_order_id(), client_order_id);
+    assert_eq!(
+        matching_engine_response.num_base_lots_posted,
+        BaseLots::new(25)
+    );
+    assert_eq!(
+        market.get_base_lots_at_tick(Side::Ask, Ticks::new(100)),
+        BaseLots::new(10)
+    );
+    let iceberg_maker_state = market.get_trader_state(&iceberg_maker).unwrap();
+    assert_eq!(iceberg_maker_state.base_lots_locked, BaseLots::new(25));
+    let iceberg_maker_index = market.get_trader_index(&iceberg_maker).unwrap() as u64;
 
-    assert!(market
+    market
         .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 5),
+            &mut |e| event_recorder.push_back(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let maker_index = market.get_trader_index(&maker).unwrap() as u64;
+    event_recorder.clear();
+
+    // Filling the visible size replenishes it from the reserve at the back of the queue, with the
+    // client order id of the original order
+    let next_order_id = market.get_next_order_id(Side::Ask, Ticks::new(100));
+    market
+        .place_order_with_iceberg_reserves(
             &taker,
-            OrderPacket::new_limit_order(
+            OrderPacket::new_ioc_by_lots(
                 Side::Bid,
                 100,
-                5,
+                12,
                 SelfTradeBehavior::Abort,
                 None,
                 0,
                 false,
             ),
-            &mut record_event_fn,
+            None,
+            &mut *reserves,
+            &mut |e| event_recorder.push_back(e),
             &mut get_clock_fn,
         )
-        .is_some());
+        .unwrap();
+    assert!(event_recorder.iter().any(|e| matches!(
+        e,
+        MarketEvent::Place {
+            order_sequence_number,
+            client_order_id: placed_client_order_id,
+            base_lots_placed,
+            ..
+        } if *order_sequence_number == next_order_id.order_sequence_number
+            && *placed_client_order_id == client_order_id
+            && *base_lots_placed == BaseLots::new(10)
+    )));
+    let queue = market
+        .get_book(Side::Ask)
+        .iter()
+        .map(|(order_id, resting_order)| {
+            (
+                *order_id,
+                resting_order.trader_index,
+                resting_order.num_base_lots,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue[0].1, maker_index);
+    assert_eq!(queue[0].2, BaseLots::new(3));
+    assert_eq!(
+        queue[1],
+        (next_order_id, iceberg_maker_index, BaseLots::new(10))
+    );
+    // The reserve follows the replenished order
+    assert!(reserves.get(&order_id).is_none());
+    assert_eq!(
+        reserves.get(&next_order_id).unwrap().reserve_base_lots,
+        BaseLots::new(5)
+    );
 
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_limit_order(
-                Side::Ask,
-                100,
-                5,
+    // Cancelling the iceberg order unlocks the visible size along with the reserve
+    market.cancel_all_orders(&iceberg_maker, false, Some(&mut *reserves), &mut |e| {
+        event_recorder.push_back(e)
+    });
+    assert!(reserves.get(&next_order_id).is_none());
+    let iceberg_maker_state = market.get_trader_state(&iceberg_maker).unwrap();
+    assert_eq!(iceberg_maker_state.base_lots_locked, BaseLots::ZERO);
+    assert_eq!(iceberg_maker_state.base_lots_free, BaseLots::new(15));
+
+    // Bids lock the quote lots of the reserve separately from the visible order, and the total
+    // stays the same as the visible size is replenished
+    market
+        .place_order_with_iceberg_reserves(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
+                Side::Bid,
+                99,
+                25,
+                10,
                 SelfTradeBehavior::Abort,
-                None,
-                0,
+                client_order_id,
                 false,
             ),
-            &mut record_event_fn,
+            None,
+            &mut *reserves,
+            &mut |e| event_recorder.push_back(e),
             &mut get_clock_fn,
         )
-        .is_some());
-
-    // Order succeeds as the trader has funds to cover the matched amount, but not the sent amount
-    assert!(market
-        .place_order(
-            &trader,
+        .unwrap();
+    let quote_lots_locked = market
+        .get_trader_state(&iceberg_maker)
+        .unwrap()
+        .quote_lots_locked;
+    market
+        .place_order_with_iceberg_reserves(
+            &taker,
             OrderPacket::new_ioc_by_lots(
-                Side::Bid,
-                100,
-                6,
+                Side::Ask,
+                99,
+                10,
                 SelfTradeBehavior::Abort,
                 None,
                 0,
-                true,
+                false,
             ),
-            &mut record_event_fn,
+            None,
+            &mut *reserves,
+            &mut |e| event_recorder.push_back(e),
             &mut get_clock_fn,
         )
-        .is_some());
-}
-
-fn seed_market_with_orders(
-    trader: &TraderId,
-    market: &mut Dex,
-    record_event_fn: &mut dyn FnMut(MarketEvent<TraderId>),
-) {
-    for i in 1..11 {
-        assert!(market
-            .place_order(
-                trader,
-                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10),
-                record_event_fn,
-                &mut get_clock_fn,
-            )
-            .is_some());
-        assert!(market
-            .place_order(
-                trader,
-                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10),
-                record_event_fn,
-                &mut get_clock_fn,
-            )
-            .is_some());
-    }
+        .unwrap();
+    let (order_id, resting_order) = market
+        .get_book(Side::Bid)
+        .iter()
+        .map(|(order_id, resting_order)| (*order_id, *resting_order))
+        .next()
+        .unwrap();
+    assert_eq!(resting_order.num_base_lots, BaseLots::new(10));
+    assert!(reserves.get(&order_id).is_some());
+    let iceberg_maker_state = market.get_trader_state(&iceberg_maker).unwrap();
+    assert_eq!(
+        iceberg_maker_state.quote_lots_locked,
+        QuoteLots::new(quote_lots_locked.as_u64() * 15 / 25)
+    );
+    market.cancel_all_orders(&iceberg_maker, false, Some(&mut *reserves), &mut |e| {
+        event_recorder.push_back(e)
+    });
+    assert_eq!(reserves.len(), 0);
+    assert_eq!(
+        market
+            .get_trader_state(&iceberg_maker)
+            .unwrap()
+            .quote_lots_locked,
+        QuoteLots::ZERO
+    );
 }
 
 #[test]
-fn test_fok_and_ioc_limit_1() {
+fn test_iceberg_reserves_released_when_order_leaves_book() {
     let mut rng = StdRng::seed_from_u64(2);
     let mut market = Box::new(setup_market());
+    let mut reserves = Box::new(RedBlackTree::<FIFOOrderId, IcebergReserve, 128>::default());
+    reserves.initialize();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+    let iceberg_maker = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    let evicter = rng.gen::<u128>();
 
-    // buy through 3 levels of offers
-    let expected_quote_lots_used = Ticks::new(102) // average price 102
-                * market.tick_size_in_quote_lots_per_base_unit
-                * BaseLots::new(30) // clear 3 levels of 10 base lots each
-                / market.base_lots_per_base_unit;
-    let (order, matching_engine_response) = market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                103,
-                30,
+    // Reducing the visible order keeps the reserve until the order leaves the book
+    let (order_id, _) = market
+        .place_order_with_iceberg_reserves(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
+                Side::Ask,
+                100,
+                25,
+                10,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
+            None,
+            &mut *reserves,
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(order.is_none());
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_buy(expected_quote_lots_used, BaseLots::new(30))
+    let order_id = order_id.unwrap();
+    market
+        .reduce_order(
+            &iceberg_maker,
+            &order_id,
+            Side::Ask,
+            Some(BaseLots::new(4)),
+            false,
+            Some(&mut *reserves),
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(
+        reserves.get(&order_id).unwrap().reserve_base_lots,
+        BaseLots::new(15)
     );
-
-    // sell through 3 levels of bids
-    let expected_quote_lots_used = Ticks::new(98) // average price 98
-                * market.tick_size_in_quote_lots_per_base_unit
-                * BaseLots::new(30) // clear 3 levels of 10 base lots each
-                / market.base_lots_per_base_unit;
-    let (order, matching_engine_response) = market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                97,
-                30,
+    assert_eq!(
+        market
+            .get_trader_state(&iceberg_maker)
+            .unwrap()
+            .base_lots_locked,
+        BaseLots::new(21)
+    );
+    // The funds of the reserve are claimed along with the rest of the order
+    let response = market
+        .reduce_order(
+            &iceberg_maker,
+            &order_id,
+            Side::Ask,
+            Some(BaseLots::new(6)),
+            true,
+            Some(&mut *reserves),
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(21));
+    assert_eq!(reserves.len(), 0);
+    let iceberg_maker_state = market.get_trader_state(&iceberg_maker).unwrap();
+    assert_eq!(iceberg_maker_state.base_lots_locked, BaseLots::ZERO);
+    assert_eq!(iceberg_maker_state.base_lots_free, BaseLots::new(4));
+
+    // An iceberg order that is evicted from a full book releases its reserve
+    for _ in 0..market.get_book(Side::Ask).capacity() - 1 {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, 1000, 1),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    market
+        .place_order_with_iceberg_reserves(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
+                Side::Ask,
+                1500,
+                25,
+                10,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
+            None,
+            &mut *reserves,
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(order.is_none());
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_sell(BaseLots::new(30), expected_quote_lots_used,)
-    );
+    assert!(market.is_book_full(Side::Ask));
+    assert_eq!(reserves.len(), 1);
+    market
+        .place_order_with_iceberg_reserves(
+            &evicter,
+            OrderPacket::new_post_only_default(Side::Ask, 1001, 1),
+            None,
+            &mut *reserves,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(reserves.len(), 0);
+    let iceberg_maker_state = market.get_trader_state(&iceberg_maker).unwrap();
+    assert_eq!(iceberg_maker_state.base_lots_locked, BaseLots::ZERO);
+    // The free base lots left over from the reduced order were used for the evicted order
+    assert_eq!(iceberg_maker_state.base_lots_free, BaseLots::new(25));
 }
 
 #[test]
-fn test_fok_and_ioc_limit_2() {
+fn test_get_next_order_id() {
     let mut rng = StdRng::seed_from_u64(2);
-    let mut market = Box::new(setup_market());
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
+    for (side, price) in [(Side::Bid, 99), (Side::Ask, 101), (Side::Bid, 98)] {
+        let next_order_id = market.get_next_order_id(side, Ticks::new(price));
+        let (order_id, _) = market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(side, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert_eq!(order_id, Some(next_order_id));
+        // The preview is stale once the order is placed
+        assert_ne!(
+            market.get_next_order_id(side, Ticks::new(price)),
+            next_order_id
+        );
+    }
+}
 
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+#[test]
+fn test_get_remaining_sequence_numbers() {
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    // (2) FOK should fail if the tick/lot budget is not enough to fill the order (changed price limit)
-    assert!(market
+    let remaining = market.get_remaining_sequence_numbers();
+    assert_eq!(
+        remaining,
+        MAX_ORDER_SEQUENCE_NUMBER - market.get_sequence_number()
+    );
+    market
         .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                102,
-                30,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            &1,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
-    assert!(market
-        .place_order(
+        .unwrap();
+    assert_eq!(market.get_remaining_sequence_numbers(), remaining - 1);
+}
+
+#[test]
+fn test_get_book_imbalance() {
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    assert_eq!(market.get_book_imbalance(5), None);
+    for (side, price, size) in [
+        (Side::Bid, 99, 30),
+        (Side::Bid, 98, 10),
+        (Side::Bid, 97, 100),
+        (Side::Ask, 101, 10),
+        (Side::Ask, 102, 10),
+    ] {
+        market
+            .place_order(
+                &1,
+                OrderPacket::new_post_only_default(side, price, size),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    // 40 bid lots and 20 ask lots in the top two levels
+    assert_eq!(market.get_book_imbalance(2), Some(3333));
+    // 140 bid lots and 20 ask lots in the top three levels
+    assert_eq!(market.get_book_imbalance(3), Some(7500));
+    assert_eq!(market.get_book_imbalance(0), None);
+}
+
+#[test]
+fn test_get_detailed_ladder() {
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    assert_eq!(
+        market.get_detailed_ladder(5),
+        DetailedLadder {
+            bids: vec![],
+            asks: vec![],
+        }
+    );
+    for (trader, side, price, size) in [
+        (1, Side::Bid, 99, 30),
+        (2, Side::Bid, 99, 10),
+        (1, Side::Bid, 98, 10),
+        (3, Side::Bid, 99, 5),
+        (1, Side::Bid, 97, 100),
+        (1, Side::Ask, 101, 10),
+        (2, Side::Ask, 102, 10),
+        (2, Side::Ask, 101, 20),
+    ] {
+        market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(side, price, size),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    let level = |price_in_ticks, size_in_base_lots, num_orders| DetailedLadderOrder {
+        price_in_ticks: Ticks::new(price_in_ticks),
+        size_in_base_lots: BaseLots::new(size_in_base_lots),
+        num_orders,
+    };
+    let ladder = market.get_detailed_ladder(2);
+    assert_eq!(ladder.bids, vec![level(99, 45, 3), level(98, 10, 1)]);
+    assert_eq!(ladder.asks, vec![level(101, 30, 2), level(102, 10, 1)]);
+
+    // The sizes match the typed ladder
+    let typed_ladder = market.get_typed_ladder(5);
+    let detailed_ladder = market.get_detailed_ladder(5);
+    for (typed, detailed) in [
+        (&typed_ladder.bids, &detailed_ladder.bids),
+        (&typed_ladder.asks, &detailed_ladder.asks),
+    ] {
+        assert_eq!(typed.len(), detailed.len());
+        for (typed_level, detailed_level) in typed.iter().zip(detailed.iter()) {
+            assert_eq!(typed_level.price_in_ticks, detailed_level.price_in_ticks);
+            assert_eq!(
+                typed_level.size_in_base_lots,
+                detailed_level.size_in_base_lots
+            );
+        }
+    }
+    assert!(market.get_detailed_ladder(0).bids.is_empty());
+}
+
+#[test]
+fn test_volatility_band() {
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let (maker, taker) = (1, 2);
+    market.set_volatility_band(BaseLots::new(5), 10, 100);
+    for price in [100, 110] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    let buy = |market: &mut Dex,
+               record_event_fn: &mut dyn FnMut(MarketEvent<TraderId>),
+               slot: u64,
+               price: u64,
+               size: u64| {
+        market.place_order(
             &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                98,
-                30,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                price,
+                size,
                 SelfTradeBehavior::Abort,
                 None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
+            record_event_fn,
+            &mut || (slot, 0),
+        )
+    };
+
+    // A fill of 8 base lots at 100 ticks starts the cooldown at slot 50
+    buy(&mut market, &mut record_event_fn, 50, 100, 8).unwrap();
+    assert_eq!(market.get_last_trade_price(), Ticks::new(100));
+    assert_eq!(market.get_last_large_fill_slot(), 50);
+
+    // During the cooldown, bids are limited to 1% above the last trade price
+    assert_eq!(
+        buy(&mut market, &mut record_event_fn, 55, 110, 1).unwrap_err(),
+        PhoenixMatchError::OutsideVolatilityBand
+    );
+    buy(&mut market, &mut record_event_fn, 55, 101, 1).unwrap();
+    // Orders that cannot take liquidity are not checked
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 90, 1),
             &mut record_event_fn,
-            &mut get_clock_fn,
+            &mut || (55, 0),
         )
-        .is_none());
+        .unwrap();
+
+    // Once the cooldown has passed, the order is matched
+    let (_, response) = buy(&mut market, &mut record_event_fn, 60, 110, 3).unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(3));
+    assert_eq!(market.get_last_trade_price(), Ticks::new(110));
+    assert_eq!(market.get_last_large_fill_slot(), 50);
 }
 
 #[test]
-fn test_fok_and_ioc_limit_3() {
-    let mut rng = StdRng::seed_from_u64(2);
-    let mut market = Box::new(setup_market());
+fn test_taking_disabled() {
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let (maker, taker) = (1, 2);
+    market.set_taking_disabled(true);
+    assert!(market.is_taking_disabled());
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
-
-    // (3) IOC should succeed if the tick/lot budget is not enough to fill the order (same params as 1)
-    let (o, matching_engine_response) = market
+    // Post-only orders and limit orders that do not cross can still be placed
+    let (ask_id, _) = market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let (bid_id, _) = market
         .place_order(
             &taker,
-            OrderPacket::new_ioc_buy_with_limit_price(
-                103,
-                (Ticks::new(102)
-                    * market.tick_size_in_quote_lots_per_base_unit
-                    * BaseLots::new(30)
-                    / market.base_lots_per_base_unit
-                    + QuoteLots::ONE)
-                    .as_u64(),
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            OrderPacket::new_limit_order_default(Side::Bid, 99, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o.is_none());
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_buy(
-                Ticks::new(102) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
-                    / market.base_lots_per_base_unit,
-                BaseLots::new(30)
-            )
+    assert!(bid_id.is_some());
+
+    // IOC orders are rejected even if they would not cross
+    for price in [100, 90] {
+        assert_eq!(
+            market.place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    price,
+                    5,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            ),
+            Err(PhoenixMatchError::TakingDisabled)
+        );
+    }
+
+    // Limit orders that cross are rejected
+    assert_eq!(
+        market.place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        ),
+        Err(PhoenixMatchError::TakingDisabled)
     );
-    let (o, matching_engine_response) = market
+    assert_eq!(
+        market
+            .get_book(Side::Ask)
+            .get(&ask_id.unwrap())
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(10)
+    );
+
+    // Orders can still be cancelled
+    assert!(market
+        .cancel_order(
+            &taker,
+            &bid_id.unwrap(),
+            Side::Bid,
+            false,
+            None,
+            &mut record_event_fn
+        )
+        .is_some());
+    assert_eq!(market.get_book(Side::Bid).len(), 0);
+
+    // Taking is allowed again once the flag is cleared
+    market.set_taking_disabled(false);
+    let (_, response) = market
         .place_order(
             &taker,
-            OrderPacket::new_ioc_sell_with_limit_price(
-                97,
-                31,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                5,
                 SelfTradeBehavior::Abort,
                 None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o.is_none());
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_sell(
-                BaseLots::new(30),
-                Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
-                    / market.base_lots_per_base_unit,
-            )
-    );
+    assert_eq!(response.num_base_lots_out, BaseLots::new(5));
 }
 
 #[test]
-fn test_fok_and_ioc_limit_4() {
-    let mut rng = StdRng::seed_from_u64(2);
-    let mut market = Box::new(setup_market());
+fn test_min_taker_fill() {
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let (maker, taker) = (1, 2);
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 20),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market.set_min_taker_fill_base_lots(BaseLots::new(5));
+    assert_eq!(market.get_min_taker_fill_base_lots(), BaseLots::new(5));
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
+    let ioc = |price, num_base_lots| {
+        OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            price,
+            num_base_lots,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        )
+    };
 
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+    // IOC orders that do not match anything are unaffected
+    let (_, response) = market
+        .place_order(&taker, ioc(90, 3), &mut record_event_fn, &mut get_clock_fn)
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::ZERO);
 
-    // (4) IOC should succeed if the tick/lot budget is not enough to fill the order (same params as 2)
-    let (o, matching_engine_response) = market
+    // IOC orders that fill at least the minimum succeed
+    let (_, response) = market
+        .place_order(&taker, ioc(100, 5), &mut record_event_fn, &mut get_clock_fn)
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(5));
+
+    // Limit orders are not subject to the minimum
+    let (_, response) = market
         .place_order(
             &taker,
-            OrderPacket::new_ioc_buy_with_limit_price(
-                102,
-                (Ticks::new(102)
-                    * market.tick_size_in_quote_lots_per_base_unit
-                    * BaseLots::new(30)
-                    / market.base_lots_per_base_unit)
-                    .as_u64(),
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 1),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o.is_none());
-    // Expect two levels filled
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_buy(
-                Ticks::new(101) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
-                    / market.base_lots_per_base_unit
-                    + Ticks::new(102)
-                        * market.tick_size_in_quote_lots_per_base_unit
-                        * BaseLots::new(10)
-                        / market.base_lots_per_base_unit,
-                BaseLots::new(20)
-            )
+    assert_eq!(response.num_base_lots_out, BaseLots::new(1));
+
+    // IOC orders that fill less than the minimum are voided
+    assert_eq!(
+        market.place_order(&taker, ioc(100, 3), &mut record_event_fn, &mut get_clock_fn),
+        Err(PhoenixMatchError::TakerFillBelowMinimum)
     );
 
-    let (o, matching_engine_response) = market
-        .place_order(
+    // The minimum can be overridden for a single order without changing the market's minimum
+    let (_, response) = market
+        .place_order_with_min_taker_fill(
             &taker,
-            OrderPacket::new_ioc_sell_with_limit_price(
-                98,
-                30,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            ioc(100, 3),
+            BaseLots::ZERO,
+            None,
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o.is_none());
-    // expect two levels filled
-    assert!(
-        matching_engine_response
-            == MatchingEngineResponse::new_from_sell(
-                BaseLots::new(20),
-                Ticks::new(99) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
-                    / market.base_lots_per_base_unit
-                    + Ticks::new(98)
-                        * market.tick_size_in_quote_lots_per_base_unit
-                        * BaseLots::new(10)
-                        / market.base_lots_per_base_unit,
-            )
-    );
+    assert_eq!(response.num_base_lots_out, BaseLots::new(3));
+    assert_eq!(market.get_min_taker_fill_base_lots(), BaseLots::new(5));
 }
 
 #[test]
-fn test_fok_and_ioc_limit_5() {
-    let mut rng = StdRng::seed_from_u64(2);
-    let mut market = Box::new(setup_market());
+fn test_get_withdrawable() {
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
-    // (1) FOK should fail if the base lot budget is not enough to fill the order (changed tick limit)
-    assert!(market
+    let trader = 1;
+    assert_eq!(market.get_withdrawable(&trader), None);
+    market.get_or_register_trader(&trader).unwrap();
+    assert_eq!(
+        market.get_withdrawable(&trader),
+        Some((BaseLots::ZERO, QuoteLots::ZERO))
+    );
+    {
+        let trader_state = market.get_trader_state_mut(&trader).unwrap();
+        trader_state.base_lots_free = BaseLots::new(50);
+        trader_state.quote_lots_free = QuoteLots::new(7000);
+    }
+    // Funds locked in resting orders are not withdrawable
+    market
         .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                103,
-                31,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_none());
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                97,
-                31,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 12, 20),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .unwrap();
+    assert_eq!(
+        market.get_withdrawable(&trader),
+        Some((BaseLots::new(30), QuoteLots::new(7000)))
+    );
+}
 
-    let mut mock_clock_fn = || (1000, 1000);
+#[test]
+fn test_get_trader_equity_in_quote_lots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    // IOC order should return no matches if TIF constraint is not met
+    let trader = rng.gen::<u128>();
     assert_eq!(
+        market.get_trader_equity_in_quote_lots(&trader, Ticks::new(10)),
+        None
+    );
+    market.get_or_register_trader(&trader).unwrap();
+    {
+        let trader_state = market.get_trader_state_mut(&trader).unwrap();
+        trader_state.base_lots_free = BaseLots::new(50);
+        trader_state.quote_lots_free = QuoteLots::new(7000);
+    }
+    // Lock 20 base lots and 3000 quote lots in resting orders
+    for (side, price, size) in [(Side::Ask, 12, 20), (Side::Bid, 10, 3)] {
         market
             .place_order(
-                &taker,
-                OrderPacket::new_ioc(
-                    Side::Bid,
-                    None,
-                    100,
-                    0,
-                    0,
-                    0,
+                &trader,
+                OrderPacket::new_limit_order(
+                    side,
+                    price,
+                    size,
                     SelfTradeBehavior::Abort,
                     None,
-                    rng.gen::<u128>(),
-                    false,
-                    Some(2),
-                    None
+                    0,
+                    true,
                 ),
                 &mut record_event_fn,
-                &mut mock_clock_fn,
+                &mut get_clock_fn,
             )
-            .unwrap()
-            .1,
-        MatchingEngineResponse::default()
+            .unwrap();
+    }
+    let trader_state = market.get_trader_state(&trader).unwrap();
+    assert_eq!(trader_state.base_lots_locked, BaseLots::new(20));
+    assert_eq!(trader_state.quote_lots_locked, QuoteLots::new(3000));
+
+    // At 10 ticks, each base lot is worth 1000 quote lots
+    assert_eq!(
+        market.get_trader_equity_in_quote_lots(&trader, Ticks::new(10)),
+        Some(QuoteLots::new(50 * 1000 + 7000))
+    );
+    assert_eq!(
+        market.get_trader_equity_in_quote_lots(&trader, Ticks::new(11)),
+        Some(QuoteLots::new(50 * 1100 + 7000))
+    );
+    assert_eq!(
+        market.get_trader_equity_in_quote_lots(&trader, Ticks::MAX),
+        None
     );
+}
 
-    // IOC order should return no matches if TIF constraint is not met
+#[test]
+fn test_get_active_traders() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let depositor = rng.gen::<u128>();
+    let idle = rng.gen::<u128>();
+    assert!(market.get_active_traders().is_empty());
+
+    // Traders with locked funds, free funds, or no funds at all
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market.get_or_register_trader(&depositor).unwrap();
+    market
+        .get_trader_state_mut(&depositor)
+        .unwrap()
+        .base_lots_free = BaseLots::new(5);
+    market.get_or_register_trader(&idle).unwrap();
+
+    let active_traders = market.get_active_traders();
+    assert_eq!(active_traders.len(), 2);
+    for (trader, trader_state) in active_traders.iter() {
+        assert!(*trader == maker || *trader == depositor);
+        assert_eq!(trader_state, market.get_trader_state(trader).unwrap());
+    }
+
+    // Cancelling without claiming leaves the maker's funds free, so it remains active
+    market.cancel_all_orders(&maker, false, None, &mut record_event_fn);
+    assert_eq!(market.get_active_traders().len(), 2);
+    market.claim_all_funds(&maker, true);
     assert_eq!(
-        market
-            .place_order(
-                &taker,
-                OrderPacket::new_ioc(
-                    Side::Bid,
-                    None,
-                    100,
-                    0,
-                    0,
-                    0,
-                    SelfTradeBehavior::Abort,
-                    None,
-                    rng.gen::<u128>(),
-                    false,
-                    None,
-                    Some(2),
-                ),
-                &mut record_event_fn,
-                &mut mock_clock_fn,
-            )
-            .unwrap()
-            .1,
-        MatchingEngineResponse::default()
+        market.get_active_traders(),
+        vec![(depositor, *market.get_trader_state(&depositor).unwrap())]
     );
+}
 
-    // IOC order should match if TIF constraint is set but not met
-    assert_ne!(
-        market
+#[test]
+fn test_get_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let mut order_ids = vec![];
+    for (side, price, size) in [(Side::Bid, 100, 10), (Side::Ask, 101, 20)] {
+        let (order_id, _) = market
             .place_order(
-                &taker,
-                OrderPacket::new_ioc(
-                    Side::Bid,
-                    None,
-                    100,
-                    0,
-                    0,
-                    0,
-                    SelfTradeBehavior::Abort,
-                    None,
-                    rng.gen::<u128>(),
-                    false,
-                    Some(1200),
-                    Some(1200),
-                ),
+                &maker,
+                OrderPacket::new_post_only_default(side, price, size),
                 &mut record_event_fn,
-                &mut mock_clock_fn,
+                &mut get_clock_fn,
             )
-            .unwrap()
-            .1,
-        MatchingEngineResponse::default()
+            .unwrap();
+        order_ids.push(order_id.unwrap());
+    }
+
+    // Each order is found on the side encoded in its sequence number
+    for order_id in order_ids.iter() {
+        let side = Side::from_order_sequence_number(order_id.order_sequence_number);
+        let order = market.get_order(order_id).unwrap();
+        let resting_order = market.get_book(side).get(order_id).unwrap();
+        assert_eq!(order.num_base_lots, resting_order.num_base_lots);
+        assert_eq!(order.trader_index, resting_order.trader_index);
+    }
+    assert_eq!(
+        market.get_order(&order_ids[0]).unwrap().num_base_lots,
+        BaseLots::new(10)
+    );
+    assert_eq!(
+        market.get_order(&order_ids[1]).unwrap().num_base_lots,
+        BaseLots::new(20)
     );
 
-    assert!(
+    // An id with the right sequence number but the wrong price is not found
+    let wrong_price = FIFOOrderId::new(Ticks::new(99), order_ids[0].order_sequence_number);
+    assert!(market.get_order(&wrong_price).is_none());
+
+    market.cancel_all_orders(&maker, false, None, &mut record_event_fn);
+    assert!(order_ids
+        .iter()
+        .all(|order_id| market.get_order(order_id).is_none()));
+}
+
+#[test]
+fn test_get_fillable_base_lots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+
+    // Nothing can be filled on an empty book
+    assert_eq!(
+        market.get_fillable_base_lots(Side::Bid, Ticks::MAX),
+        BaseLots::ZERO
+    );
+    assert_eq!(
+        market.get_fillable_base_lots(Side::Ask, Ticks::MIN),
+        BaseLots::ZERO
+    );
+
+    for (side, price_in_ticks, num_base_lots, last_valid_slot) in [
+        (Side::Ask, 100, 5, None),
+        (Side::Ask, 101, 3, None),
+        (Side::Ask, 103, 2, Some(10)),
+        (Side::Bid, 90, 4, None),
+        (Side::Bid, 88, 6, None),
+    ] {
+        let mut order_packet =
+            OrderPacket::new_post_only_default(side, price_in_ticks, num_base_lots);
+        order_packet.set_last_valid_slot(last_valid_slot);
         market
             .place_order(
-                &taker,
-                OrderPacket::new_ioc(
-                    Side::Bid,
-                    None,
-                    100,
-                    1,
-                    0,
-                    0,
-                    SelfTradeBehavior::Abort,
-                    None,
-                    rng.gen::<u128>(),
-                    false,
-                    None,
-                    None
-                ),
+                &maker,
+                order_packet,
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_none(),
-        "Only one of num_base_lots or num_quote_lots should be set"
+            .unwrap();
+    }
+
+    // Limits outside of the book can not be filled
+    assert_eq!(
+        market.get_fillable_base_lots(Side::Bid, Ticks::new(99)),
+        BaseLots::ZERO
+    );
+    assert_eq!(
+        market.get_fillable_base_lots(Side::Ask, Ticks::new(91)),
+        BaseLots::ZERO
+    );
+
+    // Orders at the limit price are included
+    for (limit_price, fillable) in [(100, 5), (102, 8), (103, 10), (u64::MAX, 10)] {
+        assert_eq!(
+            market.get_fillable_base_lots(Side::Bid, Ticks::new(limit_price)),
+            BaseLots::new(fillable)
+        );
+    }
+    for (limit_price, fillable) in [(90, 4), (89, 4), (88, 10), (0, 10)] {
+        assert_eq!(
+            market.get_fillable_base_lots(Side::Ask, Ticks::new(limit_price)),
+            BaseLots::new(fillable)
+        );
+    }
+
+    // Expired orders are skipped
+    assert_eq!(
+        market.get_fillable_base_lots_with_expiration(Side::Bid, Ticks::new(103), Some(11), None),
+        BaseLots::new(8)
     );
 }
 
 #[test]
-fn test_fok_and_ioc_with_free_funds() {
+fn test_limit_order_by_quote() {
     let mut rng = StdRng::seed_from_u64(2);
-    let mut market = Box::new(setup_market());
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
-
-    market.get_or_register_trader(&taker).unwrap();
+    market.get_or_register_trader(&trader).unwrap();
+    market
+        .get_trader_state_mut(&trader)
+        .unwrap()
+        .quote_lots_free = QuoteLots::new(25500);
 
-    let tick_size = market.tick_size_in_quote_lots_per_base_unit;
-    let base_lots_per_base_unit = market.base_lots_per_base_unit;
-    {
-        let trader_state = market.get_trader_state_mut(&taker).unwrap();
-        trader_state.base_lots_free += BaseLots::new(29);
-        trader_state.quote_lots_free +=
-            Ticks::new(103) * tick_size * BaseLots::new(1) / base_lots_per_base_unit;
-    }
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                99,
-                10,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-    assert!(market
+    // At 10 ticks, one base lot costs 10 * 10000 / 100 = 1000 quote lots
+    let (order_id, _) = market
         .place_order(
-            &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                98,
+            &trader,
+            OrderPacket::new_limit_order_by_quote(
+                Side::Bid,
                 10,
+                25500,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 true,
             ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .unwrap();
+    let resting_order = market.get_book(Side::Bid).get(&order_id.unwrap()).unwrap();
+    assert_eq!(resting_order.num_base_lots, BaseLots::new(25));
+
+    // The quote lots that cannot buy a full base lot remain free
+    let trader_state = market.get_trader_state(&trader).unwrap();
+    assert_eq!(trader_state.quote_lots_locked, QuoteLots::new(25000));
+    assert_eq!(trader_state.quote_lots_free, QuoteLots::new(500));
+
+    // The order is empty if the quote lots cannot buy a single base lot
     assert!(market
         .place_order(
-            &taker,
-            OrderPacket::new_fok_sell_with_limit_price(
-                97,
+            &trader,
+            OrderPacket::new_limit_order_by_quote(
+                Side::Bid,
                 10,
+                500,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 true,
             ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_none());
+        .is_err());
 
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                101,
-                10,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                false,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                102,
-                10,
-                SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
-                true,
-            ),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+    // A relative expiry is converted to an absolute slot, just like for limit orders
+    let mut order_packet = OrderPacket::new_limit_order_by_quote(
+        Side::Bid,
+        9,
+        9000,
+        SelfTradeBehavior::Abort,
+        0,
+        false,
+    );
+    order_packet.set_valid_for_slots(Some(25));
+    let mut clock_fn = || (1000, 0);
+    let (order_id, _) = market
+        .place_order(&trader, order_packet, &mut record_event_fn, &mut clock_fn)
+        .unwrap();
+    let resting_order = market.get_book(Side::Bid).get(&order_id.unwrap()).unwrap();
+    assert_eq!(resting_order.num_base_lots, BaseLots::new(10));
+    assert_eq!(resting_order.last_valid_slot, 1025);
 
-    let trader_state = market.get_trader_state_mut(&taker).unwrap();
-    println!("trader_state: {:?}", trader_state);
+    order_packet.set_last_valid_slot(Some(1050));
+    assert_eq!(
+        market.place_order(&trader, order_packet, &mut record_event_fn, &mut clock_fn),
+        Err(PhoenixMatchError::InvalidOrderExpiration)
+    );
 
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_fok_buy_with_limit_price(
-                103,
-                10,
+    // A price whose quote lots per base unit overflow is rejected
+    assert_eq!(
+        market.place_order(
+            &trader,
+            OrderPacket::new_limit_order_by_quote(
+                Side::Bid,
+                u64::MAX,
+                25500,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 true,
             ),
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .is_none());
-}
-
-// Base lots = (quote lots * base lots per base unit) / (tick size in quote lots per base unit * price in ticks)
-// Then adjust for fees.
-fn get_min_base_lots_out(
-    quote_lots_in: QuoteLots,
-    tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
-    base_lots_per_base_unit: BaseLotsPerBaseUnit,
-    price_in_ticks: Ticks,
-    slippage_bps: u64,
-) -> BaseLots {
-    let base_lots_out = ((quote_lots_in * base_lots_per_base_unit).as_u64() as f64
-        / ((tick_size_in_quote_lots_per_base_unit * price_in_ticks).as_u64() as f64))
-        * (1.0 - (slippage_bps as f64 / 10000.0));
-    BaseLots::new(base_lots_out as u64)
-}
-
-fn get_min_quote_lots_out(
-    base_lots_in: BaseLots,
-    tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
-    base_lots_per_base_unit: BaseLotsPerBaseUnit,
-    price_in_ticks: Ticks,
-    slippage_bps: u64,
-) -> QuoteLots {
-    let quote_lots_out = ((tick_size_in_quote_lots_per_base_unit * price_in_ticks * base_lots_in)
-        .as_u64() as f64
-        / (base_lots_per_base_unit.as_u64() as f64))
-        * (1.0 - (slippage_bps as f64 / 10000.0));
-    QuoteLots::new(quote_lots_out as u64)
+        ),
+        Err(PhoenixMatchError::InvalidOrderPrice)
+    );
 }
 
 #[test]
-fn test_fok_with_slippage_1() {
+fn test_self_trade_behavior_override() {
     let mut rng = StdRng::seed_from_u64(2);
-    let taker_bps = 5;
-    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
-    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    for i in 1..11 {
-        assert!(market
-            .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
-                &mut record_event_fn,
-                &mut get_clock_fn,
-            )
-            .is_some());
-        assert!(market
-            .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
-                &mut record_event_fn,
-                &mut get_clock_fn,
-            )
-            .is_some());
-    }
-
-    let starting_ladder = market.get_typed_ladder(5);
+    let ioc_buy =
+        OrderPacket::new_ioc_by_lots(Side::Bid, 100, 10, SelfTradeBehavior::Abort, None, 0, false);
+    let (ask_id, _) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let ask_id = ask_id.unwrap();
+    assert_eq!(
+        market
+            .get_trader_state(&trader)
+            .unwrap()
+            .self_trade_behavior_override(),
+        None
+    );
 
-    assert!(starting_ladder.asks[2].price_in_ticks == Ticks::new(103));
-    assert!(starting_ladder.asks[2].size_in_base_lots == BaseLots::new(30000));
+    // Without an override, the packet's behavior is used and the self trade aborts
+    assert!(market
+        .place_order(&trader, ioc_buy, &mut record_event_fn, &mut get_clock_fn)
+        .is_err());
+    assert!(market.get_book(Side::Ask).get(&ask_id).is_some());
 
-    let mut event_recorder = VecDeque::new();
-    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
-    // Performs a swap order with a slippage of at most 50bps
-    // Go through approximately 3 levels of the book
-    let quote_lots_in =
-        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(60000)
-            / market.base_lots_per_base_unit;
-    let slippage_bps = 50;
-    let min_base_lots_out = get_min_base_lots_out(
-        quote_lots_in,
-        market.tick_size_in_quote_lots_per_base_unit,
-        base_lots_per_base_unit,
-        Ticks::new(102),
-        slippage_bps,
+    market
+        .get_trader_state_mut(&trader)
+        .unwrap()
+        .set_self_trade_behavior_override(Some(SelfTradeBehavior::CancelProvide));
+    assert_eq!(
+        market
+            .get_trader_state(&trader)
+            .unwrap()
+            .self_trade_behavior_override(),
+        Some(SelfTradeBehavior::CancelProvide)
     );
 
-    println!("min base_lots_out: {}", min_base_lots_out);
-    println!("quote_lots_in: {}", quote_lots_in);
+    // The override supersedes the packet, so the resting ask is cancelled instead
+    let (_, response) = market
+        .place_order(&trader, ioc_buy, &mut record_event_fn, &mut get_clock_fn)
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::ZERO);
+    assert!(market.get_book(Side::Ask).get(&ask_id).is_none());
 
-    let (order, matching_engine_response) = market
+    // Clearing the override restores the packet's behavior
+    market
         .place_order(
-            &taker,
-            OrderPacket::new_ioc_buy_with_slippage(
-                quote_lots_in.as_u64(),
-                min_base_lots_out.as_u64(),
-            ),
+            &trader,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    println!("matching_engine_response: {:?}", matching_engine_response);
-    assert!(order.is_none());
-
-    // Ensure that the fill was within the slippage limit
-    let average_price_in_ticks = (base_lots_per_base_unit.as_u64() as f64
-        * matching_engine_response.num_quote_lots_in.as_u64() as f64
-        / (market.tick_size_in_quote_lots_per_base_unit.as_u64() as f64))
-        / matching_engine_response.num_base_lots_out.as_u64() as f64;
-    println!("average_price_in_ticks: {}", average_price_in_ticks);
-    let bps = (average_price_in_ticks - 102.0) / 102.0 * 10000.0;
-    println!("bps: {}", bps);
-    assert!(bps.floor() <= 50.0);
-
-    let ladder = market.get_typed_ladder(5);
-
-    let mut prev_ladder = starting_ladder;
-    for event in event_recorder.iter() {
-        if let MarketEvent::Fill {
-            order_sequence_number: order_id,
-            base_lots_filled,
-            price_in_ticks,
-            ..
-        } = event
-        {
-            let book = match Side::from_order_sequence_number(*order_id) {
-                Side::Bid => &mut prev_ladder.bids,
-                Side::Ask => &mut prev_ladder.asks,
-            };
-            assert!(!book.is_empty());
-            assert!(book[0].price_in_ticks == *price_in_ticks);
-            book[0].size_in_base_lots -= *base_lots_filled;
-            if book[0].size_in_base_lots == BaseLots::ZERO {
-                book.remove(0);
-            }
-        }
-    }
-    assert!(ladder.asks[0].price_in_ticks == prev_ladder.asks[0].price_in_ticks);
-    assert!(ladder.asks[0].size_in_base_lots == prev_ladder.asks[0].size_in_base_lots);
+    market
+        .get_trader_state_mut(&trader)
+        .unwrap()
+        .set_self_trade_behavior_override(None);
+    assert!(market
+        .place_order(&trader, ioc_buy, &mut record_event_fn, &mut get_clock_fn)
+        .is_err());
 }
 
 #[test]
-fn test_fok_with_slippage_2() {
+fn test_trader_volume_tracking() {
     let mut rng = StdRng::seed_from_u64(2);
-    let taker_bps = 5;
-    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
     let taker = rng.gen::<u128>();
-
-    for i in 1..11 {
+    let unregistered_taker = rng.gen::<u128>();
+    market.get_or_register_trader(&taker).unwrap();
+    for price in [100, 102] {
         assert!(market
             .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price, 10),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
+            .is_ok());
+    }
+    assert!(!market.is_trader_volume_tracking_enabled());
+    assert_eq!(market.get_trader_volume(&maker), Some((0, 0)));
+    assert_eq!(market.get_trader_volume(&unregistered_taker), None);
+
+    let mut take = |market: &mut Dex, trader: &TraderId, num_base_lots: u64| {
         assert!(market
             .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
+                trader,
+                OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    102,
+                    num_base_lots,
+                    SelfTradeBehavior::CancelProvide,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                ),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
-    }
+            .is_ok());
+    };
 
-    // Show that the order is rejected if the slippage is too high
-    assert!(market
-        .place_order(
-            &taker,
-            OrderPacket::new_ioc_sell_with_slippage(
-                50_000,
-                (Ticks::new(98)
-                    * market.tick_size_in_quote_lots_per_base_unit
-                    * BaseLots::new(50000)
-                    / market.base_lots_per_base_unit)
-                    .as_u64()
-            ), // 2 full levels, 1 partial level
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_none());
+    // Fills are not recorded while tracking is disabled
+    take(&mut market, &taker, 5);
+    assert_eq!(market.get_trader_volume(&maker), Some((0, 0)));
+    assert_eq!(market.get_trader_volume(&taker), Some((0, 0)));
+
+    market.set_trader_volume_tracking_enabled(true);
+    assert!(market.is_trader_volume_tracking_enabled());
+    // Fills of 5 @ 100 and 5 @ 102, i.e. 50_000 + 51_000 quote lots
+    take(&mut market, &taker, 10);
+    assert_eq!(market.get_trader_volume(&maker), Some((101_000, 2)));
+    assert_eq!(market.get_trader_volume(&taker), Some((101_000, 2)));
+
+    // Unregistered takers only add to the maker's totals
+    take(&mut market, &unregistered_taker, 1);
+    assert_eq!(market.get_trader_volume(&maker), Some((111_200, 3)));
+    assert_eq!(market.get_trader_volume(&taker), Some((101_000, 2)));
+    assert_eq!(market.get_trader_volume(&unregistered_taker), None);
+
+    // Disabling tracking keeps the existing totals
+    market.set_trader_volume_tracking_enabled(false);
+    take(&mut market, &taker, 1);
+    assert_eq!(market.get_trader_volume(&maker), Some((111_200, 3)));
+    assert_eq!(market.get_trader_volume(&taker), Some((101_000, 2)));
 }
 
 #[test]
-fn test_fok_with_slippage_3() {
+fn test_change_tick_size() {
     let mut rng = StdRng::seed_from_u64(2);
-    let taker_bps = 5;
-    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
-    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    let mut market = setup_market();
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    for i in 1..11 {
-        assert!(market
-            .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
-                &mut record_event_fn,
-                &mut get_clock_fn,
-            )
-            .is_some());
+    let maker = rng.gen::<u128>();
+    for (side, price, size) in [
+        (Side::Bid, 99, 10),
+        (Side::Bid, 98, 5),
+        (Side::Ask, 101, 10),
+        (Side::Ask, 102, 4),
+    ] {
         assert!(market
             .place_order(
-                &trader,
-                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
+                &maker,
+                OrderPacket::new_post_only_default(side, price, size),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
+            .is_ok());
     }
+    let trader_state = *market.get_trader_state(&maker).unwrap();
 
-    let starting_ladder = market.get_typed_ladder(5);
-    // Performs a swap sell order with a slippage of at most 28bps
-    let target_bps = 28;
-    let base_lots_in = BaseUnits::new(50) * base_lots_per_base_unit;
-    let min_quote_lots_out = get_min_quote_lots_out(
-        base_lots_in,
-        market.tick_size_in_quote_lots_per_base_unit,
-        base_lots_per_base_unit,
-        Ticks::new(98),
-        target_bps,
-    );
+    // The tick size must be a multiple of the base lots per base unit
+    assert!(market
+        .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(15050))
+        .is_none());
+    assert_eq!(
+        market.get_tick_size(),
+        QuoteLotsPerBaseUnitPerTick::new(10000)
+    );
 
-    println!("min_quote_lots_out: {}", min_quote_lots_out);
-    println!(
-        "quote_lots out at price of 98: {}",
-        (Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * base_lots_in)
-            / (base_lots_per_base_unit)
+    let order_ids = market
+        .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(20000))
+        .unwrap();
+    assert_eq!(order_ids.len(), 4);
+    assert_eq!(
+        market.get_tick_size(),
+        QuoteLotsPerBaseUnitPerTick::new(20000)
     );
-    let adjusted_bps = (1.0
-        - (min_quote_lots_out.as_u64() as f64
-            / (Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * base_lots_in
-                / (base_lots_per_base_unit))
-                .as_u64() as f64))
-        * 10000.0;
-    println!("adjusted_bps: {}", adjusted_bps);
-    let (order, matching_engine_response) = market
+
+    // Bids are rounded down and asks are rounded up
+    let ladder = market.get_typed_ladder(u64::MAX);
+    assert_eq!(ladder.bids.len(), 1);
+    assert_eq!(ladder.bids[0].price_in_ticks, Ticks::new(49));
+    assert_eq!(ladder.bids[0].size_in_base_lots, BaseLots::new(15));
+    assert_eq!(ladder.asks.len(), 1);
+    assert_eq!(ladder.asks[0].price_in_ticks, Ticks::new(51));
+    assert_eq!(ladder.asks[0].size_in_base_lots, BaseLots::new(14));
+
+    // Orders that land on the same tick keep their time priority
+    let bids = market
+        .get_book(Side::Bid)
+        .iter()
+        .map(|(_, order)| order.num_base_lots)
+        .collect::<Vec<_>>();
+    assert_eq!(bids, vec![BaseLots::new(10), BaseLots::new(5)]);
+
+    // The bid at 99 now requires 49 * 20000 * 10 / 100 = 98000 quote lots instead of 99000
+    let new_trader_state = *market.get_trader_state(&maker).unwrap();
+    assert_eq!(
+        new_trader_state.quote_lots_locked,
+        trader_state.quote_lots_locked - QuoteLots::new(1000)
+    );
+    assert_eq!(
+        new_trader_state.quote_lots_free,
+        trader_state.quote_lots_free + QuoteLots::new(1000)
+    );
+    assert_eq!(
+        new_trader_state.base_lots_locked,
+        trader_state.base_lots_locked
+    );
+
+    // Bids cannot be rounded down to a price of zero
+    assert!(market
+        .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(10_000_000))
+        .is_none());
+    assert_eq!(
+        market.get_tick_size(),
+        QuoteLotsPerBaseUnitPerTick::new(20000)
+    );
+    assert_eq!(market.get_typed_ladder(u64::MAX), ladder);
+
+    // Cancelling the migrated orders releases exactly the remaining locked funds
+    market.cancel_all_orders(&maker, false, None, &mut record_event_fn);
+    let final_trader_state = *market.get_trader_state(&maker).unwrap();
+    assert_eq!(final_trader_state.quote_lots_locked, QuoteLots::ZERO);
+    assert_eq!(final_trader_state.base_lots_locked, BaseLots::ZERO);
+
+    // The last trade price is moved to the nearest tick on the new grid
+    assert!(market
         .place_order(
-            &taker,
-            OrderPacket::new_ioc_sell_with_slippage(
-                base_lots_in.as_u64(),
-                min_quote_lots_out.as_u64(),
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 51, 1),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &rng.gen::<u128>(),
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                51,
+                1,
+                SelfTradeBehavior::CancelProvide,
+                None,
+                rng.gen::<u128>(),
+                false,
             ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
+        .is_ok());
+    assert_eq!(market.get_last_trade_price(), Ticks::new(51));
+    market
+        .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(30000))
         .unwrap();
-    assert!(order.is_none());
-    // Ensure that the fill was within the slippage limit
-    assert!(matching_engine_response.num_base_lots_in == base_lots_in);
-    let average_price_in_ticks = (base_lots_per_base_unit.as_u64() as f64
-        * matching_engine_response.num_quote_lots_out.as_u64() as f64
-        / market.tick_size_in_quote_lots_per_base_unit.as_u64() as f64)
-        / matching_engine_response.num_base_lots_in.as_u64() as f64;
-
-    let bps = ((98.0 - average_price_in_ticks) / 98.0) * 10000.0;
-    println!("average_price_in_ticks: {}", average_price_in_ticks);
-    println!("bps: {}", bps);
-    assert!(bps.floor() <= bps + taker_bps as f64);
-
-    let ladder = market.get_typed_ladder(5);
-    let mut prev_ladder = starting_ladder;
-    for event in event_recorder.iter() {
-        if let MarketEvent::Fill {
-            order_sequence_number: order_id,
-            base_lots_filled,
-            price_in_ticks,
-            ..
-        } = event
-        {
-            let book = match Side::from_order_sequence_number(*order_id) {
-                Side::Bid => &mut prev_ladder.bids,
-                Side::Ask => &mut prev_ladder.asks,
-            };
-            assert!(!book.is_empty());
-            assert!(book[0].price_in_ticks == *price_in_ticks);
-            book[0].size_in_base_lots -= *base_lots_filled;
-            if book[0].size_in_base_lots == BaseLots::ZERO {
-                book.remove(0);
-            }
-        }
-    }
-    assert!(ladder.bids[0].price_in_ticks == prev_ladder.bids[0].price_in_ticks);
-    assert!(ladder.bids[0].size_in_base_lots == prev_ladder.bids[0].size_in_base_lots);
+    // 51 * 20000 / 30000 = 34
+    assert_eq!(market.get_last_trade_price(), Ticks::new(34));
+    market
+        .change_tick_size(QuoteLotsPerBaseUnitPerTick::new(40000))
+        .unwrap();
+    // 34 * 30000 / 40000 = 25.5, which is rounded to 26
+    assert_eq!(market.get_last_trade_price(), Ticks::new(26));
 }
 
 #[test]
-fn test_sell_with_quote_lot_budget() {
+fn test_place_partial_limit_orders() {
     let mut rng = StdRng::seed_from_u64(2);
-    let taker_bps = 0;
-    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let mut market = Box::new(setup_market());
     let mut event_recorder = VecDeque::new();
     let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    let bidder = rng.gen::<u128>();
+    let asker = rng.gen::<u128>();
+    market.get_or_register_trader(&trader).unwrap();
+    {
+        let trader_state = market.get_trader_state_mut(&trader).unwrap();
+        trader_state.base_lots_free += BaseLots::new(4);
+        // 3.5 base lots at a price of 100 ticks
+        trader_state.quote_lots_free += QuoteLots::new(35_000);
+    }
 
-    assert!(market
-        .place_order(
-            &trader,
-            OrderPacket::new_post_only_default(Side::Bid, 100, 10000),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
+    let resting_size = |market: &Dex, side: Side, order_id: Option<FIFOOrderId>| {
+        market
+            .get_book(side)
+            .get(&order_id.unwrap())
+            .unwrap()
+            .num_base_lots
+    };
 
-    assert!(market
+    // With only deposited funds, the bid is clamped to the 3 base lots that the free quote
+    // lots can lock
+    let (order_id, matching_engine_response) = market
         .place_order(
             &trader,
-            OrderPacket::new_post_only_default(Side::Bid, 99, 20000),
-            &mut record_event_fn,
-            &mut get_clock_fn,
-        )
-        .is_some());
-
-    let (_, res) = market
-        .place_order(
-            &taker,
-            OrderPacket::ImmediateOrCancel {
-                side: Side::Ask,
-                num_base_lots: BaseLots::ZERO,
-                num_quote_lots: QuoteLots::new(1000000),
-                min_base_lots_to_fill: BaseLots::new(0),
-                min_quote_lots_to_fill: QuoteLots::new(0),
-                match_limit: None,
-                use_only_deposited_funds: false,
-                client_order_id: 0,
-                price_in_ticks: None,
-                self_trade_behavior: SelfTradeBehavior::Abort,
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
+            OrderPacket::new_place_partial_limit_order(
+                Side::Bid,
+                100,
+                10,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
+    assert_eq!(resting_size(&market, Side::Bid, order_id), BaseLots::new(3));
+    assert_eq!(
+        matching_engine_response.num_quote_lots_posted,
+        QuoteLots::new(30_000)
+    );
 
-    assert_eq!(res.num_quote_lots_out, QuoteLots::new(1000000));
-
-    let (_, res) = market
+    // The ask is clamped to the 4 free base lots
+    let (order_id, _) = market
         .place_order(
-            &taker,
-            OrderPacket::ImmediateOrCancel {
-                side: Side::Ask,
-                num_base_lots: BaseLots::ZERO,
-                num_quote_lots: QuoteLots::new(1000000),
-                min_base_lots_to_fill: BaseLots::new(0),
-                min_quote_lots_to_fill: QuoteLots::new(0),
-                match_limit: None,
-                use_only_deposited_funds: false,
-                client_order_id: 0,
-                price_in_ticks: None,
-                self_trade_behavior: SelfTradeBehavior::Abort,
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
+            &trader,
+            OrderPacket::new_place_partial_limit_order(
+                Side::Ask,
+                110,
+                10,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
+    assert_eq!(resting_size(&market, Side::Ask, order_id), BaseLots::new(4));
 
-    assert_eq!(res.num_base_lots_in, BaseLots::new(10101));
-    assert_eq!(res.num_quote_lots_out, QuoteLots::new(10101 * 99));
-}
-
-#[test]
-fn test_fees_basic() {
-    let mut rng = StdRng::seed_from_u64(2);
-    let taker_bps = 5;
-    let tick_size_in_quote_lots_per_base_unit = QuoteLotsPerBaseUnitPerTick::new(10000_u64);
-    let mut market = Box::new(setup_market_with_params(
-        tick_size_in_quote_lots_per_base_unit.as_u64(),
-        1000_u64,
-        taker_bps,
-    ));
-    let base_lots_per_base_unit = market.base_lots_per_base_unit;
-    let mut event_recorder = VecDeque::new();
-    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
-
-    let trader = rng.gen::<u128>();
-    let taker = rng.gen::<u128>();
-
-    assert!(market
+    // Nothing is affordable, so the order is not posted
+    let (order_id, matching_engine_response) = market
         .place_order(
             &trader,
-            OrderPacket::new_post_only_default(Side::Bid, 9900, 10),
+            OrderPacket::new_place_partial_limit_order(
+                Side::Ask,
+                111,
+                10,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
-    assert!(market
+        .unwrap();
+    assert!(order_id.is_none());
+    assert_eq!(matching_engine_response, MatchingEngineResponse::default());
+
+    market
         .place_order(
-            &trader,
-            OrderPacket::new_post_only_default(Side::Ask, 10100, 10),
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Ask, 105, 2),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .unwrap();
 
-    let (o_id, release_quantities) = market
-        .place_order(
-            &taker,
-            OrderPacket::new_ioc_by_lots(
+    // The bid fills 2 base lots at 105 for 21_000 quote lots, and the remaining 29_000 quote lots
+    // of the budget lock 2 base lots at 106
+    let (order_id, matching_engine_response) = market
+        .place_order_with_token_budget(
+            &bidder,
+            OrderPacket::new_place_partial_limit_order(
                 Side::Bid,
-                10100,
+                106,
                 10,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
+            (BaseLots::ZERO, QuoteLots::new(50_000)),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o_id.is_none());
-    assert!(release_quantities.num_base_lots_out == BaseLots::new(10));
+    assert_eq!(matching_engine_response.num_base_lots_out, BaseLots::new(2));
+    assert_eq!(resting_size(&market, Side::Bid, order_id), BaseLots::new(2));
+    assert_eq!(
+        matching_engine_response.num_quote_lots_posted,
+        QuoteLots::new(21_200)
+    );
     assert!(
-        release_quantities.num_quote_lots_in.as_u64()
-            == (Ticks::new(10100) * tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
-                / base_lots_per_base_unit)
-                .as_u64()
-                * (10000 + taker_bps)
-                / 10000
+        matching_engine_response.num_quote_lots_in + matching_engine_response.num_quote_lots_posted
+            <= QuoteLots::new(50_000)
     );
 
-    let (o_id, release_quantities) = market
-        .place_order(
-            &taker,
-            OrderPacket::new_ioc_by_lots(
+    // The ask fills the 2 base lots bid at 106 and rests the last base lot of the budget
+    let (order_id, matching_engine_response) = market
+        .place_order_with_token_budget(
+            &asker,
+            OrderPacket::new_place_partial_limit_order(
                 Side::Ask,
-                9900,
+                101,
                 10,
                 SelfTradeBehavior::Abort,
-                None,
-                rng.gen::<u128>(),
+                0,
                 false,
             ),
+            (BaseLots::new(3), QuoteLots::ZERO),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
         .unwrap();
-    assert!(o_id.is_none());
-    assert!(release_quantities.num_base_lots_in == BaseLots::new(10));
-    assert!(
-        release_quantities.num_quote_lots_out.as_u64()
-            == (Ticks::new(9900) * tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
-                / base_lots_per_base_unit)
-                .as_u64()
-                * (10000 - taker_bps)
-                / 10000
-    );
-
-    market.collect_fees(&mut record_event_fn);
-    assert_eq!(market.get_uncollected_fee_amount(), QuoteLots::ZERO);
+    assert_eq!(matching_engine_response.num_base_lots_in, BaseLots::new(2));
+    assert_eq!(resting_size(&market, Side::Ask, order_id), BaseLots::new(1));
 }
 
 #[test]
-fn test_evict_order() {
+fn test_reduce_only_limit_orders() {
     let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
 
     let trader = rng.gen::<u128>();
-    let stink_order = rng.gen::<u128>();
-    let evicter = rng.gen::<u128>();
-    for side in [Side::Bid, Side::Ask].into_iter() {
-        let mut market = setup_market();
+    let maker = rng.gen::<u128>();
+    market.get_or_register_trader(&trader).unwrap();
 
+    let tick_size = market.tick_size_in_quote_lots_per_base_unit;
+    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    {
+        let trader_state = market.get_trader_state_mut(&trader).unwrap();
+        trader_state.base_lots_free += BaseLots::new(30);
+        trader_state.quote_lots_free +=
+            Ticks::new(100) * tick_size * BaseLots::new(10) / base_lots_per_base_unit;
+    }
+
+    // The ask is capped to the 30 free base lots
+    let (order_id, _) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_reduce_only_limit_order(
+                Side::Ask,
+                110,
+                50,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let order_id = order_id.unwrap();
+    assert_eq!(
+        market
+            .get_book(Side::Ask)
+            .get(&order_id)
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(30)
+    );
+
+    // The resting ask locks the inventory it was capped to, so a fill reduces the order and the
+    // inventory backing it by the same amount
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                110,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(
+        market
+            .get_book(Side::Ask)
+            .get(&order_id)
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(20)
+    );
+    let trader_state = market.get_trader_state(&trader).unwrap();
+    assert_eq!(trader_state.base_lots_locked, BaseLots::new(20));
+    assert_eq!(trader_state.base_lots_free, BaseLots::ZERO);
+    // The proceeds of the fill are withdrawn, so only the initial quote lots are free
+    market
+        .claim_funds(
+            &trader,
+            Some(Ticks::new(110) * tick_size * BaseLots::new(10) / base_lots_per_base_unit),
+            None,
+            false,
+        )
+        .unwrap();
+
+    // There is no free base inventory left, so another reduce-only ask is skipped
+    let (order_id, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_reduce_only_limit_order(
+                Side::Ask,
+                111,
+                10,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order_id.is_none());
+    assert_eq!(matching_engine_response, MatchingEngineResponse::default());
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+
+    // The bid is capped to the 10 base lots that the free quote lots can buy
+    let (order_id, _) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_reduce_only_limit_order(
+                Side::Bid,
+                100,
+                50,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let order_id = order_id.unwrap();
+    assert_eq!(
+        market
+            .get_book(Side::Bid)
+            .get(&order_id)
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(10)
+    );
+    let trader_state = market.get_trader_state(&trader).unwrap();
+    assert_eq!(trader_state.base_lots_free, BaseLots::ZERO);
+    assert_eq!(trader_state.quote_lots_free, QuoteLots::ZERO);
+
+    // A crossing reduce-only bid only takes as much liquidity as the free quote lots can buy
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Ask, 105, 100),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market
+        .get_trader_state_mut(&trader)
+        .unwrap()
+        .quote_lots_free +=
+        Ticks::new(106) * tick_size * BaseLots::new(4) / base_lots_per_base_unit;
+    let (order_id, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_reduce_only_limit_order(
+                Side::Bid,
+                106,
+                100,
+                SelfTradeBehavior::Abort,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order_id.is_none());
+    assert_eq!(matching_engine_response.num_base_lots(), BaseLots::new(4));
+    let trader_state = market.get_trader_state(&trader).unwrap();
+    assert_eq!(trader_state.base_lots_free, BaseLots::new(4));
+    assert_eq!(
+        trader_state.quote_lots_free,
+        Ticks::new(1) * tick_size * BaseLots::new(4) / base_lots_per_base_unit
+    );
+}
+
+#[test]
+fn test_mid_price_and_spread() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let trader = rng.gen::<u128>();
+
+    assert_eq!(market.get_mid_price(), None);
+    assert_eq!(market.get_spread(), None);
+
+    market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_mid_price(), None);
+    assert_eq!(market.get_spread(), None);
+
+    market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 105, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    // The mid price is rounded down to a whole tick
+    assert_eq!(market.get_mid_price(), Some(Ticks::new(102)));
+    assert_eq!(market.get_spread(), Some(Ticks::new(5)));
+
+    market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 104, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_mid_price(), Some(Ticks::new(104)));
+    assert_eq!(market.get_spread(), Some(Ticks::new(1)));
+}
+
+#[test]
+fn test_limit_orders_with_self_trade() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    let ladder = market.get_typed_ladder(1);
+    // Assert that cancel provide yields the correct order on the book
+    assert!(ladder.bids[0].size_in_base_lots == BaseLots::new(5));
+    // Can place limit order that would self trade
+    let (_order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let mut res = MatchingEngineResponse::default();
+    res.post_base_lots(BaseLots::new(10));
+    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(1);
+    // Assert that cancel provide yields the correct order on the book
+    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(10));
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                100,
+                15,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                100,
+                15,
+                SelfTradeBehavior::DecrementTake,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::default();
+
+    res.post_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
+            / market.base_lots_per_base_unit,
+    );
+    res.use_free_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
+            / market.base_lots_per_base_unit,
+    );
+    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(1);
+    assert!(ladder.bids[0].size_in_base_lots == BaseLots::new(5));
+
+    // Try to trade with DecrementTake for more than the order size
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order(
+                Side::Ask,
+                100,
+                10,
+                SelfTradeBehavior::DecrementTake,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::new_from_sell(
+        BaseLots::new(5),
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
+            / market.base_lots_per_base_unit,
+    );
+    res.post_base_lots(BaseLots::new(5));
+    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(1);
+    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(5));
+
+    // Try to trade with DecrementTake for less than the order size
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                100,
+                1,
+                SelfTradeBehavior::DecrementTake,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    let res = MatchingEngineResponse::default();
+    assert!(order.is_none());
+    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(1);
+    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(4));
+}
+
+#[test]
+fn test_limit_orders_with_cancel_take() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    assert!(market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 99, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Ask, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // The bid matches against the maker and then terminates when it reaches the trader's own order
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                100,
+                15,
+                SelfTradeBehavior::CancelTake,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    let res = MatchingEngineResponse::new_from_buy(
+        Ticks::new(99) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(5)
+            / market.base_lots_per_base_unit,
+        BaseLots::new(5),
+    );
+    assert!(matching_engine_response == res);
+    let ladder = market.get_typed_ladder(1);
+    // The resting order is left untouched and the remainder of the bid is not posted
+    assert!(ladder.bids.is_empty());
+    assert!(ladder.asks[0].price_in_ticks == Ticks::new(100));
+    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(10));
+
+    // An IOC order that only crosses the trader's own order does not match anything
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                5,
+                SelfTradeBehavior::CancelTake,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    assert!(matching_engine_response == MatchingEngineResponse::default());
+    let ladder = market.get_typed_ladder(1);
+    assert!(ladder.asks[0].size_in_base_lots == BaseLots::new(10));
+}
+
+#[test]
+fn test_limit_orders_with_free_lots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Place 2 bids for 100 and 95, then fill them both
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 95, 15),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Ask, 95, 15,),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Place an offer that utilizes only free lots
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 100, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::default();
+    res.post_base_lots(BaseLots::new(5));
+    res.use_free_base_lots(BaseLots::new(5));
+    assert!(matching_engine_response == res);
+
+    // Place an offer that utilizes both free and new lots
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 100, 20),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::default();
+    res.post_base_lots(BaseLots::new(20));
+    res.use_free_base_lots(BaseLots::new(10));
+
+    assert!(matching_engine_response == res);
+
+    // Place a self trade to free up lots
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Place an offer that matches with the book and posts using free lots
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Bid, 101, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 100, 50),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::new_from_sell(
+        BaseLots::new(10),
+        Ticks::new(101) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
+            / market.base_lots_per_base_unit,
+    );
+    res.post_base_lots(BaseLots::new(40));
+    res.use_free_base_lots(BaseLots::new(25));
+    assert!(matching_engine_response == res);
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Bid, 105, 55,),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Place a bid using some of the freed lots
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 20),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::default();
+
+    res.post_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(20)
+            / market.base_lots_per_base_unit,
+    );
+    res.use_free_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(20)
+            / market.base_lots_per_base_unit,
+    );
+    assert!(matching_engine_response == res);
+
+    // Place a bid using the rest of the freed lots
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 50),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::default();
+    res.post_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
+            / market.base_lots_per_base_unit,
+    );
+    res.use_free_quote_lots(
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
+            / market.base_lots_per_base_unit,
+    );
+    assert!(matching_engine_response == res);
+
+    // Place a bid and self trade against it to free up lots
+    println!("ladder: {:?}", market.get_typed_ladder(3));
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 120, 50),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Ask, 120, 25),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Place a bid that matches with the book and posts using the newly freed lots
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Ask, 110, 25),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    let (order, matching_engine_response) = market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order_default(Side::Bid, 111, 75),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_some());
+    let mut res = MatchingEngineResponse::new_from_buy(
+        Ticks::new(110) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(25)
+            / market.base_lots_per_base_unit,
+        BaseLots::new(25),
+    );
+    res.post_quote_lots(
+        Ticks::new(111) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
+            / market.base_lots_per_base_unit,
+    );
+    res.use_free_quote_lots(
+        Ticks::new(120) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(50)
+            / market.base_lots_per_base_unit,
+    );
+
+    assert!(matching_engine_response == res);
+}
+
+#[test]
+fn test_orders_with_only_free_funds() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Note that both the taker and trader will be registered after attempting to place their first limit order
+    // Limit order fails as the taker has no free funds
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_post_only(Side::Bid, 100, 5, 0, false, true,),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only(Side::Bid, 100, 5, 0, false, false,),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // IOC order fails as the taker has no free funds
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Ask,
+                100,
+                5,
+                SelfTradeBehavior::CancelProvide,
+                None,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Ask,
+                100,
+                5,
+                SelfTradeBehavior::CancelProvide,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Order succeeds with sufficient deposited funds
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_limit_order(
+                Side::Ask,
+                100,
+                5,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                100,
+                5,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order(
+                Side::Ask,
+                100,
+                5,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    // Order succeeds as the trader has funds to cover the matched amount, but not the sent amount
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                6,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+}
+
+fn seed_market_with_orders(
+    trader: &TraderId,
+    market: &mut Dex,
+    record_event_fn: &mut dyn FnMut(MarketEvent<TraderId>),
+) {
+    for i in 1..11 {
+        assert!(market
+            .place_order(
+                trader,
+                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10),
+                record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+        assert!(market
+            .place_order(
+                trader,
+                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10),
+                record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+}
+
+#[test]
+fn test_ioc_with_max_quote_lot_budget() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // A quote lot budget that overflows the adjusted quote lots is treated as unbounded
+    for side in [Side::Bid, Side::Ask] {
+        let (_, matching_engine_response) = market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc(
+                    side,
+                    None,
+                    0,
+                    u64::MAX,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                    None,
+                    None,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert_eq!(matching_engine_response.num_base_lots(), BaseLots::new(100));
+        assert_eq!(market.get_book(side.opposite()).len(), 0);
+    }
+}
+
+#[test]
+fn test_fok_and_ioc_limit_1() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // buy through 3 levels of offers
+    let expected_quote_lots_used = Ticks::new(102) // average price 102
+                * market.tick_size_in_quote_lots_per_base_unit
+                * BaseLots::new(30) // clear 3 levels of 10 base lots each
+                / market.base_lots_per_base_unit;
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                103,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_buy(expected_quote_lots_used, BaseLots::new(30))
+    );
+
+    // sell through 3 levels of bids
+    let expected_quote_lots_used = Ticks::new(98) // average price 98
+                * market.tick_size_in_quote_lots_per_base_unit
+                * BaseLots::new(30) // clear 3 levels of 10 base lots each
+                / market.base_lots_per_base_unit;
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                97,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_sell(BaseLots::new(30), expected_quote_lots_used,)
+    );
+}
+
+#[test]
+fn test_fok_and_ioc_limit_2() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // (2) FOK should fail if the tick/lot budget is not enough to fill the order (changed price limit)
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                102,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                98,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+}
+
+#[test]
+fn test_fok_fill_summary() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // A FOK order that cannot be fully filled is voided and does not record a fill summary
+    let mut failed_events = vec![];
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok(
+                Side::Bid,
+                Some(102),
+                30,
+                0,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+                None,
+                None,
+            ),
+            &mut |e| failed_events.push(e),
+            &mut get_clock_fn,
+        )
+        .is_err());
+    assert!(!failed_events
+        .iter()
+        .any(|e| matches!(e, MarketEvent::FillSummary { .. })));
+
+    // A FOK order that is fully filled records exactly one fill summary
+    let mut events = vec![];
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok(
+                Side::Bid,
+                Some(103),
+                30,
+                0,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+                None,
+                None,
+            ),
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    assert_eq!(
+        matching_engine_response.num_base_lots_out,
+        BaseLots::new(30)
+    );
+    assert_eq!(
+        events
+            .iter()
+            .filter(|e| matches!(e, MarketEvent::FillSummary { .. }))
+            .count(),
+        1
+    );
+    // The fill summary identifies the taker
+    assert!(matches!(
+        events.last(),
+        Some(MarketEvent::FillSummary { taker_id, .. }) if *taker_id == taker
+    ));
+}
+
+#[test]
+fn test_fok_and_ioc_limit_3() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // (3) IOC should succeed if the tick/lot budget is not enough to fill the order (same params as 1)
+    let (o, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_buy_with_limit_price(
+                103,
+                (Ticks::new(102)
+                    * market.tick_size_in_quote_lots_per_base_unit
+                    * BaseLots::new(30)
+                    / market.base_lots_per_base_unit
+                    + QuoteLots::ONE)
+                    .as_u64(),
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o.is_none());
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_buy(
+                Ticks::new(102) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
+                    / market.base_lots_per_base_unit,
+                BaseLots::new(30)
+            )
+    );
+    let (o, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_sell_with_limit_price(
+                97,
+                31,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o.is_none());
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_sell(
+                BaseLots::new(30),
+                Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(30)
+                    / market.base_lots_per_base_unit,
+            )
+    );
+}
+
+#[test]
+fn test_fok_and_ioc_limit_4() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // (4) IOC should succeed if the tick/lot budget is not enough to fill the order (same params as 2)
+    let (o, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_buy_with_limit_price(
+                102,
+                (Ticks::new(102)
+                    * market.tick_size_in_quote_lots_per_base_unit
+                    * BaseLots::new(30)
+                    / market.base_lots_per_base_unit)
+                    .as_u64(),
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o.is_none());
+    // Expect two levels filled
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_buy(
+                Ticks::new(101) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
+                    / market.base_lots_per_base_unit
+                    + Ticks::new(102)
+                        * market.tick_size_in_quote_lots_per_base_unit
+                        * BaseLots::new(10)
+                        / market.base_lots_per_base_unit,
+                BaseLots::new(20)
+            )
+    );
+
+    let (o, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_sell_with_limit_price(
+                98,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o.is_none());
+    // expect two levels filled
+    assert!(
+        matching_engine_response
+            == MatchingEngineResponse::new_from_sell(
+                BaseLots::new(20),
+                Ticks::new(99) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
+                    / market.base_lots_per_base_unit
+                    + Ticks::new(98)
+                        * market.tick_size_in_quote_lots_per_base_unit
+                        * BaseLots::new(10)
+                        / market.base_lots_per_base_unit,
+            )
+    );
+}
+
+#[test]
+fn test_fok_and_ioc_limit_5() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+    // (1) FOK should fail if the base lot budget is not enough to fill the order (changed tick limit)
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                103,
+                31,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                97,
+                31,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+
+    let mut mock_clock_fn = || (1000, 1000);
+
+    // IOC order should return no matches if TIF constraint is not met
+    assert_eq!(
+        market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc(
+                    Side::Bid,
+                    None,
+                    100,
+                    0,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                    Some(2),
+                    None
+                ),
+                &mut record_event_fn,
+                &mut mock_clock_fn,
+            )
+            .unwrap()
+            .1,
+        MatchingEngineResponse::default()
+    );
+
+    // IOC order should return no matches if TIF constraint is not met
+    assert_eq!(
+        market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc(
+                    Side::Bid,
+                    None,
+                    100,
+                    0,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                    None,
+                    Some(2),
+                ),
+                &mut record_event_fn,
+                &mut mock_clock_fn,
+            )
+            .unwrap()
+            .1,
+        MatchingEngineResponse::default()
+    );
+
+    // IOC order should match if TIF constraint is set but not met
+    assert_ne!(
+        market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc(
+                    Side::Bid,
+                    None,
+                    100,
+                    0,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                    Some(1200),
+                    Some(1200),
+                ),
+                &mut record_event_fn,
+                &mut mock_clock_fn,
+            )
+            .unwrap()
+            .1,
+        MatchingEngineResponse::default()
+    );
+
+    assert!(
+        market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc(
+                    Side::Bid,
+                    None,
+                    100,
+                    1,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    rng.gen::<u128>(),
+                    false,
+                    None,
+                    None
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_err(),
+        "Only one of num_base_lots or num_quote_lots should be set"
+    );
+}
+
+#[test]
+fn test_fok_and_ioc_with_free_funds() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    market.get_or_register_trader(&taker).unwrap();
+
+    let tick_size = market.tick_size_in_quote_lots_per_base_unit;
+    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    {
+        let trader_state = market.get_trader_state_mut(&taker).unwrap();
+        trader_state.base_lots_free += BaseLots::new(29);
+        trader_state.quote_lots_free +=
+            Ticks::new(103) * tick_size * BaseLots::new(1) / base_lots_per_base_unit;
+    }
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                99,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                98,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_limit_price(
+                97,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                101,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                102,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    let trader_state = market.get_trader_state_mut(&taker).unwrap();
+    println!("trader_state: {:?}", trader_state);
+
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                103,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                true,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+}
+
+// Base lots = (quote lots * base lots per base unit) / (tick size in quote lots per base unit * price in ticks)
+// Then adjust for fees.
+fn get_min_base_lots_out(
+    quote_lots_in: QuoteLots,
+    tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    base_lots_per_base_unit: BaseLotsPerBaseUnit,
+    price_in_ticks: Ticks,
+    slippage_bps: u64,
+) -> BaseLots {
+    let base_lots_out = ((quote_lots_in * base_lots_per_base_unit).as_u64() as f64
+        / ((tick_size_in_quote_lots_per_base_unit * price_in_ticks).as_u64() as f64))
+        * (1.0 - (slippage_bps as f64 / 10000.0));
+    BaseLots::new(base_lots_out as u64)
+}
+
+fn get_min_quote_lots_out(
+    base_lots_in: BaseLots,
+    tick_size_in_quote_lots_per_base_unit: QuoteLotsPerBaseUnitPerTick,
+    base_lots_per_base_unit: BaseLotsPerBaseUnit,
+    price_in_ticks: Ticks,
+    slippage_bps: u64,
+) -> QuoteLots {
+    let quote_lots_out = ((tick_size_in_quote_lots_per_base_unit * price_in_ticks * base_lots_in)
+        .as_u64() as f64
+        / (base_lots_per_base_unit.as_u64() as f64))
+        * (1.0 - (slippage_bps as f64 / 10000.0));
+    QuoteLots::new(quote_lots_out as u64)
+}
+
+#[test]
+fn test_fok_with_slippage_1() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 5;
+    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    for i in 1..11 {
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+
+    let starting_ladder = market.get_typed_ladder(5);
+
+    assert!(starting_ladder.asks[2].price_in_ticks == Ticks::new(103));
+    assert!(starting_ladder.asks[2].size_in_base_lots == BaseLots::new(30000));
+
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    // Performs a swap order with a slippage of at most 50bps
+    // Go through approximately 3 levels of the book
+    let quote_lots_in =
+        Ticks::new(100) * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(60000)
+            / market.base_lots_per_base_unit;
+    let slippage_bps = 50;
+    let min_base_lots_out = get_min_base_lots_out(
+        quote_lots_in,
+        market.tick_size_in_quote_lots_per_base_unit,
+        base_lots_per_base_unit,
+        Ticks::new(102),
+        slippage_bps,
+    );
+
+    println!("min base_lots_out: {}", min_base_lots_out);
+    println!("quote_lots_in: {}", quote_lots_in);
+
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_buy_with_slippage(
+                quote_lots_in.as_u64(),
+                min_base_lots_out.as_u64(),
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    println!("matching_engine_response: {:?}", matching_engine_response);
+    assert!(order.is_none());
+
+    // Ensure that the fill was within the slippage limit
+    let average_price_in_ticks = (base_lots_per_base_unit.as_u64() as f64
+        * matching_engine_response.num_quote_lots_in.as_u64() as f64
+        / (market.tick_size_in_quote_lots_per_base_unit.as_u64() as f64))
+        / matching_engine_response.num_base_lots_out.as_u64() as f64;
+    println!("average_price_in_ticks: {}", average_price_in_ticks);
+    let bps = (average_price_in_ticks - 102.0) / 102.0 * 10000.0;
+    println!("bps: {}", bps);
+    assert!(bps.floor() <= 50.0);
+
+    let ladder = market.get_typed_ladder(5);
+
+    let mut prev_ladder = starting_ladder;
+    for event in event_recorder.iter() {
+        if let MarketEvent::Fill {
+            order_sequence_number: order_id,
+            base_lots_filled,
+            price_in_ticks,
+            ..
+        } = event
+        {
+            let book = match Side::from_order_sequence_number(*order_id) {
+                Side::Bid => &mut prev_ladder.bids,
+                Side::Ask => &mut prev_ladder.asks,
+            };
+            assert!(!book.is_empty());
+            assert!(book[0].price_in_ticks == *price_in_ticks);
+            book[0].size_in_base_lots -= *base_lots_filled;
+            if book[0].size_in_base_lots == BaseLots::ZERO {
+                book.remove(0);
+            }
+        }
+    }
+    assert!(ladder.asks[0].price_in_ticks == prev_ladder.asks[0].price_in_ticks);
+    assert!(ladder.asks[0].size_in_base_lots == prev_ladder.asks[0].size_in_base_lots);
+}
+
+#[test]
+fn test_fok_with_slippage_2() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 5;
+    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    for i in 1..11 {
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+
+    // Show that the order is rejected if the slippage is too high
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_sell_with_slippage(
+                50_000,
+                (Ticks::new(98)
+                    * market.tick_size_in_quote_lots_per_base_unit
+                    * BaseLots::new(50000)
+                    / market.base_lots_per_base_unit)
+                    .as_u64()
+            ), // 2 full levels, 1 partial level
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+}
+
+#[test]
+fn test_fok_with_slippage_3() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 5;
+    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    for i in 1..11 {
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Bid, 100 - i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+        assert!(market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(Side::Ask, 100 + i, 10000 * i),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_ok());
+    }
+
+    let starting_ladder = market.get_typed_ladder(5);
+    // Performs a swap sell order with a slippage of at most 28bps
+    let target_bps = 28;
+    let base_lots_in = BaseUnits::new(50) * base_lots_per_base_unit;
+    let min_quote_lots_out = get_min_quote_lots_out(
+        base_lots_in,
+        market.tick_size_in_quote_lots_per_base_unit,
+        base_lots_per_base_unit,
+        Ticks::new(98),
+        target_bps,
+    );
+
+    println!("min_quote_lots_out: {}", min_quote_lots_out);
+    println!(
+        "quote_lots out at price of 98: {}",
+        (Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * base_lots_in)
+            / (base_lots_per_base_unit)
+    );
+    let adjusted_bps = (1.0
+        - (min_quote_lots_out.as_u64() as f64
+            / (Ticks::new(98) * market.tick_size_in_quote_lots_per_base_unit * base_lots_in
+                / (base_lots_per_base_unit))
+                .as_u64() as f64))
+        * 10000.0;
+    println!("adjusted_bps: {}", adjusted_bps);
+    let (order, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_sell_with_slippage(
+                base_lots_in.as_u64(),
+                min_quote_lots_out.as_u64(),
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order.is_none());
+    // Ensure that the fill was within the slippage limit
+    assert!(matching_engine_response.num_base_lots_in == base_lots_in);
+    let average_price_in_ticks = (base_lots_per_base_unit.as_u64() as f64
+        * matching_engine_response.num_quote_lots_out.as_u64() as f64
+        / market.tick_size_in_quote_lots_per_base_unit.as_u64() as f64)
+        / matching_engine_response.num_base_lots_in.as_u64() as f64;
+
+    let bps = ((98.0 - average_price_in_ticks) / 98.0) * 10000.0;
+    println!("average_price_in_ticks: {}", average_price_in_ticks);
+    println!("bps: {}", bps);
+    assert!(bps.floor() <= bps + taker_bps as f64);
+
+    let ladder = market.get_typed_ladder(5);
+    let mut prev_ladder = starting_ladder;
+    for event in event_recorder.iter() {
+        if let MarketEvent::Fill {
+            order_sequence_number: order_id,
+            base_lots_filled,
+            price_in_ticks,
+            ..
+        } = event
+        {
+            let book = match Side::from_order_sequence_number(*order_id) {
+                Side::Bid => &mut prev_ladder.bids,
+                Side::Ask => &mut prev_ladder.asks,
+            };
+            assert!(!book.is_empty());
+            assert!(book[0].price_in_ticks == *price_in_ticks);
+            book[0].size_in_base_lots -= *base_lots_filled;
+            if book[0].size_in_base_lots == BaseLots::ZERO {
+                book.remove(0);
+            }
+        }
+    }
+    assert!(ladder.bids[0].price_in_ticks == prev_ladder.bids[0].price_in_ticks);
+    assert!(ladder.bids[0].size_in_base_lots == prev_ladder.bids[0].size_in_base_lots);
+}
+
+#[test]
+fn test_fok_with_quote_lot_budget() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market_with_params(10000, 100, 5);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Each base lot costs 100 quote lots per tick
+    for (side, price_in_ticks) in [
+        (Side::Ask, 10),
+        (Side::Ask, 11),
+        (Side::Bid, 9),
+        (Side::Bid, 8),
+    ] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(side, price_in_ticks, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+
+    // After the 5 bps fee, 5000 quote lots buy 4 base lots at 10 ticks. The rest of the budget
+    // cannot pay for another base lot, so the order is filled even though it spends less than
+    // its budget
+    let fok_buy = OrderPacket::new_fok_buy_with_quote_lot_budget(
+        11,
+        5000,
+        SelfTradeBehavior::Abort,
+        None,
+        0,
+        false,
+    );
+    let simulated_response = simulate_swap(&market, &fok_buy, get_clock_fn());
+    let (_, response) = market
+        .place_order(&taker, fok_buy, &mut record_event_fn, &mut get_clock_fn)
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(4));
+    assert_eq!(response.num_quote_lots_in, QuoteLots::new(4000 + 2));
+    assert_eq!(simulated_response, response);
+
+    // Selling 2 base lots at 9 ticks is as close as whole base lots get to receiving 2500 quote
+    // lots
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_fok_sell_with_quote_lot_budget(
+                8,
+                2500,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_in, BaseLots::new(2));
+    assert_eq!(response.num_quote_lots_out, QuoteLots::new(1800 - 1));
+
+    // Orders whose budget exceeds the liquidity within their limit price are not filled
+    for fok_order_packet in [
+        OrderPacket::new_fok_buy_with_quote_lot_budget(
+            11,
+            30000,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        ),
+        OrderPacket::new_fok_sell_with_quote_lot_budget(
+            8,
+            100000,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        ),
+    ] {
+        assert_eq!(
+            simulate_swap(&market, &fok_order_packet, get_clock_fn()),
+            MatchingEngineResponse::default()
+        );
+        assert_eq!(
+            market.place_order(
+                &taker,
+                fok_order_packet,
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            ),
+            Err(PhoenixMatchError::FillOrKillNotFilled)
+        );
+    }
+}
+
+#[test]
+fn test_sell_with_quote_lot_budget() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 0;
+    let mut market = Box::new(setup_market_with_params(1000_u64, 1000_u64, taker_bps));
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Bid, 100, 10000),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 20000),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    let (_, res) = market
+        .place_order(
+            &taker,
+            OrderPacket::ImmediateOrCancel {
+                side: Side::Ask,
+                num_base_lots: BaseLots::ZERO,
+                num_quote_lots: QuoteLots::new(1000000),
+                min_base_lots_to_fill: BaseLots::new(0),
+                min_quote_lots_to_fill: QuoteLots::new(0),
+                match_limit: None,
+                use_only_deposited_funds: false,
+                client_order_id: 0,
+                price_in_ticks: None,
+                self_trade_behavior: SelfTradeBehavior::Abort,
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+                max_avg_price_in_ticks: None,
+                referrer: None,
+            },
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    assert_eq!(res.num_quote_lots_out, QuoteLots::new(1000000));
+
+    let (_, res) = market
+        .place_order(
+            &taker,
+            OrderPacket::ImmediateOrCancel {
+                side: Side::Ask,
+                num_base_lots: BaseLots::ZERO,
+                num_quote_lots: QuoteLots::new(1000000),
+                min_base_lots_to_fill: BaseLots::new(0),
+                min_quote_lots_to_fill: QuoteLots::new(0),
+                match_limit: None,
+                use_only_deposited_funds: false,
+                client_order_id: 0,
+                price_in_ticks: None,
+                self_trade_behavior: SelfTradeBehavior::Abort,
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+                max_avg_price_in_ticks: None,
+                referrer: None,
+            },
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    assert_eq!(res.num_base_lots_in, BaseLots::new(10101));
+    assert_eq!(res.num_quote_lots_out, QuoteLots::new(10101 * 99));
+}
+
+#[test]
+fn test_fees_basic() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 5;
+    let tick_size_in_quote_lots_per_base_unit = QuoteLotsPerBaseUnitPerTick::new(10000_u64);
+    let mut market = Box::new(setup_market_with_params(
+        tick_size_in_quote_lots_per_base_unit.as_u64(),
+        1000_u64,
+        taker_bps,
+    ));
+    let base_lots_per_base_unit = market.base_lots_per_base_unit;
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Bid, 9900, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+    assert!(market
+        .place_order(
+            &trader,
+            OrderPacket::new_post_only_default(Side::Ask, 10100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    let (o_id, release_quantities) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                10100,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o_id.is_none());
+    assert!(release_quantities.num_base_lots_out == BaseLots::new(10));
+    assert!(
+        release_quantities.num_quote_lots_in.as_u64()
+            == (Ticks::new(10100) * tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
+                / base_lots_per_base_unit)
+                .as_u64()
+                * (10000 + taker_bps)
+                / 10000
+    );
+
+    let (o_id, release_quantities) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Ask,
+                9900,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(o_id.is_none());
+    assert!(release_quantities.num_base_lots_in == BaseLots::new(10));
+    assert!(
+        release_quantities.num_quote_lots_out.as_u64()
+            == (Ticks::new(9900) * tick_size_in_quote_lots_per_base_unit * BaseLots::new(10)
+                / base_lots_per_base_unit)
+                .as_u64()
+                * (10000 - taker_bps)
+                / 10000
+    );
+
+    market.collect_fees(&mut record_event_fn);
+    assert_eq!(market.get_uncollected_fee_amount(), QuoteLots::ZERO);
+}
+
+#[test]
+fn test_maker_rebates() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let taker_bps = 5;
+    let maker_rebate_bps = 2;
+    let mut market = Box::new(setup_market_with_params(10000, 1000, taker_bps));
+    market.set_maker_rebate(maker_rebate_bps);
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 10100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 10200, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    // Notional is 10100 * 10000 * 10 / 1000 = 1_010_000 quote lots
+    let mut events = vec![];
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                10100,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let expected_fee = QuoteLots::new(1_010_000 * taker_bps / 10000);
+    let expected_rebate = QuoteLots::new(1_010_000 * maker_rebate_bps / 10000);
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_free,
+        QuoteLots::new(1_010_000) + expected_rebate
+    );
+    assert_eq!(
+        market.get_uncollected_fee_amount(),
+        expected_fee - expected_rebate
+    );
+    assert!(events.iter().any(|e| matches!(
+        e,
+        MarketEvent::Rebate {
+            maker_id,
+            rebate_in_quote_lots,
+        } if *maker_id == maker && *rebate_in_quote_lots == expected_rebate
+    )));
+
+    // Rebates are clamped to the taker fees collected on the match
+    market.set_maker_rebate(taker_bps * 2);
+    let uncollected_fees = market.get_uncollected_fee_amount();
+    let maker_quote_lots_free = market.get_trader_state(&maker).unwrap().quote_lots_free;
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                10200,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let expected_fee = QuoteLots::new(1_020_000 * taker_bps / 10000);
+    assert_eq!(market.get_uncollected_fee_amount(), uncollected_fees);
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_free,
+        maker_quote_lots_free + QuoteLots::new(1_020_000) + expected_fee
+    );
+
+    // Each fill is rebated separately, including repeated fills against the same maker
+    market.set_maker_rebate(maker_rebate_bps);
+    let other_maker = rng.gen::<u128>();
+    for trader in [&maker, &other_maker, &maker] {
+        market
+            .place_order(
+                trader,
+                OrderPacket::new_post_only_default(Side::Ask, 10300, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    let uncollected_fees = market.get_uncollected_fee_amount();
+    let maker_quote_lots_free = market.get_trader_state(&maker).unwrap().quote_lots_free;
+    let mut events = vec![];
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                10300,
+                30,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    // Each fill has a notional of 1_030_000 quote lots
+    let rebate_per_fill = 1_030_000 * maker_rebate_bps / 10000;
+    let rebates = events
+        .iter()
+        .filter_map(|e| match e {
+            MarketEvent::Rebate {
+                maker_id,
+                rebate_in_quote_lots,
+            } => Some((*maker_id, rebate_in_quote_lots.as_u64())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rebates,
+        vec![
+            (maker, rebate_per_fill),
+            (other_maker, rebate_per_fill),
+            (maker, rebate_per_fill)
+        ]
+    );
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_free,
+        maker_quote_lots_free + QuoteLots::new(2 * 1_030_000 + 2 * rebate_per_fill)
+    );
+    let expected_fee = 3 * 1_030_000 * taker_bps / 10000;
+    assert_eq!(
+        market.get_uncollected_fee_amount(),
+        uncollected_fees + QuoteLots::new(expected_fee - 3 * rebate_per_fill)
+    );
+}
+
+#[test]
+fn test_get_impact_price() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader = rng.gen::<u128>();
+    assert!(market
+        .get_impact_price(Side::Bid, BaseLots::new(10))
+        .is_none());
+    seed_market_with_orders(&trader, &mut market, &mut record_event_fn);
+
+    // 10 @ 101, 10 @ 102, 5 @ 103 averages 101.8, rounded up for buys
+    assert_eq!(
+        market.get_impact_price(Side::Bid, BaseLots::new(25)),
+        Some((Ticks::new(102), BaseLots::new(25)))
+    );
+    // 10 @ 99, 10 @ 98, 5 @ 97 averages 98.2, rounded down for sells
+    assert_eq!(
+        market.get_impact_price(Side::Ask, BaseLots::new(25)),
+        Some((Ticks::new(98), BaseLots::new(25)))
+    );
+    // The fill is capped by the size of the book
+    assert_eq!(
+        market.get_impact_price(Side::Bid, BaseLots::new(1000)),
+        Some((Ticks::new(106), BaseLots::new(100)))
+    );
+    assert_eq!(
+        market.get_impact_price(Side::Ask, BaseLots::new(10)),
+        Some((Ticks::new(99), BaseLots::new(10)))
+    );
+
+    let order_packet = OrderPacket::PostOnly {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(100),
+        num_base_lots: BaseLots::new(10),
+        client_order_id: rng.gen::<u128>(),
+        use_only_deposited_funds: false,
+        reject_post_only: true,
+        last_valid_slot: Some(5),
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+    market
+        .place_order(
+            &trader,
+            order_packet,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(
+        market.get_impact_price(Side::Bid, BaseLots::new(10)),
+        Some((Ticks::new(100), BaseLots::new(10)))
+    );
+    // Expired orders are skipped
+    assert_eq!(
+        market.get_impact_price_with_expiration(Side::Bid, BaseLots::new(10), Some(6), None),
+        Some((Ticks::new(101), BaseLots::new(10)))
+    );
+
+    // The book is not modified
+    let sequence_number = market.get_sequence_number();
+    let ladder = market.get_typed_ladder(5);
+    market.get_impact_price(Side::Bid, BaseLots::new(1000));
+    assert_eq!(market.get_sequence_number(), sequence_number);
+    assert_eq!(market.get_typed_ladder(5), ladder);
+}
+
+#[test]
+fn test_cancel_up_to_lots_to_free() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    for (side, price) in [
+        (Side::Bid, 100),
+        (Side::Bid, 99),
+        (Side::Bid, 98),
+        (Side::Ask, 101),
+        (Side::Ask, 102),
+    ] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(side, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+
+    // Each bid is worth price * 10000 * 10 / 100 quote lots. The order that crosses the
+    // threshold is cancelled in full.
+    market
+        .cancel_up_to(
+            &maker,
+            Side::Bid,
+            None,
+            None,
+            None,
+            Some(150_000),
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    let bids = market
+        .get_book(Side::Bid)
+        .iter()
+        .map(|(o_id, _)| o_id.price_in_ticks)
+        .collect::<Vec<_>>();
+    assert_eq!(bids, vec![Ticks::new(98)]);
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_free,
+        QuoteLots::new(199_000)
+    );
+
+    // Asks are measured in base lots
+    market
+        .cancel_up_to(
+            &maker,
+            Side::Ask,
+            None,
+            None,
+            None,
+            Some(10),
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    let asks = market
+        .get_book(Side::Ask)
+        .iter()
+        .map(|(o_id, _)| o_id.price_in_ticks)
+        .collect::<Vec<_>>();
+    assert_eq!(asks, vec![Ticks::new(102)]);
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().base_lots_free,
+        BaseLots::new(10)
+    );
+
+    // The order count limit takes effect before the lots target is reached
+    market
+        .cancel_up_to(
+            &maker,
+            Side::Bid,
+            None,
+            Some(0),
+            None,
+            Some(1),
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_book(Side::Bid).len(), 1);
+}
+
+#[test]
+fn test_cancel_in_price_range() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let other_maker = rng.gen::<u128>();
+    for price in [100, 99, 98, 97] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Bid, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    market
+        .place_order(
+            &other_maker,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 101, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    fn get_bids(market: &Dex, trader: &TraderId) -> Vec<u64> {
+        let trader_index = market.get_trader_index(trader).unwrap() as u64;
+        market
+            .get_book(Side::Bid)
+            .iter()
+            .filter(|(_, o)| o.trader_index == trader_index)
+            .map(|(o_id, _)| o_id.price_in_ticks.as_u64())
+            .collect::<Vec<_>>()
+    }
+
+    // Only the maker's orders inside the inclusive range are removed, and the funds are claimed
+    let response = market
+        .cancel_in_price_range(
+            &maker,
+            Side::Bid,
+            Ticks::new(98),
+            Ticks::new(99),
+            true,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_quote_lots_out, QuoteLots::new(197_000));
+    assert_eq!(get_bids(&market, &maker), vec![100, 97]);
+    assert_eq!(get_bids(&market, &other_maker), vec![99]);
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+
+    // A range with equal bounds cancels a single price level, and funds stay free in the market
+    let response = market
+        .cancel_in_price_range(
+            &maker,
+            Side::Bid,
+            Ticks::new(100),
+            Ticks::new(100),
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_quote_lots_out, QuoteLots::ZERO);
+    assert_eq!(get_bids(&market, &maker), vec![97]);
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_free,
+        QuoteLots::new(100_000)
+    );
+
+    // A range with no orders is a no-op
+    market
+        .cancel_in_price_range(
+            &maker,
+            Side::Ask,
+            Ticks::new(102),
+            Ticks::new(200),
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+}
+
+#[test]
+fn test_amend_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let other_maker = rng.gen::<u128>();
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let order_id = order_id.unwrap();
+    let (other_order_id, _) = market
+        .place_order(
+            &other_maker,
+            OrderPacket::new_post_only_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let other_order_id = other_order_id.unwrap();
+
+    // Traders cannot amend orders that they do not own
+    assert!(market
+        .amend_order(
+            &maker,
+            &other_order_id,
+            Side::Bid,
+            BaseLots::new(5),
+            None,
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_none());
+
+    // Decreasing the size at the same price preserves priority
+    let mut events = vec![];
+    let (amended_order_id, _) = market
+        .amend_order(
+            &maker,
+            &order_id,
+            Side::Bid,
+            BaseLots::new(5),
+            Some(Ticks::new(100)),
+            0,
+            None,
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(amended_order_id, Some(order_id));
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        events[0],
+        MarketEvent::Reduce {
+            order_sequence_number,
+            base_lots_removed,
+            base_lots_remaining,
+            ..
+        } if order_sequence_number == order_id.order_sequence_number
+            && base_lots_removed == BaseLots::new(5)
+            && base_lots_remaining == BaseLots::new(5)
+    ));
+    let (first_order_id, first_order) = market.get_book(Side::Bid).iter().next().unwrap();
+    assert_eq!(*first_order_id, order_id);
+    assert_eq!(first_order.num_base_lots, BaseLots::new(5));
+
+    // Increasing the size re-queues the order behind other orders at the same price
+    let mut events = vec![];
+    let (amended_order_id, _) = market
+        .amend_order(
+            &maker,
+            &order_id,
+            Side::Bid,
+            BaseLots::new(8),
+            None,
+            0,
+            None,
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let amended_order_id = amended_order_id.unwrap();
+    assert_eq!(amended_order_id.price_in_ticks, Ticks::new(100));
+    assert!(amended_order_id.order_sequence_number > other_order_id.order_sequence_number);
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        events[0],
+        MarketEvent::Reduce {
+            base_lots_removed,
+            base_lots_remaining,
+            ..
+        } if base_lots_removed == BaseLots::new(5) && base_lots_remaining == BaseLots::ZERO
+    ));
+    assert!(matches!(
+        events[1],
+        MarketEvent::Place {
+            base_lots_placed,
+            ..
+        } if base_lots_placed == BaseLots::new(8)
+    ));
+    let (first_order_id, _) = market.get_book(Side::Bid).iter().next().unwrap();
+    assert_eq!(*first_order_id, other_order_id);
+
+    // Changing the price re-queues the order
+    let (repriced_order_id, _) = market
+        .amend_order(
+            &maker,
+            &amended_order_id,
+            Side::Bid,
+            BaseLots::new(8),
+            Some(Ticks::new(99)),
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let repriced_order_id = repriced_order_id.unwrap();
+    assert_eq!(repriced_order_id.price_in_ticks, Ticks::new(99));
+    assert!(market.get_book(Side::Bid).get(&amended_order_id).is_none());
+    assert_eq!(
+        market
+            .get_book(Side::Bid)
+            .get(&repriced_order_id)
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(8)
+    );
+
+    // Increasing the size beyond the trader's deposited funds fails
+    assert!(market
+        .amend_order(
+            &maker,
+            &repriced_order_id,
+            Side::Bid,
+            BaseLots::new(20),
+            None,
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_none());
+}
+
+#[test]
+fn test_reprice_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let other_maker = rng.gen::<u128>();
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let order_id = order_id.unwrap();
+    let (other_order_id, _) = market
+        .place_order(
+            &other_maker,
+            OrderPacket::new_post_only_default(Side::Ask, 110, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let other_order_id = other_order_id.unwrap();
+    // 10 base lots at a price of 100 ticks
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().quote_lots_locked,
+        QuoteLots::new(100_000)
+    );
+
+    // Traders cannot reprice orders that they do not own
+    assert!(market
+        .reprice_order(
+            &maker,
+            &other_order_id,
+            Side::Ask,
+            Ticks::new(120),
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_none());
+
+    // Repricing to a price that crosses the book fails
+    assert!(market
+        .reprice_order(
+            &maker,
+            &order_id,
+            Side::Bid,
+            Ticks::new(110),
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_none());
+
+    // Lowering the price of a bid unlocks the difference in quote lots
+    let mut events = vec![];
+    let repriced_order_id = market
+        .reprice_order(
+            &maker,
+            &order_id,
+            Side::Bid,
+            Ticks::new(90),
+            7,
+            None,
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(repriced_order_id.price_in_ticks, Ticks::new(90));
+    assert!(repriced_order_id.order_sequence_number > other_order_id.order_sequence_number);
+    assert!(market.get_book(Side::Bid).get(&order_id).is_none());
+    assert_eq!(
+        market
+            .get_book(Side::Bid)
+            .get(&repriced_order_id)
+            .unwrap()
+            .num_base_lots,
+        BaseLots::new(10)
+    );
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        events[0],
+        MarketEvent::Reduce {
+            order_sequence_number,
+            base_lots_removed,
+            base_lots_remaining,
+            ..
+        } if order_sequence_number == order_id.order_sequence_number
+            && base_lots_removed == BaseLots::new(10)
+            && base_lots_remaining == BaseLots::ZERO
+    ));
+    assert!(matches!(
+        events[1],
+        MarketEvent::Place {
+            order_sequence_number,
+            price_in_ticks,
+            base_lots_placed,
+            client_order_id,
+        } if order_sequence_number == repriced_order_id.order_sequence_number
+            && price_in_ticks == Ticks::new(90)
+            && base_lots_placed == BaseLots::new(10)
+            && client_order_id == 7
+    ));
+    let trader_state = market.get_trader_state(&maker).unwrap();
+    assert_eq!(trader_state.quote_lots_locked, QuoteLots::new(90_000));
+    assert_eq!(trader_state.quote_lots_free, QuoteLots::new(10_000));
+    assert_eq!(trader_state.num_open_orders(Side::Bid), 1);
+
+    // Raising the price of a bid requires enough free quote lots to cover the difference
+    assert!(market
+        .reprice_order(
+            &maker,
+            &repriced_order_id,
+            Side::Bid,
+            Ticks::new(105),
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_none());
+    assert!(market.get_book(Side::Bid).get(&repriced_order_id).is_some());
+    market.get_trader_state_mut(&maker).unwrap().quote_lots_free += QuoteLots::new(5_000);
+    let final_order_id = market
+        .reprice_order(
+            &maker,
+            &repriced_order_id,
+            Side::Bid,
+            Ticks::new(105),
+            0,
+            None,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(final_order_id.price_in_ticks, Ticks::new(105));
+    let trader_state = market.get_trader_state(&maker).unwrap();
+    assert_eq!(trader_state.quote_lots_locked, QuoteLots::new(105_000));
+    assert_eq!(trader_state.quote_lots_free, QuoteLots::ZERO);
+    assert_eq!(market.get_book(Side::Bid).len(), 1);
+}
+
+#[test]
+fn test_bid_quote_lots_locked_returns_to_zero() {
+    for seed in 0..20 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut market = Box::new(setup_market_with_params(100, 100, 0));
+        let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+        let maker = rng.gen::<u128>();
+        let taker = rng.gen::<u128>();
+
+        let mut order_ids = vec![];
+        for _ in 0..rng.gen_range(1, 50) {
+            let (order_id, _) = market
+                .place_order(
+                    &maker,
+                    OrderPacket::new_post_only_default(
+                        Side::Bid,
+                        rng.gen_range(1, 1000),
+                        rng.gen_range(1, 1000),
+                    ),
+                    &mut record_event_fn,
+                    &mut get_clock_fn,
+                )
+                .unwrap();
+            order_ids.extend(order_id);
+        }
+        // Partially reduce and reprice some of the orders
+        for order_id in order_ids.iter_mut() {
+            let size = market
+                .get_book(Side::Bid)
+                .get(order_id)
+                .unwrap()
+                .num_base_lots;
+            match rng.gen_range(0, 3) {
+                0 => {
+                    market
+                        .reduce_order(
+                            &maker,
+                            order_id,
+                            Side::Bid,
+                            Some(BaseLots::new(rng.gen_range(0, size.as_u64()))),
+                            false,
+                            None,
+                            &mut record_event_fn,
+                        )
+                        .unwrap();
+                }
+                1 => {
+                    market.get_trader_state_mut(&maker).unwrap().quote_lots_free +=
+                        QuoteLots::new(1_000_000);
+                    *order_id = market
+                        .reprice_order(
+                            &maker,
+                            order_id,
+                            Side::Bid,
+                            Ticks::new(rng.gen_range(1, 1000)),
+                            0,
+                            None,
+                            &mut record_event_fn,
+                            &mut get_clock_fn,
+                        )
+                        .unwrap();
+                }
+                _ => {}
+            }
+        }
+        // Partially fill the bids
+        market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
+                    Side::Ask,
+                    1,
+                    rng.gen_range(1, 10_000),
+                    SelfTradeBehavior::DecrementTake,
+                    None,
+                    0,
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+
+        market
+            .cancel_all_orders(&maker, false, None, &mut record_event_fn)
+            .unwrap();
+        let trader_state = market.get_trader_state(&maker).unwrap();
+        assert_eq!(trader_state.quote_lots_locked, QuoteLots::ZERO);
+        assert_eq!(trader_state.num_open_orders(Side::Bid), 0);
+    }
+}
+
+#[test]
+fn test_evict_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+
+    let trader = rng.gen::<u128>();
+    let stink_order = rng.gen::<u128>();
+    let evicter = rng.gen::<u128>();
+    for side in [Side::Bid, Side::Ask].into_iter() {
+        let mut market = setup_market();
+
+        let mut event_recorder = VecDeque::new();
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        let price = Ticks::new(1000);
+        for _ in 0..market.get_book(side).capacity() - 1 {
+            market
+                .place_order(
+                    &trader,
+                    OrderPacket::new_post_only_default(side, price.as_u64(), 1),
+                    &mut record_event_fn,
+                    &mut get_clock_fn,
+                )
+                .unwrap();
+        }
+        let direction = match side {
+            Side::Bid => -1,
+            Side::Ask => 1,
+        };
+        let stink_price = Ticks::new((price.as_u64() as i64 + direction * 500) as u64);
+        assert!(!market.is_book_full(side));
+        let (stink_order_id, _) = market
+            .place_order(
+                &stink_order,
+                OrderPacket::new_post_only_default(side, stink_price.as_u64(), 99),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert!(market.is_book_full(side));
+        assert!(!market.is_book_full(side.opposite()));
+        // The stink order is the least aggressive order, so it is the next order to be evicted
+        let (worst_order_id, worst_order) = market.get_worst_order(side).unwrap();
+        assert_eq!(Some(worst_order_id), stink_order_id);
+        assert_eq!(worst_order.num_base_lots, BaseLots::new(99));
+        // Order must be more aggressive than the least aggressive order in a full book
+        assert!(market
+            .place_order(
+                &stink_order,
+                OrderPacket::new_post_only_default(side, stink_price.as_u64(), 99),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .is_err());
+        let mut event_recorder = VecDeque::new();
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        let (evicter_order_id, _) = market
+            .place_order(
+                &evicter,
+                OrderPacket::new_post_only_default(
+                    side,
+                    (price.as_u64() as i64 + direction) as u64,
+                    99,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+
+        event_recorder.pop_back();
+        let evict_event = *event_recorder.back().unwrap();
+        if let MarketEvent::Evict {
+            order_sequence_number: order_id,
+            price_in_ticks,
+            maker_id,
+            base_lots_evicted: base_lots_removed,
+            evicting_order_id,
+        } = evict_event
+        {
+            assert_eq!(Some(evicting_order_id), evicter_order_id);
+            assert!(Side::from_order_sequence_number(order_id) == side);
+            assert_eq!(price_in_ticks, stink_price);
+            assert_eq!(maker_id, stink_order);
+            assert_eq!(base_lots_removed, BaseLots::new(99));
+            let trader_state = market.traders.get(&stink_order).unwrap();
+            if side == Side::Ask {
+                assert_eq!(trader_state.base_lots_free, BaseLots::new(99));
+            } else {
+                assert_eq!(
+                    trader_state.quote_lots_free,
+                    stink_price * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(99)
+                        / market.base_lots_per_base_unit
+                );
+            }
+        } else {
+            panic!("Expected evict event");
+        }
+    }
+}
+
+#[test]
+fn test_get_worst_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    for side in [Side::Bid, Side::Ask] {
+        assert!(market.get_worst_order(side).is_none());
+        assert!(!market.is_book_full(side));
+    }
+
+    let mut place_order =
+        |market: &mut Dex, side: Side, price_in_ticks: u64, num_base_lots: u64| {
+            market
+                .place_order(
+                    &maker,
+                    OrderPacket::new_post_only_default(side, price_in_ticks, num_base_lots),
+                    &mut record_event_fn,
+                    &mut get_clock_fn,
+                )
+                .unwrap()
+                .0
+                .unwrap()
+        };
+    place_order(&mut market, Side::Bid, 99, 1);
+    let worst_bid_id = place_order(&mut market, Side::Bid, 97, 2);
+    place_order(&mut market, Side::Bid, 98, 3);
+    place_order(&mut market, Side::Ask, 101, 1);
+    place_order(&mut market, Side::Ask, 103, 2);
+    let worst_ask_id = place_order(&mut market, Side::Ask, 103, 3);
+
+    // The worst order is the one with the least aggressive price, and the newest order at that
+    // price
+    let (order_id, order) = market.get_worst_order(Side::Bid).unwrap();
+    assert_eq!(order_id, worst_bid_id);
+    assert_eq!(order.num_base_lots, BaseLots::new(2));
+    let (order_id, order) = market.get_worst_order(Side::Ask).unwrap();
+    assert_eq!(order_id, worst_ask_id);
+    assert_eq!(order.num_base_lots, BaseLots::new(3));
+
+    // Filling the more aggressive orders does not change the worst order
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Ask,
+                98,
+                4,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_book(Side::Bid).len(), 1);
+    assert_eq!(market.get_worst_order(Side::Bid).unwrap().0, worst_bid_id);
+    assert!(!market.is_book_full(Side::Bid));
+}
+
+#[test]
+fn test_reduce_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let maker = rng.gen::<u128>();
+    let mut event_recorder = VecDeque::new();
+
+    let client_ids = vec![rng.gen::<u128>()];
+    let order_packet = OrderPacket::new_post_only_default_with_client_order_id(
+        Side::Bid,
+        1000,
+        100,
+        client_ids[0],
+    );
+
+    {
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        market
+            .place_order(
+                &maker,
+                order_packet,
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+
+    let event = event_recorder.pop_back().unwrap();
+    let order_id = if let MarketEvent::<u128>::Place {
+        order_sequence_number,
+        price_in_ticks,
+        base_lots_placed,
+        client_order_id,
+    } = event
+    {
+        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
+        assert_eq!(price_in_ticks, Ticks::new(1000));
+        assert_eq!(base_lots_placed, BaseLots::new(100));
+        assert_eq!(client_order_id, client_ids[0]);
+        FIFOOrderId::new(price_in_ticks, order_sequence_number)
+    } else {
+        panic!("Expected place event");
+    };
+
+    {
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        market
+            .reduce_order(
+                &maker,
+                &order_id,
+                Side::Bid,
+                Some(BaseLots::new(10)),
+                true,
+                None,
+                &mut record_event_fn,
+            )
+            .unwrap();
+    }
+
+    let event = event_recorder.pop_back().unwrap();
+    if let MarketEvent::<u128>::Reduce {
+        order_sequence_number,
+        price_in_ticks,
+        base_lots_removed,
+        base_lots_remaining,
+    } = event
+    {
+        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
+        assert_eq!(price_in_ticks, Ticks::new(1000));
+        assert_eq!(base_lots_removed, BaseLots::new(10));
+        assert_eq!(base_lots_remaining, BaseLots::new(90));
+    } else {
+        panic!("Expected reduce event");
+    }
+    assert!(market.bids.get(&order_id).is_some());
+
+    let random_maker = rng.gen::<u128>();
+    {
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        market
+            .place_order(
+                &random_maker,
+                order_packet,
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert!(
+            market
+                .reduce_order(
+                    &random_maker,
+                    &order_id,
+                    Side::Bid,
+                    Some(BaseLots::new(10)),
+                    true,
+                    None,
+                    &mut record_event_fn,
+                )
+                .is_none(),
+            "Trader ID must match order"
+        );
+
+        assert_eq!(
+            market
+                .reduce_order(
+                    &maker,
+                    &FIFOOrderId::new_from_untyped(rng.gen::<u64>(), rng.gen::<u64>()),
+                    Side::Bid,
+                    Some(BaseLots::new(10)),
+                    true,
+                    None,
+                    &mut record_event_fn,
+                )
+                .unwrap(),
+            MatchingEngineResponse::default(),
+            "Order ID not in book"
+        );
+    }
+    // If we pass in more size than is in the order, it should reduce the order to zero and should be removed from the book
+    {
+        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+        market
+            .reduce_order(
+                &maker,
+                &order_id,
+                Side::Bid,
+                Some(BaseLots::new(100)),
+                true,
+                None,
+                &mut record_event_fn,
+            )
+            .unwrap();
+    }
+    let event = event_recorder.pop_back().unwrap();
+    if let MarketEvent::<u128>::Reduce {
+        order_sequence_number,
+        price_in_ticks,
+        base_lots_removed,
+        base_lots_remaining,
+    } = event
+    {
+        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
+        assert_eq!(price_in_ticks, Ticks::new(1000));
+        assert_eq!(base_lots_removed, BaseLots::new(90));
+        assert_eq!(base_lots_remaining, BaseLots::new(0));
+    } else {
+        panic!("Expected reduce event");
+    }
+
+    assert!(market.bids.get(&order_id).is_none());
+}
+
+#[test]
+fn test_reduce_multiple_orders() {
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let (maker, other_maker) = (1, 2);
+
+    let mut place_ask = |trader: &TraderId, price_in_ticks: u64, num_base_lots: u64| {
+        market
+            .place_order(
+                trader,
+                OrderPacket::new_post_only_default(Side::Ask, price_in_ticks, num_base_lots),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap()
+            .0
+            .unwrap()
+    };
+    let first = place_ask(&maker, 100, 10);
+    let second = place_ask(&maker, 101, 10);
+    let not_owned = place_ask(&other_maker, 102, 10);
+    let missing = FIFOOrderId::new_from_untyped(103, 1_000);
+
+    let response = market
+        .reduce_multiple_orders(
+            &maker,
+            &[
+                (first, BaseLots::new(4)),
+                (second, BaseLots::new(25)),
+                (not_owned, BaseLots::new(5)),
+                (missing, BaseLots::new(5)),
+            ],
+            true,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+
+    // The released funds of both orders are claimed together
+    assert_eq!(response.num_base_lots_out, BaseLots::new(14));
+    assert_eq!(response.num_quote_lots_out, QuoteLots::ZERO);
+    assert_eq!(
+        market.asks.get(&first).unwrap().num_base_lots,
+        BaseLots::new(6)
+    );
+    assert!(market.asks.get(&second).is_none());
+    // Orders that belong to other traders are skipped
+    assert_eq!(
+        market.asks.get(&not_owned).unwrap().num_base_lots,
+        BaseLots::new(10)
+    );
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().base_lots_locked,
+        BaseLots::new(6)
+    );
+    assert_eq!(
+        market.get_trader_state(&maker).unwrap().base_lots_free,
+        BaseLots::ZERO
+    );
+}
+
+#[test]
+fn test_tif() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let maker = rng.gen::<u128>();
+
+    pub struct MockClock {
+        slot: u64,
+        timestamp: u64,
+    }
+
+    let now = SystemTime::now();
+    let exp = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .checked_add(1000)
+        .unwrap();
+
+    let order_packet_unix_timestamp_tif = OrderPacket::PostOnly {
+        side: Side::Bid,
+        price_in_ticks: Ticks::new(1000),
+        num_base_lots: BaseLots::new(100),
+        client_order_id: rng.gen::<u128>(),
+        use_only_deposited_funds: false,
+        reject_post_only: true,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: Some(exp),
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+
+    let order_packet_slot_tif = OrderPacket::PostOnly {
+        side: Side::Bid,
+        price_in_ticks: Ticks::new(1000),
+        num_base_lots: BaseLots::new(100),
+        client_order_id: rng.gen::<u128>(),
+        use_only_deposited_funds: false,
+        reject_post_only: true,
+        last_valid_slot: Some(2000),
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+
+    for order_packet in [order_packet_unix_timestamp_tif, order_packet_slot_tif] {
+        let mut mock_clock = MockClock {
+            slot: 1000,
+            timestamp: now.duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        };
         let mut event_recorder = VecDeque::new();
         let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
-        let price = Ticks::new(1000);
-        for _ in 0..market.get_book(side).capacity() - 1 {
-            market.place_order(
-                &trader,
-                OrderPacket::new_post_only_default(side, price.as_u64(), 1),
+
+        {
+            let expired_mock_clock = MockClock {
+                slot: 3000,
+                timestamp: now.duration_since(UNIX_EPOCH).unwrap().as_secs() + 2000,
+            };
+            let mut mock_clock_fn = || (expired_mock_clock.slot, expired_mock_clock.timestamp);
+            assert_eq!(
+                market
+                    .place_order(
+                        &maker,
+                        order_packet,
+                        &mut record_event_fn,
+                        &mut mock_clock_fn,
+                    )
+                    .unwrap()
+                    .1,
+                MatchingEngineResponse::default()
+            );
+        }
+
+        {
+            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
+            market
+                .place_order(
+                    &maker,
+                    order_packet,
+                    &mut record_event_fn,
+                    &mut mock_clock_fn,
+                )
+                .unwrap();
+        }
+
+        let taker = rng.gen::<u128>();
+
+        if order_packet.get_last_valid_slot().is_some() {
+            mock_clock.slot += 500;
+        } else {
+            mock_clock.timestamp += 500;
+        }
+        let (_, matching_engine_response) = {
+            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
+            market
+                .place_order(
+                    &taker,
+                    OrderPacket::new_ioc_by_lots(
+                        Side::Ask,
+                        0,
+                        10,
+                        SelfTradeBehavior::Abort,
+                        None,
+                        rng.gen::<u128>(),
+                        false,
+                    ),
+                    &mut record_event_fn,
+                    &mut mock_clock_fn,
+                )
+                .unwrap()
+        };
+
+        assert!(matching_engine_response.num_quote_lots_out > QuoteLots::ZERO);
+
+        // Check that order are still not expired on the boundary
+        if order_packet.get_last_valid_slot().is_some() {
+            mock_clock.slot += 500;
+        } else {
+            mock_clock.timestamp += 500;
+        }
+
+        let (_, matching_engine_response) = {
+            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
+            market
+                .place_order(
+                    &taker,
+                    OrderPacket::new_ioc_by_lots(
+                        Side::Ask,
+                        0,
+                        10,
+                        SelfTradeBehavior::Abort,
+                        None,
+                        rng.gen::<u128>(),
+                        false,
+                    ),
+                    &mut record_event_fn,
+                    &mut mock_clock_fn,
+                )
+                .unwrap()
+        };
+
+        assert!(matching_engine_response.num_quote_lots_out > QuoteLots::ZERO);
+
+        if order_packet.get_last_valid_slot().is_some() {
+            mock_clock.slot += 1;
+        } else {
+            mock_clock.timestamp += 1;
+        }
+
+        let (_, matching_engine_response) = {
+            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
+            market
+                .place_order(
+                    &taker,
+                    OrderPacket::new_ioc_by_lots(
+                        Side::Ask,
+                        0,
+                        10,
+                        SelfTradeBehavior::Abort,
+                        None,
+                        rng.gen::<u128>(),
+                        false,
+                    ),
+                    &mut record_event_fn,
+                    &mut mock_clock_fn,
+                )
+                .unwrap()
+        };
+
+        // Assert that TIF kicked in
+        assert_eq!(matching_engine_response.num_quote_lots_out, QuoteLots::ZERO);
+
+        // Verify that the events are released in the expected order
+        for (i, event) in event_recorder.iter().enumerate() {
+            match i {
+                0 => {
+                    assert!(matches!(event, MarketEvent::Place { .. }));
+                }
+                1 => {
+                    assert!(matches!(event, MarketEvent::TimeInForce { .. }));
+                }
+                2 | 4 => {
+                    assert!(matches!(event, MarketEvent::Fill { .. }));
+                }
+                3 | 5 | 7 => {
+                    assert!(matches!(event, MarketEvent::FillSummary { .. }));
+                }
+                6 => {
+                    if let MarketEvent::ExpiredOrder {
+                        maker_id,
+                        order_sequence_number,
+                        price_in_ticks,
+                        base_lots_removed,
+                    } = event
+                    {
+                        assert_eq!(maker_id, &maker);
+                        assert_eq!(
+                            Side::from_order_sequence_number(*order_sequence_number),
+                            Side::Bid
+                        );
+                        assert_eq!(*price_in_ticks, Ticks::new(1000));
+                        assert_eq!(*base_lots_removed, BaseLots::new(80));
+                    } else {
+                        panic!("Invalid event")
+                    }
+                }
+                _ => {
+                    panic!("Invalid event")
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_limit_order_crossing() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut event_recorder = VecDeque::new();
+    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    let trader_1 = rng.gen::<u128>();
+    let trader_2 = rng.gen::<u128>();
+
+    // Place 2 bids for 100 and 95, then fill them both
+    assert!(market
+        .place_order(
+            &trader_1,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &trader_1,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    assert!(market
+        .place_order(
+            &trader_2,
+            OrderPacket::Limit {
+                side: Side::Ask,
+                price_in_ticks: Ticks::new(95),
+                num_base_lots: BaseLots::new(20),
+                match_limit: Some(1), // Note: the behavior of this the parameter is being tested
+                self_trade_behavior: SelfTradeBehavior::Abort,
+                client_order_id: rng.gen::<u128>(),
+                use_only_deposited_funds: false,
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+                fail_silently_on_insufficient_funds: false,
+                reduce_only: false,
+                place_partial: false,
+                valid_for_slots: None,
+            },
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_ok());
+
+    let ladder = market.get_ladder(5);
+    assert!(ladder.asks.is_empty());
+}
+
+#[test]
+fn test_min_base_lots_per_order() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    market.set_min_base_lots_per_order(BaseLots::new(5));
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Resting orders below the minimum are rejected
+    assert!(market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 101, 4),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+    assert!(market.get_book(Side::Ask).is_empty());
+
+    // Orders at the minimum are accepted
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 101, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+
+    // A limit order that fully fills does not rest and is accepted
+    let (order_id, _) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Bid, 101, 2),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(order_id.is_none());
+
+    // IOC orders are exempt from the minimum
+    let (_, matching_engine_response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                101,
+                1,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(matching_engine_response.num_base_lots_out, BaseLots::new(1));
+
+    // Existing orders below the minimum are left on the book
+    assert_eq!(
+        market
+            .get_book(Side::Ask)
+            .iter()
+            .map(|(_, o)| o.num_base_lots)
+            .collect::<Vec<_>>(),
+        vec![BaseLots::new(2)]
+    );
+
+    // A limit order whose remainder after matching is below the minimum is rejected
+    assert!(market
+        .place_order(
+            &taker,
+            OrderPacket::new_limit_order_default(Side::Bid, 101, 6),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .is_err());
+}
+
+#[test]
+fn test_get_orders_for_trader() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let trader = rng.gen::<u128>();
+    let other_trader = rng.gen::<u128>();
+    assert!(market.get_orders_for_trader(&trader).is_empty());
+
+    let mut order_ids = vec![];
+    for (maker, side, price) in [
+        (trader, Side::Ask, 103),
+        (trader, Side::Bid, 97),
+        (other_trader, Side::Ask, 102),
+        (trader, Side::Bid, 99),
+        (other_trader, Side::Bid, 98),
+        (trader, Side::Ask, 103),
+    ] {
+        let (order_id, _) = market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(side, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        if maker == trader {
+            order_ids.push(order_id.unwrap());
+        }
+    }
+
+    let orders = market.get_orders_for_trader(&trader);
+    assert_eq!(
+        orders.iter().map(|(o_id, _)| *o_id).collect::<Vec<_>>(),
+        vec![order_ids[1], order_ids[2], order_ids[0], order_ids[3]]
+    );
+    let trader_index = market.get_trader_index(&trader).unwrap() as u64;
+    assert!(orders
+        .iter()
+        .all(|(_, order)| order.trader_index == trader_index
+            && order.num_base_lots == BaseLots::new(10)));
+    assert_eq!(market.get_orders_for_trader(&other_trader).len(), 2);
+}
+
+#[test]
+fn test_get_typed_ladder_excluding_trader() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let trader = rng.gen::<u128>();
+    let other_trader = rng.gen::<u128>();
+    for (maker, side, price, size) in [
+        (trader, Side::Bid, 99, 10),
+        (other_trader, Side::Bid, 99, 5),
+        (other_trader, Side::Bid, 98, 7),
+        (trader, Side::Ask, 101, 10),
+        (trader, Side::Ask, 102, 3),
+        (other_trader, Side::Ask, 103, 4),
+    ] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(side, price, size),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+
+    let ladder = market.get_typed_ladder_excluding_trader(2, &trader);
+    let levels = |orders: &[TypedLadderOrder]| {
+        orders
+            .iter()
+            .map(|o| (o.price_in_ticks.as_u64(), o.size_in_base_lots.as_u64()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(levels(&ladder.bids), vec![(99, 5), (98, 7)]);
+    assert_eq!(levels(&ladder.asks), vec![(103, 4)]);
+
+    // A trader that is not registered does not filter out any orders
+    let ladder = market.get_typed_ladder_excluding_trader(5, &rng.gen::<u128>());
+    let full_ladder = market.get_typed_ladder(5);
+    assert_eq!(levels(&ladder.bids), levels(&full_ladder.bids));
+    assert_eq!(levels(&ladder.asks), levels(&full_ladder.asks));
+    assert_eq!(levels(&ladder.bids), vec![(99, 15), (98, 7)]);
+}
+
+#[test]
+fn test_prune_expired_orders() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    for (price, last_valid_slot, last_valid_unix_timestamp_in_seconds) in [
+        (101, Some(5), None),
+        (102, None, None),
+        (103, None, Some(50)),
+        (104, Some(5), None),
+    ] {
+        let order_packet = OrderPacket::PostOnly {
+            side: Side::Ask,
+            price_in_ticks: Ticks::new(price),
+            num_base_lots: BaseLots::new(10),
+            client_order_id: rng.gen::<u128>(),
+            use_only_deposited_funds: false,
+            reject_post_only: true,
+            last_valid_slot,
+            last_valid_unix_timestamp_in_seconds,
+            fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
+        };
+        market
+            .place_order(
+                &maker,
+                order_packet,
                 &mut record_event_fn,
                 &mut get_clock_fn,
+            )
+            .unwrap();
+    }
+    let base_lots_locked = market.get_trader_state(&maker).unwrap().base_lots_locked;
+
+    // Nothing is removed before the orders expire
+    assert_eq!(
+        market.prune_expired_orders(Side::Ask, 10, None, &mut record_event_fn, &mut get_clock_fn),
+        Some(0)
+    );
+    assert_eq!(market.get_book(Side::Ask).len(), 4);
+
+    // Only the first `num_orders_to_scan` orders are considered
+    let mut events = vec![];
+    let mut record_event_fn = |e: MarketEvent<TraderId>| events.push(e);
+    assert_eq!(
+        market.prune_expired_orders(Side::Ask, 3, None, &mut record_event_fn, &mut || (6, 51)),
+        Some(2)
+    );
+    assert_eq!(
+        events
+            .iter()
+            .filter(|e| matches!(e, MarketEvent::ExpiredOrder { .. }))
+            .count(),
+        2
+    );
+    assert_eq!(
+        market
+            .get_book(Side::Ask)
+            .iter()
+            .map(|(o_id, _)| o_id.price_in_ticks)
+            .collect::<Vec<_>>(),
+        vec![Ticks::new(102), Ticks::new(104)]
+    );
+
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    assert_eq!(
+        market.prune_expired_orders(Side::Ask, 3, None, &mut record_event_fn, &mut || (6, 51)),
+        Some(1)
+    );
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+
+    // The maker's funds are unlocked but remain deposited
+    let trader_state = market.get_trader_state(&maker).unwrap();
+    assert_eq!(
+        trader_state.base_lots_locked,
+        base_lots_locked - BaseLots::new(30)
+    );
+    assert_eq!(trader_state.base_lots_free, BaseLots::new(30));
+}
+
+#[test]
+fn test_copy_from_smaller_market() {
+    type SmallDex = FIFOMarket<TraderId, 512, 512, 128>;
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut data = vec![0; std::mem::size_of::<SmallDex>()];
+    let small_market = SmallDex::load_mut_bytes(&mut data).unwrap();
+    small_market.initialize_with_params(
+        QuoteLotsPerBaseUnitPerTick::new(10000),
+        BaseLotsPerBaseUnit::new(100),
+    );
+    small_market.set_fee(5);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    // Register and remove a trader so that the trader indices of the source market have a gap
+    let removed_trader = rng.gen::<u128>();
+    small_market
+        .get_or_register_trader(&removed_trader)
+        .unwrap();
+    let makers = (0..4).map(|_| rng.gen::<u128>()).collect::<Vec<_>>();
+    for (i, maker) in makers.iter().enumerate() {
+        for (side, price) in [(Side::Bid, 99 - i as u64), (Side::Ask, 101 + i as u64)] {
+            small_market
+                .place_order(
+                    maker,
+                    OrderPacket::new_post_only_default(side, price, 10 + i as u64),
+                    &mut record_event_fn,
+                    &mut get_clock_fn,
+                )
+                .unwrap();
+        }
+    }
+    small_market
+        .try_remove_trader_state(&removed_trader)
+        .unwrap();
+
+    let mut market = Box::new(setup_market_with_params(1, 1, 0));
+    market.copy_from(&*small_market).unwrap();
+
+    assert_eq!(
+        market.get_sequence_number(),
+        small_market.get_sequence_number()
+    );
+    assert_eq!(market.get_taker_fee_bps(), 5);
+    assert_eq!(market.get_tick_size(), small_market.get_tick_size());
+    assert_eq!(
+        market.get_base_lots_per_base_unit(),
+        small_market.get_base_lots_per_base_unit()
+    );
+    assert_eq!(
+        market.get_typed_ladder(10),
+        small_market.get_typed_ladder(10)
+    );
+    assert_eq!(market.get_registered_traders().len(), makers.len());
+    for maker in makers.iter() {
+        assert_eq!(
+            market.get_trader_state(maker),
+            small_market.get_trader_state(maker)
+        );
+        let orders = market.get_orders_for_trader(maker);
+        let source_orders = small_market.get_orders_for_trader(maker);
+        assert_eq!(orders.len(), 2);
+        for ((order_id, order), (source_order_id, source_order)) in
+            orders.iter().zip(source_orders.iter())
+        {
+            assert_eq!(order_id, source_order_id);
+            assert_eq!(order.num_base_lots, source_order.num_base_lots);
+            assert_eq!(
+                market.get_trader_id_from_index(order.trader_index as u32),
+                *maker
             );
         }
-        let direction = match side {
-            Side::Bid => -1,
-            Side::Ask => 1,
-        };
-        let stink_price = Ticks::new((price.as_u64() as i64 + direction * 500) as u64);
-        market.place_order(
-            &stink_order,
-            OrderPacket::new_post_only_default(side, stink_price.as_u64(), 99),
+    }
+}
+
+#[test]
+fn test_order_complete_event() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+    let mut order_ids = vec![];
+    for price in [101, 102] {
+        let (order_id, _) = market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        order_ids.push(order_id.unwrap());
+    }
+
+    // The first order is fully filled and the second order is partially filled
+    let mut events = vec![];
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                102,
+                15,
+                SelfTradeBehavior::Abort,
+                None,
+                rng.gen::<u128>(),
+                false,
+            ),
+            &mut |e| events.push(e),
+            &mut get_clock_fn,
+        )
+        .unwrap();
+
+    let completed_orders = events
+        .iter()
+        .filter_map(|e| match e {
+            MarketEvent::OrderComplete {
+                maker_id,
+                order_sequence_number,
+            } => Some((*maker_id, *order_sequence_number)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        completed_orders,
+        vec![(maker, order_ids[0].order_sequence_number)]
+    );
+    // The event immediately follows the fill that removed the order
+    let complete_index = events
+        .iter()
+        .position(|e| matches!(e, MarketEvent::OrderComplete { .. }))
+        .unwrap();
+    assert!(matches!(
+        events[complete_index - 1],
+        MarketEvent::Fill {
+            order_sequence_number,
+            base_lots_remaining,
+            ..
+        } if order_sequence_number == order_ids[0].order_sequence_number
+            && base_lots_remaining == BaseLots::ZERO
+    ));
+    assert_eq!(market.get_book(Side::Ask).len(), 1);
+}
+
+#[test]
+fn test_pro_rata_matching() {
+    type ProRataDex = ProRataMarket<TraderId, 512, 512, 128>;
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut data = vec![0; std::mem::size_of::<ProRataDex>()];
+    let market = ProRataDex::load_mut_bytes(&mut data).unwrap();
+    market.initialize_with_params(
+        QuoteLotsPerBaseUnitPerTick::new(10000),
+        BaseLotsPerBaseUnit::new(100),
+    );
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+
+    let makers = (0..3).map(|_| rng.gen::<u128>()).collect::<Vec<_>>();
+    let taker = rng.gen::<u128>();
+    let mut order_ids = vec![];
+    for (maker, price, size) in [
+        (makers[0], 100, 30),
+        (makers[1], 100, 10),
+        (makers[2], 101, 10),
+    ] {
+        let (order_id, _) = market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price, size),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        order_ids.push(order_id.unwrap());
+    }
+    let remaining_base_lots = |market: &ProRataDex| {
+        order_ids
+            .iter()
+            .map(|order_id| {
+                market
+                    .get_book(Side::Ask)
+                    .get(order_id)
+                    .map_or(0, |order| order.num_base_lots.as_u64())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The fill is split across the level in proportion to the size of each order
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                20,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(20));
+    assert_eq!(remaining_base_lots(market), vec![15, 5, 10]);
+
+    // Base lots that cannot be split evenly are assigned to the oldest order
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                3,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(3));
+    assert_eq!(remaining_base_lots(market), vec![12, 5, 10]);
+
+    // Levels that can be filled entirely are matched in full before moving to the next price
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                101,
+                22,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
             &mut record_event_fn,
             &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(22));
+    assert_eq!(remaining_base_lots(market), vec![0, 0, 5]);
+    // Each base lot at a price of 100 ticks is worth 10000 quote lots
+    for (maker, quote_lots) in makers.iter().zip([300_000, 100_000, 50_500]) {
+        assert_eq!(
+            market.get_trader_state(maker).unwrap().quote_lots_free,
+            QuoteLots::new(quote_lots)
         );
-        // Order must be more aggressive than the least aggressive order in a full book
-        assert!(market
-            .place_order(
-                &stink_order,
-                OrderPacket::new_post_only_default(side, stink_price.as_u64(), 99),
+    }
+}
+
+#[test]
+fn test_max_order_lifetime_slots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    market.set_max_order_lifetime_slots(100);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let mut clock_fn = || (1000, 0);
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    let post_only_with_expiry = |last_valid_slot: Option<u64>| OrderPacket::PostOnly {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(100),
+        num_base_lots: BaseLots::new(10),
+        client_order_id: 0,
+        use_only_deposited_funds: false,
+        reject_post_only: true,
+        last_valid_slot,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+
+    // Orders that expire after the maximum lifetime are rejected
+    assert_eq!(
+        market.place_order(
+            &maker,
+            post_only_with_expiry(Some(1101)),
+            &mut record_event_fn,
+            &mut clock_fn,
+        ),
+        Err(PhoenixMatchError::OrderLifetimeTooLong)
+    );
+
+    // Orders within the maximum lifetime keep their expiry
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            post_only_with_expiry(Some(1050)),
+            &mut record_event_fn,
+            &mut clock_fn,
+        )
+        .unwrap();
+    let order = *market.get_book(Side::Ask).get(&order_id.unwrap()).unwrap();
+    assert_eq!(order.last_valid_slot, 1050);
+
+    // Orders without an expiry are clamped to the maximum lifetime
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            post_only_with_expiry(None),
+            &mut record_event_fn,
+            &mut clock_fn,
+        )
+        .unwrap();
+    let order = *market.get_book(Side::Ask).get(&order_id.unwrap()).unwrap();
+    assert_eq!(order.last_valid_slot, 1100);
+
+    // Take-only orders never rest on the book, so they are unaffected
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                5,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(5));
+
+    // Removing the limit allows orders to rest indefinitely
+    market.set_max_order_lifetime_slots(0);
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            post_only_with_expiry(None),
+            &mut record_event_fn,
+            &mut clock_fn,
+        )
+        .unwrap();
+    let order = *market.get_book(Side::Ask).get(&order_id.unwrap()).unwrap();
+    assert_eq!(order.last_valid_slot, 0);
+}
+
+#[test]
+fn test_valid_for_slots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let mut clock_fn = || (1000, 0);
+    let maker = rng.gen::<u128>();
+
+    let limit_with_expiry =
+        |last_valid_slot: Option<u64>, valid_for_slots: Option<u32>| OrderPacket::Limit {
+            side: Side::Bid,
+            price_in_ticks: Ticks::new(100),
+            num_base_lots: BaseLots::new(10),
+            self_trade_behavior: SelfTradeBehavior::Abort,
+            match_limit: None,
+            client_order_id: 0,
+            use_only_deposited_funds: false,
+            last_valid_slot,
+            last_valid_unix_timestamp_in_seconds: None,
+            fail_silently_on_insufficient_funds: false,
+            reduce_only: false,
+            place_partial: false,
+            valid_for_slots,
+        };
+
+    // The relative expiry is converted to an absolute slot using the current slot
+    let (order_id, _) = market
+        .place_order(
+            &maker,
+            limit_with_expiry(None, Some(25)),
+            &mut record_event_fn,
+            &mut clock_fn,
+        )
+        .unwrap();
+    let order_id = order_id.unwrap();
+    let order = *market.get_book(Side::Bid).get(&order_id).unwrap();
+    assert_eq!(order.last_valid_slot, 1025);
+
+    // The order expires once the slot passes
+    let mut later_clock_fn = || (1026, 0);
+    let (_, response) = market
+        .place_order(
+            &rng.gen::<u128>(),
+            OrderPacket::new_ioc_by_lots(
+                Side::Ask,
+                100,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut later_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::ZERO);
+    assert!(market.get_book(Side::Bid).get(&order_id).is_none());
+
+    // Setting both an absolute and a relative expiry is rejected
+    assert_eq!(
+        market.place_order(
+            &maker,
+            limit_with_expiry(Some(1050), Some(25)),
+            &mut record_event_fn,
+            &mut clock_fn,
+        ),
+        Err(PhoenixMatchError::InvalidOrderExpiration)
+    );
+
+    // The converted expiry is subject to the market's maximum order lifetime
+    market.set_max_order_lifetime_slots(100);
+    assert_eq!(
+        market.place_order(
+            &maker,
+            limit_with_expiry(None, Some(101)),
+            &mut record_event_fn,
+            &mut clock_fn,
+        ),
+        Err(PhoenixMatchError::OrderLifetimeTooLong)
+    );
+}
+
+#[test]
+fn test_max_match_limit() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    for price_in_ticks in 100..105 {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, price_in_ticks, 1),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_none());
-        let mut event_recorder = VecDeque::new();
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
-        assert!(market
+            .unwrap();
+    }
+
+    let mut buy = |market: &mut Dex, match_limit: Option<u64>| {
+        let (_, response) = market
             .place_order(
-                &evicter,
-                OrderPacket::new_post_only_default(
-                    side,
-                    (price.as_u64() as i64 + direction) as u64,
-                    99
+                &taker,
+                OrderPacket::new_ioc(
+                    Side::Bid,
+                    None,
+                    5,
+                    0,
+                    0,
+                    0,
+                    SelfTradeBehavior::Abort,
+                    match_limit,
+                    0,
+                    false,
+                    None,
+                    None,
                 ),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
-            .is_some());
+            .unwrap();
+        response.num_base_lots_out
+    };
 
-        event_recorder.pop_back();
-        let evict_event = *event_recorder.back().unwrap();
-        if let MarketEvent::Evict {
-            order_sequence_number: order_id,
-            price_in_ticks,
-            maker_id,
-            base_lots_evicted: base_lots_removed,
-        } = evict_event
-        {
-            assert!(Side::from_order_sequence_number(order_id) == side);
-            assert_eq!(price_in_ticks, stink_price);
-            assert_eq!(maker_id, stink_order);
-            assert_eq!(base_lots_removed, BaseLots::new(99));
-            let trader_state = market.traders.get(&stink_order).unwrap();
-            if side == Side::Ask {
-                assert_eq!(trader_state.base_lots_free, BaseLots::new(99));
-            } else {
-                assert_eq!(
-                    trader_state.quote_lots_free,
-                    stink_price * market.tick_size_in_quote_lots_per_base_unit * BaseLots::new(99)
-                        / market.base_lots_per_base_unit
-                );
-            }
-        } else {
-            panic!("Expected evict event");
-        }
-    }
+    // Orders without a match limit use the market's limit
+    market.set_max_match_limit(2);
+    assert_eq!(buy(&mut market, None), BaseLots::new(2));
+
+    // The market's limit caps larger match limits, but smaller ones are respected
+    assert_eq!(buy(&mut market, Some(1)), BaseLots::new(1));
+    market.set_max_match_limit(1);
+    assert_eq!(buy(&mut market, Some(10)), BaseLots::new(1));
+
+    // Zero removes the limit
+    market.set_max_match_limit(0);
+    assert_eq!(buy(&mut market, None), BaseLots::new(1));
+    assert_eq!(market.get_book(Side::Ask).len(), 0);
 }
 
 #[test]
-fn test_reduce_order() {
+fn test_max_orders_per_side_per_trader() {
     let mut rng = StdRng::seed_from_u64(2);
     let mut market = setup_market();
+    market.set_max_orders_per_side_per_trader(2);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
     let maker = rng.gen::<u128>();
-    let mut event_recorder = VecDeque::new();
-
-    let client_ids = vec![rng.gen::<u128>()];
-    let order_packet = OrderPacket::new_post_only_default_with_client_order_id(
-        Side::Bid,
-        1000,
-        100,
-        client_ids[0],
-    );
+    let taker = rng.gen::<u128>();
 
-    {
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let place = |market: &mut Dex, side: Side, price_in_ticks: u64| {
         market
             .place_order(
                 &maker,
-                order_packet,
-                &mut record_event_fn,
+                OrderPacket::new_post_only_default(side, price_in_ticks, 1),
+                &mut |_e: MarketEvent<TraderId>| {},
                 &mut get_clock_fn,
             )
-            .unwrap();
-    }
-
-    let event = event_recorder.pop_back().unwrap();
-    let order_id = if let MarketEvent::<u128>::Place {
-        order_sequence_number,
-        price_in_ticks,
-        base_lots_placed,
-        client_order_id,
-    } = event
-    {
-        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
-        assert_eq!(price_in_ticks, Ticks::new(1000));
-        assert_eq!(base_lots_placed, BaseLots::new(100));
-        assert_eq!(client_order_id, client_ids[0]);
-        FIFOOrderId::new(price_in_ticks, order_sequence_number)
-    } else {
-        panic!("Expected place event");
+            .map(|(order_id, _)| order_id.unwrap())
     };
-
-    {
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let num_open_orders = |market: &Dex, side: Side| {
         market
-            .reduce_order(
-                &maker,
-                &order_id,
+            .get_trader_state(&maker)
+            .unwrap()
+            .num_open_orders(side)
+    };
+
+    let first_ask = place(&mut market, Side::Ask, 100).unwrap();
+    place(&mut market, Side::Ask, 101).unwrap();
+    assert_eq!(num_open_orders(&market, Side::Ask), 2);
+    assert_eq!(
+        place(&mut market, Side::Ask, 102),
+        Err(PhoenixMatchError::TooManyOpenOrders)
+    );
+
+    // The limit applies to each side separately
+    place(&mut market, Side::Bid, 90).unwrap();
+    place(&mut market, Side::Bid, 89).unwrap();
+    assert_eq!(num_open_orders(&market, Side::Bid), 2);
+
+    // Filled orders no longer count towards the limit
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
                 Side::Bid,
-                Some(BaseLots::new(10)),
-                true,
+                100,
+                1,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert!(market.get_order(&first_ask).is_none());
+    assert_eq!(num_open_orders(&market, Side::Ask), 1);
+    let replacement_ask = place(&mut market, Side::Ask, 102).unwrap();
+
+    // Neither do cancelled orders
+    market
+        .reduce_order(
+            &maker,
+            &replacement_ask,
+            Side::Ask,
+            None,
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    assert_eq!(num_open_orders(&market, Side::Ask), 1);
+    place(&mut market, Side::Ask, 103).unwrap();
+
+    // Zero removes the limit
+    market.set_max_orders_per_side_per_trader(0);
+    place(&mut market, Side::Ask, 104).unwrap();
+    assert_eq!(num_open_orders(&market, Side::Ask), 3);
+}
+
+#[test]
+fn test_book_snapshot() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let other_maker = rng.gen::<u128>();
+
+    let mut order_ids = vec![];
+    for (trader, side, price) in [
+        (maker, Side::Bid, 98),
+        (other_maker, Side::Bid, 99),
+        (maker, Side::Ask, 101),
+    ] {
+        let (order_id, _) = market
+            .place_order(
+                &trader,
+                OrderPacket::new_post_only_default(side, price, 10),
                 &mut record_event_fn,
+                &mut get_clock_fn,
             )
             .unwrap();
+        order_ids.push(order_id.unwrap());
     }
+    let (order_id, _) = market
+        .place_order(
+            &other_maker,
+            OrderPacket::PostOnly {
+                side: Side::Ask,
+                price_in_ticks: Ticks::new(102),
+                num_base_lots: BaseLots::new(5),
+                client_order_id: 0,
+                use_only_deposited_funds: false,
+                reject_post_only: true,
+                last_valid_slot: Some(500),
+                last_valid_unix_timestamp_in_seconds: None,
+                fail_silently_on_insufficient_funds: false,
+                max_tick_adjustment: None,
+                require_top_of_book: false,
+                valid_for_slots: None,
+            },
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    order_ids.push(order_id.unwrap());
+
+    let snapshot_bytes = MarketWrapper::new(
+        &market as &dyn Market<TraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    )
+    .serialize_book_snapshot();
+    let snapshot = deserialize_book_snapshot::<TraderId>(&snapshot_bytes).unwrap();
+    assert_eq!(snapshot.sequence_number, market.get_sequence_number());
+
+    let expected_order =
+        |order_id: &FIFOOrderId, maker_id, num_base_lots, last_valid_slot| BookSnapshotOrder {
+            price_in_ticks: order_id.price_in_ticks.as_u64(),
+            order_sequence_number: order_id.order_sequence_number,
+            num_base_lots,
+            maker_id,
+            last_valid_slot,
+            last_valid_unix_timestamp_in_seconds: 0,
+        };
+    // Each side is ordered from the top of the book
+    assert_eq!(
+        snapshot.bids,
+        vec![
+            expected_order(&order_ids[1], other_maker, 10, 0),
+            expected_order(&order_ids[0], maker, 10, 0),
+        ]
+    );
+    assert_eq!(
+        snapshot.asks,
+        vec![
+            expected_order(&order_ids[2], maker, 10, 0),
+            expected_order(&order_ids[3], other_maker, 5, 500),
+        ]
+    );
+}
 
-    let event = event_recorder.pop_back().unwrap();
-    if let MarketEvent::<u128>::Reduce {
-        order_sequence_number,
-        price_in_ticks,
-        base_lots_removed,
-        base_lots_remaining,
-    } = event
-    {
-        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
-        assert_eq!(price_in_ticks, Ticks::new(1000));
-        assert_eq!(base_lots_removed, BaseLots::new(10));
-        assert_eq!(base_lots_remaining, BaseLots::new(90));
-    } else {
-        panic!("Expected reduce event");
-    }
-    assert!(market.bids.get(&order_id).is_some());
+#[test]
+fn test_fee_rounding_mode() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-    let random_maker = rng.gen::<u128>();
-    {
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    // Buys 1 base lot at the given price and returns the quote lots traded and the fee charged
+    let trade_with_fee = |taker_fee_bps: u64,
+                          fee_rounding_mode: FeeRoundingMode,
+                          price_in_ticks: u64|
+     -> (QuoteLots, QuoteLots) {
+        let mut market = setup_market_with_params(10000, 100, taker_fee_bps);
+        market.set_fee_rounding_mode(fee_rounding_mode);
+        assert_eq!(market.get_fee_rounding_mode(), fee_rounding_mode);
+        let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
         market
             .place_order(
-                &random_maker,
-                order_packet,
+                &maker,
+                OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, 10),
                 &mut record_event_fn,
                 &mut get_clock_fn,
             )
             .unwrap();
-        assert!(
-            market
-                .reduce_order(
-                    &random_maker,
-                    &order_id,
+        let (_, response) = market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
                     Side::Bid,
-                    Some(BaseLots::new(10)),
-                    true,
-                    &mut record_event_fn,
-                )
-                .is_none(),
-            "Trader ID must match order"
-        );
+                    price_in_ticks,
+                    1,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        assert_eq!(response.num_base_lots_out, BaseLots::new(1));
+        let fee = market.get_uncollected_fee_amount();
+        (response.num_quote_lots_in - fee, fee)
+    };
 
-        assert_eq!(
-            market
-                .reduce_order(
-                    &maker,
-                    &FIFOOrderId::new_from_untyped(rng.gen::<u64>(), rng.gen::<u64>()),
-                    Side::Bid,
-                    Some(BaseLots::new(10)),
-                    true,
-                    &mut record_event_fn,
-                )
-                .unwrap(),
-            MatchingEngineResponse::default(),
-            "Order ID not in book"
-        );
+    let modes = [
+        FeeRoundingMode::Up,
+        FeeRoundingMode::Down,
+        FeeRoundingMode::Nearest,
+    ];
+
+    // A market without fees never charges anything, regardless of rounding
+    for mode in modes {
+        for price_in_ticks in [1, 99, 117, 1003] {
+            assert_eq!(trade_with_fee(0, mode, price_in_ticks).1, QuoteLots::ZERO);
+        }
     }
-    // If we pass in more size than is in the order, it should reduce the order to zero and should be removed from the book
-    {
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+
+    // 11700 quote lots at 3 bps is an exact fee of 3.51 quote lots
+    assert_eq!(
+        trade_with_fee(3, FeeRoundingMode::Up, 117).1,
+        QuoteLots::new(4)
+    );
+    assert_eq!(
+        trade_with_fee(3, FeeRoundingMode::Down, 117).1,
+        QuoteLots::new(3)
+    );
+    assert_eq!(
+        trade_with_fee(3, FeeRoundingMode::Nearest, 117).1,
+        QuoteLots::new(4)
+    );
+
+    // 10100 quote lots at 3 bps is an exact fee of 3.03 quote lots
+    assert_eq!(
+        trade_with_fee(3, FeeRoundingMode::Nearest, 101).1,
+        QuoteLots::new(3)
+    );
+
+    // Rounding down never charges more than the exact fee, and rounding up never charges less
+    for taker_fee_bps in 1..10 {
+        for price_in_ticks in (1..2000).step_by(37) {
+            let (quote_lots, down_fee) =
+                trade_with_fee(taker_fee_bps, FeeRoundingMode::Down, price_in_ticks);
+            let (_, up_fee) = trade_with_fee(taker_fee_bps, FeeRoundingMode::Up, price_in_ticks);
+            let (_, nearest_fee) =
+                trade_with_fee(taker_fee_bps, FeeRoundingMode::Nearest, price_in_ticks);
+            let exact_fee_times_10000 = quote_lots.as_u64() * taker_fee_bps;
+            assert!(down_fee.as_u64() * 10000 <= exact_fee_times_10000);
+            assert!(up_fee.as_u64() * 10000 >= exact_fee_times_10000);
+            assert!(down_fee <= nearest_fee && nearest_fee <= up_fee);
+        }
+    }
+}
+
+#[test]
+fn test_order_book_hash() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    let mut hashes = vec![market.get_order_book_hash()];
+    // The hash is stable while the book is unchanged
+    assert_eq!(market.get_order_book_hash(), hashes[0]);
+
+    let (bid_id, _) = market
+        .place_order(
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    hashes.push(market.get_order_book_hash());
+
+    // Placing an order behind the best ask still changes the sequence number
+    for price_in_ticks in [105, 110] {
         market
-            .reduce_order(
+            .place_order(
                 &maker,
-                &order_id,
-                Side::Bid,
-                Some(BaseLots::new(100)),
-                true,
+                OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, 10),
                 &mut record_event_fn,
+                &mut get_clock_fn,
             )
             .unwrap();
-    }
-    let event = event_recorder.pop_back().unwrap();
-    if let MarketEvent::<u128>::Reduce {
-        order_sequence_number,
-        price_in_ticks,
-        base_lots_removed,
-        base_lots_remaining,
-    } = event
-    {
-        assert!(Side::from_order_sequence_number(order_sequence_number) == Side::Bid);
-        assert_eq!(price_in_ticks, Ticks::new(1000));
-        assert_eq!(base_lots_removed, BaseLots::new(90));
-        assert_eq!(base_lots_remaining, BaseLots::new(0));
-    } else {
-        panic!("Expected reduce event");
+        hashes.push(market.get_order_book_hash());
     }
 
-    assert!(market.bids.get(&order_id).is_none());
+    // A partial fill changes the size at the top of the book
+    market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                105,
+                4,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    hashes.push(market.get_order_book_hash());
+
+    // Cancelling the best bid empties that side of the book
+    market
+        .cancel_order(
+            &maker,
+            &bid_id.unwrap(),
+            Side::Bid,
+            false,
+            None,
+            &mut record_event_fn,
+        )
+        .unwrap();
+    hashes.push(market.get_order_book_hash());
+
+    let num_hashes = hashes.len();
+    hashes.sort_unstable();
+    hashes.dedup();
+    assert_eq!(hashes.len(), num_hashes);
 }
 
 #[test]
-fn test_tif() {
+fn test_peek_match() {
     let mut rng = StdRng::seed_from_u64(2);
     let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
     let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-    pub struct MockClock {
-        slot: u64,
-        timestamp: u64,
+    for (price_in_ticks, num_base_lots) in [(101, 10), (102, 10), (103, 10), (105, 10)] {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, num_base_lots),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
     }
-
-    let now = SystemTime::now();
-    let exp = now
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
-        .checked_add(1000)
+    // This order expires before the match, so it is removed instead of filled
+    let mut expiring_order = OrderPacket::new_limit_order_default(Side::Ask, 100, 10);
+    expiring_order.set_last_valid_slot(Some(5));
+    market
+        .place_order(
+            &maker,
+            expiring_order,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
         .unwrap();
+    market.get_or_register_trader(&taker).unwrap();
+    let ladder = market.get_typed_ladder(10);
+    let sequence_number = market.get_sequence_number();
 
-    let order_packet_unix_timestamp_tif = OrderPacket::PostOnly {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(1000),
-        num_base_lots: BaseLots::new(100),
-        client_order_id: rng.gen::<u128>(),
-        use_only_deposited_funds: false,
-        reject_post_only: true,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: Some(exp),
-        fail_silently_on_insufficient_funds: false,
-    };
-
-    let order_packet_slot_tif = OrderPacket::PostOnly {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(1000),
-        num_base_lots: BaseLots::new(100),
-        client_order_id: rng.gen::<u128>(),
-        use_only_deposited_funds: false,
-        reject_post_only: true,
-        last_valid_slot: Some(2000),
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
-    };
-
-    for order_packet in [order_packet_unix_timestamp_tif, order_packet_slot_tif] {
-        let mut mock_clock = MockClock {
-            slot: 1000,
-            timestamp: now.duration_since(UNIX_EPOCH).unwrap().as_secs(),
-        };
-        let mut event_recorder = VecDeque::new();
-        let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let inflight_order = InflightOrder::new(
+        Side::Bid,
+        SelfTradeBehavior::Abort,
+        Ticks::new(104),
+        u64::MAX,
+        BaseLots::new(25),
+        AdjustedQuoteLots::MAX,
+        None,
+        None,
+    );
+    let taker_index = market.get_trader_index(&taker).unwrap();
+    let mut taker_order = inflight_order;
+    let (matched_base_lots, matched_adjusted_quote_lots) =
+        market.peek_match(&mut taker_order, taker_index, None, (10, 0));
+    assert_eq!(matched_base_lots, BaseLots::new(25));
+
+    // Peeking does not modify the market
+    assert_eq!(market.get_typed_ladder(10), ladder);
+    assert_eq!(market.get_sequence_number(), sequence_number);
+
+    // Matching against its own orders aborts the order, so nothing would be matched
+    let maker_index = market.get_trader_index(&maker).unwrap();
+    let mut maker_order = inflight_order;
+    assert_eq!(
+        market.peek_match(&mut maker_order, maker_index, None, (10, 0)),
+        (BaseLots::ZERO, AdjustedQuoteLots::ZERO)
+    );
 
-        {
-            let expired_mock_clock = MockClock {
-                slot: 3000,
-                timestamp: now.duration_since(UNIX_EPOCH).unwrap().as_secs() + 2000,
-            };
-            let mut mock_clock_fn = || (expired_mock_clock.slot, expired_mock_clock.timestamp);
-            assert_eq!(
-                market
-                    .place_order(
-                        &maker,
-                        order_packet,
-                        &mut record_event_fn,
-                        &mut mock_clock_fn,
-                    )
-                    .unwrap()
-                    .1,
-                MatchingEngineResponse::default()
-            );
-        }
+    // The peeked fills match the fills of the order when it is placed
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                104,
+                25,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            &mut record_event_fn,
+            &mut || (10, 0),
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, matched_base_lots);
+    assert_eq!(
+        response.num_quote_lots_in,
+        matched_adjusted_quote_lots / market.get_base_lots_per_base_unit()
+    );
+}
 
-        {
-            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
-            market
-                .place_order(
-                    &maker,
-                    order_packet,
-                    &mut record_event_fn,
-                    &mut mock_clock_fn,
-                )
-                .unwrap();
-        }
+#[test]
+fn test_peek_match_iceberg() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = Box::new(setup_market());
+    let mut reserves = Box::new(RedBlackTree::<FIFOOrderId, IcebergReserve, 128>::default());
+    reserves.initialize();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let iceberg_maker = rng.gen::<u128>();
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-        let taker = rng.gen::<u128>();
+    market
+        .place_order_with_iceberg_reserves(
+            &iceberg_maker,
+            OrderPacket::new_iceberg_order(
+                Side::Ask,
+                100,
+                25,
+                10,
+                SelfTradeBehavior::Abort,
+                0,
+                false,
+            ),
+            None,
+            &mut *reserves,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Ask, 100, 5),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    let ladder = market.get_typed_ladder(10);
 
-        if order_packet.get_last_valid_slot().is_some() {
-            mock_clock.slot += 500;
-        } else {
-            mock_clock.timestamp += 500;
-        }
-        let (_, matching_engine_response) = {
-            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
-            market
-                .place_order(
-                    &taker,
-                    OrderPacket::new_ioc_by_lots(
-                        Side::Ask,
-                        0,
-                        10,
-                        SelfTradeBehavior::Abort,
-                        None,
-                        rng.gen::<u128>(),
-                        false,
-                    ),
-                    &mut record_event_fn,
-                    &mut mock_clock_fn,
-                )
-                .unwrap()
-        };
+    // The iceberg order is replenished behind the other order at its price, so the peeked match
+    // fills 10 base lots of the iceberg order, 5 of the other order and 7 of the replenished order
+    let inflight_order = InflightOrder::new(
+        Side::Bid,
+        SelfTradeBehavior::Abort,
+        Ticks::new(100),
+        u64::MAX,
+        BaseLots::new(22),
+        AdjustedQuoteLots::MAX,
+        None,
+        None,
+    );
+    let mut peeked_order = inflight_order;
+    let (matched_base_lots, _) =
+        market.peek_match(&mut peeked_order, u32::MAX, Some(&*reserves), (0, 0));
+    assert_eq!(matched_base_lots, BaseLots::new(22));
+    // Without the reserves, only the visible size can be matched
+    let mut peeked_order = inflight_order;
+    let (matched_base_lots, _) = market.peek_match(&mut peeked_order, u32::MAX, None, (0, 0));
+    assert_eq!(matched_base_lots, BaseLots::new(15));
+    assert_eq!(market.get_typed_ladder(10), ladder);
+    assert_eq!(reserves.len(), 1);
+
+    let (_, response) = market
+        .place_order_with_iceberg_reserves(
+            &taker,
+            OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                100,
+                22,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            None,
+            &mut *reserves,
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(22));
+}
 
-        assert!(matching_engine_response.num_quote_lots_out > QuoteLots::ZERO);
+#[test]
+fn test_min_fee() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market_with_params(10000, 100, 5);
+    market.set_min_fee_quote_lots(QuoteLots::new(20));
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-        // Check that order are still not expired on the boundary
-        if order_packet.get_last_valid_slot().is_some() {
-            mock_clock.slot += 500;
-        } else {
-            mock_clock.timestamp += 500;
-        }
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Ask, 1, 100),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
 
-        let (_, matching_engine_response) = {
-            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
-            market
-                .place_order(
-                    &taker,
-                    OrderPacket::new_ioc_by_lots(
-                        Side::Ask,
-                        0,
-                        10,
-                        SelfTradeBehavior::Abort,
-                        None,
-                        rng.gen::<u128>(),
-                        false,
-                    ),
-                    &mut record_event_fn,
-                    &mut mock_clock_fn,
-                )
-                .unwrap()
-        };
+    let mut trade = |market: &mut Dex, side: Side, price_in_ticks: u64| {
+        let fees_before = market.get_uncollected_fee_amount();
+        let (_, response) = market
+            .place_order(
+                &taker,
+                OrderPacket::new_ioc_by_lots(
+                    side,
+                    price_in_ticks,
+                    1,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        (response, market.get_uncollected_fee_amount() - fees_before)
+    };
 
-        assert!(matching_engine_response.num_quote_lots_out > QuoteLots::ZERO);
+    // 1 base lot at 1 tick is worth 100 quote lots, so the 5 bps fee rounds up to 1 quote lot
+    // and the trade pays the minimum fee instead
+    let (response, fee) = trade(&mut market, Side::Bid, 1);
+    assert_eq!(response.num_base_lots_out, BaseLots::new(1));
+    assert_eq!(fee, QuoteLots::new(20));
+    assert_eq!(response.num_quote_lots_in, QuoteLots::new(100 + 20));
+
+    // An order that does not match pays nothing
+    let (response, fee) = trade(&mut market, Side::Ask, 1);
+    assert_eq!(response.num_base_lots_in, BaseLots::ZERO);
+    assert_eq!(response.num_quote_lots_out, QuoteLots::ZERO);
+    assert_eq!(fee, QuoteLots::ZERO);
+
+    // The minimum fee never exceeds the quote value of the trade
+    market.set_min_fee_quote_lots(QuoteLots::new(1000));
+    let (response, fee) = trade(&mut market, Side::Bid, 1);
+    assert_eq!(fee, QuoteLots::new(100));
+    assert_eq!(response.num_quote_lots_in, QuoteLots::new(200));
+
+    // Fees above the minimum are unaffected
+    market.set_min_fee_quote_lots(QuoteLots::ZERO);
+    let (_, fee) = trade(&mut market, Side::Bid, 1);
+    assert_eq!(fee, QuoteLots::new(1));
+}
 
-        if order_packet.get_last_valid_slot().is_some() {
-            mock_clock.slot += 1;
-        } else {
-            mock_clock.timestamp += 1;
-        }
+#[test]
+fn test_min_fee_with_quote_lot_budget() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market_with_params(10000, 100, 5);
+    market.set_min_fee_quote_lots(QuoteLots::new(20));
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-        let (_, matching_engine_response) = {
-            let mut mock_clock_fn = || (mock_clock.slot, mock_clock.timestamp);
-            market
-                .place_order(
-                    &taker,
-                    OrderPacket::new_ioc_by_lots(
-                        Side::Ask,
-                        0,
-                        10,
-                        SelfTradeBehavior::Abort,
-                        None,
-                        rng.gen::<u128>(),
-                        false,
-                    ),
-                    &mut record_event_fn,
-                    &mut mock_clock_fn,
-                )
-                .unwrap()
-        };
+    market
+        .place_order(
+            &maker,
+            OrderPacket::new_limit_order_default(Side::Ask, 1, 10),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
 
-        // Assert that TIF kicked in
-        assert_eq!(matching_engine_response.num_quote_lots_out, QuoteLots::ZERO);
+    // Each base lot costs 100 quote lots. After the 5 bps fee, 210 quote lots would buy 2 base
+    // lots, but the minimum fee of 20 quote lots leaves only enough for 1
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_buy_with_slippage(210, 0),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(1));
+    assert_eq!(response.num_quote_lots_in, QuoteLots::new(100 + 20));
 
-        // Verify that the events are released in the expected order
-        for (i, event) in event_recorder.iter().enumerate() {
-            match i {
-                0 => {
-                    assert!(matches!(event, MarketEvent::Place { .. }));
-                }
-                1 => {
-                    assert!(matches!(event, MarketEvent::TimeInForce { .. }));
-                }
-                2 | 4 => {
-                    assert!(matches!(event, MarketEvent::Fill { .. }));
-                }
-                3 | 5 | 7 => {
-                    assert!(matches!(event, MarketEvent::FillSummary { .. }));
-                }
-                6 => {
-                    if let MarketEvent::ExpiredOrder {
-                        maker_id,
-                        order_sequence_number,
-                        price_in_ticks,
-                        base_lots_removed,
-                    } = event
-                    {
-                        assert_eq!(maker_id, &maker);
-                        assert_eq!(
-                            Side::from_order_sequence_number(*order_sequence_number),
-                            Side::Bid
-                        );
-                        assert_eq!(*price_in_ticks, Ticks::new(1000));
-                        assert_eq!(*base_lots_removed, BaseLots::new(80));
-                    } else {
-                        panic!("Invalid event")
-                    }
-                }
-                _ => {
-                    panic!("Invalid event")
-                }
+    // A budget that covers the minimum fee is spent in full
+    let (_, response) = market
+        .place_order(
+            &taker,
+            OrderPacket::new_ioc_buy_with_slippage(220, 0),
+            &mut record_event_fn,
+            &mut get_clock_fn,
+        )
+        .unwrap();
+    assert_eq!(response.num_base_lots_out, BaseLots::new(2));
+    assert_eq!(response.num_quote_lots_in, QuoteLots::new(200 + 20));
+}
+
+#[test]
+fn test_ioc_max_avg_price() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut market = setup_market();
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Buying or selling 20 lots sweeps both price levels on the opposite side of the book, for an
+    // average price of 105 ticks on buys and 85 ticks on sells
+    for (side, prices, limits) in [
+        (Side::Bid, [100, 110], [104, 105]),
+        (Side::Ask, [90, 80], [86, 85]),
+    ] {
+        for (max_avg_price_in_ticks, should_fill) in [(limits[0], false), (limits[1], true)] {
+            // Failed orders are rolled back by the runtime, so the book is refilled for each attempt
+            market.cancel_all_orders(&maker, false, None, &mut record_event_fn);
+            for price_in_ticks in prices {
+                market
+                    .place_order(
+                        &maker,
+                        OrderPacket::new_post_only_default(side.opposite(), price_in_ticks, 10),
+                        &mut record_event_fn,
+                        &mut get_clock_fn,
+                    )
+                    .unwrap();
+            }
+            let mut order_packet = OrderPacket::new_ioc(
+                side,
+                None,
+                20,
+                0,
+                0,
+                0,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+                None,
+                None,
+            );
+            order_packet.set_max_avg_price_in_ticks(Some(Ticks::new(max_avg_price_in_ticks)));
+            let result = market.place_order(
+                &taker,
+                order_packet,
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            );
+            if !should_fill {
+                assert!(matches!(
+                    result,
+                    Err(PhoenixMatchError::ImmediateOrCancelAveragePriceNotMet)
+                ));
+                continue;
             }
+            let (_, response) = result.unwrap();
+            assert_eq!(response.num_base_lots(), BaseLots::new(20));
+            assert_eq!(
+                response.num_quote_lots(),
+                QuoteLots::new(max_avg_price_in_ticks * 10000 * 20 / 100)
+            );
         }
     }
 }
 
 #[test]
-fn test_limit_order_crossing() {
+fn test_referral_fee_split() {
+    use solana_program::pubkey::Pubkey;
     let mut rng = StdRng::seed_from_u64(2);
-    let mut market = setup_market();
-    let mut event_recorder = VecDeque::new();
-    let mut record_event_fn = |e: MarketEvent<TraderId>| event_recorder.push_back(e);
+    let mut market = setup_market_with_params(10000, 100, 100);
+    market.set_maker_rebate(20);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
 
-    let trader_1 = rng.gen::<u128>();
-    let trader_2 = rng.gen::<u128>();
+    // Each trade buys 10 base lots at 10 ticks, which is 10000 quote lots. The taker pays a fee of
+    // 100 quote lots and the maker is rebated 20 quote lots
+    let mut trade = |market: &mut Dex, referrer: Option<Pubkey>| {
+        market
+            .place_order(
+                &maker,
+                OrderPacket::new_post_only_default(Side::Ask, 10, 10),
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        let fees_before = market.get_uncollected_fee_amount();
+        let referral_fees_before = market.get_unclaimed_referral_fee_amount();
+        let mut order_packet = OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            10,
+            10,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        );
+        order_packet.set_referrer(referrer);
+        let mut fee_charged = QuoteLots::ZERO;
+        let mut rebates = QuoteLots::ZERO;
+        market
+            .place_order(
+                &taker,
+                order_packet,
+                &mut |e| match e {
+                    MarketEvent::FillSummary {
+                        total_fee_in_quote_lots,
+                        ..
+                    } => fee_charged += total_fee_in_quote_lots,
+                    MarketEvent::Rebate {
+                        rebate_in_quote_lots,
+                        ..
+                    } => rebates += rebate_in_quote_lots,
+                    _ => {}
+                },
+                &mut get_clock_fn,
+            )
+            .unwrap();
+        let market_fees = market.get_uncollected_fee_amount() - fees_before;
+        let referral_fees = market.get_unclaimed_referral_fee_amount() - referral_fees_before;
+        // The fee paid by the taker is exactly split between the market, the maker and the referrer
+        assert_eq!(fee_charged, QuoteLots::new(100));
+        assert_eq!(rebates, QuoteLots::new(20));
+        assert_eq!(market_fees + rebates + referral_fees, fee_charged);
+        (market_fees, referral_fees)
+    };
 
-    // Place 2 bids for 100 and 95, then fill them both
-    assert!(market
-        .place_order(
-            &trader_1,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+    // Orders without a referrer are not affected by the referral fee
+    market.set_referral_fee_bps(5000);
+    assert_eq!(
+        trade(&mut market, None),
+        (QuoteLots::new(80), QuoteLots::ZERO)
+    );
+
+    // Half of the fee is accrued to the referrer
+    let referrer = Some(Pubkey::new_unique());
+    assert_eq!(
+        trade(&mut market, referrer),
+        (QuoteLots::new(30), QuoteLots::new(50))
+    );
+
+    // The referrer's share is capped at what the market keeps after the maker rebate
+    market.set_referral_fee_bps(10000);
+    assert_eq!(
+        trade(&mut market, referrer),
+        (QuoteLots::ZERO, QuoteLots::new(80))
+    );
+
+    // Unclaimed referral fees are owed by the quote vault
+    let tracked_quote_lots = market.get_tracked_quote_lots();
+    assert_eq!(
+        market.get_unclaimed_referral_fee_amount(),
+        QuoteLots::new(130)
+    );
+    assert!(market.claim_referral_fees(QuoteLots::new(131)).is_none());
+    market.claim_referral_fees(QuoteLots::new(130)).unwrap();
+    assert_eq!(market.get_unclaimed_referral_fee_amount(), QuoteLots::ZERO);
+    assert_eq!(
+        market.get_tracked_quote_lots(),
+        tracked_quote_lots - QuoteLots::new(130)
+    );
+}
+
+#[test]
+fn test_match_errors() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut record_event_fn = |_e: MarketEvent<TraderId>| {};
+    let maker = rng.gen::<u128>();
+    let taker = rng.gen::<u128>();
+
+    // Orders cannot be placed on a market that has not been initialized
+    let mut data = vec![0; std::mem::size_of::<Dex>()];
+    let uninitialized_market = Dex::load_mut_bytes(&mut data).unwrap();
+    assert_eq!(
+        uninitialized_market.place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .is_some());
+        ),
+        Err(PhoenixMatchError::MarketUninitialized)
+    );
 
-    assert!(market
-        .place_order(
-            &trader_1,
-            OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+    // New traders cannot be registered once every seat is taken
+    type SmallDex = FIFOMarket<TraderId, 512, 512, 128>;
+    let mut data = vec![0; std::mem::size_of::<SmallDex>()];
+    let small_market = SmallDex::load_mut_bytes(&mut data).unwrap();
+    small_market.initialize_with_params(
+        QuoteLotsPerBaseUnitPerTick::new(10000),
+        BaseLotsPerBaseUnit::new(100),
+    );
+    for trader in 0..128 {
+        small_market.get_or_register_trader(&trader).unwrap();
+    }
+    assert_eq!(
+        small_market.place_order(
+            &maker,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
-        )
-        .is_some());
+        ),
+        Err(PhoenixMatchError::TraderRegistrationFailed)
+    );
 
-    assert!(market
+    let mut market = Box::new(setup_market());
+    market.set_min_base_lots_per_order(BaseLots::new(5));
+    market
         .place_order(
-            &trader_2,
-            OrderPacket::Limit {
-                side: Side::Ask,
-                price_in_ticks: Ticks::new(95),
-                num_base_lots: BaseLots::new(20),
-                match_limit: Some(1), // Note: the behavior of this the parameter is being tested
-                self_trade_behavior: SelfTradeBehavior::Abort,
-                client_order_id: rng.gen::<u128>(),
-                use_only_deposited_funds: false,
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-                fail_silently_on_insufficient_funds: false
-            },
+            &maker,
+            OrderPacket::new_post_only_default(Side::Ask, 101, 10),
             &mut record_event_fn,
             &mut get_clock_fn,
         )
-        .is_some());
+        .unwrap();
 
-    let ladder = market.get_ladder(5);
-    assert!(ladder.asks.is_empty());
+    let rejected_orders = [
+        (
+            maker,
+            OrderPacket::new_limit_order_default(Side::Bid, 0, 10),
+            PhoenixMatchError::InvalidBidPrice,
+        ),
+        (
+            maker,
+            OrderPacket::new_limit_order_default(Side::Bid, 99, 0),
+            PhoenixMatchError::EmptyOrder,
+        ),
+        (
+            taker,
+            OrderPacket::new_ioc(
+                Side::Bid,
+                Some(101),
+                5,
+                500,
+                0,
+                0,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+                None,
+                None,
+            ),
+            PhoenixMatchError::InvalidImmediateOrderParams,
+        ),
+        (
+            taker,
+            OrderPacket::new_post_only_default(Side::Bid, 101, 10),
+            PhoenixMatchError::PostOnlyCrossesBook,
+        ),
+        (
+            maker,
+            OrderPacket::new_limit_order(
+                Side::Bid,
+                101,
+                10,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            PhoenixMatchError::MatchOrderFailed,
+        ),
+        (
+            taker,
+            OrderPacket::new_post_only_default(Side::Bid, 99, 4),
+            PhoenixMatchError::OrderBelowMinimumSize,
+        ),
+        (
+            taker,
+            OrderPacket::new_post_only(Side::Bid, 99, 10, 0, true, true),
+            PhoenixMatchError::InsufficientDepositedFunds,
+        ),
+        // Immediate orders match against the book before they are rejected, so they go last
+        (
+            taker,
+            OrderPacket::new_fok_buy_with_limit_price(
+                101,
+                20,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+            PhoenixMatchError::FillOrKillNotFilled,
+        ),
+        (
+            taker,
+            OrderPacket::new_ioc(
+                Side::Bid,
+                Some(101),
+                20,
+                0,
+                15,
+                0,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+                None,
+                None,
+            ),
+            PhoenixMatchError::ImmediateOrCancelMinimumNotMet,
+        ),
+    ];
+    for (trader, order_packet, expected_error) in rejected_orders {
+        assert_eq!(
+            market.place_order(
+                &trader,
+                order_packet,
+                &mut record_event_fn,
+                &mut get_clock_fn,
+            ),
+            Err(expected_error)
+        );
+    }
 }
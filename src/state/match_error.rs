@@ -0,0 +1,70 @@
+use num_enum::TryFromPrimitive;
+use thiserror::Error;
+
+/// Reasons the matching engine can reject an order. Each variant maps to a distinct custom
+/// program error code so that clients can tell why an order failed.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum PhoenixMatchError {
+    #[error("Market is uninitialized")]
+    MarketUninitialized = 100,
+    #[error("Order sequence number exceeded maximum")]
+    SequenceNumberExhausted = 101,
+    #[error("Bid price is too low")]
+    InvalidBidPrice = 102,
+    #[error("Failed to register trader")]
+    TraderRegistrationFailed = 103,
+    #[error("Either num_base_lots or num_quote_lots must be nonzero")]
+    EmptyOrder = 104,
+    #[error(
+        "Exactly one of num_base_lots or num_quote_lots must be nonzero for IOC and FOK orders"
+    )]
+    InvalidImmediateOrderParams = 105,
+    #[error("PostOnly order crosses the book")]
+    PostOnlyCrossesBook = 106,
+    #[error("PostOnly order crosses the book and can not be amended to a valid price")]
+    PostOnlyInvalidPrice = 107,
+    #[error("Failed to match order")]
+    MatchOrderFailed = 108,
+    #[error("FOK order failed to fill completely")]
+    FillOrKillNotFilled = 109,
+    #[error("IOC order failed to meet minimum fill requirements")]
+    ImmediateOrCancelMinimumNotMet = 110,
+    #[error("Order size is below the market minimum")]
+    OrderBelowMinimumSize = 111,
+    #[error("Failed to insert order into book")]
+    BookInsertionFailed = 112,
+    #[error("Trader does not have enough deposited funds")]
+    InsufficientDepositedFunds = 113,
+    #[error("Order withdraws funds when only deposited funds may be used")]
+    UnexpectedWithdrawal = 114,
+    #[error("Iceberg display size must be nonzero and no larger than the order size")]
+    InvalidIcebergParams = 115,
+    #[error("Order expires after the market's maximum order lifetime")]
+    OrderLifetimeTooLong = 116,
+    #[error("IOC order failed to meet its worst average price")]
+    ImmediateOrCancelAveragePriceNotMet = 117,
+    #[error("PostOnly order would not be at the top of the book")]
+    PostOnlyNotTopOfBook = 118,
+    #[error("Trader has reached the market's maximum number of open orders on this side")]
+    TooManyOpenOrders = 119,
+    #[error("Order price is outside of the volatility band around the last trade price")]
+    OutsideVolatilityBand = 120,
+    #[error("Order cannot set both last_valid_slot and valid_for_slots")]
+    InvalidOrderExpiration = 121,
+    #[error("Orders that take liquidity are disabled on this market")]
+    TakingDisabled = 122,
+    #[error("Order matched fewer base lots than the market's minimum taker fill")]
+    TakerFillBelowMinimum = 123,
+    #[error("Iceberg orders are not enabled on this market")]
+    IcebergOrdersDisabled = 124,
+    #[error("Order price is too large to convert its quote lots into base lots")]
+    InvalidOrderPrice = 125,
+}
+
+impl PhoenixMatchError {
+    /// Returns the error with the given custom program error code, if there is one
+    pub fn from_u32(code: u32) -> Option<Self> {
+        Self::try_from(code).ok()
+    }
+}
@@ -1,11 +1,13 @@
 pub mod enums;
 pub mod inflight_order;
 pub mod markets;
+pub mod match_error;
 pub mod matching_engine_response;
 pub mod order_schema;
 pub mod trader_state;
 
 pub use enums::*;
+pub use match_error::*;
 pub use matching_engine_response::*;
 pub use order_schema::*;
 pub use trader_state::*;
@@ -6,6 +6,7 @@ use crate::{
     quantities::{BaseLots, QuoteLots, Ticks, WrapperU64},
     state::{SelfTradeBehavior, Side},
 };
+use solana_program::pubkey::Pubkey;
 
 pub trait OrderPacketMetadata {
     fn is_take_only(&self) -> bool {
@@ -50,6 +51,18 @@ pub enum OrderPacket {
 
         /// If this is set, the order will fail silently if there are insufficient funds
         fail_silently_on_insufficient_funds: bool,
+
+        /// If this is set and the order crosses the book with `reject_post_only` set to false, the
+        /// order is rejected instead of amended if its price would move by more than this many ticks
+        max_tick_adjustment: Option<u64>,
+
+        /// If this is set, the order is rejected unless its price is at least as aggressive as the
+        /// best resting order on the same side of the book
+        require_top_of_book: bool,
+
+        /// If this is set, the order will be invalid after this many slots past the slot that it
+        /// is placed in. This cannot be set together with `last_valid_slot`
+        valid_for_slots: Option<u32>,
     },
 
     /// This order type is used to place a limit order on the book
@@ -86,6 +99,28 @@ pub enum OrderPacket {
 
         /// If this is set, the order will fail silently if there are insufficient funds
         fail_silently_on_insufficient_funds: bool,
+
+        /// If this is set, the order can only trade inventory that the trader already has deposited
+        /// on the market, so it never requires new funds. When the order is placed, the size of an
+        /// ask is capped to the trader's free base lots, and the size of a bid is capped to the
+        /// number of base lots that the trader's free quote lots can buy at the limit price after
+        /// taker fees. If the trader has no free inventory on the relevant side, the order is
+        /// skipped.
+        ///
+        /// The cap is only applied at placement. The portion of the order that rests on the book
+        /// locks the inventory it was capped to, and that inventory can only be released by fills
+        /// or cancels of the order itself. The resting size therefore never exceeds the inventory
+        /// backing it, so it is not trimmed again when the trader's other balances change.
+        reduce_only: bool,
+
+        /// If this is set, the portion of the order that rests on the book is reduced to the size
+        /// that the trader can afford with their free funds and the tokens available to deposit,
+        /// after paying for any fills. If nothing is affordable, the order is not posted.
+        place_partial: bool,
+
+        /// If this is set, the order will be invalid after this many slots past the slot that it
+        /// is placed in. This cannot be set together with `last_valid_slot`
+        valid_for_slots: Option<u32>,
     },
 
     /// This order type is used to place an order that will be matched against existing resting orders
@@ -138,6 +173,137 @@ pub enum OrderPacket {
 
         /// If this is set, the order will be invalid after the specified unix timestamp
         last_valid_unix_timestamp_in_seconds: Option<u64>,
+
+        /// The worst average fill price the order will accept, in ticks. If the average price of the
+        /// matched lots (including fees) is above this price for a buy or below this price for a sell,
+        /// the order will be voided.
+        max_avg_price_in_ticks: Option<Ticks>,
+
+        /// If this is set, a share of the taker fee charged to this order is accrued to the
+        /// referrer, who can claim it from the market.
+        referrer: Option<Pubkey>,
+    },
+
+    /// This order type is used to place an order that will be matched against existing resting orders.
+    /// The order is matched atomically: if the full `num_base_lots` (or `num_quote_lots`) cannot be
+    /// filled, the entire order is voided and no state is modified.
+    FillOrKill {
+        side: Side,
+
+        /// The most aggressive price an order can be matched at. If this value is None, then the order
+        /// is treated as a market order.
+        price_in_ticks: Option<Ticks>,
+
+        /// The number of base lots to fill against the order book. Either this parameter or the `num_quote_lots`
+        /// parameter must be set to a nonzero value.
+        num_base_lots: BaseLots,
+
+        /// The number of quote lots to fill against the order book. Either this parameter or the `num_base_lots`
+        /// parameter must be set to a nonzero value.
+        num_quote_lots: QuoteLots,
+
+        /// How the matching engine should handle a self trade.
+        self_trade_behavior: SelfTradeBehavior,
+
+        /// Number of orders to match against. If set to `None`, there is no limit.
+        match_limit: Option<u64>,
+
+        /// Client order id used to identify the order in the program's inner instruction data.
+        client_order_id: u128,
+
+        /// Flag for whether or not the order should only use funds that are already in the account.
+        /// Using only deposited funds will allow the trader to pass in less accounts per instruction and
+        /// save transaction space as well as compute. This is only for traders who have a seat
+        use_only_deposited_funds: bool,
+
+        /// If this is set, the order will be invalid after the specified slot
+        last_valid_slot: Option<u64>,
+
+        /// If this is set, the order will be invalid after the specified unix timestamp
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+    },
+
+    /// This order type is matched like a limit order, but only `display_base_lots` of the
+    /// remaining size are shown on the book at a time. The rest is held in reserve in the market's
+    /// iceberg reserve book, so iceberg orders can only be placed on markets that were initialized
+    /// with iceberg orders enabled. When the visible portion is fully filled, it is replenished
+    /// from the reserve with a new order sequence number and the same client order id, so the
+    /// replenished order joins the back of the queue at its price.
+    ///
+    /// Reducing an iceberg order only reduces its visible portion. If the visible portion is
+    /// cancelled, reduced to zero, evicted or expired, the reserve is released to the trader's free
+    /// funds. Iceberg orders cannot be amended or repriced.
+    Iceberg {
+        side: Side,
+
+        /// The price of the order, in ticks
+        price_in_ticks: Ticks,
+
+        /// Total number of base lots to fill or place on the book, including the reserve
+        num_base_lots: BaseLots,
+
+        /// Maximum number of base lots that are visible on the book at a time
+        display_base_lots: BaseLots,
+
+        /// How the matching engine should handle a self trade
+        self_trade_behavior: SelfTradeBehavior,
+
+        /// Number of orders to match against. If this is `None` there is no limit
+        match_limit: Option<u64>,
+
+        /// Client order id used to identify the order in the response to the client
+        client_order_id: u128,
+
+        /// Flag for whether or not the order should only use funds that are already in the account.
+        /// The full `num_base_lots`, including the reserve that is not yet visible on the book, is
+        /// locked from the trader's deposited funds when the order is placed. This is only for
+        /// traders who have a seat
+        use_only_deposited_funds: bool,
+
+        /// If this is set, the order will be invalid after the specified slot
+        last_valid_slot: Option<u64>,
+
+        /// If this is set, the order will be invalid after the specified unix timestamp
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+    },
+
+    /// This order type is a limit order whose size is specified in quote lots instead of base lots.
+    /// The matching engine converts `num_quote_lots` into base lots at the limit price, rounding
+    /// down, and then handles the order exactly like a `Limit` order of that size. Quote lots that
+    /// are too small to buy a full base lot are never locked, so they remain free funds.
+    LimitByQuote {
+        side: Side,
+
+        /// The price of the order, in ticks
+        price_in_ticks: Ticks,
+
+        /// Notional value of the order in quote lots, at the limit price
+        num_quote_lots: QuoteLots,
+
+        /// How the matching engine should handle a self trade
+        self_trade_behavior: SelfTradeBehavior,
+
+        /// Number of orders to match against. If this is `None` there is no limit
+        match_limit: Option<u64>,
+
+        /// Client order id used to identify the order in the response to the client
+        client_order_id: u128,
+
+        /// Flag for whether or not the order should only use funds that are already in the account.
+        /// Only the converted base lot size is paid for from deposited funds, so any quote lots
+        /// that are too small to buy a full base lot stay free. This is only for traders who have
+        /// a seat
+        use_only_deposited_funds: bool,
+
+        /// If this is set, the order will be invalid after the specified slot
+        last_valid_slot: Option<u64>,
+
+        /// If this is set, the order will be invalid after the specified unix timestamp
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+
+        /// If this is set, the order will be invalid after this many slots past the slot that it
+        /// is placed in. This cannot be set together with `last_valid_slot`
+        valid_for_slots: Option<u32>,
     },
 }
 
@@ -148,6 +314,7 @@ impl OrderPacketMetadata for OrderPacket {
 
     fn is_fok(&self) -> bool {
         match self {
+            Self::FillOrKill { .. } => true,
             &Self::ImmediateOrCancel {
                 num_base_lots,
                 num_quote_lots,
@@ -180,6 +347,18 @@ impl OrderPacketMetadata for OrderPacket {
                 use_only_deposited_funds,
                 ..
             } => use_only_deposited_funds,
+            Self::FillOrKill {
+                use_only_deposited_funds,
+                ..
+            } => use_only_deposited_funds,
+            Self::Iceberg {
+                use_only_deposited_funds,
+                ..
+            } => use_only_deposited_funds,
+            Self::LimitByQuote {
+                use_only_deposited_funds,
+                ..
+            } => use_only_deposited_funds,
         }
     }
 }
@@ -196,6 +375,9 @@ impl OrderPacket {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
         }
     }
 
@@ -215,6 +397,9 @@ impl OrderPacket {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
         }
     }
 
@@ -234,6 +419,9 @@ impl OrderPacket {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
         }
     }
 
@@ -255,6 +443,9 @@ impl OrderPacket {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
         }
     }
 
@@ -307,6 +498,104 @@ impl OrderPacket {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            reduce_only: false,
+            place_partial: false,
+            valid_for_slots: None,
+        }
+    }
+
+    pub fn new_reduce_only_limit_order(
+        side: Side,
+        price_in_ticks: u64,
+        num_base_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::Limit {
+            side,
+            price_in_ticks: Ticks::new(price_in_ticks),
+            num_base_lots: BaseLots::new(num_base_lots),
+            self_trade_behavior,
+            match_limit: None,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+            fail_silently_on_insufficient_funds: false,
+            reduce_only: true,
+            place_partial: false,
+            valid_for_slots: None,
+        }
+    }
+
+    pub fn new_place_partial_limit_order(
+        side: Side,
+        price_in_ticks: u64,
+        num_base_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::Limit {
+            side,
+            price_in_ticks: Ticks::new(price_in_ticks),
+            num_base_lots: BaseLots::new(num_base_lots),
+            self_trade_behavior,
+            match_limit: None,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+            fail_silently_on_insufficient_funds: false,
+            reduce_only: false,
+            place_partial: true,
+            valid_for_slots: None,
+        }
+    }
+
+    pub fn new_iceberg_order(
+        side: Side,
+        price_in_ticks: u64,
+        num_base_lots: u64,
+        display_base_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::Iceberg {
+            side,
+            price_in_ticks: Ticks::new(price_in_ticks),
+            num_base_lots: BaseLots::new(num_base_lots),
+            display_base_lots: BaseLots::new(display_base_lots),
+            self_trade_behavior,
+            match_limit: None,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        }
+    }
+
+    pub fn new_limit_order_by_quote(
+        side: Side,
+        price_in_ticks: u64,
+        num_quote_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::LimitByQuote {
+            side,
+            price_in_ticks: Ticks::new(price_in_ticks),
+            num_quote_lots: QuoteLots::new(num_quote_lots),
+            self_trade_behavior,
+            match_limit: None,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+            valid_for_slots: None,
         }
     }
 
@@ -318,13 +607,11 @@ impl OrderPacket {
         client_order_id: u128,
         use_only_deposited_funds: bool,
     ) -> Self {
-        Self::new_ioc(
+        Self::new_fok(
             Side::Ask,
             Some(target_price_in_ticks),
             base_lot_budget,
             0,
-            base_lot_budget,
-            0,
             self_trade_behavior,
             match_limit,
             client_order_id,
@@ -342,13 +629,55 @@ impl OrderPacket {
         client_order_id: u128,
         use_only_deposited_funds: bool,
     ) -> Self {
-        Self::new_ioc(
+        Self::new_fok(
             Side::Bid,
             Some(target_price_in_ticks),
             base_lot_budget,
             0,
-            base_lot_budget,
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            use_only_deposited_funds,
+            None,
+            None,
+        )
+    }
+
+    pub fn new_fok_sell_with_quote_lot_budget(
+        target_price_in_ticks: u64,
+        quote_lot_budget: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::new_fok(
+            Side::Ask,
+            Some(target_price_in_ticks),
+            0,
+            quote_lot_budget,
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            use_only_deposited_funds,
+            None,
+            None,
+        )
+    }
+
+    pub fn new_fok_buy_with_quote_lot_budget(
+        target_price_in_ticks: u64,
+        quote_lot_budget: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+    ) -> Self {
+        Self::new_fok(
+            Side::Bid,
+            Some(target_price_in_ticks),
             0,
+            quote_lot_budget,
             self_trade_behavior,
             match_limit,
             client_order_id,
@@ -493,6 +822,35 @@ impl OrderPacket {
             use_only_deposited_funds,
             last_valid_slot,
             last_valid_unix_timestamp_in_seconds,
+            max_avg_price_in_ticks: None,
+            referrer: None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_fok(
+        side: Side,
+        price_in_ticks: Option<u64>,
+        num_base_lots: u64,
+        num_quote_lots: u64,
+        self_trade_behavior: SelfTradeBehavior,
+        match_limit: Option<u64>,
+        client_order_id: u128,
+        use_only_deposited_funds: bool,
+        last_valid_slot: Option<u64>,
+        last_valid_unix_timestamp_in_seconds: Option<u64>,
+    ) -> Self {
+        Self::FillOrKill {
+            side,
+            price_in_ticks: price_in_ticks.map(Ticks::new),
+            num_base_lots: BaseLots::new(num_base_lots),
+            num_quote_lots: QuoteLots::new(num_quote_lots),
+            self_trade_behavior,
+            match_limit,
+            client_order_id,
+            use_only_deposited_funds,
+            last_valid_slot,
+            last_valid_unix_timestamp_in_seconds,
         }
     }
 }
@@ -503,6 +861,9 @@ impl OrderPacket {
             Self::PostOnly { side, .. } => *side,
             Self::Limit { side, .. } => *side,
             Self::ImmediateOrCancel { side, .. } => *side,
+            Self::FillOrKill { side, .. } => *side,
+            Self::Iceberg { side, .. } => *side,
+            Self::LimitByQuote { side, .. } => *side,
         }
     }
 
@@ -517,6 +878,33 @@ impl OrderPacket {
                 ..
             } => *fail_silently_on_insufficient_funds,
             Self::ImmediateOrCancel { .. } => false,
+            Self::FillOrKill { .. } => false,
+            Self::Iceberg { .. } => false,
+            Self::LimitByQuote { .. } => false,
+        }
+    }
+
+    pub fn is_reduce_only(&self) -> bool {
+        match self {
+            Self::Limit { reduce_only, .. } => *reduce_only,
+            _ => false,
+        }
+    }
+
+    pub fn is_place_partial(&self) -> bool {
+        match self {
+            Self::Limit { place_partial, .. } => *place_partial,
+            _ => false,
+        }
+    }
+
+    /// Returns the maximum visible size of an iceberg order, or None for other order types.
+    pub fn display_base_lots(&self) -> Option<BaseLots> {
+        match self {
+            Self::Iceberg {
+                display_base_lots, ..
+            } => Some(*display_base_lots),
+            _ => None,
         }
     }
 
@@ -531,6 +919,15 @@ impl OrderPacket {
             Self::ImmediateOrCancel {
                 client_order_id, ..
             } => *client_order_id,
+            Self::FillOrKill {
+                client_order_id, ..
+            } => *client_order_id,
+            Self::Iceberg {
+                client_order_id, ..
+            } => *client_order_id,
+            Self::LimitByQuote {
+                client_order_id, ..
+            } => *client_order_id,
         }
     }
 
@@ -539,6 +936,9 @@ impl OrderPacket {
             Self::PostOnly { num_base_lots, .. } => *num_base_lots,
             Self::Limit { num_base_lots, .. } => *num_base_lots,
             Self::ImmediateOrCancel { num_base_lots, .. } => *num_base_lots,
+            Self::FillOrKill { num_base_lots, .. } => *num_base_lots,
+            Self::Iceberg { num_base_lots, .. } => *num_base_lots,
+            Self::LimitByQuote { .. } => BaseLots::ZERO,
         }
     }
 
@@ -547,6 +947,9 @@ impl OrderPacket {
             Self::PostOnly { .. } => QuoteLots::ZERO,
             Self::Limit { .. } => QuoteLots::ZERO,
             Self::ImmediateOrCancel { num_quote_lots, .. } => *num_quote_lots,
+            Self::FillOrKill { num_quote_lots, .. } => *num_quote_lots,
+            Self::Iceberg { .. } => QuoteLots::ZERO,
+            Self::LimitByQuote { num_quote_lots, .. } => *num_quote_lots,
         }
     }
 
@@ -573,6 +976,9 @@ impl OrderPacket {
             Self::PostOnly { .. } => u64::MAX,
             Self::Limit { match_limit, .. } => match_limit.unwrap_or(u64::MAX),
             Self::ImmediateOrCancel { match_limit, .. } => match_limit.unwrap_or(u64::MAX),
+            Self::FillOrKill { match_limit, .. } => match_limit.unwrap_or(u64::MAX),
+            Self::Iceberg { match_limit, .. } => match_limit.unwrap_or(u64::MAX),
+            Self::LimitByQuote { match_limit, .. } => match_limit.unwrap_or(u64::MAX),
         }
     }
 
@@ -587,6 +993,18 @@ impl OrderPacket {
                 self_trade_behavior,
                 ..
             } => *self_trade_behavior,
+            Self::FillOrKill {
+                self_trade_behavior,
+                ..
+            } => *self_trade_behavior,
+            Self::Iceberg {
+                self_trade_behavior,
+                ..
+            } => *self_trade_behavior,
+            Self::LimitByQuote {
+                self_trade_behavior,
+                ..
+            } => *self_trade_behavior,
         }
     }
 
@@ -594,7 +1012,10 @@ impl OrderPacket {
         match self {
             Self::PostOnly { price_in_ticks, .. } => *price_in_ticks,
             Self::Limit { price_in_ticks, .. } => *price_in_ticks,
-            Self::ImmediateOrCancel { price_in_ticks, .. } => {
+            Self::Iceberg { price_in_ticks, .. } => *price_in_ticks,
+            Self::LimitByQuote { price_in_ticks, .. } => *price_in_ticks,
+            Self::ImmediateOrCancel { price_in_ticks, .. }
+            | Self::FillOrKill { price_in_ticks, .. } => {
                 price_in_ticks.unwrap_or(match self.side() {
                     Side::Bid => Ticks::MAX,
                     Side::Ask => Ticks::MIN,
@@ -617,6 +1038,47 @@ impl OrderPacket {
                 price_in_ticks: old_price_in_ticks,
                 ..
             } => *old_price_in_ticks = Some(price_in_ticks),
+            Self::FillOrKill {
+                price_in_ticks: old_price_in_ticks,
+                ..
+            } => *old_price_in_ticks = Some(price_in_ticks),
+            Self::Iceberg {
+                price_in_ticks: old_price_in_ticks,
+                ..
+            } => *old_price_in_ticks = price_in_ticks,
+            Self::LimitByQuote {
+                price_in_ticks: old_price_in_ticks,
+                ..
+            } => *old_price_in_ticks = price_in_ticks,
+        }
+    }
+
+    pub fn set_last_valid_slot(&mut self, last_valid_slot: Option<u64>) {
+        match self {
+            Self::PostOnly {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
+            Self::Limit {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
+            Self::ImmediateOrCancel {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
+            Self::FillOrKill {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
+            Self::Iceberg {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
+            Self::LimitByQuote {
+                last_valid_slot: old_last_valid_slot,
+                ..
+            } => *old_last_valid_slot = last_valid_slot,
         }
     }
 
@@ -631,6 +1093,87 @@ impl OrderPacket {
             Self::ImmediateOrCancel {
                 last_valid_slot, ..
             } => *last_valid_slot,
+            Self::FillOrKill {
+                last_valid_slot, ..
+            } => *last_valid_slot,
+            Self::Iceberg {
+                last_valid_slot, ..
+            } => *last_valid_slot,
+            Self::LimitByQuote {
+                last_valid_slot, ..
+            } => *last_valid_slot,
+        }
+    }
+
+    /// Returns the number of slots that a post-only, limit or limit-by-quote order is valid for
+    /// after the slot that it is placed in, or None for other order types.
+    pub fn get_valid_for_slots(&self) -> Option<u32> {
+        match self {
+            Self::PostOnly {
+                valid_for_slots, ..
+            } => *valid_for_slots,
+            Self::Limit {
+                valid_for_slots, ..
+            } => *valid_for_slots,
+            Self::LimitByQuote {
+                valid_for_slots, ..
+            } => *valid_for_slots,
+            _ => None,
+        }
+    }
+
+    pub fn set_valid_for_slots(&mut self, valid_for_slots: Option<u32>) {
+        match self {
+            Self::PostOnly {
+                valid_for_slots: old_valid_for_slots,
+                ..
+            } => *old_valid_for_slots = valid_for_slots,
+            Self::Limit {
+                valid_for_slots: old_valid_for_slots,
+                ..
+            } => *old_valid_for_slots = valid_for_slots,
+            Self::LimitByQuote {
+                valid_for_slots: old_valid_for_slots,
+                ..
+            } => *old_valid_for_slots = valid_for_slots,
+            _ => {}
+        }
+    }
+
+    pub fn get_max_avg_price_in_ticks(&self) -> Option<Ticks> {
+        match self {
+            Self::ImmediateOrCancel {
+                max_avg_price_in_ticks,
+                ..
+            } => *max_avg_price_in_ticks,
+            _ => None,
+        }
+    }
+
+    pub fn set_max_avg_price_in_ticks(&mut self, max_avg_price_in_ticks: Option<Ticks>) {
+        if let Self::ImmediateOrCancel {
+            max_avg_price_in_ticks: old_max_avg_price_in_ticks,
+            ..
+        } = self
+        {
+            *old_max_avg_price_in_ticks = max_avg_price_in_ticks;
+        }
+    }
+
+    pub fn get_referrer(&self) -> Option<Pubkey> {
+        match self {
+            Self::ImmediateOrCancel { referrer, .. } => *referrer,
+            _ => None,
+        }
+    }
+
+    pub fn set_referrer(&mut self, referrer: Option<Pubkey>) {
+        if let Self::ImmediateOrCancel {
+            referrer: old_referrer,
+            ..
+        } = self
+        {
+            *old_referrer = referrer;
         }
     }
 
@@ -648,6 +1191,18 @@ impl OrderPacket {
                 last_valid_unix_timestamp_in_seconds,
                 ..
             } => *last_valid_unix_timestamp_in_seconds,
+            Self::FillOrKill {
+                last_valid_unix_timestamp_in_seconds,
+                ..
+            } => *last_valid_unix_timestamp_in_seconds,
+            Self::Iceberg {
+                last_valid_unix_timestamp_in_seconds,
+                ..
+            } => *last_valid_unix_timestamp_in_seconds,
+            Self::LimitByQuote {
+                last_valid_unix_timestamp_in_seconds,
+                ..
+            } => *last_valid_unix_timestamp_in_seconds,
         }
     }
 
@@ -684,7 +1239,10 @@ pub fn decode_order_packet(bytes: &[u8]) -> Option<OrderPacket> {
             let additional_fields = &[
                 0_u8, /* last_valid_slot */
                 0_u8, /* last_valid_unix_timestamp_in_seconds */
-                0_u8, /* fail_silently_on_insufficient_funds */
+                0_u8, /* fail_silently_on_insufficient_funds or valid_for_slots (LimitByQuote) */
+                0_u8, /* max_tick_adjustment (PostOnly), reduce_only (Limit) or max_avg_price_in_ticks (IOC) */
+                0_u8, /* place_partial (Limit), require_top_of_book (PostOnly) or referrer (IOC) */
+                0_u8, /* valid_for_slots (PostOnly and Limit) */
             ];
             let mut padded_bytes = [bytes, additional_fields].concat();
             for _ in 0..additional_fields.len() {
@@ -761,6 +1319,9 @@ fn test_decode_order_packet() {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            max_tick_adjustment: None,
+            require_top_of_book: false,
+            valid_for_slots: None,
         };
         let deprecated_packet = DeprecatedOrderPacket::PostOnly {
             side,
@@ -774,12 +1335,14 @@ fn test_decode_order_packet() {
         let decoded_normal = decode_order_packet(&bytes).unwrap();
         let decoded_inferred_1 = decode_order_packet(&bytes[..bytes.len() - 1]).unwrap();
         let decoded_inferred_2 = decode_order_packet(&bytes[..bytes.len() - 3]).unwrap();
+        let decoded_inferred_3 = decode_order_packet(&bytes[..bytes.len() - 4]).unwrap();
         let deprecated_bytes = deprecated_packet.try_to_vec().unwrap();
         let decoded_deprecated = decode_order_packet(&deprecated_bytes).unwrap();
         assert_eq!(packet, decoded_normal);
         assert_eq!(decoded_normal, decoded_inferred_1);
         assert_eq!(decoded_inferred_1, decoded_deprecated);
         assert_eq!(decoded_inferred_1, decoded_inferred_2);
+        assert_eq!(decoded_inferred_2, decoded_inferred_3);
     }
 
     for _ in 0..num_iters {
@@ -792,10 +1355,11 @@ fn test_decode_order_packet() {
         let price_in_ticks = Ticks::new(rng.gen::<u64>());
         let num_base_lots = BaseLots::new(rng.gen::<u64>());
         let client_order_id = rng.gen::<u128>();
-        let self_trade_behavior = match rng.gen_range(0, 3) {
+        let self_trade_behavior = match rng.gen_range(0, 4) {
             0 => SelfTradeBehavior::DecrementTake,
             1 => SelfTradeBehavior::CancelProvide,
             2 => SelfTradeBehavior::Abort,
+            3 => SelfTradeBehavior::CancelTake,
             _ => unreachable!(),
         };
         let match_limit = if rng.gen::<f64>() > 0.5 {
@@ -815,6 +1379,9 @@ fn test_decode_order_packet() {
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
             fail_silently_on_insufficient_funds: false,
+            reduce_only: false,
+            place_partial: false,
+            valid_for_slots: None,
         };
         let deprecated_packet = DeprecatedOrderPacket::Limit {
             side,
@@ -829,12 +1396,14 @@ fn test_decode_order_packet() {
         let decoded_normal = decode_order_packet(&bytes).unwrap();
         let decoded_inferred_1 = decode_order_packet(&bytes[..bytes.len() - 1]).unwrap();
         let decoded_inferred_2 = decode_order_packet(&bytes[..bytes.len() - 3]).unwrap();
+        let decoded_inferred_3 = decode_order_packet(&bytes[..bytes.len() - 4]).unwrap();
         let deprecated_bytes = deprecated_packet.try_to_vec().unwrap();
         let decoded_deprecated = decode_order_packet(&deprecated_bytes).unwrap();
         assert_eq!(packet, decoded_normal);
         assert_eq!(decoded_normal, decoded_inferred_1);
         assert_eq!(decoded_inferred_1, decoded_deprecated);
         assert_eq!(decoded_inferred_1, decoded_inferred_2);
+        assert_eq!(decoded_inferred_2, decoded_inferred_3);
     }
 
     for _ in 0..num_iters {
@@ -854,10 +1423,11 @@ fn test_decode_order_packet() {
         let num_quote_lots = QuoteLots::new(rng.gen::<u64>());
         let min_quote_lots_to_fill = QuoteLots::new(rng.gen::<u64>());
         let client_order_id = rng.gen::<u128>();
-        let self_trade_behavior = match rng.gen_range(0, 3) {
+        let self_trade_behavior = match rng.gen_range(0, 4) {
             0 => SelfTradeBehavior::DecrementTake,
             1 => SelfTradeBehavior::CancelProvide,
             2 => SelfTradeBehavior::Abort,
+            3 => SelfTradeBehavior::CancelTake,
             _ => unreachable!(),
         };
         let match_limit = if rng.gen::<f64>() > 0.5 {
@@ -879,6 +1449,8 @@ fn test_decode_order_packet() {
             use_only_deposited_funds,
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
+            max_avg_price_in_ticks: None,
+            referrer: None,
         };
         let deprecated_packet = DeprecatedOrderPacket::ImmediateOrCancel {
             side,
@@ -903,4 +1475,72 @@ fn test_decode_order_packet() {
         assert_eq!(decoded_inferred_1, decoded_deprecated);
         assert_eq!(decoded_inferred_1, decoded_inferred_2);
     }
+
+    for _ in 0..num_iters {
+        let side = if rng.gen::<f64>() > 0.5 {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+        let price_in_ticks = if rng.gen::<f64>() > 0.5 {
+            Some(rng.gen::<u64>())
+        } else {
+            None
+        };
+        let self_trade_behavior = match rng.gen_range(0, 4) {
+            0 => SelfTradeBehavior::DecrementTake,
+            1 => SelfTradeBehavior::CancelProvide,
+            2 => SelfTradeBehavior::Abort,
+            3 => SelfTradeBehavior::CancelTake,
+            _ => unreachable!(),
+        };
+        let match_limit = if rng.gen::<f64>() > 0.5 {
+            Some(rng.gen::<u64>())
+        } else {
+            None
+        };
+        let packet = OrderPacket::new_fok(
+            side,
+            price_in_ticks,
+            rng.gen::<u64>(),
+            rng.gen::<u64>(),
+            self_trade_behavior,
+            match_limit,
+            rng.gen::<u128>(),
+            rng.gen::<bool>(),
+            None,
+            None,
+        );
+        let bytes = packet.try_to_vec().unwrap();
+        let decoded_normal = decode_order_packet(&bytes).unwrap();
+        let decoded_inferred_1 = decode_order_packet(&bytes[..bytes.len() - 2]).unwrap();
+        let decoded_inferred_2 = decode_order_packet(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(decoded_normal.is_fok());
+        assert_eq!(packet, decoded_normal);
+        assert_eq!(decoded_normal, decoded_inferred_1);
+        assert_eq!(decoded_inferred_1, decoded_inferred_2);
+    }
+}
+
+#[test]
+fn test_decode_limit_order_by_quote_without_valid_for_slots() {
+    let mut order_packet = OrderPacket::new_limit_order_by_quote(
+        Side::Bid,
+        10,
+        25000,
+        SelfTradeBehavior::Abort,
+        7,
+        true,
+    );
+    order_packet.set_last_valid_slot(Some(100));
+    let bytes = order_packet.try_to_vec().unwrap();
+    // Packets serialized before `valid_for_slots` was added decode with it unset
+    assert_eq!(
+        decode_order_packet(&bytes[..bytes.len() - 1]).unwrap(),
+        order_packet
+    );
+
+    order_packet.set_valid_for_slots(Some(25));
+    let bytes = order_packet.try_to_vec().unwrap();
+    assert_eq!(decode_order_packet(&bytes).unwrap(), order_packet);
 }
@@ -1,4 +1,5 @@
-use crate::quantities::{BaseLots, QuoteLots};
+use crate::quantities::{BaseLots, QuoteLots, WrapperU64};
+use crate::state::{SelfTradeBehavior, Side};
 use bytemuck::{Pod, Zeroable};
 
 #[repr(C)]
@@ -8,10 +9,117 @@ pub struct TraderState {
     pub quote_lots_free: QuoteLots,
     pub base_lots_locked: BaseLots,
     pub base_lots_free: BaseLots,
-    _padding: [u64; 8],
+    /// Index into the market's fee tiers. Tier 0 is the default tier, which pays the market's
+    /// taker fee. The tier is reset when the trader's seat is removed from the market.
+    pub fee_tier: u8,
+    /// Self trade behavior that overrides the behavior of every order packet sent by the trader.
+    /// 0 means the packet's behavior is used. Use `self_trade_behavior_override()` to read it.
+    self_trade_behavior_override: u8,
+    _padding0: [u8; 6],
+    /// Number of fills the trader has participated in, as either maker or taker. Only
+    /// incremented while trader volume tracking is enabled on the market.
+    pub cumulative_fills: u64,
+    /// Cumulative quote lots traded, as either maker or taker. The u128 is split into two u64s
+    /// to keep the struct 8-byte aligned. Use `cumulative_quote_lots_traded()` to read it.
+    cumulative_quote_lots_traded: [u64; 2],
+    /// The slot in which the trader last placed an order. This is also set when the trader's seat
+    /// is approved, so newly approved traders are not immediately considered inactive.
+    pub last_active_slot: u64,
+    /// Number of the trader's orders resting on the bid side of the book. Use `num_open_orders()`
+    /// to read it.
+    num_open_bids: u32,
+    /// Number of the trader's orders resting on the ask side of the book. Use `num_open_orders()`
+    /// to read it.
+    num_open_asks: u32,
+    /// Funds held by the trader's stop orders. These are not free, so they cannot be withdrawn
+    /// or used by other orders until the stop orders trigger or are cancelled.
+    pub quote_lots_locked_for_stops: QuoteLots,
+    pub base_lots_locked_for_stops: BaseLots,
 }
 
 impl TraderState {
+    /// Returns true if the trader has no locked or free funds on the market and is in the default
+    /// fee tier, so removing the trader from the market loses no state
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.fee_tier == 0
+            && self.quote_lots_locked == QuoteLots::ZERO
+            && self.quote_lots_free == QuoteLots::ZERO
+            && self.base_lots_locked == BaseLots::ZERO
+            && self.base_lots_free == BaseLots::ZERO
+            && self.quote_lots_locked_for_stops == QuoteLots::ZERO
+            && self.base_lots_locked_for_stops == BaseLots::ZERO
+    }
+
+    /// Returns the cumulative quote lots the trader has traded on the market
+    #[inline(always)]
+    pub fn cumulative_quote_lots_traded(&self) -> u128 {
+        (self.cumulative_quote_lots_traded[0] as u128)
+            | ((self.cumulative_quote_lots_traded[1] as u128) << 64)
+    }
+
+    /// Returns the self trade behavior that supersedes the one in the trader's order packets
+    #[inline(always)]
+    pub fn self_trade_behavior_override(&self) -> Option<SelfTradeBehavior> {
+        match self.self_trade_behavior_override {
+            1 => Some(SelfTradeBehavior::Abort),
+            2 => Some(SelfTradeBehavior::CancelProvide),
+            3 => Some(SelfTradeBehavior::DecrementTake),
+            4 => Some(SelfTradeBehavior::CancelTake),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_self_trade_behavior_override(
+        &mut self,
+        self_trade_behavior: Option<SelfTradeBehavior>,
+    ) {
+        self.self_trade_behavior_override = match self_trade_behavior {
+            None => 0,
+            Some(SelfTradeBehavior::Abort) => 1,
+            Some(SelfTradeBehavior::CancelProvide) => 2,
+            Some(SelfTradeBehavior::DecrementTake) => 3,
+            Some(SelfTradeBehavior::CancelTake) => 4,
+        };
+    }
+
+    /// Returns the number of the trader's orders resting on the given side of the book
+    #[inline(always)]
+    pub fn num_open_orders(&self, side: Side) -> u32 {
+        match side {
+            Side::Bid => self.num_open_bids,
+            Side::Ask => self.num_open_asks,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn increment_open_orders(&mut self, side: Side) {
+        match side {
+            Side::Bid => self.num_open_bids = self.num_open_bids.saturating_add(1),
+            Side::Ask => self.num_open_asks = self.num_open_asks.saturating_add(1),
+        }
+    }
+
+    /// Orders placed before the counts were tracked are not included in them, so the counts
+    /// saturate at zero when those orders are removed.
+    #[inline(always)]
+    pub(crate) fn decrement_open_orders(&mut self, side: Side) {
+        match side {
+            Side::Bid => self.num_open_bids = self.num_open_bids.saturating_sub(1),
+            Side::Ask => self.num_open_asks = self.num_open_asks.saturating_sub(1),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record_trade(&mut self, quote_lots: QuoteLots, num_fills: u64) {
+        let total = self
+            .cumulative_quote_lots_traded()
+            .saturating_add(quote_lots.as_u64() as u128);
+        self.cumulative_quote_lots_traded = [total as u64, (total >> 64) as u64];
+        self.cumulative_fills = self.cumulative_fills.saturating_add(num_fills);
+    }
+
     #[inline(always)]
     pub(crate) fn unlock_quote_lots(&mut self, quote_lots: QuoteLots) {
         self.quote_lots_locked -= quote_lots;
@@ -24,6 +132,22 @@ impl TraderState {
         self.base_lots_free += base_lots;
     }
 
+    #[inline(always)]
+    pub(crate) fn lock_funds_for_stop(&mut self, quote_lots: QuoteLots, base_lots: BaseLots) {
+        self.quote_lots_free -= quote_lots;
+        self.base_lots_free -= base_lots;
+        self.quote_lots_locked_for_stops += quote_lots;
+        self.base_lots_locked_for_stops += base_lots;
+    }
+
+    #[inline(always)]
+    pub(crate) fn unlock_funds_for_stop(&mut self, quote_lots: QuoteLots, base_lots: BaseLots) {
+        self.quote_lots_locked_for_stops -= quote_lots;
+        self.base_lots_locked_for_stops -= base_lots;
+        self.quote_lots_free += quote_lots;
+        self.base_lots_free += base_lots;
+    }
+
     #[inline(always)]
     pub(crate) fn process_limit_sell(
         &mut self,
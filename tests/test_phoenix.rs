@@ -1,13 +1,20 @@
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use ellipsis_client::program_test::*;
 use ellipsis_client::EllipsisClient;
 use itertools::Itertools;
 use phoenix::phoenix_log_authority;
-use phoenix::program::deposit::DepositParams;
+use phoenix::program::deposit::{DepositAtomsParams, DepositParams};
+use phoenix::program::fees::FeeSplit;
+use phoenix::program::governance::SetVolatilityBandParams;
 use phoenix::program::instruction_builders::*;
+use phoenix::program::manage_seat::MAX_TRADERS_PER_SEAT_BATCH;
+use phoenix::program::new_order::CancelAndSwapParams;
 use phoenix::program::new_order::CondensedOrder;
 use phoenix::program::new_order::FailedMultipleLimitOrderBehavior;
 use phoenix::program::new_order::MultipleOrderPacket;
+use phoenix::program::new_order::PlaceByFundsFractionParams;
+use phoenix::program::new_order::SwapRouteParams;
+use phoenix::program::withdraw::WithdrawParams;
 use phoenix::program::MarketHeader;
 use phoenix::quantities::Ticks;
 use phoenix::quantities::WrapperU64;
@@ -15,7 +22,11 @@ use phoenix::quantities::{BaseLots, QuoteLots};
 use phoenix_sdk::sdk_client::MarketEventDetails;
 use phoenix_sdk::sdk_client::MarketMetadata;
 use phoenix_sdk::sdk_client::Reduce;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sokoban::node_allocator::NodeAllocatorMap;
 use sokoban::ZeroCopy;
+use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
 use solana_program::instruction::AccountMeta;
 use solana_program::instruction::Instruction;
 use solana_program::system_instruction::{self, transfer};
@@ -31,6 +42,7 @@ use phoenix_sdk::sdk_client::SDKClient;
 
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
 
 pub mod helpers;
 use crate::helpers::*;
@@ -118,11 +130,8 @@ async fn get_token_balance(client: &EllipsisClient, ata: Pubkey) -> u64 {
     get_token_account(client, &ata).await.unwrap().amount
 }
 
-async fn bootstrap_default(fees_bps: u16) -> (PhoenixTestClient, PhoenixTestContext) {
-    bootstrap_with_parameters(100_000, 1_000, 1_000, 9, 6, fees_bps, None).await
-}
-
-async fn bootstrap_with_parameters(
+/// Parameters of the market created by `bootstrap_with_parameters`
+struct BootstrapParams {
     num_quote_lots_per_quote_unit: u64,
     num_base_lots_per_base_unit: u64,
     tick_size_in_quote_lots_per_base_unit: u64,
@@ -130,7 +139,57 @@ async fn bootstrap_with_parameters(
     quote_decimals: u8,
     fee_bps: u16,
     raw_base_units_per_base_unit: Option<u32>,
+    enable_client_order_id_index: bool,
+    enable_stop_orders: bool,
+    enable_referral_fees: bool,
+    enable_recent_fills: bool,
+    enable_iceberg_orders: bool,
+}
+
+impl Default for BootstrapParams {
+    fn default() -> Self {
+        Self {
+            num_quote_lots_per_quote_unit: 100_000,
+            num_base_lots_per_base_unit: 1_000,
+            tick_size_in_quote_lots_per_base_unit: 1_000,
+            base_decimals: 9,
+            quote_decimals: 6,
+            fee_bps: 0,
+            raw_base_units_per_base_unit: None,
+            enable_client_order_id_index: false,
+            enable_stop_orders: false,
+            enable_referral_fees: false,
+            enable_recent_fills: false,
+            enable_iceberg_orders: false,
+        }
+    }
+}
+
+async fn bootstrap_default(fee_bps: u16) -> (PhoenixTestClient, PhoenixTestContext) {
+    bootstrap_with_parameters(BootstrapParams {
+        fee_bps,
+        ..Default::default()
+    })
+    .await
+}
+
+async fn bootstrap_with_parameters(
+    bootstrap_params: BootstrapParams,
 ) -> (PhoenixTestClient, PhoenixTestContext) {
+    let BootstrapParams {
+        num_quote_lots_per_quote_unit,
+        num_base_lots_per_base_unit,
+        tick_size_in_quote_lots_per_base_unit,
+        base_decimals,
+        quote_decimals,
+        fee_bps,
+        raw_base_units_per_base_unit,
+        enable_client_order_id_index,
+        enable_stop_orders,
+        enable_referral_fees,
+        enable_recent_fills,
+        enable_iceberg_orders,
+    } = bootstrap_params;
     let context = phoenix_test().start_with_context().await;
     let mut ellipsis_client = EllipsisClient::from_banks(&context.banks_client, &context.payer)
         .await
@@ -206,17 +265,27 @@ async fn bootstrap_with_parameters(
     let mut init_instructions = vec![];
 
     init_instructions.extend_from_slice(
-        &create_initialize_market_instructions_default(
+        &create_initialize_market_instructions(
             &market.pubkey(),
             &base_mint.pubkey(),
             &quote_mint.pubkey(),
+            &spl_token::id(),
             &payer.pubkey(),
-            params,
-            num_quote_lots_per_quote_unit,
-            num_base_lots_per_base_unit,
-            tick_size_in_quote_lots_per_base_unit,
-            fee_bps,
-            raw_base_units_per_base_unit,
+            InitializeParams {
+                market_size_params: params,
+                num_quote_lots_per_quote_unit,
+                num_base_lots_per_base_unit,
+                tick_size_in_quote_lots_per_base_unit,
+                taker_fee_bps: fee_bps,
+                fee_collector: payer.pubkey(),
+                raw_base_units_per_base_unit,
+                enable_client_order_id_index: Some(enable_client_order_id_index),
+                enable_stop_orders: Some(enable_stop_orders),
+                enable_referral_fees: Some(enable_referral_fees),
+                enable_recent_fills: Some(enable_recent_fills),
+                enable_iceberg_orders: Some(enable_iceberg_orders),
+                ..Default::default()
+            },
         )
         .unwrap(),
     );
@@ -571,6 +640,7 @@ async fn test_phoenix_request_seats() {
                     &maker1.pubkey(),
                     base_mint,
                     quote_mint,
+                    &spl_token::id(),
                 )],
                 vec![],
             )
@@ -588,6 +658,7 @@ async fn test_phoenix_request_seats() {
                 &sdk.client.payer.pubkey(),
                 base_mint,
                 quote_mint,
+                &spl_token::id(),
             ),
             vec![],
         )
@@ -603,6 +674,7 @@ async fn test_phoenix_request_seats() {
                 &maker1.pubkey(),
                 base_mint,
                 quote_mint,
+                &spl_token::id(),
             )],
             vec![],
         )
@@ -618,6 +690,102 @@ async fn get_sequence_number(client: &EllipsisClient, market: &Pubkey) -> u64 {
     full_market.inner.get_sequence_number()
 }
 
+async fn assert_header_counters(client: &EllipsisClient, market: &Pubkey) {
+    let market_data = client.get_account(market).await.unwrap().data;
+    let (header_bytes, bytes) = market_data.split_at(size_of::<MarketHeader>());
+    let header = Box::new(MarketHeader::load_bytes(header_bytes).unwrap());
+    let full_market = load_with_dispatch(&header.market_size_params, bytes).unwrap();
+    assert_eq!(
+        header.num_bids,
+        full_market.inner.get_book(Side::Bid).len() as u64
+    );
+    assert_eq!(
+        header.num_asks,
+        full_market.inner.get_book(Side::Ask).len() as u64
+    );
+    assert_eq!(
+        header.num_traders,
+        full_market.inner.get_registered_traders().len() as u64
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_header_counters() {
+    let (phoenix_client, ctx) = bootstrap_default(0).await;
+    let second_maker = get_new_maker(&phoenix_client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestClient {
+        ctx: _,
+        sdk,
+        meta,
+        market,
+    } = &phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+
+    assert_header_counters(&sdk.client, market).await;
+
+    let makers = [default_maker, &second_maker];
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..40 {
+        let maker = makers[rng.gen_range(0, makers.len())];
+        let instruction = if rng.gen_range(0, 8) == 0 {
+            create_cancel_all_orders_instruction(
+                market,
+                &maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+            )
+        } else {
+            // Bids and asks never cross, so every order rests on the book
+            let (side, price) = if rng.gen::<bool>() {
+                (Side::Bid, rng.gen_range(90, 100))
+            } else {
+                (Side::Ask, rng.gen_range(102, 112))
+            };
+            create_new_order_instruction(
+                market,
+                &maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    side,
+                    meta.float_price_to_ticks_rounded_down(price as f64),
+                    rng.gen_range(1, 5),
+                ),
+            )
+        };
+        sdk.client
+            .sign_send_instructions(vec![instruction], vec![&maker.user])
+            .await
+            .unwrap();
+        assert_header_counters(&sdk.client, market).await;
+    }
+
+    // Crossing orders remove resting orders from the book
+    let PhoenixTestContext { default_taker, .. } = &ctx;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(120.0),
+                    20,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    assert_header_counters(&sdk.client, market).await;
+}
+
 #[tokio::test]
 async fn test_phoenix_orders() {
     let (phoenix_client, ctx) = bootstrap_default(0).await;
@@ -1211,7 +1379,8 @@ async fn test_phoenix_admin() {
                         side: Side::Bid,
                         tick_limit: None,
                         num_orders_to_cancel: Some(1),
-                        num_orders_to_search: None
+                        num_orders_to_search: None,
+                        lots_to_free: None
                     },
                 )],
                 vec![&default_maker.user],
@@ -1310,7 +1479,8 @@ async fn test_phoenix_admin() {
                             side: Side::Bid,
                             tick_limit: None,
                             num_orders_to_cancel: None,
-                            num_orders_to_search: None
+                            num_orders_to_search: None,
+                            lots_to_free: None
                         },
                     ),
                     create_cancel_up_to_instruction(
@@ -1322,7 +1492,8 @@ async fn test_phoenix_admin() {
                             side: Side::Ask,
                             tick_limit: None,
                             num_orders_to_cancel: None,
-                            num_orders_to_search: None
+                            num_orders_to_search: None,
+                            lots_to_free: None
                         },
                     ),
                 ],
@@ -1345,6 +1516,7 @@ async fn test_phoenix_admin() {
                         &default_maker.user.pubkey(),
                         base_mint,
                         quote_mint,
+                        &spl_token::id(),
                     )
                 ],
                 vec![&successor],
@@ -1379,6 +1551,7 @@ async fn test_phoenix_admin() {
                         &default_maker.user.pubkey(),
                         base_mint,
                         quote_mint,
+                        &spl_token::id(),
                     )
                 ],
                 vec![&successor],
@@ -1430,6 +1603,263 @@ async fn test_phoenix_admin() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_phoenix_trading_window() {
+    let (phoenix_client, phoenix_ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient {
+        mut ctx,
+        sdk,
+        meta,
+        market,
+    } = phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &phoenix_ctx;
+    let trader = default_maker.user.pubkey();
+    let admin = sdk.client.payer.pubkey();
+    let new_order = |price: f64| {
+        create_new_order_instruction(
+            &market,
+            &trader,
+            &meta.base_mint,
+            &meta.quote_mint,
+            &OrderPacket::new_limit_order_default(
+                Side::Bid,
+                meta.float_price_to_ticks_rounded_down(price),
+                1,
+            ),
+        )
+    };
+
+    sdk.client
+        .sign_send_instructions(vec![new_order(100.0)], vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    // The window must end after it starts
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_set_trading_window_instruction(
+                &admin, &market, 200, 100
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_set_trading_window_instruction(
+                &admin, &market, 0, 200,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // New orders and reprices are rejected during the window, but cancels are still allowed
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order(99.0)], vec![&default_maker.user])
+        .await
+        .is_err());
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    let (bid_id, _) = market_state.orderbook.bids.iter().next().unwrap();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_reprice_order_instruction(
+                &market,
+                &trader,
+                &RepriceOrderParams {
+                    base_params: CancelOrderParams {
+                        side: Side::Bid,
+                        price_in_ticks: bid_id.price_in_ticks.into(),
+                        order_sequence_number: bid_id.order_sequence_number,
+                    },
+                    new_price_in_ticks: meta.float_price_to_ticks_rounded_down(99.0),
+                    client_order_id: 0,
+                },
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .is_err());
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_all_orders_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    assert!(market_state.orderbook.bids.is_empty());
+
+    // Trading resumes once the window has passed
+    ctx.warp_to_slot(200).unwrap();
+    sdk.client
+        .sign_send_instructions(vec![new_order(98.0)], vec![&default_maker.user])
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+}
+
+#[tokio::test]
+async fn test_phoenix_change_market_symbols() {
+    let (phoenix_client, phoenix_ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient { sdk, market, .. } = &phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &phoenix_ctx;
+    let admin = sdk.client.payer.pubkey();
+    let get_header = || async move {
+        let data = sdk.client.get_account_data(market).await.unwrap();
+        *MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()]).unwrap()
+    };
+
+    // Markets initialized without metadata have no symbols
+    let header = get_header().await;
+    assert_eq!(header.get_base_symbol(), None);
+    assert_eq!(header.get_quote_symbol(), None);
+
+    let base_symbol = encode_market_symbol("SOL").unwrap();
+    let quote_symbol = encode_market_symbol("USDC").unwrap();
+    // Only the market authority can change the symbols
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_change_market_symbols_instruction(
+                &default_maker.user.pubkey(),
+                market,
+                base_symbol,
+                quote_symbol,
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .is_err());
+    // Symbols must be zero-padded UTF-8
+    let mut invalid_symbol = base_symbol;
+    invalid_symbol[MARKET_SYMBOL_LEN - 1] = b'X';
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_change_market_symbols_instruction(
+                &admin,
+                market,
+                invalid_symbol,
+                quote_symbol,
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_market_symbols_instruction(
+                &admin,
+                market,
+                base_symbol,
+                quote_symbol,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let header = get_header().await;
+    assert_eq!(header.get_base_symbol(), Some("SOL"));
+    assert_eq!(header.get_quote_symbol(), Some("USDC"));
+    assert_eq!(header.base_display_decimals, 0);
+    assert_eq!(header.quote_display_decimals, 0);
+}
+
+#[tokio::test]
+async fn test_phoenix_book_checkpoints() {
+    let (
+        mut phoenix_test_client,
+        PhoenixTestContext {
+            admin,
+            default_maker,
+            ..
+        },
+    ) = bootstrap_default(5).await;
+
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut phoenix_test_client;
+
+    let get_book_checkpoints_enabled = |data: Vec<u8>| {
+        let header = MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()]).unwrap();
+        header.book_checkpoints_enabled
+    };
+
+    assert!(
+        sdk.client
+            .sign_send_instructions(
+                vec![create_change_book_checkpoints_instruction(
+                    &default_maker.user.pubkey(),
+                    market,
+                    true
+                )],
+                vec![&default_maker.user],
+            )
+            .await
+            .is_err(),
+        "Only the market authority can enable book checkpoints"
+    );
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_book_checkpoints_instruction(
+                &admin.pubkey(),
+                market,
+                true,
+            )],
+            vec![&admin],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    assert_eq!(get_book_checkpoints_enabled(market_data), 1);
+
+    // Instructions that modify the book succeed with the checkpoint appended to the event stream
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(100.0),
+                    1,
+                ),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_book_checkpoints_instruction(
+                &admin.pubkey(),
+                market,
+                false,
+            )],
+            vec![&admin],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    assert_eq!(get_book_checkpoints_enabled(market_data), 0);
+}
+
 #[tokio::test]
 async fn test_phoenix_basic() {
     let (mut client, ctx) = bootstrap_default(0).await;
@@ -1529,6 +1959,7 @@ async fn test_phoenix_basic() {
         tick_limit: None,
         num_orders_to_search: None,
         num_orders_to_cancel: None,
+        lots_to_free: None,
     };
 
     let cancel_multiple_ix = create_cancel_up_to_instruction(
@@ -1931,6 +2362,7 @@ async fn test_phoenix_cancel_with_free_funds() {
                         tick_limit: None,
                         num_orders_to_cancel: None,
                         num_orders_to_search: None,
+                        lots_to_free: None,
                     },
                 ),
             ],
@@ -1946,350 +2378,3501 @@ async fn test_phoenix_cancel_with_free_funds() {
 }
 
 #[tokio::test]
-async fn test_phoenix_orders_with_free_funds() {
-    let (mut client, ctx) = bootstrap_default(0).await;
-    let PhoenixTestContext {
-        default_maker,
-        default_taker,
-        ..
-    } = &ctx;
-    let second_maker = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+async fn test_phoenix_force_cancel_with_free_funds() {
+    let (client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
     let PhoenixTestClient {
         sdk, market, meta, ..
-    } = &mut client;
-
-    let quote_mint = &meta.quote_mint;
-    let base_mint = &meta.base_mint;
+    } = &client;
+    let trader = default_maker.user.pubkey();
 
-    sdk.set_payer(clone_keypair(&default_maker.user));
-
-    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-
-    layer_orders(
-        meta,
-        market,
-        &sdk,
-        meta.float_price_to_ticks_rounded_down(40.0),
-        meta.float_price_to_ticks_rounded_down(30.0),
-        meta.float_price_to_ticks_rounded_down(1.0),
-        meta.raw_base_units_to_base_lots_rounded_down(1.0),
-        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+    let bid = OrderPacket::new_limit_order_default(
         Side::Bid,
-    )
-    .await;
-
-    layer_orders(
-        meta,
-        market,
-        &sdk,
-        meta.float_price_to_ticks_rounded_down(50.0),
-        meta.float_price_to_ticks_rounded_down(60.0),
-        meta.float_price_to_ticks_rounded_down(1.0),
-        meta.raw_base_units_to_base_lots_rounded_down(1.0),
-        meta.raw_base_units_to_base_lots_rounded_down(1.0),
-        Side::Ask,
-    )
-    .await;
-    sdk.set_payer(clone_keypair(&default_taker.user));
-
-    //Attempt to use free funds to trade, will reject because the taker has no seat
-    let sell_params = OrderPacket::new_ioc_by_lots(
+        meta.float_price_to_ticks_rounded_down(100.0),
+        10,
+    );
+    let ask = OrderPacket::new_limit_order_default(
         Side::Ask,
-        meta.float_price_to_ticks_rounded_down(31.0),
-        meta.raw_base_units_to_base_lots_rounded_down(55.0),
-        SelfTradeBehavior::Abort,
-        None,
-        0,
-        true,
+        meta.float_price_to_ticks_rounded_down(101.0),
+        10,
     );
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_new_order_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &bid,
+                ),
+                create_new_order_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &ask,
+                ),
+            ],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
 
-    let new_order_ix = create_new_order_with_free_funds_instruction(
-        market,
-        &default_taker.user.pubkey(),
-        &sell_params,
-    );
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(!market_state.orderbook.bids.is_empty());
+    assert!(!market_state.orderbook.asks.is_empty());
+    assert_eq!(market_state.traders[&trader].base_lots_free, 0);
+    assert_eq!(market_state.traders[&trader].quote_lots_free, 0);
+    let base_balance = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance = get_token_balance(&sdk.client, default_maker.quote_ata).await;
 
+    // Only the market authority can force cancel orders
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_taker.user])
+        .sign_send_instructions(
+            create_force_cancel_orders_with_free_funds_instructions(market, &trader, &trader),
+            vec![&default_maker.user],
+        )
         .await
         .is_err());
 
-    //Trade through the first 10 levels of the book and self trade the last level on each side
-    let sell_params = OrderPacket::new_ioc_by_lots(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(31.0),
-        meta.raw_base_units_to_base_lots_rounded_down(55.0),
-        SelfTradeBehavior::Abort,
-        None,
-        0,
-        false,
-    );
+    sdk.client
+        .sign_send_instructions(
+            create_force_cancel_orders_with_free_funds_instructions(
+                market,
+                &trader,
+                &sdk.client.payer.pubkey(),
+            ),
+            vec![],
+        )
+        .await
+        .unwrap();
 
-    let buy_params = OrderPacket::new_ioc_by_lots(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(59.0),
-        meta.raw_base_units_to_base_lots_rounded_down(55.0),
-        SelfTradeBehavior::Abort,
-        None,
-        0,
-        false,
+    // The orders are removed and the funds are credited to the trader state, no tokens move
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(market_state.orderbook.bids.is_empty());
+    assert!(market_state.orderbook.asks.is_empty());
+    assert_eq!(market_state.traders[&trader].base_lots_free, 10);
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        meta.float_price_to_ticks_rounded_down(100.0)
+            * 10
+            * meta.tick_size_in_quote_atoms_per_base_unit
+            / (meta.num_base_lots_per_base_unit * meta.quote_atoms_per_quote_lot)
     );
-
-    let self_trade_bid_params = OrderPacket::new_ioc_by_lots(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(30.0),
-        meta.raw_base_units_to_base_lots_rounded_down(11.0),
-        SelfTradeBehavior::DecrementTake,
-        None,
-        0,
-        false,
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        base_balance
     );
-
-    let self_trade_offer_params = OrderPacket::new_ioc_by_lots(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(60.0),
-        meta.raw_base_units_to_base_lots_rounded_down(11.0),
-        SelfTradeBehavior::DecrementTake,
-        None,
-        0,
-        false,
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_balance
     );
+}
 
-    let taker_params = vec![sell_params, buy_params];
-    let maker_params = vec![self_trade_bid_params, self_trade_offer_params];
+#[tokio::test]
+async fn test_phoenix_cancel_by_client_order_id() {
+    let (mut client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_client_order_id_index: true,
+        ..Default::default()
+    })
+    .await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
 
-    for param in taker_params {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &default_taker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &param,
-        );
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&default_taker.user])
-            .await
-            .unwrap();
-    }
+    let header_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&header_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.client_order_id_index_enabled, 1);
 
-    for param in maker_params {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &default_maker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &param,
-        );
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
-            .await
-            .unwrap();
+    let mut instructions = vec![create_deposit_funds_instruction(
+        &market,
+        &trader,
+        &meta.base_mint,
+        &meta.quote_mint,
+        &DepositParams {
+            quote_lots_to_deposit: meta.quote_units_to_quote_lots(10000.0),
+            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(100.0),
+        },
+    )];
+    for (side, price, client_order_id) in [
+        (Side::Bid, 98, 1),
+        (Side::Bid, 99, 2),
+        (Side::Ask, 101, 1),
+        (Side::Ask, 102, 3),
+    ] {
+        instructions.push(create_new_order_with_free_funds_instruction(
+            &market,
+            &trader,
+            &OrderPacket::new_post_only(side, price, 10, client_order_id, true, true),
+        ));
     }
+    sdk.client
+        .sign_send_instructions(instructions, vec![])
+        .await
+        .unwrap();
 
-    let base_balance_new = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_new = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    println!("Base balance start: {}", base_balance_start);
-    println!("Quote balance start: {}", quote_balance_start);
-    println!("Base balance new: {}", base_balance_new);
-    println!("Quote balance new: {}", quote_balance_new);
-    assert_eq!(quote_balance_start - quote_balance_new, 2200000000);
-    assert_eq!(base_balance_start - base_balance_new, 66000000000);
+    // All of the trader's orders with the client order id are cancelled
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_cancel_by_client_order_id_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &CancelByClientOrderIdParams { client_order_id: 1 },
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let mut prices = [&market_state.orderbook.bids, &market_state.orderbook.asks]
+        .iter()
+        .flat_map(|ob| ob.iter().map(|(k, _)| k.price_in_ticks.into()))
+        .collect::<Vec<u64>>();
+    prices.sort();
+    assert_eq!(prices, vec![99, 102]);
 
-    //Attempt to send a LimitOrderWithFreeFunds with the second maker that will fail due to insufficient funds
-    sdk.client.payer = clone_keypair(&second_maker.user);
-    let new_order_ix = create_new_order_with_free_funds_instruction(
-        market,
-        &second_maker.user.pubkey(),
-        &OrderPacket::new_post_only_default(
-            Side::Bid,
-            meta.float_price_to_ticks_rounded_down(100.0),
-            meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        ),
-    );
+    // Cancelling an unknown client order id is a no-op
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_by_client_order_id_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &CancelByClientOrderIdParams { client_order_id: 4 },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+
+    // Markets without the index reject the instruction
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient { sdk, market, .. } = &mut client;
+    let trader = ctx.default_maker.user.pubkey();
+    sdk.client.set_payer(&trader).unwrap();
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+        .sign_send_instructions(
+            vec![
+                create_cancel_by_client_order_id_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &CancelByClientOrderIdParams { client_order_id: 1 },
+                )
+            ],
+            vec![],
+        )
         .await
         .is_err());
+}
 
-    //Add limit orders using the second maker, then use only free lots from the original maker to buy/sell via IOC
-    let limit_buy_params = OrderPacket::new_limit_order_default(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(30.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-    );
-
-    let limit_sell_params = OrderPacket::new_limit_order_default(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(34.1),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-    );
+#[tokio::test]
+async fn test_phoenix_stop_orders() {
+    let (client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_stop_orders: true,
+        ..Default::default()
+    })
+    .await;
+    let stop_trader = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+    let trader = stop_trader.user.pubkey();
 
-    let ioc_buy_params = OrderPacket::new_ioc_by_lots(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(34.1),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        true,
-    );
+    let header_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&header_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.stop_orders_enabled, 1);
+    let stop_order_book_offset = size_of::<MarketHeader>()
+        + get_stop_order_book_offset(&header.market_size_params, false).unwrap();
 
-    let ioc_sell_params = OrderPacket::new_ioc_by_lots(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(30.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        true,
+    let quote_lots_deposited = meta.quote_units_to_quote_lots(10000.0);
+    let trigger_price = meta.float_price_to_ticks_rounded_down(101.0);
+    let limit_price = meta.float_price_to_ticks_rounded_down(105.0);
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit: quote_lots_deposited,
+                        base_lots_to_deposit: 0,
+                    },
+                ),
+                create_place_stop_order_instruction(
+                    market,
+                    &trader,
+                    &PlaceStopOrderParams {
+                        side: Side::Bid,
+                        trigger_price_in_ticks: trigger_price,
+                        limit_price_in_ticks: limit_price,
+                        num_base_lots: 10,
+                        client_order_id: 7,
+                    },
+                ),
+            ],
+            vec![&stop_trader.user],
+        )
+        .await
+        .unwrap();
+
+    // The quote lots needed to buy the full size at the limit price are locked
+    let quote_lots_for_10_base_lots = |price_in_ticks: u64| {
+        price_in_ticks * 10 * meta.tick_size_in_quote_atoms_per_base_unit
+            / (meta.num_base_lots_per_base_unit * meta.quote_atoms_per_quote_lot)
+    };
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_deposited - quote_lots_for_10_base_lots(limit_price)
     );
-    let second_maker_params = vec![limit_buy_params, limit_sell_params];
-    let maker_ioc_params = vec![ioc_buy_params, ioc_sell_params];
-    for param in second_maker_params {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &second_maker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &param,
-        );
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let stop_order_book = load_stop_order_book(&market_data[stop_order_book_offset..]).unwrap();
+    assert_eq!(stop_order_book.len(), 1);
 
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
-            .await
-            .unwrap();
-    }
-    sdk.set_payer(clone_keypair(&default_maker.user));
-    for param in maker_ioc_params {
-        let new_order_ix = create_new_order_with_free_funds_instruction(
+    // A fill at the trigger price sends the stop order as an immediate-or-cancel order
+    let mut instructions = vec![];
+    for (price, size) in [(101.0, 5), (103.0, 20)] {
+        instructions.push(create_new_order_instruction(
             market,
             &default_maker.user.pubkey(),
-            &param,
-        );
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
-            .await
-            .unwrap();
+            &meta.base_mint,
+            &meta.quote_mint,
+            &OrderPacket::new_limit_order_default(
+                Side::Ask,
+                meta.float_price_to_ticks_rounded_down(price),
+                size,
+            ),
+        ));
     }
+    sdk.client
+        .sign_send_instructions(instructions, vec![&default_maker.user])
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    trigger_price,
+                    5,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
 
-    let base_balance_after_ioc = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_after_ioc = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    // No deposits/withdraws, keep same amount of base lots free, lose 41000000 quote lots free
-    assert_eq!(quote_balance_after_ioc - quote_balance_new, 0);
-    assert_eq!(base_balance_after_ioc - base_balance_new, 0);
-
-    //Place a new buy and sell order using all remaining free lots + 1 extra unit
-    let limit_buy_params = OrderPacket::new_limit_order_default(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(33.69),
-        meta.raw_base_units_to_base_lots_rounded_down(101.0),
-    );
-
-    let limit_sell_params = OrderPacket::new_limit_order_default(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(50.0),
-        meta.raw_base_units_to_base_lots_rounded_down(67.0),
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].base_lots_free, 10);
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_deposited
+            - quote_lots_for_10_base_lots(meta.float_price_to_ticks_rounded_down(103.0))
     );
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let stop_order_book = load_stop_order_book(&market_data[stop_order_book_offset..]).unwrap();
+    assert!(stop_order_book.is_empty());
 
-    let maker_params = vec![limit_buy_params, limit_sell_params];
-
-    for param in maker_params {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &default_maker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &param,
-        );
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
-            .await
-            .unwrap();
-    }
-
-    //Check we only used 1 unit worth of new deposits
-    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    assert_eq!(quote_balance_after_ioc - quote_balance_end, 33690000);
-    assert_eq!(base_balance_after_ioc - base_balance_end, 1000000000);
+    // Cancelling a stop order unlocks its funds
+    sdk.client
+        .sign_send_instructions(
+            vec![create_place_stop_order_instruction(
+                market,
+                &trader,
+                &PlaceStopOrderParams {
+                    side: Side::Ask,
+                    trigger_price_in_ticks: meta.float_price_to_ticks_rounded_down(90.0),
+                    limit_price_in_ticks: meta.float_price_to_ticks_rounded_down(85.0),
+                    num_base_lots: 10,
+                    client_order_id: 8,
+                },
+            )],
+            vec![&stop_trader.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].base_lots_free, 0);
 
-    //Attempt to send a SwapWithFreeFunds with the second maker that will fail due to insufficient funds
-    sdk.client.payer = clone_keypair(&second_maker.user);
-    let second_maker_base_balance_start =
-        get_token_balance(&sdk.client, second_maker.base_ata).await;
-    let second_maker_quote_balance_start =
-        get_token_balance(&sdk.client, second_maker.quote_ata).await;
-    let new_order_ix = create_new_order_with_free_funds_instruction(
-        market,
-        &second_maker.user.pubkey(),
-        &OrderPacket::new_ioc_by_lots(
-            Side::Bid,
-            meta.float_price_to_ticks_rounded_down(250.0),
-            meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            SelfTradeBehavior::CancelProvide,
-            None,
-            0,
-            true,
-        ),
-    );
+    // Only the trader that placed the stop order can cancel it
+    let cancel_params = CancelStopOrderParams {
+        side: Side::Ask,
+        trigger_price_in_ticks: meta.float_price_to_ticks_rounded_down(90.0),
+        stop_sequence_number: 1,
+    };
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+        .sign_send_instructions(
+            vec![create_cancel_stop_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                &cancel_params,
+            )],
+            vec![&default_maker.user],
+        )
         .await
         .is_err());
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_stop_order_instruction(
+                market,
+                &trader,
+                &cancel_params,
+            )],
+            vec![&stop_trader.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].base_lots_free, 10);
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let stop_order_book = load_stop_order_book(&market_data[stop_order_book_offset..]).unwrap();
+    assert!(stop_order_book.is_empty());
+}
 
-    //Add limit orders using the second maker using only free funds
-    let limit_buy_params = OrderPacket::new_limit_order(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(34.1),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        true,
-    );
+#[tokio::test]
+async fn test_phoenix_stop_order_trigger_failures() {
+    let (client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_stop_orders: true,
+        ..Default::default()
+    })
+    .await;
+    let stop_trader = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        mut ctx,
+        sdk,
+        market,
+        meta,
+    } = client;
+    let market = &market;
+    let trader = stop_trader.user.pubkey();
+    let admin = sdk.client.payer.pubkey();
+    ctx.warp_to_slot(2).unwrap();
 
-    let limit_sell_params = OrderPacket::new_limit_order(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(35.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        true,
-    );
+    let header_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&header_data[..size_of::<MarketHeader>()]).unwrap();
+    let stop_order_book_offset = size_of::<MarketHeader>()
+        + get_stop_order_book_offset(&header.market_size_params, false).unwrap();
 
-    for params in [limit_buy_params, limit_sell_params] {
-        let new_order_ix = create_new_order_with_free_funds_instruction(
+    let quote_lots_deposited = meta.quote_units_to_quote_lots(100000.0);
+    let trigger_price = meta.float_price_to_ticks_rounded_down(100.0);
+    let place_stop = |client_order_id: u128| {
+        create_place_stop_order_instruction(
             market,
-            &second_maker.user.pubkey(),
-            &params,
-        );
+            &trader,
+            &PlaceStopOrderParams {
+                side: Side::Bid,
+                trigger_price_in_ticks: trigger_price,
+                limit_price_in_ticks: meta.float_price_to_ticks_rounded_down(120.0),
+                num_base_lots: 10,
+                client_order_id,
+            },
+        )
+    };
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_funds_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositParams {
+                    quote_lots_to_deposit: quote_lots_deposited,
+                    base_lots_to_deposit: 0,
+                },
+            )],
+            vec![&stop_trader.user],
+        )
+        .await
+        .unwrap();
+
+    // Each trader can only have a limited number of stop orders
+    for client_order_id in 0..MAX_STOP_ORDERS_PER_TRADER as u128 {
         sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .sign_send_instructions(vec![place_stop(client_order_id)], vec![&stop_trader.user])
             .await
             .unwrap();
     }
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![place_stop(MAX_STOP_ORDERS_PER_TRADER as u128)],
+            vec![&stop_trader.user]
+        )
+        .await
+        .is_err());
 
-    //Check that the second maker has used only free funds
-    let second_maker_base_balance_new = get_token_balance(&sdk.client, second_maker.base_ata).await;
-    let second_maker_quote_balance_new =
-        get_token_balance(&sdk.client, second_maker.quote_ata).await;
-    assert_eq!(
-        second_maker_base_balance_new - second_maker_base_balance_start,
-        0
-    );
-    assert_eq!(
-        second_maker_quote_balance_new - second_maker_quote_balance_start,
-        0
-    );
+    // During the cooldown of the volatility band, the triggered stop orders are priced outside of
+    // the band and cannot be placed
+    sdk.client
+        .sign_send_instructions(
+            vec![create_set_volatility_band_instruction(
+                &admin,
+                market,
+                &SetVolatilityBandParams {
+                    fill_threshold_base_lots: 1,
+                    cooldown_slots: 1000,
+                    band_bps: 100,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_limit_order_default(Side::Ask, trigger_price, 5),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
 
-    //Check that internal free funds are now zero, so a new order uses new deposits
-    let limit_buy_params = OrderPacket::new_limit_order(
-        Side::Bid,
+    // The fill that triggers the stop orders still succeeds. The stop orders are dropped and their
+    // funds are returned to the trader.
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    trigger_price,
+                    5,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let stop_order_book = load_stop_order_book(&market_data[stop_order_book_offset..]).unwrap();
+    assert!(stop_order_book.is_empty());
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_deposited
+    );
+    assert_eq!(market_state.traders[&trader].base_lots_free, 0);
+}
+
+#[tokio::test]
+async fn test_phoenix_referral_fees() {
+    let (client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        fee_bps: 10,
+        enable_referral_fees: true,
+        ..Default::default()
+    })
+    .await;
+    let referrer = get_new_maker(&client, &ctx, 0, 0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+
+    let header_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&header_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.referral_fees_enabled, 1);
+    let referral_fee_book_offset = size_of::<MarketHeader>()
+        + get_referral_fee_book_offset(&header.market_size_params, false, false).unwrap();
+
+    // Half of each taker fee is accrued to the referrer
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_referral_fee_instruction(
+                &sdk.client.payer.pubkey(),
+                market,
+                5000,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_change_referral_fee_instruction(
+                &sdk.client.payer.pubkey(),
+                market,
+                10001,
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    let price_in_ticks = meta.float_price_to_ticks_rounded_down(100.0);
+    let make_and_take = || {
+        let mut order_packet = OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            price_in_ticks,
+            10,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        );
+        order_packet.set_referrer(Some(referrer.user.pubkey()));
+        async move {
+            sdk.client
+                .sign_send_instructions(
+                    vec![create_new_order_instruction(
+                        market,
+                        &default_maker.user.pubkey(),
+                        &meta.base_mint,
+                        &meta.quote_mint,
+                        &OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, 10),
+                    )],
+                    vec![&default_maker.user],
+                )
+                .await
+                .unwrap();
+            sdk.client
+                .sign_send_instructions(
+                    vec![create_new_order_instruction(
+                        market,
+                        &default_taker.user.pubkey(),
+                        &meta.base_mint,
+                        &meta.quote_mint,
+                        &order_packet,
+                    )],
+                    vec![&default_taker.user],
+                )
+                .await
+                .unwrap();
+        }
+    };
+    let get_uncollected_fees = || async move {
+        let market_data = sdk.client.get_account_data(market).await.unwrap();
+        let (header_bytes, bytes) = market_data.split_at(size_of::<MarketHeader>());
+        let header = MarketHeader::load_bytes(header_bytes).unwrap();
+        load_with_dispatch(&header.market_size_params, bytes)
+            .unwrap()
+            .inner
+            .get_uncollected_fee_amount()
+    };
+
+    // A referrer that is not registered does not accrue any fees
+    make_and_take().await;
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let referral_fee_book =
+        load_referral_fee_book(&market_data[referral_fee_book_offset..]).unwrap();
+    assert!(referral_fee_book.is_empty());
+    // 10 base lots at 100 is 100_000 quote lots, so the 10 bps fee is 100 quote lots
+    assert_eq!(get_uncollected_fees().await, QuoteLots::new(100));
+
+    // Only the market authority can register referrers
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_register_referrer_instruction(
+                &referrer.user.pubkey(),
+                market,
+                &referrer.user.pubkey(),
+            )],
+            vec![&referrer.user],
+        )
+        .await
+        .is_err());
+    sdk.client
+        .sign_send_instructions(
+            vec![create_register_referrer_instruction(
+                &sdk.client.payer.pubkey(),
+                market,
+                &referrer.user.pubkey(),
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    make_and_take().await;
+    assert_eq!(get_uncollected_fees().await, QuoteLots::new(150));
+
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let referral_fee_book =
+        load_referral_fee_book(&market_data[referral_fee_book_offset..]).unwrap();
+    assert_eq!(
+        referral_fee_book.get_unclaimed(&referrer.user.pubkey()),
+        QuoteLots::new(50)
+    );
+
+    let referrer_balance_start = get_token_balance(&sdk.client, referrer.quote_ata).await;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_claim_referral_fees_instruction(
+                market,
+                &referrer.user.pubkey(),
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![&referrer.user],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&sdk.client, referrer.quote_ata).await - referrer_balance_start,
+        50 * meta.quote_atoms_per_quote_lot
+    );
+    // The referrer stays registered after claiming
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let referral_fee_book =
+        load_referral_fee_book(&market_data[referral_fee_book_offset..]).unwrap();
+    assert_eq!(referral_fee_book.len(), 1);
+    assert_eq!(
+        referral_fee_book.get_unclaimed(&referrer.user.pubkey()),
+        QuoteLots::ZERO
+    );
+
+    // Deregistering a referrer forfeits its unclaimed fees to the market
+    make_and_take().await;
+    assert_eq!(get_uncollected_fees().await, QuoteLots::new(200));
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deregister_referrer_instruction(
+                &sdk.client.payer.pubkey(),
+                market,
+                &referrer.user.pubkey(),
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert_eq!(get_uncollected_fees().await, QuoteLots::new(250));
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let referral_fee_book =
+        load_referral_fee_book(&market_data[referral_fee_book_offset..]).unwrap();
+    assert!(referral_fee_book.is_empty());
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_deregister_referrer_instruction(
+                &sdk.client.payer.pubkey(),
+                market,
+                &referrer.user.pubkey(),
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_phoenix_recent_fills() {
+    let (client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_stop_orders: true,
+        enable_referral_fees: true,
+        enable_recent_fills: true,
+        ..Default::default()
+    })
+    .await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+
+    // The recent fills are stored after the stop order book and the referral fee book
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.recent_fills_enabled, 1);
+    let recent_fills_offset = size_of::<MarketHeader>()
+        + get_recent_fills_offset_from_header(header)
+            .unwrap()
+            .unwrap();
+    assert_eq!(
+        recent_fills_offset + get_recent_fills_size(),
+        market_data.len()
+    );
+    assert!(load_recent_fills(&market_data[recent_fills_offset..])
+        .unwrap()
+        .get_recent_fills()
+        .is_empty());
+
+    let price_in_ticks = meta.float_price_to_ticks_rounded_down(100.0);
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_new_order_instruction(
+                    market,
+                    &default_maker.user.pubkey(),
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, 10),
+                ),
+                create_new_order_instruction(
+                    market,
+                    &default_maker.user.pubkey(),
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks + 1, 10),
+                ),
+            ],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+
+    let take = |num_base_lots| {
+        create_new_order_instruction(
+            market,
+            &default_taker.user.pubkey(),
+            &meta.base_mint,
+            &meta.quote_mint,
+            &OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                price_in_ticks + 1,
+                num_base_lots,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                false,
+            ),
+        )
+    };
+    sdk.client
+        .sign_send_instructions(vec![take(12)], vec![&default_taker.user])
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(vec![take(3)], vec![&default_taker.user])
+        .await
+        .unwrap();
+
+    // Fills are stored oldest first and stamped with the sequence number of their instruction
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+    let recent_fills = load_recent_fills(&market_data[recent_fills_offset..])
+        .unwrap()
+        .get_recent_fills()
+        .iter()
+        .map(|fill| {
+            (
+                fill.market_sequence_number,
+                fill.price_in_ticks.as_u64(),
+                fill.base_lots_filled.as_u64(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let sequence_number = header.market_sequence_number;
+    assert_eq!(
+        recent_fills,
+        vec![
+            (sequence_number - 2, price_in_ticks, 10),
+            (sequence_number - 2, price_in_ticks + 1, 2),
+            (sequence_number - 1, price_in_ticks + 1, 3),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_iceberg_orders() {
+    let (client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_iceberg_orders: true,
+        ..Default::default()
+    })
+    .await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+
+    // The iceberg reserve book is the last region of the market account
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.iceberg_orders_enabled, 1);
+    let iceberg_reserve_book_offset = size_of::<MarketHeader>()
+        + get_iceberg_reserve_book_offset_from_header(header)
+            .unwrap()
+            .unwrap();
+    assert_eq!(
+        iceberg_reserve_book_offset + get_iceberg_reserve_book_size(),
+        market_data.len()
+    );
+
+    let price_in_ticks = meta.float_price_to_ticks_rounded_down(100.0);
+    let client_order_id = 7;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_iceberg_order(
+                    Side::Ask,
+                    price_in_ticks,
+                    25,
+                    10,
+                    SelfTradeBehavior::Abort,
+                    client_order_id,
+                    false,
+                ),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let iceberg_reserve_book =
+        load_iceberg_reserve_book(&market_data[iceberg_reserve_book_offset..]).unwrap();
+    let (_, iceberg_reserve) = iceberg_reserve_book.reserves.iter().next().unwrap();
+    assert_eq!(iceberg_reserve.reserve_base_lots, BaseLots::new(15));
+    assert_eq!(iceberg_reserve.client_order_id(), client_order_id);
+
+    // The taker fills the visible size and part of the replenished order
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    price_in_ticks,
+                    12,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    let iceberg_reserve_book =
+        load_iceberg_reserve_book(&market_data[iceberg_reserve_book_offset..]).unwrap();
+    assert_eq!(iceberg_reserve_book.len(), 1);
+    let (order_id, iceberg_reserve) = iceberg_reserve_book.reserves.iter().next().unwrap();
+    assert_eq!(iceberg_reserve.reserve_base_lots, BaseLots::new(5));
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let ask = market_state
+        .orderbook
+        .asks
+        .iter()
+        .map(|(order_id, order)| (order_id.order_sequence_number, order.num_base_lots))
+        .collect::<Vec<_>>();
+    assert_eq!(ask, vec![(order_id.order_sequence_number, 8)]);
+
+    // Cancelling the visible order releases the reserve
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_all_order_with_free_funds_instruction(
+                market,
+                &default_maker.user.pubkey(),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+    assert!(
+        load_iceberg_reserve_book(&market_data[iceberg_reserve_book_offset..])
+            .unwrap()
+            .is_empty()
+    );
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let maker_state = &market_state.traders[&default_maker.user.pubkey()];
+    assert_eq!(maker_state.base_lots_locked, 0);
+    assert_eq!(maker_state.base_lots_free, 15);
+}
+
+#[tokio::test]
+async fn test_phoenix_initialize_market_pda() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient { sdk, meta, .. } = &mut client;
+    let admin = sdk.client.payer.pubkey();
+
+    let initialize_params = InitializeParams {
+        market_size_params: MarketSizeParams {
+            bids_size: 512,
+            asks_size: 512,
+            num_seats: 128,
+        },
+        num_quote_lots_per_quote_unit: 100_000,
+        num_base_lots_per_base_unit: 1_000,
+        tick_size_in_quote_lots_per_base_unit: 1_000,
+        taker_fee_bps: 0,
+        fee_collector: admin,
+        enable_recent_fills: Some(true),
+        ..Default::default()
+    };
+    let market_account_size = initialize_params.get_market_account_size().unwrap();
+    let (market, _) = get_market_address(&meta.base_mint, &meta.quote_mint, 0);
+    let instructions = create_initialize_market_pda_instructions(
+        &meta.base_mint,
+        &meta.quote_mint,
+        &spl_token::id(),
+        &admin,
+        0,
+        initialize_params,
+    )
+    .unwrap();
+    // The market account is larger than a single allocation, so it takes several instructions
+    assert!(market_account_size > MAX_PERMITTED_DATA_INCREASE);
+    assert_eq!(
+        instructions.len(),
+        get_num_initialize_market_pda_instructions(market_account_size)
+    );
+
+    for (i, ix) in instructions.into_iter().enumerate() {
+        // Vary the compute budget so that the transactions are not duplicates of each other
+        let compute_ix = ComputeBudgetInstruction::set_compute_unit_limit(400_000 + i as u32);
+        if i == 1 {
+            // Only the creator of the market account can continue allocating it
+            let mut attacker_ix = ix.clone();
+            attacker_ix.accounts[3].pubkey = default_maker.user.pubkey();
+            assert!(sdk
+                .client
+                .sign_send_instructions(
+                    vec![compute_ix.clone(), attacker_ix],
+                    vec![&default_maker.user]
+                )
+                .await
+                .is_err());
+        }
+        sdk.client
+            .sign_send_instructions(vec![compute_ix, ix], vec![])
+            .await
+            .unwrap();
+        let market_data = sdk.client.get_account_data(&market).await.unwrap();
+        let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+        if market_data.len() < market_account_size {
+            assert_eq!(header.status, MarketStatus::Uninitialized as u64);
+            assert_eq!(market_data.len(), (i + 1) * MAX_PERMITTED_DATA_INCREASE);
+        } else {
+            assert_eq!(header.status, MarketStatus::PostOnly as u64);
+        }
+    }
+    let market_data = sdk.client.get_account_data(&market).await.unwrap();
+    assert_eq!(market_data.len(), market_account_size);
+
+    // The market can be activated and traded on
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_change_market_status_instruction(&admin, &market, MarketStatus::Active),
+                create_request_seat_authorized_instruction(
+                    &admin,
+                    &admin,
+                    &market,
+                    &default_maker.user.pubkey(),
+                ),
+                create_change_seat_status_instruction(
+                    &admin,
+                    &market,
+                    &default_maker.user.pubkey(),
+                    SeatApprovalStatus::Approved,
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+    sdk.add_market(&market).await.unwrap();
+    let meta = *sdk.get_market_metadata_from_cache(&market).unwrap();
+    let price_in_ticks = meta.float_price_to_ticks_rounded_down(100.0);
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                &market,
+                &default_maker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_limit_order_default(Side::Ask, price_in_ticks, 10),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                &market,
+                &default_taker.user.pubkey(),
+                &meta.base_mint,
+                &meta.quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    price_in_ticks,
+                    4,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    let asks = market_state
+        .orderbook
+        .asks
+        .values()
+        .map(|order| order.num_base_lots)
+        .collect::<Vec<_>>();
+    assert_eq!(asks, vec![6]);
+    let market_data = sdk.client.get_account_data(&market).await.unwrap();
+    let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+    let recent_fills_offset = size_of::<MarketHeader>()
+        + get_recent_fills_offset_from_header(header)
+            .unwrap()
+            .unwrap();
+    let recent_fills = load_recent_fills(&market_data[recent_fills_offset..]).unwrap();
+    assert_eq!(recent_fills.get_recent_fills().len(), 1);
+    assert_eq!(
+        recent_fills.get_recent_fills()[0].base_lots_filled,
+        BaseLots::new(4)
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_resize_market() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient { sdk, meta, .. } = &mut client;
+    let admin = sdk.client.payer.pubkey();
+    let (base_mint, quote_mint) = (meta.base_mint, meta.quote_mint);
+    let maker = default_maker.user.pubkey();
+
+    // Create a small market with a client order id index and recent fills
+    let market = Keypair::new();
+    let current_size_params = MarketSizeParams {
+        bids_size: 512,
+        asks_size: 512,
+        num_seats: 128,
+    };
+    sdk.client
+        .sign_send_instructions_with_payer(
+            create_initialize_market_instructions(
+                &market.pubkey(),
+                &base_mint,
+                &quote_mint,
+                &spl_token::id(),
+                &admin,
+                InitializeParams {
+                    market_size_params: current_size_params,
+                    num_quote_lots_per_quote_unit: 100_000,
+                    num_base_lots_per_base_unit: 1_000,
+                    tick_size_in_quote_lots_per_base_unit: 1_000,
+                    taker_fee_bps: 0,
+                    fee_collector: admin,
+                    enable_client_order_id_index: Some(true),
+                    enable_recent_fills: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+            vec![&market],
+        )
+        .await
+        .unwrap();
+    let market = market.pubkey();
+    let mut instructions = vec![create_change_market_status_instruction(
+        &admin,
+        &market,
+        MarketStatus::Active,
+    )];
+    for trader in [maker, default_taker.user.pubkey()] {
+        instructions.push(create_request_seat_authorized_instruction(
+            &admin, &admin, &market, &trader,
+        ));
+        instructions.push(create_change_seat_status_instruction(
+            &admin,
+            &market,
+            &trader,
+            SeatApprovalStatus::Approved,
+        ));
+    }
+    sdk.client
+        .sign_send_instructions(instructions, vec![])
+        .await
+        .unwrap();
+    sdk.add_market(&market).await.unwrap();
+    let meta = *sdk.get_market_metadata_from_cache(&market).unwrap();
+    let orders = (0..5)
+        .flat_map(|i| {
+            [
+                OrderPacket::new_limit_order(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(99.0 - i as f64),
+                    10 + i,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    2 * i as u128,
+                    false,
+                ),
+                OrderPacket::new_limit_order(
+                    Side::Ask,
+                    meta.float_price_to_ticks_rounded_down(101.0 + i as f64),
+                    10 + i,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    2 * i as u128 + 1,
+                    false,
+                ),
+            ]
+        })
+        .map(|order_packet| {
+            create_new_order_instruction(&market, &maker, &base_mint, &quote_mint, &order_packet)
+        })
+        .collect::<Vec<_>>();
+    sdk.client
+        .sign_send_instructions(orders, vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    // Returns the resting orders with their traders, the registered traders, and the sequence
+    // number of the market
+    let snapshot = |data: &[u8]| {
+        let (header_bytes, bytes) = data.split_at(size_of::<MarketHeader>());
+        let header = MarketHeader::load_bytes(header_bytes).unwrap();
+        let market = load_with_dispatch(&header.market_size_params, bytes)
+            .unwrap()
+            .inner;
+        let orders = [Side::Bid, Side::Ask]
+            .iter()
+            .flat_map(|side| {
+                market
+                    .get_book(*side)
+                    .iter()
+                    .map(|(order_id, order)| {
+                        (
+                            *order_id,
+                            market.get_trader_id_from_index(order.trader_index as u32),
+                            order.num_base_lots,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let traders = market
+            .get_registered_traders()
+            .iter()
+            .map(|(trader, trader_state)| (*trader, *trader_state))
+            .collect::<Vec<_>>();
+        (orders, traders, market.get_sequence_number())
+    };
+    let data = sdk.client.get_account_data(&market).await.unwrap();
+    let (orders_before, traders_before, sequence_number_before) = snapshot(&data);
+    assert_eq!(orders_before.len(), 10);
+    assert_eq!(traders_before.len(), 2);
+    let initial_len = data.len();
+
+    // The market must be paused, and only the market authority can resize it
+    let new_size_params = MarketSizeParams {
+        bids_size: 1024,
+        asks_size: 1024,
+        num_seats: 128,
+    };
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_resize_market_instruction(
+                &market,
+                &admin,
+                &admin,
+                new_size_params
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_market_status_instruction(
+                &admin,
+                &market,
+                MarketStatus::Paused,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_resize_market_instruction(
+                &market,
+                &maker,
+                &maker,
+                new_size_params
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .is_err());
+
+    // The account grows by at most `MAX_PERMITTED_DATA_INCREASE` bytes per instruction, and the
+    // market keeps its current layout until the last instruction
+    let mut num_instructions = 0;
+    loop {
+        // Vary the compute budget so that the transactions are not duplicates of each other
+        let compute_ix =
+            ComputeBudgetInstruction::set_compute_unit_limit(1_400_000 - num_instructions as u32);
+        sdk.client
+            .sign_send_instructions(
+                vec![
+                    compute_ix,
+                    create_resize_market_instruction(&market, &admin, &admin, new_size_params),
+                ],
+                vec![],
+            )
+            .await
+            .unwrap();
+        num_instructions += 1;
+        let data = sdk.client.get_account_data(&market).await.unwrap();
+        let header = MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()]).unwrap();
+        if header.market_size_params.bids_size == new_size_params.bids_size {
+            break;
+        }
+        assert_eq!(
+            data.len(),
+            initial_len + num_instructions * MAX_PERMITTED_DATA_INCREASE
+        );
+        assert_eq!(header.market_size_params.bids_size, 512);
+        assert_eq!(snapshot(&data).0, orders_before);
+    }
+    assert!(num_instructions > 1);
+
+    // The resized market has the new layout and the same orders, traders, and sequence number
+    let data = sdk.client.get_account_data(&market).await.unwrap();
+    let header = MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.market_size_params.asks_size, 1024);
+    assert_eq!(header.market_size_params.num_seats, 128);
+    assert_eq!(
+        data.len(),
+        size_of::<MarketHeader>()
+            + get_market_size(&new_size_params).unwrap()
+            + get_client_order_id_index_size(&new_size_params).unwrap()
+            + get_recent_fills_size()
+    );
+    assert_eq!(
+        snapshot(&data),
+        (orders_before, traders_before, sequence_number_before)
+    );
+
+    // The resized market can be traded on
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_market_status_instruction(
+                &admin,
+                &market,
+                MarketStatus::Active,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_new_order_instruction(
+                    &market,
+                    &default_taker.user.pubkey(),
+                    &base_mint,
+                    &quote_mint,
+                    &OrderPacket::new_ioc_by_lots(
+                        Side::Bid,
+                        meta.float_price_to_ticks_rounded_down(101.0),
+                        4,
+                        SelfTradeBehavior::Abort,
+                        None,
+                        0,
+                        false,
+                    ),
+                ),
+                // The client order id index was copied into the resized market
+                create_cancel_by_client_order_id_with_free_funds_instruction(
+                    &market,
+                    &maker,
+                    &CancelByClientOrderIdParams { client_order_id: 0 },
+                ),
+                create_new_order_instruction(
+                    &market,
+                    &maker,
+                    &base_mint,
+                    &quote_mint,
+                    &OrderPacket::new_limit_order_default(
+                        Side::Bid,
+                        meta.float_price_to_ticks_rounded_down(98.5),
+                        7,
+                    ),
+                ),
+            ],
+            vec![&default_maker.user, &default_taker.user],
+        )
+        .await
+        .unwrap();
+    let data = sdk.client.get_account_data(&market).await.unwrap();
+    let (orders, _, sequence_number) = snapshot(&data);
+    assert_eq!(orders.len(), 10);
+    assert!(sequence_number > sequence_number_before);
+    let price_in_ticks = |price: f64| Ticks::new(meta.float_price_to_ticks_rounded_down(price));
+    let best_ask = orders
+        .iter()
+        .filter(|(order_id, _, _)| order_id.price_in_ticks >= price_in_ticks(101.0))
+        .min_by_key(|(order_id, _, _)| order_id.price_in_ticks)
+        .unwrap();
+    assert_eq!(best_ask.2, BaseLots::new(6));
+    assert!(orders
+        .iter()
+        .all(|(order_id, _, _)| order_id.price_in_ticks != price_in_ticks(99.0)));
+    let header = MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()]).unwrap();
+    let recent_fills_offset = size_of::<MarketHeader>()
+        + get_recent_fills_offset_from_header(header)
+            .unwrap()
+            .unwrap();
+    let recent_fills = load_recent_fills(&data[recent_fills_offset..]).unwrap();
+    assert_eq!(recent_fills.get_recent_fills().len(), 1);
+}
+
+#[tokio::test]
+async fn test_phoenix_assert_market_price() {
+    let (client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+
+    let bid_price = meta.float_price_to_ticks_rounded_down(99.0);
+    let ask_price = meta.float_price_to_ticks_rounded_down(101.0);
+    let assert_price = |side: Side, min_price_in_ticks: u64, max_price_in_ticks: u64| {
+        create_assert_market_price_instruction(
+            market,
+            &AssertMarketPriceParams {
+                side,
+                min_price_in_ticks,
+                max_price_in_ticks,
+            },
+        )
+    };
+
+    // The assertion fails if that side of the book is empty
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![assert_price(Side::Ask, 0, u64::MAX)], vec![])
+        .await
+        .is_err());
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_new_order_instruction(
+                    market,
+                    &default_maker.user.pubkey(),
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(Side::Bid, bid_price, 10),
+                ),
+                create_new_order_instruction(
+                    market,
+                    &default_maker.user.pubkey(),
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(Side::Ask, ask_price, 10),
+                ),
+            ],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account_data(market).await.unwrap();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                assert_price(Side::Bid, bid_price, bid_price),
+                assert_price(Side::Ask, ask_price - 1, ask_price + 1),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+    // The assertion does not modify the market
+    assert_eq!(
+        sdk.client.get_account_data(market).await.unwrap(),
+        market_data
+    );
+
+    // A swap that follows a failed assertion is not executed
+    let swap = create_new_order_instruction(
+        market,
+        &default_taker.user.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            ask_price,
+            5,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        ),
+    );
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![assert_price(Side::Ask, 0, ask_price - 1), swap.clone()],
+            vec![&default_taker.user],
+        )
+        .await
+        .is_err());
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state
+            .orderbook
+            .asks
+            .iter()
+            .map(|(_, order)| order.num_base_lots)
+            .sum::<u64>(),
+        10
+    );
+
+    sdk.client
+        .sign_send_instructions(
+            vec![assert_price(Side::Ask, 0, ask_price), swap],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state
+            .orderbook
+            .asks
+            .iter()
+            .map(|(_, order)| order.num_base_lots)
+            .sum::<u64>(),
+        5
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_cancel_and_swap() {
+    let (client, ctx) = bootstrap_default(0).await;
+    let bidder = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &client;
+    let trader = default_maker.user.pubkey();
+
+    for (maker, side, price) in [
+        (default_maker, Side::Ask, 105.0),
+        (&bidder, Side::Bid, 100.0),
+    ] {
+        sdk.client
+            .sign_send_instructions(
+                vec![create_new_order_instruction(
+                    market,
+                    &maker.user.pubkey(),
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(
+                        side,
+                        meta.float_price_to_ticks_rounded_down(price),
+                        if side == Side::Ask { 20 } else { 10 },
+                    ),
+                )],
+                vec![&maker.user],
+            )
+            .await
+            .unwrap();
+    }
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let (ask_id, _) = market_state.orderbook.asks.iter().next().unwrap();
+    let base_params = CancelOrderParams {
+        side: Side::Ask,
+        price_in_ticks: ask_id.price_in_ticks.into(),
+        order_sequence_number: ask_id.order_sequence_number,
+    };
+    let base_lots_free = market_state.traders[&trader].base_lots_free;
+    let quote_lots_free = market_state.traders[&trader].quote_lots_free;
+
+    // The IOC fails to meet its minimum fill, so the ask is not cancelled
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_cancel_and_swap_instruction(
+                market,
+                &trader,
+                &CancelAndSwapParams {
+                    base_params,
+                    size: u64::MAX,
+                    price_in_ticks: None,
+                    min_base_lots_to_fill: 20,
+                    min_quote_lots_to_fill: 0,
+                    self_trade_behavior: SelfTradeBehavior::Abort,
+                    client_order_id: 0,
+                },
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .is_err());
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+
+    // The freed base lots are sold into the bid, and the unfilled base lots remain free
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_and_swap_instruction(
+                market,
+                &trader,
+                &CancelAndSwapParams {
+                    base_params,
+                    size: u64::MAX,
+                    price_in_ticks: None,
+                    min_base_lots_to_fill: 10,
+                    min_quote_lots_to_fill: 0,
+                    self_trade_behavior: SelfTradeBehavior::Abort,
+                    client_order_id: 0,
+                },
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(market_state.orderbook.asks.is_empty());
+    assert!(market_state.orderbook.bids.is_empty());
+    assert_eq!(
+        market_state.traders[&trader].base_lots_free,
+        base_lots_free + 10
+    );
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_free
+            + meta.float_price_to_ticks_rounded_down(100.0)
+                * 10
+                * meta.tick_size_in_quote_atoms_per_base_unit
+                / (meta.num_base_lots_per_base_unit * meta.quote_atoms_per_quote_lot)
+    );
+
+    // Reducing an order that does not exist frees nothing and fails
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_cancel_and_swap_instruction(
+                market,
+                &trader,
+                &CancelAndSwapParams {
+                    base_params,
+                    size: u64::MAX,
+                    price_in_ticks: None,
+                    min_base_lots_to_fill: 0,
+                    min_quote_lots_to_fill: 0,
+                    self_trade_behavior: SelfTradeBehavior::Abort,
+                    client_order_id: 0,
+                },
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .is_err());
+}
+
+/// Sends `instructions` in a transaction paid for by `signer` and returns the return data that
+/// Phoenix set
+async fn get_phoenix_return_data(
+    ctx: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signer: &Keypair,
+) -> Vec<u8> {
+    let recent_blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signer.pubkey()),
+        &[signer],
+        recent_blockhash,
+    );
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok());
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, phoenix::id());
+    return_data.data
+}
+
+#[tokio::test]
+async fn test_phoenix_swap_return_data() {
+    let (mut client, ctx) = bootstrap_default(5).await;
+    let taker = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let price_in_ticks = client.meta.float_price_to_ticks_rounded_down(100.0);
+    let quote_lots_filled = |price_in_ticks: u64, num_base_lots: u64| {
+        price_in_ticks * num_base_lots * client.meta.tick_size_in_quote_atoms_per_base_unit
+            / (client.meta.num_base_lots_per_base_unit * client.meta.quote_atoms_per_quote_lot)
+    };
+
+    for (trader, side, price_in_ticks) in [
+        (default_maker, Side::Ask, price_in_ticks),
+        (&taker, Side::Bid, price_in_ticks - 1),
+    ] {
+        client
+            .sdk
+            .client
+            .sign_send_instructions(
+                vec![create_new_order_instruction(
+                    &client.market,
+                    &trader.user.pubkey(),
+                    &client.meta.base_mint,
+                    &client.meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(side, price_in_ticks, 20),
+                )],
+                vec![&trader.user],
+            )
+            .await
+            .unwrap();
+    }
+
+    // Swaps set a summary of the swap as their return data, so that CPI callers can read it
+    let swap_ix = create_new_order_instruction(
+        &client.market,
+        &taker.user.pubkey(),
+        &client.meta.base_mint,
+        &client.meta.quote_mint,
+        &OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            price_in_ticks,
+            10,
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            false,
+        ),
+    );
+    let return_data = get_phoenix_return_data(&mut client.ctx, &[swap_ix], &taker.user).await;
+    let swap_summary = SwapSummary::try_from_slice(&return_data).unwrap();
+    assert_eq!(swap_summary.base_lots_in, 0);
+    assert_eq!(swap_summary.base_lots_out, 10);
+    assert_eq!(swap_summary.quote_lots_out, 0);
+    assert!(swap_summary.fee_in_quote_lots > 0);
+    assert_eq!(
+        swap_summary.quote_lots_in,
+        quote_lots_filled(price_in_ticks, 10) + swap_summary.fee_in_quote_lots
+    );
+
+    // Swaps with deposited funds report the lots credited to the trader's deposited funds
+    let instructions = [
+        create_deposit_funds_instruction(
+            &client.market,
+            &taker.user.pubkey(),
+            &client.meta.base_mint,
+            &client.meta.quote_mint,
+            &DepositParams {
+                quote_lots_to_deposit: 2 * quote_lots_filled(price_in_ticks, 10),
+                base_lots_to_deposit: 0,
+            },
+        ),
+        create_new_order_with_free_funds_instruction(
+            &client.market,
+            &taker.user.pubkey(),
+            &OrderPacket::new_ioc_by_lots(
+                Side::Bid,
+                price_in_ticks,
+                5,
+                SelfTradeBehavior::Abort,
+                None,
+                0,
+                true,
+            ),
+        ),
+    ];
+    let return_data = get_phoenix_return_data(&mut client.ctx, &instructions, &taker.user).await;
+    let swap_summary = SwapSummary::try_from_slice(&return_data).unwrap();
+    assert_eq!(swap_summary.base_lots_out, 5);
+    assert_eq!(
+        swap_summary.quote_lots_in,
+        quote_lots_filled(price_in_ticks, 5) + swap_summary.fee_in_quote_lots
+    );
+    let market_state = client.sdk.get_market_state(&client.market).await.unwrap();
+    assert_eq!(market_state.traders[&taker.user.pubkey()].base_lots_free, 5);
+
+    // The ask that is reduced by CancelAndSwap is sold into the bid, and the quote lots out are
+    // credited to the maker's deposited funds
+    let market_state = client.sdk.get_market_state(&client.market).await.unwrap();
+    let (ask_id, _) = market_state.orderbook.asks.iter().next().unwrap();
+    let cancel_and_swap_ix = create_cancel_and_swap_instruction(
+        &client.market,
+        &default_maker.user.pubkey(),
+        &CancelAndSwapParams {
+            base_params: CancelOrderParams {
+                side: Side::Ask,
+                price_in_ticks: ask_id.price_in_ticks.into(),
+                order_sequence_number: ask_id.order_sequence_number,
+            },
+            size: 5,
+            price_in_ticks: None,
+            min_base_lots_to_fill: 5,
+            min_quote_lots_to_fill: 0,
+            self_trade_behavior: SelfTradeBehavior::Abort,
+            client_order_id: 0,
+        },
+    );
+    let return_data =
+        get_phoenix_return_data(&mut client.ctx, &[cancel_and_swap_ix], &default_maker.user).await;
+    let swap_summary = SwapSummary::try_from_slice(&return_data).unwrap();
+    assert_eq!(swap_summary.base_lots_in, 5);
+    assert_eq!(swap_summary.base_lots_out, 0);
+    assert_eq!(
+        swap_summary.quote_lots_out,
+        quote_lots_filled(price_in_ticks - 1, 5) - swap_summary.fee_in_quote_lots
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_cancel_multiple_orders_by_id_return_data() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let other_maker = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let price_in_ticks = client.meta.float_price_to_ticks_rounded_down(100.0);
+
+    let mut instructions = (1..=3)
+        .map(|i| {
+            create_new_order_instruction(
+                &client.market,
+                &default_maker.user.pubkey(),
+                &client.meta.base_mint,
+                &client.meta.quote_mint,
+                &OrderPacket::new_post_only_default(Side::Ask, price_in_ticks + i, 10),
+            )
+        })
+        .collect::<Vec<_>>();
+    instructions.push(create_new_order_instruction(
+        &client.market,
+        &other_maker.user.pubkey(),
+        &client.meta.base_mint,
+        &client.meta.quote_mint,
+        &OrderPacket::new_post_only_default(Side::Bid, price_in_ticks, 10),
+    ));
+    client
+        .sdk
+        .client
+        .sign_send_instructions(instructions, vec![&default_maker.user, &other_maker.user])
+        .await
+        .unwrap();
+    let market_state = client.sdk.get_market_state(&client.market).await.unwrap();
+    let [asks, bids] = [
+        (Side::Ask, &market_state.orderbook.asks),
+        (Side::Bid, &market_state.orderbook.bids),
+    ]
+    .map(|(side, orders)| {
+        orders
+            .iter()
+            .map(|(order_id, _)| CancelOrderParams {
+                side,
+                price_in_ticks: order_id.price_in_ticks.into(),
+                order_sequence_number: order_id.order_sequence_number,
+            })
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(asks.len(), 3);
+    assert_eq!(bids.len(), 1);
+
+    // One bit is returned per requested order, in request order. Orders that do not exist or
+    // belong to another trader are not found.
+    let missing_order = CancelOrderParams {
+        order_sequence_number: asks[0].order_sequence_number + 1_000,
+        ..asks[0]
+    };
+    let mut orders = vec![asks[0], missing_order, bids[0], asks[1]];
+    orders.extend(std::iter::repeat(missing_order).take(4));
+    orders.push(asks[2]);
+    let cancel_ix = create_cancel_multiple_orders_by_id_with_free_funds_instruction(
+        &client.market,
+        &default_maker.user.pubkey(),
+        &CancelMultipleOrdersByIdParams { orders },
+    );
+    let return_data =
+        get_phoenix_return_data(&mut client.ctx, &[cancel_ix], &default_maker.user).await;
+    assert_eq!(return_data, vec![0b0000_1001, 0b0000_0001]);
+
+    // Only the trader's own orders were cancelled
+    let market_state = client.sdk.get_market_state(&client.market).await.unwrap();
+    assert!(market_state.orderbook.asks.is_empty());
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+}
+
+#[tokio::test]
+async fn test_phoenix_withdraw_and_close_seat() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+    let (seat, _) = get_seat_address(market, &trader);
+    let base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit: meta.quote_units_to_quote_lots(1000.0),
+                        base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                    },
+                ),
+                create_new_order_with_free_funds_instruction(
+                    market,
+                    &trader,
+                    &OrderPacket::new_post_only_default(Side::Bid, 99, 10),
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The seat can not be closed while the trader has resting orders
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_withdraw_funds_and_close_seat_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+    assert!(sdk.client.get_account(&seat).await.is_ok());
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_all_order_with_free_funds_instruction(
+                market, &trader,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let seat_lamports = sdk.client.get_account(&seat).await.unwrap().lamports;
+    let trader_lamports = sdk.client.get_account(&trader).await.unwrap().lamports;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_withdraw_funds_and_close_seat_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // All funds are returned and the trader is removed from the market
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        base_start
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_start
+    );
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(!market_state.traders.contains_key(&trader));
+
+    // The seat is closed and its lamports (less the transaction fee) are returned to the trader
+    assert!(sdk.client.get_account(&seat).await.is_err());
+    let trader_lamports_end = sdk.client.get_account(&trader).await.unwrap().lamports;
+    assert!(trader_lamports_end > trader_lamports);
+    assert!(trader_lamports_end <= trader_lamports + seat_lamports);
+}
+
+#[tokio::test]
+async fn test_phoenix_deposit_atoms() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+    let base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let (base_lots_start, quote_lots_start) = market_state
+        .traders
+        .get(&trader)
+        .map(|trader_state| (trader_state.base_lots_free, trader_state.quote_lots_free))
+        .unwrap_or_default();
+
+    // Both amounts are a few atoms short of an additional lot
+    let quote_atoms_to_deposit =
+        25 * meta.quote_atoms_per_quote_lot + meta.quote_atoms_per_quote_lot - 1;
+    let base_atoms_to_deposit = 7 * meta.base_atoms_per_base_lot + meta.base_atoms_per_base_lot / 2;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_atoms_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositAtomsParams {
+                    quote_atoms_to_deposit,
+                    base_atoms_to_deposit,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // Only the whole lots are deposited and the remainder stays in the trader's token accounts
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_start - 25 * meta.quote_atoms_per_quote_lot
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        base_start - 7 * meta.base_atoms_per_base_lot
+    );
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let trader_state = market_state.traders.get(&trader).unwrap();
+    assert_eq!(trader_state.quote_lots_free, quote_lots_start + 25);
+    assert_eq!(trader_state.base_lots_free, base_lots_start + 7);
+
+    // Amounts smaller than a lot are not deposited
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_atoms_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositAtomsParams {
+                    quote_atoms_to_deposit: meta.quote_atoms_per_quote_lot - 1,
+                    base_atoms_to_deposit: 0,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_start - 25 * meta.quote_atoms_per_quote_lot
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_native_sol() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient { sdk, meta, .. } = &mut client;
+    let admin = sdk.client.payer.pubkey();
+    let base_mint = meta.base_mint;
+    let native_mint = spl_token::native_mint::id();
+
+    // Create a market whose quote mint is the native mint
+    let market = Keypair::new();
+    create_associated_token_account(&sdk.client, &admin, &native_mint, &spl_token::id())
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions_with_payer(
+            create_initialize_market_instructions(
+                &market.pubkey(),
+                &base_mint,
+                &native_mint,
+                &spl_token::id(),
+                &admin,
+                InitializeParams {
+                    market_size_params: MarketSizeParams {
+                        bids_size: 512,
+                        asks_size: 512,
+                        num_seats: 128,
+                    },
+                    num_quote_lots_per_quote_unit: 100_000,
+                    num_base_lots_per_base_unit: 1_000,
+                    tick_size_in_quote_lots_per_base_unit: 1_000,
+                    taker_fee_bps: 0,
+                    fee_collector: admin,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+            vec![&market],
+        )
+        .await
+        .unwrap();
+    let market = market.pubkey();
+    let mut instructions = vec![create_change_market_status_instruction(
+        &admin,
+        &market,
+        MarketStatus::Active,
+    )];
+    for trader in [default_maker.user.pubkey(), default_taker.user.pubkey()] {
+        instructions.push(create_request_seat_authorized_instruction(
+            &admin, &admin, &market, &trader,
+        ));
+        instructions.push(create_change_seat_status_instruction(
+            &admin,
+            &market,
+            &trader,
+            SeatApprovalStatus::Approved,
+        ));
+    }
+    sdk.client
+        .sign_send_instructions(instructions, vec![])
+        .await
+        .unwrap();
+    sdk.add_market(&market).await.unwrap();
+    let meta = *sdk.get_market_metadata_from_cache(&market).unwrap();
+
+    // The trader's own wrapped SOL account is never touched by the native SOL instructions
+    let trader = default_taker.user.pubkey();
+    let trader_wsol =
+        create_associated_token_account(&sdk.client, &trader, &native_mint, &spl_token::id())
+            .await
+            .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                system_instruction::transfer(&admin, &trader_wsol, sol(1.0)),
+                spl_token::instruction::sync_native(&spl_token::id(), &trader_wsol).unwrap(),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let native_sol_account = get_native_sol_address(&market, &trader).0;
+
+    // Depositing wraps exactly the deposited amount and closes the temporary account
+    let lamports_start = sdk.client.get_account(&trader).await.unwrap().lamports;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_funds_with_native_sol_instruction(
+                &market,
+                &trader,
+                &base_mint,
+                &spl_token::id(),
+                &DepositParams {
+                    quote_lots_to_deposit: 1_000,
+                    base_lots_to_deposit: 0,
+                },
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let lamports_after_deposit = sdk.client.get_account(&trader).await.unwrap().lamports;
+    assert_eq!(
+        lamports_start - lamports_after_deposit,
+        1_000 * meta.quote_atoms_per_quote_lot
+    );
+    assert!(sdk.client.get_account(&native_sol_account).await.is_err());
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].quote_lots_free, 1_000);
+
+    // Withdrawing unwraps the withdrawn amount to the trader
+    sdk.client
+        .sign_send_instructions(
+            vec![create_withdraw_funds_to_native_sol_instruction(
+                &market,
+                &trader,
+                &base_mint,
+                &spl_token::id(),
+                &WithdrawParams {
+                    quote_lots_to_withdraw: Some(400),
+                    base_lots_to_withdraw: Some(0),
+                    close_seat: None,
+                },
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let lamports_after_withdraw = sdk.client.get_account(&trader).await.unwrap().lamports;
+    assert_eq!(
+        lamports_after_withdraw - lamports_after_deposit,
+        400 * meta.quote_atoms_per_quote_lot
+    );
+    assert!(sdk.client.get_account(&native_sol_account).await.is_err());
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].quote_lots_free, 600);
+
+    // A buy wraps enough SOL for its limit price and returns what it did not spend
+    create_associated_token_account(
+        &sdk.client,
+        &default_maker.user.pubkey(),
+        &native_mint,
+        &spl_token::id(),
+    )
+    .await
+    .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                &market,
+                &default_maker.user.pubkey(),
+                &base_mint,
+                &native_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Ask,
+                    meta.float_price_to_ticks_rounded_down(1.0),
+                    10,
+                ),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    let base_start = get_token_balance(&sdk.client, default_taker.base_ata).await;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_swap_with_native_sol_instruction(
+                &market,
+                &trader,
+                &base_mint,
+                &spl_token::id(),
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(1.5),
+                    10,
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
+    let lamports_after_swap = sdk.client.get_account(&trader).await.unwrap().lamports;
+    let base_atoms_bought = 10 * meta.base_atoms_per_base_lot;
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.base_ata).await - base_start,
+        base_atoms_bought
+    );
+    // The price is 1 SOL per base unit, and the base mint has 9 decimals like SOL
+    assert_eq!(
+        lamports_after_withdraw - lamports_after_swap,
+        base_atoms_bought
+    );
+    assert!(sdk.client.get_account(&native_sol_account).await.is_err());
+
+    // The instructions only accept the trader's temporary wrapped SOL account
+    let mut ix = create_deposit_funds_with_native_sol_instruction(
+        &market,
+        &trader,
+        &base_mint,
+        &spl_token::id(),
+        &DepositParams {
+            quote_lots_to_deposit: 1,
+            base_lots_to_deposit: 0,
+        },
+    );
+    ix.accounts[6].pubkey = trader_wsol;
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![ix], vec![&default_taker.user])
+        .await
+        .is_err());
+    assert_eq!(get_token_balance(&sdk.client, trader_wsol).await, sol(1.0));
+}
+
+#[tokio::test]
+async fn test_phoenix_cancel_all_and_close_seat() {
+    let (mut client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        enable_stop_orders: true,
+        ..Default::default()
+    })
+    .await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+    let (seat, _) = get_seat_address(market, &trader);
+    let base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit: meta.quote_units_to_quote_lots(1000.0),
+                        base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                    },
+                ),
+                create_new_order_with_free_funds_instruction(
+                    market,
+                    &trader,
+                    &OrderPacket::new_post_only_default(
+                        Side::Bid,
+                        meta.float_price_to_ticks_rounded_down(99.0),
+                        10,
+                    ),
+                ),
+                create_new_order_with_free_funds_instruction(
+                    market,
+                    &trader,
+                    &OrderPacket::new_post_only_default(
+                        Side::Ask,
+                        meta.float_price_to_ticks_rounded_down(101.0),
+                        10,
+                    ),
+                ),
+                create_place_stop_order_instruction(
+                    market,
+                    &trader,
+                    &PlaceStopOrderParams {
+                        side: Side::Bid,
+                        trigger_price_in_ticks: meta.float_price_to_ticks_rounded_down(102.0),
+                        limit_price_in_ticks: meta.float_price_to_ticks_rounded_down(105.0),
+                        num_base_lots: 10,
+                        client_order_id: 0,
+                    },
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The seat can not be closed while the stop order holds some of the trader's funds
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_cancel_all_and_close_seat_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+    assert!(sdk.client.get_account(&seat).await.is_ok());
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_stop_order_instruction(
+                market,
+                &trader,
+                &CancelStopOrderParams {
+                    side: Side::Bid,
+                    trigger_price_in_ticks: meta.float_price_to_ticks_rounded_down(102.0),
+                    stop_sequence_number: 0,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let seat_lamports = sdk.client.get_account(&seat).await.unwrap().lamports;
+    let trader_lamports = sdk.client.get_account(&trader).await.unwrap().lamports;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_cancel_all_and_close_seat_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The orders are cancelled, all funds are returned and the trader is removed from the market
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        base_start
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_start
+    );
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(market_state.orderbook.bids.is_empty());
+    assert!(market_state.orderbook.asks.is_empty());
+    assert!(!market_state.traders.contains_key(&trader));
+
+    // The seat is closed and its lamports (less the transaction fee) are returned to the trader
+    assert!(sdk.client.get_account(&seat).await.is_err());
+    let trader_lamports_end = sdk.client.get_account(&trader).await.unwrap().lamports;
+    assert!(trader_lamports_end > trader_lamports);
+    assert!(trader_lamports_end <= trader_lamports + seat_lamports);
+}
+
+#[tokio::test]
+async fn test_phoenix_withdraw_to_destination() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+    let maker_base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let maker_quote_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_funds_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositParams {
+                    quote_lots_to_deposit: meta.quote_units_to_quote_lots(1000.0),
+                    base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // Destination accounts must match the market's mints
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_withdraw_funds_to_instruction(
+                market,
+                &trader,
+                &default_taker.quote_ata,
+                &default_taker.base_ata,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    let maker_base_deposited =
+        maker_base_start - get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let maker_quote_deposited =
+        maker_quote_start - get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    let taker_base_start = get_token_balance(&sdk.client, default_taker.base_ata).await;
+    let taker_quote_start = get_token_balance(&sdk.client, default_taker.quote_ata).await;
+
+    // The maker's free funds are sent to token accounts owned by the taker
+    sdk.client
+        .sign_send_instructions(
+            vec![create_withdraw_funds_to_instruction(
+                market,
+                &trader,
+                &default_taker.base_ata,
+                &default_taker.quote_ata,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        maker_base_start - maker_base_deposited
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        maker_quote_start - maker_quote_deposited
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.base_ata).await,
+        taker_base_start + maker_base_deposited
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.quote_ata).await,
+        taker_quote_start + maker_quote_deposited
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_internal_transfer() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let destination = get_new_maker(&client, &ctx, 0, 0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+    let quote_vault = get_vault_address(market, &meta.quote_mint).0;
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_funds_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositParams {
+                    quote_lots_to_deposit: 1000,
+                    base_lots_to_deposit: 100,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let quote_vault_start = get_token_balance(&sdk.client, quote_vault).await;
+
+    let transfer = |destination: Pubkey, quote_lots: u64, base_lots: u64| {
+        create_internal_transfer_instruction(
+            market,
+            &trader,
+            &InternalTransferParams {
+                destination,
+                quote_lots_to_transfer: quote_lots,
+                base_lots_to_transfer: base_lots,
+            },
+        )
+    };
+
+    // The transfer fails if the source does not have enough free funds
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![transfer(destination.user.pubkey(), 1001, 0)], vec![])
+        .await
+        .is_err());
+    // The destination must have an approved seat
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![transfer(default_taker.user.pubkey(), 100, 0)], vec![])
+        .await
+        .is_err());
+
+    sdk.client
+        .sign_send_instructions(vec![transfer(destination.user.pubkey(), 400, 30)], vec![])
+        .await
+        .unwrap();
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.traders[&trader].quote_lots_free, 600);
+    assert_eq!(market_state.traders[&trader].base_lots_free, 70);
+    assert_eq!(
+        market_state.traders[&destination.user.pubkey()].quote_lots_free,
+        400
+    );
+    assert_eq!(
+        market_state.traders[&destination.user.pubkey()].base_lots_free,
+        30
+    );
+    // No tokens leave the vaults
+    assert_eq!(
+        get_token_balance(&sdk.client, quote_vault).await,
+        quote_vault_start
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_place_by_funds_fraction() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let trader = default_maker.user.pubkey();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_funds_instruction(
+                market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &DepositParams {
+                    quote_lots_to_deposit: 1000,
+                    base_lots_to_deposit: 100,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The size in the packet is ignored
+    let place_by_fraction = |side: Side, price_in_ticks: u64, fraction_in_bps: u64| {
+        create_place_by_funds_fraction_instruction(
+            market,
+            &trader,
+            &PlaceByFundsFractionParams::new(
+                fraction_in_bps,
+                OrderPacket::new_post_only(side, price_in_ticks, 1, 0, true, true),
+            ),
+        )
+    };
+
+    // The fraction must be in (0, 10000]
+    for fraction_in_bps in [0, 10001] {
+        assert!(sdk
+            .client
+            .sign_send_instructions(
+                vec![place_by_fraction(Side::Bid, 10, fraction_in_bps)],
+                vec![]
+            )
+            .await
+            .is_err());
+    }
+
+    // Half of the 1000 free quote lots buys 50 base lots at 10 quote lots per base lot
+    sdk.client
+        .sign_send_instructions(vec![place_by_fraction(Side::Bid, 10, 5000)], vec![])
+        .await
+        .unwrap();
+    // All of the free base lots are offered
+    sdk.client
+        .sign_send_instructions(vec![place_by_fraction(Side::Ask, 20, 10000)], vec![])
+        .await
+        .unwrap();
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    let [bid_sizes, ask_sizes] =
+        [&market_state.orderbook.bids, &market_state.orderbook.asks].map(|ob| {
+            ob.iter()
+                .map(|(_, o)| o.num_base_lots)
+                .collect::<Vec<u64>>()
+        });
+    assert_eq!(bid_sizes, vec![50]);
+    assert_eq!(ask_sizes, vec![100]);
+    assert_eq!(market_state.traders[&trader].quote_lots_free, 500);
+    assert_eq!(market_state.traders[&trader].base_lots_free, 0);
+
+    // With no free base lots left, the instruction succeeds without placing an order
+    sdk.client
+        .sign_send_instructions(vec![place_by_fraction(Side::Ask, 21, 5000)], vec![])
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+}
+
+#[tokio::test]
+async fn test_phoenix_withdraw_from_markets() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    let trader = default_maker.user.pubkey();
+    let admin = sdk.client.payer.pubkey();
+
+    // Create a second market with the same mints and give the maker a seat on it
+    let second_market = Keypair::new();
+    let mut init_instructions = create_initialize_market_instructions(
+        &second_market.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &spl_token::id(),
+        &admin,
+        InitializeParams {
+            market_size_params: MarketSizeParams {
+                bids_size: BOOK_SIZE as u64,
+                asks_size: BOOK_SIZE as u64,
+                num_seats: NUM_SEATS as u64,
+            },
+            num_quote_lots_per_quote_unit: 100_000,
+            num_base_lots_per_base_unit: 1_000,
+            tick_size_in_quote_lots_per_base_unit: 1_000,
+            taker_fee_bps: 0,
+            fee_collector: admin,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    init_instructions.push(create_change_market_status_instruction(
+        &admin,
+        &second_market.pubkey(),
+        MarketStatus::Active,
+    ));
+    sdk.client
+        .sign_send_instructions_with_payer(init_instructions, vec![&second_market])
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_request_seat_authorized_instruction(
+                    &admin,
+                    &admin,
+                    &second_market.pubkey(),
+                    &trader,
+                ),
+                create_change_seat_status_instruction(
+                    &admin,
+                    &second_market.pubkey(),
+                    &trader,
+                    SeatApprovalStatus::Approved,
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    sdk.client.set_payer(&trader).unwrap();
+    let base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    let params = DepositParams {
+        quote_lots_to_deposit: meta.quote_units_to_quote_lots(1000.0),
+        base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+    };
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &params,
+                ),
+                create_deposit_funds_instruction(
+                    &second_market.pubkey(),
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &params,
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert!(get_token_balance(&sdk.client, default_maker.base_ata).await < base_start);
+    assert!(get_token_balance(&sdk.client, default_maker.quote_ata).await < quote_start);
+
+    // The first market is passed twice, and the second group is skipped because its free funds
+    // have already been withdrawn
+    let markets = vec![
+        (*market, meta.base_mint, meta.quote_mint),
+        (second_market.pubkey(), meta.base_mint, meta.quote_mint),
+        (*market, meta.base_mint, meta.quote_mint),
+    ];
+    let instructions = create_withdraw_from_markets_instructions(&trader, &markets);
+    assert_eq!(instructions.len(), 1);
+    let sequence_number = get_sequence_number(&sdk.client, market).await;
+    sdk.client
+        .sign_send_instructions(instructions, vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.base_ata).await,
+        base_start
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_maker.quote_ata).await,
+        quote_start
+    );
+    assert_eq!(
+        get_sequence_number(&sdk.client, market).await,
+        sequence_number + 1
+    );
+
+    // Markets are split across multiple instructions to respect the account limit
+    let markets = vec![
+        (*market, meta.base_mint, meta.quote_mint);
+        2 * MAX_MARKETS_PER_WITHDRAW_INSTRUCTION + 1
+    ];
+    assert_eq!(
+        create_withdraw_from_markets_instructions(&trader, &markets).len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_reduce_order_by_quote() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let quote_lots_to_deposit = meta.quote_units_to_quote_lots(10000.0);
+    let base_lots_to_deposit = meta.raw_base_units_to_base_lots_rounded_down(100.0);
+    let trader = default_maker.user.pubkey();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    &market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit,
+                        base_lots_to_deposit,
+                    },
+                ),
+                create_new_order_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &OrderPacket::new_post_only(Side::Bid, 100, 10, 0, true, true),
+                ),
+                create_new_order_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &OrderPacket::new_post_only(Side::Ask, 110, 10, 0, true, true),
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let quote_lots_per_base_lot = 100 * meta.tick_size_in_quote_atoms_per_base_unit
+        / (meta.num_base_lots_per_base_unit * meta.quote_atoms_per_quote_lot);
+    let bid = CancelOrderParams {
+        side: Side::Bid,
+        price_in_ticks: 100,
+        order_sequence_number: !0,
+    };
+
+    // Asks cannot be reduced by quote lots
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_reduce_order_by_quote_with_free_funds_instruction(
+                &market,
+                &trader,
+                &ReduceOrderByQuoteParams {
+                    base_params: CancelOrderParams {
+                        side: Side::Ask,
+                        price_in_ticks: 110,
+                        order_sequence_number: 1,
+                    },
+                    quote_lots: quote_lots_per_base_lot,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    // The quote lots are converted to base lots at the order's price, rounded down
+    sdk.client
+        .sign_send_instructions(
+            vec![create_reduce_order_by_quote_with_free_funds_instruction(
+                &market,
+                &trader,
+                &ReduceOrderByQuoteParams {
+                    base_params: bid,
+                    quote_lots: quote_lots_per_base_lot * 5 / 2,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state
+            .orderbook
+            .bids
+            .iter()
+            .map(|(_, o)| o.num_base_lots)
+            .collect::<Vec<_>>(),
+        vec![8]
+    );
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_to_deposit - 8 * quote_lots_per_base_lot
+    );
+
+    // Requests that exceed the value of the order remove the order in full
+    sdk.client
+        .sign_send_instructions(
+            vec![create_reduce_order_by_quote_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &ReduceOrderByQuoteParams {
+                    base_params: bid,
+                    quote_lots: quote_lots_to_deposit,
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The released quote lots are withdrawn, so the trader's free funds are unchanged
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(market_state.orderbook.bids.is_empty());
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_to_deposit - 8 * quote_lots_per_base_lot
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_reduce_multiple_orders() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let quote_lots_to_deposit = meta.quote_units_to_quote_lots(10000.0);
+    let base_lots_to_deposit = meta.raw_base_units_to_base_lots_rounded_down(100.0);
+    let trader = default_maker.user.pubkey();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    &market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit,
+                        base_lots_to_deposit,
+                    },
+                ),
+                create_new_order_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &OrderPacket::new_post_only(Side::Ask, 110, 10, 0, true, true),
+                ),
+                create_new_order_with_free_funds_instruction(
+                    &market,
+                    &trader,
+                    &OrderPacket::new_post_only(Side::Ask, 111, 10, 0, true, true),
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let reduce = |price_in_ticks, order_sequence_number, size| ReduceOrderParams {
+        base_params: CancelOrderParams {
+            side: Side::from_order_sequence_number(order_sequence_number),
+            price_in_ticks,
+            order_sequence_number,
+        },
+        size,
+    };
+
+    // Orders that are not on the book are skipped
+    sdk.client
+        .sign_send_instructions(
+            vec![create_reduce_multiple_orders_with_free_funds_instruction(
+                &market,
+                &trader,
+                &ReduceMultipleOrdersParams {
+                    orders: vec![reduce(110, 0, 3), reduce(112, 7, 5)],
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state
+            .orderbook
+            .asks
+            .iter()
+            .map(|(_, o)| o.num_base_lots)
+            .collect::<Vec<_>>(),
+        vec![7, 10]
+    );
+    assert_eq!(
+        market_state.traders[&trader].base_lots_free,
+        base_lots_to_deposit - 17
+    );
+
+    // The funds released by all of the orders are withdrawn together
+    let base_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    sdk.client
+        .sign_send_instructions(
+            vec![create_reduce_multiple_orders_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &ReduceMultipleOrdersParams {
+                    orders: vec![reduce(110, 0, 100), reduce(111, 1, 4)],
+                },
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let base_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(
+        market_state
+            .orderbook
+            .asks
+            .iter()
+            .map(|(_, o)| o.num_base_lots)
+            .collect::<Vec<_>>(),
+        vec![6]
+    );
+    assert_eq!(
+        market_state.traders[&trader].base_lots_free,
+        base_lots_to_deposit - 17
+    );
+    assert_eq!(base_end - base_start, 11 * meta.base_atoms_per_base_lot);
+}
+
+#[tokio::test]
+async fn test_phoenix_deposit_and_place() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    sdk.client.set_payer(&default_maker.user.pubkey()).unwrap();
+    let quote_lots_to_deposit = meta.quote_units_to_quote_lots(10000.0);
+    let base_lots_to_deposit = meta.raw_base_units_to_base_lots_rounded_down(100.0);
+    let params = DepositParams {
+        quote_lots_to_deposit,
+        base_lots_to_deposit,
+    };
+    let trader = default_maker.user.pubkey();
+
+    // The order must only use deposited funds
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_deposit_and_place_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &params,
+                &OrderPacket::new_limit_order_default(Side::Bid, 100, 10),
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+
+    // If the order cannot be placed, the deposit is rolled back
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_deposit_and_place_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &params,
+                &OrderPacket::new_post_only(
+                    Side::Ask,
+                    100,
+                    base_lots_to_deposit + 1,
+                    0,
+                    true,
+                    true
+                ),
+            )],
+            vec![],
+        )
+        .await
+        .is_err());
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert!(
+        !market_state.traders.contains_key(&trader)
+            || market_state.traders[&trader].base_lots_free == 0
+    );
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_deposit_and_place_instruction(
+                &market,
+                &trader,
+                &meta.base_mint,
+                &meta.quote_mint,
+                &params,
+                &OrderPacket::new_post_only(Side::Bid, 100, 10, 0, true, true),
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // The unused portion of the deposit remains in the trader's free funds
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+    assert_eq!(
+        market_state.traders[&trader].base_lots_free,
+        base_lots_to_deposit
+    );
+    assert_eq!(
+        market_state.traders[&trader].quote_lots_free,
+        quote_lots_to_deposit
+            - (100 * 10 * meta.tick_size_in_quote_atoms_per_base_unit
+                / (meta.num_base_lots_per_base_unit * meta.quote_atoms_per_quote_lot))
+    );
+}
+
+#[tokio::test]
+async fn test_phoenix_orders_with_free_funds() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let second_maker = get_new_maker(&client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
+
+    sdk.set_payer(clone_keypair(&default_maker.user));
+
+    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    layer_orders(
+        meta,
+        market,
+        &sdk,
+        meta.float_price_to_ticks_rounded_down(40.0),
+        meta.float_price_to_ticks_rounded_down(30.0),
+        meta.float_price_to_ticks_rounded_down(1.0),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        Side::Bid,
+    )
+    .await;
+
+    layer_orders(
+        meta,
+        market,
+        &sdk,
+        meta.float_price_to_ticks_rounded_down(50.0),
+        meta.float_price_to_ticks_rounded_down(60.0),
+        meta.float_price_to_ticks_rounded_down(1.0),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        Side::Ask,
+    )
+    .await;
+    sdk.set_payer(clone_keypair(&default_taker.user));
+
+    //Attempt to use free funds to trade, will reject because the taker has no seat
+    let sell_params = OrderPacket::new_ioc_by_lots(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(31.0),
+        meta.raw_base_units_to_base_lots_rounded_down(55.0),
+        SelfTradeBehavior::Abort,
+        None,
+        0,
+        true,
+    );
+
+    let new_order_ix = create_new_order_with_free_funds_instruction(
+        market,
+        &default_taker.user.pubkey(),
+        &sell_params,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_taker.user])
+        .await
+        .is_err());
+
+    //Trade through the first 10 levels of the book and self trade the last level on each side
+    let sell_params = OrderPacket::new_ioc_by_lots(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(31.0),
+        meta.raw_base_units_to_base_lots_rounded_down(55.0),
+        SelfTradeBehavior::Abort,
+        None,
+        0,
+        false,
+    );
+
+    let buy_params = OrderPacket::new_ioc_by_lots(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(59.0),
+        meta.raw_base_units_to_base_lots_rounded_down(55.0),
+        SelfTradeBehavior::Abort,
+        None,
+        0,
+        false,
+    );
+
+    let self_trade_bid_params = OrderPacket::new_ioc_by_lots(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(30.0),
+        meta.raw_base_units_to_base_lots_rounded_down(11.0),
+        SelfTradeBehavior::DecrementTake,
+        None,
+        0,
+        false,
+    );
+
+    let self_trade_offer_params = OrderPacket::new_ioc_by_lots(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(60.0),
+        meta.raw_base_units_to_base_lots_rounded_down(11.0),
+        SelfTradeBehavior::DecrementTake,
+        None,
+        0,
+        false,
+    );
+
+    let taker_params = vec![sell_params, buy_params];
+    let maker_params = vec![self_trade_bid_params, self_trade_offer_params];
+
+    for param in taker_params {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &default_taker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &param,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&default_taker.user])
+            .await
+            .unwrap();
+    }
+
+    for param in maker_params {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &default_maker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &param,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+            .await
+            .unwrap();
+    }
+
+    let base_balance_new = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_new = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    println!("Base balance start: {}", base_balance_start);
+    println!("Quote balance start: {}", quote_balance_start);
+    println!("Base balance new: {}", base_balance_new);
+    println!("Quote balance new: {}", quote_balance_new);
+    assert_eq!(quote_balance_start - quote_balance_new, 2200000000);
+    assert_eq!(base_balance_start - base_balance_new, 66000000000);
+
+    //Attempt to send a LimitOrderWithFreeFunds with the second maker that will fail due to insufficient funds
+    sdk.client.payer = clone_keypair(&second_maker.user);
+    let new_order_ix = create_new_order_with_free_funds_instruction(
+        market,
+        &second_maker.user.pubkey(),
+        &OrderPacket::new_post_only_default(
+            Side::Bid,
+            meta.float_price_to_ticks_rounded_down(100.0),
+            meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        ),
+    );
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+        .await
+        .is_err());
+
+    //Add limit orders using the second maker, then use only free lots from the original maker to buy/sell via IOC
+    let limit_buy_params = OrderPacket::new_limit_order_default(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(30.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+    );
+
+    let limit_sell_params = OrderPacket::new_limit_order_default(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(34.1),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+    );
+
+    let ioc_buy_params = OrderPacket::new_ioc_by_lots(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(34.1),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+
+    let ioc_sell_params = OrderPacket::new_ioc_by_lots(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(30.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+    let second_maker_params = vec![limit_buy_params, limit_sell_params];
+    let maker_ioc_params = vec![ioc_buy_params, ioc_sell_params];
+    for param in second_maker_params {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &param,
+        );
+
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .unwrap();
+    }
+    sdk.set_payer(clone_keypair(&default_maker.user));
+    for param in maker_ioc_params {
+        let new_order_ix = create_new_order_with_free_funds_instruction(
+            market,
+            &default_maker.user.pubkey(),
+            &param,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+            .await
+            .unwrap();
+    }
+
+    let base_balance_after_ioc = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_after_ioc = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    // No deposits/withdraws, keep same amount of base lots free, lose 41000000 quote lots free
+    assert_eq!(quote_balance_after_ioc - quote_balance_new, 0);
+    assert_eq!(base_balance_after_ioc - base_balance_new, 0);
+
+    //Place a new buy and sell order using all remaining free lots + 1 extra unit
+    let limit_buy_params = OrderPacket::new_limit_order_default(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(33.69),
+        meta.raw_base_units_to_base_lots_rounded_down(101.0),
+    );
+
+    let limit_sell_params = OrderPacket::new_limit_order_default(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(50.0),
+        meta.raw_base_units_to_base_lots_rounded_down(67.0),
+    );
+
+    let maker_params = vec![limit_buy_params, limit_sell_params];
+
+    for param in maker_params {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &default_maker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &param,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+            .await
+            .unwrap();
+    }
+
+    //Check we only used 1 unit worth of new deposits
+    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    assert_eq!(quote_balance_after_ioc - quote_balance_end, 33690000);
+    assert_eq!(base_balance_after_ioc - base_balance_end, 1000000000);
+
+    //Attempt to send a SwapWithFreeFunds with the second maker that will fail due to insufficient funds
+    sdk.client.payer = clone_keypair(&second_maker.user);
+    let second_maker_base_balance_start =
+        get_token_balance(&sdk.client, second_maker.base_ata).await;
+    let second_maker_quote_balance_start =
+        get_token_balance(&sdk.client, second_maker.quote_ata).await;
+    let new_order_ix = create_new_order_with_free_funds_instruction(
+        market,
+        &second_maker.user.pubkey(),
+        &OrderPacket::new_ioc_by_lots(
+            Side::Bid,
+            meta.float_price_to_ticks_rounded_down(250.0),
+            meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            SelfTradeBehavior::CancelProvide,
+            None,
+            0,
+            true,
+        ),
+    );
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+        .await
+        .is_err());
+
+    //Add limit orders using the second maker using only free funds
+    let limit_buy_params = OrderPacket::new_limit_order(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(34.1),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+
+    let limit_sell_params = OrderPacket::new_limit_order(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(35.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+
+    for params in [limit_buy_params, limit_sell_params] {
+        let new_order_ix = create_new_order_with_free_funds_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            &params,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .unwrap();
+    }
+
+    //Check that the second maker has used only free funds
+    let second_maker_base_balance_new = get_token_balance(&sdk.client, second_maker.base_ata).await;
+    let second_maker_quote_balance_new =
+        get_token_balance(&sdk.client, second_maker.quote_ata).await;
+    assert_eq!(
+        second_maker_base_balance_new - second_maker_base_balance_start,
+        0
+    );
+    assert_eq!(
+        second_maker_quote_balance_new - second_maker_quote_balance_start,
+        0
+    );
+
+    //Check that internal free funds are now zero, so a new order uses new deposits
+    let limit_buy_params = OrderPacket::new_limit_order(
+        Side::Bid,
         meta.float_price_to_ticks_rounded_down(34.1),
         meta.raw_base_units_to_base_lots_rounded_down(10.0),
         SelfTradeBehavior::CancelProvide,
@@ -2298,72 +5881,487 @@ async fn test_phoenix_orders_with_free_funds() {
         false,
     );
 
-    let limit_sell_params = OrderPacket::new_limit_order(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(35.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        false,
+    let limit_sell_params = OrderPacket::new_limit_order(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(35.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        false,
+    );
+
+    for params in [limit_buy_params, limit_sell_params] {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &params,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .unwrap();
+    }
+
+    let second_maker_base_balance_end = get_token_balance(&sdk.client, second_maker.base_ata).await;
+    let second_maker_quote_balance_end =
+        get_token_balance(&sdk.client, second_maker.quote_ata).await;
+    assert_eq!(
+        second_maker_base_balance_new - second_maker_base_balance_end,
+        10000000000
+    );
+    assert_eq!(
+        second_maker_quote_balance_new - second_maker_quote_balance_end,
+        341000000
+    );
+
+    // Cancel all to free up some funds
+    let cancel_all_ix =
+        create_cancel_all_order_with_free_funds_instruction(market, &second_maker.user.pubkey());
+
+    sdk.client
+        .sign_send_instructions(vec![cancel_all_ix], vec![&second_maker.user])
+        .await
+        .unwrap();
+
+    let limit_buy_params = OrderPacket::new_limit_order(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(34.1),
+        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+
+    let limit_sell_params = OrderPacket::new_limit_order(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(35.0),
+        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        true,
+    );
+
+    //Check that sending an orderpacket with free funds set to true fails if we send via the wrong instruction type
+    for params in [limit_buy_params, limit_sell_params] {
+        let new_order_ix = create_new_order_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            base_mint,
+            quote_mint,
+            &params,
+        );
+        assert!(sdk
+            .client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .is_err());
+    }
+
+    // Free funds order packet succeeds with correct instruction type
+    for params in [limit_buy_params, limit_sell_params] {
+        let new_order_ix = create_new_order_with_free_funds_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            &params,
+        );
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .unwrap();
+    }
+
+    let limit_buy_params = OrderPacket::new_limit_order(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(34.1),
+        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        false,
+    );
+
+    let limit_sell_params = OrderPacket::new_limit_order(
+        Side::Ask,
+        meta.float_price_to_ticks_rounded_down(35.0),
+        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        SelfTradeBehavior::CancelProvide,
+        None,
+        0,
+        false,
+    );
+
+    // Order packet with free funds set to false fails if we send via the free funds instruction type
+    for params in [limit_buy_params, limit_sell_params] {
+        let new_order_ix = create_new_order_with_free_funds_instruction(
+            market,
+            &second_maker.user.pubkey(),
+            &params,
+        );
+        assert!(sdk
+            .client
+            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .await
+            .is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_phoenix_place_multiple_limit_orders() {
+    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
+
+    let PhoenixTestContext { default_maker, .. } = &phoenix_ctx;
+
+    let second_maker = get_new_maker(&client, &phoenix_ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestClient {
+        ctx,
+        sdk,
+        market,
+        meta,
+    } = &mut client;
+
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
+
+    sdk.set_payer(clone_keypair(&default_maker.user));
+
+    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    // Place multiple post only orders successfully
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(11.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+    );
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &multiple_order_packet,
+    );
+
+    {
+        let mut adversarial_ix = new_order_ix.clone();
+        adversarial_ix.accounts = adversarial_ix.accounts[..5].to_vec();
+
+        assert!(sdk
+            .client
+            .sign_send_instructions(vec![adversarial_ix], vec![&default_maker.user])
+            .await
+            .is_err());
+    }
+
+    sdk.client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    assert_eq!(base_balance_start - base_balance_end, 20000000000);
+    assert_eq!(quote_balance_start - quote_balance_end, 170000000);
+
+    let cancel_order_ix =
+        create_cancel_all_order_with_free_funds_instruction(market, &default_maker.user.pubkey());
+
+    sdk.client
+        .sign_send_instructions(vec![cancel_order_ix], vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    // Ensure free funds order doesnt place if not enough base lots but enough quote lots
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(8.0),
+                meta.raw_base_units_to_base_lots_rounded_down(9.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(11.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(10.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(11.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(12.0),
+                meta.raw_base_units_to_base_lots_rounded_down(4.0),
+            ),
+        ],
+    );
+
+    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        &multiple_order_packet,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .is_err());
+
+    // Ensure free funds order doesnt place if not enough quote lots but enough base lots
+
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(3.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(11.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+    );
+
+    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        &multiple_order_packet,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .is_err());
+
+    // place multiple post only orders successfully with free funds
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+        vec![
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(17.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(17.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(5.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+            CondensedOrder {
+                price_in_ticks: meta.float_price_to_ticks_rounded_down(12.0),
+                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(5.0),
+                last_valid_slot: None,
+                last_valid_unix_timestamp_in_seconds: None,
+            },
+        ],
+    );
+    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        &multiple_order_packet,
+    );
+
+    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+
+    sdk.client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    // Assert that no new funds were used
+    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    assert_eq!(base_balance_start - base_balance_end, 0);
+    assert_eq!(quote_balance_start - quote_balance_end, 0);
+
+    // We need to increment the slot because you cannot send duplicated transactions (same blockhash and same instruction)
+    ctx.warp_to_slot(2).unwrap();
+
+    // Cancel orders to return the orderbook to empty
+    let cancel_order_ix =
+        create_cancel_all_order_with_free_funds_instruction(market, &default_maker.user.pubkey());
+
+    sdk.client
+        .sign_send_instructions(vec![cancel_order_ix], vec![&default_maker.user])
+        .await
+        .unwrap();
+
+    // Ensure we can't place orders in cross against themselves
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(8.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(9.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(9.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(11.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+    );
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &multiple_order_packet,
     );
 
-    for params in [limit_buy_params, limit_sell_params] {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &second_maker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &params,
-        );
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
-            .await
-            .unwrap();
-    }
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .is_err());
 
-    let second_maker_base_balance_end = get_token_balance(&sdk.client, second_maker.base_ata).await;
-    let second_maker_quote_balance_end =
-        get_token_balance(&sdk.client, second_maker.quote_ata).await;
-    assert_eq!(
-        second_maker_base_balance_new - second_maker_base_balance_end,
-        10000000000
-    );
-    assert_eq!(
-        second_maker_quote_balance_new - second_maker_quote_balance_end,
-        341000000
+    // Ensure we can't place orders in cross against themselves, different variation
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(29.0),
+                meta.raw_base_units_to_base_lots_rounded_down(1.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(9.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(19.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(30.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(25.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
     );
 
-    // Cancel all to free up some funds
-    let cancel_all_ix =
-        create_cancel_all_order_with_free_funds_instruction(market, &second_maker.user.pubkey());
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &multiple_order_packet,
+    );
 
-    sdk.client
-        .sign_send_instructions(vec![cancel_all_ix], vec![&second_maker.user])
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
         .await
-        .unwrap();
+        .is_err());
 
+    // Add limit orders to the book from the second maker
     let limit_buy_params = OrderPacket::new_limit_order(
         Side::Bid,
-        meta.float_price_to_ticks_rounded_down(34.1),
-        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        meta.float_price_to_ticks_rounded_down(10.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
         SelfTradeBehavior::CancelProvide,
         None,
         0,
-        true,
+        false,
     );
 
     let limit_sell_params = OrderPacket::new_limit_order(
         Side::Ask,
-        meta.float_price_to_ticks_rounded_down(35.0),
-        meta.raw_base_units_to_base_lots_rounded_down(5.0),
+        meta.float_price_to_ticks_rounded_down(20.0),
+        meta.raw_base_units_to_base_lots_rounded_down(10.0),
         SelfTradeBehavior::CancelProvide,
         None,
         0,
-        true,
+        false,
     );
 
-    //Check that sending an orderpacket with free funds set to true fails if we send via the wrong instruction type
     for params in [limit_buy_params, limit_sell_params] {
         let new_order_ix = create_new_order_instruction(
             market,
@@ -2372,112 +6370,126 @@ async fn test_phoenix_orders_with_free_funds() {
             quote_mint,
             &params,
         );
-        assert!(sdk
-            .client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
-            .await
-            .is_err());
-    }
-
-    // Free funds order packet succeeds with correct instruction type
-    for params in [limit_buy_params, limit_sell_params] {
-        let new_order_ix = create_new_order_with_free_funds_instruction(
-            market,
-            &second_maker.user.pubkey(),
-            &params,
-        );
         sdk.client
             .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
             .await
             .unwrap();
     }
 
-    let limit_buy_params = OrderPacket::new_limit_order(
-        Side::Bid,
-        meta.float_price_to_ticks_rounded_down(34.1),
-        meta.raw_base_units_to_base_lots_rounded_down(5.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        false,
+    // Ensure we can't place orders in cross against the existing book
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(8.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(9.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(10.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(11.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
     );
 
-    let limit_sell_params = OrderPacket::new_limit_order(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(35.0),
-        meta.raw_base_units_to_base_lots_rounded_down(5.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        false,
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &multiple_order_packet,
     );
 
-    // Order packet with free funds set to false fails if we send via the free funds instruction type
-    for params in [limit_buy_params, limit_sell_params] {
-        let new_order_ix = create_new_order_with_free_funds_instruction(
-            market,
-            &second_maker.user.pubkey(),
-            &params,
-        );
-        assert!(sdk
-            .client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
-            .await
-            .is_err());
-    }
-}
-
-#[tokio::test]
-async fn test_phoenix_place_multiple_limit_orders() {
-    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .is_err());
 
-    let PhoenixTestContext { default_maker, .. } = &phoenix_ctx;
+    let multiple_order_packet = MultipleOrderPacket::new_default(
+        vec![
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(20.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(9.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+        ],
+        vec![],
+    );
 
-    let second_maker = get_new_maker(&client, &phoenix_ctx, 1_000_000, 1_000_000).await;
-    let PhoenixTestClient {
-        ctx,
-        sdk,
+    let new_order_ix = create_new_multiple_order_instruction(
         market,
-        meta,
-    } = &mut client;
-
-    let quote_mint = &meta.quote_mint;
-    let base_mint = &meta.base_mint;
-
-    sdk.set_payer(clone_keypair(&default_maker.user));
+        &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &multiple_order_packet,
+    );
 
-    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .await
+        .is_err());
 
-    // Place multiple post only orders successfully
+    // Check that we use all our free funds first on a normal place multiple
+    // Currently have 20 base units and 170 quote units available
     let multiple_order_packet = MultipleOrderPacket::new_default(
         vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(5.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(4.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(3.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(5.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
+            CondensedOrder::new_default(
+                //this order is all of the extra quote lots we need to deposit
+                meta.float_price_to_ticks_rounded_down(4.0),
+                meta.raw_base_units_to_base_lots_rounded_down(10.0),
+            ),
         ],
         vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(11.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(100.0),
+                meta.raw_base_units_to_base_lots_rounded_down(5.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(105.0),
+                meta.raw_base_units_to_base_lots_rounded_down(5.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(100.0),
+                meta.raw_base_units_to_base_lots_rounded_down(5.0),
+            ),
+            CondensedOrder::new_default(
+                meta.float_price_to_ticks_rounded_down(103.0),
+                meta.raw_base_units_to_base_lots_rounded_down(5.0),
+            ),
+            CondensedOrder::new_default(
+                //this order is all of the extra base lots we need to deposit
+                meta.float_price_to_ticks_rounded_down(102.0),
+                meta.raw_base_units_to_base_lots_rounded_down(5.0),
+            ),
         ],
     );
 
@@ -2489,16 +6501,8 @@ async fn test_phoenix_place_multiple_limit_orders() {
         &multiple_order_packet,
     );
 
-    {
-        let mut adversarial_ix = new_order_ix.clone();
-        adversarial_ix.accounts = adversarial_ix.accounts[..5].to_vec();
-
-        assert!(sdk
-            .client
-            .sign_send_instructions(vec![adversarial_ix], vec![&default_maker.user])
-            .await
-            .is_err());
-    }
+    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
 
     sdk.client
         .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
@@ -2507,9 +6511,13 @@ async fn test_phoenix_place_multiple_limit_orders() {
 
     let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
     let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    assert_eq!(base_balance_start - base_balance_end, 20000000000);
-    assert_eq!(quote_balance_start - quote_balance_end, 170000000);
+    // Check that we only used an extra 40 quote units and 5 base units
+    assert_eq!(base_balance_start - base_balance_end, 5000000000);
+    assert_eq!(quote_balance_start - quote_balance_end, 40000000);
 
+    ctx.warp_to_slot(3).unwrap();
+
+    // Cancel orders for both makers to return the orderbook to empty
     let cancel_order_ix =
         create_cancel_all_order_with_free_funds_instruction(market, &default_maker.user.pubkey());
 
@@ -2518,480 +6526,776 @@ async fn test_phoenix_place_multiple_limit_orders() {
         .await
         .unwrap();
 
-    // Ensure free funds order doesnt place if not enough base lots but enough quote lots
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(8.0),
-                meta.raw_base_units_to_base_lots_rounded_down(9.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(11.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![
+    let cancel_order_ix =
+        create_cancel_all_order_with_free_funds_instruction(market, &second_maker.user.pubkey());
+
+    sdk.client
+        .sign_send_instructions(vec![cancel_order_ix], vec![&second_maker.user])
+        .await
+        .unwrap();
+
+    // Send 21 orders on each side to verify there is enough compute to do so (this is the upper bound due to the transaction size)
+    let bids = (1..22)
+        .map(|i| {
             CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(10.0),
+                meta.float_price_to_ticks_rounded_down(100.0 - (i as f64 * 0.1)),
                 meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
+            )
+        })
+        .collect::<Vec<_>>();
+    let asks = (1..22)
+        .map(|i| {
             CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(11.0),
+                meta.float_price_to_ticks_rounded_down(100.0 + (i as f64 * 0.1)),
                 meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(12.0),
-                meta.raw_base_units_to_base_lots_rounded_down(4.0),
-            ),
-        ],
-    );
+            )
+        })
+        .collect::<Vec<_>>();
 
-    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
+    let multiple_order_packet = MultipleOrderPacket::new_default(bids, asks);
+
+    let byte_len = multiple_order_packet.try_to_vec().unwrap().len();
+    assert_eq!(byte_len, 766);
+
+    let new_order_ix = create_new_multiple_order_instruction(
         market,
         &default_maker.user.pubkey(),
+        base_mint,
+        quote_mint,
         &multiple_order_packet,
     );
 
-    assert!(sdk
-        .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                new_order_ix,
+            ],
+            vec![&default_maker.user],
+        )
         .await
-        .is_err());
+        .unwrap();
+}
 
-    // Ensure free funds order doesnt place if not enough quote lots but enough base lots
+#[tokio::test]
+async fn test_phoenix_geometric_ladder() {
+    let (phoenix_client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient {
+        ctx: _,
+        sdk,
+        meta,
+        market,
+    } = &phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
 
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(3.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(1.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-        ],
-        vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(11.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-        ],
-    );
+    let tick_step = meta.float_price_to_ticks_rounded_down(0.1);
+    let start_bid = meta.float_price_to_ticks_rounded_down(99.0);
+    let start_ask = meta.float_price_to_ticks_rounded_down(101.0);
 
-    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        &multiple_order_packet,
-    );
+    // Ladders that are empty, too long, or hit a zero price or size are rejected
+    let ladder = |side, start, step, num_orders, size, growth| {
+        create_geometric_ladder_orders(
+            market,
+            &default_maker.user.pubkey(),
+            &meta.base_mint,
+            &meta.quote_mint,
+            side,
+            start,
+            step,
+            num_orders,
+            size,
+            growth,
+        )
+    };
+    assert!(ladder(Side::Bid, start_bid, tick_step, 0, 10, 1.5).is_none());
+    assert!(ladder(
+        Side::Bid,
+        start_bid,
+        tick_step,
+        MAX_MULTIPLE_ORDERS_PER_SIDE + 1,
+        10,
+        1.5
+    )
+    .is_none());
+    assert!(ladder(Side::Ask, start_ask, 0, 5, 10, 1.5).is_none());
+    assert!(ladder(Side::Bid, 3, 1, 5, 10, 1.5).is_none());
+    assert!(ladder(Side::Bid, start_bid, tick_step, 5, 10, 0.5).is_none());
+
+    for (side, start) in [(Side::Bid, start_bid), (Side::Ask, start_ask)] {
+        let ix = ladder(
+            side,
+            start,
+            tick_step,
+            MAX_MULTIPLE_ORDERS_PER_SIDE,
+            10,
+            1.1,
+        )
+        .unwrap();
+        sdk.client
+            .sign_send_instructions(
+                vec![
+                    ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                    ix,
+                ],
+                vec![&default_maker.user],
+            )
+            .await
+            .unwrap();
+    }
 
-    assert!(sdk
-        .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
-        .await
-        .is_err());
+    let market_data = sdk.client.get_account(market).await.unwrap().data;
+    let (header_bytes, bytes) = market_data.split_at(size_of::<MarketHeader>());
+    let header = Box::new(MarketHeader::load_bytes(header_bytes).unwrap());
+    let full_market = load_with_dispatch(&header.market_size_params, bytes).unwrap();
+    for (side, start) in [(Side::Bid, start_bid), (Side::Ask, start_ask)] {
+        let orders = full_market
+            .inner
+            .get_book(side)
+            .iter()
+            .map(|(order_id, order)| {
+                (
+                    order_id.price_in_ticks.as_u64(),
+                    order.num_base_lots.as_u64(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut size = 10.0_f64;
+        let expected = (0..MAX_MULTIPLE_ORDERS_PER_SIDE as u64)
+            .map(|i| {
+                let price = match side {
+                    Side::Bid => start - i * tick_step,
+                    Side::Ask => start + i * tick_step,
+                };
+                if i > 0 {
+                    size *= 1.1;
+                }
+                (price, size.floor() as u64)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(orders, expected);
+    }
+}
 
-    // place multiple post only orders successfully with free funds
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(8.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(9.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-        ],
-        vec![
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(17.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(17.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(5.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-            CondensedOrder {
-                price_in_ticks: meta.float_price_to_ticks_rounded_down(12.0),
-                size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(5.0),
-                last_valid_slot: None,
-                last_valid_unix_timestamp_in_seconds: None,
-            },
-        ],
-    );
-    let new_order_ix = create_new_multiple_order_with_free_funds_instruction(
+#[tokio::test]
+async fn test_phoenix_replace_quotes() {
+    let (phoenix_client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient {
+        ctx: _,
+        sdk,
+        meta,
         market,
-        &default_maker.user.pubkey(),
-        &multiple_order_packet,
-    );
+    } = &phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
+    let base_mint = &meta.base_mint;
+    let quote_mint = &meta.quote_mint;
+    let base_vault = get_vault_address(market, base_mint).0;
+    let quote_vault = get_vault_address(market, quote_mint).0;
 
-    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
+    let quotes = |bid_price: f64, ask_price: f64| {
+        MultipleOrderPacket::new_default(
+            (0..5)
+                .map(|i| {
+                    CondensedOrder::new_default(
+                        meta.float_price_to_ticks_rounded_down(bid_price - i as f64 * 0.1),
+                        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                    )
+                })
+                .collect(),
+            (0..5)
+                .map(|i| {
+                    CondensedOrder::new_default(
+                        meta.float_price_to_ticks_rounded_down(ask_price + i as f64 * 0.1),
+                        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                    )
+                })
+                .collect(),
+        )
+    };
 
     sdk.client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_new_multiple_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &quotes(99.0, 101.0),
+            )],
+            vec![&default_maker.user],
+        )
         .await
         .unwrap();
 
-    // Assert that no new funds were used
-    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    assert_eq!(base_balance_start - base_balance_end, 0);
-    assert_eq!(quote_balance_start - quote_balance_end, 0);
-
-    // We need to increment the slot because you cannot send duplicated transactions (same blockhash and same instruction)
-    ctx.warp_to_slot(2).unwrap();
-
-    // Cancel orders to return the orderbook to empty
-    let cancel_order_ix =
-        create_cancel_all_order_with_free_funds_instruction(market, &default_maker.user.pubkey());
+    let balances = [
+        default_maker.base_ata,
+        default_maker.quote_ata,
+        base_vault,
+        quote_vault,
+    ];
+    let mut balances_before = vec![];
+    for account in balances.iter() {
+        balances_before.push(get_token_balance(&sdk.client, *account).await);
+    }
 
+    // The new quotes are cheaper than the old ones, so they are fully funded by the cancels
     sdk.client
-        .sign_send_instructions(vec![cancel_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_replace_quotes_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &quotes(98.0, 102.0),
+            )],
+            vec![&default_maker.user],
+        )
         .await
         .unwrap();
 
-    // Ensure we can't place orders in cross against themselves
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(8.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(9.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(9.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(11.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-    );
+    // No tokens are transferred in or out of the vaults
+    for (account, balance_before) in balances.iter().zip(balances_before) {
+        assert_eq!(
+            get_token_balance(&sdk.client, *account).await,
+            balance_before
+        );
+    }
 
-    let new_order_ix = create_new_multiple_order_instruction(
+    let market_data = sdk.client.get_account(market).await.unwrap().data;
+    let (header_bytes, bytes) = market_data.split_at(size_of::<MarketHeader>());
+    let header = Box::new(MarketHeader::load_bytes(header_bytes).unwrap());
+    let full_market = load_with_dispatch(&header.market_size_params, bytes).unwrap();
+    let expected_quotes = quotes(98.0, 102.0);
+    for (side, expected) in [
+        (Side::Bid, &expected_quotes.bids),
+        (Side::Ask, &expected_quotes.asks),
+    ] {
+        let prices = full_market
+            .inner
+            .get_book(side)
+            .iter()
+            .map(|(order_id, _)| order_id.price_in_ticks.as_u64())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            prices,
+            expected
+                .iter()
+                .map(|o| o.price_in_ticks)
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_phoenix_post_only_status() {
+    let (phoenix_client, ctx) = bootstrap_default(0).await;
+    let second_maker = get_new_maker(&phoenix_client, &ctx, 1_000_000, 1_000_000).await;
+    let PhoenixTestClient {
+        ctx: _,
+        sdk,
+        meta,
         market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
-    );
+    } = &phoenix_client;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let base_mint = &meta.base_mint;
+    let quote_mint = &meta.quote_mint;
+    let authority = sdk.client.payer.pubkey();
+
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_market_status_instruction(
+                &authority,
+                market,
+                MarketStatus::PostOnly,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // Makers can still build the book
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Ask,
+                    meta.float_price_to_ticks_rounded_down(101.0),
+                    meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                ),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &second_maker.user.pubkey(),
+                    base_mint,
+                    quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit: meta.quote_units_to_quote_lots(1000.0),
+                        base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(10.0),
+                    },
+                ),
+                create_new_multiple_order_instruction(
+                    market,
+                    &second_maker.user.pubkey(),
+                    base_mint,
+                    quote_mint,
+                    &MultipleOrderPacket::new_default(
+                        vec![CondensedOrder::new_default(
+                            meta.float_price_to_ticks_rounded_down(99.0),
+                            meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                        )],
+                        vec![CondensedOrder::new_default(
+                            meta.float_price_to_ticks_rounded_down(102.0),
+                            meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                        )],
+                    ),
+                ),
+            ],
+            vec![&second_maker.user],
+        )
+        .await
+        .unwrap();
 
+    // Any order that would match is rejected, whether or not it withdraws its fill
+    let crossing_bid = |use_only_deposited_funds| {
+        OrderPacket::new_limit_order(
+            Side::Bid,
+            meta.float_price_to_ticks_rounded_down(101.0),
+            meta.raw_base_units_to_base_lots_rounded_down(1.0),
+            SelfTradeBehavior::Abort,
+            None,
+            0,
+            use_only_deposited_funds,
+        )
+    };
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_new_order_with_free_funds_instruction(
+                market,
+                &second_maker.user.pubkey(),
+                &crossing_bid(true),
+            )],
+            vec![&second_maker.user],
+        )
         .await
         .is_err());
-
-    // Ensure we can't place orders in cross against themselves, different variation
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(29.0),
-                meta.raw_base_units_to_base_lots_rounded_down(1.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(9.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(19.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(30.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(25.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-    );
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
-    );
-
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &second_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &crossing_bid(false),
+            )],
+            vec![&second_maker.user],
+        )
+        .await
+        .is_err());
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &OrderPacket::new_ioc_by_lots(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(101.0),
+                    meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                    SelfTradeBehavior::Abort,
+                    None,
+                    0,
+                    false,
+                ),
+            )],
+            vec![&default_taker.user],
+        )
         .await
         .is_err());
 
-    // Add limit orders to the book from the second maker
-    let limit_buy_params = OrderPacket::new_limit_order(
+    // Once the market is active, the same order fills
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_market_status_instruction(
+                &authority,
+                market,
+                MarketStatus::Active,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_with_free_funds_instruction(
+                market,
+                &second_maker.user.pubkey(),
+                &crossing_bid(true),
+            )],
+            vec![&second_maker.user],
+        )
+        .await
+        .unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.asks.len(), 1);
+}
+
+#[tokio::test]
+async fn test_phoenix_change_taking_disabled() {
+    let (phoenix_client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient {
+        ctx: _,
+        sdk,
+        meta,
+        market,
+    } = &phoenix_client;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
+    let base_mint = &meta.base_mint;
+    let quote_mint = &meta.quote_mint;
+    let authority = sdk.client.payer.pubkey();
+    let get_status = || async move {
+        let data = sdk.client.get_account_data(market).await.unwrap();
+        MarketStatus::from(
+            MarketHeader::load_bytes(&data[..size_of::<MarketHeader>()])
+                .unwrap()
+                .status,
+        )
+    };
+    let ioc_bid = OrderPacket::new_ioc_by_lots(
         Side::Bid,
-        meta.float_price_to_ticks_rounded_down(10.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
+        meta.float_price_to_ticks_rounded_down(101.0),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        SelfTradeBehavior::Abort,
         None,
         0,
         false,
     );
 
-    let limit_sell_params = OrderPacket::new_limit_order(
-        Side::Ask,
-        meta.float_price_to_ticks_rounded_down(20.0),
-        meta.raw_base_units_to_base_lots_rounded_down(10.0),
-        SelfTradeBehavior::CancelProvide,
-        None,
-        0,
-        false,
-    );
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Ask,
+                    meta.float_price_to_ticks_rounded_down(101.0),
+                    meta.raw_base_units_to_base_lots_rounded_down(2.0),
+                ),
+            )],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
 
-    for params in [limit_buy_params, limit_sell_params] {
-        let new_order_ix = create_new_order_instruction(
-            market,
-            &second_maker.user.pubkey(),
-            base_mint,
-            quote_mint,
-            &params,
-        );
+    let send_ioc_bid = |compute_unit_limit: u32| async move {
         sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&second_maker.user])
+            .sign_send_instructions(
+                vec![
+                    ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
+                    create_new_order_instruction(
+                        market,
+                        &default_taker.user.pubkey(),
+                        base_mint,
+                        quote_mint,
+                        &ioc_bid,
+                    ),
+                ],
+                vec![&default_taker.user],
+            )
             .await
-            .unwrap();
-    }
-
-    // Ensure we can't place orders in cross against the existing book
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(8.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(9.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(10.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(11.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-    );
+    };
 
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
-    );
+    // Disabling taking does not change the market status
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_taking_disabled_instruction(
+                &authority, market, true,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert_eq!(get_status().await, MarketStatus::Active);
 
+    // IOC orders and crossing limit orders are rejected
+    assert!(send_ioc_bid(400_000).await.is_err());
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(101.0),
+                    meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                ),
+            )],
+            vec![&default_taker.user],
+        )
         .await
         .is_err());
 
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(20.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(9.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![],
-    );
+    // Limit orders that do not cross can still be placed
+    sdk.client
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &OrderPacket::new_limit_order_default(
+                    Side::Bid,
+                    meta.float_price_to_ticks_rounded_down(99.0),
+                    meta.raw_base_units_to_base_lots_rounded_down(1.0),
+                ),
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .unwrap();
 
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
-    );
+    // Pausing and reactivating the market does not re-enable taking
+    for status in [MarketStatus::Paused, MarketStatus::Active] {
+        sdk.client
+            .sign_send_instructions(
+                vec![create_change_market_status_instruction(
+                    &authority, market, status,
+                )],
+                vec![],
+            )
+            .await
+            .unwrap();
+    }
+    assert_eq!(get_status().await, MarketStatus::Active);
+    assert!(send_ioc_bid(400_001).await.is_err());
 
+    // Only the market authority can change the flag
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_change_taking_disabled_instruction(
+                &default_taker.user.pubkey(),
+                market,
+                false,
+            )],
+            vec![&default_taker.user],
+        )
         .await
         .is_err());
 
-    // Check that we use all our free funds first on a normal place multiple
-    // Currently have 20 base units and 170 quote units available
-    let multiple_order_packet = MultipleOrderPacket::new_default(
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(5.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(4.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(3.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(5.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-            CondensedOrder::new_default(
-                //this order is all of the extra quote lots we need to deposit
-                meta.float_price_to_ticks_rounded_down(4.0),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            ),
-        ],
-        vec![
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(100.0),
-                meta.raw_base_units_to_base_lots_rounded_down(5.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(105.0),
-                meta.raw_base_units_to_base_lots_rounded_down(5.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(100.0),
-                meta.raw_base_units_to_base_lots_rounded_down(5.0),
-            ),
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(103.0),
-                meta.raw_base_units_to_base_lots_rounded_down(5.0),
-            ),
-            CondensedOrder::new_default(
-                //this order is all of the extra base lots we need to deposit
-                meta.float_price_to_ticks_rounded_down(102.0),
-                meta.raw_base_units_to_base_lots_rounded_down(5.0),
-            ),
-        ],
-    );
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
-    );
-
-    let base_balance_start = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-
+    // Once taking is enabled again, the IOC order fills
     sdk.client
-        .sign_send_instructions(vec![new_order_ix], vec![&default_maker.user])
+        .sign_send_instructions(
+            vec![create_change_taking_disabled_instruction(
+                &authority, market, false,
+            )],
+            vec![],
+        )
         .await
         .unwrap();
+    send_ioc_bid(400_002).await.unwrap();
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
+    assert_eq!(
+        market_state
+            .orderbook
+            .asks
+            .iter()
+            .map(|(_, order)| order.num_base_lots)
+            .sum::<u64>(),
+        meta.raw_base_units_to_base_lots_rounded_down(1.0)
+    );
+}
 
-    let base_balance_end = get_token_balance(&sdk.client, default_maker.base_ata).await;
-    let quote_balance_end = get_token_balance(&sdk.client, default_maker.quote_ata).await;
-    // Check that we only used an extra 40 quote units and 5 base units
-    assert_eq!(base_balance_start - base_balance_end, 5000000000);
-    assert_eq!(quote_balance_start - quote_balance_end, 40000000);
-
-    ctx.warp_to_slot(3).unwrap();
-
-    // Cancel orders for both makers to return the orderbook to empty
-    let cancel_order_ix =
-        create_cancel_all_order_with_free_funds_instruction(market, &default_maker.user.pubkey());
+#[tokio::test]
+async fn test_phoenix_collect_fees_split() {
+    let (mut client, ctx) = bootstrap_default(5).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        admin,
+        mint_authority,
+    } = &ctx;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
 
+    sdk.set_payer(clone_keypair(&default_maker.user));
+    let limit_order = OrderPacket::new_limit_order_default(
+        Side::Bid,
+        meta.float_price_to_ticks_rounded_down(100.0),
+        1000,
+    );
     sdk.client
-        .sign_send_instructions(vec![cancel_order_ix], vec![&default_maker.user])
-        .await
-        .unwrap();
-
-    let cancel_order_ix =
-        create_cancel_all_order_with_free_funds_instruction(market, &second_maker.user.pubkey());
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_maker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &limit_order,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
 
+    sdk.client.set_payer(&default_taker.user.pubkey()).unwrap();
+    let taker_order = OrderPacket::new_ioc_sell_with_limit_price(
+        meta.float_price_to_ticks_rounded_down(100.0),
+        1000,
+        SelfTradeBehavior::Abort,
+        None,
+        0,
+        false,
+    );
     sdk.client
-        .sign_send_instructions(vec![cancel_order_ix], vec![&second_maker.user])
+        .sign_send_instructions(
+            vec![create_new_order_instruction(
+                market,
+                &default_taker.user.pubkey(),
+                base_mint,
+                quote_mint,
+                &taker_order,
+            )],
+            vec![],
+        )
         .await
         .unwrap();
+    sdk.client.set_payer(&admin.pubkey()).unwrap();
 
-    // Send 21 orders on each side to verify there is enough compute to do so (this is the upper bound due to the transaction size)
-    let bids = (1..22)
-        .map(|i| {
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(100.0 - (i as f64 * 0.1)),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            )
-        })
-        .collect::<Vec<_>>();
-    let asks = (1..22)
-        .map(|i| {
-            CondensedOrder::new_default(
-                meta.float_price_to_ticks_rounded_down(100.0 + (i as f64 * 0.1)),
-                meta.raw_base_units_to_base_lots_rounded_down(10.0),
-            )
-        })
-        .collect::<Vec<_>>();
-
-    let multiple_order_packet = MultipleOrderPacket::new_default(bids, asks);
+    let treasury = setup_account(
+        &sdk.client,
+        mint_authority,
+        meta.base_mint,
+        meta.quote_mint,
+        0,
+        0,
+    )
+    .await;
+    let referrer = setup_account(
+        &sdk.client,
+        mint_authority,
+        meta.base_mint,
+        meta.quote_mint,
+        0,
+        0,
+    )
+    .await;
 
-    let byte_len = multiple_order_packet.try_to_vec().unwrap().len();
-    assert_eq!(byte_len, 766);
+    let splits = [
+        FeeSplit {
+            recipient: treasury.user.pubkey(),
+            bps: 7000,
+        },
+        FeeSplit {
+            recipient: referrer.user.pubkey(),
+            bps: 2000,
+        },
+    ];
+    assert!(
+        sdk.client
+            .sign_send_instructions(
+                vec![create_collect_fees_split_instruction(
+                    &admin.pubkey(),
+                    market,
+                    quote_mint,
+                    &spl_token::id(),
+                    &splits,
+                )],
+                vec![admin],
+            )
+            .await
+            .is_err(),
+        "Fee split bps must sum to 10000"
+    );
 
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &default_maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &multiple_order_packet,
+    let splits = [
+        FeeSplit {
+            recipient: treasury.user.pubkey(),
+            bps: 7000,
+        },
+        FeeSplit {
+            recipient: referrer.user.pubkey(),
+            bps: 3000,
+        },
+    ];
+    assert!(
+        sdk.client
+            .sign_send_instructions(
+                vec![create_collect_fees_split_instruction(
+                    &default_taker.user.pubkey(),
+                    market,
+                    quote_mint,
+                    &spl_token::id(),
+                    &splits,
+                )],
+                vec![&default_taker.user],
+            )
+            .await
+            .is_err(),
+        "Only the market authority can split fees"
     );
 
+    let treasury_ata = get_associated_token_address(&treasury.user.pubkey(), quote_mint);
+    let referrer_ata = get_associated_token_address(&referrer.user.pubkey(), quote_mint);
+    let quote_vault = get_vault_address(market, quote_mint).0;
+    let quote_balance_start = get_token_balance(&sdk.client, quote_vault).await;
+
     sdk.client
         .sign_send_instructions(
-            vec![
-                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
-                new_order_ix,
-            ],
-            vec![&default_maker.user],
+            vec![create_collect_fees_split_instruction(
+                &admin.pubkey(),
+                market,
+                quote_mint,
+                &spl_token::id(),
+                &splits,
+            )],
+            vec![admin],
         )
         .await
         .unwrap();
+
+    let quote_balance_end = get_token_balance(&sdk.client, quote_vault).await;
+    let treasury_balance = get_token_balance(&sdk.client, treasury_ata).await;
+    let referrer_balance = get_token_balance(&sdk.client, referrer_ata).await;
+
+    let fees_collected = quote_balance_start - quote_balance_end;
+    assert_eq!(fees_collected, 50000);
+    let fee_lots = fees_collected / meta.quote_atoms_per_quote_lot;
+    assert_eq!(
+        referrer_balance,
+        fee_lots * 3000 / 10000 * meta.quote_atoms_per_quote_lot
+    );
+    assert_eq!(treasury_balance, fees_collected - referrer_balance);
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -3324,15 +7628,14 @@ async fn test_phoenix_basic_with_raw_base_unit_adjustment() {
     let tick_size_in_quote_lots_per_base_unit = 10; // base_unit is BaseUnit (adjusted)
     let base_lot_per_base_unit = 10; // base_unit is BaseUnit (adjusted)
 
-    let (mut client, ctx) = bootstrap_with_parameters(
-        1_000_000,
-        base_lot_per_base_unit,
+    let (mut client, ctx) = bootstrap_with_parameters(BootstrapParams {
+        num_quote_lots_per_quote_unit: 1_000_000,
+        num_base_lots_per_base_unit: base_lot_per_base_unit,
         tick_size_in_quote_lots_per_base_unit,
-        5,
-        6,
-        0,
-        Some(raw_base_units_per_base_unit as u32),
-    )
+        base_decimals: 5,
+        raw_base_units_per_base_unit: Some(raw_base_units_per_base_unit as u32),
+        ..Default::default()
+    })
     .await;
     let PhoenixTestContext {
         default_maker,
@@ -3344,6 +7647,14 @@ async fn test_phoenix_basic_with_raw_base_unit_adjustment() {
         sdk, market, meta, ..
     } = &mut client;
 
+    let market_account_data = (sdk.client.get_account_data(market)).await.unwrap();
+    let header =
+        MarketHeader::load_bytes(&market_account_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(
+        header.get_raw_base_units_per_base_unit() as u64,
+        raw_base_units_per_base_unit
+    );
+
     mint_tokens(
         &sdk.client,
         &ctx.mint_authority,
@@ -3516,32 +7827,205 @@ async fn test_phoenix_place_order_quiet_failure() {
 
     sdk.set_payer(clone_keypair(&maker.user));
 
-    let base_balance_start = get_token_balance(&sdk.client, maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, maker.quote_ata).await;
-    println!("Base balance start: {}", base_balance_start);
-    println!("Quote balance start: {}", quote_balance_start);
+    let base_balance_start = get_token_balance(&sdk.client, maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, maker.quote_ata).await;
+    println!("Base balance start: {}", base_balance_start);
+    println!("Quote balance start: {}", quote_balance_start);
+
+    println!("Depositing 3 SOL and 3 USDC");
+    let deposit_ix = create_deposit_funds_instruction(
+        market,
+        &maker.user.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &DepositParams {
+            quote_lots_to_deposit: meta.quote_units_to_quote_lots(3.0),
+            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(3.0),
+        },
+    );
+    sdk.client
+        .sign_send_instructions(vec![deposit_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    println!("Placing ask order for 97 SOL (deposited funds + tokens)");
+    let params = OrderPacket::Limit {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(97_f64)),
+        self_trade_behavior: SelfTradeBehavior::Abort,
+        match_limit: None,
+        client_order_id: 0,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
+    };
+
+    let new_order_ix =
+        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+
+    sdk.client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    let base_balance = get_token_balance(&sdk.client, maker.base_ata).await;
+    assert_eq!(base_balance, 3e9 as u64, "Order failed to deposit 97 SOL");
+
+    println!("Placing ask order for 1 SOL");
+    let params = OrderPacket::Limit {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
+        self_trade_behavior: SelfTradeBehavior::Abort,
+        match_limit: None,
+        client_order_id: 0,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
+    };
+    let new_order_ix =
+        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+
+    sdk.client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    println!("Placing ask order (using only deposited funds) for 1 SOL");
+
+    let deposit_ix = create_deposit_funds_instruction(
+        market,
+        &maker.user.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &DepositParams {
+            quote_lots_to_deposit: 0,
+            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(1.0),
+        },
+    );
+
+    let params = OrderPacket::Limit {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
+        self_trade_behavior: SelfTradeBehavior::Abort,
+        match_limit: None,
+        client_order_id: 0,
+        use_only_deposited_funds: true,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
+    };
+    let new_order_ix =
+        create_new_order_with_free_funds_instruction(market, &maker.user.pubkey(), &params);
+
+    sdk.client
+        .sign_send_instructions(vec![deposit_ix, new_order_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    let market_start = sdk.get_market_orderbook(market).await.unwrap();
+    assert_eq!(market_start.asks.len(), 3);
+
+    // This order should fail silently
+    let params = OrderPacket::Limit {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
+        self_trade_behavior: SelfTradeBehavior::Abort,
+        match_limit: None,
+        client_order_id: 0,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
+    };
+
+    let new_order_ix =
+        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+
+    sdk.client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    let market_end = sdk.get_market_orderbook(market).await.unwrap();
+    assert_eq!(
+        market_start.asks.len(),
+        market_end.asks.len(),
+        "Order should have failed silently"
+    );
+
+    // This order should fail
+    let params = OrderPacket::Limit {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
+        self_trade_behavior: SelfTradeBehavior::Abort,
+        match_limit: None,
+        client_order_id: 0,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
+    };
+    let new_order_ix =
+        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+
+    assert!(
+        sdk.client
+            .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+            .await
+            .is_err(),
+        "Order should have failed"
+    );
 
-    println!("Depositing 3 SOL and 3 USDC");
-    let deposit_ix = create_deposit_funds_instruction(
-        market,
-        &maker.user.pubkey(),
-        &meta.base_mint,
-        &meta.quote_mint,
-        &DepositParams {
-            quote_lots_to_deposit: meta.quote_units_to_quote_lots(3.0),
-            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(3.0),
-        },
+    let market_end = sdk.get_market_orderbook(market).await.unwrap();
+    assert_eq!(
+        market_start.asks.len(),
+        market_end.asks.len(),
+        "Order count should be the same"
     );
+
+    println!("Cancelling all orders");
     sdk.client
-        .sign_send_instructions(vec![deposit_ix], vec![&maker.user])
+        .sign_send_instructions(
+            vec![sdk.get_cancel_all_ix(market).unwrap()],
+            vec![&maker.user],
+        )
         .await
         .unwrap();
 
-    println!("Placing ask order for 97 SOL (deposited funds + tokens)");
+    let base_balance_end = get_token_balance(&sdk.client, maker.base_ata).await;
+    assert_eq!(
+        base_balance_start, base_balance_end as u64,
+        "Balances should not change"
+    );
+
+    println!("Placing bid order for 997 USDC (deposited funds + tokens)");
+
     let params = OrderPacket::Limit {
-        side: Side::Ask,
+        side: Side::Bid,
         price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(97_f64)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(99.7_f64)),
         self_trade_behavior: SelfTradeBehavior::Abort,
         match_limit: None,
         client_order_id: 0,
@@ -3549,6 +8033,9 @@ async fn test_phoenix_place_order_quiet_failure() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
     };
 
     let new_order_ix =
@@ -3559,13 +8046,17 @@ async fn test_phoenix_place_order_quiet_failure() {
         .await
         .unwrap();
 
-    let base_balance = get_token_balance(&sdk.client, maker.base_ata).await;
-    assert_eq!(base_balance, 3e9 as u64, "Order failed to deposit 97 SOL");
+    let quote_balance = get_token_balance(&sdk.client, maker.quote_ata).await;
+    assert_eq!(
+        quote_balance, 3e6 as u64,
+        "Order failed to deposit 997 USDC"
+    );
+
+    println!("Placing bid order for 1 USDC");
 
-    println!("Placing ask order for 1 SOL");
     let params = OrderPacket::Limit {
-        side: Side::Ask,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        side: Side::Bid,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(1.0)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
         self_trade_behavior: SelfTradeBehavior::Abort,
         match_limit: None,
@@ -3574,7 +8065,11 @@ async fn test_phoenix_place_order_quiet_failure() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
     };
+
     let new_order_ix =
         create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
 
@@ -3583,22 +8078,21 @@ async fn test_phoenix_place_order_quiet_failure() {
         .await
         .unwrap();
 
-    println!("Placing ask order (using only deposited funds) for 1 SOL");
-
+    println!("Placing bid order (using only deposited funds) for 1 USDC");
     let deposit_ix = create_deposit_funds_instruction(
         market,
         &maker.user.pubkey(),
         &meta.base_mint,
         &meta.quote_mint,
         &DepositParams {
-            quote_lots_to_deposit: 0,
-            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(1.0),
+            quote_lots_to_deposit: meta.quote_units_to_quote_lots(1.0),
+            base_lots_to_deposit: 0,
         },
     );
 
     let params = OrderPacket::Limit {
-        side: Side::Ask,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
+        side: Side::Bid,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(1.0)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
         self_trade_behavior: SelfTradeBehavior::Abort,
         match_limit: None,
@@ -3607,6 +8101,9 @@ async fn test_phoenix_place_order_quiet_failure() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
     };
     let new_order_ix =
         create_new_order_with_free_funds_instruction(market, &maker.user.pubkey(), &params);
@@ -3617,11 +8114,11 @@ async fn test_phoenix_place_order_quiet_failure() {
         .unwrap();
 
     let market_start = sdk.get_market_orderbook(market).await.unwrap();
-    assert_eq!(market_start.asks.len(), 3);
+    assert_eq!(market_start.bids.len(), 3);
 
     // This order should fail silently
     let params = OrderPacket::Limit {
-        side: Side::Ask,
+        side: Side::Bid,
         price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
         self_trade_behavior: SelfTradeBehavior::Abort,
@@ -3631,6 +8128,9 @@ async fn test_phoenix_place_order_quiet_failure() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: true,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
     };
 
     let new_order_ix =
@@ -3641,16 +8141,9 @@ async fn test_phoenix_place_order_quiet_failure() {
         .await
         .unwrap();
 
-    let market_end = sdk.get_market_orderbook(market).await.unwrap();
-    assert_eq!(
-        market_start.asks.len(),
-        market_end.asks.len(),
-        "Order should have failed silently"
-    );
-
     // This order should fail
     let params = OrderPacket::Limit {
-        side: Side::Ask,
+        side: Side::Bid,
         price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
         self_trade_behavior: SelfTradeBehavior::Abort,
@@ -3660,6 +8153,9 @@ async fn test_phoenix_place_order_quiet_failure() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: false,
+        reduce_only: false,
+        place_partial: false,
+        valid_for_slots: None,
     };
     let new_order_ix =
         create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
@@ -3673,10 +8169,11 @@ async fn test_phoenix_place_order_quiet_failure() {
     );
 
     let market_end = sdk.get_market_orderbook(market).await.unwrap();
+
     assert_eq!(
-        market_start.asks.len(),
-        market_end.asks.len(),
-        "Order count should be the same"
+        market_start.bids.len(),
+        market_end.bids.len(),
+        "Order should have failed silently"
     );
 
     println!("Cancelling all orders");
@@ -3688,176 +8185,343 @@ async fn test_phoenix_place_order_quiet_failure() {
         .await
         .unwrap();
 
-    let base_balance_end = get_token_balance(&sdk.client, maker.base_ata).await;
+    let quote_balance_end = get_token_balance(&sdk.client, maker.quote_ata).await;
     assert_eq!(
-        base_balance_start, base_balance_end as u64,
+        quote_balance_start, quote_balance_end,
         "Balances should not change"
     );
+}
+
+/// This tests that a user can place multiple orders that fail silently even if the user
+/// is out of funds.
+#[tokio::test]
+async fn test_phoenix_multiple_orders_fail_silently_basic() {
+    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
+
+    let maker = get_new_maker(&client, &phoenix_ctx, 99, 901).await;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
+
+    sdk.set_payer(clone_keypair(&maker.user));
+
+    let deposit_ix = create_deposit_funds_instruction(
+        market,
+        &maker.user.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &DepositParams {
+            quote_lots_to_deposit: meta.quote_units_to_quote_lots(312.0),
+            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(39.0),
+        },
+    );
+    sdk.client
+        .sign_send_instructions(vec![deposit_ix], vec![&maker.user])
+        .await
+        .unwrap();
+
+    let mut bids = vec![];
+    for i in 0..10 {
+        bids.push(CondensedOrder {
+            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0 - 0.01 * i as f64),
+            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        });
+    }
+
+    let mut asks = vec![];
+
+    for i in 0..10 {
+        asks.push(CondensedOrder {
+            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0 + 0.01 * (i + 1) as f64),
+            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        });
+    }
+
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
+    };
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .is_err());
+
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndFailOnCross,
+    };
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .is_ok());
+
+    let market = sdk.get_market_orderbook(market).await.unwrap();
+
+    // Unflip the bits of the bid order_sequence_numbers to get the true order of placement
+    let bid_sequence_numbers = market
+        .bids
+        .iter()
+        .sorted_by(|a, b| a.0.price_in_ticks.cmp(&b.0.price_in_ticks))
+        .map(|order| !order.0.order_sequence_number)
+        .collect::<Vec<u64>>();
+
+    assert!(
+        bid_sequence_numbers
+            .iter()
+            .zip(bid_sequence_numbers.iter().skip(1))
+            .all(|(a, b)| a > b),
+        "Bids with higher prices should have lower sequence numbers"
+    );
+
+    let ask_sequence_numbers = market
+        .asks
+        .iter()
+        .sorted_by(|a, b| a.0.price_in_ticks.cmp(&b.0.price_in_ticks))
+        .map(|order| order.0.order_sequence_number)
+        .collect::<Vec<u64>>();
+
+    assert!(
+        ask_sequence_numbers
+            .iter()
+            .zip(ask_sequence_numbers.iter().skip(1))
+            .all(|(a, b)| a < b),
+        "Asks with lower prices should have lower sequence numbers"
+    );
+    assert_eq!(market.bids.len(), 9);
+    assert_eq!(market.asks.len(), 9);
+}
+
+/// This tests that placing multiple orders will fail if the input orders cross
+#[tokio::test]
+async fn test_phoenix_multiple_orders_crossing_order_input() {
+    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
+
+    // 100 SOL, 1_000 USDC
+    let maker = get_new_maker(&client, &phoenix_ctx, 10, 100).await;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
+
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
+
+    sdk.set_payer(clone_keypair(&maker.user));
+
+    let base_balance_start = get_token_balance(&sdk.client, maker.base_ata).await;
+    let quote_balance_start = get_token_balance(&sdk.client, maker.quote_ata).await;
+    println!("Base balance start: {}", base_balance_start);
+    println!("Quote balance start: {}", quote_balance_start);
 
-    println!("Placing bid order for 997 USDC (deposited funds + tokens)");
+    let bids = vec![CondensedOrder {
+        price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
+        size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+    }];
 
-    let params = OrderPacket::Limit {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(99.7_f64)),
-        self_trade_behavior: SelfTradeBehavior::Abort,
-        match_limit: None,
-        client_order_id: 0,
-        use_only_deposited_funds: false,
+    let asks = vec![CondensedOrder {
+        price_in_ticks: meta.float_price_to_ticks_rounded_down(9.99),
+        size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: true,
+    }];
+
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
     };
 
-    let new_order_ix =
-        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
 
-    sdk.client
+    assert!(sdk
+        .client
         .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
         .await
-        .unwrap();
+        .is_err());
+}
 
-    let quote_balance = get_token_balance(&sdk.client, maker.quote_ata).await;
-    assert_eq!(
-        quote_balance, 3e6 as u64,
-        "Order failed to deposit 997 USDC"
-    );
+/// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
+#[tokio::test]
+async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid() {
+    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
 
-    println!("Placing bid order for 1 USDC");
+    let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
+    let PhoenixTestClient {
+        sdk, market, meta, ..
+    } = &mut client;
 
-    let params = OrderPacket::Limit {
+    let quote_mint = &meta.quote_mint;
+    let base_mint = &meta.base_mint;
+
+    sdk.set_payer(clone_keypair(&maker.user));
+
+    // Create limit orders at 9.96 and 10.01
+    let bid_order_packet = OrderPacket::PostOnly {
         side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(1.0)),
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(9.96)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        self_trade_behavior: SelfTradeBehavior::Abort,
-        match_limit: None,
         client_order_id: 0,
+        reject_post_only: true,
         use_only_deposited_funds: false,
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: true,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
-
-    let new_order_ix =
-        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
-
-    sdk.client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
-        .await
-        .unwrap();
-
-    println!("Placing bid order (using only deposited funds) for 1 USDC");
-    let deposit_ix = create_deposit_funds_instruction(
+    let bid_ix = create_new_order_instruction(
         market,
         &maker.user.pubkey(),
-        &meta.base_mint,
-        &meta.quote_mint,
-        &DepositParams {
-            quote_lots_to_deposit: meta.quote_units_to_quote_lots(1.0),
-            base_lots_to_deposit: 0,
-        },
+        &base_mint,
+        &quote_mint,
+        &bid_order_packet,
     );
 
-    let params = OrderPacket::Limit {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(1.0)),
+    let ask_order_packet = OrderPacket::PostOnly {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.01)),
         num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        self_trade_behavior: SelfTradeBehavior::Abort,
-        match_limit: None,
         client_order_id: 0,
-        use_only_deposited_funds: true,
+        reject_post_only: true,
+        use_only_deposited_funds: false,
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: true,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
-    let new_order_ix =
-        create_new_order_with_free_funds_instruction(market, &maker.user.pubkey(), &params);
+    let ask_ix = create_new_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        &base_mint,
+        &quote_mint,
+        &ask_order_packet,
+    );
 
     sdk.client
-        .sign_send_instructions(vec![deposit_ix, new_order_ix], vec![&maker.user])
+        .sign_send_instructions(vec![bid_ix, ask_ix], vec![&maker.user])
         .await
         .unwrap();
 
-    let market_start = sdk.get_market_orderbook(market).await.unwrap();
-    assert_eq!(market_start.bids.len(), 3);
+    let mut bids = vec![];
 
-    // This order should fail silently
-    let params = OrderPacket::Limit {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
-        self_trade_behavior: SelfTradeBehavior::Abort,
-        match_limit: None,
-        client_order_id: 0,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: true,
+    for i in 0..10 {
+        bids.push(CondensedOrder {
+            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.01 - 0.01 * i as f64),
+            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        });
+    }
+
+    let mut asks = vec![];
+
+    for i in 0..10 {
+        asks.push(CondensedOrder {
+            price_in_ticks: meta.float_price_to_ticks_rounded_up(10.02 + 0.01 * i as f64),
+            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        });
+    }
+
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
     };
 
-    let new_order_ix =
-        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
 
-    sdk.client
+    assert!(sdk
+        .client
         .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
         .await
-        .unwrap();
+        .is_err());
 
-    // This order should fail
-    let params = OrderPacket::Limit {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.0)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(2_f64)),
-        self_trade_behavior: SelfTradeBehavior::Abort,
-        match_limit: None,
-        client_order_id: 0,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndFailOnCross,
     };
-    let new_order_ix =
-        create_new_order_instruction(market, &maker.user.pubkey(), base_mint, quote_mint, &params);
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
 
     assert!(
         sdk.client
             .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
             .await
             .is_err(),
-        "Order should have failed"
-    );
-
-    let market_end = sdk.get_market_orderbook(market).await.unwrap();
-
-    assert_eq!(
-        market_start.bids.len(),
-        market_end.bids.len(),
-        "Order should have failed silently"
-    );
-
-    println!("Cancelling all orders");
-    sdk.client
-        .sign_send_instructions(
-            vec![sdk.get_cancel_all_ix(market).unwrap()],
-            vec![&maker.user],
-        )
-        .await
-        .unwrap();
-
-    let quote_balance_end = get_token_balance(&sdk.client, maker.quote_ata).await;
-    assert_eq!(
-        quote_balance_start, quote_balance_end,
-        "Balances should not change"
+        "Order should fail on cross"
     );
 }
 
-/// This tests that a user can place multiple orders that fail silently even if the user
-/// is out of funds.
+/// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
 #[tokio::test]
-async fn test_phoenix_multiple_orders_fail_silently_basic() {
+async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
     let (mut client, phoenix_ctx) = bootstrap_default(0).await;
 
-    let maker = get_new_maker(&client, &phoenix_ctx, 99, 901).await;
+    let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
     let PhoenixTestClient {
         sdk, market, meta, ..
     } = &mut client;
@@ -3867,25 +8531,61 @@ async fn test_phoenix_multiple_orders_fail_silently_basic() {
 
     sdk.set_payer(clone_keypair(&maker.user));
 
-    let deposit_ix = create_deposit_funds_instruction(
+    // Create limit orders at 9.96 and 10.01
+    let bid_order_packet = OrderPacket::PostOnly {
+        side: Side::Bid,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(9.96)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
+        client_order_id: 0,
+        reject_post_only: true,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+    let bid_ix = create_new_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        &base_mint,
+        &quote_mint,
+        &bid_order_packet,
+    );
+
+    let ask_order_packet = OrderPacket::PostOnly {
+        side: Side::Ask,
+        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.01)),
+        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
+        client_order_id: 0,
+        reject_post_only: true,
+        use_only_deposited_funds: false,
+        last_valid_slot: None,
+        last_valid_unix_timestamp_in_seconds: None,
+        fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
+    };
+    let ask_ix = create_new_order_instruction(
         market,
         &maker.user.pubkey(),
-        &meta.base_mint,
-        &meta.quote_mint,
-        &DepositParams {
-            quote_lots_to_deposit: meta.quote_units_to_quote_lots(312.0),
-            base_lots_to_deposit: meta.raw_base_units_to_base_lots_rounded_down(39.0),
-        },
+        &base_mint,
+        &quote_mint,
+        &ask_order_packet,
     );
+
     sdk.client
-        .sign_send_instructions(vec![deposit_ix], vec![&maker.user])
+        .sign_send_instructions(vec![bid_ix, ask_ix], vec![&maker.user])
         .await
         .unwrap();
 
     let mut bids = vec![];
+
     for i in 0..10 {
         bids.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0 - 0.01 * i as f64),
+            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.01 - 0.01 * i as f64),
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
@@ -3896,7 +8596,7 @@ async fn test_phoenix_multiple_orders_fail_silently_basic() {
 
     for i in 0..10 {
         asks.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0 + 0.01 * (i + 1) as f64),
+            price_in_ticks: meta.float_price_to_ticks_rounded_up(10.02 + 0.01 * i as f64),
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
@@ -3930,7 +8630,7 @@ async fn test_phoenix_multiple_orders_fail_silently_basic() {
         bids: bids.clone(),
         client_order_id: None,
         failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndFailOnCross,
+            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndAmendOnCross,
     };
 
     let new_order_ix = create_new_multiple_order_instruction(
@@ -3948,102 +8648,41 @@ async fn test_phoenix_multiple_orders_fail_silently_basic() {
         .is_ok());
 
     let market = sdk.get_market_orderbook(market).await.unwrap();
-
-    // Unflip the bits of the bid order_sequence_numbers to get the true order of placement
-    let bid_sequence_numbers = market
+    let market_bids = market
         .bids
         .iter()
-        .sorted_by(|a, b| a.0.price_in_ticks.cmp(&b.0.price_in_ticks))
-        .map(|order| !order.0.order_sequence_number)
-        .collect::<Vec<u64>>();
+        .map(|(o, _)| o.price_in_ticks.into())
+        .collect::<Vec<_>>();
+    for bid in bids {
+        if bid.price_in_ticks >= 1001 {
+            assert!(!market_bids.contains(&bid.price_in_ticks));
+        } else {
+            assert!(market_bids.contains(&bid.price_in_ticks));
+        }
+    }
 
-    assert!(
-        bid_sequence_numbers
-            .iter()
-            .zip(bid_sequence_numbers.iter().skip(1))
-            .all(|(a, b)| a > b),
-        "Bids with higher prices should have lower sequence numbers"
-    );
+    assert_eq!(market_bids.len(), 11);
+    assert!(market_bids.iter().filter(|&x| *x == 1000).count() == 2);
 
-    let ask_sequence_numbers = market
+    let market_asks = market
         .asks
         .iter()
-        .sorted_by(|a, b| a.0.price_in_ticks.cmp(&b.0.price_in_ticks))
-        .map(|order| order.0.order_sequence_number)
-        .collect::<Vec<u64>>();
-
-    assert!(
-        ask_sequence_numbers
-            .iter()
-            .zip(ask_sequence_numbers.iter().skip(1))
-            .all(|(a, b)| a < b),
-        "Asks with lower prices should have lower sequence numbers"
-    );
-    assert_eq!(market.bids.len(), 9);
-    assert_eq!(market.asks.len(), 9);
-}
-
-/// This tests that placing multiple orders will fail if the input orders cross
-#[tokio::test]
-async fn test_phoenix_multiple_orders_crossing_order_input() {
-    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
-
-    // 100 SOL, 1_000 USDC
-    let maker = get_new_maker(&client, &phoenix_ctx, 10, 100).await;
-    let PhoenixTestClient {
-        sdk, market, meta, ..
-    } = &mut client;
-
-    let quote_mint = &meta.quote_mint;
-    let base_mint = &meta.base_mint;
-
-    sdk.set_payer(clone_keypair(&maker.user));
-
-    let base_balance_start = get_token_balance(&sdk.client, maker.base_ata).await;
-    let quote_balance_start = get_token_balance(&sdk.client, maker.quote_ata).await;
-    println!("Base balance start: {}", base_balance_start);
-    println!("Quote balance start: {}", quote_balance_start);
-
-    let bids = vec![CondensedOrder {
-        price_in_ticks: meta.float_price_to_ticks_rounded_down(10.0),
-        size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-    }];
-
-    let asks = vec![CondensedOrder {
-        price_in_ticks: meta.float_price_to_ticks_rounded_down(9.99),
-        size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-    }];
-
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
-    };
+        .map(|(o, _)| o.price_in_ticks.into())
+        .collect::<Vec<_>>();
 
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
-    );
+    for ask in asks {
+        println!("{:?}", ask);
+        assert!(market_asks.contains(&ask.price_in_ticks));
+    }
+    assert_eq!(market_asks.len(), 11);
 
-    assert!(sdk
-        .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
-        .await
-        .is_err());
+    assert_eq!(market_asks[0], 1001);
+    assert_eq!(market_bids[0], 1000);
 }
 
 /// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
 #[tokio::test]
-async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid() {
+async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_ask() {
     let (mut client, phoenix_ctx) = bootstrap_default(0).await;
 
     let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
@@ -4067,6 +8706,9 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
     let bid_ix = create_new_order_instruction(
         market,
@@ -4086,6 +8728,9 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
     let ask_ix = create_new_order_instruction(
         market,
@@ -4104,7 +8749,7 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid
 
     for i in 0..10 {
         bids.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.01 - 0.01 * i as f64),
+            price_in_ticks: 994 - i,
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
@@ -4115,7 +8760,7 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid
 
     for i in 0..10 {
         asks.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_up(10.02 + 0.01 * i as f64),
+            price_in_ticks: 995 + i,
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
@@ -4171,7 +8816,7 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_bid
 
 /// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
 #[tokio::test]
-async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
+async fn test_phoenix_multiple_orders_crossing_existing_book_amend_ask() {
     let (mut client, phoenix_ctx) = bootstrap_default(0).await;
 
     let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
@@ -4195,6 +8840,9 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
     let bid_ix = create_new_order_instruction(
         market,
@@ -4214,6 +8862,9 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
         last_valid_slot: None,
         last_valid_unix_timestamp_in_seconds: None,
         fail_silently_on_insufficient_funds: false,
+        max_tick_adjustment: None,
+        require_top_of_book: false,
+        valid_for_slots: None,
     };
     let ask_ix = create_new_order_instruction(
         market,
@@ -4232,18 +8883,20 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
 
     for i in 0..10 {
         bids.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_down(10.01 - 0.01 * i as f64),
+            price_in_ticks: 994 - i,
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
         });
     }
 
+    println!("bids: {:?}", bids);
+
     let mut asks = vec![];
 
     for i in 0..10 {
         asks.push(CondensedOrder {
-            price_in_ticks: meta.float_price_to_ticks_rounded_up(10.02 + 0.01 * i as f64),
+            price_in_ticks: 995 + i,
             size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
             last_valid_slot: None,
             last_valid_unix_timestamp_in_seconds: None,
@@ -4268,347 +8921,646 @@ async fn test_phoenix_multiple_orders_crossing_existing_book_amend_bid() {
 
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .is_err());
+
+    let order_packet = MultipleOrderPacket {
+        asks: asks.clone(),
+        bids: bids.clone(),
+        client_order_id: None,
+        failed_multiple_limit_order_behavior:
+            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndAmendOnCross,
+    };
+
+    let new_order_ix = create_new_multiple_order_instruction(
+        market,
+        &maker.user.pubkey(),
+        base_mint,
+        quote_mint,
+        &order_packet,
+    );
+
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .await
+        .is_ok());
+
+    let market = sdk.get_market_orderbook(market).await.unwrap();
+    let market_bids = market
+        .bids
+        .iter()
+        .map(|(o, _)| o.price_in_ticks.into())
+        .collect::<Vec<_>>();
+    for bid in bids {
+        assert!(market_bids.contains(&bid.price_in_ticks));
+    }
+
+    assert_eq!(market_bids.len(), 11);
+
+    let market_asks = market
+        .asks
+        .iter()
+        .map(|(o, _)| o.price_in_ticks.into())
+        .collect::<Vec<_>>();
+
+    for ask in asks {
+        if ask.price_in_ticks > 996 {
+            assert!(market_asks.contains(&ask.price_in_ticks));
+        }
+    }
+
+    assert!(market_asks.iter().filter(|&x| *x == 997).count() == 3);
+    assert_eq!(market_asks.len(), 11);
+
+    assert_eq!(market_asks[0], 997);
+    assert_eq!(market_bids[0], 996);
+}
+
+#[tokio::test]
+async fn test_phoenix_request_seat_batch() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        admin,
+        ..
+    } = &ctx;
+    let PhoenixTestClient { sdk, market, .. } = &mut client;
+    sdk.client.set_payer(&admin.pubkey()).unwrap();
+
+    // The default maker already has an approved seat, so it is skipped
+    let mut traders = vec![default_maker.user.pubkey()];
+    traders.extend((0..MAX_TRADERS_PER_SEAT_BATCH + 2).map(|_| Keypair::new().pubkey()));
+    let instructions =
+        create_request_seat_batch_instructions(&admin.pubkey(), &admin.pubkey(), market, &traders);
+    assert_eq!(instructions.len(), 2);
+    for instruction in instructions.iter() {
+        sdk.client
+            .sign_send_instructions(vec![instruction.clone()], vec![])
+            .await
+            .unwrap();
+    }
+
+    let market_state = sdk.get_market_state(market).await.unwrap();
+    for trader in traders.iter() {
+        assert!(market_state.traders.contains_key(trader));
+        let seat_account = sdk
+            .client
+            .get_account(&get_seat_address(market, trader).0)
+            .await
+            .unwrap();
+        let seat = Seat::load_bytes(&seat_account.data).unwrap();
+        assert_eq!(seat.approval_status, SeatApprovalStatus::Approved as u64);
+    }
+
+    // Sending the batch again is a no-op
+    sdk.client
+        .sign_send_instructions(vec![instructions[0].clone()], vec![])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_phoenix_create_seat() {
+    let (client, _ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient { sdk, market, .. } = &client;
+    let trader = Keypair::new();
+    airdrop(&sdk.client, &trader.pubkey(), sol(1.0))
+        .await
+        .unwrap();
+    let seat_address = get_seat_address(market, &trader.pubkey()).0;
+
+    // Creating the seat a second time succeeds without changes
+    for _ in 0..2 {
+        sdk.client
+            .sign_send_instructions_with_payer(
+                vec![create_create_seat_instruction(&trader.pubkey(), market)],
+                vec![&trader],
+            )
+            .await
+            .unwrap();
+        let seat_account = sdk.client.get_account(&seat_address).await.unwrap();
+        let seat = Seat::load_bytes(&seat_account.data).unwrap();
+        assert_eq!(seat.approval_status, SeatApprovalStatus::NotApproved as u64);
+        let market_state = sdk.get_market_state(market).await.unwrap();
+        assert!(market_state.traders.contains_key(&trader.pubkey()));
+    }
+}
+
+#[tokio::test]
+async fn test_phoenix_demote_inactive_seat() {
+    let (phoenix_client, phoenix_ctx) = bootstrap_default(0).await;
+    let PhoenixTestClient {
+        mut ctx,
+        sdk,
+        meta,
+        market,
+    } = phoenix_client;
+    let PhoenixTestContext { default_maker, .. } = &phoenix_ctx;
+    let trader = default_maker.user.pubkey();
+    let admin = sdk.client.payer.pubkey();
+    let new_order = |price_in_ticks: u64| {
+        create_new_order_instruction(
+            &market,
+            &trader,
+            &meta.base_mint,
+            &meta.quote_mint,
+            &OrderPacket::new_limit_order_default(Side::Bid, price_in_ticks, 1),
+        )
+    };
+    let get_approval_status = |seat_account: solana_sdk::account::Account| {
+        SeatApprovalStatus::from(
+            Seat::load_bytes(&seat_account.data)
+                .unwrap()
+                .approval_status,
+        )
+    };
+    let seat_address = get_seat_address(&market, &trader).0;
+
+    // Each attempt is signed by a new keypair, since any signer can demote an inactive seat.
+    // Demotion is disabled until the market authority sets a threshold
+    let signer = Keypair::new();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader
+            )],
+            vec![&signer],
+        )
         .await
         .is_err());
 
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndAmendOnCross,
-    };
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
-    );
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_seat_inactivity_threshold_instruction(
+                &admin, &market, 100,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let market_data = sdk.client.get_account(&market).await.unwrap().data;
+    let header = MarketHeader::load_bytes(&market_data[..size_of::<MarketHeader>()]).unwrap();
+    assert_eq!(header.seat_inactivity_threshold_slots, 100);
 
+    // Placing an order counts as activity
+    ctx.warp_to_slot(50).unwrap();
+    sdk.client
+        .sign_send_instructions(vec![new_order(100)], vec![&default_maker.user])
+        .await
+        .unwrap();
+    ctx.warp_to_slot(150).unwrap();
+    let signer = Keypair::new();
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader
+            )],
+            vec![&signer],
+        )
         .await
-        .is_ok());
+        .is_err());
 
-    let market = sdk.get_market_orderbook(market).await.unwrap();
-    let market_bids = market
-        .bids
-        .iter()
-        .map(|(o, _)| o.price_in_ticks.into())
-        .collect::<Vec<_>>();
-    for bid in bids {
-        if bid.price_in_ticks >= 1001 {
-            assert!(!market_bids.contains(&bid.price_in_ticks));
-        } else {
-            assert!(market_bids.contains(&bid.price_in_ticks));
-        }
-    }
+    ctx.warp_to_slot(151).unwrap();
+    let signer = Keypair::new();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader,
+            )],
+            vec![&signer],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_approval_status(sdk.client.get_account(&seat_address).await.unwrap()),
+        SeatApprovalStatus::NotApproved
+    );
 
-    assert_eq!(market_bids.len(), 11);
-    assert!(market_bids.iter().filter(|&x| *x == 1000).count() == 2);
+    // The demoted maker can no longer place limit orders, but the existing order is untouched
+    assert!(sdk
+        .client
+        .sign_send_instructions(vec![new_order(99)], vec![&default_maker.user])
+        .await
+        .is_err());
+    let market_state = sdk.get_market_state(&market).await.unwrap();
+    assert_eq!(market_state.orderbook.bids.len(), 1);
 
-    let market_asks = market
-        .asks
-        .iter()
-        .map(|(o, _)| o.price_in_ticks.into())
-        .collect::<Vec<_>>();
+    // Once the seat is approved again, the maker is active as of the approval
+    sdk.client
+        .sign_send_instructions(
+            vec![create_change_seat_status_instruction(
+                &admin,
+                &market,
+                &trader,
+                SeatApprovalStatus::Approved,
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    let signer = Keypair::new();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader
+            )],
+            vec![&signer],
+        )
+        .await
+        .is_err());
+    sdk.client
+        .sign_send_instructions(vec![new_order(99)], vec![&default_maker.user])
+        .await
+        .unwrap();
 
-    for ask in asks {
-        println!("{:?}", ask);
-        assert!(market_asks.contains(&ask.price_in_ticks));
-    }
-    assert_eq!(market_asks.len(), 11);
+    // Seats approved before activity was tracked have a last active slot of 0. The trader state
+    // follows the trader's key in the market's trader tree.
+    let mut market_account = ctx.banks_client.get_account(market).await.unwrap().unwrap();
+    let trader_state_offset = market_account
+        .data
+        .windows(32)
+        .position(|window| window == trader.as_ref())
+        .unwrap()
+        + 32;
+    let trader_state_bytes = &mut market_account.data
+        [trader_state_offset..trader_state_offset + size_of::<TraderState>()];
+    let mut trader_state = bytemuck::pod_read_unaligned::<TraderState>(trader_state_bytes);
+    assert_ne!(trader_state.last_active_slot, 0);
+    trader_state.last_active_slot = 0;
+    trader_state_bytes.copy_from_slice(bytemuck::bytes_of(&trader_state));
+    ctx.set_account(
+        &market,
+        &solana_sdk::account::AccountSharedData::from(market_account),
+    );
+
+    // An unknown last active slot is not treated as slot 0. The first demotion attempt starts the
+    // inactivity clock instead of demoting the seat
+    ctx.warp_to_slot(1000).unwrap();
+    let signer = Keypair::new();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader,
+            )],
+            vec![&signer],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_approval_status(sdk.client.get_account(&seat_address).await.unwrap()),
+        SeatApprovalStatus::Approved
+    );
+    let signer = Keypair::new();
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader
+            )],
+            vec![&signer],
+        )
+        .await
+        .is_err());
 
-    assert_eq!(market_asks[0], 1001);
-    assert_eq!(market_bids[0], 1000);
+    ctx.warp_to_slot(1101).unwrap();
+    let signer = Keypair::new();
+    sdk.client
+        .sign_send_instructions(
+            vec![create_demote_inactive_seat_instruction(
+                &market,
+                &signer.pubkey(),
+                &trader,
+            )],
+            vec![&signer],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_approval_status(sdk.client.get_account(&seat_address).await.unwrap()),
+        SeatApprovalStatus::NotApproved
+    );
 }
 
-/// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
 #[tokio::test]
-async fn test_phoenix_multiple_orders_crossing_existing_book_ignore_crossing_ask() {
-    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
-
-    let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
+async fn test_phoenix_swap_route() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext {
+        default_maker,
+        default_taker,
+        ..
+    } = &ctx;
     let PhoenixTestClient {
-        sdk, market, meta, ..
-    } = &mut client;
-
-    let quote_mint = &meta.quote_mint;
-    let base_mint = &meta.base_mint;
-
-    sdk.set_payer(clone_keypair(&maker.user));
-
-    // Create limit orders at 9.96 and 10.01
-    let bid_order_packet = OrderPacket::PostOnly {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(9.96)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        client_order_id: 0,
-        reject_post_only: true,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
-    };
-    let bid_ix = create_new_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        &base_mint,
-        &quote_mint,
-        &bid_order_packet,
-    );
-
-    let ask_order_packet = OrderPacket::PostOnly {
-        side: Side::Ask,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.01)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        client_order_id: 0,
-        reject_post_only: true,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
-    };
-    let ask_ix = create_new_order_instruction(
+        ctx: program_test_ctx,
+        sdk,
         market,
-        &maker.user.pubkey(),
-        &base_mint,
-        &quote_mint,
-        &ask_order_packet,
-    );
-
+        meta,
+    } = &mut client;
+    let maker = default_maker.user.pubkey();
+    let taker = default_taker.user.pubkey();
+    let admin = sdk.client.payer.pubkey();
+
+    // Create a second market with the same mints and give the maker a seat on it
+    let second_market = Keypair::new();
+    let mut init_instructions = create_initialize_market_instructions(
+        &second_market.pubkey(),
+        &meta.base_mint,
+        &meta.quote_mint,
+        &spl_token::id(),
+        &admin,
+        InitializeParams {
+            market_size_params: MarketSizeParams {
+                bids_size: BOOK_SIZE as u64,
+                asks_size: BOOK_SIZE as u64,
+                num_seats: NUM_SEATS as u64,
+            },
+            num_quote_lots_per_quote_unit: 100_000,
+            num_base_lots_per_base_unit: 1_000,
+            tick_size_in_quote_lots_per_base_unit: 1_000,
+            taker_fee_bps: 0,
+            fee_collector: admin,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    init_instructions.push(create_change_market_status_instruction(
+        &admin,
+        &second_market.pubkey(),
+        MarketStatus::Active,
+    ));
     sdk.client
-        .sign_send_instructions(vec![bid_ix, ask_ix], vec![&maker.user])
+        .sign_send_instructions_with_payer(init_instructions, vec![&second_market])
+        .await
+        .unwrap();
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_request_seat_authorized_instruction(
+                    &admin,
+                    &admin,
+                    &second_market.pubkey(),
+                    &maker,
+                ),
+                create_change_seat_status_instruction(
+                    &admin,
+                    &second_market.pubkey(),
+                    &maker,
+                    SeatApprovalStatus::Approved,
+                ),
+            ],
+            vec![],
+        )
         .await
         .unwrap();
 
-    let mut bids = vec![];
-
-    for i in 0..10 {
-        bids.push(CondensedOrder {
-            price_in_ticks: 994 - i,
-            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-            last_valid_slot: None,
-            last_valid_unix_timestamp_in_seconds: None,
-        });
-    }
-
-    let mut asks = vec![];
-
-    for i in 0..10 {
-        asks.push(CondensedOrder {
-            price_in_ticks: 995 + i,
-            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-            last_valid_slot: None,
-            last_valid_unix_timestamp_in_seconds: None,
-        });
-    }
+    // The base token is cheaper on the first market than on the second market
+    let num_base_lots = meta.raw_base_units_to_base_lots_rounded_down(10.0);
+    sdk.client
+        .sign_send_instructions(
+            vec![
+                create_new_order_instruction(
+                    market,
+                    &maker,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(
+                        Side::Ask,
+                        meta.float_price_to_ticks_rounded_down(99.0),
+                        num_base_lots,
+                    ),
+                ),
+                create_new_order_instruction(
+                    &second_market.pubkey(),
+                    &maker,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &OrderPacket::new_limit_order_default(
+                        Side::Bid,
+                        meta.float_price_to_ticks_rounded_down(101.0),
+                        num_base_lots,
+                    ),
+                ),
+            ],
+            vec![&default_maker.user],
+        )
+        .await
+        .unwrap();
 
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
+    // Buy 5 base units on the first market and sell all of them on the second market
+    let first_market = (*market, meta.base_mint, meta.quote_mint);
+    let second_market = (second_market.pubkey(), meta.base_mint, meta.quote_mint);
+    let expected_output_atoms =
+        meta.quote_units_to_quote_lots(5.0 * 101.0) * meta.quote_atoms_per_quote_lot;
+    let swap_route = |min_output_atoms: u64| {
+        create_swap_route_instruction(
+            &taker,
+            &first_market,
+            &second_market,
+            &SwapRouteParams {
+                first_order_packet: OrderPacket::new_ioc_buy_with_slippage(
+                    meta.quote_units_to_quote_lots(5.0 * 99.0),
+                    0,
+                ),
+                second_order_packet: OrderPacket::new_ioc_sell_with_slippage(0, 0),
+                min_output_atoms,
+            },
+        )
     };
+    let base_start = get_token_balance(&sdk.client, default_taker.base_ata).await;
+    let quote_start = get_token_balance(&sdk.client, default_taker.quote_ata).await;
+    let first_sequence_number = get_sequence_number(&sdk.client, market).await;
+    let second_sequence_number = get_sequence_number(&sdk.client, &second_market.0).await;
 
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
-    );
-
+    // The route reverts if it returns less than the minimum output
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .sign_send_instructions(
+            vec![swap_route(expected_output_atoms + 1)],
+            vec![&default_taker.user]
+        )
         .await
         .is_err());
-
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndFailOnCross,
-    };
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.quote_ata).await,
+        quote_start
     );
 
-    assert!(
-        sdk.client
-            .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
-            .await
-            .is_err(),
-        "Order should fail on cross"
+    let return_data = get_phoenix_return_data(
+        program_test_ctx,
+        &[swap_route(expected_output_atoms)],
+        &default_taker.user,
+    )
+    .await;
+    let swap_route_summary = SwapRouteSummary::try_from_slice(&return_data).unwrap();
+    assert_eq!(
+        swap_route_summary.first_swap.quote_lots_in,
+        meta.quote_units_to_quote_lots(5.0 * 99.0)
+    );
+    assert_eq!(
+        swap_route_summary.first_swap.base_lots_out,
+        num_base_lots / 2
+    );
+    assert_eq!(
+        swap_route_summary.second_swap.base_lots_in,
+        num_base_lots / 2
+    );
+    assert_eq!(
+        swap_route_summary.second_swap.quote_lots_out,
+        meta.quote_units_to_quote_lots(5.0 * 101.0)
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.base_ata).await,
+        base_start
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, default_taker.quote_ata).await,
+        quote_start + meta.quote_units_to_quote_lots(5.0 * 2.0) * meta.quote_atoms_per_quote_lot
+    );
+    assert_eq!(
+        get_sequence_number(&sdk.client, market).await,
+        first_sequence_number + 1
+    );
+    assert_eq!(
+        get_sequence_number(&sdk.client, &second_market.0).await,
+        second_sequence_number + 1
     );
+
+    // Both swaps must be on different markets
+    assert!(sdk
+        .client
+        .sign_send_instructions(
+            vec![create_swap_route_instruction(
+                &taker,
+                &first_market,
+                &first_market,
+                &SwapRouteParams {
+                    first_order_packet: OrderPacket::new_ioc_buy_with_slippage(
+                        meta.quote_units_to_quote_lots(99.0),
+                        0,
+                    ),
+                    second_order_packet: OrderPacket::new_ioc_sell_with_slippage(0, 0),
+                    min_output_atoms: 0,
+                },
+            )],
+            vec![&default_taker.user],
+        )
+        .await
+        .is_err());
 }
 
-/// This tests that placing multiple orders will still succeed if one of the orders crosses the bid-ask spread
 #[tokio::test]
-async fn test_phoenix_multiple_orders_crossing_existing_book_amend_ask() {
-    let (mut client, phoenix_ctx) = bootstrap_default(0).await;
-
-    let maker = get_new_maker(&client, &phoenix_ctx, 101, 1010).await;
+async fn test_phoenix_sweep_dust() {
+    let (mut client, ctx) = bootstrap_default(0).await;
+    let PhoenixTestContext { default_maker, .. } = &ctx;
     let PhoenixTestClient {
         sdk, market, meta, ..
     } = &mut client;
+    let admin = sdk.client.payer.pubkey();
+    let trader = default_maker.user.pubkey();
+    let quote_vault = get_vault_address(market, &meta.quote_mint).0;
+    let fee_ata = get_associated_token_address(&admin, &meta.quote_mint);
 
-    let quote_mint = &meta.quote_mint;
-    let base_mint = &meta.base_mint;
-
-    sdk.set_payer(clone_keypair(&maker.user));
-
-    // Create limit orders at 9.96 and 10.01
-    let bid_order_packet = OrderPacket::PostOnly {
-        side: Side::Bid,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(9.96)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        client_order_id: 0,
-        reject_post_only: true,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
-    };
-    let bid_ix = create_new_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        &base_mint,
-        &quote_mint,
-        &bid_order_packet,
-    );
-
-    let ask_order_packet = OrderPacket::PostOnly {
-        side: Side::Ask,
-        price_in_ticks: Ticks::new(meta.float_price_to_ticks_rounded_down(10.01)),
-        num_base_lots: BaseLots::new(meta.raw_base_units_to_base_lots_rounded_down(1_f64)),
-        client_order_id: 0,
-        reject_post_only: true,
-        use_only_deposited_funds: false,
-        last_valid_slot: None,
-        last_valid_unix_timestamp_in_seconds: None,
-        fail_silently_on_insufficient_funds: false,
-    };
-    let ask_ix = create_new_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        &base_mint,
-        &quote_mint,
-        &ask_order_packet,
-    );
-
+    // Tokens transferred directly into the vault are not owed to anyone
     sdk.client
-        .sign_send_instructions(vec![bid_ix, ask_ix], vec![&maker.user])
+        .sign_send_instructions(
+            vec![
+                create_deposit_funds_instruction(
+                    market,
+                    &trader,
+                    &meta.base_mint,
+                    &meta.quote_mint,
+                    &DepositParams {
+                        quote_lots_to_deposit: meta.quote_units_to_quote_lots(100.0),
+                        base_lots_to_deposit: 0,
+                    },
+                ),
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &default_maker.quote_ata,
+                    &quote_vault,
+                    &trader,
+                    &[],
+                    12345,
+                )
+                .unwrap(),
+            ],
+            vec![&default_maker.user],
+        )
         .await
         .unwrap();
+    let vault_start = get_token_balance(&sdk.client, quote_vault).await;
+    let fee_start = get_token_balance(&sdk.client, fee_ata).await;
 
-    let mut bids = vec![];
-
-    for i in 0..10 {
-        bids.push(CondensedOrder {
-            price_in_ticks: 994 - i,
-            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-            last_valid_slot: None,
-            last_valid_unix_timestamp_in_seconds: None,
-        });
-    }
-
-    println!("bids: {:?}", bids);
-
-    let mut asks = vec![];
-
-    for i in 0..10 {
-        asks.push(CondensedOrder {
-            price_in_ticks: 995 + i,
-            size_in_base_lots: meta.raw_base_units_to_base_lots_rounded_down(10_f64),
-            last_valid_slot: None,
-            last_valid_unix_timestamp_in_seconds: None,
-        });
-    }
-
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::FailOnInsufficientFundsAndFailOnCross,
-    };
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
-    );
-
+    // Only the market authority can sweep dust
     assert!(sdk
         .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+        .sign_send_instructions(
+            vec![create_sweep_dust_instruction(
+                &trader,
+                market,
+                &admin,
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![&default_maker.user],
+        )
         .await
         .is_err());
 
-    let order_packet = MultipleOrderPacket {
-        asks: asks.clone(),
-        bids: bids.clone(),
-        client_order_id: None,
-        failed_multiple_limit_order_behavior:
-            FailedMultipleLimitOrderBehavior::SkipOnInsufficientFundsAndAmendOnCross,
-    };
-
-    let new_order_ix = create_new_multiple_order_instruction(
-        market,
-        &maker.user.pubkey(),
-        base_mint,
-        quote_mint,
-        &order_packet,
+    sdk.client
+        .sign_send_instructions(
+            vec![create_sweep_dust_instruction(
+                &admin,
+                market,
+                &admin,
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&sdk.client, fee_ata).await,
+        fee_start + 12345
+    );
+    assert_eq!(
+        get_token_balance(&sdk.client, quote_vault).await,
+        vault_start - 12345
     );
 
-    assert!(sdk
-        .client
-        .sign_send_instructions(vec![new_order_ix], vec![&maker.user])
+    // Sweeping again is a no-op
+    sdk.client
+        .sign_send_instructions(
+            vec![create_sweep_dust_instruction(
+                &admin,
+                market,
+                &admin,
+                &meta.quote_mint,
+                &spl_token::id(),
+            )],
+            vec![],
+        )
         .await
-        .is_ok());
-
-    let market = sdk.get_market_orderbook(market).await.unwrap();
-    let market_bids = market
-        .bids
-        .iter()
-        .map(|(o, _)| o.price_in_ticks.into())
-        .collect::<Vec<_>>();
-    for bid in bids {
-        assert!(market_bids.contains(&bid.price_in_ticks));
-    }
-
-    assert_eq!(market_bids.len(), 11);
-
-    let market_asks = market
-        .asks
-        .iter()
-        .map(|(o, _)| o.price_in_ticks.into())
-        .collect::<Vec<_>>();
-
-    for ask in asks {
-        if ask.price_in_ticks > 996 {
-            assert!(market_asks.contains(&ask.price_in_ticks));
-        }
-    }
-
-    assert!(market_asks.iter().filter(|&x| *x == 997).count() == 3);
-    assert_eq!(market_asks.len(), 11);
-
-    assert_eq!(market_asks[0], 997);
-    assert_eq!(market_bids[0], 996);
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&sdk.client, fee_ata).await,
+        fee_start + 12345
+    );
 }